@@ -65,7 +65,7 @@ fn produce<T, R: Read>(
     // BandLimLowTreb
     // BandLimLowBass
     // BandLimNarrow
-    let mut bandlim = BlepAmpFilter::new(SDT::from_sample(0.777),
+    let mut bandlim = BlepAmpFilter::new(SDT::from_sample(STEREO_AMP_FILTER),
                             BlepStereo::new(SDT::from_sample(0.5),
                                 BandLimited::<SDT, FT>::new(2)));
     let mut cpu = Z80NMOS::default();
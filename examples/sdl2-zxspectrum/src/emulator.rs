@@ -23,7 +23,8 @@ use sdl2::{mouse::MouseButton, keyboard::{Keycode, Mod as Modifier}};
 use spectrusty::audio::{
     BlepAmpFilter, BlepStereo, AudioSample, AudioFrame,
     synth::{BandLimited, ext::BandLimitedExt},
-    carousel::AudioFrameResult
+    carousel::AudioFrameResult,
+    filter::AudioFilter
 };
 use spectrusty::z80emu::{Z80Any, Cpu};
 use spectrusty::audio::{UlaAudioFrame, host::sdl2::AudioHandle};
@@ -53,6 +54,7 @@ use spectrusty::video::{
 use zxspectrum_common::{
     JoystickAccess, DeviceAccess,
     ModelRequest,
+    FAST_FORWARD_SPEEDS,
     MouseAccess,
     UlaPlusMode
 };
@@ -108,6 +110,8 @@ pub struct ZxSpectrumEmu<'a, C: Cpu, U> {
     #[serde(skip)]
     bandlim: BandLim,
     #[serde(skip)]
+    audio_filter: AudioFilter,
+    #[serde(skip)]
     pub mouse_rel: (i32, i32),
     #[serde(skip)]
     info_text: String,
@@ -146,7 +150,8 @@ impl<'a, C: Cpu, U> ZxSpectrumEmu<'a, C, U> {
         // let writer = Some(hound::WavWriter::create("spectrum.wav", spec).unwrap());
         // let audio = Audio::create(sdl_context, U::frame_duration_nanos(), latency)?;
         let mut bandlim = BlepAmpFilter::build(0.25)(BlepStereo::build(0.86)(BandLimited::new(audio.channels.into())));
-        spectrum.ula.ensure_audio_frame_time(&mut bandlim, audio.sample_rate, U::effective_cpu_rate(1.0));
+        spectrum.ensure_audio_frame_time(&mut bandlim, audio.sample_rate);
+        let audio_filter = model.audio_filter(audio.sample_rate, audio.channels.into());
         let time_sync = ThreadSyncTimer::new(U::frame_duration_nanos());
         Ok(ZxSpectrumEmu {
             model,
@@ -154,6 +159,7 @@ impl<'a, C: Cpu, U> ZxSpectrumEmu<'a, C, U> {
             audio,
             time_sync,
             bandlim,
+            audio_filter,
             mouse_rel: (0, 0),
             info_text: String::new(),
             info_range: 0..0
@@ -185,16 +191,34 @@ impl<'a, C: Cpu, U> ZxSpectrumEmu<'a, C, U> {
         where U: UlaCommon  + UlaAudioFrame<BandLim>
     {
         let frame_sample_count = self.spectrum.render_audio(&mut self.bandlim);
-        let Self { audio, bandlim, .. } = self;
-        let output_channels = audio.channels.into();
-        audio.producer.render_frame(|ref mut vec| {
-            vec.resize(frame_sample_count * output_channels, Sample::silence());
-            bandlim.render_audio_map_interleaved(&mut vec[..], output_channels, &[0, 1]);
-        });
-        // prepare BLEP for the next frame
-        bandlim.next_frame();
-        // send sample buffer to the consumer
-        audio.producer.send_frame()
+        produce_audio_frame(self.audio, &mut self.bandlim, &mut self.audio_filter, frame_sample_count)
+    }
+
+    /// Runs [EmulatorState::fast_forward_speed] frames, producing the sped-up audio of each frame.
+    pub fn run_frames_fast_forward(&mut self) -> Result<bool>
+        where U: UlaCommon  + UlaAudioFrame<BandLim>
+    {
+        let Self { spectrum, audio, bandlim, audio_filter, .. } = self;
+        let (_, state_changed) = spectrum.run_frames_fast_forward(bandlim, |bandlim, frame_sample_count| {
+            produce_audio_frame(audio, bandlim, audio_filter, frame_sample_count)?;
+            Ok(())
+        })?;
+        Ok(state_changed)
+    }
+
+    /// Cycles the fast-forward speed multiple through [FAST_FORWARD_SPEEDS] and then disables
+    /// the fast-forward mode.
+    pub fn cycle_fast_forward(&mut self)
+        where U: HostConfig + AudioFrame<BandLim>
+    {
+        let state = &mut self.spectrum.state;
+        let speed = match state.fast_forward {
+            None => Some(*FAST_FORWARD_SPEEDS.start()),
+            Some(speed) if speed < *FAST_FORWARD_SPEEDS.end() => Some(speed + 1),
+            Some(_) => None
+        };
+        state.set_fast_forward(speed);
+        self.spectrum.ensure_audio_frame_time(&mut self.bandlim, self.audio.sample_rate);
     }
 
     pub fn move_mouse(&mut self, dx: i32, dy: i32) {
@@ -337,7 +361,7 @@ impl<'a, C: Cpu, U> ZxSpectrumEmu<'a, C, U> {
                 tape.stop();
                 let old_tape = tape.insert_as_reader(file);
                 if let Err(e) = self.tape_info() {
-                    self.spectrum.state.tape.tap = old_tape;
+                    self.spectrum.state.tape.set_tap(old_tape);
                     return Err(e)
                 }
                 self.spectrum.state.tape.rewind_nth_chunk(1)?;
@@ -438,6 +462,9 @@ impl<'a, C: Cpu, U> ZxSpectrumEmu<'a, C, U> {
         else if self.spectrum.state.turbo {
             info.push_str(" 🏎️");
         }
+        else if let Some(speed) = self.spectrum.state.fast_forward {
+            write!(info, " ⏩{}x", speed)?;
+        }
         if let Some(name) = self.spectrum.current_joystick() {
             if name == "Sinclair" {
                 write!(info, " 🕹{}{}", &name[0..1], self.spectrum.state.sub_joy + 1)?;
@@ -455,7 +482,7 @@ impl<'a, C: Cpu, U> ZxSpectrumEmu<'a, C, U> {
 
         let running = self.spectrum.state.tape.running;
         // is there any TAPE inserted at all?
-        if let Some(tap) = self.spectrum.state.tape.tap.as_mut() {
+        if let Some(tap) = self.spectrum.state.tape.tap_mut() {
             let flash = if self.spectrum.state.instant_tape { "⚡⚡" }
                         else if self.spectrum.state.flash_tape { "⚡" }
                         else { " " };
@@ -571,6 +598,25 @@ fn dynamic_info<C, U, W: fmt::Write>(spec: &ZxSpectrum<C, U>, info: &mut W) -> R
     Ok(())
 }
 
+fn produce_audio_frame(
+        audio: &mut Audio,
+        bandlim: &mut BandLim,
+        audio_filter: &mut AudioFilter,
+        frame_sample_count: usize
+    ) -> AudioFrameResult<()>
+{
+    let output_channels = audio.channels.into();
+    audio.producer.render_frame(|ref mut vec| {
+        vec.resize(frame_sample_count * output_channels, Sample::silence());
+        bandlim.render_audio_map_interleaved(&mut vec[..], output_channels, &[0, 1]);
+        audio_filter.process_interleaved(&mut vec[..]);
+    });
+    // prepare BLEP for the next frame
+    bandlim.next_frame();
+    // send sample buffer to the consumer
+    audio.producer.send_frame()
+}
+
 #[cfg(test)]
 mod tests {
     use core::mem::size_of;
@@ -604,4 +650,5 @@ mod tests {
                                         NonBlockingStdinReader,
                                         EpsonGfxFilteredStdoutWriter>>());
     }
-}
\ No newline at end of file
+}
+
@@ -48,7 +48,8 @@ use sdl2::{Sdl,
 use spectrusty::z80emu::{Z80Any, Cpu};
 use spectrusty::audio::UlaAudioFrame;
 use spectrusty::clock::TimestampOps;
-use spectrusty::chip::{MemoryAccess, UlaCommon};
+use spectrusty::chip::{HostConfig, MemoryAccess, UlaCommon};
+use spectrusty::memory::PowerOnMemory;
 
 use spectrusty::peripherals::memory::ZxInterface1MemExt;
 use spectrusty::bus::{
@@ -110,6 +111,7 @@ Drag & drop TAP, SNA or SCR files over the emulator window in order to load them
 Esc: Release grabbed pointer.
 F1: Shows this help.
 F2: Turbo - toggle run as fast as possible.
+Shift+F2: Fast-forward - cycles through 2x, 3x, 4x speed with the sped-up audio and off.
 F3: Saves printer spooler content.
 F4: Changes joystick implementation (cursor keys).
 F5: Plays current TAP file.
@@ -156,11 +158,13 @@ fn main() -> Result<()> {
         (@arg joystick: -j --joystick +takes_value "Selects joystick at startup")
         (@arg printer: -p --printer "Inserts ZX Printer device")
         (@arg interface1: -i --interface1 +takes_value "Installs ZX Interface 1 (ROM path required)")
+        (@arg instant_md: --("instant-md") "Services the ZX Interface 1 microdrive sector hook codes instantly")
         (@arg zxnet_bind: --bind +takes_value "Bind address of ZX-NET UDP socket")
         (@arg zxnet_conn: --conn +takes_value "Connect address of ZX-NET UDP socket")
         (@arg melodik: --ay "Inserts Melodik AY-3-8910 device")
         (@arg fuller: --fuller "Inserts Fuller AY-3-8910 device")
         (@arg mouse: --mouse "Inserts Kempston Mouse device")
+        (@arg seed: --seed +takes_value "Seed of the pseudo-random power-on memory content")
         (@arg FILES: ... "Sets the file(s) to load at startup")
     ).get_matches();
 
@@ -217,6 +221,7 @@ fn main() -> Result<()> {
 
         let mut request_if1 = None;
         let mut if1_rom_paged_in = false;
+        let mut request_audio_flush = false;
 
         if let Some((filepath, kind)) = maybe_snapshot.take() {
             let mut load_snapshot = || -> Result<_> {
@@ -251,6 +256,7 @@ fn main() -> Result<()> {
                 }
                 Err(err) => return Err(err)
             };
+            request_audio_flush = true;
             mreq = ModelRequest::from(&model);
         }
         else {
@@ -267,7 +273,10 @@ fn main() -> Result<()> {
                     }
                 };
             }
-            model = ZxSpectrumModel::new(mreq);
+            let power_on_memory = matches.value_of("seed")
+                                         .map(u64::from_str).transpose()?
+                                         .map(PowerOnMemory::Random);
+            model = ZxSpectrumModel::new_power_on(mreq, power_on_memory);
         }
 
         info!("{}: cpu_hz: {} T-states/frame: {} pixel density: {}",
@@ -298,6 +307,9 @@ fn main() -> Result<()> {
                 state.sub_joy = spec.state.sub_joy; // overwrite current joystick sub-index
                 spec.set_state(state);
             }
+            if request_audio_flush {
+                spec.state.request_audio_flush();
+            }
             let mut zx = ZxSpectrumEmu::new_with(mreq, spec, audio)?;
             let show_copyright = preload_files(&mut zx, files.take(), allow_autoload)?;
             // ensure emulator window is open and has the correct size and title
@@ -390,6 +402,10 @@ fn configure_model<U: 'static>(
                 spec.microdrives_mut().unwrap().replace_cartridge(n,
                         MicroCartridge::new_formatted(180, format!("blank {}", n + 1)));
             }
+            if matches.is_present("instant_md") {
+                info!("Instant microdrive enabled");
+                spec.zxinterface1_mut().unwrap().set_instant_microdrive(true);
+            }
             if if1_rom_paged_in {
                 let (mem, ext) = spec.ula.memory_with_ext_mut();
                 ext.map_exrom(mem)?;
@@ -400,6 +416,10 @@ fn configure_model<U: 'static>(
         eprintln!(r#"--bind and --conn options require --interface1"#);
         return Ok(())
     }
+    else if matches.is_present("instant_md") {
+        eprintln!(r#"--instant-md option requires --interface1"#);
+        return Ok(())
+    }
     else if request_if1 == Some(true) {
         eprintln!(r#"Snapshot requires ZX Interface 1 but no IF 1 ROM path was provided."#);
         return Ok(())
@@ -486,6 +506,7 @@ fn run<'a, C, U: 'static>(
     ) -> Result<Option<(String, SnapshotKind)>>
     where C: Cpu + fmt::Display,
           U: UlaCommon
+           + HostConfig
            + UlaAudioFrame<BandLim>
            + SpoolerAccess
            + ScreenDataProvider
@@ -613,6 +634,12 @@ fn run<'a, C, U: 'static>(
                     };
                     update_info = true;
                 }
+                Event::KeyDown { keycode: Some(Keycode::F2), keymod, repeat: false, ..}
+                    if !zx.spectrum.state.paused &&
+                       keymod.intersects(Modifier::LSHIFTMOD|Modifier::RSHIFTMOD) => {
+                        zx.cycle_fast_forward();
+                        update_info = true;
+                    }
                 Event::KeyDown { keycode: Some(Keycode::F2), repeat: false, ..}
                     if !zx.spectrum.state.paused => {
                         if zx.spectrum.state.turbo {
@@ -810,9 +837,15 @@ fn run<'a, C, U: 'static>(
             let viewport = emu_canvas.window().drawable_size();
             zx.send_mouse_move(viewport);
 
+            // the turbo mode takes precedence over the fast-forward mode
             let state_changed = if zx.spectrum.state.turbo {
                 zx.spectrum.run_frames_accelerated(&mut zx.time_sync)?.1
             }
+            else if zx.spectrum.state.fast_forward.is_some() {
+                let sc = zx.run_frames_fast_forward()?;
+                zx.synchronize_thread_to_frame();
+                sc
+            }
             else {
                 let sc = zx.spectrum.run_frame()?.1;
                 zx.render_audio()?;
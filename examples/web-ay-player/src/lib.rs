@@ -318,6 +318,11 @@ impl AyPlayerHandle {
         self.player.borrow_mut().player.set_channels_mode(chan_mode);
         Ok(())
     }
+    /// Fast-forwards the played song to the given frame. Returns the number of frames skipped.
+    #[wasm_bindgen]
+    pub fn seek(&self, frames: f64) -> f64 {
+        self.player.borrow_mut().player.seek(frames as u64) as f64
+    }
     /// Sets the gain for this audio source, between 0.0 and 1.0.
     #[wasm_bindgen(js_name = setGain)]
     pub fn set_gain(&self, gain: f32) {
@@ -17,7 +17,10 @@ use spectrusty::formats::{
     ay::*,
     sna::*
 };
-use spectrusty::chip::{ControlUnit, ay_player::AyPlayer};
+use spectrusty::chip::{
+    ControlUnit, FrameState,
+    ay_player::{AyPlayer, MONO_AMP_FILTER, STEREO_AMP_FILTER}
+};
 pub use spectrusty::audio::synth::{BandLimWide, BandLimLowTreb, BandLimLowBass, BandLimNarrow};
 pub use spectrusty::audio::AmpLevels;
 pub use spectrusty::peripherals::ay::audio::{AyAmps, AyFuseAmps};
@@ -96,12 +99,11 @@ pub struct AyFilePlayer<F=BandLimWide> {
     bandlim: BlepAmpFilter<BlepStereo<BandLimited<f32, F>>>,
     sample_rate: u32,
     ay_file: Option<PinAyFile>,
+    song_index: usize,
+    amp_filter: f32,
     channels: [usize; 3]
 }
 
-const MONO_AMP_FILTER: f32 = 2.0/3.0;
-const STEREO_AMP_FILTER: f32 = 0.777;
-
 impl<F: BandLimOpt> AyFilePlayer<F> {
     pub fn new(sample_rate: u32) -> Self {
         let mut bandlim = BlepAmpFilter::new(STEREO_AMP_FILTER,
@@ -116,7 +118,9 @@ impl<F: BandLimOpt> AyFilePlayer<F> {
         player.reset_frames();
         AyFilePlayer {
             cpu, player, bandlim, channels, sample_rate,
-            ay_file: None
+            ay_file: None,
+            song_index: 0,
+            amp_filter: STEREO_AMP_FILTER
         }
     }
     // sets cpu clocking
@@ -152,6 +156,7 @@ impl<F: BandLimOpt> AyFilePlayer<F> {
         };
         ay_file.initialize_player(&mut self.cpu,
                                 &mut self.player.memory, 0);
+        self.song_index = 0;
         let res = json!({
             "type": ".ay",
             "author": ay_file.meta.author.to_str_lossy(),
@@ -170,27 +175,42 @@ impl<F: BandLimOpt> AyFilePlayer<F> {
         if let Some(ay_file) = self.ay_file.as_ref() {
             if let Some(song) = ay_file.songs.get(song_index) {
                 ay_file.initialize_player(&mut self.cpu, &mut self.player.memory, song_index);
+                self.player.reset_frames();
+                self.song_index = song_index;
                 return Some(json!({
                     "name": song.name.to_str_lossy(),
                     "duration": song.song_duration,
                     "fade_duration": song.fade_duration,
+                    "total_frames": ay_file.song_duration(song_index),
                 }))
             }
         }
         None
     }
+    /// Fast-forwards the current song to the given `frames` target. Returns the number of frames skipped.
+    pub fn seek(&mut self, frames: u64) -> u64 {
+        self.player.seek(&mut self.cpu, frames)
+    }
     /// Sets channel mode and adjusts amplifier filter.
     pub fn set_channels_mode(&mut self, config: AyChannelsMode) {
         if config.is_mono() {
-            self.bandlim.filter = MONO_AMP_FILTER;
+            self.amp_filter = MONO_AMP_FILTER;
         }
         else {
-            self.bandlim.filter = STEREO_AMP_FILTER;
+            self.amp_filter = STEREO_AMP_FILTER;
         }
+        self.bandlim.filter = self.amp_filter;
         self.channels = config.into();
     }
-    /// Runs a single frame, returns a number of samples to be rendered
+    /// Runs a single frame, returns a number of samples to be rendered.
+    ///
+    /// Fades out the song after its duration and renders silence when the fade ends.
     pub fn run_frame<V: AmpLevels<f32>>(&mut self) -> usize {
+        if let Some(ay_file) = self.ay_file.as_ref() {
+            let frame = self.player.frame_tstate().0;
+            let amplitude = ay_file.song_amplitude(self.song_index, frame).unwrap_or(0.0);
+            self.bandlim.filter = self.amp_filter * amplitude;
+        }
         self.player.execute_next_frame(&mut self.cpu);
         self.player.render_ay_audio_frame::<V>(&mut self.bandlim, self.channels);
         self.player.render_earmic_out_audio_frame::<EarOutAmps4<f32>>(&mut self.bandlim, 2);
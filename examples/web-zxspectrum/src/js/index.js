@@ -106,6 +106,9 @@ import("../../pkg").then(rust_module => {
   .bind("turbo",
     (ev) => spectrum.turbo = ev.target.checked,
     (el) => el.checked = spectrum.turbo
+  ).bind("fast-forward",
+    (ev) => spectrum.fastForward = parseInt(ev.target.value, 10),
+    (el) => el.value = spectrum.fastForward || 0
   ).bind("sound-gain", "input",
     (ev) => spectrum.gain = ev.target.value,
     (el) => el.value = spectrum.gain
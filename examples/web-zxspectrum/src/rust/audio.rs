@@ -15,11 +15,12 @@ use web_sys::{
 };
 use spectrusty::audio::{
     BlepAmpFilter, BlepStereo,
+    filter::AudioFilter,
     synth::BandLimited
 };
 use crate::utils::Result;
 
-const AUDIO_CHANNELS: u32 = 2;
+pub const AUDIO_CHANNELS: u32 = 2;
 /// The maximum number of audio buffers in use.
 const QUEUE_MAX_LEN: usize = 8;
 /// The minimum sound start delay.
@@ -46,6 +47,8 @@ pub fn create_blep() -> BandLim {
 pub struct AudioStream {
     /// Intermediary audio buffers.
     buffers: [Vec<f32>;AUDIO_CHANNELS as usize],
+    /// The filter of the rendered audio.
+    filter: Option<AudioFilter>,
     /// Web AudioBuffer queue.
     audio_queue: AudioQueue,
     /// Cached AudioContext's sample rate.
@@ -100,11 +103,16 @@ impl AudioStream {
             audio_queue,
             ctx,
             buffers: Default::default(),
+            filter: None,
             sample_rate,
             gain,
             next_at: 0.0
         })
     }
+    /// Sets the filter of the rendered audio.
+    pub fn set_filter(&mut self, filter: Option<AudioFilter>) {
+        self.filter = filter;
+    }
     /// Returns the sample rate of the audio playback.
     pub fn sample_rate(&self) -> u32 {
         self.sample_rate as u32
@@ -163,6 +171,9 @@ impl AudioStream {
             nsamples = sample_iter.len();
             target.clear();
             target.extend(sample_iter);
+            if let Some(filter) = self.filter.as_mut() {
+                filter.process_channel(target, channel);
+            }
         }
         bandlim.next_frame();
         nsamples
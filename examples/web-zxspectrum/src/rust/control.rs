@@ -4,10 +4,11 @@
 
     For the full copyright notice, see the lib.rs file.
 */
+use core::fmt::Write;
 use core::ops::Range;
-use std::io::{self, Write};
+use std::io;
 
-use spectrusty::z80emu::{Cpu, Z80NMOS, disasm};
+use spectrusty::z80emu::Cpu;
 use spectrusty::audio::{Blep, UlaAudioFrame};
 use spectrusty::clock::FTs;
 use spectrusty::chip::{
@@ -22,6 +23,7 @@ use spectrusty::peripherals::{
     mouse::MouseButtons
 };
 use spectrusty_utils::{
+    disasm::disassemble_range,
     keyboard::web_sys::{
         update_keymap, update_keypad_keys,
         update_joystick_from_key_event
@@ -48,6 +50,7 @@ pub trait SpectrumControl<B: Blep>: VideoControl +
 {
     fn run_frames_accelerated(&mut self, time_sync: &mut AnimationFrameSyncTimer) -> Result<(FTs, bool)>;
     fn run_frame(&mut self) -> Result<(FTs, bool)>;
+    fn run_frames_fast_forward(&mut self, blep: &mut B, produce: &mut dyn FnMut(&mut B, usize) -> Result<()>) -> Result<(FTs, bool)>;
     fn render_audio(&mut self, blep: &mut B) -> usize;
     fn reset(&mut self, hard: bool);
     fn trigger_nmi(&mut self);
@@ -83,6 +86,10 @@ impl<C: Cpu, U, B> SpectrumControl<B> for ZxSpectrum<C, U, MemTap>
         self.run_frame()
     }
 
+    fn run_frames_fast_forward(&mut self, blep: &mut B, produce: &mut dyn FnMut(&mut B, usize) -> Result<()>) -> Result<(FTs, bool)> {
+        self.run_frames_fast_forward(blep, produce)
+    }
+
     fn render_audio(&mut self, blep: &mut B) -> usize {
         self.render_audio(blep)
     }
@@ -177,18 +184,14 @@ impl<C: Cpu, U, B> SpectrumControl<B> for ZxSpectrum<C, U, MemTap>
     }
 
     fn dump_memory(&self, range: Range<u16>) -> io::Result<Vec<u8>> {
-        let mut mem = Vec::with_capacity(range.len());
-        for page in self.ula.memory_ref().iter_pages(range)? {
-            mem.write_all(page)?;
-        }
-        Ok(mem)
+        Ok(self.ula.memory_ref().dump_range(range)?)
     }
 
     fn disassemble_memory(&self, range: Range<u16>) -> io::Result<String> {
-        let pc = range.start;
-        let temp = self.dump_memory(range)?;
         let mut output = String::new();
-        disasm::disasm_memory_write_text::<Z80NMOS, _>(pc, &temp, &mut output).unwrap();
+        for (address, text) in disassemble_range(self.ula.memory_ref(), range) {
+            writeln!(output, "{:04x}h {}", address, text).unwrap();
+        }
         Ok(output)
     }
 }
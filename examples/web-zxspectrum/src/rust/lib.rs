@@ -37,7 +37,7 @@ use web_sys::{
 use spectrusty::z80emu::Z80NMOS;
 
 use spectrusty::chip::{AnimationFrameSyncTimer, ReadEarMode};
-use spectrusty::memory::NoMemoryExtension;
+use spectrusty::memory::{NoMemoryExtension, PowerOnMemory};
 use spectrusty::formats::{
     snapshot::SnapshotResult,
     z80::{load_z80, save_z80v1, save_z80v2, save_z80v3},
@@ -52,7 +52,7 @@ use zxspectrum_common::{
     spectrum_model_dispatch
 };
 
-use audio::{BandLim, AudioStream, create_blep};
+use audio::{BandLim, AudioStream, AUDIO_CHANNELS, create_blep};
 use control::{SpectrumControl};
 use utils::{Result, JsErr};
 use self::serde::{SerdeDynDevice, DeviceType, recreate_model_dynamic_devices};
@@ -103,10 +103,12 @@ impl ZxSpectrumEmu {
     #[wasm_bindgen(constructor)]
     pub fn new(audio_buffer_max_duration: f32, model: &str) -> Result<ZxSpectrumEmu> {
         let mut bandlim = create_blep();
-        let audio_stream = AudioStream::new(audio_buffer_max_duration)?;
+        let mut audio_stream = AudioStream::new(audio_buffer_max_duration)?;
         let model_request = ModelRequest::from_str(model).unwrap_or(ModelRequest::SpectrumPlus2B);
         let model = ZxSpectrumModel::new(model_request);
         model.ensure_audio_frame_time(&mut bandlim, audio_stream.sample_rate());
+        audio_stream.set_filter(Some(model_request.audio_filter(audio_stream.sample_rate(),
+                                                                AUDIO_CHANNELS as usize)));
         let animation_sync = AnimationFrameSyncTimer::new(utils::now(), model.effective_frame_duration_nanos());
         Ok(ZxSpectrumEmu {
             audio_stream,
@@ -165,13 +167,24 @@ impl ZxSpectrumEmu {
                 return Ok(None)
             }
             else {
+                let fast_forward = model.emulator_state_ref().fast_forward.is_some();
                 for _ in 0..num_frames {
-                    let(_, stchg) = model.run_frame().js_err()?;
+                    let stchg = if fast_forward {
+                        let audio_stream = &mut self.audio_stream;
+                        model.run_frames_fast_forward(&mut self.bandlim, &mut |bandlim, _|
+                            audio_stream.play_next_audio_frame(bandlim)
+                                        .map_err(|e| format!("{:?}", e).into())
+                        ).js_err()?.1
+                    }
+                    else {
+                        let(_, stchg) = model.run_frame().js_err()?;
+                        model.render_audio(&mut self.bandlim);
+                        self.audio_stream.play_next_audio_frame(&mut self.bandlim)?;
+                        stchg
+                    };
                     if stchg {
                         state_changed = true;
                     }
-                    model.render_audio(&mut self.bandlim);
-                    self.audio_stream.play_next_audio_frame(&mut self.bandlim)?;
                 }
             }
         }
@@ -272,13 +285,11 @@ impl ZxSpectrumEmu {
     /// Emulates the power off/on cycle of the emulated Spectrum model.
     ///
     /// This method re-initializes peripheral devices state and randomizes memory content.
+    /// See [ZxSpectrumEmu::set_power_on_seed] for a deterministic memory content.
     #[wasm_bindgen(js_name = powerCycle)]
     pub fn power_cycle(&mut self) -> Result<()> {
-        let mut model = ZxSpectrumModel::new((&self.model).into());
-        core::mem::swap(&mut self.model, &mut model);
-        recreate_model_dynamic_devices(&model, &mut self.model)?;
-        let (_, state) = model.into_cpu_and_state();
-        self.model.set_emulator_state(state);
+        let prev_model = self.model.power_cycle();
+        recreate_model_dynamic_devices(&prev_model, &mut self.model)?;
         Ok(())
     }
     /// Initializes NMI trigger. The NMI will be triggered at the earliest possible moment
@@ -340,7 +351,7 @@ impl ZxSpectrumEmu {
     #[wasm_bindgen(js_name = setCpuRateFactor)]
     pub fn set_cpu_rate_factor(&mut self, rate: f32) {
         let rate = rate.max(0.2).min(5.0);
-        self.model.emulator_state_mut().clock_rate_factor = rate;
+        self.model.emulator_state_mut().set_clock_rate_factor(rate);
         self.update_on_frame_duration_changed();
     }
     /// Returns the current CPU rate factor.
@@ -348,6 +359,21 @@ impl ZxSpectrumEmu {
     pub fn cpu_rate_factor(&self) -> f32 {
         self.model.emulator_state_ref().clock_rate_factor
     }
+    /// Sets the seed of the pseudo-random RAM content being applied on the next power cycle.
+    ///
+    /// Provide `undefined` to restore the non-deterministic random content.
+    #[wasm_bindgen(setter = powerOnSeed)]
+    pub fn set_power_on_seed(&mut self, seed: Option<u32>) {
+        self.model.emulator_state_mut().power_on_memory = seed.map(|seed| PowerOnMemory::Random(seed.into()));
+    }
+    /// Returns the seed of the pseudo-random RAM content being applied on the next power cycle.
+    #[wasm_bindgen(getter = powerOnSeed)]
+    pub fn power_on_seed(&self) -> Option<u32> {
+        match self.model.emulator_state_ref().power_on_memory {
+            Some(PowerOnMemory::Random(seed)) => Some(seed as u32),
+            _ => None
+        }
+    }
     /// Sets turbo mode.
     ///
     /// When turbo mode is enabled frames are run as fast as possible and audio is not being played.
@@ -360,6 +386,20 @@ impl ZxSpectrumEmu {
     pub fn turbo(&mut self) -> bool {
         self.model.emulator_state_ref().turbo
     }
+    /// Sets the fast-forward speed multiple, `undefined` or `0` disables it.
+    ///
+    /// The speed is capped between `2` and `4`. Unlike in the turbo mode, the audio is still being
+    /// played, sped-up accordingly. The turbo mode takes precedence over the fast-forward mode.
+    #[wasm_bindgen(setter = fastForward)]
+    pub fn set_fast_forward(&mut self, speed: Option<u8>) {
+        self.model.emulator_state_mut().set_fast_forward(speed.filter(|&speed| speed != 0));
+        self.update_on_frame_duration_changed();
+    }
+    /// Returns the fast-forward speed multiple or `undefined` if the fast-forward mode is disabled.
+    #[wasm_bindgen(getter = fastForward)]
+    pub fn fast_forward(&self) -> Option<u8> {
+        self.model.emulator_state_ref().fast_forward
+    }
     /// Sets the volume percent gain for audio playback.
     ///
     /// Provided values above 100 are being capped.
@@ -436,6 +476,7 @@ impl ZxSpectrumEmu {
     #[wasm_bindgen(js_name = loadSna)]
     pub fn load_sna(&mut self, sna_data: Vec<u8>) -> Result<()> {
         load_sna(Cursor::new(sna_data), self).js_err()?;
+        self.model.emulator_state_mut().request_audio_flush();
         self.update_on_frame_duration_changed();
         Ok(())
     }
@@ -446,6 +487,7 @@ impl ZxSpectrumEmu {
     #[wasm_bindgen(js_name = loadZ80)]
     pub fn load_z80(&mut self, z80_data: Vec<u8>) -> Result<()> {
         load_z80(&z80_data[..], self).js_err()?;
+        self.model.emulator_state_mut().request_audio_flush();
         self.update_on_frame_duration_changed();
         Ok(())
     }
@@ -528,7 +570,7 @@ impl ZxSpectrumEmu {
                       .map_err(|e| JsValue::from_str(&e.to_string()))
                       .and_then(|_| self.tape_info());
         if res.is_err() {
-            self.model.emulator_state_mut().tape.tap = mb_old_tap;
+            self.model.emulator_state_mut().tape.set_tap(mb_old_tap);
         }
         res
     }
@@ -588,6 +630,19 @@ impl ZxSpectrumEmu {
     pub fn to_json(&self) -> Result<String> {
         serde_json::to_string(&self.model).js_err()
     }
+    /// Serializes the current state of the emulated Spectrum model to a JSON string, storing only
+    /// the memory banks that differ from the power-on state.
+    ///
+    /// The result can be restored with [ZxSpectrumEmu::parse_json].
+    #[wasm_bindgen(js_name = toDeltaJSON)]
+    pub fn to_delta_json(&mut self) -> Result<String> {
+        // a freshly created memory is filled with 0xFF unless the power-on pattern has been applied
+        let base = self.model.emulator_state_ref().power_on_memory.unwrap_or(PowerOnMemory::Ones);
+        self.model.set_memory_delta_base(Some(base));
+        let res = serde_json::to_string(&self.model).js_err();
+        self.model.set_memory_delta_base(None);
+        res
+    }
     /// Attempts to deserialize a Spectrum model with the serialized state from a JSON string.
     ///
     /// # Errors
@@ -596,6 +651,7 @@ impl ZxSpectrumEmu {
     pub fn parse_json(&mut self, json: &str) -> Result<()> {
         self.model = serde_json::from_str(json).js_err()?;
         self.model.rebuild_device_index();
+        self.model.emulator_state_mut().request_audio_flush();
         self.update_on_frame_duration_changed();
         Ok(())
     }
@@ -774,7 +830,10 @@ impl ZxSpectrumEmu {
     }
 
     fn update_on_frame_duration_changed(&mut self) {
-        self.model.ensure_audio_frame_time(&mut self.bandlim, self.audio_stream.sample_rate());
+        let sample_rate = self.audio_stream.sample_rate();
+        self.model.ensure_audio_frame_time(&mut self.bandlim, sample_rate);
+        let model_request = ModelRequest::from(&self.model);
+        self.audio_stream.set_filter(Some(model_request.audio_filter(sample_rate, AUDIO_CHANNELS as usize)));
         self.animation_sync.set_frame_duration(self.model.effective_frame_duration_nanos());
     }
 
@@ -5,6 +5,7 @@
     For the full copyright notice, see the lib.rs file.
 */
 use core::fmt;
+use core::mem;
 use std::collections::hash_map::Entry;
 use std::io;
 
@@ -35,8 +36,9 @@ use spectrusty::video::VideoFrame;
 use spectrusty::peripherals::serial::SerialPortDevice;
 use spectrusty_utils::io::{Empty, Sink};
 
-use super::spectrum::{SpectrumUla, ZxSpectrum};
+use super::spectrum::{EmulatorState, SpectrumUla, ZxSpectrum};
 use super::models::*;
+use super::peripherals::JoystickAccess;
 
 /// A static pluggable multi-joystick bus device.
 pub type PluggableJoystick<D> = OptionalBusDevice<
@@ -108,6 +110,8 @@ pub trait DynamicDevices: SpectrumUla {
     /// `device` instance.
     fn attach_device<D: Into<BoxNamedDynDevice<SpecBusTs<Self>>>>(&mut self, device: D) -> bool;
     fn detach_device<D: NamedBusDevice<SpecBusTs<Self>> + 'static>(&mut self) -> Option<Box<D>>;
+    /// Detaches all devices from the dynamic bus [DynamicBus] if it's present in the static device chain.
+    fn clear_devices(&mut self);
     fn device_mut<D: NamedBusDevice<SpecBusTs<Self>> + 'static>(&mut self) -> Option<&mut D>;
     fn device_ref<D: NamedBusDevice<SpecBusTs<Self>> + 'static>(&self) -> Option<&D>;
     /// This must be called after deserializing a struct with dynamic devices.
@@ -385,6 +389,13 @@ impl<C, U, F> DynamicDevices for ZxSpectrum<C, U, F>
         })
     }
 
+    fn clear_devices(&mut self) {
+        if let Some(dynbus) = self.ula.dyn_bus_device_mut() {
+            dynbus.clear();
+        }
+        self.state.devices.clear();
+    }
+
     fn rebuild_device_index(&mut self) {
         self.state.devices.clear();
         if let Some(dynbus) = self.ula.dyn_bus_device_ref() {
@@ -409,3 +420,111 @@ impl<C: Cpu, S, X, F, R, W> ZxSpectrumModel<C, S, X, F, R, W>
         spectrum_model_dispatch!(self(spec) => spec.rebuild_device_index());
     }
 }
+
+impl<C: Cpu, S: 'static, X, F, R, W> ZxSpectrumModel<C, S, X, F, R, W>
+    where X: MemoryExtension,
+          R: io::Read + fmt::Debug,
+          W: io::Write + fmt::Debug,
+{
+    /// Restores the default device chain of the model.
+    ///
+    /// Detaches all dynamic devices and the joystick.
+    pub fn clear_devices(&mut self) {
+        spectrum_model_dispatch!(self(spec) => spec.clear_devices());
+        self.select_joystick(usize::max_value());
+    }
+    /// Restores the default hardware configuration of the model.
+    ///
+    /// Restores the default device chain with [ZxSpectrumModel::clear_devices], resets the emulator
+    /// settings to their default values, except the inserted tape, and requests the hard reset.
+    pub fn reset_hardware_config(&mut self) {
+        self.clear_devices();
+        let state = self.emulator_state_mut();
+        let tape = mem::take(&mut state.tape);
+        *state = EmulatorState { tape, ..EmulatorState::default() };
+        spectrum_model_dispatch!(self(spec) => spec.reset(true));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use spectrusty::z80emu::{Io, Z80NMOS};
+    use spectrusty::clock::VideoTs;
+    use spectrusty::memory::NoMemoryExtension;
+    use spectrusty::peripherals::ay::AyRegister;
+    use super::*;
+
+    type TestModel = ZxSpectrumModel<Z80NMOS, (), NoMemoryExtension>;
+    type TestTs = SpecBusTs<ZxSpectrum48k<Z80NMOS, UlaPALDevice<()>>>;
+
+    fn read_port(model: &mut TestModel, port: u16) -> u8 {
+        spectrum_model_dispatch!(model(spec) => spec.ula.read_io(port, VideoTs::new(0, 0)).0)
+    }
+
+    fn dyn_devices_count(model: &TestModel) -> usize {
+        spectrum_model_dispatch!(model(spec) => spec.ula.dyn_bus_device_ref().unwrap().len())
+    }
+
+    #[test]
+    fn clear_devices_works() {
+        let mut model = TestModel::new(ModelRequest::Spectrum48);
+        // no Kempston joystick, floating bus in the border area
+        assert_eq!(read_port(&mut model, 0x1F), 0xFF);
+        if let ZxSpectrumModel::Spectrum48(spec) = &mut model {
+            assert!(spec.attach_device(KempstonMouse::<TestTs>::default()));
+            assert!(spec.attach_device(Ay3_891xMelodik::<TestTs>::default()));
+            assert!(spec.attach_device(Ay3_891xFullerBox::<TestTs>::default()));
+            assert!(spec.device_ref::<KempstonMouse<TestTs>>().is_some());
+        }
+        model.select_joystick(0);
+        assert_eq!(model.current_joystick(), Some("Kempston"));
+        assert_eq!(dyn_devices_count(&model), 3);
+        assert_eq!(read_port(&mut model, 0x1F), 0x00);
+        model.emulator_state_mut().turbo = true;
+
+        model.clear_devices();
+        assert_eq!(dyn_devices_count(&model), 0);
+        assert!(model.emulator_state_ref().devices.is_empty());
+        assert_eq!(model.current_joystick(), None);
+        assert_eq!(read_port(&mut model, 0x1F), 0xFF);
+        if let ZxSpectrumModel::Spectrum48(spec) = &mut model {
+            assert!(spec.device_ref::<KempstonMouse<TestTs>>().is_none());
+            assert!(spec.detach_device::<Ay3_891xMelodik<TestTs>>().is_none());
+            assert_eq!(spec.reset_request, None);
+        }
+        assert!(model.emulator_state_ref().turbo);
+
+        model.select_joystick(1);
+        model.reset_hardware_config();
+        assert_eq!(model.current_joystick(), None);
+        assert!(!model.emulator_state_ref().turbo);
+        if let ZxSpectrumModel::Spectrum48(spec) = &model {
+            assert_eq!(spec.reset_request, Some(true));
+        }
+    }
+
+    #[test]
+    fn rebuild_device_index_works() {
+        let mut model = TestModel::new(ModelRequest::Spectrum48);
+        if let ZxSpectrumModel::Spectrum48(spec) = &mut model {
+            assert!(spec.attach_device(KempstonMouse::<TestTs>::default()));
+            let mut ay = Ay3_891xMelodik::<TestTs>::default();
+            ay.ay_io.set(AyRegister::ToneFineA, 0x5A);
+            assert!(spec.attach_device(ay));
+        }
+        // the device index is not being serialized
+        model.emulator_state_mut().devices.clear();
+        if let ZxSpectrumModel::Spectrum48(spec) = &mut model {
+            assert!(spec.device_ref::<Ay3_891xMelodik<TestTs>>().is_none());
+        }
+        model.rebuild_device_index();
+        assert_eq!(model.emulator_state_ref().devices.len(), 2);
+        if let ZxSpectrumModel::Spectrum48(spec) = &mut model {
+            assert!(spec.device_ref::<KempstonMouse<TestTs>>().is_some());
+            let ay = spec.device_ref::<Ay3_891xMelodik<TestTs>>().unwrap();
+            assert_eq!(ay.ay_io.get(AyRegister::ToneFineA), 0x5A);
+            assert!(spec.detach_device::<Ay3_891xMelodik<TestTs>>().is_some());
+        }
+        assert_eq!(dyn_devices_count(&model), 1);
+    }
+}
@@ -15,13 +15,13 @@ use rand::prelude::*;
 use serde::{Serialize, Deserialize};
 
 use spectrusty::z80emu::{Cpu, Z80, Z80Any, {z80::Flavour}, host::Io};
-use spectrusty::audio::Blep;
+use spectrusty::audio::{Blep, filter::AudioFilter};
 #[allow(unused_imports)] use spectrusty::clock::{FTs, VFrameTs};
 use spectrusty::formats::snapshot::ComputerModel;
 use spectrusty::memory::{
     ZxMemory, PagedMemory8k,
     Memory16kEx, Memory48kEx, Memory48kDock64kEx,
-    MemoryExtension, NoMemoryExtension
+    MemoryExtension, NoMemoryExtension, PowerOnMemory
 };
 use spectrusty::bus::{
     BusDevice, DeserializeDynDevice, SerializeDynDevice,
@@ -406,6 +406,18 @@ impl ModelRequest {
     pub fn iter() -> ModelRequestIter {
         ModelRequestIter(Some(ModelRequest::Spectrum16))
     }
+    /// Returns a new [AudioFilter] matching the audio path of this model type.
+    pub fn audio_filter(self, sample_rate: u32, channels: usize) -> AudioFilter {
+        use ModelRequest::*;
+        match self {
+            Spectrum16|Spectrum48|SpectrumNTSC|TimexTC2048|Spectrum48Plus => {
+                AudioFilter::new_48k(sample_rate, channels)
+            }
+            Spectrum128|SpectrumPlus2|SpectrumPlus2A|SpectrumPlus3|SpectrumPlusPlus2|SpectrumPlus2B => {
+                AudioFilter::new_128k(sample_rate, channels)
+            }
+        }
+    }
 }
 
 impl Iterator for ModelRequestIter {
@@ -886,6 +898,20 @@ impl<C, S, X, F, R, W> ZxSpectrumModel<C, S, X, F, R, W>
     pub fn into_cpu_and_state(self) -> (C, EmulatorState<F>) {
         spectrum_model_dispatch!(self(spec) => (spec.cpu, spec.state))
     }
+    /// Fills RAM with the [EmulatorState::power_on_memory] pattern if one has been set.
+    ///
+    /// Should be called on a freshly created model to reproduce the power-on memory content.
+    pub fn apply_power_on_memory(&mut self) {
+        if let Some(pattern) = self.emulator_state_ref().power_on_memory {
+            spectrum_model_dispatch!(self(spec) => spec.ula.memory_mut().fill_ram_power_on(pattern))
+        }
+    }
+    /// Sets the power-on memory pattern as the reference for serializing the memory as a delta.
+    ///
+    /// See [ZxMemory::set_serde_delta_base].
+    pub fn set_memory_delta_base(&mut self, base: Option<PowerOnMemory>) -> bool {
+        spectrum_model_dispatch!(self(spec) => spec.ula.memory_mut().set_serde_delta_base(base))
+    }
     /// Returns a dynamicaly dispatched reader from paged in RAM.
     pub fn read_ram<'a>(&'a self) -> Box<dyn Read + 'a> {
         match self {
@@ -964,7 +990,37 @@ impl<C, S, X, F, R, W> ZxSpectrumModel<C, S, X, F, R, W>
             _ => {}
         }
         let prev_model = core::mem::replace(self, Self::new(request));
-        spectrum_model_dispatch!(self(spec) => spec.copy_from(prev_model))
+        spectrum_model_dispatch!(self(spec) => spec.copy_from(prev_model));
+        self.emulator_state_mut().request_audio_flush();
+    }
+
+    /// Returns a freshly powered on instance of a requested model with RAM filled according to
+    /// the given power-on memory `pattern`.
+    ///
+    /// The `pattern` is stored in [EmulatorState::power_on_memory], so it is re-applied on
+    /// subsequent [ZxSpectrumModel::power_cycle]s.
+    pub fn new_power_on(request: ModelRequest, pattern: Option<PowerOnMemory>) -> Self
+        where X: Default, R: Default, W: Default,
+    {
+        let mut model = Self::new(request);
+        model.emulator_state_mut().power_on_memory = pattern;
+        model.apply_power_on_memory();
+        model
+    }
+
+    /// Emulates the power off/on cycle by replacing `self` with a fresh instance of the same model.
+    ///
+    /// The [EmulatorState] is preserved and RAM is re-filled with the
+    /// [EmulatorState::power_on_memory] pattern. Returns the previous instance, so the caller
+    /// can re-create dynamic devices that should survive the power cycle.
+    pub fn power_cycle(&mut self) -> Self
+        where X: Default, R: Default, W: Default,
+    {
+        let mut prev_model = core::mem::replace(self, Self::new(ModelRequest::from(&*self)));
+        let state = core::mem::take(prev_model.emulator_state_mut());
+        self.set_emulator_state(state);
+        self.apply_power_on_memory();
+        prev_model
     }
 
     pub fn set_frame_tstate(&mut self, ts: FTs) {
@@ -1062,3 +1118,196 @@ impl<'a, U> UlaPlusMode for UlaPlus<U>
         true
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use spectrusty::z80emu::{Cpu, CpuFlags, Z80NMOS};
+    use spectrusty::audio::synth::BandLimited;
+    use spectrusty::memory::NoMemoryExtension;
+    use spectrusty::chip::{ControlUnit, EarIn};
+    use spectrusty::video::BorderSize;
+    use super::*;
+
+    type TestModel = ZxSpectrumModel<Z80NMOS, (), NoMemoryExtension>;
+
+    const SAMPLE_RATE: u32 = 44100;
+
+    fn run_frame_with_audio(model: &mut TestModel, blep: &mut BandLimited<f32>) -> Vec<f32> {
+        spectrum_model_dispatch!(model(spec) => {
+            spec.run_frame().unwrap();
+            spec.render_audio(blep)
+        });
+        let channel = model.emulator_state_ref().earmic_channel;
+        let samples = blep.sum_iter::<f32>(channel).collect();
+        blep.next_frame();
+        samples
+    }
+
+    fn model_switch_samples(audio_click_suppression: bool) -> (Vec<f32>, Vec<f32>) {
+        let mut model = TestModel::new(ModelRequest::Spectrum48);
+        model.emulator_state_mut().audio_click_suppression = audio_click_suppression;
+        // DI; JR -2
+        let spec_ref = &mut model;
+        spectrum_model_dispatch!(spec_ref(spec) => {
+            spec.ula.memory_mut().load_into_mem(0x8000..=0x8002, &[0xF3, 0x18, 0xFE][..]).unwrap()
+        });
+        model.cpu_mut().set_pc(0x8000);
+        let mut blep = BandLimited::<f32>::new(3);
+        model.ensure_audio_frame_time(&mut blep, SAMPLE_RATE);
+        // the EAR output goes high
+        model.write_port(0xFE, 0x10);
+        let before = run_frame_with_audio(&mut model, &mut blep);
+        model.change_model(ModelRequest::Spectrum128);
+        model.ensure_audio_frame_time(&mut blep, SAMPLE_RATE);
+        let after = run_frame_with_audio(&mut model, &mut blep);
+        (before, after)
+    }
+
+    #[test]
+    fn audio_click_suppression_works() {
+        let (before, after) = model_switch_samples(true);
+        let last_level = *before.last().unwrap();
+        assert!(last_level > 0.05);
+        // no jump at the beginning of the frame
+        assert!((after[0] - last_level).abs() < 0.001);
+        // the level ramps down gradually
+        let max_step = (last_level / after.len() as f32) * 2.0;
+        assert!(after.windows(2).all(|w| w[1] <= w[0] && w[0] - w[1] <= max_step));
+        // to the silence of the new model
+        assert!(after.last().unwrap().abs() < 0.001);
+        // without the suppression the stale level persists
+        let (_, after) = model_switch_samples(false);
+        assert!(*after.last().unwrap() > 0.01);
+    }
+
+    #[derive(Default)]
+    struct StepRecorder(Vec<(usize, FTs, f32)>);
+
+    impl Blep for StepRecorder {
+        type SampleDelta = f32;
+        fn ensure_frame_time(&mut self, _: u32, _: f64, _: FTs, _: FTs) {}
+        fn end_frame(&mut self, _: FTs) -> usize { 0 }
+        fn add_step(&mut self, channel: usize, timestamp: FTs, delta: f32) {
+            self.0.push((channel, timestamp, delta))
+        }
+    }
+
+    fn beeper_and_tape_steps(tape_audio_gain: f32) -> (f32, f32) {
+        let mut model = TestModel::new(ModelRequest::Spectrum48);
+        // LD A,0x10; OUT (0xFE),A; DI; JR -2
+        model.cpu_mut().set_pc(0x8000);
+        let mut blep = StepRecorder::default();
+        let spec_ref = &mut model;
+        spectrum_model_dispatch!(spec_ref(spec) => {
+            spec.ula.memory_mut().load_into_mem(0x8000..=0x8006,
+                &[0x3E, 0x10, 0xD3, 0xFE, 0xF3, 0x18, 0xFE][..]).unwrap();
+            assert!(spec.ula.set_tape_audio_gain(tape_audio_gain));
+            spec.ula.ensure_next_frame();
+            spec.ula.set_ear_in(true, 10000);
+            spec.ula.execute_next_frame(&mut spec.cpu);
+            spec.render_audio(&mut blep)
+        });
+        let channel = model.emulator_state_ref().earmic_channel;
+        assert_eq!(2, blep.0.len());
+        assert!(blep.0.iter().all(|&(ch, ..)| ch == channel));
+        let (_, beeper_ts, beeper) = blep.0[0];
+        let (_, tape_ts, tape) = blep.0[1];
+        assert!(beeper_ts < tape_ts);
+        (beeper, tape)
+    }
+
+    #[test]
+    fn tape_audio_gain_works() {
+        let (beeper, tape) = beeper_and_tape_steps(1.0);
+        let (half_beeper, half_tape) = beeper_and_tape_steps(0.5);
+        assert!(tape > 0.0);
+        assert_eq!(beeper, half_beeper);
+        assert!((half_tape - tape * 0.5).abs() < 1e-6);
+        assert!(half_tape / half_beeper < tape / beeper);
+        let (_, muted_tape) = beeper_and_tape_steps(-1.0);
+        assert_eq!(0.0, muted_tape);
+    }
+
+    #[test]
+    fn ear_in_probing_ignores_keyboard_scan() {
+        const LD_BYTES: u16 = 0x0556;
+        let mut model = TestModel::new(ModelRequest::Spectrum48);
+        let spec_ref = &mut model;
+        spectrum_model_dispatch!(spec_ref(spec) => {
+            for _ in 0..100 {
+                spec.run_frame().unwrap();
+            }
+            // the ROM is waiting for a key
+            assert!(spec.ula.read_ear_in_count() != 0);
+            assert!(!spec.is_ear_in_probed());
+            // the ROM is waiting for the leader tone
+            spec.cpu.set_flags(CpuFlags::C);
+            spec.cpu.set_pc(LD_BYTES);
+            spec.run_frame().unwrap();
+            assert!(spec.is_ear_in_probed());
+        });
+    }
+
+    #[test]
+    fn fast_forward_renders_audio() {
+        const WALL_FRAMES: u32 = 10;
+        let mut model = TestModel::new(ModelRequest::Spectrum48);
+        let mut blep = BandLimited::<f32>::new(3);
+        model.emulator_state_mut().set_fast_forward(Some(2));
+        assert_eq!(model.emulator_state_ref().fast_forward_speed(), 2);
+        model.ensure_audio_frame_time(&mut blep, SAMPLE_RATE);
+        let (mut frames, mut samples) = (0, 0);
+        let spec_ref = &mut model;
+        let mut tstates: FTs = 0;
+        spectrum_model_dispatch!(spec_ref(spec) => {
+            // each wall frame is run once per the frame duration in real-time
+            for _ in 0..WALL_FRAMES {
+                tstates += spec.run_frames_fast_forward(&mut blep, |blep, count| {
+                    frames += 1;
+                    samples += count;
+                    blep.next_frame();
+                    Ok(())
+                }).unwrap().0;
+            }
+        });
+        assert_eq!(frames, 2 * WALL_FRAMES);
+        let frame_tstates = model.frame_tstates_count();
+        assert!((tstates - 2 * WALL_FRAMES as FTs * frame_tstates).abs() < 100, "{}", tstates);
+        // the audio still fills the wall time
+        let wall_time = f64::from(model.frame_duration_nanos()) * f64::from(WALL_FRAMES) / 1e9;
+        let expected = f64::from(SAMPLE_RATE) * wall_time;
+        assert!((samples as f64 - expected).abs() < 2.0, "{} {}", samples, expected);
+        // disabled
+        model.emulator_state_mut().set_fast_forward(None);
+        assert_eq!(model.emulator_state_ref().fast_forward_speed(), 1);
+        model.emulator_state_mut().set_fast_forward(Some(10));
+        assert_eq!(model.emulator_state_ref().fast_forward, Some(4));
+    }
+
+    fn ram_of(model: &mut TestModel) -> Vec<u8> {
+        spectrum_model_dispatch!(model(spec) => spec.ula.memory_ref().ram_ref().to_vec())
+    }
+
+    #[test]
+    fn power_cycle_works() {
+        let pattern = Some(PowerOnMemory::Random(42));
+        let mut model = TestModel::new_power_on(ModelRequest::Spectrum128, pattern);
+        let ram = ram_of(&mut model);
+        assert!(ram.iter().any(|&b| b != ram[0]));
+        model.emulator_state_mut().border_size = BorderSize::Tiny;
+        let spec_ref = &mut model;
+        spectrum_model_dispatch!(spec_ref(spec) => {
+            spec.ula.memory_mut().load_into_mem(0x8000..=0x8002, &[0xF3, 0x18, 0xFE][..]).unwrap();
+            spec.run_frame().unwrap();
+        });
+        assert_ne!(ram, ram_of(&mut model));
+        let prev_model = model.power_cycle();
+        assert_eq!(ModelRequest::Spectrum128, ModelRequest::from(&prev_model));
+        assert_eq!(ModelRequest::Spectrum128, ModelRequest::from(&model));
+        // the emulator state survives and the power-on memory content is reproduced
+        assert_eq!(BorderSize::Tiny, model.emulator_state_ref().border_size);
+        assert_eq!(pattern, model.emulator_state_ref().power_on_memory);
+        assert_eq!(ram, ram_of(&mut model));
+        assert_eq!(0, model.cpu_ref().get_pc());
+    }
+}
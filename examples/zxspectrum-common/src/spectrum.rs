@@ -4,6 +4,7 @@
 
     For the full copyright notice, see the lib.rs file.
 */
+use core::ops::RangeInclusive;
 use core::time::Duration;
 use std::io::{Read, Write, Seek, Cursor};
 
@@ -30,6 +31,7 @@ use spectrusty::chip::ThreadSyncTimer;
 #[cfg(target_arch = "wasm32")]
 use spectrusty::chip::AnimationFrameSyncTimer;
 
+use spectrusty::memory::PowerOnMemory;
 use spectrusty::video::{
     VideoFrame, BorderSize
 };
@@ -41,7 +43,7 @@ use spectrusty::peripherals::{
 
 use spectrusty::formats::tap::TapChunkRead;
 use spectrusty_utils::{
-    tap::{Tape, romload::try_instant_rom_tape_load_or_verify}
+    tap::{Tape, romload::TapeLoaderDetector}
 };
 pub use spectrusty_utils::tap::TapState;
 
@@ -51,6 +53,9 @@ use super::devices::DeviceAccess;
 /// A common result type used by many methods in this library.
 pub type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>;
 
+/// The range of the fast-forward speed multiples, see [EmulatorState::fast_forward].
+pub const FAST_FORWARD_SPEEDS: RangeInclusive<u8> = 2..=4;
+
 /// A helper trait for defining contraints on the chipset type from the specialized [ZxSpectrum] types.
 pub trait SpectrumUla {
     /// The type of the [ZxSpectrum] chipset.
@@ -112,6 +117,11 @@ pub struct EmulatorState<F=MemTap> {
     pub turbo: bool,
     /// Emulation speed factor: 1.0 - original rate.
     pub clock_rate_factor: f32,
+    /// The fast-forward speed multiple, `None` if disabled.
+    ///
+    /// Unlike in the turbo mode, the audio is still being rendered, sped-up accordingly.
+    #[serde(default)]
+    pub fast_forward: Option<u8>,
     /// Should the tape loading and saving be auto-accelerated?
     pub flash_tape: bool,
     /// Should the tape audio signal be emitted when acceleration is disabled?
@@ -132,13 +142,27 @@ pub struct EmulatorState<F=MemTap> {
     /// Indicates if an instant tape loading using ROM loading routines should be enabled.
     #[serde(default = "default_instant_tape")]
     pub instant_tape: bool,
+    /// Restricts the instant tape loading to the standard ROM loader.
+    #[serde(skip)]
+    pub tape_loader_detector: TapeLoaderDetector,
+    /// Should the audio output be faded out after disruptive changes to avoid clicks?
+    #[serde(default = "default_audio_click_suppression")]
+    pub audio_click_suppression: bool,
+    /// The RAM content pattern to be applied on power on, `None` for the non-deterministic random content.
+    #[serde(default)]
+    pub power_on_memory: Option<PowerOnMemory>,
     /// Index of attached dynamic devices.
     #[serde(skip)]
-    pub devices: DeviceIndex
+    pub devices: DeviceIndex,
+    #[serde(skip)]
+    pub(crate) audio_flush_request: bool
 }
 
 fn default_instant_tape() -> bool { true }
 
+fn default_audio_click_suppression() -> bool { true }
+
+
 impl<C: Cpu, U: ControlUnit, F> SpectrumUla for ZxSpectrum<C, U, F> {
     type Chipset = U;
 }
@@ -152,6 +176,7 @@ impl<F> Default for EmulatorState<F> {
             paused: false,
             turbo: false,
             clock_rate_factor: 1.0,
+            fast_forward: None,
             flash_tape: true,
             audible_tape: true,
             ay_channels: AyChannelsMode::default(),
@@ -161,9 +186,45 @@ impl<F> Default for EmulatorState<F> {
             border_size: BorderSize::Full,
             interlace: InterlaceMode::default(),
             instant_tape: default_instant_tape(),
-            devices: DeviceIndex::default()
+            tape_loader_detector: TapeLoaderDetector::default(),
+            audio_click_suppression: default_audio_click_suppression(),
+            power_on_memory: None,
+            devices: DeviceIndex::default(),
+            audio_flush_request: false
+        }
+    }
+}
+
+impl<F> EmulatorState<F> {
+    /// Requests the audio output to be faded out on the next call to [ZxSpectrum::render_audio]
+    /// if [EmulatorState::audio_click_suppression] is enabled.
+    ///
+    /// Should be called after disruptive changes, e.g. after a snapshot has been loaded.
+    pub fn request_audio_flush(&mut self) {
+        self.audio_flush_request = true;
+    }
+    /// Changes the emulation speed factor and requests the audio output to be faded out.
+    pub fn set_clock_rate_factor(&mut self, clock_rate_factor: f32) {
+        if self.clock_rate_factor != clock_rate_factor {
+            self.clock_rate_factor = clock_rate_factor;
+            self.request_audio_flush();
+        }
+    }
+    /// Enables the fast-forward mode with the given speed multiple clamped to [FAST_FORWARD_SPEEDS]
+    /// or disables it if `speed` is `None`, and requests the audio output to be faded out.
+    ///
+    /// [ZxSpectrum::ensure_audio_frame_time] should be called after the mode changes.
+    pub fn set_fast_forward(&mut self, speed: Option<u8>) {
+        let speed = speed.map(|speed| speed.clamp(*FAST_FORWARD_SPEEDS.start(), *FAST_FORWARD_SPEEDS.end()));
+        if self.fast_forward != speed {
+            self.fast_forward = speed;
+            self.request_audio_flush();
         }
     }
+    /// Returns the number of frames to be run during a single frame duration in real-time.
+    pub fn fast_forward_speed(&self) -> u8 {
+        self.fast_forward.unwrap_or(1)
+    }
 }
 
 impl<L: Flavour, U, F> ZxSpectrum<Z80<L>, U, F>
@@ -197,10 +258,15 @@ impl<C: Cpu, U, F> ZxSpectrum<C, U, F>
         U::effective_frame_duration(self.state.clock_rate_factor as f64)
     }
 
+    /// Sets up the [Blep] audio frame time for the current emulation speed.
+    ///
+    /// In the fast-forward mode, the audio of each frame is resampled to take a fraction of
+    /// the frame duration, so the audio of all frames run during a single frame duration fits into it.
     pub fn ensure_audio_frame_time<B: Blep>(&self, blep: &mut B, sample_rate: u32)
         where U: AudioFrame<B>
     {
-        self.ula.ensure_audio_frame_time(blep, sample_rate, self.effective_cpu_rate())
+        let cpu_rate = self.effective_cpu_rate() * f64::from(self.state.fast_forward_speed());
+        self.ula.ensure_audio_frame_time(blep, sample_rate, cpu_rate)
     }
 }
 
@@ -271,13 +337,14 @@ impl<C: Cpu, U, F> ZxSpectrum<C, U, F>
         let state = &mut self.state;
         if state.instant_tape {
             let mut chunk_no = 0;
-            // try to instantly load/verify if ROM loader is being used
-            if let Some(read_len) = try_instant_rom_tape_load_or_verify(
+            let tape = &mut state.tape;
+            // try to instantly load/verify if ROM loader is being used and no custom loader is present
+            if let Some(read_len) = state.tape_loader_detector.try_instant_tape_load_or_verify(
                 &mut self.cpu,
                 self.ula.memory_mut(),
                 || {
-                    state.tape.make_reader()?;
-                    let pulse_iter = state.tape.reader_mut().unwrap().get_mut();
+                    tape.make_reader()?;
+                    let pulse_iter = tape.reader_mut().unwrap().get_mut();
                     let is_lead = pulse_iter.state().is_lead();
                     let chunk_reader = pulse_iter.get_mut();
                     chunk_no = chunk_reader.chunk_no();
@@ -354,6 +421,13 @@ impl<C: Cpu, U, F> ZxSpectrum<C, U, F>
         Ok(false)
     }
 
+    /// Returns `true` if the `EAR IN` line was probed in the last frame more often than the ROM
+    /// keyboard scanning routine would do, which reads port 0xFE only a few times each frame.
+    pub fn is_ear_in_probed(&self) -> bool {
+        const PROBE_THRESHOLD_TS: u32 = 1000;
+        self.ula.read_ear_in_count() > U::VideoFrame::FRAME_TSTATES_COUNT as u32 / PROBE_THRESHOLD_TS
+    }
+
     /// Returns `Ok(end_of_tape)`
     fn feed_ear_in_or_stop_tape(&mut self) -> Result<bool> {
         // get the reader if the tape is inserted and is being played
@@ -395,6 +469,9 @@ impl<C: Cpu, U, F> ZxSpectrum<C, U, F>
                 }
             }
         };
+        if self.state.tape.update_auto_stop(self.is_ear_in_probed()) {
+            info!("Auto STOP: loading finished");
+        }
         // clean up the internal buffers of ULA so we won't append the EAR IN data
         // to the previous frame's data
         self.ula.ensure_next_frame();
@@ -506,6 +583,9 @@ impl<C: Cpu, U, F> ZxSpectrum<C, U, F>
               EarInAmps2<B::SampleDelta>: AmpLevels<B::SampleDelta>,
               EarOutAmps4<B::SampleDelta>: AmpLevels<B::SampleDelta>
     {
+        if core::mem::take(&mut self.state.audio_flush_request) && self.state.audio_click_suppression {
+            self.flush_audio_silence(blep);
+        }
         let ay_channels = self.state.ay_channels.into();
         match self.state.ay_amps {
             AyAmpSelect::Spec => {
@@ -527,4 +607,39 @@ impl<C: Cpu, U, F> ZxSpectrum<C, U, F>
         }
         self.ula.end_audio_frame(blep)
     }
+
+    /// Runs [EmulatorState::fast_forward_speed] frames, rendering audio data after each frame.
+    ///
+    /// The `produce` closure is called with the [Blep] and the number of samples ready to be produced
+    /// after each frame has been rendered. It should consume the samples and prepare the [Blep] for
+    /// the next frame.
+    ///
+    /// Stops early if the state has changed. Returns a tuple of `(T-states difference, state_changed)`.
+    /// The returned `state_changed` is a hint if the UI needs to be updated.
+    pub fn run_frames_fast_forward<B, FN>(&mut self, blep: &mut B, mut produce: FN) -> Result<(FTs, bool)>
+        where U: UlaAudioFrame<B>,
+              B: Blep,
+              AyAmps<B::SampleDelta>: AmpLevels<B::SampleDelta>,
+              AyFuseAmps<B::SampleDelta>: AmpLevels<B::SampleDelta>,
+              EarMicAmps4<B::SampleDelta>: AmpLevels<B::SampleDelta>,
+              EarInAmps2<B::SampleDelta>: AmpLevels<B::SampleDelta>,
+              EarOutAmps4<B::SampleDelta>: AmpLevels<B::SampleDelta>,
+              FN: FnMut(&mut B, usize) -> Result<()>
+    {
+        let mut sum: FTs = 0;
+        for _ in 0..self.state.fast_forward_speed() {
+            let (cycles, state_changed) = self.run_frame()?;
+            sum += cycles;
+            let frame_sample_count = self.render_audio(blep);
+            produce(blep, frame_sample_count)?;
+            if state_changed {
+                return Ok((sum, true))
+            }
+        }
+        Ok((sum, false))
+    }
+
+    fn flush_audio_silence<B: Blep>(&self, blep: &mut B) {
+        blep.fade_out();
+    }
 }
@@ -79,13 +79,16 @@ pub struct AudioFrameConsumer<T> {
     rx: Receiver<AudioBuffer<T>>,
 }
 
+/// A type of the audio monitor callback that can be set with [AudioFrameProducer::set_audio_monitor].
+pub type AudioMonitor<T> = Box<dyn FnMut(&[T]) + Send>;
+
 /// Allows relaying rendered [AudioBuffer] to the [AudioFrameConsumer].
-#[derive(Debug)]
 pub struct AudioFrameProducer<T> {
     /// The next audio buffer frame to render samples to.
     pub buffer: AudioBuffer<T>,
     rx: Receiver<AudioBuffer<T>>,
     consumer_tx: Sender<AudioBuffer<T>>,
+    monitor: Option<AudioMonitor<T>>
 }
 
 /// Creates an inter-connected pair or [AudioFrameProducer] and [AudioFrameConsumer].
@@ -308,6 +311,17 @@ impl<T: 'static + Copy + Send> AudioFrameConsumer<T> {
     }
 }
 
+impl<T: fmt::Debug> fmt::Debug for AudioFrameProducer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AudioFrameProducer")
+         .field("buffer", &self.buffer)
+         .field("rx", &self.rx)
+         .field("consumer_tx", &self.consumer_tx)
+         .field("monitor", &self.monitor.is_some())
+         .finish()
+    }
+}
+
 impl<T> AudioFrameProducer<T> {
     /// Creates a new instance of `AudioFrameProducer`.
     ///
@@ -315,16 +329,32 @@ impl<T> AudioFrameProducer<T> {
     pub fn new(buffer: AudioBuffer<T>,
                consumer_tx: Sender<AudioBuffer<T>>,
                producer_rx: Receiver<AudioBuffer<T>>) -> Self {
-        AudioFrameProducer { buffer, rx: producer_rx, consumer_tx }
+        AudioFrameProducer { buffer, rx: producer_rx, consumer_tx, monitor: None }
     }
     /// Provides the current frame buffer as `Vec` of samples for rendering via a closure.
     ///
     /// The closure should ensure the size of the `Vec` is resized to the number of actually
     /// rendered samples.
+    ///
+    /// If an audio monitor is set, it is being invoked with the rendered samples after the closure returns.
     pub fn render_frame<F: FnOnce(&mut Vec<T>)>(&mut self, render: F) {
         render(&mut self.buffer);
+        if let Some(monitor) = self.monitor.as_mut() {
+            monitor(&self.buffer);
+        }
         // eprintln!("smpl: {}", self.buffer.sampled_size);
     }
+    /// Sets the audio monitor callback, that will receive the final audio samples of each rendered frame.
+    ///
+    /// The samples are passed in the same format and channel layout as rendered by [AudioFrameProducer::render_frame].
+    /// This can be used to visualize waveforms or audio levels without re-reading the host buffer.
+    pub fn set_audio_monitor<F: FnMut(&[T]) + Send + 'static>(&mut self, monitor: F) {
+        self.monitor = Some(Box::new(monitor));
+    }
+    /// Removes the audio monitor callback and returns it if it was set.
+    pub fn take_audio_monitor(&mut self) -> Option<AudioMonitor<T>> {
+        self.monitor.take()
+    }
 }
 
 impl<T: 'static + Send> AudioFrameProducer<T> {
@@ -373,6 +403,46 @@ mod tests {
     use super::*;
     use std::thread;
     use std::f32::consts::PI;
+    use std::sync::{Arc, Mutex};
+    use spectrusty_core::audio::Blep;
+    use crate::synth::{BandLimited, ext::BandLimitedExt};
+
+    #[test]
+    fn audio_monitor_works() {
+        const SAMPLE_RATE: u32 = 44100;
+        const TS_RATE: f64 = 3_500_000.0;
+        const FRAME_TS: i32 = 70000;
+        const HALF_PERIOD_TS: i32 = 1750; // 1kHz
+        const AMPLITUDE: f32 = 0.5;
+        let mut bandlim = BandLimited::<f32>::new(1);
+        bandlim.ensure_frame_time(SAMPLE_RATE, TS_RATE, FRAME_TS, 0);
+        let mut delta = AMPLITUDE;
+        for ts in (0..FRAME_TS).step_by(HALF_PERIOD_TS as usize) {
+            bandlim.add_step(0, ts, delta);
+            delta = -delta;
+        }
+        let nsamples = bandlim.end_frame(FRAME_TS);
+        assert_eq!(nsamples, 882);
+
+        let monitored = Arc::new(Mutex::new((0usize, 0.0f32)));
+        let (mut producer, _consumer) = create_carousel::<f32>(1, nsamples, 1);
+        let mon = Arc::clone(&monitored);
+        producer.set_audio_monitor(move |samples| {
+            let peak = samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+            *mon.lock().unwrap() = (samples.len(), peak);
+        });
+        producer.render_frame(|vec| {
+            vec.resize(nsamples, 0.0);
+            bandlim.render_audio_channel(vec, 0);
+        });
+        let (len, peak) = *monitored.lock().unwrap();
+        assert_eq!(len, nsamples);
+        assert!(peak > AMPLITUDE * 0.9 && peak < AMPLITUDE * 1.2, "peak: {}", peak);
+
+        assert!(producer.take_audio_monitor().is_some());
+        producer.render_frame(|vec| vec.clear());
+        assert_eq!(monitored.lock().unwrap().0, nsamples);
+    }
 
     #[test]
     fn carousel_works() -> Result<(), Box<dyn error::Error>> {
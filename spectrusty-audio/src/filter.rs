@@ -0,0 +1,192 @@
+/*
+    Copyright (C) 2020-2023  Rafal Michalski
+
+    This file is part of SPECTRUSTY, a Rust library for building emulators.
+
+    For the full copyright notice, see the lib.rs file.
+*/
+//! Audio output filtering.
+//!
+//! The analog audio paths of the Spectrum computers soften the output of the beeper and the AY-3-8912
+//! with their RC circuits. [AudioFilter] emulates them with a one-pole high-pass filter, removing
+//! the DC offset of the beeper, followed by a one-pole low-pass filter.
+//!
+//! The filter processes audio samples already rendered, e.g. by [BandLimited][crate::synth::BandLimited].
+use core::f32::consts::PI;
+use spectrusty_core::audio::{FromSample, IntoSample};
+
+/// The high-pass filter cutoff frequency in Hz of both the ZX Spectrum 48K and 128K audio paths.
+///
+/// The coupling capacitors of the audio outputs block the DC offset of the beeper. Their cutoff
+/// frequencies depend on the connected equipment, so the lower limit of the human hearing range is
+/// used instead: the DC offset is removed without audibly attenuating the bass.
+pub const DC_BLOCKING_HIGH_PASS_HZ: f32 = 20.0;
+/// The approximated low-pass filter cutoff frequency of the ZX Spectrum 48K audio path in Hz.
+///
+/// This value is not derived from a measurement or a schematic of the audio output. It is an
+/// estimate chosen by ear to soften the beeper's square waves, the way the 48K's small built-in
+/// speaker does. Use [AudioFilter::new] to apply a cutoff measured on a particular machine.
+pub const ZX48K_LOW_PASS_HZ: f32 = 6_000.0;
+/// The approximated low-pass filter cutoff frequency of the ZX Spectrum 128K audio path in Hz.
+///
+/// Like [ZX48K_LOW_PASS_HZ], this value is an estimate chosen by ear and not a measured figure.
+/// It is set higher because the 128K outputs sound through the TV speaker or an external amplifier.
+pub const ZX128K_LOW_PASS_HZ: f32 = 12_000.0;
+
+/// A configurable one-pole high-pass and low-pass audio filter.
+///
+/// Keeps the state of each audio channel separately, so consecutive buffers of a continuous audio
+/// stream can be processed one after another.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AudioFilter {
+    high_pass: Option<f32>,
+    low_pass: Option<f32>,
+    state: Box<[FilterState]>
+}
+
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+struct FilterState {
+    low_out: f32,
+    high_in: f32,
+    high_out: f32
+}
+
+/// Returns the RC time constant of the given cutoff frequency and the sample period.
+fn rc_dt(sample_rate: u32, cutoff_hz: f32) -> (f32, f32) {
+    assert!(sample_rate > 0 && cutoff_hz > 0.0);
+    (1.0 / (2.0 * PI * cutoff_hz), 1.0 / sample_rate as f32)
+}
+
+impl AudioFilter {
+    /// Returns a new instance of `AudioFilter`.
+    ///
+    /// * `sample_rate` - the sample rate of the processed audio in Hz.
+    /// * `channels` - the number of audio channels.
+    /// * `high_pass_hz` - the high-pass filter cutoff frequency in Hz, `None` disables the high-pass filter.
+    /// * `low_pass_hz` - the low-pass filter cutoff frequency in Hz, `None` disables the low-pass filter.
+    ///
+    /// # Panics
+    /// Panics if `channels` or `sample_rate` equals to `0` or if any of the cutoff frequencies is not positive.
+    pub fn new(sample_rate: u32, channels: usize, high_pass_hz: Option<f32>, low_pass_hz: Option<f32>) -> Self {
+        assert!(channels > 0, "AudioFilter: channels should be 1 or more");
+        let high_pass = high_pass_hz.map(|hz| {
+            let (rc, dt) = rc_dt(sample_rate, hz);
+            rc / (rc + dt)
+        });
+        let low_pass = low_pass_hz.map(|hz| {
+            let (rc, dt) = rc_dt(sample_rate, hz);
+            dt / (rc + dt)
+        });
+        let state = vec![FilterState::default(); channels].into_boxed_slice();
+        AudioFilter { high_pass, low_pass, state }
+    }
+    /// Returns a new instance of `AudioFilter` matching the ZX Spectrum 48K audio path.
+    pub fn new_48k(sample_rate: u32, channels: usize) -> Self {
+        AudioFilter::new(sample_rate, channels, Some(DC_BLOCKING_HIGH_PASS_HZ), Some(ZX48K_LOW_PASS_HZ))
+    }
+    /// Returns a new instance of `AudioFilter` matching the ZX Spectrum 128K audio path.
+    pub fn new_128k(sample_rate: u32, channels: usize) -> Self {
+        AudioFilter::new(sample_rate, channels, Some(DC_BLOCKING_HIGH_PASS_HZ), Some(ZX128K_LOW_PASS_HZ))
+    }
+    /// Returns the number of audio channels.
+    pub fn channels(&self) -> usize {
+        self.state.len()
+    }
+    /// Clears the state of the filter.
+    pub fn reset(&mut self) {
+        for state in self.state.iter_mut() {
+            *state = FilterState::default();
+        }
+    }
+    /// Filters samples of the `channel` in the single channel `buffer`.
+    ///
+    /// # Panics
+    /// Panics if `channel` is not lower than the number of channels.
+    pub fn process_channel<S>(&mut self, buffer: &mut [S], channel: usize)
+        where S: Copy + IntoSample<f32> + FromSample<f32>
+    {
+        let (high_pass, low_pass) = (self.high_pass, self.low_pass);
+        let state = &mut self.state[channel];
+        for sample in buffer.iter_mut() {
+            *sample = S::from_sample(state.filter(high_pass, low_pass, (*sample).into_sample()));
+        }
+    }
+    /// Filters samples of all channels in the channel-interleaved `buffer`.
+    ///
+    /// The number of channels in the `buffer` must match the number of channels of the filter.
+    pub fn process_interleaved<S>(&mut self, buffer: &mut [S])
+        where S: Copy + IntoSample<f32> + FromSample<f32>
+    {
+        let (high_pass, low_pass) = (self.high_pass, self.low_pass);
+        for frame in buffer.chunks_mut(self.state.len()) {
+            for (sample, state) in frame.iter_mut().zip(self.state.iter_mut()) {
+                *sample = S::from_sample(state.filter(high_pass, low_pass, (*sample).into_sample()));
+            }
+        }
+    }
+}
+
+impl FilterState {
+    #[inline]
+    fn filter(&mut self, high_pass: Option<f32>, low_pass: Option<f32>, input: f32) -> f32 {
+        let mut output = input;
+        if let Some(alpha) = high_pass {
+            self.high_out = alpha * (self.high_out + output - self.high_in);
+            self.high_in = output;
+            output = self.high_out;
+        }
+        if let Some(alpha) = low_pass {
+            self.low_out += alpha * (output - self.low_out);
+            output = self.low_out;
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn audio_filter_high_pass_works() {
+        let mut filter = AudioFilter::new(44100, 1, Some(DC_BLOCKING_HIGH_PASS_HZ), None);
+        assert_eq!(1, filter.channels());
+        let mut buffer = [1.0f32; 44100];
+        filter.process_channel(&mut buffer, 0);
+        assert!(buffer[0] > 0.99 && buffer[0] <= 1.0);
+        for pair in buffer.windows(2) {
+            assert!(pair[1] <= pair[0] && pair[1] >= 0.0);
+        }
+        assert!(buffer[4410] < 0.5);
+        assert!(buffer[44099] < 0.001);
+        let mut buffer = [1.0f32; 100];
+        filter.process_channel(&mut buffer, 0);
+        assert!(buffer.iter().all(|&s| s < 0.001));
+        filter.reset();
+        let mut buffer = [1.0f32; 100];
+        filter.process_channel(&mut buffer, 0);
+        assert!(buffer[0] > 0.99);
+    }
+
+    #[test]
+    fn audio_filter_low_pass_works() {
+        let mut filter = AudioFilter::new(44100, 2, None, Some(ZX48K_LOW_PASS_HZ));
+        let mut buffer = [0i16; 200];
+        for frame in buffer.chunks_mut(2) {
+            frame[0] = i16::MAX;
+        }
+        filter.process_interleaved(&mut buffer);
+        assert!(buffer[0] > 0 && buffer[0] < i16::MAX / 2);
+        for pair in buffer.chunks(2).collect::<Vec<_>>().windows(2) {
+            assert!(pair[1][0] >= pair[0][0]);
+            assert_eq!(0, pair[1][1]);
+        }
+        assert!(buffer[198] > i16::MAX - 100);
+        let mut filter = AudioFilter::new_128k(44100, 1);
+        let mut buffer = [1.0f32; 4410];
+        filter.process_channel(&mut buffer, 0);
+        assert!(buffer[0] < 1.0);
+        assert!(buffer.iter().any(|&s| s > 0.9));
+        assert!(buffer[4409] < 0.1);
+    }
+}
@@ -20,6 +20,7 @@
 */
 //! Audio related utilities for the SPECTRUSTY library.
 pub mod carousel;
+pub mod filter;
 pub mod host;
 pub mod music;
 pub mod synth;
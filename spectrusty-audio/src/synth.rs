@@ -64,6 +64,9 @@ impl BandLimOpt for BandLimNarrow {
 ///
 /// `T` specifies pulse step amplitude unit types. Currently, implementations are provided for:
 ///  `f64`, `f32`, `i32`, `i16`, and `i8`. Although using `i8` renders very poor quality sound.
+///
+/// Pulse steps are rendered directly at the audio sample rate given to [Blep::ensure_frame_time],
+/// at fractional sample phases, so no further resampling of the output is necessary.
 pub struct BandLimited<T, O=BandLimWide> {
     steps: [[T; STEP_WIDTH]; PHASE_COUNT],
     diffs: Vec<T>,
@@ -374,8 +377,39 @@ where I: Iterator<Item=&'a T>,
     }
 }
 
+impl<T, O> BandLimited<T, O>
+where T: Copy + MulNorm + FromSample<f32>,
+      O: BandLimOpt
+{
+    /// Fades out the output level of each channel down to silence over the duration of the current frame.
+    ///
+    /// The level the previous frame has ended with is being gradually reduced, taking into account
+    /// the high-pass filter, so it reaches zero at the end of the current frame. Pulse steps added in
+    /// the current frame are not affected.
+    ///
+    /// This method must be called before the call to [BandLimited::end_frame_at] or [Blep::end_frame].
+    pub fn fade_out_frame(&mut self) {
+        let channels = self.channels.get();
+        let num_samples = (self.frame_time as usize).min(self.diffs.len() / channels);
+        if num_samples == 0 {
+            return
+        }
+        // the level after n samples: sum * hp^n + delta * hp * (1 - hp^n) / (1 - hp)
+        let hp = O::HIGH_PASS as f64;
+        let hpn = hp.powi(num_samples as i32);
+        let ratio = T::from_sample((-hpn / hp * (1.0 - hp) / (1.0 - hpn)) as f32);
+        for (channel, (sum, _)) in self.sums.iter().enumerate() {
+            let delta = sum.mul_norm(ratio);
+            for diff in self.diffs[..num_samples*channels].iter_mut().skip(channel).step_by(channels) {
+                *diff = diff.saturating_add(delta);
+            }
+        }
+    }
+}
+
 impl<T, O> Blep for BandLimited<T, O>
-where T: SampleDelta + MulNorm
+where T: SampleDelta + MulNorm + FromSample<f32>,
+      O: BandLimOpt
 {
     type SampleDelta = T;
 
@@ -402,4 +436,122 @@ where T: SampleDelta + MulNorm
         let time = self.time_rate * timestamp as f64;
         self.add_step_at(channel, time, delta)
     }
+
+    #[inline]
+    fn fade_out(&mut self) {
+        self.fade_out_frame()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render_square_wave(sample_rate: u32, freq: u32, frames: usize) -> (usize, usize) {
+        const TS_RATE: f64 = 3_500_000.0;
+        const FRAME_TS: FTs = 69888;
+        let half_period = TS_RATE / (2 * freq) as f64;
+        let mut blep = BandLimited::<f32>::new(1);
+        blep.ensure_frame_time(sample_rate, TS_RATE, FRAME_TS, 0);
+        let mut delta = 0.5f32;
+        let mut next_edge = 0.0f64;
+        let mut frame_start = 0.0f64;
+        let mut nsamples = 0;
+        let mut crossings = 0;
+        let mut last: Option<f32> = None;
+        for frame in 0..frames {
+            while next_edge < frame_start + FRAME_TS as f64 {
+                blep.add_step(0, (next_edge - frame_start).round() as FTs, delta);
+                delta = if delta > 0.0 { -1.0 } else { 1.0 };
+                next_edge += half_period;
+            }
+            blep.end_frame(FRAME_TS);
+            // skip the first frame to let the high-pass filter settle
+            if frame != 0 {
+                for sample in blep.sum_iter::<f32>(0) {
+                    if let Some(prev) = last {
+                        if prev.is_sign_negative() != sample.is_sign_negative() {
+                            crossings += 1;
+                        }
+                    }
+                    last = Some(sample);
+                    nsamples += 1;
+                }
+            }
+            blep.next_frame();
+            frame_start += FRAME_TS as f64;
+        }
+        (nsamples, crossings)
+    }
+
+    #[test]
+    fn band_limited_target_sample_rate_works() {
+        for &sample_rate in &[44100u32, 48000, 22050] {
+            let (nsamples, crossings) = render_square_wave(sample_rate, 1000, 51);
+            let seconds = 50.0 * 69888.0 / 3_500_000.0;
+            let expected_samples = seconds * sample_rate as f64;
+            assert!((nsamples as f64 - expected_samples).abs() <= 1.0,
+                "{}: {} samples, expected: {}", sample_rate, nsamples, expected_samples);
+            let freq = crossings as f64 / 2.0 / (nsamples as f64 / sample_rate as f64);
+            assert!((freq - 1000.0).abs() < 5.0, "{}: {} Hz", sample_rate, freq);
+        }
+    }
+
+    #[test]
+    fn band_limited_fade_out_works() {
+        use spectrusty_core::audio::BlepAmpFilter;
+        let mut blep = BlepAmpFilter::new(1.0, BandLimited::<f32>::new(1));
+        blep.ensure_frame_time(44100, 3_500_000.0, 69888, 0);
+        blep.add_step(0, 0, 0.5);
+        blep.end_frame(69888);
+        let last = blep.sum_iter::<f32>(0).last().unwrap();
+        assert!(last > 0.1, "{}", last);
+        blep.next_frame();
+        blep.fade_out();
+        blep.end_frame(69888);
+        let samples: Vec<f32> = blep.sum_iter(0).collect();
+        // no jump from the level the previous frame has ended with
+        assert!((samples[0] - last).abs() < 0.01, "{} {}", samples[0], last);
+        // the level is descending gradually
+        for pair in samples.windows(2) {
+            assert!(pair[1] <= pair[0] + 1e-5 && pair[0] - pair[1] < 0.01, "{:?}", pair);
+        }
+        assert!(samples.last().unwrap().abs() < 1e-3);
+        blep.next_frame();
+        // and stays silent
+        blep.end_frame(69888);
+        assert!(blep.sum_iter::<f32>(0).all(|s| s.abs() < 1e-3));
+    }
+
+    #[test]
+    fn blep_stereo_channels_work() {
+        use spectrusty_core::audio::BlepStereo;
+        use super::ext::BandLimitedExt;
+        let mut blep = BlepStereo::new(0.5, BandLimited::<f32>::new(2));
+        blep.ensure_frame_time(44100, 3_500_000.0, 69888, 0);
+        blep.add_step(0, 1000, 0.5);
+        let nsamples = blep.end_frame(69888);
+        assert_eq!(880, nsamples);
+        let mut left = vec![0.0f32; nsamples];
+        let mut right = vec![0.0f32; nsamples];
+        blep.render_audio_channel(&mut left, 0);
+        blep.render_audio_channel(&mut right, 1);
+        assert!(left.iter().any(|&s| s > 0.4));
+        assert!(right.iter().all(|&s| s == 0.0));
+        let mut interleaved = vec![0.0f32; nsamples * 2];
+        blep.render_audio_map_interleaved(&mut interleaved, 2, &[0, 1]);
+        for (frame, (l, r)) in interleaved.chunks(2).zip(left.iter().zip(right.iter())) {
+            assert_eq!(frame, [*l, *r]);
+        }
+        blep.next_frame();
+        // a centered channel
+        blep.reset();
+        blep.add_step(2, 1000, 0.5);
+        let nsamples = blep.end_frame(69888);
+        let (left, right) = (&mut left[..nsamples], &mut right[..nsamples]);
+        blep.render_audio_channel(left, 0);
+        blep.render_audio_channel(right, 1);
+        assert!(left.iter().any(|&s| s > 0.2 && s < 0.3));
+        assert_eq!(left, right);
+    }
 }
@@ -212,7 +212,7 @@ impl<T, O, S> BandLimitedExt<T, S> for BandLimited<T, O>
 }
 
 impl<T> Blep for BandLimitedAny<T>
-where T: SampleDelta + MulNorm
+where T: SampleDelta + MulNorm + FromSample<f32>
 {
     type SampleDelta = T;
 
@@ -229,7 +229,12 @@ where T: SampleDelta + MulNorm
     #[inline]
     fn add_step(&mut self, channel: usize, timestamp: FTs, delta: T) {
         implement_any! { self, b, b.add_step(channel, timestamp, delta) }
-    }    
+    }
+
+    #[inline]
+    fn fade_out(&mut self) {
+        implement_any! { self, b, b.fade_out_frame() }
+    }
 }
 
 impl<T, S> BandLimitedExt<T, S> for BandLimitedAny<T>
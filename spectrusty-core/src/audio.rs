@@ -8,8 +8,10 @@
 //! # Audio API.
 mod sample;
 
+use core::fmt;
 use core::ops::{Deref, DerefMut};
 use core::marker::PhantomData;
+use core::str::FromStr;
 
 use crate::clock::{VFrameTs, VideoTs};
 use crate::video::VideoFrame;
@@ -93,6 +95,13 @@ pub trait Blep {
     ///
     /// The implementation may panic if this requirement is not uphold.
     fn end_frame(&mut self, timestamp: FTs) -> usize;
+    /// Fades out the output level of all audio channels down to silence during the current frame.
+    ///
+    /// This may be used to avoid audible clicks when the source of the pulse steps is being replaced,
+    /// e.g. after the emulated hardware has been changed.
+    ///
+    /// This method should be called before [Blep::end_frame]. The default implementation does nothing.
+    fn fade_out(&mut self) {}
 }
 
 /// A wrapper [Blep] implementation that filters pulses' ∆ amplitude before sending them to the
@@ -128,6 +137,52 @@ pub struct BlepStereo<B: Blep> {
     pub blep: B,
 }
 
+/// The number of audio sources handled by [BlepMixer].
+pub const MIXER_SOURCES: usize = 4;
+
+/// An audio source that can be muted or isolated by [BlepMixer].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MixerSource {
+    /// The AY-3-891x channel A.
+    AyChannelA = 0,
+    /// The AY-3-891x channel B.
+    AyChannelB = 1,
+    /// The AY-3-891x channel C.
+    AyChannelC = 2,
+    /// The EAR/MIC output (the beeper).
+    Beeper = 3,
+}
+
+/// A wrapper [Blep] implementation that can mute or isolate (solo) individual audio sources.
+///
+/// The sources should be rendered to the [BlepMixer] channels given by [MixerSource::channel],
+/// e.g. the AY-3-891x channels to [BlepMixer::AY_CHANNELS] and the EAR/MIC output to
+/// [BlepMixer::BEEPER_CHANNEL]. The steps of each source are then redirected to the downstream channel
+/// given in `routes` unless the source is silenced. Steps added to the channels from [MIXER_SOURCES]
+/// and above are passed to the downstream channel `channel - MIXER_SOURCES`.
+///
+/// The muted sources contribute no steps, except a single step bringing their output level back to 0.
+/// If any of the sources is soloed, only the soloed sources are audible.
+/// ```text
+/// BlepMixer channel       Blep impl channel
+///     0 (AY A) --- mute/solo ---> routes[0]
+///     1 (AY B) --- mute/solo ---> routes[1]
+///     2 (AY C) --- mute/solo ---> routes[2]
+///     3 (EAR)  --- mute/solo ---> routes[3]
+///  >= 4 ------------------------> channel - 4
+/// ```
+#[derive(Debug)]
+pub struct BlepMixer<B: Blep> {
+    /// The downstream [Blep] channels of each [MixerSource].
+    pub routes: [usize; MIXER_SOURCES],
+    /// A downstream [Blep] implementation.
+    pub blep: B,
+    muted: [bool; MIXER_SOURCES],
+    solo: [bool; MIXER_SOURCES],
+    levels: [B::SampleDelta; MIXER_SOURCES],
+    out_levels: [B::SampleDelta; MIXER_SOURCES],
+}
+
 /// A digital level to a sample amplitude conversion trait.
 pub trait AmpLevels<T: Copy> {
     /// This method should return the appropriate digital sample amplitude for the given `level`.
@@ -215,39 +270,131 @@ pub const AMPS_EAR_MIC_I16: [i16; 4] = [0x0bc3, 0x16d5, 0x7b27, 0x7fff];
 pub const AMPS_EAR_OUT_I16: [i16; 4] = [0x0bc3, 0x0bc3, 0x7fff, 0x7fff];
 pub const AMPS_EAR_IN_I16:  [i16; 2] = [0x0bc3, 0x16d5];
 
+pub const AMPS_EAR_MIC_ISS2: [f32; 4] = [0.39/3.79, 0.73/3.79, 3.66/3.79, 1.0];
+pub const AMPS_EAR_OUT_ISS2: [f32; 4] = [0.39/3.79, 0.39/3.79, 1.0, 1.0];
+pub const AMPS_EAR_IN_ISS2:  [f32; 2] = [0.39/3.79, 0.73/3.79];
+
+pub const AMPS_EAR_MIC_ISS2_F64: [f64; 4] = [0.39/3.79, 0.73/3.79, 3.66/3.79, 1.0];
+pub const AMPS_EAR_OUT_ISS2_F64: [f64; 4] = [0.39/3.79, 0.39/3.79, 1.0, 1.0];
+pub const AMPS_EAR_IN_ISS2_F64:  [f64; 2] = [0.39/3.79, 0.73/3.79];
+
+pub const AMPS_EAR_MIC_ISS2_I32: [i32; 4] = [0x0d2b_e7af, 0x18a7_83b7, 0x7b9c_081a, 0x7fff_ffff];
+pub const AMPS_EAR_OUT_ISS2_I32: [i32; 4] = [0x0d2b_e7af, 0x0d2b_e7af, 0x7fff_ffff, 0x7fff_ffff];
+pub const AMPS_EAR_IN_ISS2_I32:  [i32; 2] = [0x0d2b_e7af, 0x18a7_83b7];
+
+pub const AMPS_EAR_MIC_ISS2_I16: [i16; 4] = [0x0d2c, 0x18a7, 0x7b9b, 0x7fff];
+pub const AMPS_EAR_OUT_ISS2_I16: [i16; 4] = [0x0d2c, 0x0d2c, 0x7fff, 0x7fff];
+pub const AMPS_EAR_IN_ISS2_I16:  [i16; 2] = [0x0d2c, 0x18a7];
+
+/*
+The 128K presets scale the Issue 3 levels by 0.5, so the loudest beeper level matches half of the full
+volume of a single AY-3-8912 channel rendered with the normalized AY amplitude levels. This ratio is an
+estimate of the 128K mixing balance, not a measurement.
+*/
+pub const AMPS_EAR_MIC_128: [f32; 4] = [0.17/3.70, 0.33/3.70, 1.78/3.70, 0.5];
+pub const AMPS_EAR_OUT_128: [f32; 4] = [0.17/3.70, 0.17/3.70, 0.5, 0.5];
+pub const AMPS_EAR_IN_128:  [f32; 2] = [0.17/3.70, 0.33/3.70];
+
+pub const AMPS_EAR_MIC_128_F64: [f64; 4] = [0.17/3.70, 0.33/3.70, 1.78/3.70, 0.5];
+pub const AMPS_EAR_OUT_128_F64: [f64; 4] = [0.17/3.70, 0.17/3.70, 0.5, 0.5];
+pub const AMPS_EAR_IN_128_F64:  [f64; 2] = [0.17/3.70, 0.33/3.70];
+
+pub const AMPS_EAR_MIC_128_I32: [i32; 4] = [0x05e1_8e87, 0x0b6a_8d25, 0x3d94_109a, 0x3fff_ffff];
+pub const AMPS_EAR_OUT_128_I32: [i32; 4] = [0x05e1_8e87, 0x05e1_8e87, 0x3fff_ffff, 0x3fff_ffff];
+pub const AMPS_EAR_IN_128_I32:  [i32; 2] = [0x05e1_8e87, 0x0b6a_8d25];
+
+pub const AMPS_EAR_MIC_128_I16: [i16; 4] = [0x05e1, 0x0b6a, 0x3d93, 0x3fff];
+pub const AMPS_EAR_OUT_128_I16: [i16; 4] = [0x05e1, 0x05e1, 0x3fff, 0x3fff];
+pub const AMPS_EAR_IN_128_I16:  [i16; 2] = [0x05e1, 0x0b6a];
+
 /// Implements [AmpLevels] trait, useful when rendering combined EAR OUT and MIC OUT audio signal.
 ///
 /// Uses 2 lowest bits of a given `level`.
+///
+/// The levels are calibrated from the output voltages measured on the Issue 3 ZX Spectrum 48K,
+/// see [EarMicIss2Amps4] for the Issue 2 levels.
 #[derive(Clone, Default, Debug)]
 pub struct EarMicAmps4<T>(PhantomData<T>);
 /// Implements [AmpLevels] trait, useful when rendering EAR OUT audio ignoring MIC OUT signal.
 ///
 /// Uses 2 lowest bits of a given `level`, but ignores the lowest bit.
+///
+/// The levels are calibrated from the output voltages measured on the Issue 3 ZX Spectrum 48K,
+/// see [EarOutIss2Amps4] for the Issue 2 levels.
 #[derive(Clone, Default, Debug)]
 pub struct EarOutAmps4<T>(PhantomData<T>);
 /// Implements [AmpLevels] trait, useful when rendering EAR IN audio.
 ///
 /// Uses only one bit of a given `level`.
+///
+/// The levels are calibrated from the output voltages measured on the Issue 3 ZX Spectrum 48K,
+/// see [EarInIss2Amps2] for the Issue 2 levels.
 #[derive(Clone, Default, Debug)]
 pub struct EarInAmps2<T>(PhantomData<T>);
+/// Implements [AmpLevels] trait like [EarMicAmps4], but with the levels calibrated from the output
+/// voltages measured on the Issue 2 ZX Spectrum 48K.
+#[derive(Clone, Default, Debug)]
+pub struct EarMicIss2Amps4<T>(PhantomData<T>);
+/// Implements [AmpLevels] trait like [EarOutAmps4], but with the levels calibrated from the output
+/// voltages measured on the Issue 2 ZX Spectrum 48K.
+#[derive(Clone, Default, Debug)]
+pub struct EarOutIss2Amps4<T>(PhantomData<T>);
+/// Implements [AmpLevels] trait like [EarInAmps2], but with the levels calibrated from the output
+/// voltages measured on the Issue 2 ZX Spectrum 48K.
+#[derive(Clone, Default, Debug)]
+pub struct EarInIss2Amps2<T>(PhantomData<T>);
+/// Implements [AmpLevels] trait like [EarMicAmps4], but with the levels balanced against the AY-3-8912
+/// channels of the ZX Spectrum 128K, rendered with the normalized AY amplitude levels.
+///
+/// The loudest beeper level is half of the full volume of a single AY channel.
+#[derive(Clone, Default, Debug)]
+pub struct EarMic128Amps4<T>(PhantomData<T>);
+/// Implements [AmpLevels] trait like [EarOutAmps4], but with the levels balanced against the AY-3-8912
+/// channels of the ZX Spectrum 128K, see [EarMic128Amps4].
+#[derive(Clone, Default, Debug)]
+pub struct EarOut128Amps4<T>(PhantomData<T>);
+/// Implements [AmpLevels] trait like [EarInAmps2], but with the levels balanced against the AY-3-8912
+/// channels of the ZX Spectrum 128K, see [EarMic128Amps4].
+#[derive(Clone, Default, Debug)]
+pub struct EarIn128Amps2<T>(PhantomData<T>);
+
+/// The named EAR/MIC amplitude level presets, selectable at run time.
+///
+/// The presets can be parsed from and converted to their names, see [AmpLevelsPreset::name].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum AmpLevelsPreset {
+    /// The Issue 3 ZX Spectrum 48K levels: [EarMicAmps4], [EarOutAmps4] and [EarInAmps2].
+    #[default]
+    Issue3,
+    /// The Issue 2 ZX Spectrum 48K levels: [EarMicIss2Amps4], [EarOutIss2Amps4] and [EarInIss2Amps2].
+    Issue2,
+    /// The ZX Spectrum 128K levels mixed with the AY-3-8912 output: [EarMic128Amps4], [EarOut128Amps4]
+    /// and [EarIn128Amps2].
+    Zx128k,
+}
+
+/// The error type returned when parsing an [AmpLevelsPreset] name failed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseAmpLevelsPresetError;
 
 macro_rules! impl_amp_levels {
-    ($([$ty:ty, $ear_mic:ident, $ear_out:ident, $ear_in:ident]),*) => { $(
-        impl AmpLevels<$ty> for EarMicAmps4<$ty> {
+    ([$ear_mic_amps:ident, $ear_out_amps:ident, $ear_in_amps:ident]:
+     $([$ty:ty, $ear_mic:ident, $ear_out:ident, $ear_in:ident]),*) => { $(
+        impl AmpLevels<$ty> for $ear_mic_amps<$ty> {
             #[inline(always)]
             fn amp_level(level: u32) -> $ty {
                 $ear_mic[(level & 3) as usize]
             }
         }
 
-        impl AmpLevels<$ty> for EarOutAmps4<$ty> {
+        impl AmpLevels<$ty> for $ear_out_amps<$ty> {
             #[inline(always)]
             fn amp_level(level: u32) -> $ty {
                 $ear_out[(level & 3) as usize]
             }
         }
 
-        impl AmpLevels<$ty> for EarInAmps2<$ty> {
+        impl AmpLevels<$ty> for $ear_in_amps<$ty> {
             #[inline(always)]
             fn amp_level(level: u32) -> $ty {
                 $ear_in[(level & 1) as usize]
@@ -255,10 +402,95 @@ macro_rules! impl_amp_levels {
         }
     )* };
 }
-impl_amp_levels!([f32, AMPS_EAR_MIC, AMPS_EAR_OUT, AMPS_EAR_IN],
+impl_amp_levels!([EarMicAmps4, EarOutAmps4, EarInAmps2]:
+                 [f32, AMPS_EAR_MIC, AMPS_EAR_OUT, AMPS_EAR_IN],
                  [f64, AMPS_EAR_MIC_F64, AMPS_EAR_OUT_F64, AMPS_EAR_IN_F64],
                  [i32, AMPS_EAR_MIC_I32, AMPS_EAR_OUT_I32, AMPS_EAR_IN_I32],
                  [i16, AMPS_EAR_MIC_I16, AMPS_EAR_OUT_I16, AMPS_EAR_IN_I16]);
+impl_amp_levels!([EarMicIss2Amps4, EarOutIss2Amps4, EarInIss2Amps2]:
+                 [f32, AMPS_EAR_MIC_ISS2, AMPS_EAR_OUT_ISS2, AMPS_EAR_IN_ISS2],
+                 [f64, AMPS_EAR_MIC_ISS2_F64, AMPS_EAR_OUT_ISS2_F64, AMPS_EAR_IN_ISS2_F64],
+                 [i32, AMPS_EAR_MIC_ISS2_I32, AMPS_EAR_OUT_ISS2_I32, AMPS_EAR_IN_ISS2_I32],
+                 [i16, AMPS_EAR_MIC_ISS2_I16, AMPS_EAR_OUT_ISS2_I16, AMPS_EAR_IN_ISS2_I16]);
+
+impl_amp_levels!([EarMic128Amps4, EarOut128Amps4, EarIn128Amps2]:
+                 [f32, AMPS_EAR_MIC_128, AMPS_EAR_OUT_128, AMPS_EAR_IN_128],
+                 [f64, AMPS_EAR_MIC_128_F64, AMPS_EAR_OUT_128_F64, AMPS_EAR_IN_128_F64],
+                 [i32, AMPS_EAR_MIC_128_I32, AMPS_EAR_OUT_128_I32, AMPS_EAR_IN_128_I32],
+                 [i16, AMPS_EAR_MIC_128_I16, AMPS_EAR_OUT_128_I16, AMPS_EAR_IN_128_I16]);
+
+impl AmpLevelsPreset {
+    /// All the presets.
+    pub const ALL: [AmpLevelsPreset; 3] = [AmpLevelsPreset::Issue3, AmpLevelsPreset::Issue2,
+                                           AmpLevelsPreset::Zx128k];
+    /// Returns the name of the preset: `"issue3"`, `"issue2"` or `"128k"`.
+    pub fn name(self) -> &'static str {
+        match self {
+            AmpLevelsPreset::Issue3 => "issue3",
+            AmpLevelsPreset::Issue2 => "issue2",
+            AmpLevelsPreset::Zx128k => "128k",
+        }
+    }
+    /// Returns the amplitude of the combined EAR/MIC output `level` of the preset.
+    ///
+    /// See [EarMicOutAudioFrame::render_earmic_out_audio_frame] for the `level` values.
+    pub fn earmic_amp_level<T: Copy>(self, level: u32) -> T
+        where EarMicAmps4<T>: AmpLevels<T>,
+              EarMicIss2Amps4<T>: AmpLevels<T>,
+              EarMic128Amps4<T>: AmpLevels<T>
+    {
+        match self {
+            AmpLevelsPreset::Issue3 => EarMicAmps4::<T>::amp_level(level),
+            AmpLevelsPreset::Issue2 => EarMicIss2Amps4::<T>::amp_level(level),
+            AmpLevelsPreset::Zx128k => EarMic128Amps4::<T>::amp_level(level),
+        }
+    }
+    /// Renders the EAR/MIC output of `audio` with the combined EAR/MIC levels of the preset.
+    ///
+    /// See [EarMicOutAudioFrame::render_earmic_out_audio_frame].
+    pub fn render_earmic_out_audio_frame<A, B>(self, audio: &A, blep: &mut B, channel: usize)
+        where A: EarMicOutAudioFrame<B>,
+              B: Blep,
+              EarMicAmps4<B::SampleDelta>: AmpLevels<B::SampleDelta>,
+              EarMicIss2Amps4<B::SampleDelta>: AmpLevels<B::SampleDelta>,
+              EarMic128Amps4<B::SampleDelta>: AmpLevels<B::SampleDelta>
+    {
+        match self {
+            AmpLevelsPreset::Issue3 => audio.render_earmic_out_audio_frame::<EarMicAmps4<_>>(blep, channel),
+            AmpLevelsPreset::Issue2 => audio.render_earmic_out_audio_frame::<EarMicIss2Amps4<_>>(blep, channel),
+            AmpLevelsPreset::Zx128k => audio.render_earmic_out_audio_frame::<EarMic128Amps4<_>>(blep, channel),
+        }
+    }
+}
+
+impl fmt::Display for AmpLevelsPreset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::error::Error for ParseAmpLevelsPresetError {}
+
+impl fmt::Display for ParseAmpLevelsPresetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("unrecognized amplitude levels preset")
+    }
+}
+
+impl FromStr for AmpLevelsPreset {
+    type Err = ParseAmpLevelsPresetError;
+    /// Parses the preset name using case insensitive matching.
+    ///
+    /// Apart from [AmpLevelsPreset::name], `"48k"` is recognized as [AmpLevelsPreset::Issue3].
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        if name.eq_ignore_ascii_case("48k") {
+            return Ok(AmpLevelsPreset::Issue3)
+        }
+        AmpLevelsPreset::ALL.iter().copied()
+            .find(|preset| name.eq_ignore_ascii_case(preset.name()))
+            .ok_or(ParseAmpLevelsPresetError)
+    }
+}
 
 impl<B: Blep> BlepAmpFilter<B> {
     pub fn build(filter: B::SampleDelta) -> impl FnOnce(B) -> Self
@@ -281,6 +513,67 @@ impl<B: Blep> BlepStereo<B> {
     }
 }
 
+impl MixerSource {
+    /// All the mixer sources, ordered by their channels.
+    pub const ALL: [MixerSource; MIXER_SOURCES] = [MixerSource::AyChannelA, MixerSource::AyChannelB,
+                                                   MixerSource::AyChannelC, MixerSource::Beeper];
+    /// Returns the [BlepMixer] channel of the source.
+    #[inline]
+    pub fn channel(self) -> usize {
+        self as usize
+    }
+}
+
+impl<B: Blep> BlepMixer<B> {
+    /// The [BlepMixer] channels of the AY-3-891x `[A, B, C]` channels.
+    pub const AY_CHANNELS: [usize; 3] = [0, 1, 2];
+    /// The [BlepMixer] channel of the EAR/MIC output.
+    pub const BEEPER_CHANNEL: usize = 3;
+
+    pub fn build(routes: [usize; MIXER_SOURCES]) -> impl FnOnce(B) -> Self {
+        move |blep| Self::new(routes, blep)
+    }
+
+    pub fn new(routes: [usize; MIXER_SOURCES], blep: B) -> Self {
+        BlepMixer {
+            routes,
+            blep,
+            muted: Default::default(),
+            solo: Default::default(),
+            levels: Default::default(),
+            out_levels: Default::default()
+        }
+    }
+    /// Mutes or unmutes the given audio `source`.
+    pub fn set_channel_mute(&mut self, source: MixerSource, mute: bool) {
+        self.muted[source.channel()] = mute;
+    }
+    /// Returns `true` if the given audio `source` is muted.
+    pub fn is_channel_muted(&self, source: MixerSource) -> bool {
+        self.muted[source.channel()]
+    }
+    /// Isolates the given audio `source` or cancels its isolation.
+    ///
+    /// While any of the sources is soloed, the other ones are silenced.
+    pub fn set_channel_solo(&mut self, source: MixerSource, solo: bool) {
+        self.solo[source.channel()] = solo;
+    }
+    /// Returns `true` if the given audio `source` is soloed.
+    pub fn is_channel_solo(&self, source: MixerSource) -> bool {
+        self.solo[source.channel()]
+    }
+    /// Returns `true` if the given audio `source` is currently audible.
+    pub fn is_channel_audible(&self, source: MixerSource) -> bool {
+        let index = source.channel();
+        if self.solo.contains(&true) {
+            self.solo[index]
+        }
+        else {
+            !self.muted[index]
+        }
+    }
+}
+
 impl<B: Blep> Deref for BlepAmpFilter<B> {
     type Target = B;
     fn deref(&self) -> &B {
@@ -307,6 +600,19 @@ impl<B: Blep> DerefMut for BlepStereo<B> {
     }
 }
 
+impl<B: Blep> Deref for BlepMixer<B> {
+    type Target = B;
+    fn deref(&self) -> &B {
+        &self.blep
+    }
+}
+
+impl<B: Blep> DerefMut for BlepMixer<B> {
+    fn deref_mut(&mut self) -> &mut B {
+        &mut self.blep
+    }
+}
+
 impl<B: Blep + ?Sized> Blep for &mut B {
     type SampleDelta = B::SampleDelta;
 
@@ -322,6 +628,10 @@ impl<B: Blep + ?Sized> Blep for &mut B {
     fn add_step(&mut self, channel: usize, timestamp: FTs, delta: Self::SampleDelta) {
         (*self).add_step(channel, timestamp, delta)
     }
+    #[inline]
+    fn fade_out(&mut self) {
+        (*self).fade_out()
+    }
 }
 
 impl<B> Blep for BlepAmpFilter<B>
@@ -341,6 +651,10 @@ impl<B> Blep for BlepAmpFilter<B>
     fn add_step(&mut self, channel: usize, timestamp: FTs, delta: Self::SampleDelta) {
         self.blep.add_step(channel, timestamp, delta.mul_norm(self.filter))
     }
+    #[inline]
+    fn fade_out(&mut self) {
+        self.blep.fade_out()
+    }
 }
 
 impl<B> Blep for BlepStereo<B>
@@ -367,6 +681,49 @@ impl<B> Blep for BlepStereo<B>
             }
         }
     }
+    #[inline]
+    fn fade_out(&mut self) {
+        self.blep.fade_out()
+    }
+}
+
+impl<B> Blep for BlepMixer<B>
+    where B: Blep, B::SampleDelta: MulNorm + SampleDelta
+{
+    type SampleDelta = B::SampleDelta;
+
+    #[inline]
+    fn ensure_frame_time(&mut self, sample_rate: u32, ts_rate: f64, frame_ts: FTs, margin_ts: FTs) {
+        self.blep.ensure_frame_time(sample_rate, ts_rate, frame_ts, margin_ts)
+    }
+    #[inline]
+    fn end_frame(&mut self, timestamp: FTs) -> usize {
+        self.blep.end_frame(timestamp)
+    }
+    #[inline]
+    fn add_step(&mut self, channel: usize, timestamp: FTs, delta: B::SampleDelta) {
+        match MixerSource::ALL.get(channel) {
+            Some(&source) => {
+                let level = self.levels[channel].saturating_add(delta);
+                self.levels[channel] = level;
+                let target = if self.is_channel_audible(source) {
+                    level
+                }
+                else {
+                    B::SampleDelta::default()
+                };
+                if let Some(delta) = self.out_levels[channel].sample_delta(target) {
+                    self.blep.add_step(self.routes[channel], timestamp, delta);
+                    self.out_levels[channel] = target;
+                }
+            }
+            None => self.blep.add_step(channel - MIXER_SOURCES, timestamp, delta)
+        }
+    }
+    #[inline]
+    fn fade_out(&mut self) {
+        self.blep.fade_out()
+    }
 }
 
 /// A helper method for rendering square-wave audio from slices containing updates of audio
@@ -431,3 +788,79 @@ pub fn render_audio_frame_ts<VL,L,A,T>(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn levels<L: AmpLevels<T>, T: Copy>(n: u32) -> Vec<T> {
+        (0..n).map(L::amp_level).collect()
+    }
+
+    fn assert_levels_match(levels: &[f64], levels_f32: &[f32], levels_i32: &[i32], levels_i16: &[i16]) {
+        for (((&f64l, &f32l), &i32l), &i16l) in levels.iter().zip(levels_f32).zip(levels_i32).zip(levels_i16) {
+            assert!((f64l - f32l as f64).abs() < 1e-6);
+            assert!((f64l - i32l as f64 / i32::MAX as f64).abs() < 1e-6);
+            assert!((f64l - i16l as f64 / i16::MAX as f64).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn ear_mic_amp_levels_work() {
+        // output voltages of EAR+MIC combinations: (EAR, MIC) = (0,0), (0,1), (1,0), (1,1)
+        const ISS2_V: [f64; 4] = [0.39, 0.73, 3.66, 3.79];
+        const ISS3_V: [f64; 4] = [0.34, 0.66, 3.56, 3.70];
+        for (volts, ear_mic, ear_out, ear_in) in [
+            (ISS3_V, levels::<EarMicAmps4<f64>, _>(4), levels::<EarOutAmps4<f64>, _>(4), levels::<EarInAmps2<f64>, _>(2)),
+            (ISS2_V, levels::<EarMicIss2Amps4<f64>, _>(4), levels::<EarOutIss2Amps4<f64>, _>(4), levels::<EarInIss2Amps2<f64>, _>(2))
+        ] {
+            assert!(ear_mic.windows(2).all(|w| w[0] < w[1]));
+            for (&level, &v) in ear_mic.iter().zip(volts.iter()) {
+                assert!((level - v / volts[3]).abs() < 1e-9);
+            }
+            assert_eq!(ear_out, [ear_mic[0], ear_mic[0], ear_mic[3], ear_mic[3]]);
+            assert_eq!(ear_in, ear_mic[..2]);
+        }
+        // an Issue 2 MIC signal is louder than on Issue 3
+        assert!(EarMicIss2Amps4::<f32>::amp_level(1) > EarMicAmps4::<f32>::amp_level(1));
+        assert_levels_match(&AMPS_EAR_MIC_F64, &AMPS_EAR_MIC, &AMPS_EAR_MIC_I32, &AMPS_EAR_MIC_I16);
+        assert_levels_match(&AMPS_EAR_OUT_F64, &AMPS_EAR_OUT, &AMPS_EAR_OUT_I32, &AMPS_EAR_OUT_I16);
+        assert_levels_match(&AMPS_EAR_IN_F64, &AMPS_EAR_IN, &AMPS_EAR_IN_I32, &AMPS_EAR_IN_I16);
+        assert_levels_match(&AMPS_EAR_MIC_ISS2_F64, &AMPS_EAR_MIC_ISS2, &AMPS_EAR_MIC_ISS2_I32, &AMPS_EAR_MIC_ISS2_I16);
+        assert_levels_match(&AMPS_EAR_OUT_ISS2_F64, &AMPS_EAR_OUT_ISS2, &AMPS_EAR_OUT_ISS2_I32, &AMPS_EAR_OUT_ISS2_I16);
+        assert_levels_match(&AMPS_EAR_IN_ISS2_F64, &AMPS_EAR_IN_ISS2, &AMPS_EAR_IN_ISS2_I32, &AMPS_EAR_IN_ISS2_I16);
+        assert_levels_match(&AMPS_EAR_MIC_128_F64, &AMPS_EAR_MIC_128, &AMPS_EAR_MIC_128_I32, &AMPS_EAR_MIC_128_I16);
+        assert_levels_match(&AMPS_EAR_OUT_128_F64, &AMPS_EAR_OUT_128, &AMPS_EAR_OUT_128_I32, &AMPS_EAR_OUT_128_I16);
+        assert_levels_match(&AMPS_EAR_IN_128_F64, &AMPS_EAR_IN_128, &AMPS_EAR_IN_128_I32, &AMPS_EAR_IN_128_I16);
+    }
+
+    #[test]
+    fn ear_mic_128_amp_levels_work() {
+        let ear_mic = levels::<EarMic128Amps4<f64>, _>(4);
+        assert!(ear_mic.windows(2).all(|w| w[0] < w[1]));
+        // the Issue 3 levels at half of the full AY channel volume
+        for (&level, &level48) in ear_mic.iter().zip(levels::<EarMicAmps4<f64>, _>(4).iter()) {
+            assert!((level - level48 / 2.0).abs() < 1e-9);
+        }
+        assert_eq!(levels::<EarOut128Amps4<f64>, _>(4), [ear_mic[0], ear_mic[0], ear_mic[3], ear_mic[3]]);
+        assert_eq!(levels::<EarIn128Amps2<f64>, _>(2), ear_mic[..2]);
+    }
+
+    #[test]
+    fn amp_levels_preset_works() {
+        assert_eq!(AmpLevelsPreset::default(), AmpLevelsPreset::Issue3);
+        for preset in AmpLevelsPreset::ALL {
+            assert_eq!(preset.name().parse::<AmpLevelsPreset>(), Ok(preset));
+            assert_eq!(preset.name().to_uppercase().parse::<AmpLevelsPreset>(), Ok(preset));
+            assert_eq!(preset.to_string(), preset.name());
+        }
+        assert_eq!("48K".parse::<AmpLevelsPreset>(), Ok(AmpLevelsPreset::Issue3));
+        assert_eq!("issue1".parse::<AmpLevelsPreset>(), Err(ParseAmpLevelsPresetError));
+        assert_eq!("".parse::<AmpLevelsPreset>(), Err(ParseAmpLevelsPresetError));
+        for level in 0..4 {
+            assert_eq!(AmpLevelsPreset::Issue3.earmic_amp_level::<f32>(level), EarMicAmps4::<f32>::amp_level(level));
+            assert_eq!(AmpLevelsPreset::Issue2.earmic_amp_level::<i16>(level), EarMicIss2Amps4::<i16>::amp_level(level));
+            assert_eq!(AmpLevelsPreset::Zx128k.earmic_amp_level::<i32>(level), EarMic128Amps4::<i32>::amp_level(level));
+        }
+    }
+}
@@ -18,6 +18,8 @@ use serde::{Serialize, Deserialize};
 mod dynbus;
 
 use crate::clock::VFrameTs;
+use crate::memory::{ZxMemory, ScreenArray};
+use crate::z80emu::{Cpu, Prefix, StkReg16};
 
 pub use dynbus::*;
 
@@ -129,6 +131,78 @@ pub trait BusDevice: Debug {
     fn next_frame(&mut self, eof_timestamp: Self::Timestamp) {
         self.next_device_mut().next_frame(eof_timestamp)
     }
+    /// This method is called by the control unit just before each [BusDevice::read_io] with the
+    /// screen memory being currently displayed.
+    ///
+    /// Allows devices sensing the light emitted by the screen, e.g. light guns, to sample the screen
+    /// data under the video beam at the given `timestamp`.
+    ///
+    /// Default implementation forwards this call to the next device.
+    ///
+    /// **NOTE**: Implementations should always forward this call down the chain after optionally applying it
+    /// to `self`.
+    #[inline(always)]
+    fn probe_screen(&mut self, screen: &ScreenArray, timestamp: Self::Timestamp) {
+        self.next_device_mut().probe_screen(screen, timestamp)
+    }
+    /// Returns `true` if the device, or any device in this chain, is a bus master requesting the control
+    /// of the bus, e.g. a DMA controller performing a transfer.
+    ///
+    /// The control unit checks this after each [BusDevice::write_io] and performs the bus cycles requested
+    /// with [BusDevice::bus_request] before executing the next instruction.
+    ///
+    /// Default implementation forwards this call to the next device.
+    #[inline(always)]
+    fn is_bus_requested(&self) -> bool {
+        self.next_device_ref().is_bus_requested()
+    }
+    /// Returns the next bus cycle to be performed by the control unit on behalf of the bus master device and
+    /// the cycle length in T-states.
+    ///
+    /// Returns `None` if the bus is being released. If [BusDevice::is_bus_requested] still returns `true`,
+    /// the control unit executes a single instruction before asking for the next cycle.
+    ///
+    /// Default implementation forwards this call to the next device.
+    #[inline(always)]
+    fn bus_request(&mut self) -> Option<(BusCycle, u8)> {
+        self.next_device_mut().bus_request()
+    }
+    /// This method is called by the control unit with the `data` read in the [BusCycle::ReadMemory] or
+    /// [BusCycle::ReadIo] cycle requested with [BusDevice::bus_request].
+    ///
+    /// Default implementation forwards this call to the next device.
+    ///
+    /// **NOTE**: Implementations should always forward this call down the chain after optionally applying it
+    /// to `self`.
+    #[inline(always)]
+    fn bus_read_data(&mut self, data: u8) {
+        self.next_device_mut().bus_read_data(data)
+    }
+    /// Returns `true` if the device, or any device in this chain, traps the execution of the ROM routines
+    /// with [BusDevice::rom_trap].
+    ///
+    /// While this returns `true`, the control unit executes instructions one by one, calling
+    /// [BusDevice::rom_trap] before each instruction.
+    ///
+    /// Default implementation forwards this call to the next device.
+    #[inline(always)]
+    fn has_rom_traps(&self) -> bool {
+        self.next_device_ref().has_rom_traps()
+    }
+    /// This method is called by the control unit before executing the instruction at the program counter
+    /// provided by `access` if [BusDevice::has_rom_traps] returns `true`.
+    ///
+    /// Returns `true` if the trap has been serviced instantly, in which case the device should have modified
+    /// the CPU registers, e.g. the program counter, so the trapped routine is skipped. Otherwise the instruction
+    /// is executed as usual.
+    ///
+    /// Default implementation forwards this call to the next device.
+    ///
+    /// **NOTE**: Implementations should only forward this call if the trap has not been serviced by `self`.
+    #[inline(always)]
+    fn rom_trap(&mut self, access: &mut dyn RomTrapAccess) -> bool {
+        self.next_device_mut().rom_trap(access)
+    }
     /// This method is called by the control unit during an I/O read cycle.
     ///
     /// Default implementation forwards this call to the next device.
@@ -139,6 +213,8 @@ pub trait BusDevice: Debug {
     /// or if not all bits are modified by the implementing device. In the latter case the result from the
     /// forwarded call should be logically `ANDed` with the result of reading from this device and if the
     /// upstream result is `None` the result should be returned with all unused bits set to 1.
+    /// If the device doesn't respond to the `port`, the forwarded result must be returned unmodified,
+    /// so the control unit can substitute `None` with the floating bus value.
     #[inline(always)]
     fn read_io(&mut self, port: u16, timestamp: Self::Timestamp) -> Option<(u8, Option<NonZeroU16>)> {
         self.next_device_mut().read_io(port, timestamp)
@@ -198,6 +274,30 @@ impl<D: BusDevice> BusDevice for Box<D> {
         (**self).next_frame(eof_timestamp)
     }
     #[inline]
+    fn probe_screen(&mut self, screen: &ScreenArray, timestamp: Self::Timestamp) {
+        (**self).probe_screen(screen, timestamp)
+    }
+    #[inline]
+    fn is_bus_requested(&self) -> bool {
+        (**self).is_bus_requested()
+    }
+    #[inline]
+    fn bus_request(&mut self) -> Option<(BusCycle, u8)> {
+        (**self).bus_request()
+    }
+    #[inline]
+    fn bus_read_data(&mut self, data: u8) {
+        (**self).bus_read_data(data)
+    }
+    #[inline]
+    fn has_rom_traps(&self) -> bool {
+        (**self).has_rom_traps()
+    }
+    #[inline]
+    fn rom_trap(&mut self, access: &mut dyn RomTrapAccess) -> bool {
+        (**self).rom_trap(access)
+    }
+    #[inline]
     fn read_io(&mut self, port: u16, timestamp: Self::Timestamp) -> Option<(u8, Option<NonZeroU16>)> {
         (**self).read_io(port, timestamp)
     }
@@ -207,6 +307,94 @@ impl<D: BusDevice> BusDevice for Box<D> {
     }
 }
 
+/// A bus cycle requested by a bus master device with [BusDevice::bus_request].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BusCycle {
+    /// Reads a byte from the memory at the given address.
+    ReadMemory(u16),
+    /// Writes a byte to the memory at the given address.
+    WriteMemory(u16, u8),
+    /// Reads a byte from the given I/O port.
+    ReadIo(u16),
+    /// Writes a byte to the given I/O port.
+    WriteIo(u16, u8)
+}
+
+/// An access to the CPU registers and the memory given to the bus devices servicing the ROM traps
+/// with [BusDevice::rom_trap].
+pub trait RomTrapAccess {
+    /// Returns the current value of the program counter.
+    fn get_pc(&self) -> u16;
+    /// Sets the current value of the program counter.
+    fn set_pc(&mut self, pc: u16);
+    /// Returns the current value of the stack pointer.
+    fn get_sp(&self) -> u16;
+    /// Sets the current value of the stack pointer.
+    fn set_sp(&mut self, sp: u16);
+    /// Returns the value of the 16-bit register pair.
+    fn get_reg16(&self, reg: StkReg16) -> u16;
+    /// Sets the value of the 16-bit register pair.
+    fn set_reg16(&mut self, reg: StkReg16, val: u16);
+    /// Returns the value of the `IX` or `IY` register.
+    fn get_index16(&self, prefix: Prefix) -> u16;
+    /// Sets the value of the `IX` or `IY` register.
+    fn set_index16(&mut self, prefix: Prefix, val: u16);
+    /// Reads a byte from the memory at the given address.
+    fn read_mem(&self, addr: u16) -> u8;
+    /// Writes a byte to the memory at the given address.
+    fn write_mem(&mut self, addr: u16, data: u8);
+}
+
+/// Implements [RomTrapAccess] for the given `cpu` and `memory`.
+#[derive(Debug)]
+pub struct CpuMemoryTrapAccess<'a, C, M> {
+    pub cpu: &'a mut C,
+    pub memory: &'a mut M
+}
+
+impl<'a, C: Cpu, M: ZxMemory> RomTrapAccess for CpuMemoryTrapAccess<'a, C, M> {
+    #[inline]
+    fn get_pc(&self) -> u16 {
+        self.cpu.get_pc()
+    }
+    #[inline]
+    fn set_pc(&mut self, pc: u16) {
+        self.cpu.set_pc(pc)
+    }
+    #[inline]
+    fn get_sp(&self) -> u16 {
+        self.cpu.get_sp()
+    }
+    #[inline]
+    fn set_sp(&mut self, sp: u16) {
+        self.cpu.set_sp(sp)
+    }
+    #[inline]
+    fn get_reg16(&self, reg: StkReg16) -> u16 {
+        self.cpu.get_reg16(reg)
+    }
+    #[inline]
+    fn set_reg16(&mut self, reg: StkReg16, val: u16) {
+        self.cpu.set_reg16(reg, val)
+    }
+    #[inline]
+    fn get_index16(&self, prefix: Prefix) -> u16 {
+        self.cpu.get_index16(prefix)
+    }
+    #[inline]
+    fn set_index16(&mut self, prefix: Prefix, val: u16) {
+        self.cpu.set_index16(prefix, val)
+    }
+    #[inline]
+    fn read_mem(&self, addr: u16) -> u8 {
+        self.memory.read(addr)
+    }
+    #[inline]
+    fn write_mem(&mut self, addr: u16, data: u8) {
+        self.memory.write(addr, data)
+    }
+}
+
 /// A helper trait for matching I/O port addresses.
 pub trait PortAddress: Debug {
     /// Relevant address bits should be set to 1.
@@ -261,6 +449,32 @@ impl<T> BusDevice for NullDevice<T> {
     #[inline(always)]
     fn next_frame(&mut self, _timestamp: Self::Timestamp) {}
 
+    #[inline(always)]
+    fn probe_screen(&mut self, _screen: &ScreenArray, _timestamp: Self::Timestamp) {}
+
+    #[inline(always)]
+    fn is_bus_requested(&self) -> bool {
+        false
+    }
+
+    #[inline(always)]
+    fn bus_request(&mut self) -> Option<(BusCycle, u8)> {
+        None
+    }
+
+    #[inline(always)]
+    fn bus_read_data(&mut self, _data: u8) {}
+
+    #[inline(always)]
+    fn has_rom_traps(&self) -> bool {
+        false
+    }
+
+    #[inline(always)]
+    fn rom_trap(&mut self, _access: &mut dyn RomTrapAccess) -> bool {
+        false
+    }
+
     #[inline(always)]
     fn read_io(&mut self, _port: u16, _timestamp: Self::Timestamp) -> Option<(u8, Option<NonZeroU16>)> {
         None
@@ -360,6 +574,46 @@ impl<D, N> BusDevice for OptionalBusDevice<D, N>
         self.next_device.next_frame(timestamp);
     }
     #[inline]
+    fn probe_screen(&mut self, screen: &ScreenArray, timestamp: Self::Timestamp) {
+        if let Some(device) = &mut self.device {
+            device.probe_screen(screen, timestamp);
+        }
+        self.next_device.probe_screen(screen, timestamp);
+    }
+    #[inline]
+    fn is_bus_requested(&self) -> bool {
+        matches!(&self.device, Some(device) if device.is_bus_requested()) ||
+            self.next_device.is_bus_requested()
+    }
+    #[inline]
+    fn bus_request(&mut self) -> Option<(BusCycle, u8)> {
+        match &mut self.device {
+            Some(device) if device.is_bus_requested() => device.bus_request(),
+            _ => self.next_device.bus_request()
+        }
+    }
+    #[inline]
+    fn bus_read_data(&mut self, data: u8) {
+        if let Some(device) = &mut self.device {
+            device.bus_read_data(data);
+        }
+        self.next_device.bus_read_data(data);
+    }
+    #[inline]
+    fn has_rom_traps(&self) -> bool {
+        matches!(&self.device, Some(device) if device.has_rom_traps()) ||
+            self.next_device.has_rom_traps()
+    }
+    #[inline]
+    fn rom_trap(&mut self, access: &mut dyn RomTrapAccess) -> bool {
+        if let Some(device) = &mut self.device {
+            if device.rom_trap(access) {
+                return true
+            }
+        }
+        self.next_device.rom_trap(access)
+    }
+    #[inline]
     fn read_io(&mut self, port: u16, timestamp: Self::Timestamp) -> Option<(u8, Option<NonZeroU16>)> {
         let dev_data = if let Some((data, ws)) = self.device
                             .as_mut()
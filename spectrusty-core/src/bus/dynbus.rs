@@ -20,7 +20,8 @@ mod serde;
 #[cfg(feature = "snapshot")]
 pub use self::serde::*;
 
-use super::{BusDevice, VFNullDevice, NullDevice};
+use crate::memory::ScreenArray;
+use super::{BusDevice, BusCycle, RomTrapAccess, VFNullDevice, NullDevice};
 
 /// A trait for dynamic bus devices, which currently includes methods from [Display] and [BusDevice].
 /// Devices implementing this trait can be used with a [DynamicBus].
@@ -300,6 +301,45 @@ impl<D> BusDevice for DynamicBus<D>
         self.bus.next_frame(timestamp);
     }
 
+    #[inline]
+    fn probe_screen(&mut self, screen: &ScreenArray, timestamp: Self::Timestamp) {
+        for dev in self.devices.iter_mut() {
+            dev.probe_screen(screen, timestamp);
+        }
+        self.bus.probe_screen(screen, timestamp);
+    }
+
+    #[inline]
+    fn is_bus_requested(&self) -> bool {
+        self.devices.iter().any(|dev| dev.is_bus_requested()) || self.bus.is_bus_requested()
+    }
+
+    #[inline]
+    fn bus_request(&mut self) -> Option<(BusCycle, u8)> {
+        match self.devices.iter_mut().find(|dev| dev.is_bus_requested()) {
+            Some(dev) => dev.bus_request(),
+            None => self.bus.bus_request()
+        }
+    }
+
+    #[inline]
+    fn bus_read_data(&mut self, data: u8) {
+        for dev in self.devices.iter_mut() {
+            dev.bus_read_data(data);
+        }
+        self.bus.bus_read_data(data);
+    }
+
+    #[inline]
+    fn has_rom_traps(&self) -> bool {
+        self.devices.iter().any(|dev| dev.has_rom_traps()) || self.bus.has_rom_traps()
+    }
+
+    #[inline]
+    fn rom_trap(&mut self, access: &mut dyn RomTrapAccess) -> bool {
+        self.devices.iter_mut().any(|dev| dev.rom_trap(access)) || self.bus.rom_trap(access)
+    }
+
     #[inline]
     fn read_io(&mut self, port: u16, timestamp: Self::Timestamp) -> Option<(u8, Option<NonZeroU16>)> {
         let mut bus_data = None;
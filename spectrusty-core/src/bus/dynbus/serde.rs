@@ -287,6 +287,30 @@ impl<S, D: BusDevice> BusDevice for DynamicSerdeBus<S, D>
         self.0.next_frame(timestamp)
     }
     #[inline(always)]
+    fn probe_screen(&mut self, screen: &ScreenArray, timestamp: Self::Timestamp) {
+        self.0.probe_screen(screen, timestamp)
+    }
+    #[inline(always)]
+    fn is_bus_requested(&self) -> bool {
+        self.0.is_bus_requested()
+    }
+    #[inline(always)]
+    fn bus_request(&mut self) -> Option<(BusCycle, u8)> {
+        self.0.bus_request()
+    }
+    #[inline(always)]
+    fn bus_read_data(&mut self, data: u8) {
+        self.0.bus_read_data(data)
+    }
+    #[inline(always)]
+    fn has_rom_traps(&self) -> bool {
+        self.0.has_rom_traps()
+    }
+    #[inline(always)]
+    fn rom_trap(&mut self, access: &mut dyn RomTrapAccess) -> bool {
+        self.0.rom_trap(access)
+    }
+    #[inline(always)]
     fn read_io(&mut self, port: u16, timestamp: Self::Timestamp) -> Option<(u8, Option<NonZeroU16>)> {
         self.0.read_io(port, timestamp)
     }
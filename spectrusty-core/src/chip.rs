@@ -16,8 +16,11 @@ use z80emu::{CpuDebug, Cpu, host::Result};
 use crate::bus::BusDevice;
 use crate::clock::FTs;
 use crate::memory::{ZxMemory, MemoryExtension};
+use crate::video::{Video, VideoFrame};
 
+mod breakpoints;
 mod flags;
+pub use breakpoints::*;
 pub use flags::*;
 
 /// A trait for directly accessing an emulated memory implementation and memory extensions.
@@ -120,6 +123,119 @@ pub trait ControlUnit {
             cpu: &mut C,
             debug: Option<F>
     ) -> Result<(), ()>;
+    /// Executes instructions on the `cpu` until at least `cycles` T-states have passed, regardless of
+    /// the frame boundaries.
+    ///
+    /// The last instruction is always executed completely, so the number of T-states that have actually
+    /// passed may exceed `cycles` by the duration of a single instruction. The frames are advanced
+    /// and interrupts are being requested as they would be by calling [ControlUnit::execute_single_step]
+    /// repeatedly.
+    ///
+    /// Returns the number of T-states that have actually passed.
+    fn run_cycles<C: Cpu>(&mut self, cpu: &mut C, cycles: u64) -> u64
+        where Self: FrameState + Video
+    {
+        let frame_tstates = <Self as Video>::VideoFrame::FRAME_TSTATES_COUNT as i64;
+        let start_frame = self.current_frame();
+        let start_ts = self.current_tstate() as i64;
+        let mut elapsed = 0;
+        while elapsed < cycles {
+            if self.execute_single_step(cpu, None::<fn(CpuDebug)>).is_err() {
+                break
+            }
+            let frames = self.current_frame().wrapping_sub(start_frame) as i64;
+            elapsed = (frames * frame_tstates + self.current_tstate() as i64 - start_ts) as u64;
+        }
+        elapsed
+    }
+    /// Executes a single instruction on the `cpu` and returns the new value of the `PC` register.
+    ///
+    /// The memory contention and the video and audio timestamps are being accounted for just like
+    /// with [ControlUnit::execute_single_step]. Likewise, if the T-state counter value is near the end
+    /// of a frame, the internal state is being prepared for the next frame before executing the instruction,
+    /// so the video and audio of the previous frame should be rendered first.
+    fn step_instruction<C: Cpu>(&mut self, cpu: &mut C) -> u16 {
+        let _ = self.execute_single_step(cpu, None::<fn(CpuDebug)>);
+        cpu.get_pc()
+    }
+    /// Executes a single instruction on the `cpu` with [ControlUnit::step_instruction]. If the instruction
+    /// was a `CALL` or `RST` and the subroutine has been called, continues executing instructions until
+    /// the subroutine returns to the instruction following the call.
+    ///
+    /// The execution is not being limited by the frame boundaries, but it stops after at least `max_tstates`
+    /// T-states have passed, which prevents hanging when the subroutine never returns.
+    ///
+    /// Returns the new value of the `PC` register or `None` if the execution has been stopped due to the
+    /// `max_tstates` limit.
+    fn step_over<C: Cpu>(&mut self, cpu: &mut C, max_tstates: u64) -> Option<u16>
+        where Self: FrameState + Video
+    {
+        let frame_tstates = <Self as Video>::VideoFrame::FRAME_TSTATES_COUNT as i64;
+        let sp = cpu.get_sp();
+        let mut debug = None;
+        let _ = self.execute_single_step(cpu, Some(|deb| debug = Some(deb)));
+        let ret_pc = match debug {
+            Some(CpuDebug { mnemonic: "CALL"|"RST", pc, code, .. }) if cpu.get_sp() == sp.wrapping_sub(2) => {
+                pc.wrapping_add(code.len() as u16)
+            }
+            _ => return Some(cpu.get_pc())
+        };
+        let start_frame = self.current_frame();
+        let start_ts = self.current_tstate() as i64;
+        let mut elapsed = 0;
+        while elapsed < max_tstates {
+            let pc = self.step_instruction(cpu);
+            if pc == ret_pc && cpu.get_sp() == sp {
+                return Some(pc)
+            }
+            let frames = self.current_frame().wrapping_sub(start_frame) as i64;
+            elapsed = (frames * frame_tstates + self.current_tstate() as i64 - start_ts) as u64;
+        }
+        None
+    }
+    /// Executes instructions on the `cpu` one by one until the end of the current frame, checking
+    /// the given `breakpoints` before and the watchpoints after executing each instruction.
+    ///
+    /// If the T-state counter value is near the end of a frame, prepares the internal state for the next frame
+    /// before executing the first instruction.
+    ///
+    /// When the execution is being resumed from the breakpoint last hit with the same `breakpoints`,
+    /// i.e. neither the `PC` register nor the T-state counter has changed since then, the breakpoint
+    /// is not being checked again, so the execution can continue by calling this method again.
+    ///
+    /// Returns the reason for returning along with the number of T-states remaining to the end of the frame
+    /// in case a breakpoint or a watchpoint has been hit.
+    ///
+    /// This method is considerably slower than [ControlUnit::execute_next_frame], so it should be used only
+    /// when debugging.
+    fn run_frame<C: Cpu>(&mut self, cpu: &mut C, breakpoints: &mut Breakpoints) -> DebugBreak
+        where Self: FrameState + Video
+    {
+        let frame_tstates = <Self as Video>::VideoFrame::FRAME_TSTATES_COUNT;
+        self.ensure_next_frame();
+        let mut resumed = breakpoints.is_resuming(self.current_frame(), self.current_tstate(), cpu.get_pc());
+        breakpoints.set_last_break(None);
+        while !self.is_frame_over() {
+            let pc = cpu.get_pc();
+            if !resumed && breakpoints.has_breakpoint(pc) {
+                let ts = self.current_tstate();
+                breakpoints.set_last_break(Some((self.current_frame(), ts, pc)));
+                let remaining = frame_tstates - ts;
+                return DebugBreak::Breakpoint { pc, remaining }
+            }
+            resumed = false;
+            let regs = AddrRegisters::from_cpu(cpu);
+            let mut debug = None;
+            let _ = self.execute_single_step(cpu, Some(|deb| debug = Some(deb)));
+            if let Some(deb) = debug {
+                if let Some((address, access)) = breakpoints.check_watchpoints(&deb, &regs, cpu.get_sp()) {
+                    let remaining = (frame_tstates - self.current_tstate()).max(0);
+                    return DebugBreak::Watchpoint { pc: deb.pc, address, access, remaining }
+                }
+            }
+        }
+        DebugBreak::FrameEnd
+    }
 }
 
 /// A trait for reading the MIC line output.
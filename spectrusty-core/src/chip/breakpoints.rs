@@ -0,0 +1,250 @@
+/*
+    Copyright (C) 2020-2023  Rafal Michalski
+
+    This file is part of SPECTRUSTY, a Rust library for building emulators.
+
+    For the full copyright notice, see the lib.rs file.
+*/
+use std::collections::{BTreeMap, BTreeSet};
+use bitflags::bitflags;
+
+use z80emu::{
+    Cpu, CpuDebug, CpuDebugArg, CpuDebugArgs, CpuDebugAddr,
+    Prefix, Reg16, StkReg16
+};
+use crate::clock::FTs;
+
+bitflags! {
+    /// Kinds of memory accesses being watched by watchpoints.
+    #[derive(Default, Debug, PartialEq, Eq, Hash, Clone, Copy)]
+    pub struct WatchAccess: u8 {
+        const READ       = 0b01;
+        const WRITE      = 0b10;
+        const READ_WRITE = 0b11;
+    }
+}
+
+/// The reason for returning from [ControlUnit::run_frame][super::ControlUnit::run_frame].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DebugBreak {
+    /// The end of the frame has been reached.
+    FrameEnd,
+    /// The `PC` register has reached an address of a breakpoint.
+    ///
+    /// The instruction at `pc` has not been executed yet.
+    Breakpoint {
+        /// The address of the breakpoint.
+        pc: u16,
+        /// The number of T-states remaining to the end of the frame.
+        remaining: FTs
+    },
+    /// The instruction at `pc` has accessed the watched memory `address`.
+    ///
+    /// The instruction has already been executed.
+    Watchpoint {
+        /// The address of the instruction that accessed the watched memory.
+        pc: u16,
+        /// The watched memory address.
+        address: u16,
+        /// The kind of the watched access.
+        access: WatchAccess,
+        /// The number of T-states remaining to the end of the frame.
+        remaining: FTs
+    }
+}
+
+/// A set of CPU breakpoints and memory watchpoints for [ControlUnit::run_frame][super::ControlUnit::run_frame].
+///
+/// Watchpoints are being checked against memory accesses of the executed instructions, including the
+/// machine stack accesses and block instructions. The instruction fetches and the machine stack accesses
+/// during the interrupt acceptance are not being watched.
+#[derive(Clone, Debug, Default)]
+pub struct Breakpoints {
+    breakpoints: BTreeSet<u16>,
+    watchpoints: BTreeMap<u16, WatchAccess>,
+    // the frame counter, the T-state counter and the PC of the last hit breakpoint
+    last_break: Option<(u64, FTs, u16)>
+}
+
+impl PartialEq for Breakpoints {
+    fn eq(&self, other: &Self) -> bool {
+        self.breakpoints == other.breakpoints && self.watchpoints == other.watchpoints
+    }
+}
+
+impl Eq for Breakpoints {}
+
+/// A copy of the CPU registers used for addressing memory before an instruction is executed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct AddrRegisters {
+    bc: u16,
+    de: u16,
+    hl: u16,
+    sp: u16,
+    ix: u16,
+    iy: u16
+}
+
+impl AddrRegisters {
+    pub(crate) fn from_cpu<C: Cpu>(cpu: &C) -> Self {
+        AddrRegisters {
+            bc: cpu.get_reg16(StkReg16::BC),
+            de: cpu.get_reg16(StkReg16::DE),
+            hl: cpu.get_reg16(StkReg16::HL),
+            sp: cpu.get_sp(),
+            ix: cpu.get_index16(Prefix::Xdd),
+            iy: cpu.get_index16(Prefix::Yfd)
+        }
+    }
+
+    fn address(&self, addr: CpuDebugAddr) -> Option<u16> {
+        Some(match addr {
+            CpuDebugAddr::ImmAddr(addr) => addr,
+            CpuDebugAddr::RegAddr(Reg16::BC) => self.bc,
+            CpuDebugAddr::RegAddr(Reg16::DE) => self.de,
+            CpuDebugAddr::RegAddr(Reg16::HL) => self.hl,
+            CpuDebugAddr::RegAddr(Reg16::SP) => self.sp,
+            CpuDebugAddr::IndexAddr(prefix, Some(index)) => {
+                let base = match prefix {
+                    Prefix::Xdd => self.ix,
+                    Prefix::Yfd => self.iy
+                };
+                base.wrapping_add(index as i16 as u16)
+            }
+            CpuDebugAddr::IndexAddr(_, None) => return None
+        })
+    }
+}
+
+fn is_read_modify_write(mnemonic: &str) -> bool {
+    matches!(mnemonic, "INC"|"DEC"|"RLC"|"RRC"|"RL"|"RR"|"SLA"|"SRA"|"SLL"|"SRL"|"SET"|"RES"|"EX")
+}
+
+fn operand_size(arg: CpuDebugArg) -> u16 {
+    match arg {
+        CpuDebugArg::Reg16(..)|CpuDebugArg::Stk16(..)|CpuDebugArg::Imm16(..) => 2,
+        _ => 1
+    }
+}
+
+impl Breakpoints {
+    /// Returns an empty set of breakpoints and watchpoints.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Returns `true` if there are no breakpoints and no watchpoints.
+    pub fn is_empty(&self) -> bool {
+        self.breakpoints.is_empty() && self.watchpoints.is_empty()
+    }
+    /// Removes all breakpoints and watchpoints.
+    pub fn clear(&mut self) {
+        self.breakpoints.clear();
+        self.watchpoints.clear();
+        self.last_break = None;
+    }
+    /// Adds a breakpoint at the given `address`. Returns `false` if the breakpoint was already present.
+    pub fn add_breakpoint(&mut self, address: u16) -> bool {
+        self.breakpoints.insert(address)
+    }
+    /// Removes a breakpoint at the given `address`. Returns `true` if the breakpoint was present.
+    pub fn remove_breakpoint(&mut self, address: u16) -> bool {
+        self.breakpoints.remove(&address)
+    }
+    /// Returns `true` if there is a breakpoint at the given `address`.
+    pub fn has_breakpoint(&self, address: u16) -> bool {
+        self.breakpoints.contains(&address)
+    }
+    /// Returns an iterator of breakpoint addresses in ascending order.
+    pub fn breakpoints(&self) -> impl Iterator<Item=u16> + '_ {
+        self.breakpoints.iter().copied()
+    }
+    /// Adds a watchpoint of the memory `access` at the given `address`.
+    ///
+    /// The `access` is being combined with the access of the already present watchpoint at the same `address`.
+    pub fn add_watchpoint(&mut self, address: u16, access: WatchAccess) {
+        if !access.is_empty() {
+            *self.watchpoints.entry(address).or_default() |= access;
+        }
+    }
+    /// Removes a watchpoint at the given `address`. Returns the watched access if the watchpoint was present.
+    pub fn remove_watchpoint(&mut self, address: u16) -> Option<WatchAccess> {
+        self.watchpoints.remove(&address)
+    }
+    /// Returns the watched access at the given `address` if there is a watchpoint.
+    pub fn watchpoint(&self, address: u16) -> Option<WatchAccess> {
+        self.watchpoints.get(&address).copied()
+    }
+    /// Returns an iterator of watchpoint addresses and accesses in ascending address order.
+    pub fn watchpoints(&self) -> impl Iterator<Item=(u16, WatchAccess)> + '_ {
+        self.watchpoints.iter().map(|(&address, &access)| (address, access))
+    }
+
+    /// Records the location of the hit breakpoint.
+    pub(crate) fn set_last_break(&mut self, last_break: Option<(u64, FTs, u16)>) {
+        self.last_break = last_break;
+    }
+    /// Returns `true` if the execution is being resumed from the last hit breakpoint.
+    pub(crate) fn is_resuming(&self, frame: u64, ts: FTs, pc: u16) -> bool {
+        self.last_break == Some((frame, ts, pc))
+    }
+
+    fn find_access(&self, address: u16, size: u16, access: WatchAccess) -> Option<(u16, WatchAccess)> {
+        (0..size).map(|offset| address.wrapping_add(offset)).find_map(|address| {
+            self.watchpoint(address).map(|watched| watched & access)
+                .filter(|access| !access.is_empty())
+                .map(|access| (address, access))
+        })
+    }
+    /// Returns the first watched memory access of the instruction that has just been executed.
+    ///
+    /// `regs` must be captured before the instruction was executed and `sp` is the value of the `SP`
+    /// register after the execution.
+    pub(crate) fn check_watchpoints(
+            &self,
+            deb: &CpuDebug,
+            regs: &AddrRegisters,
+            sp: u16
+        ) -> Option<(u16, WatchAccess)>
+    {
+        use WatchAccess as Wa;
+        if self.watchpoints.is_empty() {
+            return None
+        }
+        let mnemonic = deb.mnemonic;
+        match mnemonic {
+            "LDI"|"LDD"|"LDIR"|"LDDR" => {
+                return self.find_access(regs.hl, 1, Wa::READ)
+                           .or_else(|| self.find_access(regs.de, 1, Wa::WRITE))
+            }
+            "CPI"|"CPD"|"CPIR"|"CPDR"|"OUTI"|"OUTD"|"OTIR"|"OTDR" => {
+                return self.find_access(regs.hl, 1, Wa::READ)
+            }
+            "INI"|"IND"|"INIR"|"INDR" => return self.find_access(regs.hl, 1, Wa::WRITE),
+            "RLD"|"RRD" => return self.find_access(regs.hl, 1, Wa::READ_WRITE),
+            "PUSH"|"CALL"|"RST" if sp == regs.sp.wrapping_sub(2) => {
+                return self.find_access(sp, 2, Wa::WRITE)
+            }
+            "POP"|"RET"|"RETI"|"RETN" if sp == regs.sp.wrapping_add(2) => {
+                return self.find_access(regs.sp, 2, Wa::READ)
+            }
+            "JP" => return None,
+            _ => {}
+        }
+        let rmw = is_read_modify_write(mnemonic);
+        let (addr, size, access) = match deb.args {
+            CpuDebugArgs::Single(CpuDebugArg::Addr(addr)) => {
+                (addr, 1, if rmw { Wa::READ_WRITE } else { Wa::READ })
+            }
+            CpuDebugArgs::Double(CpuDebugArg::Addr(addr), arg) => {
+                (addr, operand_size(arg), if rmw { Wa::READ_WRITE } else { Wa::WRITE })
+            }
+            CpuDebugArgs::Double(arg, CpuDebugArg::Addr(addr)) => {
+                (addr, operand_size(arg), if rmw { Wa::READ_WRITE } else { Wa::READ })
+            }
+            CpuDebugArgs::BitOpExt(_, CpuDebugArg::Addr(addr), _) => (addr, 1, Wa::READ_WRITE),
+            _ => return None
+        };
+        let address = regs.address(addr)?;
+        self.find_access(address, size, access)
+    }
+}
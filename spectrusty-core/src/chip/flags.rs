@@ -77,6 +77,15 @@ macro_rules! test_bitflags_all_bits_defined_no_masks {
 pub use test_bitflags_all_bits_defined_no_masks;
 
 /// This enum determines the EAR input (bit 6) read from the 0xFE port when there is no EAR input feed.
+///
+/// The value of bit 6 depending on bits 4 (EAR) and 3 (MIC) of the last value written to the 0xFE port:
+///
+/// | EAR | MIC | Issue 3 | Issue 2 | Clear | Set |
+/// |-----|-----|---------|---------|-------|-----|
+/// |   0 |   0 |       0 |       0 |     0 |   1 |
+/// |   0 |   1 |       0 |       1 |     0 |   1 |
+/// |   1 |   0 |       1 |       1 |     0 |   1 |
+/// |   1 |   1 |       1 |       1 |     0 |   1 |
 #[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ReadEarMode {
@@ -184,6 +193,8 @@ bitflags! {
     /// | 0xC000 | 0XFFFF | RAM0 - RAM7 |
     ///
     /// [128K]: https://sinclair.wiki.zxnet.co.uk/wiki/ZX_Spectrum_128#Paging
+    #[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+    #[cfg_attr(feature = "snapshot", serde(from = "u8", into = "u8"))]
     #[derive(Default, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
     pub struct Ula128MemFlags: u8 {
         const RAM_BANK0     = 0b00_0001;
@@ -279,6 +290,48 @@ pub enum Ula3Paging {
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct TryFromU8Ula3PagingError(pub u8);
 
+bitflags! {
+    /// Scorpion [ZS-256] extended memory control flags.
+    ///
+    /// Any I/O port matching: `0001_xxxx_xxxx_xx0x` (`0x1ffd`).
+    ///
+    /// | Dir | b7  | b6  | b5  | b4  | b3  | b2  | b1  | b0  |
+    /// |-----|-----|-----|-----|-----|-----|-----|-----|-----|
+    /// | OUT |     |     |     | RB3 |     |     | SRV | RM0 |
+    ///
+    /// In pair with [Ula128MemFlags] I/O port matching: `01xx_xxxx_xxxx_xx0x` (`0x7ffd`).
+    ///
+    /// | Dir | b7  | b6  | b5  | b4  | b3  | b2  | b1  | b0  |
+    /// |-----|-----|-----|-----|-----|-----|-----|-----|-----|
+    /// | OUT |     |     | LCK | ROL | SCR | RB2 | RB1 | RB0 |
+    ///
+    /// RAM bank: `RB3 * 8 + RB2 * 4 + RB1 * 2 + RB0`.
+    ///
+    /// SCR bank: `0 in RAM5, 1 in RAM7`.
+    ///
+    /// ROM bank: `SRV * 2 + ROL`, where `SRV` selects the service ROM bank `2` regardless of `ROL`.
+    ///
+    /// | Start  | Top    | Memory Bank         |
+    /// |--------|--------|---------------------|
+    /// | 0x0000 | 0x3FFF | ROM0 - ROM2 or RAM0 |
+    /// | 0x4000 | 0x7FFF | RAM5                |
+    /// | 0x8000 | 0xBFFF | RAM2                |
+    /// | 0xC000 | 0XFFFF | RAM0 - RAM15        |
+    ///
+    /// `RM0` maps RAM bank `0` in place of the ROM at the first memory page and takes precedence over `SRV`.
+    ///
+    /// [ZS-256]: https://sinclair.wiki.zxnet.co.uk/wiki/Scorpion_ZS-256
+    #[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+    #[cfg_attr(feature = "snapshot", serde(from = "u8", into = "u8"))]
+    #[derive(Default, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+    pub struct ScorpionCtrlFlags: u8 {
+        const RAM_PAGE0   = 0b0_0001;
+        const SERVICE_ROM = 0b0_0010;
+        const RAM_BANK3   = 0b1_0000;
+    }
+}
+bitflags_from_data!(ScorpionCtrlFlags);
+
 bitflags! {
     /// Timex [TC2048/TC2068/TS2068] screen and memory control flags.
     ///
@@ -550,6 +603,20 @@ impl Ula128MemFlags {
     }
 }
 
+impl From<Ula128MemFlags> for u8 {
+    #[inline]
+    fn from(flags: Ula128MemFlags) -> u8 {
+        flags.bits()
+    }
+}
+
+impl From<u8> for Ula128MemFlags {
+    #[inline]
+    fn from(flags: u8) -> Ula128MemFlags {
+        Ula128MemFlags::from_data(flags)
+    }
+}
+
 /****************************** Ula3CtrlFlags ******************************/
 
 impl Ula3CtrlFlags {
@@ -606,6 +673,47 @@ impl Ula3CtrlFlags {
     }
 }
 
+/****************************** ScorpionCtrlFlags ******************************/
+
+impl ScorpionCtrlFlags {
+    /// The index of the service ROM bank.
+    pub const SERVICE_ROM_BANK: usize = 2;
+    /// Returns modified flags with the extended RAM bank flag set from the `bank` bit 3.
+    pub fn with_last_ram_page_bank_hi(mut self, bank: usize) -> Self {
+        self.set(ScorpionCtrlFlags::RAM_BANK3, bank & 8 != 0);
+        self
+    }
+    /// Returns a bit 3 value of a RAM bank index mapped at the last memory page.
+    ///
+    /// The complete RAM bank index can be obtained by bitwise ORing the returned value with
+    /// the result from [Ula128MemFlags::last_ram_page_bank].
+    pub fn last_ram_page_bank_hi(self) -> usize {
+        ((self & ScorpionCtrlFlags::RAM_BANK3).bits() >> 1).into()
+    }
+    /// Returns `true` if the service ROM bit is 1. Otherwise returns `false`.
+    pub fn is_service_rom(self) -> bool {
+        self.intersects(ScorpionCtrlFlags::SERVICE_ROM)
+    }
+    /// Returns `true` if RAM bank `0` should be mapped at the first memory page. Otherwise returns `false`.
+    pub fn is_ram_at_page0(self) -> bool {
+        self.intersects(ScorpionCtrlFlags::RAM_PAGE0)
+    }
+}
+
+impl From<ScorpionCtrlFlags> for u8 {
+    #[inline]
+    fn from(flags: ScorpionCtrlFlags) -> u8 {
+        flags.bits()
+    }
+}
+
+impl From<u8> for ScorpionCtrlFlags {
+    #[inline]
+    fn from(flags: u8) -> ScorpionCtrlFlags {
+        ScorpionCtrlFlags::from_data(flags)
+    }
+}
+
 /****************************** Ula3Paging ******************************/
 
 impl From<Ula3Paging> for u8 {
@@ -22,8 +22,10 @@ use crate::video::VideoFrame;
 
 mod packed;
 mod ops;
+mod contention;
 pub use packed::*;
 pub use ops::*;
+pub use contention::*;
 
 /// A linear T-state timestamp type.
 pub type FTs = i32;
@@ -57,6 +59,13 @@ pub struct VFrameTs<V> {
 /// A trait used by [VFrameTsCounter] for checking if an `address` is a contended one.
 pub trait MemoryContention: Copy + Debug {
     fn is_contended_address(self, address: u16) -> bool;
+    /// Returns a horizontal T-state counter after adding the memory contention delay at the given `hc`.
+    ///
+    /// The default implementation uses [VideoFrame::contention].
+    #[inline(always)]
+    fn contention<V: VideoFrame>(self, hc: Ts) -> Ts {
+        V::contention(hc)
+    }
 }
 
 /// A generic [`VFrameTs<V>`][VFrameTs] based T-states counter.
@@ -293,6 +302,18 @@ impl<V, C> VFrameTsCounter<V, C>
     pub fn is_contended_address(self, address: u16) -> bool {
         self.contention.is_contended_address(address)
     }
+    /// Returns `true` if the I/O cycle accessing the given `port` is subject to the ULA contention.
+    ///
+    /// This is the case when the port address points to the contended memory or when the lowest bit
+    /// of the port address is reset, which selects the ULA port. See [ula_io_contention].
+    ///
+    /// Always returns `false` if the video frame doesn't contend the I/O cycles (other than MREQ),
+    /// see [VideoFrame::is_contended_line_no_mreq].
+    #[inline]
+    pub fn is_contended_port(self, port: u16) -> bool {
+        V::is_contended_line_no_mreq(V::VSL_PIXELS.start) &&
+            (port & 1 == 0 || self.is_contended_address(port))
+    }
 }
 
 /// This macro is used to implement the ULA I/O contention scheme, for [z80emu::Clock::add_io] method of
@@ -302,7 +323,7 @@ impl<V, C> VFrameTsCounter<V, C>
 /// * $mc should be a type implementing [MemoryContention] trait.
 /// * $port is a port address.
 /// * $hc is an identifier of a mutable variable containing the `hc` property of a `VideoTs` timestamp.
-/// * $contention should be a path to the [VideoFrame::contention] function.
+/// * $contention should be a path to the [VideoFrame::contention] function or a similar one.
 ///
 /// The macro returns a horizontal timestamp pointing after the whole I/O cycle is over.
 /// The `hc` variable is modified to contain a horizontal timestamp indicating when the data R/W operation 
@@ -416,7 +437,7 @@ impl<V: VideoFrame, C: MemoryContention> Clock for VFrameTsCounter<V, C> {
         let mut hc = self.hc;
         if V::is_contended_line_no_mreq(self.vc) && self.contention.is_contended_address(address) {
             for _ in 0..add_ts.get() {
-                hc = V::contention(hc) + 1;
+                hc = self.contention.contention::<V>(hc) + 1;
             }
         }
         else {
@@ -434,7 +455,7 @@ impl<V: VideoFrame, C: MemoryContention> Clock for VFrameTsCounter<V, C> {
         //     _ => {}
         // }
         let hc = if V::is_contended_line_mreq(self.vc) && self.contention.is_contended_address(address) {
-            V::contention(self.hc)
+            self.contention.contention::<V>(self.hc)
         }
         else {
             self.hc
@@ -446,7 +467,7 @@ impl<V: VideoFrame, C: MemoryContention> Clock for VFrameTsCounter<V, C> {
     #[inline(always)]
     fn add_mreq(&mut self, address: u16) -> Self::Timestamp {
         let hc = if V::is_contended_line_mreq(self.vc) && self.contention.is_contended_address(address) {
-            V::contention(self.hc)
+            self.contention.contention::<V>(self.hc)
         }
         else {
             self.hc
@@ -493,7 +514,9 @@ impl<V: VideoFrame, C: MemoryContention> Clock for VFrameTsCounter<V, C> {
         //     println!("0x{:04x}: {} {:?}", port, self.as_tstates(), self.tsc);
         // }
         let hc1 = if V::is_contended_line_no_mreq(vc) {
-            ula_io_contention!(self.contention, port, hc, V::contention)
+            let mc = self.contention;
+            let contention = |hc| mc.contention::<V>(hc);
+            ula_io_contention!(mc, port, hc, contention)
             // if is_contended_address(self.contention_mask, port) {
             //     hc = V::contention(hc) + IO_IORQ_LOW_TS as Ts;
             //     if port & 1 == 0 { // C:1, C:3
@@ -0,0 +1,91 @@
+/*
+    Copyright (C) 2020-2023  Rafal Michalski
+
+    This file is part of SPECTRUSTY, a Rust library for building emulators.
+
+    For the full copyright notice, see the lib.rs file.
+*/
+#[cfg(feature = "snapshot")]
+use serde::{Serialize, Deserialize};
+
+use super::{MemoryContention, Ts};
+use crate::video::VideoFrame;
+
+/// A memory contention delay pattern, repeated every 8 T-states.
+///
+/// Each entry is the number of additional T-states a contended memory access is being delayed by
+/// when it starts at the corresponding T-state of the 8 T-state cycle.
+pub type ContentionPattern = [u8; 8];
+
+/// The contention delay pattern of the Ferranti ULA: `6, 5, 4, 3, 2, 1, 0, 0`.
+pub const ULA_CONTENTION_PATTERN: ContentionPattern = [6, 5, 4, 3, 2, 1, 0, 0];
+
+/// A [MemoryContention] implementation with a configurable contention delay pattern, for emulating
+/// clone machines with their own memory contention timings.
+///
+/// The delay pattern is applied to memory accesses of any of the contended 16kb memory pages, made
+/// at horizontal T-states within the contended range, on scan lines specified by the [VideoFrame]
+/// implementation. The first entry of the pattern applies to the first T-state of the range.
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "snapshot", serde(rename_all = "camelCase"))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CustomContention {
+    pattern: ContentionPattern,
+    hc_start: Ts,
+    hc_end: Ts,
+    pages: u8
+}
+
+impl Default for CustomContention {
+    /// Returns the contention of the Ferranti ULA of the 16k/48k Spectrum.
+    fn default() -> Self {
+        CustomContention::new(ULA_CONTENTION_PATTERN)
+    }
+}
+
+impl CustomContention {
+    /// Returns a new instance with the given delay `pattern` applied within the horizontal T-states
+    /// range: [-1, 125) to addresses in the range: [0x4000, 0x7FFF], just like in the 16k/48k Spectrum.
+    pub const fn new(pattern: ContentionPattern) -> Self {
+        CustomContention { pattern, hc_start: -1, hc_end: 125, pages: 0b0010 }
+    }
+    /// Returns a copy of self with the contended horizontal T-states range: [`start`, `end`).
+    pub const fn with_hc_range(self, start: Ts, end: Ts) -> Self {
+        CustomContention { hc_start: start, hc_end: end, ..self }
+    }
+    /// Returns a copy of self with the contended 16kb memory pages given as a bit mask.
+    ///
+    /// Bit 0 represents addresses [0x0000, 0x3FFF], bit 1 - [0x4000, 0x7FFF] and so on.
+    pub const fn with_contended_pages(self, pages: u8) -> Self {
+        CustomContention { pages: pages & 0b1111, ..self }
+    }
+    /// Returns the delay pattern.
+    pub fn pattern(&self) -> ContentionPattern {
+        self.pattern
+    }
+    /// Returns the contended horizontal T-states range as a tuple: (start, end).
+    pub fn hc_range(&self) -> (Ts, Ts) {
+        (self.hc_start, self.hc_end)
+    }
+    /// Returns the contended 16kb memory pages as a bit mask.
+    pub fn contended_pages(&self) -> u8 {
+        self.pages
+    }
+}
+
+impl MemoryContention for CustomContention {
+    #[inline(always)]
+    fn is_contended_address(self, address: u16) -> bool {
+        (self.pages & 1 << (address >> 14)) != 0
+    }
+
+    #[inline]
+    fn contention<V: VideoFrame>(self, hc: Ts) -> Ts {
+        if (self.hc_start..self.hc_end).contains(&hc) {
+            hc + self.pattern[(hc.wrapping_sub(self.hc_start) & 7) as usize] as Ts
+        }
+        else {
+            hc
+        }
+    }
+}
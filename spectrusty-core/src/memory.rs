@@ -12,10 +12,12 @@ use std::rc::Rc;
 use std::io::{self, Read};
 
 mod extension;
+mod power_on;
 #[cfg(feature = "snapshot")] pub mod arrays;
 #[cfg(feature = "snapshot")] pub mod serde;
 
 pub use extension::*;
+pub use power_on::*;
 
 pub const MEM16K_SIZE : usize = 0x4000;
 pub const MEM32K_SIZE : usize = 2 * MEM16K_SIZE;
@@ -344,6 +346,115 @@ pub trait ZxMemory {
             Ok(())
         })
     }
+    /// Fills the whole RAM (all banks) with the given power-on `pattern`.
+    ///
+    /// Unlike [ZxMemory::fill_mem], this method doesn't depend on the memory paging and leaves ROM intact.
+    fn fill_ram_power_on(&mut self, pattern: PowerOnMemory) {
+        pattern.fill(self.ram_mut())
+    }
+    /// Sets the reference content for serializing the memory as a delta, `None` to always store the
+    /// whole memory.
+    ///
+    /// With the reference set, human-readable serializers (e.g. JSON) store the `base` pattern and only
+    /// the memory banks that differ from their reference: ROM filled with `0xFF` and RAM filled with
+    /// the `base` pattern (see [ZxMemory::fill_ram_power_on]). The reference is not part of the
+    /// serialized state, so it's always unset in the deserialized memory.
+    ///
+    /// Returns `false` if the memory type doesn't support the delta format.
+    fn set_serde_delta_base(&mut self, _base: Option<PowerOnMemory>) -> bool {
+        false
+    }
+    /// Returns the reference content for serializing the memory as a delta if set.
+    fn serde_delta_base(&self) -> Option<PowerOnMemory> {
+        None
+    }
+    /// Writes `data` into the paged-in memory starting at the given `address`.
+    ///
+    /// The data may span across the page boundaries. Writes to the read-only pages are being
+    /// ignored, just like with [ZxMemory::write].
+    ///
+    /// # Errors
+    /// Returns an [ZxMemoryError::UnsupportedAddressRange] error if `data` would extend beyond
+    /// [ZxMemory::RAMTOP]. In this instance, the memory is left unmodified.
+    fn load_into(&mut self, address: u16, data: &[u8]) -> Result<()> {
+        if address as usize + data.len() > Self::RAMTOP as usize + 1 {
+            return Err(ZxMemoryError::UnsupportedAddressRange)
+        }
+        for (addr, &val) in (address..=Self::RAMTOP).zip(data) {
+            self.write(addr, val);
+        }
+        Ok(())
+    }
+    /// Returns a copy of the paged-in memory content at the given address range.
+    ///
+    /// # Errors
+    /// May return an [ZxMemoryError::UnsupportedAddressRange] error.
+    fn dump_range<A: RangeBounds<u16>>(&self, address_range: A) -> Result<Vec<u8>> {
+        let mut dump = Vec::new();
+        for page in self.iter_pages(address_range)? {
+            dump.extend_from_slice(page);
+        }
+        Ok(dump)
+    }
+    /// Searches the paged-in memory at the given address range for the byte `pattern`.
+    ///
+    /// Returns addresses of all occurrences of the `pattern`, including the overlapping ones.
+    /// The occurrences may span across the page boundaries but must fit entirely within the range.
+    ///
+    /// # Errors
+    /// May return an [ZxMemoryError::UnsupportedAddressRange] error.
+    fn find_bytes<A: RangeBounds<u16>>(&self, pattern: &[u8], address_range: A) -> Result<Vec<u16>> {
+        let start = match address_range.start_bound() {
+            Bound::Included(start) => *start,
+            Bound::Excluded(start) => start.saturating_add(1),
+            Bound::Unbounded => 0
+        };
+        let dump = self.dump_range(address_range)?;
+        Ok(find_pattern(&dump, pattern, start))
+    }
+    /// Searches the given RAM bank for the byte `pattern`.
+    ///
+    /// Returns offsets into the bank of all occurrences of the `pattern`, including the overlapping ones,
+    /// regardless of the bank being currently paged in.
+    ///
+    /// # Errors
+    /// May return an [ZxMemoryError::InvalidBankIndex] error.
+    fn find_bytes_in_ram_bank(&self, pattern: &[u8], ram_bank: usize) -> Result<Vec<u16>> {
+        let bank = self.ram_bank_ref(ram_bank)?;
+        Ok(find_pattern(bank, pattern, 0))
+    }
+    /// Returns a 64-bit FNV-1a hash of the content of the given ROM or RAM bank.
+    ///
+    /// The checksum doesn't depend on the memory paging. It can be used to cheaply detect which
+    /// banks have changed between two snapshots of the memory.
+    ///
+    /// # Errors
+    /// May return an [ZxMemoryError::InvalidBankIndex] error.
+    fn bank_checksum(&self, kind: MemoryKind, index: usize) -> Result<u64> {
+        let bank = match kind {
+            MemoryKind::Rom => self.rom_bank_ref(index)?,
+            MemoryKind::Ram => self.ram_bank_ref(index)?
+        };
+        Ok(fnv1a_hash(bank))
+    }
+}
+
+fn fnv1a_hash(data: &[u8]) -> u64 {
+    const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+    data.iter().fold(FNV_OFFSET_BASIS, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+    })
+}
+
+fn find_pattern(haystack: &[u8], pattern: &[u8], base: u16) -> Vec<u16> {
+    if pattern.is_empty() {
+        return Vec::new()
+    }
+    haystack.windows(pattern.len()).enumerate()
+            .filter(|(_, window)| *window == pattern)
+            .map(|(offset, _)| base.wrapping_add(offset as u16))
+            .collect()
 }
 
 pub struct MemPageRefIter<'a, Z: ?Sized> {
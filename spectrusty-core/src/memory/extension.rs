@@ -5,6 +5,7 @@
 
     For the full copyright notice, see the lib.rs file.
 */
+use crate::clock::FTs;
 use super::ZxMemory;
 
 #[cfg(feature = "snapshot")]
@@ -19,6 +20,27 @@ pub trait MemoryExtension: core::fmt::Debug {
     fn read_opcode<M: ZxMemory>(&mut self, pc: u16, memory: &mut M) -> u8 {
         memory.read(pc)
     }
+    /// Read a byte from the given `memory` at the given `addr`, optionally intercepting the access.
+    ///
+    /// `ts` is the frame T-state counter value of the access.
+    #[inline]
+    fn read_mem<M: ZxMemory>(&self, addr: u16, _ts: FTs, memory: &M) -> u8 {
+        memory.read(addr)
+    }
+    /// Read a 16-bit word from the given `memory` at the given `addr`, optionally intercepting the access.
+    ///
+    /// `ts` is the frame T-state counter value of the access.
+    #[inline]
+    fn read_mem16<M: ZxMemory>(&self, addr: u16, _ts: FTs, memory: &M) -> u16 {
+        memory.read16(addr)
+    }
+    /// Write a byte to the given `memory` at the given `addr`, optionally intercepting the access.
+    ///
+    /// `ts` is the frame T-state counter value of the access.
+    #[inline]
+    fn write_mem<M: ZxMemory>(&mut self, addr: u16, val: u8, _ts: FTs, memory: &mut M) {
+        memory.write(addr, val)
+    }
     // /// Writes to the memory extension port. Should return optionally modified `data` if the extension wants
     // /// to influence some other chipset functions.
     // #[inline]
@@ -0,0 +1,57 @@
+/*
+    Copyright (C) 2020-2023  Rafal Michalski
+
+    This file is part of SPECTRUSTY, a Rust library for building emulators.
+
+    For the full copyright notice, see the lib.rs file.
+*/
+#[cfg(feature = "snapshot")]
+use ::serde::{Serialize, Deserialize};
+
+/// The initial content of RAM when the power is being turned on.
+///
+/// Used by [ZxMemory::fill_ram_power_on][super::ZxMemory::fill_ram_power_on] to reproduce the behavior
+/// of programs depending on uninitialized memory.
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "snapshot", serde(rename_all = "camelCase"))]
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq, Hash)]
+pub enum PowerOnMemory {
+    /// All bytes are `0x00`.
+    #[default]
+    Zeroed,
+    /// All bytes are `0xFF`.
+    Ones,
+    /// Alternating `0x55` and `0xAA` bytes.
+    Checkerboard,
+    /// Pseudo-random bytes generated from the given seed.
+    ///
+    /// The same seed always produces the same content.
+    Random(u64)
+}
+
+impl PowerOnMemory {
+    /// Fills the `mem` slice with the pattern.
+    pub fn fill(self, mem: &mut [u8]) {
+        match self {
+            PowerOnMemory::Zeroed => mem.fill(0),
+            PowerOnMemory::Ones => mem.fill(!0),
+            PowerOnMemory::Checkerboard => {
+                for (addr, p) in mem.iter_mut().enumerate() {
+                    *p = if addr & 1 == 0 { 0x55 } else { 0xAA };
+                }
+            }
+            PowerOnMemory::Random(seed) => {
+                // SplitMix64
+                let mut state = seed;
+                for chunk in mem.chunks_mut(8) {
+                    state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+                    let mut z = state;
+                    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+                    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+                    z ^= z >> 31;
+                    chunk.copy_from_slice(&z.to_le_bytes()[..chunk.len()]);
+                }
+            }
+        }
+    }
+}
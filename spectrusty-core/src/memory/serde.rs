@@ -114,6 +114,12 @@ impl<const LEN: usize> MemDeExt for Box<[u8;LEN]> {
     }
 }
 
+impl<const LEN: usize> MemSerExt for [u8;LEN] {
+    fn as_slice(&self) -> &[u8] {
+        &self[..]
+    }
+}
+
 impl<const LEN: usize> MemDeExt for [u8;LEN] {
     const PREFER_FROM_BYTE_BUF: bool = false;
 
@@ -29,6 +29,8 @@ pub const PAL_VC: u32 = 576/2;
 pub const PAL_HC: u32 = 704/2;
 /// Maximum border size measured in low-resolution pixels.
 pub const MAX_BORDER_SIZE: u32 = 6*8;
+/// A bitmask of all 24 character rows of the INK and PAPER area, see [Video::dirty_lines].
+pub const DIRTY_LINES_ALL: u32 = (1 << 24) - 1;
 
 /// This enum is used to select border size when rendering video frames.
 #[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
@@ -144,6 +146,18 @@ pub trait Video {
     /// for the screen bank which resides in a memory bank 7. For 16k/48k Spectrum, this method always
     /// returns `0`.
     fn visible_screen_bank(&self) -> usize { 0 }
+    /// Returns a bitmask of the character rows of the INK and PAPER area that might have changed
+    /// in the last emulated frame since the previous one, if supported.
+    ///
+    /// The bit `n` of the mask (counting from the least significant bit) represents the 8 pixel lines
+    /// of the character row `n`, where `0` is the top row. The rows without their bits set look exactly
+    /// the same as in the previously rendered frame, so the front-end can upload only the changed parts
+    /// of the rendered image. The border area is not being reported.
+    ///
+    /// Only the changes made by the emulated CPU, the screen bank swaps, the resets and the flash attribute
+    /// toggling are being tracked. After modifying the memory directly, the whole screen should be
+    /// considered as changed, see [DIRTY_LINES_ALL].
+    fn dirty_lines(&self) -> Option<u32> { None }
     /// Returns the current value of the video T-state counter.
     fn current_video_ts(&self) -> VideoTs;
     /// Modifies the current value of the video T-state counter.
@@ -289,6 +303,25 @@ pub trait VideoFrame: Copy + Debug {
     fn vc_hc_to_tstates(vc: Ts, hc: Ts) -> FTs {
         vc as FTs * Self::HTS_COUNT as FTs + hc as FTs
     }
+    /// Returns the low-resolution pixel coordinates (horizontal, vertical) of the screen being generated
+    /// at the given normalized timestamp.
+    ///
+    /// The coordinates are relative to the top left corner of the screen rendered with [BorderSize::Full],
+    /// so e.g. the first pixel of the pixel area is at ([MAX_BORDER_SIZE], [MAX_BORDER_SIZE]).
+    ///
+    /// Returns `None` if the timestamp points outside the visible area, e.g. during the beam retrace.
+    fn ts_to_pixel_position(VideoTs { vc, hc }: VideoTs) -> Option<(u32, u32)> {
+        let (width, _) = Self::screen_size_pixels(BorderSize::Full);
+        let top = Self::border_top_vsl_iter(BorderSize::Full).start;
+        let bot = Self::border_bot_vsl_iter(BorderSize::Full).end;
+        let x = 2 * hc as i32 + MAX_BORDER_SIZE as i32;
+        if (top..bot).contains(&vc) && x >= 0 && (x as u32) < width {
+            Some((x as u32, (vc - top) as u32))
+        }
+        else {
+            None
+        }
+    }
 }
 
 impl From<BorderSize> for &'static str {
@@ -6,6 +6,7 @@
     For the full copyright notice, see the lib.rs file.
 */
 //! Building blocks for rendering pixel surfaces.
+use core::convert::TryInto;
 use core::slice::IterMut;
 
 /// A trait for providing a way for placing pixels into byte buffers.
@@ -68,6 +69,22 @@ pub trait Palette {
     fn get_pixel_gray8(value: u8) -> Self::Pixel;
 }
 
+/// A trait for reading and writing pixels from and to the byte buffers without any alignment requirements.
+pub trait PixelBytes: Copy {
+    /// The number of bytes of a single pixel.
+    const SIZE: usize;
+    /// Should return a pixel read from `bytes`.
+    ///
+    /// # Panics
+    /// May panic if the length of `bytes` is not [PixelBytes::SIZE].
+    fn from_ne_bytes(bytes: &[u8]) -> Self;
+    /// Should write a pixel into `bytes`.
+    ///
+    /// # Panics
+    /// May panic if the length of `bytes` is not [PixelBytes::SIZE].
+    fn write_ne_bytes(self, bytes: &mut [u8]);
+}
+
 /// A [PixelBuffer] tool for placing pixels into byte buffers using 3 `u8` element arrays of color channels
 /// (3 bytes per pixel).
 pub struct PixelBufA24<'a> {
@@ -196,6 +213,38 @@ impl_pixel_buffer!(PixelBufP32<'a>, u32);
 impl_pixel_buffer!(PixelBufP16<'a>, u16);
 impl_pixel_buffer!(PixelBufP8<'a>,  u8);
 
+macro_rules! impl_pixel_bytes {
+    ($($pixel:ty),*) => {$(
+        impl PixelBytes for $pixel {
+            const SIZE: usize = core::mem::size_of::<$pixel>();
+
+            #[inline(always)]
+            fn from_ne_bytes(bytes: &[u8]) -> Self {
+                <$pixel>::from_ne_bytes(bytes.try_into().unwrap())
+            }
+            #[inline(always)]
+            fn write_ne_bytes(self, bytes: &mut [u8]) {
+                bytes.copy_from_slice(&self.to_ne_bytes())
+            }
+        }
+    )*};
+}
+
+impl_pixel_bytes!(u8, u16, u32);
+
+impl<const N: usize> PixelBytes for [u8;N] {
+    const SIZE: usize = N;
+
+    #[inline(always)]
+    fn from_ne_bytes(bytes: &[u8]) -> Self {
+        bytes.try_into().unwrap()
+    }
+    #[inline(always)]
+    fn write_ne_bytes(self, bytes: &mut [u8]) {
+        bytes.copy_from_slice(&self)
+    }
+}
+
 macro_rules! impl_palette {
     ($palette:ty, $pixel:ty) => {
         impl Palette for $palette {
@@ -211,6 +211,57 @@ const PLAYER_INIT_OFFSET: usize = 2;
 const PLAYER_TWO_INTERRUPT_OFFSET: usize = 9;
 
 impl AyFile {
+    /// Returns the intended duration in frames of the song at `song_index`, including its fade duration.
+    ///
+    /// Returns `None` if there is no song at `song_index` or the song duration is unknown (`0`),
+    /// in which case the song should be played indefinitely.
+    pub fn song_duration(&self, song_index: usize) -> Option<u32> {
+        self.songs.get(song_index).filter(|song| song.song_duration != 0)
+            .map(|song| u32::from(song.song_duration) + u32::from(song.fade_duration))
+    }
+    /// Returns the amplitude multiplier of the song at `song_index` for the given `frame` counted
+    /// from the start of the song.
+    ///
+    /// The amplitude is `1.0` until the song duration is reached, then it decreases linearly
+    /// over the fade duration. Returns `None` if the song has ended or there is no song at `song_index`.
+    /// If the song duration is unknown, the amplitude is always `1.0`.
+    pub fn song_amplitude(&self, song_index: usize, frame: u64) -> Option<f32> {
+        let song = self.songs.get(song_index)?;
+        let song_duration = u64::from(song.song_duration);
+        if song_duration == 0 || frame < song_duration {
+            return Some(1.0)
+        }
+        let fade_duration = u64::from(song.fade_duration);
+        let fade_frame = frame - song_duration;
+        if fade_frame < fade_duration {
+            Some((fade_duration - fade_frame) as f32 / fade_duration as f32)
+        }
+        else {
+            None
+        }
+    }
+    /// Sets the song and fade durations in frames of the song at `song_index`, e.g. to play a song
+    /// of unknown duration for a limited time.
+    ///
+    /// Returns `false` if there is no song at `song_index`.
+    pub fn set_song_duration(
+            self: Pin<&mut Self>,
+            song_index: usize,
+            song_duration: u16,
+            fade_duration: u16
+        ) -> bool
+    {
+        // only the durations are modified, so nothing is being moved out of the pinned data
+        let songs = unsafe { &mut self.get_unchecked_mut().songs };
+        match songs.get_mut(song_index) {
+            Some(song) => {
+                song.song_duration = song_duration;
+                song.fade_duration = fade_duration;
+                true
+            }
+            None => false
+        }
+    }
     /// Initializes `memory` and the `cpu` registers, creates a player routine, and loads song data into `memory`.
     /// Provide `song_index` of the desired song from this file to be played.
     ///
@@ -719,3 +770,61 @@ struct SongData {
     offset:  AYOffset,
 }
 */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // patches the durations of the first song in the raw AY file data
+    fn patch_first_song_durations(data: &mut [u8], song_duration: u16, fade_duration: u16) {
+        fn rel_ptr(data: &[u8], offset: usize) -> usize {
+            offset.wrapping_add(i16::from_be_bytes([data[offset], data[offset + 1]]) as usize)
+        }
+        let song_data = rel_ptr(data, rel_ptr(data, 18) + 2);
+        data[song_data + 4..song_data + 6].copy_from_slice(&song_duration.to_be_bytes());
+        data[song_data + 6..song_data + 8].copy_from_slice(&fade_duration.to_be_bytes());
+    }
+
+    #[test]
+    fn ay_song_duration_works() {
+        let mut data = std::fs::read("../resources/nodes_of_yesod.ay").unwrap();
+        assert_eq!(read_ay(&data[..]).unwrap().song_duration(0), None);
+        patch_first_song_durations(&mut data, 9000, 250);
+        let ay_file = read_ay(&data[..]).unwrap();
+        assert_eq!(ay_file.songs[0].song_duration, 9000);
+        assert_eq!(ay_file.songs[0].fade_duration, 250);
+        assert_eq!(ay_file.song_duration(0), Some(9250));
+        assert_eq!(ay_file.song_duration(1), None);
+        assert_eq!(ay_file.song_duration(ay_file.songs.len()), None);
+    }
+
+    #[test]
+    fn ay_song_amplitude_works() {
+        let mut data = std::fs::read("../resources/nodes_of_yesod.ay").unwrap();
+        let ay_file = read_ay(&data[..]).unwrap();
+        assert_eq!(ay_file.song_amplitude(0, 1_000_000), Some(1.0));
+        // patch the first song: 100 frames and 50 frames of fade
+        patch_first_song_durations(&mut data, 100, 50);
+        let ay_file = read_ay(&data[..]).unwrap();
+        assert_eq!(ay_file.song_duration(0), Some(150));
+        assert_eq!(ay_file.song_amplitude(0, 0), Some(1.0));
+        assert_eq!(ay_file.song_amplitude(0, 99), Some(1.0));
+        assert_eq!(ay_file.song_amplitude(0, 100), Some(1.0));
+        assert_eq!(ay_file.song_amplitude(0, 125), Some(0.5));
+        let mut prev = 1.0;
+        for frame in 101..150 {
+            let amp = ay_file.song_amplitude(0, frame).unwrap();
+            assert!(amp < prev && amp > 0.0);
+            prev = amp;
+        }
+        assert_eq!(ay_file.song_amplitude(0, 150), None);
+        assert_eq!(ay_file.song_amplitude(ay_file.songs.len(), 0), None);
+        let mut ay_file = read_ay(&data[..]).unwrap();
+        assert!(ay_file.as_mut().set_song_duration(0, 10, 0));
+        assert_eq!(ay_file.song_duration(0), Some(10));
+        assert_eq!(ay_file.song_amplitude(0, 9), Some(1.0));
+        assert_eq!(ay_file.song_amplitude(0, 10), None);
+        let nsongs = ay_file.songs.len();
+        assert!(!ay_file.as_mut().set_song_duration(nsongs, 10, 0));
+    }
+}
@@ -0,0 +1,224 @@
+/*
+    Copyright (C) 2020-2023  Rafal Michalski
+
+    This file is part of SPECTRUSTY, a Rust library for building emulators.
+
+    For the full copyright notice, see the lib.rs file.
+*/
+/*! **HOBETA** file format utilities.
+
+**HOBETA** files wrap a single TR-DOS file, so individual files can be imported into and exported from
+the TR-DOS disk images one at a time.
+
+The **HOBETA** file header (LSB first):
+
+| offset | size | description                                                         |
+|--------|------|---------------------------------------------------------------------|
+|      0 |    8 | The TR-DOS file name with trailing spaces.                          |
+|      8 |    1 | The TR-DOS file type, e.g. `B`, `C`, `D` or `#`.                    |
+|      9 |    2 | The start address of a `C` file or the total length of a `B` file.  |
+|     11 |    2 | The length of the file in bytes.                                    |
+|     13 |    1 | Always `0`.                                                         |
+|     14 |    1 | The length of the file in 256 byte sectors.                         |
+|     15 |    2 | The checksum of the previous 15 bytes.                              |
+|     17 |    * | The file data, padded to the length of the occupied sectors.        |
+
+The first 14 bytes of the header, excluding the byte at the offset `13`, constitute the TR-DOS directory
+entry of the file, without its disk location.
+*/
+use core::convert::TryFrom;
+use std::borrow::Cow;
+use std::io::{self, Read, Write, Error, ErrorKind};
+
+/// The size of the **HOBETA** header in bytes.
+pub const HOBETA_HEADER_SIZE: usize = 17;
+/// The size of the TR-DOS sector in bytes.
+pub const TRDOS_SECTOR_SIZE: usize = 256;
+
+/// Represents the **HOBETA** file header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HobetaHeader {
+    /// The TR-DOS file name.
+    pub name: [u8;8],
+    /// The TR-DOS file type.
+    pub file_type: u8,
+    /// The start address of a `C` file or the total length of a `B` file.
+    pub start: u16,
+    /// The length of the file in bytes.
+    pub length: u16,
+    /// The length of the file in 256 byte sectors.
+    pub sectors: u8
+}
+
+impl Default for HobetaHeader {
+    fn default() -> Self {
+        HobetaHeader {
+            name: [b' ';8],
+            file_type: b'C',
+            start: 0,
+            length: 0,
+            sectors: 0
+        }
+    }
+}
+
+/// Calculates the **HOBETA** checksum of the first 15 bytes of the header.
+pub fn checksum(header: &[u8]) -> u16 {
+    header.iter().take(15).enumerate().fold(0u16, |acc, (i, &b)| {
+        acc.wrapping_add(u16::from(b).wrapping_mul(257)).wrapping_add(i as u16)
+    })
+}
+
+impl HobetaHeader {
+    /// Creates a new code (`C`) file header with the given `start` address and `length`.
+    ///
+    /// The number of sectors is calculated from the `length`.
+    ///
+    /// # Errors
+    /// Returns an error if the `length` exceeds 255 sectors, the maximum size of a TR-DOS file.
+    pub fn new_code(start: u16, length: u16) -> Result<Self, &'static str> {
+        let sectors = sectors_from_length(length).ok_or("HOBETA: file is too large")?;
+        Ok(HobetaHeader { start, length, sectors, ..Default::default() })
+    }
+    /// Changes `name`, builder style.
+    pub fn with_name<S: AsRef<[u8]>>(mut self, name: S) -> Self {
+        let name = name.as_ref();
+        let bname = &name[0..name.len().min(8)];
+        self.name[0..bname.len()].copy_from_slice(bname);
+        for p in self.name[bname.len()..].iter_mut() {
+            *p = b' ';
+        }
+        self
+    }
+    /// Changes `file_type`, builder style.
+    pub fn with_file_type(mut self, file_type: u8) -> Self {
+        self.file_type = file_type;
+        self
+    }
+    /// Returns a header name as a string.
+    #[inline]
+    pub fn name_str(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.name)
+    }
+    /// Returns the size of the file data in bytes, including the padding of the last sector.
+    #[inline]
+    pub fn data_size(&self) -> usize {
+        usize::from(self.sectors) * TRDOS_SECTOR_SIZE
+    }
+    /// Returns the header as an array of bytes, including the checksum.
+    pub fn to_bytes(&self) -> [u8;HOBETA_HEADER_SIZE] {
+        let mut bytes = [0u8;HOBETA_HEADER_SIZE];
+        bytes[0..8].copy_from_slice(&self.name);
+        bytes[8] = self.file_type;
+        bytes[9..11].copy_from_slice(&self.start.to_le_bytes());
+        bytes[11..13].copy_from_slice(&self.length.to_le_bytes());
+        bytes[14] = self.sectors;
+        let check = checksum(&bytes);
+        bytes[15..17].copy_from_slice(&check.to_le_bytes());
+        bytes
+    }
+}
+
+/// Returns the number of sectors needed to store the `length` bytes or `None` if it exceeds 255 sectors.
+fn sectors_from_length(length: u16) -> Option<u8> {
+    u8::try_from((u32::from(length) + 0xFF) >> 8).ok()
+}
+
+impl TryFrom<&[u8]> for HobetaHeader {
+    type Error = &'static str;
+    /// Parses the **HOBETA** header from the first [HOBETA_HEADER_SIZE] bytes and validates its checksum.
+    fn try_from(header: &[u8]) -> Result<Self, Self::Error> {
+        if header.len() < HOBETA_HEADER_SIZE {
+            return Err("HOBETA: header is too short")
+        }
+        if u16::from_le_bytes([header[15], header[16]]) != checksum(header) {
+            return Err("HOBETA: invalid header checksum")
+        }
+        let mut name = [0u8;8];
+        name.copy_from_slice(&header[0..8]);
+        Ok(HobetaHeader {
+            name,
+            file_type: header[8],
+            start: u16::from_le_bytes([header[9], header[10]]),
+            length: u16::from_le_bytes([header[11], header[12]]),
+            sectors: header[14]
+        })
+    }
+}
+
+/// Reads the **HOBETA** file from `rd` and returns its header and the file data.
+///
+/// The returned data includes the padding of the last sector, so its length is always equal
+/// to [HobetaHeader::data_size].
+///
+/// # Errors
+/// This function will return an error of [ErrorKind::InvalidData] kind if the header checksum is invalid
+/// or an error of [ErrorKind::UnexpectedEof] kind if the file is shorter than its header declares.
+/// Other errors may also be returned from attempts to read the file.
+pub fn read_hobeta<R: Read>(mut rd: R) -> io::Result<(HobetaHeader, Vec<u8>)> {
+    let mut bytes = [0u8;HOBETA_HEADER_SIZE];
+    rd.read_exact(&mut bytes)?;
+    let header = HobetaHeader::try_from(&bytes[..])
+                 .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+    let mut data = vec![0u8;header.data_size()];
+    rd.read_exact(&mut data)?;
+    Ok((header, data))
+}
+
+/// Writes the **HOBETA** file with the given `header` and `data` to `wr`.
+///
+/// The `data` is padded with zeroes to the length of the occupied sectors.
+///
+/// # Errors
+/// This function will return an error of [ErrorKind::InvalidInput] kind if the `data` doesn't fit in
+/// the number of sectors declared in the `header`. Other errors may also be returned from attempts
+/// to write the file.
+pub fn write_hobeta<W: Write>(mut wr: W, header: &HobetaHeader, data: &[u8]) -> io::Result<()> {
+    let data_size = header.data_size();
+    if data.len() > data_size {
+        return Err(Error::new(ErrorKind::InvalidInput, "HOBETA: data is larger than the declared sectors"))
+    }
+    wr.write_all(&header.to_bytes())?;
+    wr.write_all(data)?;
+    io::copy(&mut io::repeat(0).take((data_size - data.len()) as u64), &mut wr)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hobeta_works() {
+        let data: Vec<u8> = (0..300u16).map(|i| i as u8).collect();
+        let header = HobetaHeader::new_code(0x8000, data.len() as u16).unwrap().with_name("hobeta");
+        assert_eq!(header.sectors, 2);
+        let mut file = Vec::new();
+        write_hobeta(&mut file, &header, &data).unwrap();
+        assert_eq!(file.len(), HOBETA_HEADER_SIZE + 512);
+        assert_eq!(&file[..15], b"hobeta  C\x00\x80\x2C\x01\x00\x02");
+        // 105 + 257 * sum
+        let sum: u16 = file[..15].iter().map(|&b| u16::from(b)).sum();
+        let check = 105u16.wrapping_add(sum.wrapping_mul(257));
+        assert_eq!(&file[15..17], &check.to_le_bytes());
+        let (header, body) = read_hobeta(&file[..]).unwrap();
+        assert_eq!(header.name_str(), "hobeta  ");
+        assert_eq!(header.file_type, b'C');
+        assert_eq!(header.start, 0x8000);
+        assert_eq!(header.length, 300);
+        assert_eq!(header.data_size(), 512);
+        assert_eq!(&body[..300], &data[..]);
+        assert!(body[300..].iter().all(|&b| b == 0));
+        // errors
+        file[16] ^= 1;
+        assert_eq!(read_hobeta(&file[..]).unwrap_err().kind(), ErrorKind::InvalidData);
+        file[16] ^= 1;
+        assert_eq!(read_hobeta(&file[..400]).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+        let header = HobetaHeader::new_code(0x8000, 256).unwrap();
+        assert_eq!(write_hobeta(io::sink(), &header, &data).unwrap_err().kind(), ErrorKind::InvalidInput);
+        // the largest TR-DOS file occupies 255 sectors
+        assert_eq!(HobetaHeader::new_code(0, 255 * 256).unwrap().sectors, 255);
+        assert!(HobetaHeader::new_code(0, 255 * 256 + 1).is_err());
+        assert!(HobetaHeader::new_code(0, u16::MAX).is_err());
+    }
+}
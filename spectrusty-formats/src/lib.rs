@@ -27,8 +27,11 @@ pub mod sna;
 pub mod tap;
 pub mod snapshot;
 pub mod scr;
+pub mod mlt;
 pub mod z80;
-// pub mod tzx;
+pub mod tzx;
+pub mod wav;
+pub mod hobeta;
 
 /// A trait that extends [Read] with methods that ease reading from chunked files.
 pub trait ReadExactEx: Read {
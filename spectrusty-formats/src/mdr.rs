@@ -99,12 +99,23 @@ pub trait MicroCartridgeExt: Sized {
     /// Returns an error if a file with the same name already exists or if there is not enough free sectors
     /// to store the complete file.
     ///
+    /// Returns an error of [io::ErrorKind::PermissionDenied] kind if the cartridge is write protected.
+    ///
     /// In case of an error of [io::ErrorKind::WriteZero] kind, you may delete the partial file data
     /// with [MicroCartridgeExt::erase_file].
     fn store_file<S: AsRef<[u8]>, R: Read>(&mut self, file_name: S, is_save: bool, rd: R) -> io::Result<u8>;
     /// Marks all sectors (including copies and unclosed files) belonging to a provided `file_name` as free.
     /// Returns the number of erased sectors.
     fn erase_file<S: AsRef<[u8]>>(&mut self, file_name: S) -> u8;
+    /// Rewrites all files into the formatted sectors, removing duplicate file copies and unclosed
+    /// files, and packing file blocks into consecutive sectors.
+    ///
+    /// Returns the number of freed sectors on success.
+    ///
+    /// Returns an error of [io::ErrorKind::PermissionDenied] kind if the cartridge is write protected
+    /// or an error of [io::ErrorKind::InvalidData] kind if the sectors or files are not valid.
+    /// In case of an error the content of the cartridge is left unmodified.
+    fn compact(&mut self) -> io::Result<usize>;
     /// Retrieves content of a binary file and writes it to a *TAP* chunk writer with
     /// a proper *TAP* header.
     ///
@@ -846,6 +857,9 @@ impl MicroCartridgeExt for MicroCartridge {
             mut rd: R
         ) -> io::Result<u8>
     {
+        if self.is_write_protected() {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, "cartridge is write protected"))
+        }
         let file_name = file_name.as_ref();
         if let Some((index, _)) = self.iter_with_indices().find(|(_,s)|
                                     !s.is_free() && s.file_name_matches(file_name)) {
@@ -890,6 +904,45 @@ impl MicroCartridgeExt for MicroCartridge {
         block_seq
     }
 
+    fn compact(&mut self) -> io::Result<usize> {
+        if self.is_write_protected() {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, "cartridge is write protected"))
+        }
+        let catalog = match self.catalog() {
+            Ok(Some(catalog)) => catalog,
+            Ok(None) => return Ok(0),
+            Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e))
+        };
+        let mut names: Vec<_> = catalog.files.into_keys().collect();
+        names.sort_unstable();
+        let mut files = Vec::with_capacity(names.len());
+        for name in names {
+            let mut is_save = false;
+            let mut data = Vec::new();
+            for sector in self.file_sectors(name) {
+                let sector = sector.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                if sector.file_block_seq() == 0 {
+                    is_save = sector.is_save_file();
+                }
+                data.extend_from_slice(sector.data_record());
+            }
+            files.push((name, is_save, data));
+        }
+        // rewrite files into a copy, so the cartridge is left unmodified if storing fails
+        let mut compacted = self.clone();
+        for sector in &mut compacted {
+            if !sector.is_free() {
+                sector.erase();
+            }
+        }
+        for (name, is_save, data) in files {
+            compacted.store_file(name, is_save, &data[..])?;
+        }
+        let freed = self.count_sectors_in_use() - compacted.count_sectors_in_use();
+        *self = compacted;
+        Ok(freed)
+    }
+
     fn file_sector_ids_unordered<S: AsRef<[u8]>>(
             &self,
             file_name: S
@@ -1056,6 +1109,59 @@ mod tests {
     use rand::prelude::*;
     use crate::tap;
 
+    #[test]
+    fn mdr_compact_works() {
+        let mut mdr = MicroCartridge::new_formatted(10, "compact");
+        assert_eq!(mdr.compact().unwrap(), 0);
+        let mut data = vec![0u8;1000];
+        thread_rng().fill(&mut data[..]);
+        assert_eq!(mdr.store_file("temp", false, io::Cursor::new(&[1u8;100])).unwrap(), 1);
+        assert_eq!(mdr.store_file("data", false, io::Cursor::new(&data[..])).unwrap(), 2);
+        assert_eq!(mdr.erase_file("temp"), 1);
+        // make a stale copy of the file in the free sectors
+        let blocks: Vec<_> = mdr.file_sector_ids_unordered("data").map(|sb| sb.index).collect();
+        assert_eq!(blocks, [1, 2]);
+        for (src, dst) in blocks.into_iter().zip([0, 3]) {
+            mdr[dst].data = mdr[src].data;
+        }
+        assert_eq!(mdr.file_info("data").unwrap().unwrap(),
+                CatFile { size: 1000, blocks: 4, copies: 2, file_type: CatFileType::Data});
+        assert_eq!(mdr.count_sectors_in_use(), 4);
+        assert_eq!(mdr.compact().unwrap(), 2);
+        assert_eq!(mdr.count_sectors_in_use(), 2);
+        assert_eq!(mdr.file_info("data").unwrap().unwrap(),
+                CatFile { size: 1000, blocks: 2, copies: 1, file_type: CatFileType::Data});
+        let blocks: Vec<_> = mdr.file_sector_ids_unordered("data").collect();
+        assert_eq!(blocks, [SectorBlock { index: 0, block_seq: 0 }, SectorBlock { index: 1, block_seq: 1 }]);
+        assert_eq!(mdr.validate_sectors().unwrap(), 10);
+        let mut wr = io::Cursor::new(Vec::new());
+        assert_eq!(mdr.retrieve_file("data", &mut wr).unwrap().unwrap(), (CatFileType::Data, 1000));
+        assert_eq!(wr.get_ref(), &data);
+        assert_eq!(mdr.compact().unwrap(), 0);
+        mdr.set_write_protected(true);
+        assert_eq!(mdr.compact().err().unwrap().kind(), io::ErrorKind::PermissionDenied);
+    }
+
+    #[test]
+    fn mdr_write_protect_works() {
+        let mut mdr = MicroCartridge::new_formatted(10, "protected");
+        assert_eq!(mdr.is_write_protected(), false);
+        assert_eq!(mdr.store_file("hello", false, io::Cursor::new(b"world")).unwrap(), 1);
+        mdr.set_write_protected(true);
+        assert_eq!(mdr.is_write_protected(), true);
+        let err = mdr.store_file("hello again", false, io::Cursor::new(b"world")).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        assert_eq!(err.to_string(), "cartridge is write protected");
+        let catalog = mdr.catalog().unwrap().unwrap();
+        assert_eq!(catalog.files.len(), 1);
+        assert_eq!(catalog.sectors_free, 9);
+        let mut wr = io::Cursor::new(Vec::new());
+        assert_eq!(mdr.retrieve_file("hello", &mut wr).unwrap().unwrap(), (CatFileType::Data, 5));
+        assert_eq!(wr.get_ref(), b"world");
+        mdr.set_write_protected(false);
+        assert_eq!(mdr.store_file("hello again", false, io::Cursor::new(b"world")).unwrap(), 1);
+    }
+
     #[test]
     fn mdr_works() {
         let mdr = MicroCartridge::default();
@@ -0,0 +1,218 @@
+/*
+    Copyright (C) 2020-2023  Rafal Michalski
+
+    This file is part of SPECTRUSTY, a Rust library for building emulators.
+
+    For the full copyright notice, see the lib.rs file.
+*/
+/*! **MLT** multicolor screen file format utilities.
+
+The **MLT** file consists of 12288 bytes:
+
+| offset | size | description                                                                   |
+|--------|------|-------------------------------------------------------------------------------|
+|      0 | 6144 | Ink/paper pixels in the standard screen memory layout.                        |
+|   6144 | 6144 | Attributes, one for each 8x1 pixel cell, ordered linearly line after line.    |
+
+Such an image can be displayed exactly by chipsets supporting the SCLD hi-color screen mode.
+On other chipsets only an approximation with one attribute per 8x8 pixel cell can be shown.
+*/
+use std::io::{self, Read, Write};
+
+use spectrusty_core::video::{pixel_line_offset, color_line_offset};
+use crate::scr::{ScrMode, ScreenDataProvider};
+
+const PIXELS_SIZE: usize = 6144;
+const MLT_SIZE: usize = PIXELS_SIZE * 2;
+const COLUMNS: usize = 32;
+const LINES: usize = 192;
+
+/// Utilities for loading and saving **MLT** files.
+///
+/// Methods of this trait are implemented automatically for types that implement [ScreenDataProvider].
+pub trait LoadMlt: ScreenDataProvider {
+    /// Attempts to read the `MLT` file from the `src` and load it into the underlying implementation.
+    ///
+    /// If the underlying implementation supports [ScrMode::HighColor] the screen mode is being switched
+    /// to it and the image is loaded exactly. Otherwise the screen mode is being switched to
+    /// [ScrMode::Classic] and each 8x8 pixel cell gets the attribute most commonly used by its lines.
+    ///
+    /// # Errors
+    /// This function will return an error if the classic screen mode can't be set by the underlying
+    /// implementation. Other errors may also be returned from attempts to read the file.
+    fn load_mlt<R: Read>(&mut self, src: R) -> io::Result<()>;
+    /// Attempts to save the screen from the underlying implementation and write as the `MLT`
+    /// file into the `dst`.
+    ///
+    /// In the classic screen modes the attributes of each 8x8 pixel cell are being repeated for all its lines.
+    ///
+    /// # Errors
+    /// This function will return an error if the current screen mode is [ScrMode::HighRes].
+    /// Other errors may also be returned from attempts to write the file.
+    fn save_mlt<W: Write>(&self, dst: W) -> io::Result<()>;
+}
+
+impl<T> LoadMlt for T where T: ScreenDataProvider {
+    fn load_mlt<R: Read>(&mut self, mut src: R) -> io::Result<()> {
+        let mut mlt = vec![0u8; MLT_SIZE];
+        src.read_exact(&mut mlt)?;
+        let (pixels, attrs) = mlt.split_at(PIXELS_SIZE);
+        if self.set_screen_mode(ScrMode::HighColor(false)) {
+            self.screen_primary_mut()[..PIXELS_SIZE].copy_from_slice(pixels);
+            let screen = self.screen_secondary_mut();
+            for (y, line) in attrs.chunks_exact(COLUMNS).enumerate() {
+                let offset = pixel_line_offset(y);
+                screen[offset..offset + COLUMNS].copy_from_slice(line);
+            }
+        }
+        else if self.set_screen_mode(ScrMode::Classic(false)) {
+            let screen = self.screen_primary_mut();
+            screen[..PIXELS_SIZE].copy_from_slice(pixels);
+            for (y, cell_lines) in attrs.chunks_exact(COLUMNS * 8).enumerate() {
+                let offset = PIXELS_SIZE + color_line_offset(y * 8);
+                for x in 0..COLUMNS {
+                    screen[offset + x] = most_common_attr(cell_lines.iter().skip(x).step_by(COLUMNS));
+                }
+            }
+        }
+        else {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Screen format not supported"));
+        }
+        Ok(())
+    }
+
+    fn save_mlt<W: Write>(&self, mut dst: W) -> io::Result<()> {
+        match self.get_screen_mode() {
+            ScrMode::Classic(..) => {
+                let screen = self.screen_primary_ref();
+                dst.write_all(&screen[..PIXELS_SIZE])?;
+                for y in 0..LINES {
+                    let offset = PIXELS_SIZE + color_line_offset(y);
+                    dst.write_all(&screen[offset..offset + COLUMNS])?;
+                }
+            }
+            ScrMode::HighColor(..) => {
+                dst.write_all(&self.screen_primary_ref()[..PIXELS_SIZE])?;
+                let screen = self.screen_secondary_ref();
+                for y in 0..LINES {
+                    let offset = pixel_line_offset(y);
+                    dst.write_all(&screen[offset..offset + COLUMNS])?;
+                }
+            }
+            ScrMode::HighRes(..) => {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, "Screen mode not supported"));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the most common attribute, preferring the one found first in case of a tie.
+fn most_common_attr<'a, I: Iterator<Item=&'a u8> + Clone>(attrs: I) -> u8 {
+    let mut best = (0, 0);
+    for &attr in attrs.clone() {
+        let count = attrs.clone().filter(|&&a| a == attr).count();
+        if count > best.1 {
+            best = (attr, count);
+        }
+    }
+    best.0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use crate::scr::ScreenArray;
+    use super::*;
+
+    struct TestScreen {
+        hi_color_supported: bool,
+        mode: ScrMode,
+        primary: ScreenArray,
+        secondary: ScreenArray
+    }
+
+    impl TestScreen {
+        fn new(hi_color_supported: bool) -> Self {
+            TestScreen {
+                hi_color_supported,
+                mode: ScrMode::Classic(false),
+                primary: [0; 6912],
+                secondary: [0; 6912]
+            }
+        }
+    }
+
+    impl ScreenDataProvider for TestScreen {
+        fn get_screen_mode(&self) -> ScrMode {
+            self.mode
+        }
+        fn set_screen_mode(&mut self, mode: ScrMode) -> bool {
+            match mode {
+                ScrMode::Classic(false) => {}
+                ScrMode::HighColor(false) if self.hi_color_supported => {}
+                _ => return false
+            }
+            self.mode = mode;
+            true
+        }
+        fn screen_primary_ref(&self) -> &ScreenArray {
+            &self.primary
+        }
+        fn screen_primary_mut(&mut self) -> &mut ScreenArray {
+            &mut self.primary
+        }
+        fn screen_secondary_ref(&self) -> &ScreenArray {
+            &self.secondary
+        }
+        fn screen_secondary_mut(&mut self) -> &mut ScreenArray {
+            &mut self.secondary
+        }
+    }
+
+    #[test]
+    fn load_mlt_works() {
+        let mut mlt = vec![0u8; MLT_SIZE];
+        for (i, p) in mlt[..PIXELS_SIZE].iter_mut().enumerate() {
+            *p = i as u8;
+        }
+        // each line of the cell at (1, 2) gets a different attribute, with 0x47 used the most
+        for (y, attr) in [0x47, 0x38, 0x47, 0x0f, 0x47, 0x38, 0x47, 0x07].iter().enumerate() {
+            mlt[PIXELS_SIZE + (16 + y) * COLUMNS + 1] = *attr;
+        }
+        // the bottom right cell
+        for y in 184..192 {
+            mlt[PIXELS_SIZE + y * COLUMNS + 31] = 0x16;
+        }
+        // hi-color
+        let mut screen = TestScreen::new(true);
+        screen.load_mlt(Cursor::new(&mlt)).unwrap();
+        assert_eq!(screen.mode, ScrMode::HighColor(false));
+        assert_eq!(&screen.primary[..PIXELS_SIZE], &mlt[..PIXELS_SIZE]);
+        assert_eq!(screen.secondary[0x0041], 0x47); // line 16
+        assert_eq!(screen.secondary[0x0141], 0x38); // line 17
+        assert_eq!(screen.secondary[0x0341], 0x0f); // line 19
+        assert_eq!(screen.secondary[0x0741], 0x07); // line 23
+        assert_eq!(screen.secondary[0x17ff], 0x16); // line 191
+        assert_eq!(screen.secondary.iter().filter(|&&a| a != 0).count(), 16);
+        let mut saved = Vec::new();
+        screen.save_mlt(&mut saved).unwrap();
+        assert_eq!(saved, mlt);
+        // classic approximation
+        let mut screen = TestScreen::new(false);
+        screen.load_mlt(Cursor::new(&mlt)).unwrap();
+        assert_eq!(screen.mode, ScrMode::Classic(false));
+        assert_eq!(&screen.primary[..PIXELS_SIZE], &mlt[..PIXELS_SIZE]);
+        assert_eq!(screen.primary[PIXELS_SIZE + 2 * COLUMNS + 1], 0x47);
+        assert_eq!(screen.primary[PIXELS_SIZE + 23 * COLUMNS + 31], 0x16);
+        assert_eq!(screen.primary[PIXELS_SIZE..].iter().filter(|&&a| a != 0).count(), 2);
+        let mut saved = Vec::new();
+        screen.save_mlt(&mut saved).unwrap();
+        assert_eq!(&saved[..PIXELS_SIZE], &mlt[..PIXELS_SIZE]);
+        for y in 16..24 {
+            assert_eq!(saved[PIXELS_SIZE + y * COLUMNS + 1], 0x47);
+        }
+        // too short
+        assert!(screen.load_mlt(Cursor::new(&mlt[..MLT_SIZE - 1])).is_err());
+    }
+}
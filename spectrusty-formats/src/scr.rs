@@ -50,6 +50,29 @@ pub enum ScrMode {
     HighRes(u8, bool),
 }
 
+/// Writes the 64 ULAplus `palette` entries to `dst` as the palette chunk of the **SCR** file.
+///
+/// The chunk should be written directly after the screen data, resulting in one of the `+palette`
+/// **SCR** file variants.
+///
+/// # Errors
+/// This function may return an error from attempts to write the file.
+pub fn write_scr_palette<W: Write>(palette: &[u8;PALETTE_SIZE as usize], mut dst: W) -> io::Result<()> {
+    dst.write_all(palette)
+}
+
+/// Reads the 64 ULAplus palette entries from the palette chunk of the **SCR** file in `src` into `palette`.
+///
+/// The palette chunk is being read from the last 64 bytes of the file. The file size is not being validated,
+/// so the caller should make sure it's one of the `+palette` **SCR** file variants.
+///
+/// # Errors
+/// This function may return an error from attempts to read the file.
+pub fn read_scr_palette<R: Read + Seek>(palette: &mut [u8;PALETTE_SIZE as usize], mut src: R) -> io::Result<()> {
+    src.seek(SeekFrom::End(-(PALETTE_SIZE as i64)))?;
+    src.read_exact(palette)
+}
+
 /// Utilities for loading and saving **SCR** files.
 ///
 /// Methods of this trait are implemented automatically for types that implement [ScreenDataProvider].
@@ -172,7 +195,7 @@ impl<T> LoadScr for T where T: ScreenDataProvider {
         if let ScrMode::Classic(true)|
                ScrMode::HighColor(true)|
                ScrMode::HighRes(.., true) = mode {
-            dst.write_all(self.screen_palette_ref())?;
+            write_scr_palette(self.screen_palette_ref(), dst.by_ref())?;
         }
         Ok(())
     }
@@ -21,6 +21,9 @@ use spectrusty_core::video::{Video, BorderColor};
 use spectrusty_core::memory::{ZxMemory, ZxMemoryError};
 use spectrusty_peripherals::ay::AyRegister;
 
+mod inspect;
+pub use inspect::*;
+
 #[non_exhaustive]
 #[derive(Debug,Clone,Copy,PartialEq,Eq,Hash)]
 pub enum ComputerModel {
@@ -0,0 +1,532 @@
+/*
+    Copyright (C) 2020-2023  Rafal Michalski
+
+    This file is part of SPECTRUSTY, a Rust library for building emulators.
+
+    For the full copyright notice, see the lib.rs file.
+*/
+use core::fmt;
+use std::io::{self, Read, Cursor};
+
+use spectrusty_core::chip::ReadEarMode;
+use spectrusty_core::clock::FTs;
+use spectrusty_core::memory::{ScreenArray, ZxMemoryError};
+use spectrusty_core::video::BorderColor;
+use spectrusty_peripherals::ay::AyRegister;
+
+use crate::{sna, z80};
+use super::*;
+
+/// The size in bytes of the 128k **SNA** file.
+const SNA128_LENGTH: usize = 131103;
+/// The size in bytes of the 128k **SNA** file with the currently paged bank included twice.
+const SNA128_DUP_LENGTH: usize = 147487;
+
+/// The snapshot file format variant recognized by [inspect_snapshot] or [detect_format].
+#[non_exhaustive]
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Hash)]
+pub enum SnapshotFormat {
+    /// The 48k **SNA** file.
+    Sna48,
+    /// The 128k extended **SNA** file.
+    Sna128,
+    /// The version 1 **Z80** file.
+    Z80v1,
+    /// The version 2 **Z80** file.
+    Z80v2,
+    /// The version 3 **Z80** file.
+    Z80v3,
+    /// The **SZX** (zx-state) file, recognized only by [detect_format].
+    Szx,
+    /// The **SP** file, recognized only by [detect_format].
+    Sp,
+}
+
+/// The information about the snapshot file returned by [inspect_snapshot].
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct SnapshotInfo {
+    /// The recognized format of the file.
+    pub format: SnapshotFormat,
+    /// The model the snapshot targets.
+    pub model: ComputerModel,
+    /// The extensions required by the snapshot.
+    pub extensions: Extensions,
+    /// The size of RAM in bytes covered by the snapshot's memory blocks.
+    pub ram_size: usize,
+    /// The joystick selected in the snapshot, if any.
+    pub joystick: Option<JoystickModel>,
+    /// The `AY-3-891x` sound processor found in the snapshot, if any.
+    pub ay: Option<Ay3_891xDevice>,
+}
+
+/// The error returned by [inspect_snapshot].
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// The data ends before the header could be read completely.
+    Truncated,
+    /// The memory block has less data than the memory range it should be loaded into.
+    InvalidBlockLength {
+        /// The memory range of the block.
+        range: MemoryRange,
+        /// The expected length of the block.
+        expected: usize,
+        /// The actual length of the block.
+        found: usize
+    },
+    /// The snapshot data is malformed, the message describes the problem.
+    InvalidData(String),
+    /// An other I/O error.
+    Io(io::Error)
+}
+
+impl std::error::Error for SnapshotError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SnapshotError::Io(err) => Some(err),
+            _ => None
+        }
+    }
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Truncated => f.write_str("Snapshot data is truncated"),
+            SnapshotError::InvalidBlockLength { range, expected, found } => write!(f,
+                "Snapshot memory block {:?} has {} bytes instead of {}", range, found, expected),
+            SnapshotError::InvalidData(msg) => write!(f, "Invalid snapshot data: {}", msg),
+            SnapshotError::Io(err) => err.fmt(f)
+        }
+    }
+}
+
+impl From<io::Error> for SnapshotError {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::UnexpectedEof => SnapshotError::Truncated,
+            io::ErrorKind::InvalidData => SnapshotError::InvalidData(err.to_string()),
+            _ => SnapshotError::Io(err)
+        }
+    }
+}
+
+/// Validates the structure of a **Z80** or **SNA** snapshot given as `bytes` without loading it into
+/// an emulated model.
+///
+/// The format is recognized by the size of the data: the files of the exact size of the 48k or
+/// 128k **SNA** file are considered **SNA** snapshots, anything else is being inspected as a **Z80**
+/// snapshot.
+///
+/// On success returns the information about the model the snapshot targets.
+///
+/// # Errors
+/// Returns an error describing the first structural problem found in the data.
+pub fn inspect_snapshot(bytes: &[u8]) -> Result<SnapshotInfo, SnapshotError> {
+    let mut inspector = Inspector::default();
+    let (format, res) = match bytes.len() as u64 {
+        sna::SNA_LENGTH => {
+            (SnapshotFormat::Sna48, sna::load_sna48(bytes, &mut inspector))
+        }
+        len if len == SNA128_LENGTH as u64 || len == SNA128_DUP_LENGTH as u64 => {
+            (SnapshotFormat::Sna128, sna::load_sna(Cursor::new(bytes), &mut inspector))
+        }
+        _ => (z80_format(bytes), z80::load_z80(bytes, &mut inspector))
+    };
+    if let Err(err) = res {
+        return Err(inspector.error.take().unwrap_or_else(|| err.into()))
+    }
+    let Inspector { model, extensions, ram_size, joystick, ay, .. } = inspector;
+    let model = model.ok_or_else(|| SnapshotError::InvalidData("model not selected".into()))?;
+    Ok(SnapshotInfo { format, model, extensions, ram_size, joystick, ay })
+}
+
+/// Detects the snapshot file format from the content of the file given as `bytes`, regardless of
+/// the file name extension.
+///
+/// * **SZX** files are recognized by the `ZXST` magic.
+/// * **SP** files are recognized by the `SP` magic and the data length matching the header.
+/// * **SNA** files are recognized by their exact size and the sane values of the interrupt mode
+///   and the border color.
+/// * Version 2 and 3 **Z80** files are recognized by the `PC` register being `0` and the length
+///   of the additional header.
+/// * Version 1 **Z80** files are recognized by the size of the uncompressed memory or by the end
+///   marker of the compressed memory.
+///
+/// Returns `None` if the format could not be recognized.
+pub fn detect_format(bytes: &[u8]) -> Option<SnapshotFormat> {
+    const SZX_MAGIC: &[u8] = b"ZXST";
+    const SP_MAGIC: &[u8] = b"SP";
+    const SP_HEADER_LENGTH: usize = 38;
+    const Z80_HEADER_LENGTH: usize = 30;
+    const Z80V1_MEMORY_LENGTH: usize = 0xC000;
+    const Z80V1_END_MARKER: &[u8] = &[0x00, 0xED, 0xED, 0x00];
+
+    if bytes.starts_with(SZX_MAGIC) {
+        return Some(SnapshotFormat::Szx)
+    }
+    if bytes.starts_with(SP_MAGIC) {
+        if let Some(&[lo, hi]) = bytes.get(2..4) {
+            if bytes.len() == SP_HEADER_LENGTH + u16::from_le_bytes([lo, hi]) as usize {
+                return Some(SnapshotFormat::Sp)
+            }
+        }
+    }
+    let sna_format = match bytes.len() {
+        len if len as u64 == sna::SNA_LENGTH => Some(SnapshotFormat::Sna48),
+        SNA128_LENGTH|SNA128_DUP_LENGTH => Some(SnapshotFormat::Sna128),
+        _ => None
+    };
+    if let Some(format) = sna_format {
+        // the interrupt mode and the border color
+        if bytes[25] <= 2 && bytes[26] <= 7 {
+            return Some(format)
+        }
+    }
+    if bytes.len() <= Z80_HEADER_LENGTH {
+        return None
+    }
+    if bytes[6..8] == [0, 0] {
+        let format = match bytes.get(30..32) {
+            Some([23, 0]) => SnapshotFormat::Z80v2,
+            Some([54, 0]|[55, 0]) => SnapshotFormat::Z80v3,
+            _ => return None
+        };
+        return Some(format)
+    }
+    let compressed = bytes[12] != 0xFF && bytes[12] & 0x20 != 0;
+    if compressed && bytes.ends_with(Z80V1_END_MARKER) ||
+       !compressed && bytes.len() == Z80_HEADER_LENGTH + Z80V1_MEMORY_LENGTH
+    {
+        return Some(SnapshotFormat::Z80v1)
+    }
+    None
+}
+
+/// Extracts the screen data of a **Z80** or **SNA** snapshot given as `bytes` without loading it into
+/// an emulated model, e.g. to display a thumbnail of the snapshot.
+///
+/// The `format` should be one of the **SNA** or **Z80** formats, as returned by [detect_format] or
+/// [inspect_snapshot]. The compressed **Z80** memory blocks are being decompressed as needed.
+///
+/// For the 128k models the screen is being taken from the memory bank selected as the screen bank by
+/// the memory paging port of the snapshot. For the Timex models the primary screen is always returned.
+///
+/// # Errors
+/// Returns an error if the `format` is not supported or the snapshot data is malformed.
+pub fn extract_screen(bytes: &[u8], format: SnapshotFormat) -> Result<ScreenArray, SnapshotError> {
+    let mut extractor = ScreenExtractor::default();
+    let res = match format {
+        SnapshotFormat::Sna48 => sna::load_sna48(bytes, &mut extractor),
+        SnapshotFormat::Sna128 => sna::load_sna(Cursor::new(bytes), &mut extractor),
+        SnapshotFormat::Z80v1|SnapshotFormat::Z80v2|
+        SnapshotFormat::Z80v3 => z80::load_z80(bytes, &mut extractor),
+        _ => return Err(SnapshotError::InvalidData("unsupported snapshot format".into()))
+    };
+    res?;
+    let ScreenExtractor { model, port7ffd, mut screens } = extractor;
+    let index = match model {
+        Some(ComputerModel::Spectrum128|ComputerModel::SpectrumPlus2|
+             ComputerModel::SpectrumPlus2A|ComputerModel::SpectrumPlus3|
+             ComputerModel::SpectrumPlus3e|ComputerModel::SpectrumSE) => {
+            if Ula128MemFlags::from_data(port7ffd).is_shadow_screen() {
+                2
+            }
+            else {
+                1
+            }
+        }
+        Some(_) => 0,
+        None => return Err(SnapshotError::InvalidData("model not selected".into()))
+    };
+    Ok(core::mem::replace(&mut screens[index], [0;6912]))
+}
+
+/// Determines the **Z80** file version from the header, assuming the header is valid.
+fn z80_format(bytes: &[u8]) -> SnapshotFormat {
+    match (bytes.get(6..8), bytes.get(30..32)) {
+        (Some([0, 0]), Some([23, 0])) => SnapshotFormat::Z80v2,
+        (Some([0, 0]), Some(_)) => SnapshotFormat::Z80v3,
+        _ => SnapshotFormat::Z80v1
+    }
+}
+
+#[derive(Default)]
+struct Inspector {
+    model: Option<ComputerModel>,
+    extensions: Extensions,
+    ram_size: usize,
+    joystick: Option<JoystickModel>,
+    ay: Option<Ay3_891xDevice>,
+    error: Option<SnapshotError>
+}
+
+impl SnapshotLoader for Inspector {
+    type Error = io::Error;
+
+    fn select_model(
+            &mut self,
+            model: ComputerModel,
+            extensions: Extensions,
+            _border: BorderColor,
+            _issue: ReadEarMode
+        ) -> Result<(), Self::Error>
+    {
+        self.model = Some(model);
+        self.extensions = extensions;
+        Ok(())
+    }
+
+    fn read_into_memory<R: Read>(&mut self, range: MemoryRange, reader: R) -> Result<(), ZxMemoryError> {
+        let expected = match &range {
+            MemoryRange::Rom(range)|MemoryRange::Ram(range)|
+            MemoryRange::SamRamRom(range) => range.len(),
+            _ => 0x4000
+        };
+        let found = io::copy(&mut reader.take(expected as u64), &mut io::sink())
+                    .map_err(ZxMemoryError::Io)? as usize;
+        if found != expected {
+            self.error = Some(SnapshotError::InvalidBlockLength { range, expected, found });
+            return Err(ZxMemoryError::Io(io::ErrorKind::UnexpectedEof.into()))
+        }
+        if let MemoryRange::Ram(range) = range {
+            self.ram_size = self.ram_size.max(range.end);
+        }
+        Ok(())
+    }
+
+    fn assign_cpu(&mut self, _cpu: CpuModel) {}
+
+    fn set_clock(&mut self, _tstates: FTs) {}
+
+    fn write_port(&mut self, _port: u16, _data: u8) {}
+
+    fn select_joystick(&mut self, joystick: JoystickModel) {
+        self.joystick = Some(joystick);
+    }
+
+    fn setup_ay(&mut self, choice: Ay3_891xDevice, _reg_selected: AyRegister, _reg_values: &[u8;16]) {
+        self.ay.get_or_insert(choice);
+    }
+
+    fn interface1_rom_paged_in(&mut self) {}
+
+    fn plus_d_rom_paged_in(&mut self) {}
+
+    fn tr_dos_rom_paged_in(&mut self) {}
+}
+
+/// The RAM offsets of the screen data: the 16k/48k screen and the 128k screen banks 5 and 7.
+const SCREEN_RAM_OFFSETS: [usize;3] = [0, 5 * 0x4000, 7 * 0x4000];
+
+struct ScreenExtractor {
+    model: Option<ComputerModel>,
+    port7ffd: u8,
+    screens: [ScreenArray;3]
+}
+
+impl Default for ScreenExtractor {
+    fn default() -> Self {
+        ScreenExtractor { model: None, port7ffd: 0, screens: [[0;6912];3] }
+    }
+}
+
+impl SnapshotLoader for ScreenExtractor {
+    type Error = io::Error;
+
+    fn select_model(
+            &mut self,
+            model: ComputerModel,
+            _extensions: Extensions,
+            _border: BorderColor,
+            _issue: ReadEarMode
+        ) -> Result<(), Self::Error>
+    {
+        self.model = Some(model);
+        Ok(())
+    }
+
+    fn read_into_memory<R: Read>(&mut self, range: MemoryRange, reader: R) -> Result<(), ZxMemoryError> {
+        let range = match range {
+            MemoryRange::Ram(range) => range,
+            MemoryRange::Rom(range)|MemoryRange::SamRamRom(range) => {
+                io::copy(&mut reader.take(range.len() as u64), &mut io::sink())
+                    .map_err(ZxMemoryError::Io)?;
+                return Ok(())
+            }
+            _ => {
+                io::copy(&mut reader.take(0x4000), &mut io::sink()).map_err(ZxMemoryError::Io)?;
+                return Ok(())
+            }
+        };
+        let mut data = Vec::with_capacity(range.len());
+        reader.take(range.len() as u64).read_to_end(&mut data).map_err(ZxMemoryError::Io)?;
+        if data.len() != range.len() {
+            return Err(ZxMemoryError::Io(io::ErrorKind::UnexpectedEof.into()))
+        }
+        for (screen, &offset) in self.screens.iter_mut().zip(SCREEN_RAM_OFFSETS.iter()) {
+            let start = range.start.max(offset);
+            let end = range.end.min(offset + screen.len());
+            if start < end {
+                screen[start - offset..end - offset]
+                    .copy_from_slice(&data[start - range.start..end - range.start]);
+            }
+        }
+        Ok(())
+    }
+
+    fn assign_cpu(&mut self, _cpu: CpuModel) {}
+
+    fn set_clock(&mut self, _tstates: FTs) {}
+
+    fn write_port(&mut self, port: u16, data: u8) {
+        if port == 0x7ffd {
+            self.port7ffd = data;
+        }
+    }
+
+    fn interface1_rom_paged_in(&mut self) {}
+
+    fn plus_d_rom_paged_in(&mut self) {}
+
+    fn tr_dos_rom_paged_in(&mut self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn z80v3_128k(hw_mode: u8) -> Vec<u8> {
+        let mut bytes = vec![0u8;30];
+        bytes.extend_from_slice(&54u16.to_le_bytes());
+        let mut header_ex = [0u8;54];
+        header_ex[2] = hw_mode;
+        bytes.extend_from_slice(&header_ex);
+        for page in 3..=10 {
+            bytes.extend_from_slice(&[0xff, 0xff, page]);
+            bytes.resize(bytes.len() + 0x4000, page);
+        }
+        bytes
+    }
+
+    #[test]
+    fn inspect_snapshot_works() {
+        let bytes = z80v3_128k(4);
+        let info = inspect_snapshot(&bytes).unwrap();
+        assert_eq!(SnapshotFormat::Z80v3, info.format);
+        assert_eq!(ComputerModel::Spectrum128, info.model);
+        assert_eq!(Extensions::NONE, info.extensions);
+        assert_eq!(0x20000, info.ram_size);
+        assert_eq!(Some(Ay3_891xDevice::Ay128k), info.ay);
+
+        match inspect_snapshot(&bytes[..bytes.len() - 100]) {
+            Err(SnapshotError::InvalidBlockLength { range, expected, found }) => {
+                assert_eq!(MemoryRange::Ram(0x1c000..0x20000), range);
+                assert_eq!(0x4000, expected);
+                assert_eq!(0x4000 - 100, found);
+            }
+            res => panic!("unexpected result: {:?}", res)
+        }
+
+        match inspect_snapshot(&z80v3_128k(99)) {
+            Err(SnapshotError::InvalidData(msg)) => assert_eq!("unsupported model", msg),
+            res => panic!("unexpected result: {:?}", res)
+        }
+
+        match inspect_snapshot(&bytes[..40]) {
+            Err(SnapshotError::Truncated) => {},
+            res => panic!("unexpected result: {:?}", res)
+        }
+
+        let mut sna = vec![0u8;sna::SNA_LENGTH as usize];
+        sna[23..25].copy_from_slice(&0xfff0u16.to_le_bytes());
+        let info = inspect_snapshot(&sna).unwrap();
+        assert_eq!(SnapshotFormat::Sna48, info.format);
+        assert_eq!(ComputerModel::Spectrum48, info.model);
+        assert_eq!(0xC000, info.ram_size);
+    }
+
+    #[test]
+    fn detect_format_works() {
+        assert_eq!(None, detect_format(b""));
+        assert_eq!(None, detect_format(b"Hello world!"));
+        assert_eq!(Some(SnapshotFormat::Szx), detect_format(b"ZXST\x01\x04\x01\x00"));
+
+        let mut sp = vec![0u8;38 + 0xC000];
+        sp[..6].copy_from_slice(b"SP\x00\xC0\x00\x40");
+        assert_eq!(Some(SnapshotFormat::Sp), detect_format(&sp));
+        assert_eq!(None, detect_format(&sp[..sp.len() - 1]));
+
+        let mut sna = vec![0u8;sna::SNA_LENGTH as usize];
+        sna[25] = 1;
+        sna[26] = 7;
+        assert_eq!(Some(SnapshotFormat::Sna48), detect_format(&sna));
+        for &len in &[SNA128_LENGTH, SNA128_DUP_LENGTH] {
+            let mut sna128 = sna.clone();
+            sna128.resize(len, 0);
+            assert_eq!(Some(SnapshotFormat::Sna128), detect_format(&sna128));
+        }
+        // an SNA file looking like a Z80 file is still an SNA file
+        sna[30] = 23;
+        assert_eq!(Some(SnapshotFormat::Sna48), detect_format(&sna));
+        // a Z80 file of the SNA file size with IM or border invalid for SNA
+        sna[25] = 0xAB;
+        assert_eq!(Some(SnapshotFormat::Z80v2), detect_format(&sna));
+        sna[25] = 2;
+        sna[26] = 8;
+        sna[30] = 54;
+        assert_eq!(Some(SnapshotFormat::Z80v3), detect_format(&sna));
+        sna[30] = 0;
+        assert_eq!(None, detect_format(&sna));
+
+        let z80 = z80v3_128k(4);
+        assert_eq!(Some(SnapshotFormat::Z80v3), detect_format(&z80));
+        assert_eq!(Some(SnapshotFormat::Z80v3), detect_format(&z80[..40]));
+        assert_eq!(None, detect_format(&z80[..31]));
+
+        let mut z80v1 = vec![0u8;30 + 0xC000];
+        z80v1[6..8].copy_from_slice(&0x8000u16.to_le_bytes());
+        assert_eq!(Some(SnapshotFormat::Z80v1), detect_format(&z80v1));
+        z80v1[12] = 0x20;
+        assert_eq!(None, detect_format(&z80v1));
+        z80v1.truncate(100);
+        z80v1.extend_from_slice(&[0x00, 0xED, 0xED, 0x00]);
+        assert_eq!(Some(SnapshotFormat::Z80v1), detect_format(&z80v1));
+    }
+
+    #[test]
+    fn extract_screen_works() {
+        let screen: Vec<u8> = (0..6912u32).map(|i| (i * 7 + i / 256) as u8).collect();
+
+        let mut sna = vec![0u8;sna::SNA_LENGTH as usize];
+        sna[23..25].copy_from_slice(&0xfff0u16.to_le_bytes());
+        sna[27..27 + 6912].copy_from_slice(&screen);
+        assert_eq!(&extract_screen(&sna, SnapshotFormat::Sna48).unwrap()[..], &screen[..]);
+        assert_eq!(Some(SnapshotFormat::Sna48), detect_format(&sna));
+
+        // banks 5, 2, 7 (paged in), the 128k header and banks 0, 1, 3, 4, 6
+        let mut sna128 = sna.clone();
+        sna128[27..27 + 0x4000].fill(5);
+        sna128[27 + 0x8000..27 + 0x8000 + 6912].copy_from_slice(&screen);
+        sna128.extend_from_slice(&[0, 0x80, 0b0000_1111, 0]);
+        sna128.resize(SNA128_LENGTH, 0xAA);
+        assert_eq!(&extract_screen(&sna128, SnapshotFormat::Sna128).unwrap()[..], &screen[..]);
+        // the normal screen bank selected
+        sna128[27 + 3 * 0x4000 + 2] = 0b0000_0111;
+        assert_eq!(extract_screen(&sna128, SnapshotFormat::Sna128).unwrap(), [5;6912]);
+
+        let mut z80 = z80v3_128k(4);
+        assert_eq!(extract_screen(&z80, SnapshotFormat::Z80v3).unwrap(), [8;6912]);
+        z80[30 + 2 + 3] = 0b0000_1000;
+        assert_eq!(extract_screen(&z80, SnapshotFormat::Z80v3).unwrap(), [10;6912]);
+
+        match extract_screen(&z80[..z80.len() - 100], SnapshotFormat::Z80v3) {
+            Err(SnapshotError::Truncated) => {},
+            res => panic!("unexpected result: {:?}", res.map(|_| ()))
+        }
+        match extract_screen(b"ZXST\x01\x04\x01\x00", SnapshotFormat::Szx) {
+            Err(SnapshotError::InvalidData(_)) => {},
+            res => panic!("unexpected result: {:?}", res.map(|_| ()))
+        }
+    }
+}
@@ -114,6 +114,8 @@ A [TapChunkInfo] is an enum providing information about the chunk and can be cre
 byte containers as well as from [std::io::Take] readers using the [TryFrom] interface.
 
 An [iterator][TapReadInfoIter] producing [TapChunkInfo] can be created [from][From] [TapChunkReader].
+The iterator also recognizes blocks loaded by custom loaders, that are not preceded by a [Header],
+as [TapChunkInfo::Headerless].
 
 ```no_run
 use spectrusty_formats::tap::*;
@@ -209,6 +211,22 @@ if chunks != 0 {
 }
 # Ok::<(), std::io::Error>(())
 ```
+
+## Editing in-memory *TAP* files
+
+[split_chunk_at] and [merge_chunks] rearrange the data of *TAP chunks* in a byte vector holding
+the whole *TAP* file, recomputing checksums of the modified chunks.
+
+```no_run
+use spectrusty_formats::tap::*;
+
+let mut tap = std::fs::read("input.tap")?;
+// split the data of the 2nd chunk after its first 256 bytes
+split_chunk_at(&mut tap, 1, 256)?;
+// and join it back together
+merge_chunks(&mut tap, 1..3)?;
+# Ok::<(), std::io::Error>(())
+```
 */
 use core::borrow::Borrow;
 use std::borrow::Cow;
@@ -219,14 +237,18 @@ use std::io::{ErrorKind, Error, Read, Write, Seek, Result, Cursor};
 use pulse::ReadEncPulseIter;
 
 pub mod pulse;
+mod edit;
 mod read;
 mod write;
+pub use edit::*;
 pub use read::*;
 pub use write::*;
 
 pub const HEAD_BLOCK_FLAG: u8 = 0x00;
 pub const DATA_BLOCK_FLAG: u8 = 0xFF;
 pub const HEADER_SIZE: usize = 19;
+/// The length of the `SCREEN$` data.
+pub const SCREEN_DATA_LENGTH: u16 = 6912;
 
 /// Calculates bit toggle checksum from the given iterator of `u8`.
 pub fn checksum<I: IntoIterator<Item=B>, B: Borrow<u8>>(iter: I) -> u8 {
@@ -293,6 +315,7 @@ pub struct Header {
 
 /// The *TAP* chunk meta-data.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum TapChunkInfo {
     /// Represents a proper header block.
     Head(Header),
@@ -308,12 +331,37 @@ pub enum TapChunkInfo {
         /// The size of the whole block including the block flag.
         size: u16,
         /// The first byte of the block (a block flag).
-        flag: u8
+        flag: u8,
+        /// Checksum of the whole block including the block flag, should be 0 if the block is not damaged.
+        checksum: u8
+    },
+    /// Represents a block that is not preceded by a [Header] describing it.
+    ///
+    /// Such blocks are loaded by machine code loaders and are recognized only by [TapReadInfoIter].
+    Headerless {
+        /// The length of data excluding a block flag and checksum byte.
+        length: u16,
+        /// The first byte of the block (a block flag).
+        flag: u8,
+        /// Checksum of the whole block including the block flag, should be 0 if the block is not damaged.
+        checksum: u8,
+        /// The guessed content of the block.
+        kind: HeaderlessKind
     },
     /// Represents an empty block.
     Empty
 }
 
+/// The guessed content of the [TapChunkInfo::Headerless] block.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum HeaderlessKind {
+    /// The length of the block data matches the length of the `SCREEN$`.
+    Screen,
+    /// Any other block, most likely a part of the program loaded by a custom loader.
+    Loader
+}
+
 /// The *TAP* chunk.
 ///
 /// Provides helper methods to interpret the underlying bytes as one of the *TAP* blocks.
@@ -369,6 +417,15 @@ impl fmt::Display for BlockType {
     }
 }
 
+impl fmt::Display for HeaderlessKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HeaderlessKind::Screen => "SCREEN$",
+            HeaderlessKind::Loader => "code",
+        }.fmt(f)
+    }
+}
+
 impl fmt::Display for TapChunkInfo {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -376,8 +433,8 @@ impl fmt::Display for TapChunkInfo {
                 write!(f, "{}: \"{}\"", header.block_type, header.name_str().trim_end())?;
                 match header.block_type {
                     BlockType::Program => {
-                        if header.start() < 10000 {
-                            write!(f, " LINE {}", header.start())?;
+                        if let Some(line) = header.basic_autorun_line() {
+                            write!(f, " LINE {}", line)?;
                         }
                         if header.vars() != header.length {
                             write!(f, " PROG {} VARS {}",
@@ -402,6 +459,9 @@ impl fmt::Display for TapChunkInfo {
             TapChunkInfo::Unknown {size, ..} => {
                 write!(f, "(unkown {})", size)
             }
+            TapChunkInfo::Headerless {length, flag, kind, ..} => {
+                write!(f, "(headerless {} {}, flag {:#04x})", kind, length, flag)
+            }
             TapChunkInfo::Empty => {
                 write!(f, "(empty)")
             }            
@@ -563,6 +623,17 @@ impl Header {
         u16::from_le_bytes(self.par2)
     }
 
+    /// Returns the line number from which the BASIC program will be automatically run after being loaded.
+    ///
+    /// Returns `None` if this is not a [BlockType::Program] header or if the program has been saved
+    /// without the `LINE` parameter.
+    pub fn basic_autorun_line(&self) -> Option<u16> {
+        match (self.block_type, self.start()) {
+            (BlockType::Program, line @ 0..=9999) => Some(line),
+            _ => None
+        }
+    }
+
     /// Returns an array variable name.
     ///
     /// Only valid for headers with [BlockType::CharArray] or [BlockType::NumberArray].
@@ -612,9 +683,43 @@ impl TapChunkInfo {
             TapChunkInfo::Head(_) => HEADER_SIZE,
             &TapChunkInfo::Data {length, ..} => length as usize + 2,
             &TapChunkInfo::Unknown {size, ..} => size as usize,
+            &TapChunkInfo::Headerless {length, ..} => length as usize + 2,
             TapChunkInfo::Empty => 0
         }
     }
+    /// Returns `true` if the checksum byte of this chunk matches the XOR of its block flag and data.
+    ///
+    /// [TapChunkInfo::Head] is always valid, as headers with an invalid checksum are represented
+    /// by [TapChunkInfo::Unknown]. Returns `false` for [TapChunkInfo::Empty].
+    pub fn is_checksum_valid(&self) -> bool {
+        match self {
+            TapChunkInfo::Head(_) => true,
+            &TapChunkInfo::Data {checksum, ..} => checksum == 0,
+            &TapChunkInfo::Unknown {size, checksum, ..} => size > 1 && checksum == 0,
+            &TapChunkInfo::Headerless {checksum, ..} => checksum == 0,
+            TapChunkInfo::Empty => false
+        }
+    }
+    /// Returns this chunk as [TapChunkInfo::Headerless] if it's a data block or an unknown block
+    /// with a flag other than the header block flag. Otherwise returns `self`.
+    ///
+    /// This method doesn't check if the block is preceded by a [Header], it's up to the caller.
+    pub fn into_headerless(self) -> Self {
+        let (length, flag, checksum) = match self {
+            TapChunkInfo::Data {length, checksum} => (length, DATA_BLOCK_FLAG, checksum),
+            TapChunkInfo::Unknown {size, flag, checksum} if size > 1 && flag != HEAD_BLOCK_FLAG => {
+                (size - 2, flag, checksum)
+            }
+            info => return info
+        };
+        let kind = if length == SCREEN_DATA_LENGTH {
+            HeaderlessKind::Screen
+        }
+        else {
+            HeaderlessKind::Loader
+        };
+        TapChunkInfo::Headerless { length, flag, checksum, kind }
+    }
 }
 
 impl TryFrom<&'_[u8]> for TapChunkInfo {
@@ -627,25 +732,25 @@ impl TryFrom<&'_[u8]> for TapChunkInfo {
                 return Ok(TapChunkInfo::Empty);
             }
             1 => {
-                return Ok(TapChunkInfo::Unknown { size: 1, flag: bytes[0] })
+                return Ok(TapChunkInfo::Unknown { size: 1, flag: bytes[0], checksum: bytes[0] })
             }
             size if size > u16::max_value().into() => {
                 return Err(Error::new(ErrorKind::InvalidData, "Not a proper TAP chunk: too large"));
             }
             size => size
         };
+        let checksum = checksum(bytes);
         match bytes.first() {
-            Some(&HEAD_BLOCK_FLAG) if size == HEADER_SIZE && checksum(bytes) == 0 => {
+            Some(&HEAD_BLOCK_FLAG) if size == HEADER_SIZE && checksum == 0 => {
                 Header::try_from(&bytes[1..HEADER_SIZE-1])
                 .map(TapChunkInfo::Head)
-                .or(Ok(TapChunkInfo::Unknown { size: size as u16, flag: HEAD_BLOCK_FLAG }))
+                .or(Ok(TapChunkInfo::Unknown { size: size as u16, flag: HEAD_BLOCK_FLAG, checksum }))
             }
             Some(&DATA_BLOCK_FLAG) => {
-                let checksum = checksum(bytes);
                 Ok(TapChunkInfo::Data{ length: size as u16 - 2, checksum })
             }
             Some(&flag) => {
-                Ok(TapChunkInfo::Unknown { size: size as u16, flag })
+                Ok(TapChunkInfo::Unknown { size: size as u16, flag, checksum })
             }
             _ => unreachable!()
         }
@@ -777,6 +882,18 @@ impl<T> TapChunk<T> where T: AsRef<[u8]> {
         None
     }
 
+    /// Returns the line number from which the BASIC program will be automatically run after being loaded,
+    /// only if the underlying bytes represents the [Header] block.
+    ///
+    /// Returns `None` if this is not a [BlockType::Program] header or if the program has been saved
+    /// without the `LINE` parameter.
+    pub fn basic_autorun_line(&self) -> Option<u16> {
+        match (self.block_type()?, self.start()?) {
+            (BlockType::Program, line @ 0..=9999) => Some(line),
+            _ => None
+        }
+    }
+
     /// Returns an offset to `VARS` only if the underlying bytes represents the [Header] block.
     ///
     /// Only valid for headers with [BlockType::Program].
@@ -858,6 +975,7 @@ impl<'a> Iterator for TapChunkIter<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use core::num::NonZeroU32;
     use std::fs::File;
     use smallvec::SmallVec;
 
@@ -932,6 +1050,33 @@ mod tests {
         });
     }
 
+    #[test]
+    fn basic_autorun_line_works() -> Result<()> {
+        let header = Header::new_program(100).with_name("autorun").with_start(9999);
+        assert_eq!(Some(9999), header.basic_autorun_line());
+        assert_eq!(Some(9999), header.to_tap_chunk().basic_autorun_line());
+        let header = Header::new_program(100);
+        assert_eq!(None, header.basic_autorun_line());
+        assert_eq!(None, header.to_tap_chunk().basic_autorun_line());
+        let header = Header::new_code(100).with_start(10);
+        assert_eq!(None, header.basic_autorun_line());
+        assert_eq!(None, header.to_tap_chunk().basic_autorun_line());
+        assert_eq!(None, TapChunk::from([DATA_BLOCK_FLAG, 0, 0, DATA_BLOCK_FLAG]).basic_autorun_line());
+
+        let file = File::open("../resources/read_tap_test.tap")?;
+        let mut tap_reader = read_tap(file);
+        let mut buf = Vec::new();
+        tap_reader.next_chunk()?;
+        tap_reader.read_to_end(&mut buf)?;
+        let chunk = TapChunk::from(&buf);
+        assert_eq!(Some(10), chunk.basic_autorun_line());
+        match chunk.info()? {
+            TapChunkInfo::Head(header) => assert_eq!(Some(10), header.basic_autorun_line()),
+            _ => unreachable!()
+        }
+        Ok(())
+    }
+
     #[test]
     fn read_tap_works() -> Result<()> {
         let file = File::open("../resources/read_tap_test.tap")?;
@@ -994,4 +1139,174 @@ mod tests {
         assert_eq!(28, infos[5].tap_chunk_size());
         Ok(())
     }
+
+    #[test]
+    fn tap_pulse_iter_tape_counter_works() {
+        let bytes = [0x13,0x00,0x00,0x03,0x52,0x4f,0x4d,0x20,0x20,0x20,0x20,0x20,0x20,0x20,0x02,0x00,0x00,0x00,0x00,0x80,0xf1,0x04,0x00,0xff,0xf3,0xaf,0xa3];
+        let mut pulse_iter = read_tap_pulse_iter(Cursor::new(&bytes[..]));
+        assert_eq!(DEFAULT_COUNTER_SCALE, pulse_iter.counter_scale);
+        assert_eq!(0, pulse_iter.elapsed_tstates());
+        assert_eq!(0, pulse_iter.tape_counter());
+        pulse_iter.counter_scale = NonZeroU32::new(1000).unwrap();
+        let mut total: u64 = 0;
+        let mut counter = 0;
+        for _ in 0..10000 {
+            total += u64::from(pulse_iter.next().unwrap().get());
+            let next_counter = pulse_iter.tape_counter();
+            assert!(next_counter >= counter);
+            counter = next_counter;
+        }
+        assert_eq!(total, pulse_iter.elapsed_tstates());
+        assert_eq!(total / 1000, pulse_iter.tape_counter());
+        assert!(pulse_iter.tape_counter() > 0);
+        let total = total + pulse_iter.by_ref().map(|p| u64::from(p.get())).sum::<u64>();
+        assert_eq!(total, pulse_iter.elapsed_tstates());
+        assert_eq!(true, pulse_iter.is_done());
+        pulse_iter.rewind();
+        assert_eq!(0, pulse_iter.elapsed_tstates());
+        assert_eq!(0, pulse_iter.tape_counter());
+        let pulse = pulse_iter.next().unwrap();
+        assert_eq!(u64::from(pulse.get()), pulse_iter.elapsed_tstates());
+        // the positions of the chunks while playing
+        pulse_iter.rewind();
+        let mut starts = vec![0];
+        while pulse_iter.next().is_some() {
+            if pulse_iter.chunk_no() as usize == starts.len() {
+                starts.push(pulse_iter.elapsed_tstates());
+            }
+        }
+        assert_eq!(starts.len(), 3);
+        assert_eq!(starts[1], u64::from(pulse::consts::PAUSE_PULSE_LENGTH.get()));
+        assert_eq!(total, pulse_iter.elapsed_tstates());
+        // the position follows the chunks being skipped
+        pulse_iter.rewind();
+        assert_eq!(true, pulse_iter.rewind_nth_chunk(2).unwrap());
+        assert_eq!(starts[2], pulse_iter.elapsed_tstates());
+        assert_eq!(starts[2] / 1000, pulse_iter.tape_counter());
+        assert_eq!(true, pulse_iter.rewind_nth_chunk(1).unwrap());
+        assert_eq!(starts[1], pulse_iter.elapsed_tstates());
+        for _ in 0..100 {
+            pulse_iter.next().unwrap();
+        }
+        assert!(pulse_iter.elapsed_tstates() > starts[1]);
+        assert_eq!(true, pulse_iter.forward_chunk().unwrap());
+        assert_eq!(starts[2], pulse_iter.elapsed_tstates());
+        assert_eq!(false, pulse_iter.forward_chunk().unwrap());
+        assert_eq!(total, pulse_iter.elapsed_tstates());
+        assert_eq!(false, pulse_iter.rewind_nth_chunk(5).unwrap());
+        assert_eq!(total, pulse_iter.elapsed_tstates());
+    }
+
+    #[test]
+    fn tap_verify_checksum_works() -> Result<()> {
+        let bytes = [0x04,0x00,0xff,0xf3,0xaf,0xa3,
+                     0x04,0x00,0xff,0xf3,0xae,0xa3,
+                     0x03,0x00,0x55,0xf3,0xa6];
+        let mut tap_reader = read_tap(Cursor::new(&bytes[..]));
+        assert!(!tap_reader.verify_checksum()?);
+        assert_eq!(Some(4), tap_reader.next_chunk()?);
+        assert!(tap_reader.verify_checksum()?);
+        let mut byte = [0u8];
+        tap_reader.read_exact(&mut byte)?;
+        assert!(tap_reader.verify_checksum()?);
+        assert_eq!(3, tap_reader.chunk_limit());
+        assert_eq!(0xff, tap_reader.checksum);
+        let mut buf = Vec::new();
+        tap_reader.read_to_end(&mut buf)?;
+        assert_eq!(&[0xf3,0xaf,0xa3][..], &buf[..]);
+        assert!(tap_reader.verify_checksum()?);
+        assert_eq!(Some(4), tap_reader.next_chunk()?);
+        assert!(!tap_reader.verify_checksum()?);
+        buf.clear();
+        tap_reader.read_to_end(&mut buf)?;
+        assert_eq!(&[0xff,0xf3,0xae,0xa3][..], &buf[..]);
+        assert_eq!(0x01, tap_reader.checksum);
+        assert_eq!(Some(3), tap_reader.next_chunk()?);
+        assert!(tap_reader.verify_checksum()?);
+        assert_eq!(None, tap_reader.next_chunk()?);
+        tap_reader.rewind();
+        let infos: Vec<_> = TapReadInfoIter::from(&mut tap_reader).collect::<Result<_>>()?;
+        // there are no headers, so all blocks are headerless
+        assert_eq!(infos[0], TapChunkInfo::Data { length: 2, checksum: 0 }.into_headerless());
+        assert!(infos[0].is_checksum_valid());
+        assert_eq!(infos[1], TapChunkInfo::Data { length: 2, checksum: 1 }.into_headerless());
+        assert!(!infos[1].is_checksum_valid());
+        assert_eq!(infos[2], TapChunkInfo::Unknown { size: 3, flag: 0x55, checksum: 0 }.into_headerless());
+        assert!(infos[2].is_checksum_valid());
+        assert_eq!(TapChunk::from(&bytes[8..12]).info()?, TapChunkInfo::Data { length: 2, checksum: 1 });
+        assert_eq!(TapChunk::from(&bytes[14..]).info()?, TapChunkInfo::Unknown { size: 3, flag: 0x55, checksum: 0 });
+        Ok(())
+    }
+
+    #[test]
+    fn tap_read_info_headerless_works() -> Result<()> {
+        let header = Header::new_code(3).with_start(32768).with_name("loader");
+        let mut screen = vec![0u8; SCREEN_DATA_LENGTH as usize + 2];
+        screen[0] = DATA_BLOCK_FLAG;
+        screen[1] = 0x55;
+        *screen.last_mut().unwrap() = DATA_BLOCK_FLAG ^ 0x55;
+        let mut wr = write_tap(Cursor::new(Vec::new()))?;
+        wr.write_header(&header)?;
+        wr.write_chunk([DATA_BLOCK_FLAG, 1, 2, 3, 0xFF])?;
+        wr.write_chunk(screen)?;
+        // a custom loader block with a non-standard flag
+        wr.write_chunk([0x42, 7, 7, 0x42])?;
+        // a data block which is longer than declared in the header
+        wr.write_header(&header)?;
+        wr.write_chunk([DATA_BLOCK_FLAG, 1, 2, 3, 4, 0xFB])?;
+        let tap = wr.into_inner().into_inner().into_inner();
+        let mut tap_reader = read_tap(Cursor::new(tap));
+        let infos: Vec<_> = TapReadInfoIter::from(&mut tap_reader).collect::<Result<_>>()?;
+        assert_eq!(infos.len(), 6);
+        assert_eq!(infos[0], TapChunkInfo::Head(header));
+        assert_eq!(infos[1], TapChunkInfo::Data { length: 3, checksum: 0 });
+        assert_eq!(format!("{}", infos[1]), "(data 3)");
+        assert_eq!(infos[2], TapChunkInfo::Headerless {
+            length: 6912, flag: 0xFF, checksum: 0, kind: HeaderlessKind::Screen });
+        assert_eq!(format!("{}", infos[2]), "(headerless SCREEN$ 6912, flag 0xff)");
+        assert_eq!(infos[2].tap_chunk_size(), 6914);
+        assert_eq!(infos[3], TapChunkInfo::Headerless {
+            length: 2, flag: 0x42, checksum: 0, kind: HeaderlessKind::Loader });
+        assert_eq!(format!("{}", infos[3]), "(headerless code 2, flag 0x42)");
+        assert!(infos[3].is_checksum_valid());
+        assert_eq!(infos[4], TapChunkInfo::Head(header));
+        assert_eq!(infos[5], TapChunkInfo::Headerless {
+            length: 4, flag: 0xFF, checksum: 0, kind: HeaderlessKind::Loader });
+        Ok(())
+    }
+
+    #[test]
+    fn tap_split_merge_chunks_works() -> Result<()> {
+        let bytes = [0x13,0x00,0x00,0x03,0x52,0x4f,0x4d,0x20,0x20,0x20,0x20,0x20,0x20,0x20,0x05,0x00,0x00,0x00,0x00,0x80,0xf6,
+                     0x07,0x00,0xff,0xf3,0xaf,0x01,0x02,0x03,0xa3,
+                     0x03,0x00,0xff,0xc9,0x36];
+        let mut tap = bytes.to_vec();
+        assert!(split_chunk_at(&mut tap, 1, 0).is_err());
+        assert!(split_chunk_at(&mut tap, 1, 5).is_err());
+        assert!(split_chunk_at(&mut tap, 3, 1).is_err());
+        assert_eq!(&bytes[..], &tap[..]);
+        split_chunk_at(&mut tap, 1, 2)?;
+        let chunks: Vec<_> = TapChunkIter::from(&tap).collect();
+        assert_eq!(4, chunks.len());
+        for chunk in chunks.iter() {
+            assert!(chunk.is_valid());
+        }
+        assert_eq!(Some(&[0xf3,0xaf][..]), chunks[1].data());
+        assert_eq!(Some(&[0x01,0x02,0x03][..]), chunks[2].data());
+        assert_eq!(Some(&[0xc9][..]), chunks[3].data());
+        let data: Vec<u8> = chunks[1..3].iter().flat_map(|c| c.data().unwrap()).copied().collect();
+        assert_eq!(&bytes[24..29], &data[..]);
+        assert!(merge_chunks(&mut tap, 1..1).is_err());
+        assert!(merge_chunks(&mut tap, 3..5).is_err());
+        merge_chunks(&mut tap, 1..3)?;
+        assert_eq!(&bytes[..], &tap[..]);
+        merge_chunks(&mut tap, 1..3)?;
+        let chunks: Vec<_> = TapChunkIter::from(&tap).collect();
+        assert_eq!(2, chunks.len());
+        assert!(chunks[1].is_valid());
+        assert_eq!(Some(&[0xf3,0xaf,0x01,0x02,0x03,0xc9][..]), chunks[1].data());
+        split_chunk_at(&mut tap, 1, 5)?;
+        assert_eq!(&bytes[..], &tap[..]);
+        Ok(())
+    }
 }
@@ -0,0 +1,115 @@
+/*
+    Copyright (C) 2020-2023  Rafal Michalski
+
+    This file is part of SPECTRUSTY, a Rust library for building emulators.
+
+    For the full copyright notice, see the lib.rs file.
+*/
+use core::ops::Range;
+use std::io::{ErrorKind, Error, Result};
+use super::checksum;
+
+const LEN_PREFIX_SIZE: usize = 2;
+
+/// Returns the range of bytes of the `index`th *TAP chunk* (counting from 0) in `tap`,
+/// excluding the chunk's length prefix.
+fn chunk_range(tap: &[u8], index: usize) -> Result<Range<usize>> {
+    let mut position = 0;
+    let mut nchunk = 0;
+    loop {
+        let length = match tap.get(position..position + LEN_PREFIX_SIZE) {
+            Some(&[lo, hi]) => u16::from_le_bytes([lo, hi]) as usize,
+            _ => return Err(Error::new(ErrorKind::InvalidInput, "TAP chunk index out of range"))
+        };
+        position += LEN_PREFIX_SIZE;
+        let range = position..position + length;
+        if range.end > tap.len() {
+            return Err(Error::new(ErrorKind::UnexpectedEof, "TAP chunk unexpectedly ended"))
+        }
+        if nchunk == index {
+            return Ok(range)
+        }
+        position = range.end;
+        nchunk += 1;
+    }
+}
+
+fn chunk_prefix(size: usize) -> Result<[u8; LEN_PREFIX_SIZE]> {
+    if size > u16::MAX.into() {
+        return Err(Error::new(ErrorKind::InvalidInput, "Not a proper TAP chunk: too large"))
+    }
+    Ok((size as u16).to_le_bytes())
+}
+
+/// Splits the data of the `index`th *TAP chunk* (counting from 0) of the in-memory *TAP* file `tap`
+/// into two chunks.
+///
+/// `offset` is the position in the chunk's data, excluding the block flag and the checksum byte,
+/// at which the data is being split. Both of the resulting chunks start with the original block flag
+/// and their checksums are recomputed.
+///
+/// Returns an error if there is no such chunk, the chunk is shorter than 2 bytes, or if `offset`
+/// would produce a chunk without any data.
+pub fn split_chunk_at(tap: &mut Vec<u8>, index: usize, offset: usize) -> Result<()> {
+    let range = chunk_range(tap, index)?;
+    if range.len() < 2 {
+        return Err(Error::new(ErrorKind::InvalidInput, "Not a proper TAP block: invalid length"))
+    }
+    let data_len = range.len() - 2;
+    if offset == 0 || offset >= data_len {
+        return Err(Error::new(ErrorKind::InvalidInput, "TAP chunk split offset out of range"))
+    }
+    let flag = tap[range.start];
+    let split_pos = range.start + 1 + offset;
+    let mut tail = Vec::with_capacity(LEN_PREFIX_SIZE + 2 + data_len - offset);
+    tail.extend_from_slice(&chunk_prefix(data_len - offset + 2)?);
+    tail.push(flag);
+    tail.extend_from_slice(&tap[split_pos..range.end - 1]);
+    tail.push(checksum(&tail[LEN_PREFIX_SIZE..]));
+    let mut head = Vec::with_capacity(LEN_PREFIX_SIZE + 2 + offset);
+    head.extend_from_slice(&chunk_prefix(offset + 2)?);
+    head.extend_from_slice(&tap[range.start..split_pos]);
+    head.push(checksum(&head[LEN_PREFIX_SIZE..]));
+    head.extend_from_slice(&tail);
+    tap.splice(range.start - LEN_PREFIX_SIZE..range.end, head);
+    Ok(())
+}
+
+/// Merges the *TAP chunks* of the in-memory *TAP* file `tap` within the given `range` of chunk indexes
+/// (counting from 0) into a single chunk.
+///
+/// The merged chunk starts with the block flag of the first chunk in the `range`, followed by
+/// the concatenated data of all the merged chunks, excluding their block flags and checksum bytes.
+/// The checksum of the merged chunk is recomputed.
+///
+/// Returns an error if the `range` is empty, any of the chunks doesn't exist or is shorter than 2 bytes,
+/// or if the merged chunk would be too large.
+pub fn merge_chunks(tap: &mut Vec<u8>, range: Range<usize>) -> Result<()> {
+    if range.is_empty() {
+        return Err(Error::new(ErrorKind::InvalidInput, "TAP chunk range is empty"))
+    }
+    let first = chunk_range(tap, range.start)?;
+    let mut merged = vec![0, 0];
+    let mut end = first.start;
+    for _ in range {
+        let length = match tap.get(end - LEN_PREFIX_SIZE..end) {
+            Some(&[lo, hi]) => u16::from_le_bytes([lo, hi]) as usize,
+            _ => return Err(Error::new(ErrorKind::InvalidInput, "TAP chunk index out of range"))
+        };
+        let chunk = match tap.get(end..end + length) {
+            Some(chunk) if chunk.len() >= 2 => chunk,
+            Some(_) => return Err(Error::new(ErrorKind::InvalidInput, "Not a proper TAP block: invalid length")),
+            None => return Err(Error::new(ErrorKind::UnexpectedEof, "TAP chunk unexpectedly ended"))
+        };
+        if merged.len() == LEN_PREFIX_SIZE {
+            merged.push(chunk[0]);
+        }
+        merged.extend_from_slice(&chunk[1..length - 1]);
+        end += length + LEN_PREFIX_SIZE;
+    }
+    merged.push(checksum(&merged[LEN_PREFIX_SIZE..]));
+    let prefix = chunk_prefix(merged.len() - LEN_PREFIX_SIZE)?;
+    merged[..LEN_PREFIX_SIZE].copy_from_slice(&prefix);
+    tap.splice(first.start - LEN_PREFIX_SIZE..end - LEN_PREFIX_SIZE, merged);
+    Ok(())
+}
@@ -13,8 +13,11 @@ use core::convert::{TryInto, TryFrom};
 use std::io::{ErrorKind, Error, Read, Seek, SeekFrom, Result, Take};
 
 use crate::ReadExactEx;
-use super::pulse::{ReadEncPulseIter, consts::PAUSE_PULSE_LENGTH};
-use super::{Header, TapChunkInfo, HEAD_BLOCK_FLAG, DATA_BLOCK_FLAG, HEADER_SIZE, checksum, try_checksum};
+use super::pulse::{ReadEncPulseIter, consts::{
+    LEAD_PULSE_LENGTH, SYNC_PULSE1_LENGTH, SYNC_PULSE2_LENGTH, ZERO_PULSE_LENGTH, ONE_PULSE_LENGTH,
+    PAUSE_PULSE_LENGTH, LEAD_PULSES_HEAD, LEAD_PULSES_DATA
+}};
+use super::{Header, TapChunkInfo, HEAD_BLOCK_FLAG, DATA_BLOCK_FLAG, HEADER_SIZE, checksum};
 
 /// Implements a [Reader][Read] of *TAP* chunks data.
 ///
@@ -25,6 +28,7 @@ pub struct TapChunkReader<R> {
     pub checksum: u8,
     next_pos: u64,
     chunk_index: u32,
+    tape_length: Option<u64>,
     inner: Take<R>,
 }
 
@@ -39,9 +43,13 @@ pub struct TapChunkReaderMut<'a, R: Seek> {
 
 /// Implements an iterator of [TapChunkInfo] instances from any mutable reference to [TapChunkReader]
 /// including anything (like smart pointers) that dereferences to it.
+///
+/// The blocks which are not preceded by a [Header] of the matching length are reported as
+/// [TapChunkInfo::Headerless].
 #[derive(Debug)]
 pub struct TapReadInfoIter<TR> {
     inner: TR,
+    header: Option<Header>
 }
 
 /// Implements an iterator of T-state pulse intervals over the [TapChunkReader].
@@ -55,9 +63,19 @@ pub struct TapChunkPulseIter<R> {
     /// * `true` the next chunk will be processed automatically and a single pulse of the interval
     ///   of [PAUSE_PULSE_LENGTH] T-states is emitted before lead pulses of the next chunk.
     pub auto_next: bool,
-    ep_iter: ReadEncPulseIter<TapChunkReader<R>>
+    /// The number of T-states of emitted pulses per a single unit of the [tape counter][TapChunkPulseIter::tape_counter].
+    ///
+    /// By default it's [DEFAULT_COUNTER_SCALE].
+    pub counter_scale: NonZeroU32,
+    ep_iter: ReadEncPulseIter<TapChunkReader<R>>,
+    elapsed_ts: u64,
+    chunk_start_ts: u64,
+    chunk_len_ts: u64
 }
 
+/// The default [TapChunkPulseIter::counter_scale], a single counter unit per second of the tape played at 3.5 MHz.
+pub const DEFAULT_COUNTER_SCALE: NonZeroU32 = PAUSE_PULSE_LENGTH;
+
 /// A trait with tools implemented by tap chunk readers.
 pub trait TapChunkRead {
     /// Returns this chunk's number.
@@ -153,36 +171,54 @@ impl<R: Read> TryFrom<&'_ mut Take<R>> for TapChunkInfo {
         let mut flag: u8 = 0;
         rd.read_exact(slice::from_mut(&mut flag))?;
         if limit == 1 {
-            return Ok(TapChunkInfo::Unknown { size: 1, flag })
+            return Ok(TapChunkInfo::Unknown { size: 1, flag, checksum: flag })
         }
         match flag {
             HEAD_BLOCK_FLAG if limit == HEADER_SIZE as u64 => {
                 let mut header: [u8; HEADER_SIZE - 1] = Default::default();
                 rd.read_exact(&mut header)?;
-                if checksum(header) != flag {
-                    Ok(TapChunkInfo::Unknown { size: limit as u16, flag })
+                let checksum = checksum(header) ^ flag;
+                if checksum != 0 {
+                    Ok(TapChunkInfo::Unknown { size: limit as u16, flag, checksum })
                 }
                 else {
                     Header::try_from(&header[..HEADER_SIZE - 2])
                     .map(TapChunkInfo::Head)
-                    .or(Ok(TapChunkInfo::Unknown { size: limit as u16, flag }))
+                    .or(Ok(TapChunkInfo::Unknown { size: limit as u16, flag, checksum }))
                 }
             }
             DATA_BLOCK_FLAG => {
-                let checksum = try_checksum(rd.by_ref().bytes())? ^ flag;
+                let checksum = read_checksum(rd)? ^ flag;
                 if rd.limit() != 0 {
                     return Err(Error::new(ErrorKind::InvalidData, "Not a proper TAP block: invalid length"));
                 }
                 Ok(TapChunkInfo::Data{ length: limit as u16 - 2, checksum })
             }
             flag => {
-                // TODO: perhaps check length and read to eof
-                Ok(TapChunkInfo::Unknown { size: limit as u16, flag })
+                let checksum = read_checksum(rd)? ^ flag;
+                if rd.limit() != 0 {
+                    return Err(Error::new(ErrorKind::InvalidData, "Not a proper TAP block: invalid length"));
+                }
+                Ok(TapChunkInfo::Unknown { size: limit as u16, flag, checksum })
             }
         }
     }    
 }
 
+/// Reads the remaining chunk data to calculate its bit toggle checksum.
+fn read_checksum<R: Read>(rd: &mut Take<R>) -> Result<u8> {
+    let mut buf = [0u8; 256];
+    let mut sum = 0;
+    loop {
+        match rd.read(&mut buf) {
+            Ok(0) => break Ok(sum),
+            Ok(len) => sum ^= checksum(&buf[..len]),
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => break Err(e)
+        }
+    }
+}
+
 impl<R> TapChunkReader<R> {
     /// Returns the wrapped reader.
     pub fn into_inner(self) -> R {
@@ -197,6 +233,11 @@ impl<R> TapChunkReader<R> {
     pub fn get_mut(&mut self) -> &mut Take<R> {
         &mut self.inner
     }
+    /// Returns the position in bytes of the tape reader relative to the beginning of the tape,
+    /// which is the end of the current chunk less the number of its bytes left to be read.
+    pub fn tape_position(&self) -> u64 {
+        self.next_pos - self.inner.limit()
+    }
 }
 
 impl<R: Read + Seek> TapChunkReader<R> {
@@ -205,9 +246,10 @@ impl<R: Read + Seek> TapChunkReader<R> {
     ///
     /// `chunk_no` should be the chunk number of the previous chunk.
     pub fn try_from_current(mut rd: R, chunk_no: u32) -> Result<Self> {
-        let next_pos = rd.seek(SeekFrom::Current(0))?;
+        let next_pos = rd.stream_position()?;
+        let tape_length = Some(rd.seek(SeekFrom::End(0))?);
         let inner = rd.take(0);
-        Ok(TapChunkReader { next_pos, chunk_index: chunk_no, checksum: 0, inner })
+        Ok(TapChunkReader { next_pos, chunk_index: chunk_no, checksum: 0, tape_length, inner })
     }
 
     /// Creates a clone of self but with a mutable reference to the underlying reader.
@@ -222,9 +264,59 @@ impl<R: Read + Seek> TapChunkReader<R> {
             checksum: self.checksum,
             next_pos: self.next_pos,
             chunk_index: self.chunk_index,
+            tape_length: self.tape_length,
             inner
         }.try_into()
     }
+
+    /// Returns `Ok(true)` if the checksum byte of the current chunk matches the XOR of its block flag
+    /// and data. Returns `Ok(false)` if the block is damaged or there is no current chunk.
+    ///
+    /// The whole current chunk is re-read from its beginning, but the position of the reader, its limit
+    /// and the [TapChunkReader::checksum] are left unchanged.
+    pub fn verify_checksum(&mut self) -> Result<bool> {
+        let mut rd = self.try_clone_mut()?;
+        rd.rewind_chunk()?;
+        let info = TapChunkInfo::try_from(rd.get_mut())?;
+        rd.done()?;
+        Ok(info.is_checksum_valid())
+    }
+
+    /// Returns the total length of the tape in bytes, leaving the position of the reader unchanged.
+    ///
+    /// The length is cached when the reader is created and on every [TapChunkRead::rewind].
+    /// It's only determined here if it couldn't have been cached before.
+    pub fn tape_length(&mut self) -> Result<u64> {
+        if let Some(length) = self.tape_length {
+            return Ok(length)
+        }
+        let rd = self.inner.get_mut();
+        let pos = rd.stream_position()?;
+        let end = rd.seek(SeekFrom::End(0))?;
+        rd.seek(SeekFrom::Start(pos))?;
+        self.tape_length = Some(end);
+        Ok(end)
+    }
+}
+
+impl<R> TapChunkReader<R> {
+    /// Returns the overall progress of reading the tape as a percentage in the range: [0.0, 100.0].
+    ///
+    /// The progress is the [TapChunkReader::tape_position] relative to the cached length of the tape,
+    /// so the larger chunks take proportionally more of the progress. An empty tape is always at 100%.
+    ///
+    /// Returns `0.0` if the length of the tape couldn't have been determined.
+    /// See [TapChunkReader::tape_length].
+    pub fn tape_overall_progress(&self) -> f32 {
+        match self.tape_length {
+            Some(0) => 100.0,
+            Some(length) => {
+                let position = self.tape_position().min(length);
+                (position as f64 * 100.0 / length as f64) as f32
+            }
+            None => 0.0
+        }
+    }
 }
 
 impl<R: Read + Seek> TapChunkRead for TapChunkReader<R> {
@@ -236,11 +328,13 @@ impl<R: Read + Seek> TapChunkRead for TapChunkReader<R> {
         self.inner.limit() as u16
     }
 
+    /// Also caches the length of the tape.
     fn rewind(&mut self) {
         self.inner.set_limit(0);
         self.checksum = 0;
         self.chunk_index = 0;
         self.next_pos = 0;
+        self.tape_length = self.inner.get_mut().seek(SeekFrom::End(0)).ok();
     }
 
     /// Also clears [TapChunkReader::checksum].
@@ -289,9 +383,10 @@ impl<R: Read> Read for TapChunkReader<R> {
 }
 
 impl<R: Read + Seek> From<R> for TapChunkReader<R> {
-    fn from(rd: R) -> Self {
+    fn from(mut rd: R) -> Self {
+        let tape_length = rd.seek(SeekFrom::End(0)).ok();
         let inner = rd.take(0);
-        TapChunkReader { next_pos: 0, chunk_index: 0, checksum: 0, inner }
+        TapChunkReader { next_pos: 0, chunk_index: 0, checksum: 0, tape_length, inner }
     }
 }
 
@@ -339,7 +434,7 @@ impl<T, R> From<T> for TapReadInfoIter<T>
 {
     #[inline]
     fn from(inner: T) -> Self {
-        TapReadInfoIter { inner }
+        TapReadInfoIter { inner, header: None }
     }
 }
 
@@ -355,7 +450,15 @@ impl<T, R> Iterator for TapReadInfoIter<T>
             Ok(_) => TapChunkInfo::try_from(self.inner.get_mut()),
             Err(e) => return Some(Err(e))
         };
-        Some(info)
+        let header = self.header.take();
+        Some(info.map(|info| match info {
+            TapChunkInfo::Head(header) => {
+                self.header = Some(header);
+                info
+            }
+            TapChunkInfo::Data { length, .. } if header.map(|h| h.length) == Some(length) => info,
+            info => info.into_headerless()
+        }))
     }
 }
 
@@ -383,7 +486,14 @@ impl<R: Read + Seek> From<TapChunkReader<R>> for TapChunkPulseIter<R> {
 
 impl<R: Read + Seek> From<ReadEncPulseIter<TapChunkReader<R>>> for TapChunkPulseIter<R> {
     fn from(ep_iter: ReadEncPulseIter<TapChunkReader<R>>) -> Self {
-        TapChunkPulseIter { auto_next: true, ep_iter }
+        TapChunkPulseIter {
+            auto_next: true,
+            counter_scale: DEFAULT_COUNTER_SCALE,
+            ep_iter,
+            elapsed_ts: 0,
+            chunk_start_ts: 0,
+            chunk_len_ts: 0
+        }
     }
 }
 
@@ -400,6 +510,25 @@ impl<R> TapChunkPulseIter<R> {
     pub fn get_mut(&mut self) -> &mut ReadEncPulseIter<TapChunkReader<R>> {
         &mut self.ep_iter
     }
+    /// Returns the position of the tape in T-states, measured from its beginning.
+    ///
+    /// While the tape is being played, the position advances by the T-states of the emitted pulses.
+    /// When the tape is forwarded or rewound to a particular chunk, the position is set to where the lead
+    /// pulses of that chunk begin, as if the tape had been played from the beginning with
+    /// [TapChunkPulseIter::auto_next] enabled, that is: with a single pulse of [PAUSE_PULSE_LENGTH] T-states
+    /// preceding each chunk.
+    pub fn elapsed_tstates(&self) -> u64 {
+        self.elapsed_ts
+    }
+    /// Returns the value of a *tape counter*, that is [TapChunkPulseIter::elapsed_tstates]
+    /// divided by [TapChunkPulseIter::counter_scale].
+    ///
+    /// The counter increases monotonically while the pulses are being emitted, follows the tape
+    /// position when the tape is forwarded or rewound to a particular chunk, and is reset to 0
+    /// when the tape is [rewound][TapChunkRead::rewind].
+    pub fn tape_counter(&self) -> u64 {
+        self.elapsed_ts / u64::from(self.counter_scale.get())
+    }
 }
 
 impl<R> TapChunkPulseIter<R>
@@ -426,25 +555,42 @@ impl<R: Read + Seek> TapChunkRead for TapChunkPulseIter<R> {
 
     /// Invokes underlying [TapChunkReader::rewind] and [resets][ReadEncPulseIter::reset] the internal
     /// pulse iterator. Returns the result from [TapChunkReader::rewind].
+    ///
+    /// Also resets the [tape counter][TapChunkPulseIter::tape_counter].
     fn rewind(&mut self) {
         self.ep_iter.get_mut().rewind();
         self.ep_iter.reset();
+        self.elapsed_ts = 0;
+        self.chunk_start_ts = 0;
+        self.chunk_len_ts = 0;
     }
 
     /// Invokes underlying [TapChunkReader::next_chunk] and [resets][ReadEncPulseIter::reset] the internal
     /// pulse iterator. Returns the result from [TapChunkReader::next_chunk].
+    ///
+    /// Also moves the [tape counter][TapChunkPulseIter::tape_counter] to the beginning of the next chunk.
     fn next_chunk(&mut self) -> Result<Option<u16>> {
-        let res = self.ep_iter.get_mut().next_chunk()?;
-        self.ep_iter.reset();
-        Ok(res)
+        self.skip_chunks(0)
     }
 
-    /// Invokes underlying [TapChunkReader::skip_chunks] and [resets][ReadEncPulseIter::reset] the internal
-    /// pulse iterator. Returns the result from [TapChunkReader::skip_chunks].
+    /// Invokes underlying [TapChunkReader::next_chunk] `skip + 1` times and [resets][ReadEncPulseIter::reset]
+    /// the internal pulse iterator. Returns the result from the last [TapChunkReader::next_chunk].
+    ///
+    /// Also moves the [tape counter][TapChunkPulseIter::tape_counter] to the beginning of the last chunk.
     fn skip_chunks(&mut self, skip: u32) -> Result<Option<u16>> {
-        let res = self.ep_iter.get_mut().skip_chunks(skip)?;
+        let mut res = None;
+        for _ in 0..=skip {
+            res = self.enter_next_chunk()?;
+            if res.is_none() {
+                break
+            }
+        }
+        self.elapsed_ts = match res {
+            Some(_) => self.chunk_start_ts,
+            None => self.chunk_start_ts + self.chunk_len_ts
+        };
         self.ep_iter.reset();
-        Ok(res)        
+        Ok(res)
     }
 }
 
@@ -464,11 +610,20 @@ impl<R: Read + Seek> Iterator for TapChunkPulseIter<R> {
     type Item = NonZeroU32;
 
     fn next(&mut self) -> Option<Self::Item> {
+        let pulse = self.next_pulse();
+        if let Some(delta) = pulse {
+            self.elapsed_ts += u64::from(delta.get());
+        }
+        pulse
+    }
+}
+
+impl<R: Read + Seek> TapChunkPulseIter<R> {
+    fn next_pulse(&mut self) -> Option<NonZeroU32> {
         match self.ep_iter.next() {
             pulse @ Some(_) => pulse,
             None if self.auto_next => {
-                if self.ep_iter.err().is_some() ||
-                        self.ep_iter.get_mut().next_chunk().is_err() {
+                if self.ep_iter.err().is_some() || self.enter_next_chunk().is_err() {
                     return None;
                 }
                 self.ep_iter.reset();
@@ -482,4 +637,48 @@ impl<R: Read + Seek> Iterator for TapChunkPulseIter<R> {
             None => None
         }
     }
+
+    /// Invokes underlying [TapChunkReader::next_chunk] and updates the position and the length
+    /// of the current chunk in T-states, leaving the pulse iterator and the tape counter unchanged.
+    fn enter_next_chunk(&mut self) -> Result<Option<u16>> {
+        let rd = self.ep_iter.get_mut();
+        let prev_end_ts = match rd.chunk_no() {
+            0 => 0,
+            _ => self.chunk_start_ts + self.chunk_len_ts
+        };
+        let res = rd.next_chunk()?;
+        if res.is_some() {
+            self.chunk_len_ts = chunk_duration_ts(rd)?;
+            self.chunk_start_ts = prev_end_ts + u64::from(PAUSE_PULSE_LENGTH.get());
+        }
+        Ok(res)
+    }
+}
+
+/// Returns the total T-states of the pulses encoding the current chunk, reading it from the beginning.
+///
+/// The position of the reader and its limit are left unchanged.
+fn chunk_duration_ts<R: Read + Seek>(rd: &mut TapChunkReader<R>) -> Result<u64> {
+    let mut rd = rd.try_clone_mut()?;
+    let mut buf = [0u8; 256];
+    let mut ts = 0;
+    loop {
+        let len = match rd.get_mut().read(&mut buf) {
+            Ok(0) => break,
+            Ok(len) => len,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e)
+        };
+        if ts == 0 {
+            let lead_pulses = if buf[0] & 0x80 == 0 { LEAD_PULSES_HEAD } else { LEAD_PULSES_DATA };
+            ts = u64::from(lead_pulses) * u64::from(LEAD_PULSE_LENGTH.get())
+                 + u64::from(SYNC_PULSE1_LENGTH.get() + SYNC_PULSE2_LENGTH.get());
+        }
+        for byte in &buf[..len] {
+            let ones = byte.count_ones();
+            ts += 2 * u64::from(ones * ONE_PULSE_LENGTH.get() + (8 - ones) * ZERO_PULSE_LENGTH.get());
+        }
+    }
+    rd.done()?;
+    Ok(ts)
 }
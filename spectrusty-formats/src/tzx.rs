@@ -5,23 +5,27 @@
 
     For the full copyright notice, see the lib.rs file.
 */
-use core::num::NonZeroU32;
-use core::convert::TryFrom;
-use std::io::{self, Read, Write, Seek};
+/*! **TZX** file format utilities.
 
-use super::tap::TapChunkWriter;
+Currently this module provides conversions between the **TAP** and the **TZX** files.
 
-pub trait TzxChunk {
-    type PulseIter: Iterator<Item=NonZeroU32>;
-    fn id(&self) -> TzxId;
-    fn len(&self) -> usize;
-    fn pulse_iter(&self) -> Self::PulseIter;
-    fn as_slice(&self) -> &[u8];
-    fn write_to_tap<W: Write + Seek>(&self, rd: &mut TapChunkWriter<W>) -> io::Result<Option<usize>>;
-}
+The **TAP** chunks are being stored in the **TZX** file as the standard speed data blocks. The other way
+around only the standard and the turbo speed data blocks can be represented as the **TAP** chunks.
+The pulse timings, pauses and all the other blocks are being lost in the process.
+*/
+use core::convert::TryFrom;
+use std::io::{self, Read, Write, Error, ErrorKind};
+
+/// The signature at the beginning of every **TZX** file.
+pub const TZX_SIGNATURE: &[u8;8] = b"ZXTape!\x1A";
+/// The version of the **TZX** format of the files being created: `(major, minor)`.
+pub const TZX_VERSION: (u8, u8) = (1, 20);
+/// The pause after each standard speed data block created from the **TAP** chunk, in milliseconds.
+pub const DEFAULT_PAUSE_MS: u16 = 1000;
 
 macro_rules! tzx_id {
     ($($id:ident = $n:literal),*) => {
+        /// The **TZX** block IDs.
         #[repr(u8)]
         #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
         pub enum TzxId {
@@ -48,6 +52,8 @@ tzx_id! {
     SeqOfPulses      = 0x13,
     PureData         = 0x14,
     DirectRec        = 0x15,
+    C64RomData       = 0x16,
+    C64TurboData     = 0x17,
     CswRecording     = 0x18,
     Generalized      = 0x19,
     Pause            = 0x20,
@@ -65,7 +71,9 @@ tzx_id! {
     Message          = 0x31,
     Archive          = 0x32,
     Hardware         = 0x33,
+    EmulationInfo    = 0x34,
     Custom           = 0x35,
+    Snapshot         = 0x40,
     Glue             = 0x5A
 }
 
@@ -74,3 +82,271 @@ impl From<TzxId> for u8 {
         id as u8
     }
 }
+
+impl TzxId {
+    /// Returns `true` if the block with this ID produces a tape signal.
+    pub fn is_signal(self) -> bool {
+        use TzxId::*;
+        matches!(self, StandardSpeed|TurboSpeed|PureTone|SeqOfPulses|PureData|
+                       DirectRec|C64RomData|C64TurboData|CswRecording|Generalized)
+    }
+}
+
+/// The result of the [tzx_to_tap] conversion.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TzxToTap {
+    /// The number of the **TAP** chunks written.
+    pub chunks: usize,
+    /// The IDs of the blocks producing a tape signal which couldn't be represented as the **TAP** chunks,
+    /// in the order of their appearance.
+    pub dropped: Vec<TzxId>
+}
+
+/// Reads the **TAP** file from `rd` and writes it to `wr` as the **TZX** file, with each **TAP** chunk
+/// stored as the standard speed data block followed by the pause of [DEFAULT_PAUSE_MS].
+///
+/// Returns the number of blocks written.
+///
+/// # Errors
+/// This function will return an error if the **TAP** file ends in the middle of a chunk.
+/// Other errors may also be returned from attempts to read or write the files.
+pub fn tap_to_tzx<R: Read, W: Write>(mut rd: R, mut wr: W) -> io::Result<usize> {
+    wr.write_all(TZX_SIGNATURE)?;
+    wr.write_all(&[TZX_VERSION.0, TZX_VERSION.1])?;
+    let mut data = Vec::new();
+    let mut blocks = 0;
+    while let Some(size) = read_tap_chunk_size(&mut rd)? {
+        data.resize(size as usize, 0);
+        rd.read_exact(&mut data)?;
+        wr.write_all(&[TzxId::StandardSpeed.into()])?;
+        wr.write_all(&DEFAULT_PAUSE_MS.to_le_bytes())?;
+        wr.write_all(&size.to_le_bytes())?;
+        wr.write_all(&data)?;
+        blocks += 1;
+    }
+    Ok(blocks)
+}
+
+/// Reads the **TZX** file from `rd` and writes the data of its standard and turbo speed data blocks
+/// to `wr` as the **TAP** file.
+///
+/// The turbo speed data blocks can be represented only if all bits of their last byte are being used.
+/// The IDs of the dropped blocks producing a tape signal are being reported in the returned [TzxToTap].
+///
+/// # Errors
+/// This function will return an error if the file is not a **TZX** file, a block with an unknown ID below
+/// `0x10` is encountered or the file ends in the middle of a block. The blocks with other unknown IDs are
+/// being skipped, assuming their length is stored in the first 4 bytes, as the **TZX** specification requires
+/// of the future extensions. Other errors may also be returned from attempts to read or write the files.
+pub fn tzx_to_tap<R: Read, W: Write>(mut rd: R, mut wr: W) -> io::Result<TzxToTap> {
+    let mut header = [0u8;10];
+    rd.read_exact(&mut header)?;
+    if &header[..8] != TZX_SIGNATURE {
+        return Err(Error::new(ErrorKind::InvalidData, "TZX: invalid signature"))
+    }
+    let mut res = TzxToTap::default();
+    let mut id = 0u8;
+    while rd.read(core::slice::from_mut(&mut id))? != 0 {
+        let id = match TzxId::try_from(id) {
+            Ok(id) => id,
+            Err(_) if id >= TzxId::StandardSpeed.into() => {
+                let length = u32::from_le_bytes(read_array::<_, 4>(&mut rd)?);
+                skip(&mut rd, length.into())?;
+                continue
+            }
+            Err(e) => return Err(Error::new(ErrorKind::InvalidData, e))
+        };
+        let (head_size, length) = match id {
+            TzxId::StandardSpeed => {
+                let head = read_array::<_, 4>(&mut rd)?;
+                let size = u16::from_le_bytes([head[2], head[3]]);
+                copy_tap_chunk(&mut rd, &mut wr, size)?;
+                res.chunks += 1;
+                continue
+            }
+            TzxId::TurboSpeed => {
+                let head = read_array::<_, 0x12>(&mut rd)?;
+                let used_bits = head[0x0C];
+                let length = u32_from_le24(&head[0x0F..]);
+                match u16::try_from(length) {
+                    Ok(size) if used_bits == 8 => {
+                        copy_tap_chunk(&mut rd, &mut wr, size)?;
+                        res.chunks += 1;
+                    }
+                    _ => {
+                        skip(&mut rd, length.into())?;
+                        res.dropped.push(id);
+                    }
+                }
+                continue
+            }
+            TzxId::PureTone => (4, 0),
+            TzxId::SeqOfPulses => {
+                let head = read_array::<_, 1>(&mut rd)?;
+                (0, u32::from(head[0]) * 2)
+            }
+            TzxId::PureData => {
+                let head = read_array::<_, 0x0A>(&mut rd)?;
+                (0, u32_from_le24(&head[0x07..]))
+            }
+            TzxId::DirectRec => {
+                let head = read_array::<_, 0x08>(&mut rd)?;
+                (0, u32_from_le24(&head[0x05..]))
+            }
+            TzxId::C64RomData|TzxId::C64TurboData|TzxId::CswRecording|TzxId::Generalized|
+            TzxId::StopIn48k|TzxId::SetLevel => {
+                (0, u32::from_le_bytes(read_array::<_, 4>(&mut rd)?))
+            }
+            TzxId::Pause|TzxId::Jump|TzxId::LoopStart => (2, 0),
+            TzxId::GroupEnd|TzxId::LoopEnd|TzxId::Return => (0, 0),
+            TzxId::GroupStart|TzxId::Text => {
+                (0, u32::from(read_array::<_, 1>(&mut rd)?[0]))
+            }
+            TzxId::CallSeq => {
+                (0, u32::from(u16::from_le_bytes(read_array::<_, 2>(&mut rd)?)) * 2)
+            }
+            TzxId::Select|TzxId::Archive => {
+                (0, u32::from(u16::from_le_bytes(read_array::<_, 2>(&mut rd)?)))
+            }
+            TzxId::Message => {
+                (0, u32::from(read_array::<_, 2>(&mut rd)?[1]))
+            }
+            TzxId::Hardware => {
+                (0, u32::from(read_array::<_, 1>(&mut rd)?[0]) * 3)
+            }
+            TzxId::EmulationInfo => (8, 0),
+            TzxId::Snapshot => {
+                let head = read_array::<_, 4>(&mut rd)?;
+                (0, u32_from_le24(&head[1..]))
+            }
+            TzxId::Custom => {
+                let head = read_array::<_, 0x14>(&mut rd)?;
+                (0, u32::from_le_bytes([head[0x10], head[0x11], head[0x12], head[0x13]]))
+            }
+            TzxId::Glue => (9, 0)
+        };
+        skip(&mut rd, head_size + u64::from(length))?;
+        if id.is_signal() {
+            res.dropped.push(id);
+        }
+    }
+    Ok(res)
+}
+
+fn read_tap_chunk_size<R: Read>(rd: &mut R) -> io::Result<Option<u16>> {
+    let mut size = [0u8;2];
+    match rd.read(&mut size[..1])? {
+        0 => Ok(None),
+        _ => {
+            rd.read_exact(&mut size[1..])?;
+            Ok(Some(u16::from_le_bytes(size)))
+        }
+    }
+}
+
+fn copy_tap_chunk<R: Read, W: Write>(rd: &mut R, wr: &mut W, size: u16) -> io::Result<()> {
+    wr.write_all(&size.to_le_bytes())?;
+    let copied = io::copy(&mut rd.take(size.into()), wr)?;
+    if copied != u64::from(size) {
+        return Err(Error::new(ErrorKind::UnexpectedEof, "TZX: unexpected end of file"))
+    }
+    Ok(())
+}
+
+fn read_array<R: Read, const N: usize>(rd: &mut R) -> io::Result<[u8;N]> {
+    let mut buf = [0u8;N];
+    rd.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn skip<R: Read>(rd: &mut R, length: u64) -> io::Result<()> {
+    let skipped = io::copy(&mut rd.take(length), &mut io::sink())?;
+    if skipped != length {
+        return Err(Error::new(ErrorKind::UnexpectedEof, "TZX: unexpected end of file"))
+    }
+    Ok(())
+}
+
+fn u32_from_le24(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0])
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tap::{Header, TapChunk, checksum, DATA_BLOCK_FLAG};
+    use super::*;
+
+    fn make_tap() -> Vec<u8> {
+        let header = Header::new_code(4).with_name("tzx test").with_start(0x8000).to_tap_chunk();
+        let mut data = vec![DATA_BLOCK_FLAG, 1, 2, 3, 4];
+        data.push(checksum(&data));
+        let mut tap = Vec::new();
+        for chunk in [header.as_ref(), TapChunk::from(&data[..]).as_ref()] {
+            tap.extend_from_slice(&(chunk.len() as u16).to_le_bytes());
+            tap.extend_from_slice(chunk);
+        }
+        tap
+    }
+
+    #[test]
+    fn tzx_round_trip_works() {
+        let tap = make_tap();
+        let mut tzx = Vec::new();
+        assert_eq!(tap_to_tzx(&tap[..], &mut tzx).unwrap(), 2);
+        assert_eq!(&tzx[..8], TZX_SIGNATURE);
+        assert_eq!(&tzx[8..10], &[1, 20]);
+        assert_eq!(&tzx[10..15], &[0x10, 0xE8, 0x03, 19, 0]);
+        assert_eq!(tzx.len(), 10 + tap.len() + 2 * 3);
+        let mut tap_out = Vec::new();
+        assert_eq!(tzx_to_tap(&tzx[..], &mut tap_out).unwrap(), TzxToTap { chunks: 2, dropped: vec![] });
+        assert_eq!(tap_out, tap);
+    }
+
+    #[test]
+    fn tzx_to_tap_drops_blocks() {
+        let mut tzx = Vec::new();
+        tap_to_tzx(&make_tap()[..], &mut tzx).unwrap();
+        // text description
+        tzx.extend_from_slice(&[0x30, 3, b'a', b'b', b'c']);
+        // pure tone
+        tzx.extend_from_slice(&[0x12, 0x78, 0x08, 0x7F, 0x1F]);
+        // turbo speed data with all bits of the last byte used
+        let mut turbo = vec![0x11, 0x78, 0x08, 0x9B, 0x02, 0xDF, 0x02, 0x57, 0x03, 0xAE, 0x06, 0x7F, 0x1F,
+                             8, 0xE8, 0x03, 3, 0, 0, DATA_BLOCK_FLAG, 9, DATA_BLOCK_FLAG ^ 9];
+        tzx.extend_from_slice(&turbo);
+        // and with only 6 bits used
+        turbo[13] = 6;
+        tzx.extend_from_slice(&turbo);
+        // pause
+        tzx.extend_from_slice(&[0x20, 0, 0]);
+        // C64 ROM type data
+        tzx.extend_from_slice(&[0x16, 2, 0, 0, 0, 0xAA, 0x55]);
+        // C64 turbo tape data
+        tzx.extend_from_slice(&[0x17, 1, 0, 0, 0, 0xAA]);
+        // emulation info
+        tzx.extend_from_slice(&[0x34, 1, 2, 3, 4, 5, 6, 7, 8]);
+        // snapshot
+        tzx.extend_from_slice(&[0x40, 0, 3, 0, 0, 1, 2, 3]);
+        // unknown extension blocks
+        tzx.extend_from_slice(&[0x4B, 3, 0, 0, 0, 1, 2, 3]);
+        tzx.extend_from_slice(&[0xFF, 0, 0, 0, 0]);
+        let mut tap = Vec::new();
+        assert_eq!(tzx_to_tap(&tzx[..], &mut tap).unwrap(), TzxToTap {
+            chunks: 3,
+            dropped: vec![TzxId::PureTone, TzxId::TurboSpeed, TzxId::C64RomData, TzxId::C64TurboData]
+        });
+        let mut expected = make_tap();
+        expected.extend_from_slice(&[3, 0, DATA_BLOCK_FLAG, 9, DATA_BLOCK_FLAG ^ 9]);
+        assert_eq!(tap, expected);
+        // errors
+        assert_eq!(tzx_to_tap(&make_tap()[..], io::sink()).unwrap_err().kind(), ErrorKind::InvalidData);
+        tzx.push(0x0F);
+        assert_eq!(tzx_to_tap(&tzx[..], io::sink()).unwrap_err().kind(), ErrorKind::InvalidData);
+        tzx.pop();
+        tzx.extend_from_slice(&[0x99, 4, 0, 0, 0, 1]);
+        assert_eq!(tzx_to_tap(&tzx[..], io::sink()).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+        tzx.truncate(tzx.len() - 6);
+        tzx.truncate(tzx.len() - 4);
+        assert_eq!(tzx_to_tap(&tzx[..], io::sink()).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,588 @@
+/*
+    Copyright (C) 2020-2023  Rafal Michalski
+
+    This file is part of SPECTRUSTY, a Rust library for building emulators.
+
+    For the full copyright notice, see the lib.rs file.
+*/
+//! **WAV** audio file utilities for loading recordings of real cassette tapes.
+//!
+//! The audio signal is converted to T-state pulse intervals by detecting the crossings of the signal
+//! through the zero level with a hysteresis, so the resulting pulses can be fed directly to the `EAR IN`
+//! buffer of the ZX Spectrum emulator (e.g. via [EarIn::feed_ear_in][spectrusty_core::chip::EarIn::feed_ear_in]).
+//!
+//! The `MIC out` signal can be recorded as a **WAV** file with [WavWriter].
+//!
+//! Rendered audio samples can be written as a 16-bit PCM **WAV** file with [write_wav_pcm16].
+use core::convert::TryFrom;
+use core::num::NonZeroU32;
+use std::io::{self, Read, Write, Seek, SeekFrom};
+
+use spectrusty_core::audio::FromSample;
+use spectrusty_core::clock::FTs;
+
+/// The default hysteresis threshold of the [WavPulseIter].
+pub const DEFAULT_THRESHOLD: u16 = 1024;
+
+const WAVE_FORMAT_PCM: u16 = 1;
+const WAV_HEADER_SIZE: u32 = 44;
+/// The 8-bit sample value of the low `MIC out` level written by the [WavWriter].
+pub const MIC_LOW_SAMPLE: u8 = 0x40;
+/// The 8-bit sample value of the high `MIC out` level written by the [WavWriter].
+pub const MIC_HIGH_SAMPLE: u8 = 0xC0;
+
+/// Decodes samples of the **WAV** file read from an underlying reader as *TAPE* T-state pulse
+/// intervals via an [Iterator] interface.
+///
+/// Only uncompressed PCM files with 8, 16, 24 or 32 bits per sample are supported, with any sample rate.
+/// If the file has more than one channel, only the first one is taken into account.
+///
+/// A new pulse begins each time the signal crosses the zero level and its absolute value exceeds
+/// the threshold. The threshold provides a hysteresis rejecting the noise around the zero level.
+/// The threshold is expressed in units of the signed 16-bit samples, regardless of the sample format
+/// of the file.
+///
+/// Reading samples one by one from an unbuffered reader is slow, so wrapping the reader in
+/// [std::io::BufReader] is advised.
+#[derive(Debug)]
+pub struct WavPulseIter<R> {
+    rd: io::Take<R>,
+    cpu_hz: u32,
+    sample_rate: u32,
+    frame: Box<[u8]>,
+    bytes_per_sample: u16,
+    threshold: u16,
+    level: bool,
+    sample_index: u64,
+    last_ts: u64,
+    error: Option<io::Error>,
+}
+
+fn invalid_data<T>(msg: &str) -> io::Result<T> {
+    Err(io::Error::new(io::ErrorKind::InvalidData, msg))
+}
+
+fn read_tag<R: Read>(rd: &mut R) -> io::Result<([u8;4], u32)> {
+    let mut header = [0u8;8];
+    rd.read_exact(&mut header)?;
+    let mut tag = [0u8;4];
+    let mut size = [0u8;4];
+    tag.copy_from_slice(&header[0..4]);
+    size.copy_from_slice(&header[4..8]);
+    Ok((tag, u32::from_le_bytes(size)))
+}
+
+fn skip_chunk<R: Read>(rd: &mut R, size: u32) -> io::Result<()> {
+    // chunks are padded to an even number of bytes
+    let size = u64::from(size) + u64::from(size & 1);
+    if io::copy(&mut rd.take(size), &mut io::sink())? != size {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "WAV chunk is truncated"))
+    }
+    Ok(())
+}
+
+impl<R: Read> WavPulseIter<R> {
+    /// Creates a new `WavPulseIter` from a given [Reader][Read] after parsing the **WAV** file header.
+    ///
+    /// `cpu_hz` is the CPU clock frequency used to scale the pulse intervals to T-states.
+    ///
+    /// # Errors
+    /// This function will return an error if the file is not a supported **WAV** file or if an error
+    /// occurred while reading the header.
+    ///
+    /// # Panics
+    /// Panics if `cpu_hz` is `0`.
+    pub fn new(mut rd: R, cpu_hz: u32) -> io::Result<Self> {
+        assert_ne!(cpu_hz, 0, "cpu_hz must not be 0");
+        let (tag, _) = read_tag(&mut rd)?;
+        let mut wave = [0u8;4];
+        rd.read_exact(&mut wave)?;
+        if &tag != b"RIFF" || &wave != b"WAVE" {
+            return invalid_data("not a WAV file")
+        }
+        let mut format = None;
+        loop {
+            match read_tag(&mut rd)? {
+                (tag, size) if &tag == b"fmt " => {
+                    if size < 16 {
+                        return invalid_data("WAV format chunk is too short")
+                    }
+                    let mut fmt = [0u8;16];
+                    rd.read_exact(&mut fmt)?;
+                    skip_chunk(&mut rd, size - 16)?;
+                    let format_tag = u16::from_le_bytes([fmt[0], fmt[1]]);
+                    let channels = u16::from_le_bytes([fmt[2], fmt[3]]);
+                    let sample_rate = u32::from_le_bytes([fmt[4], fmt[5], fmt[6], fmt[7]]);
+                    let block_align = u16::from_le_bytes([fmt[12], fmt[13]]);
+                    let bits_per_sample = u16::from_le_bytes([fmt[14], fmt[15]]);
+                    if format_tag != WAVE_FORMAT_PCM {
+                        return invalid_data("only PCM WAV files are supported")
+                    }
+                    if !matches!(bits_per_sample, 8|16|24|32) {
+                        return invalid_data("unsupported WAV sample size")
+                    }
+                    let bytes_per_sample = bits_per_sample / 8;
+                    if channels == 0 || sample_rate == 0 || block_align < bytes_per_sample {
+                        return invalid_data("invalid WAV format")
+                    }
+                    format = Some((sample_rate, block_align, bytes_per_sample));
+                }
+                (tag, size) if &tag == b"data" => {
+                    let (sample_rate, block_align, bytes_per_sample) = match format {
+                        Some(format) => format,
+                        None => return invalid_data("WAV format chunk missing")
+                    };
+                    return Ok(WavPulseIter {
+                        rd: rd.take(size.into()),
+                        cpu_hz,
+                        sample_rate,
+                        frame: vec![0u8; block_align.into()].into_boxed_slice(),
+                        bytes_per_sample,
+                        threshold: DEFAULT_THRESHOLD,
+                        level: false,
+                        sample_index: 0,
+                        last_ts: 0,
+                        error: None
+                    })
+                }
+                (_, size) => skip_chunk(&mut rd, size)?
+            }
+        }
+    }
+}
+
+impl<R> WavPulseIter<R> {
+    /// Returns a new instance with the hysteresis threshold set to the given value.
+    pub fn with_threshold(mut self, threshold: u16) -> Self {
+        self.threshold = threshold;
+        self
+    }
+    /// Returns the current hysteresis threshold.
+    pub fn threshold(&self) -> u16 {
+        self.threshold
+    }
+    /// Changes the hysteresis threshold.
+    pub fn set_threshold(&mut self, threshold: u16) {
+        self.threshold = threshold;
+    }
+    /// Returns the sample rate of the **WAV** file.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+    /// Returns the CPU clock frequency used to scale the pulse intervals.
+    pub fn cpu_hz(&self) -> u32 {
+        self.cpu_hz
+    }
+    /// Returns an error from the underlying reader if there was one.
+    pub fn err(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+    /// Returns a mutable reference to the inner reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        self.rd.get_mut()
+    }
+    /// Returns a shared reference to the inner reader.
+    pub fn get_ref(&self) -> &R {
+        self.rd.get_ref()
+    }
+    /// Returns the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.rd.into_inner()
+    }
+}
+
+impl<R: Read> WavPulseIter<R> {
+    /// Reads the next sample of the first channel converted to a signed 16-bit value.
+    fn read_sample(&mut self) -> Option<i16> {
+        let frame = &mut self.frame;
+        match self.rd.read_exact(frame) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return None,
+            Err(e) => {
+                self.error = Some(e);
+                return None
+            }
+        }
+        let bps = usize::from(self.bytes_per_sample);
+        Some(if bps == 1 {
+            // 8-bit samples are unsigned
+            i16::from(frame[0] as i8 ^ i8::MIN) << 8
+        }
+        else {
+            // the most significant bytes of the little-endian sample
+            i16::from_le_bytes([frame[bps - 2], frame[bps - 1]])
+        })
+    }
+}
+
+impl<R: Read> Iterator for WavPulseIter<R> {
+    type Item = NonZeroU32;
+    fn next(&mut self) -> Option<NonZeroU32> {
+        let threshold = i32::from(self.threshold);
+        loop {
+            let sample = i32::from(self.read_sample()?);
+            self.sample_index += 1;
+            let level = if sample > threshold {
+                true
+            }
+            else if sample < -threshold {
+                false
+            }
+            else {
+                continue
+            };
+            if level != self.level {
+                self.level = level;
+                let ts = (self.sample_index - 1) * u64::from(self.cpu_hz) / u64::from(self.sample_rate);
+                let delta = ts - self.last_ts;
+                self.last_ts = ts;
+                let delta = u32::try_from(delta).unwrap_or(u32::MAX);
+                return Some(NonZeroU32::new(delta).unwrap_or(NonZeroU32::new(1).unwrap()))
+            }
+        }
+    }
+}
+
+/// Records the `MIC out` pulses as a mono, 8-bit PCM **WAV** file written to an underlying writer.
+///
+/// Pulses should be appended after each rendered frame with [WavWriter::append_frame], e.g. from an
+/// iterator returned by [MicOut::mic_out_pulse_iter]. The RIFF header is completed by [WavWriter::finish].
+///
+/// The signal starts at the low level. The first pulse ever appended is placed not later than at the end
+/// of the frame it was appended with, as the time of the previous `MIC out` change is unknown.
+///
+/// [MicOut::mic_out_pulse_iter]: spectrusty_core::chip::MicOut::mic_out_pulse_iter
+#[derive(Debug)]
+pub struct WavWriter<W> {
+    wr: W,
+    cpu_hz: u32,
+    sample_rate: u32,
+    level: bool,
+    started: bool,
+    edge_ts: u64,
+    frame_end_ts: u64,
+    samples: u64,
+    buf: Vec<u8>,
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    /// Creates a new `WavWriter` with the given `sample_rate` and writes the preliminary **WAV** header.
+    ///
+    /// `cpu_hz` is the CPU clock frequency used to scale the T-state pulse intervals to samples.
+    ///
+    /// # Panics
+    /// Panics if `cpu_hz` or `sample_rate` is `0`.
+    pub fn new(mut wr: W, sample_rate: u32, cpu_hz: u32) -> io::Result<Self> {
+        assert_ne!(cpu_hz, 0, "cpu_hz must not be 0");
+        assert_ne!(sample_rate, 0, "sample_rate must not be 0");
+        wr.write_all(&wav_header(sample_rate, 1, 8, 0))?;
+        Ok(WavWriter {
+            wr, cpu_hz, sample_rate,
+            level: false,
+            started: false,
+            edge_ts: 0,
+            frame_end_ts: 0,
+            samples: 0,
+            buf: Vec::new()
+        })
+    }
+    /// Appends the `MIC out` pulses of a single frame lasting `frame_tstates` T-states.
+    ///
+    /// # Errors
+    /// This function will return an error if writing to the underlying writer fails or if the **WAV**
+    /// file would exceed its size limit.
+    pub fn append_frame<I>(&mut self, pulses: I, frame_tstates: FTs) -> io::Result<()>
+        where I: IntoIterator<Item=NonZeroU32>
+    {
+        self.frame_end_ts += u64::try_from(frame_tstates).unwrap_or(0);
+        for pulse in pulses {
+            let edge_ts = self.edge_ts + u64::from(pulse.get());
+            self.edge_ts = if self.started {
+                edge_ts
+            }
+            else {
+                self.started = true;
+                edge_ts.min(self.frame_end_ts)
+            };
+            self.write_level_until(self.edge_ts)?;
+            self.level = !self.level;
+        }
+        self.write_level_until(self.frame_end_ts)
+    }
+    /// Returns the number of samples written so far.
+    pub fn sample_count(&self) -> u64 {
+        self.samples
+    }
+    /// Returns the sample rate of the recorded file.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+    /// Returns the current `MIC out` level.
+    pub fn level(&self) -> bool {
+        self.level
+    }
+    /// Completes the **WAV** header and returns the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        let data_size = u32::try_from(self.samples).unwrap(); // checked by write_level_until
+        if data_size & 1 == 1 {
+            self.wr.write_all(&[0])?;
+        }
+        self.wr.seek(SeekFrom::Start(0))?;
+        self.wr.write_all(&wav_header(self.sample_rate, 1, 8, data_size))?;
+        self.wr.seek(SeekFrom::End(0))?;
+        self.wr.flush()?;
+        Ok(self.wr)
+    }
+
+    fn write_level_until(&mut self, ts: u64) -> io::Result<()> {
+        let (sample_rate, cpu_hz) = (u64::from(self.sample_rate), u64::from(self.cpu_hz));
+        // samples timestamped before ts
+        let end = match ts * sample_rate {
+            0 => 0,
+            t => (t - 1) / cpu_hz + 1
+        };
+        if end <= self.samples {
+            return Ok(())
+        }
+        if end > u64::from(u32::MAX - WAV_HEADER_SIZE) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "WAV file size limit exceeded"))
+        }
+        let sample = if self.level { MIC_HIGH_SAMPLE } else { MIC_LOW_SAMPLE };
+        self.buf.clear();
+        self.buf.resize((end - self.samples) as usize, sample);
+        self.wr.write_all(&self.buf)?;
+        self.samples = end;
+        Ok(())
+    }
+}
+
+/// Writes the channel-interleaved `samples` to `wr` as a 16-bit PCM **WAV** file.
+///
+/// # Errors
+/// This function will return an error of [io::ErrorKind::InvalidInput] kind if the data is too large
+/// for the **WAV** file. Other errors may also be returned from attempts to write the file.
+pub fn write_wav_pcm16<W, S>(
+        mut wr: W,
+        sample_rate: u32,
+        nchannels: u16,
+        samples: &[S]
+    ) -> io::Result<()>
+    where W: Write,
+          S: Copy,
+          i16: FromSample<S>
+{
+    let data_size = u32::try_from(samples.len() * 2).ok()
+                    .filter(|&size| size <= u32::MAX - WAV_HEADER_SIZE)
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput,
+                                        "WAV file size limit exceeded"))?;
+    wr.write_all(&wav_header(sample_rate, nchannels, 16, data_size))?;
+    let mut buf = Vec::with_capacity(samples.len().min(4096) * 2);
+    for chunk in samples.chunks(4096) {
+        buf.clear();
+        buf.extend(chunk.iter().flat_map(|&sample| i16::from_sample(sample).to_le_bytes()));
+        wr.write_all(&buf)?;
+    }
+    wr.flush()
+}
+
+fn wav_header(
+        sample_rate: u32,
+        nchannels: u16,
+        bits_per_sample: u16,
+        data_size: u32
+    ) -> [u8;WAV_HEADER_SIZE as usize]
+{
+    let block_align = nchannels * (bits_per_sample / 8);
+    let mut header = [0u8;WAV_HEADER_SIZE as usize];
+    let riff_size = WAV_HEADER_SIZE - 8 + data_size + (data_size & 1);
+    header[0..4].copy_from_slice(b"RIFF");
+    header[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    header[8..12].copy_from_slice(b"WAVE");
+    header[12..16].copy_from_slice(b"fmt ");
+    header[16..20].copy_from_slice(&16u32.to_le_bytes());
+    header[20..22].copy_from_slice(&WAVE_FORMAT_PCM.to_le_bytes());
+    header[22..24].copy_from_slice(&nchannels.to_le_bytes());
+    header[24..28].copy_from_slice(&sample_rate.to_le_bytes());
+    header[28..32].copy_from_slice(&(sample_rate * u32::from(block_align)).to_le_bytes()); // byte rate
+    header[32..34].copy_from_slice(&block_align.to_le_bytes());
+    header[34..36].copy_from_slice(&bits_per_sample.to_le_bytes());
+    header[36..40].copy_from_slice(b"data");
+    header[40..44].copy_from_slice(&data_size.to_le_bytes());
+    header
+}
+
+#[cfg(test)]
+mod tests {
+    use core::convert::TryInto;
+    use std::io::Cursor;
+    use super::*;
+
+    fn wav_file(sample_rate: u32, bits: u16, channels: u16, samples: &[i16]) -> Vec<u8> {
+        let bps = bits / 8;
+        let block_align = bps * channels;
+        let mut data = Vec::new();
+        for &sample in samples {
+            for _ in 0..channels {
+                match bps {
+                    1 => data.push((sample >> 8) as u8 ^ 0x80),
+                    _ => {
+                        data.resize(data.len() + usize::from(bps) - 2, 0);
+                        data.extend_from_slice(&sample.to_le_bytes());
+                    }
+                }
+            }
+        }
+        let mut wav = Vec::new();
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(4 + 8 + 16 + 8 + 6 + 8 + data.len() as u32).to_le_bytes());
+        wav.extend_from_slice(b"WAVE");
+        wav.extend_from_slice(b"LIST");
+        wav.extend_from_slice(&5u32.to_le_bytes());
+        wav.extend_from_slice(&[1, 2, 3, 4, 5, 0]);
+        wav.extend_from_slice(b"fmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&WAVE_FORMAT_PCM.to_le_bytes());
+        wav.extend_from_slice(&channels.to_le_bytes());
+        wav.extend_from_slice(&sample_rate.to_le_bytes());
+        wav.extend_from_slice(&(sample_rate * u32::from(block_align)).to_le_bytes());
+        wav.extend_from_slice(&block_align.to_le_bytes());
+        wav.extend_from_slice(&bits.to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        wav.extend_from_slice(&data);
+        wav
+    }
+
+    // a square wave tone followed by a noisy silence
+    fn square_wave(sample_rate: u32, tone_hz: u32, periods: u32) -> Vec<i16> {
+        let samples = u64::from(sample_rate * periods / tone_hz);
+        let mut wave: Vec<i16> = (0..samples).map(|i| {
+            if (i * u64::from(2 * tone_hz) / u64::from(sample_rate)) & 1 == 0 { 12000 } else { -12000 }
+        }).collect();
+        wave.extend((0..1000).map(|i| if i & 1 == 0 { 500 } else { -500 }));
+        wave
+    }
+
+    #[test]
+    fn wav_pulse_works() {
+        const CPU_HZ: u32 = 3_500_000;
+        for &(sample_rate, bits, channels) in &[(44100, 16, 1), (22050, 8, 1), (48000, 24, 2), (11025, 32, 1)] {
+            let tone_hz = 1000;
+            let wav = wav_file(sample_rate, bits, channels, &square_wave(sample_rate, tone_hz, 100));
+            let iter = WavPulseIter::new(Cursor::new(wav), CPU_HZ).unwrap();
+            assert_eq!(iter.sample_rate(), sample_rate);
+            assert_eq!(iter.cpu_hz(), CPU_HZ);
+            assert_eq!(iter.threshold(), DEFAULT_THRESHOLD);
+            let pulses: Vec<u32> = iter.map(NonZeroU32::get).collect();
+            // the first rising edge at the first sample and the last falling edge
+            assert_eq!(pulses.len(), 200);
+            assert_eq!(pulses[0], 1);
+            let half_period = CPU_HZ / tone_hz / 2;
+            let tolerance = CPU_HZ / sample_rate + 1;
+            for &pulse in &pulses[1..] {
+                assert!(pulse.abs_diff(half_period) <= tolerance,
+                    "pulse: {} expected: {}±{}", pulse, half_period, tolerance);
+            }
+            let total: u32 = pulses[1..].iter().sum();
+            assert!(total.abs_diff(199 * half_period) <= tolerance);
+        }
+    }
+
+    #[test]
+    fn wav_pulse_threshold_works() {
+        let wav = wav_file(44100, 16, 1, &square_wave(44100, 1000, 10));
+        let mut iter = WavPulseIter::new(Cursor::new(wav.clone()), 3_500_000).unwrap().with_threshold(0);
+        assert_eq!(iter.threshold(), 0);
+        // the noise is detected as pulses
+        assert_eq!(iter.by_ref().count(), 20 + 1000);
+        assert!(iter.err().is_none());
+        let mut iter = WavPulseIter::new(Cursor::new(wav), 3_500_000).unwrap();
+        iter.set_threshold(500);
+        assert_eq!(iter.count(), 20);
+    }
+
+    #[test]
+    fn wav_header_errors() {
+        let wav = wav_file(44100, 16, 1, &[]);
+        assert!(WavPulseIter::new(Cursor::new(&wav[..]), 3_500_000).unwrap().next().is_none());
+        let err = WavPulseIter::new(Cursor::new(&wav[..wav.len() - 8]), 3_500_000).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        let mut bad = wav.clone();
+        bad[8] = b'X';
+        let err = WavPulseIter::new(Cursor::new(bad), 3_500_000).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let mut bad = wav;
+        bad[12 + 8 + 6 + 8] = 3; // IEEE float
+        let err = WavPulseIter::new(Cursor::new(bad), 3_500_000).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn wav_writer_works() {
+        const CPU_HZ: u32 = 3_500_000;
+        const FRAME_TS: FTs = 69888;
+        let sample_rate = 44100;
+        let mut writer = WavWriter::new(Cursor::new(Vec::new()), sample_rate, CPU_HZ).unwrap();
+        assert_eq!(writer.sample_rate(), sample_rate);
+        let deltas = [1000u32, 2000, 3000, 20000];
+        let pulses = deltas.iter().map(|&d| NonZeroU32::new(d).unwrap());
+        writer.append_frame(pulses, FRAME_TS).unwrap();
+        assert!(!writer.level());
+        // 69888 * 44100 / 3500000 = 880.5888
+        assert_eq!(writer.sample_count(), 881);
+        // a silent frame
+        writer.append_frame(None, FRAME_TS).unwrap();
+        assert_eq!(writer.sample_count(), 1762);
+        let wav = writer.finish().unwrap().into_inner();
+        assert_eq!(wav.len(), 44 + 1762);
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(u32::from_le_bytes(wav[4..8].try_into().unwrap()), 36 + 1762);
+        assert_eq!(u32::from_le_bytes(wav[40..44].try_into().unwrap()), 1762);
+        // the waveform transitions
+        let data = &wav[44..];
+        let mut edges = vec![0];
+        edges.extend(deltas.iter().scan(0, |ts, &d| { *ts += d; Some(*ts) }));
+        for (index, &sample) in data.iter().enumerate() {
+            let ts = index as u64 * u64::from(CPU_HZ) / u64::from(sample_rate);
+            let level = edges.iter().filter(|&&e| u64::from(e) <= ts).count() & 1 == 0;
+            let expected = if level { MIC_HIGH_SAMPLE } else { MIC_LOW_SAMPLE };
+            assert_eq!(sample, expected, "sample: {}", index);
+        }
+        // read back the pulses
+        let pulses: Vec<u32> = WavPulseIter::new(Cursor::new(wav), CPU_HZ).unwrap().map(NonZeroU32::get).collect();
+        assert_eq!(pulses.len(), deltas.len());
+        let tolerance = CPU_HZ / sample_rate + 1;
+        for (pulse, delta) in pulses.into_iter().zip(deltas) {
+            assert!(pulse.abs_diff(delta) <= tolerance, "pulse: {} expected: {}", pulse, delta);
+        }
+    }
+
+    #[test]
+    fn write_wav_pcm16_works() {
+        // a stereo square wave: the first channel is loud, the second is silent
+        let samples: Vec<f32> = (0..4400).flat_map(|i| {
+            let sample = if i / 50 % 2 == 0 { 0.5 } else { -0.5 };
+            core::iter::once(sample).chain(core::iter::once(0.0))
+        }).collect();
+        let mut wav = Vec::new();
+        write_wav_pcm16(&mut wav, 44100, 2, &samples).unwrap();
+        assert_eq!(wav.len(), 44 + 4 * 4400);
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(u32::from_le_bytes(wav[4..8].try_into().unwrap()), 36 + 4 * 4400);
+        assert_eq!(u16::from_le_bytes(wav[22..24].try_into().unwrap()), 2);
+        assert_eq!(u32::from_le_bytes(wav[24..28].try_into().unwrap()), 44100);
+        assert_eq!(u32::from_le_bytes(wav[28..32].try_into().unwrap()), 4 * 44100);
+        assert_eq!(u16::from_le_bytes(wav[32..34].try_into().unwrap()), 4);
+        assert_eq!(u16::from_le_bytes(wav[34..36].try_into().unwrap()), 16);
+        assert_eq!(u32::from_le_bytes(wav[40..44].try_into().unwrap()), 4 * 4400);
+        assert_eq!(&wav[44..48], &[0xFF, 0x3F, 0, 0]);
+        // read back the pulses of the first channel
+        let pulses: Vec<u32> = WavPulseIter::new(Cursor::new(wav), 3_528_000).unwrap().map(NonZeroU32::get).collect();
+        assert_eq!(pulses.len(), 88);
+        assert!(pulses[1..].iter().all(|&pulse| pulse == 4000));
+        let mut wav = Vec::new();
+        write_wav_pcm16::<_, i16>(&mut wav, 22050, 1, &[]).unwrap();
+        assert_eq!(wav.len(), 44);
+        assert_eq!(u32::from_le_bytes(wav[40..44].try_into().unwrap()), 0);
+    }
+}
@@ -15,13 +15,16 @@
 //!
 //! * "Custom" Joystick is always interpreted as Sinclair Left Joystick, regardless of key bindings
 //!   that are being ignored at the moment.
-//! * Handling of MGT +D, DISCiPLE, or Multiface is currently not implemented.
+//! * Handling of MGT DISCiPLE or Multiface is currently not implemented. Only the paged in
+//!   state of the MGT +D ROM is being restored.
 //! * An `.xzx` extension to version 3 (additional OUT to port 0x1ffd) is being read-only if
 //!   a selected spectrum model would handle it properly.
 //!
 //! When writing to the **Z80** file:
 //!
 //! * ROMs are not being saved.
+//! * Only one of the Interface 1, SAM RAM or MGT interfaces can be represented by the hardware mode.
+//!   Other extensions are reported with [SnapshotResult::EXTENSTION_NSUP][crate::snapshot::SnapshotResult::EXTENSTION_NSUP].
 mod common;
 mod compress;
 mod decompress;
@@ -30,3 +33,171 @@ mod saver;
 
 pub use loader::*;
 pub use saver::*;
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Read};
+    use spectrusty_core::z80emu::{Cpu, Z80NMOS};
+    use spectrusty_core::clock::FTs;
+    use spectrusty_core::chip::{ReadEarMode, Ula128MemFlags, ScldCtrlFlags};
+    use spectrusty_core::video::BorderColor;
+    use spectrusty_core::memory::ZxMemoryError;
+    use spectrusty_peripherals::ay::AyRegister;
+    use crate::snapshot::*;
+    use super::*;
+
+    #[derive(Default)]
+    struct TestSnapshot {
+        model: Option<ComputerModel>,
+        extensions: Extensions,
+        cpu: Z80NMOS,
+        ram: Vec<u8>,
+        ay: Option<(Ay3_891xDevice, AyRegister, [u8;16])>,
+        plus_d_paged_in: bool,
+    }
+
+    impl TestSnapshot {
+        fn new(model: ComputerModel, extensions: Extensions) -> Self {
+            let ram_size = match model {
+                ComputerModel::Spectrum128 => 0x20000,
+                _ => 0xC000
+            };
+            let ram = (0..ram_size).map(|i| (i * 7 / 0x400) as u8).collect();
+            let mut cpu = Z80NMOS::default();
+            cpu.reset();
+            cpu.set_pc(0x8000);
+            TestSnapshot { model: Some(model), extensions, cpu, ram, ..Default::default() }
+        }
+    }
+
+    impl SnapshotCreator for TestSnapshot {
+        fn model(&self) -> ComputerModel { self.model.unwrap() }
+        fn extensions(&self) -> Extensions { self.extensions }
+        fn cpu(&self) -> CpuModel { CpuModel::NMOS(self.cpu.clone()) }
+        fn current_clock(&self) -> FTs { 1000 }
+        fn border_color(&self) -> BorderColor { BorderColor::BLUE }
+        fn issue(&self) -> ReadEarMode { ReadEarMode::Issue3 }
+        fn joystick(&self) -> Option<JoystickModel> { Some(JoystickModel::Kempston) }
+        fn memory_ref(&self, range: MemoryRange) -> Result<&[u8], ZxMemoryError> {
+            match range {
+                MemoryRange::Ram(range) => self.ram.get(range).ok_or(ZxMemoryError::UnsupportedAddressRange),
+                _ => Err(ZxMemoryError::UnsupportedAddressRange)
+            }
+        }
+        fn ay_state(&self, choice: Ay3_891xDevice) -> Option<(AyRegister, &[u8;16])> {
+            match &self.ay {
+                Some((device, reg, regs)) if *device == choice => Some((*reg, regs)),
+                _ => None
+            }
+        }
+        fn ula128_flags(&self) -> Ula128MemFlags { Ula128MemFlags::empty() }
+        fn timex_flags(&self) -> ScldCtrlFlags { ScldCtrlFlags::empty() }
+        fn timex_memory_banks(&self) -> u8 { 0 }
+        fn is_interface1_rom_paged_in(&self) -> bool { false }
+        fn is_plus_d_rom_paged_in(&self) -> bool { self.plus_d_paged_in }
+        fn is_disciple_rom_paged_in(&self) -> bool { false }
+        fn is_tr_dos_rom_paged_in(&self) -> bool { false }
+    }
+
+    impl SnapshotLoader for TestSnapshot {
+        type Error = &'static str;
+        fn select_model(
+                &mut self,
+                model: ComputerModel,
+                extensions: Extensions,
+                _border: BorderColor,
+                _issue: ReadEarMode
+            ) -> Result<(), Self::Error>
+        {
+            *self = TestSnapshot::new(model, extensions);
+            self.ram.iter_mut().for_each(|p| *p = 0);
+            Ok(())
+        }
+        fn read_into_memory<R: Read>(&mut self, range: MemoryRange, mut reader: R) -> Result<(), ZxMemoryError> {
+            match range {
+                MemoryRange::Ram(range) => {
+                    let mem = self.ram.get_mut(range).ok_or(ZxMemoryError::UnsupportedAddressRange)?;
+                    reader.read_exact(mem).map_err(ZxMemoryError::Io)
+                }
+                _ => Err(ZxMemoryError::UnsupportedAddressRange)
+            }
+        }
+        fn assign_cpu(&mut self, cpu: CpuModel) {
+            if let CpuModel::NMOS(cpu) = cpu {
+                self.cpu = cpu;
+            }
+        }
+        fn set_clock(&mut self, _tstates: FTs) {}
+        fn write_port(&mut self, _port: u16, _data: u8) {}
+        fn setup_ay(&mut self, choice: Ay3_891xDevice, reg_selected: AyRegister, reg_values: &[u8;16]) {
+            self.ay = Some((choice, reg_selected, *reg_values));
+        }
+        fn interface1_rom_paged_in(&mut self) {}
+        fn plus_d_rom_paged_in(&mut self) {
+            self.plus_d_paged_in = true;
+        }
+    }
+
+    fn round_trip_z80v3(snapshot: &TestSnapshot) -> (SnapshotResult, TestSnapshot) {
+        let mut buf = Vec::new();
+        let result = save_z80v3(snapshot, Cursor::new(&mut buf)).unwrap();
+        let mut loaded = TestSnapshot::default();
+        load_z80(Cursor::new(&buf), &mut loaded).unwrap();
+        (result, loaded)
+    }
+
+    #[test]
+    fn z80v3_round_trip_works() {
+        let regs: [u8;16] = [0x12, 0x03, 0x34, 0x05, 0x56, 0x07, 0x1f, 0x38,
+                             0x10, 0x0c, 0x0f, 0x00, 0x20, 0x0e, 0xff, 0x7f];
+        let mut snapshot = TestSnapshot::new(ComputerModel::Spectrum128, Extensions::NONE);
+        snapshot.ay = Some((Ay3_891xDevice::Ay128k, AyRegister::EnvShape, regs));
+        let (result, loaded) = round_trip_z80v3(&snapshot);
+        assert_eq!(result, SnapshotResult::OK);
+        assert_eq!(loaded.model, Some(ComputerModel::Spectrum128));
+        assert_eq!(loaded.extensions, Extensions::NONE);
+        assert_eq!(loaded.ay, snapshot.ay);
+        assert_eq!(loaded.cpu.get_pc(), 0x8000);
+        assert!(loaded.ram == snapshot.ram);
+
+        let mut snapshot = TestSnapshot::new(ComputerModel::Spectrum48, Extensions::IF1);
+        snapshot.ay = Some((Ay3_891xDevice::FullerBox, AyRegister::MixerControl, regs));
+        let (result, loaded) = round_trip_z80v3(&snapshot);
+        assert_eq!(result, SnapshotResult::OK);
+        assert_eq!(loaded.model, Some(ComputerModel::Spectrum48));
+        assert_eq!(loaded.extensions, Extensions::IF1);
+        assert_eq!(loaded.ay, snapshot.ay);
+        assert!(!loaded.plus_d_paged_in);
+        assert!(loaded.ram == snapshot.ram);
+
+        let mut snapshot = TestSnapshot::new(ComputerModel::Spectrum48, Extensions::PLUS_D);
+        snapshot.plus_d_paged_in = true;
+        let (result, loaded) = round_trip_z80v3(&snapshot);
+        assert_eq!(result, SnapshotResult::OK);
+        assert_eq!(loaded.extensions, Extensions::PLUS_D);
+        assert_eq!(loaded.ay, None);
+        assert!(loaded.plus_d_paged_in);
+
+        let snapshot = TestSnapshot::new(ComputerModel::Spectrum48, Extensions::IF1|Extensions::PLUS_D);
+        let (result, loaded) = round_trip_z80v3(&snapshot);
+        assert_eq!(result, SnapshotResult::EXTENSTION_NSUP);
+        assert_eq!(loaded.extensions, Extensions::IF1);
+
+        let snapshot = TestSnapshot::new(ComputerModel::Spectrum128, Extensions::ULA_PLUS);
+        let (result, loaded) = round_trip_z80v3(&snapshot);
+        assert_eq!(result, SnapshotResult::EXTENSTION_NSUP);
+        assert_eq!(loaded.extensions, Extensions::NONE);
+
+        let snapshot = TestSnapshot::new(ComputerModel::SpectrumPlus3, Extensions::IF1);
+        assert!(save_z80v3(&snapshot, Cursor::new(Vec::new())).is_err());
+
+        for (model, ext) in [(ComputerModel::TimexTC2048, Extensions::DISCIPLE),
+                             (ComputerModel::TimexTS2068, Extensions::IF1)].iter() {
+            let snapshot = TestSnapshot::new(*model, *ext);
+            let (result, loaded) = round_trip_z80v3(&snapshot);
+            assert_eq!(result, SnapshotResult::EXTENSTION_NSUP);
+            assert_eq!(loaded.model, Some(*model));
+            assert_eq!(loaded.extensions, Extensions::NONE);
+        }
+    }
+}
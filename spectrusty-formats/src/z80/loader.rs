@@ -271,6 +271,9 @@ pub fn load_z80<R: Read, S: SnapshotLoader>(
             }
             _ => {}
         }
+        if version == Z80Version::V3 && head_ex.mgt_rom == 0xff && extensions.intersects(Extensions::PLUS_D) {
+            loader.plus_d_rom_paged_in();
+        }
     }
     Ok(())
 }
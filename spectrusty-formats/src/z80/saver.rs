@@ -142,7 +142,19 @@ fn select_hw_model_v3<S: SnapshotCreator>(
         return Err(io::Error::new(io::ErrorKind::InvalidInput,
                 "Z80: can't create a version 3 snapshot with the external ROM paged in"))
     }
-    if ext.contains(Extensions::IF1|Extensions::SAM_RAM) {
+    // only one of the IF1, SAM RAM or a single MGT interface can be represented by the hardware mode
+    let mgt = Extensions::PLUS_D|Extensions::DISCIPLE;
+    if ext.contains(Extensions::IF1|Extensions::SAM_RAM)
+       || ext.intersects(Extensions::IF1|Extensions::SAM_RAM) && ext.intersects(mgt)
+       || ext.contains(mgt)
+       || (ext&!(Extensions::IF1|Extensions::SAM_RAM|mgt)) != Extensions::NONE
+    {
+        result.insert(SnapshotResult::EXTENSTION_NSUP);
+    }
+    // the +2A/+3 and Timex hardware modes can't represent any of the IF1, SAM RAM or MGT interfaces
+    if ext.intersects(Extensions::IF1|Extensions::SAM_RAM|mgt) && matches!(model,
+        SpectrumPlus2A|SpectrumPlus3|SpectrumPlus3e|TimexTC2048|TimexTC2068|TimexTS2068)
+    {
         result.insert(SnapshotResult::EXTENSTION_NSUP);
     }
     Ok(match model {
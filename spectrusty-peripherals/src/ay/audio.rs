@@ -156,6 +156,23 @@ pub struct Ay3_891xAudio {
     noise_control: NoiseControl,
     tone_control: [ToneControl; 3],
     mixer: Mixer,
+    #[cfg_attr(feature = "snapshot", serde(default))]
+    tick_step: TickStep,
+    #[cfg_attr(feature = "snapshot", serde(default))]
+    tick_frac: u16,
+    #[cfg_attr(feature = "snapshot", serde(skip))]
+    noise_muted: bool,
+}
+
+/// A type for the fixed-point (16.16) number of T-states between the sound generator ticks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+struct TickStep(u32);
+
+impl Default for TickStep {
+    fn default() -> Self {
+        TickStep((Ticker::CLOCK_INCREASE as u32) << 16)
+    }
 }
 
 /// A type for AY-3-891x amplitude level register values.
@@ -315,7 +332,7 @@ struct NoiseControl {
 
 impl Default for NoiseControl {
     fn default() -> Self {
-        NoiseControl { rng: 1, period: 0, tick: 0, low: false }
+        NoiseControl { rng: 1, period: 1, tick: 0, low: false }
     }
 }
 
@@ -352,7 +369,7 @@ const TONE_GEN_MIN_THRESHOLD: u16 = 5;
 const TONE_PERIOD_MASK: u16 = 0xFFF;
 
 /// A type implementing AY-3-891x tone progression.
-#[derive(Default, Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug)]
 #[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
 struct ToneControl {
     period: u16,
@@ -360,6 +377,11 @@ struct ToneControl {
     low: bool
 }
 
+impl Default for ToneControl {
+    fn default() -> Self {
+        ToneControl { period: 1, tick: 0, low: false }
+    }
+}
 
 impl ToneControl {
     #[inline]
@@ -402,13 +424,15 @@ impl ToneControl {
 #[derive(Clone, Copy, Debug)]
 struct Ticker {
     current: FTs,
+    frac: u16,
+    step: TickStep,
     end_ts: FTs
 }
 
 impl Ticker {
     const CLOCK_INCREASE: FTs = HOST_CLOCK_RATIO * INTERNAL_CLOCK_DIVISOR;
-    fn new(current: FTs, end_ts: FTs) -> Self {
-        Ticker { current, end_ts }
+    fn new(current: FTs, frac: u16, step: TickStep, end_ts: FTs) -> Self {
+        Ticker { current, frac, step, end_ts }
     }
 }
 
@@ -417,7 +441,9 @@ impl Iterator for Ticker {
     fn next(&mut self) -> Option<FTs> {
         let res = self.current;
         if res < self.end_ts {
-            self.current = res + Self::CLOCK_INCREASE;
+            let acc = u32::from(self.frac) + self.step.0;
+            self.current = res + (acc >> 16) as FTs;
+            self.frac = acc as u16;
             Some(res)
         }
         else {
@@ -429,8 +455,50 @@ impl Iterator for Ticker {
 /// Use the [Default] trait to create instances of this struct.
 impl Ay3_891xAudio {
     /// Resets the internal state to the one initialized with.
+    ///
+    /// The clock frequency set with [Ay3_891xAudio::set_ay_clock_hz] and the noise muting
+    /// are preserved.
     pub fn reset(&mut self) {
-        *self = Default::default()
+        let (tick_step, noise_muted) = (self.tick_step, self.noise_muted);
+        *self = Default::default();
+        self.tick_step = tick_step;
+        self.noise_muted = noise_muted;
+    }
+    /// Mutes or unmutes the noise generator in all the channels, regardless of the mixer register.
+    ///
+    /// The noise is mixed into each channel by the sound generator, so it can't be muted at
+    /// the audio mixing stage, like the channels with [BlepMixer][spectrusty_core::audio::BlepMixer].
+    pub fn set_noise_mute(&mut self, mute: bool) {
+        self.noise_muted = mute;
+    }
+    /// Returns `true` if the noise generator is muted.
+    pub fn is_noise_muted(&self) -> bool {
+        self.noise_muted
+    }
+    /// Sets the AY-3-891x clock frequency in relation to the CPU clock frequency, so the tone, noise
+    /// and envelope periods are scaled correctly for the emulated machine.
+    ///
+    /// `ay_clock_hz` is the AY-3-891x clock frequency in Hz and `cpu_hz` the CPU clock frequency in Hz
+    /// (the T-state rate).
+    ///
+    /// By default the sound generator is clocked at exactly half the CPU frequency, like in the ZX Spectrum 128k.
+    /// Some clones have the sound generator clocked at 1.75 MHz, while Atari ST has it at 2 MHz.
+    ///
+    /// The AY-3-891x internal clock can't run faster than the CPU clock, so the `ay_clock_hz` greater than
+    /// `cpu_hz` * [INTERNAL_CLOCK_DIVISOR] is limited to this value.
+    ///
+    /// # Panics
+    /// Panics if `ay_clock_hz` or `cpu_hz` is `0`.
+    pub fn set_ay_clock_hz(&mut self, ay_clock_hz: u32, cpu_hz: u32) {
+        assert_ne!(ay_clock_hz, 0, "Ay3_891xAudio::set_ay_clock_hz: the clock frequency must not be 0");
+        assert_ne!(cpu_hz, 0, "Ay3_891xAudio::set_ay_clock_hz: the CPU clock frequency must not be 0");
+        let step = (u64::from(cpu_hz) * INTERNAL_CLOCK_DIVISOR as u64) << 16;
+        let step = (step / u64::from(ay_clock_hz)).clamp(1 << 16, u64::from(u32::MAX));
+        self.tick_step = TickStep(step as u32);
+    }
+    /// Returns the AY-3-891x clock frequency in Hz, given the CPU clock frequency in Hz.
+    pub fn get_ay_clock_hz(&self, cpu_hz: u32) -> f64 {
+        f64::from(cpu_hz) * INTERNAL_CLOCK_DIVISOR as f64 * 65536.0 / f64::from(self.tick_step.0)
     }
     /// Converts a tone frequency given in Hz to a closest 16-bit tone period register value.
     ///
@@ -509,7 +577,7 @@ impl Ay3_891xAudio {
               A: Blep
     {
         let mut change_iter = changes.into_iter().peekable();
-        let mut ticker = Ticker::new(self.current_ts, end_ts);
+        let mut ticker = Ticker::new(self.current_ts, self.tick_frac, self.tick_step, end_ts);
         let mut tone_levels: [u8; 3] = self.last_levels;
         let mut vol_levels: [A::SampleDelta;3] = Default::default();
 
@@ -530,7 +598,7 @@ impl Ay3_891xAudio {
 
 
             let env_level = self.env_control.update_level();
-            let noise_low = self.noise_control.update_is_low();
+            let noise_low = self.noise_control.update_is_low() && !self.noise_muted;
             let mut mixer = self.mixer;
             for ((level, tone_control), tgt_lvl) in self.amp_levels.iter()
                                                     .zip(self.tone_control.iter_mut())
@@ -564,6 +632,7 @@ impl Ay3_891xAudio {
         }
 
         self.current_ts = ticker.current - frame_tstates;
+        self.tick_frac = ticker.frac;
         self.last_levels = tone_levels;
     }
     /// Updates the value of one of the sound generator registers for the indicated `reg` register,
@@ -622,13 +691,8 @@ impl Ay3_891xAudio {
     #[inline]
     pub fn get_amp_levels(&self) -> [u8;3] {
         let mut amps = [0;3];
-        for (level, tgt) in self.amp_levels.iter().zip(amps.iter_mut()) {
-            *tgt = if level.is_env_control() {
-                self.env_control.get_level()
-            }
-            else {
-                level.0
-            };
+        for (channel, tgt) in amps.iter_mut().enumerate() {
+            *tgt = self.current_volume(channel);
         }
         amps
     }
@@ -670,6 +734,54 @@ impl Ay3_891xAudio {
     pub fn get_envelope_period(&self) -> u16 {
         self.env_control.period
     }
+    /// Returns the current tone period of the indicated `channel`: `0` - A, `1` - B, `2` - C.
+    ///
+    /// The 12-bit period is assembled from the channel's fine and coarse tone registers.
+    ///
+    /// # Panics
+    /// Panics if `channel` is larger than `2`.
+    #[inline]
+    pub fn current_tone_period(&self, channel: usize) -> u16 {
+        self.tone_control[channel].period
+    }
+    /// Returns the current amplitude level of the indicated `channel`: `0` - A, `1` - B, `2` - C.
+    ///
+    /// If the channel volume register's envelope bit is set, it returns the current envelope level.
+    ///
+    /// The level is in the range: [0, 15].
+    ///
+    /// # Panics
+    /// Panics if `channel` is larger than `2`.
+    #[inline]
+    pub fn current_volume(&self, channel: usize) -> u8 {
+        let level = self.amp_levels[channel];
+        if level.is_env_control() {
+            self.env_control.get_level()
+        }
+        else {
+            level.0
+        }
+    }
+    /// Returns a snapshot of the sound generator registers, reflecting the state of the audio being rendered.
+    ///
+    /// The values are reconstructed from the sound generator state, so the period registers written
+    /// with `0` read as `1`, just like the chip interprets them, which is also the case for the periods
+    /// of the initial or reset state. The I/O port registers are not part of the sound generator
+    /// and are always `0`.
+    pub fn ay_registers(&self) -> [u8;16] {
+        let mut regs = [0u8;16];
+        for (tone, tgt) in self.tone_control.iter().zip(regs.chunks_exact_mut(2)) {
+            tgt.copy_from_slice(&tone.period.to_le_bytes());
+        }
+        regs[usize::from(AyRegister::NoisePeriod)] = self.noise_control.period;
+        regs[usize::from(AyRegister::MixerControl)] = self.mixer.0;
+        for (level, tgt) in self.amp_levels.iter().zip(regs[8..11].iter_mut()) {
+            *tgt = level.0;
+        }
+        regs[11..13].copy_from_slice(&self.env_control.period.to_le_bytes());
+        regs[usize::from(AyRegister::EnvShape)] = self.env_control.get_shape();
+        regs
+    }
 }
 
 #[cfg(test)]
@@ -697,6 +809,172 @@ mod tests {
                    31,   30,   28,   26,   25,   24,   22,   21,   20,   19,   18,   17], notes);
     }
 
+    #[derive(Default)]
+    struct TestBlep(Vec<(usize, FTs, i32)>);
+
+    impl Blep for TestBlep {
+        type SampleDelta = i32;
+        fn ensure_frame_time(&mut self, _sample_rate: u32, _ts_rate: f64, _frame_ts: FTs, _margin_ts: FTs) {}
+        fn end_frame(&mut self, _timestamp: FTs) -> usize { 0 }
+        fn add_step(&mut self, channel: usize, timestamp: FTs, delta: i32) {
+            self.0.push((channel, timestamp, delta));
+        }
+    }
+
+    struct TestAmps;
+
+    impl AmpLevels<i32> for TestAmps {
+        fn amp_level(level: u32) -> i32 {
+            level as i32 * 100
+        }
+    }
+
+    #[test]
+    fn ay_3_889x_mixer_mute_works() {
+        use AyRegister::*;
+        use spectrusty_core::audio::{BlepMixer, MixerSource};
+        const FRAME_TS: FTs = 70908;
+        type Steps = Vec<(usize, FTs, i32)>;
+        fn render_frame(ay: &mut Ay3_891xAudio, mixer: &mut BlepMixer<TestBlep>,
+                        changes: &[AyRegChange], history: &mut Steps) -> Steps {
+            ay.render_audio::<TestAmps,_,_>(changes.iter().copied(), mixer, FRAME_TS, FRAME_TS,
+                                            BlepMixer::<TestBlep>::AY_CHANNELS);
+            let steps = core::mem::take(&mut mixer.blep.0);
+            history.extend_from_slice(&steps);
+            steps
+        }
+        fn count_steps(steps: &Steps, chan: usize) -> usize {
+            steps.iter().filter(|(ch, ..)| *ch == chan).count()
+        }
+        fn output_level(steps: &Steps, chan: usize) -> i32 {
+            steps.iter().filter(|(ch, ..)| *ch == chan).map(|&(.., delta)| delta).sum()
+        }
+        let mut ay = Ay3_891xAudio::default();
+        let mut mixer = BlepMixer::new([0, 1, 2, 3], TestBlep::default());
+        let mut history = Steps::new();
+        mixer.set_channel_mute(MixerSource::AyChannelB, true);
+        assert!(mixer.is_channel_muted(MixerSource::AyChannelB));
+        assert!(!mixer.is_channel_audible(MixerSource::AyChannelB));
+        let changes = [AyRegChange::new_from_ts(0, ToneFineA, 50),
+                       AyRegChange::new_from_ts(0, ToneFineB, 60),
+                       AyRegChange::new_from_ts(0, ToneFineC, 70),
+                       AyRegChange::new_from_ts(0, MixerControl, 0b0011_1000),
+                       AyRegChange::new_from_ts(0, AmpLevelA, 15),
+                       AyRegChange::new_from_ts(0, AmpLevelB, 15),
+                       AyRegChange::new_from_ts(0, AmpLevelC, 15)];
+        let steps = render_frame(&mut ay, &mut mixer, &changes, &mut history);
+        assert!(count_steps(&steps, 0) > 40);
+        assert_eq!(count_steps(&steps, 1), 0);
+        assert!(count_steps(&steps, 2) > 40);
+        // unmuted
+        mixer.set_channel_mute(MixerSource::AyChannelB, false);
+        let steps = render_frame(&mut ay, &mut mixer, &[], &mut history);
+        assert!(count_steps(&steps, 1) > 40);
+        // only the soloed channel is audible, the other channels step down to silence
+        mixer.set_channel_solo(MixerSource::AyChannelC, true);
+        assert!(mixer.is_channel_solo(MixerSource::AyChannelC));
+        assert!(!mixer.is_channel_audible(MixerSource::AyChannelA));
+        let steps = render_frame(&mut ay, &mut mixer, &[], &mut history);
+        assert!(count_steps(&steps, 0) <= 1);
+        assert!(count_steps(&steps, 1) <= 1);
+        assert!(count_steps(&steps, 2) > 40);
+        assert_eq!(output_level(&history, 0), 0);
+        assert_eq!(output_level(&history, 1), 0);
+        mixer.set_channel_solo(MixerSource::AyChannelC, false);
+        // the noise only in the channel A
+        let changes = [AyRegChange::new_from_ts(0, MixerControl, 0b0011_0111),
+                       AyRegChange::new_from_ts(0, NoisePeriod, 1),
+                       AyRegChange::new_from_ts(0, AmpLevelB, 0),
+                       AyRegChange::new_from_ts(0, AmpLevelC, 0)];
+        let steps = render_frame(&mut ay, &mut mixer, &changes, &mut history);
+        assert!(count_steps(&steps, 0) > 40);
+        ay.set_noise_mute(true);
+        assert!(ay.is_noise_muted());
+        let steps = render_frame(&mut ay, &mut mixer, &[], &mut history);
+        assert!(count_steps(&steps, 0) <= 1);
+        assert_eq!(output_level(&history, 0), 1500);
+        ay.reset();
+        assert!(ay.is_noise_muted());
+    }
+
+    #[test]
+    fn ay_3_889x_clock_works() {
+        use AyRegister::*;
+        const CPU_HZ: u32 = 3_546_900;
+        const FRAME_TS: FTs = 70908;
+        const FRAMES: u32 = 50;
+        // counts the tone half periods rendered in FRAMES frames
+        fn render_tone(ay: &mut Ay3_891xAudio) -> usize {
+            let changes = [AyRegChange::new_from_ts(0, ToneFineA, 100),
+                           AyRegChange::new_from_ts(0, MixerControl, 0b0011_1110),
+                           AyRegChange::new_from_ts(0, AmpLevelA, 15)];
+            let mut blep = TestBlep::default();
+            ay.render_audio::<TestAmps,_,_>(changes, &mut blep, FRAME_TS, FRAME_TS, [0, 1, 2]);
+            for _ in 1..FRAMES {
+                ay.render_audio::<TestAmps,_,_>([], &mut blep, FRAME_TS, FRAME_TS, [0, 1, 2]);
+            }
+            blep.0.iter().filter(|(chan, ..)| *chan == 0).count()
+        }
+        let mut ay = Ay3_891xAudio::default();
+        assert_eq!(ay.get_ay_clock_hz(CPU_HZ), 1_773_450.0);
+        // a tone with the period of 100 at 1.77345 MHz has the frequency of 1108.4 Hz
+        let default_count = render_tone(&mut ay);
+        assert_eq!(default_count, 2 * 1108);
+        ay.set_ay_clock_hz(1_773_450, CPU_HZ);
+        assert_eq!(ay.tick_step, TickStep::default());
+        ay.set_ay_clock_hz(2_000_000, CPU_HZ);
+        ay.reset();
+        assert!((ay.get_ay_clock_hz(CPU_HZ) - 2_000_000.0).abs() < 1.0);
+        // the tone frequency scales proportionally to the clock: 1250 Hz
+        let count = render_tone(&mut ay);
+        assert!((count as f64 - default_count as f64 * 2_000_000.0 / 1_773_450.0).abs() <= 1.0);
+        assert!((count as i32 - 2 * 1250).abs() <= 1);
+        ay.set_ay_clock_hz(1_750_000, CPU_HZ);
+        ay.reset();
+        // 1093.75 Hz
+        let count = render_tone(&mut ay);
+        assert!((count as f64 - default_count as f64 * 1_750_000.0 / 1_773_450.0).abs() <= 1.0);
+        assert!((count as i32 - 2 * 1094).abs() <= 1);
+        // the internal clock is limited to the CPU clock
+        ay.set_ay_clock_hz(u32::MAX, CPU_HZ);
+        assert_eq!(ay.tick_step, TickStep(1 << 16));
+        assert_eq!(ay.get_ay_clock_hz(CPU_HZ), f64::from(CPU_HZ) * INTERNAL_CLOCK_DIVISOR as f64);
+    }
+
+    #[test]
+    fn ay_3_889x_registers_work() {
+        use AyRegister::*;
+        let mut ay = Ay3_891xAudio::default();
+        assert_eq!(ay.ay_registers(), [1, 0, 1, 0, 1, 0, 1, 0, 0, 0, 0, 1, 0, 0, 0, 0]);
+        assert_eq!(ay.get_tone_periods(), [1, 1, 1]);
+        for (reg, val) in [(ToneFineA, 0xFC), (ToneCoarseA, 0x00),
+                           (ToneFineB, 0x34), (ToneCoarseB, 0xF2),
+                           (ToneCoarseC, 0x0A), (ToneFineC, 0x00),
+                           (NoisePeriod, 0x3F), (MixerControl, 0b0011_1010),
+                           (AmpLevelA, 0x0F), (AmpLevelB, 0x07), (AmpLevelC, 0x10),
+                           (EnvPerFine, 0x20), (EnvPerCoarse, 0x03), (EnvShape, 0x0E)] {
+            ay.update_register(reg, val);
+        }
+        assert_eq!(ay.ay_registers(), [0xFC, 0x00, 0x34, 0x02, 0x00, 0x0A, 0x1F, 0b0011_1010,
+                                       0x0F, 0x07, 0x10, 0x20, 0x03, 0x0E, 0, 0]);
+        assert_eq!(ay.current_tone_period(0), 252);
+        assert_eq!(ay.current_tone_period(1), 0x234);
+        assert_eq!(ay.current_tone_period(2), 0xA00);
+        assert_eq!(ay.get_tone_periods(), [252, 0x234, 0xA00]);
+        assert_eq!(ay.current_volume(0), 15);
+        assert_eq!(ay.current_volume(1), 7);
+        // the envelope shape with attack starts from level 0
+        assert_eq!(ay.current_volume(2), 0);
+        assert_eq!(ay.get_amp_levels(), [15, 7, 0]);
+        ay.update_register(EnvShape, 0x0A);
+        assert_eq!(ay.current_volume(2), 15);
+        assert_eq!(ay.ay_registers()[13], 0x0A);
+        ay.update_register(ToneCoarseA, 0);
+        ay.update_register(ToneFineA, 0);
+        assert_eq!(ay.current_tone_period(0), 1);
+        assert_eq!(&ay.ay_registers()[0..2], &[1, 0]);
+    }
+
     #[test]
     fn ay_3_889x_env_works() {
         // println!("Ay3_891xAudio {:?}", core::mem::size_of::<Ay3_891xAudio>());
@@ -6,10 +6,122 @@
     For the full copyright notice, see the lib.rs file.
 */
 //! System bus device emulators to be used with [ControlUnit][spectrusty_core::chip::ControlUnit]s.
+pub mod audio;
 pub mod ay;
 pub mod debug;
+pub mod dma;
+pub mod fuller;
+pub mod ide;
 pub mod joystick;
+pub mod lightgun;
 pub mod mouse;
 pub mod parallel;
 pub mod zxinterface1;
 pub mod zxprinter;
+
+pub use fuller::FullerBox;
+
+#[cfg(all(test, feature = "snapshot"))]
+mod tests {
+    use std::collections::VecDeque;
+    use serde::{Serialize, Serializer, Deserializer, de::{self, Visitor, SeqAccess}, ser};
+    use spectrusty_core::bus::{
+        NamedBusDevice, NullDevice,
+        DynamicSerdeBus, SerializeDynDevice, DeserializeDynDevice
+    };
+    use spectrusty_core::clock::{FTs, TimestampOps};
+    use crate::ay::AyRegister;
+    use crate::network::zxnet::{NetworkProfile, ZxNetUdpSyncSocket};
+    use crate::storage::microdrives::MicroCartridge;
+    use super::ay::Ay3_891xMelodik;
+    use super::zxinterface1::ZxInterface1BusDevice;
+
+    type TestInterface1<T> = ZxInterface1BusDevice<VecDeque<u8>, Vec<u8>, ZxNetUdpSyncSocket, NullDevice<T>>;
+
+    struct TestSerdeDynDevice;
+
+    impl SerializeDynDevice for TestSerdeDynDevice {
+        fn serialize_dyn_device<T: TimestampOps + Serialize + 'static, S: Serializer>(
+                device: &Box<dyn NamedBusDevice<T>>,
+                serializer: S
+            ) -> Result<S::Ok, S::Error>
+        {
+            if let Some(device) = device.downcast_ref::<Ay3_891xMelodik<NullDevice<T>>>() {
+                (0u8, device).serialize(serializer)
+            }
+            else if let Some(device) = device.downcast_ref::<TestInterface1<T>>() {
+                (1u8, device).serialize(serializer)
+            }
+            else {
+                Err(ser::Error::custom("unknown device"))
+            }
+        }
+    }
+
+    impl<'de> DeserializeDynDevice<'de> for TestSerdeDynDevice {
+        fn deserialize_dyn_device<T: Default + TimestampOps + serde::Deserialize<'de> + 'static, D: Deserializer<'de>>(
+                deserializer: D
+            ) -> Result<Box<dyn NamedBusDevice<T>>, D::Error>
+        {
+            struct DeviceVisitor<T>(core::marker::PhantomData<T>);
+
+            impl<'de, T: Default + TimestampOps + serde::Deserialize<'de> + 'static> Visitor<'de> for DeviceVisitor<T> {
+                type Value = Box<dyn NamedBusDevice<T>>;
+
+                fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+                    formatter.write_str("a device tuple descriptor")
+                }
+
+                fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                    let device: Option<Box<dyn NamedBusDevice<T>>> = match seq.next_element::<u8>()? {
+                        Some(0) => seq.next_element::<Ay3_891xMelodik<NullDevice<T>>>()?.map(Into::into),
+                        Some(1) => seq.next_element::<TestInterface1<T>>()?.map(Into::into),
+                        _ => return Err(de::Error::custom("unknown device"))
+                    };
+                    device.ok_or_else(|| de::Error::invalid_length(1, &self))
+                }
+            }
+
+            deserializer.deserialize_tuple(2, DeviceVisitor::<T>(core::marker::PhantomData))
+        }
+    }
+
+    type TestDynBus = DynamicSerdeBus<TestSerdeDynDevice, NullDevice<FTs>>;
+
+    #[test]
+    fn dynamic_devices_snapshot_works() {
+        let mut bus = TestDynBus::default();
+        let mut ay = Ay3_891xMelodik::<NullDevice<FTs>>::default();
+        ay.ay_io.set(AyRegister::ToneFineA, 0x5A);
+        ay.ay_io.set(AyRegister::MixerControl, 0x38);
+        ay.ay_io.set(AyRegister::EnvShape, 0x0E);
+        ay.ay_io.select_port_write(AyRegister::AmpLevelB as u8);
+        bus.append_device(ay);
+        let mut if1 = TestInterface1::<FTs>::default();
+        if1.microdrives.replace_cartridge(0, MicroCartridge::new(10));
+        // spin the tape of the 1st drive for a while
+        if1.microdrives.write_control(0, false, false, true, true);
+        if1.microdrives.read_state(100_000);
+        assert!(if1.network.set_network_profile(NetworkProfile {
+            station: Some(7), inter_packet_gap: 1000, collision_probability: 0.5, collision_seed: 42
+        }));
+        bus.append_device(if1);
+        let head_at = bus.as_device_ref::<TestInterface1<FTs>>(1).microdrives.cartridge_at(0).unwrap().head_at();
+        assert!(head_at > 0.0);
+
+        let json = serde_json::to_string(&bus).unwrap();
+        let bus1: TestDynBus = serde_json::from_str(&json).unwrap();
+        assert_eq!(bus1.len(), 2);
+        let ay = bus.as_device_ref::<Ay3_891xMelodik<NullDevice<FTs>>>(0);
+        let ay1 = bus1.as_device_ref::<Ay3_891xMelodik<NullDevice<FTs>>>(0);
+        assert_eq!(ay1.ay_io.registers(), ay.ay_io.registers());
+        assert_eq!(ay1.ay_io.get(AyRegister::ToneFineA), 0x5A);
+        assert_eq!(ay1.ay_io.selected_register(), AyRegister::AmpLevelB);
+        let if1 = bus1.as_device_ref::<TestInterface1<FTs>>(1);
+        assert_eq!(if1.microdrives.cartridge_at(0).unwrap().head_at(), head_at);
+        assert!(if1.microdrives.cartridge_at(1).is_none());
+        assert_eq!(if1.network.network_profile().station, Some(7));
+        assert_eq!(if1.network.network_profile().collision_seed, 42);
+        assert_eq!(serde_json::to_string(&bus1).unwrap(), json);
+    }
+}
@@ -0,0 +1,203 @@
+/*
+    Copyright (C) 2020-2023  Rafal Michalski
+
+    This file is part of SPECTRUSTY, a Rust library for building emulators.
+
+    For the full copyright notice, see the lib.rs file.
+*/
+//! Bus devices producing audio.
+use core::fmt;
+use core::num::NonZeroU16;
+
+#[cfg(feature = "snapshot")]
+use serde::{Serialize, Deserialize};
+
+use spectrusty_core::{
+    audio::{Blep, AmpLevels},
+    bus::BusDevice,
+    clock::FTs
+};
+
+use super::ay::PassByAyAudioBusDevice;
+
+pub use crate::memory::{CurrahMicroSpeechControl, CurrahMicroSpeechMemExt};
+pub use crate::speech::Sp0256;
+
+/// The Currah µSpeech speech synthesizer [BusDevice] with the [Sp0256] speech processor.
+///
+/// The µSpeech doesn't respond to any I/O port. Its ROM and the speech processor are accessed via memory
+/// addresses by [CurrahMicroSpeechMemExt], which should be installed as the memory extension of the chipset.
+/// Both components must share the same speech processor:
+///
+/// ```text
+/// uspeech.set_control(memext.control().clone());
+/// ```
+///
+/// The device takes care of the speech processor's frame and reset handling. The speech audio should be
+/// rendered with [CurrahMicroSpeech::render_audio] at the end of each frame.
+///
+/// The speech processor is serialized with [CurrahMicroSpeechMemExt], so the link must be established
+/// again after both components are deserialized.
+#[derive(Clone, Default, Debug)]
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+pub struct CurrahMicroSpeech<D> {
+    #[cfg_attr(feature = "snapshot", serde(skip))]
+    control: CurrahMicroSpeechControl,
+    #[cfg_attr(feature = "snapshot", serde(default))]
+    bus: D
+}
+
+impl<D> fmt::Display for CurrahMicroSpeech<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Currah µSpeech")
+    }
+}
+
+impl<D> CurrahMicroSpeech<D> {
+    /// Attaches the speech processor handle shared with [CurrahMicroSpeechMemExt].
+    pub fn set_control(&mut self, control: CurrahMicroSpeechControl) {
+        self.control = control;
+    }
+    /// Returns a reference to the speech processor handle.
+    pub fn control(&self) -> &CurrahMicroSpeechControl {
+        &self.control
+    }
+}
+
+impl<D: BusDevice> CurrahMicroSpeech<D>
+    where D::Timestamp: Into<FTs>
+{
+    /// Renders the speech up to `end_ts` as square-wave pulses via [Blep] interface.
+    ///
+    /// Provide [AmpLevels] that can handle `level` values from 0 to 15 (4-bits).
+    /// `channel` - target [Blep] audio channel.
+    pub fn render_audio<L: AmpLevels<B::SampleDelta>, B: Blep>(
+            &mut self,
+            blep: &mut B,
+            end_ts: D::Timestamp,
+            channel: usize
+        )
+    {
+        self.control.speech_mut().render_audio::<L, B>(blep, end_ts.into(), channel)
+    }
+}
+
+impl<D> PassByAyAudioBusDevice for CurrahMicroSpeech<D> {}
+
+impl<D> BusDevice for CurrahMicroSpeech<D>
+    where D: BusDevice,
+          D::Timestamp: Into<FTs> + Copy
+{
+    type Timestamp = D::Timestamp;
+    type NextDevice = D;
+
+    #[inline]
+    fn next_device_mut(&mut self) -> &mut Self::NextDevice {
+        &mut self.bus
+    }
+
+    #[inline]
+    fn next_device_ref(&self) -> &Self::NextDevice {
+        &self.bus
+    }
+
+    #[inline]
+    fn into_next_device(self) -> Self::NextDevice {
+        self.bus
+    }
+
+    fn reset(&mut self, timestamp: Self::Timestamp) {
+        self.control.speech_mut().reset();
+        self.bus.reset(timestamp);
+    }
+
+    fn next_frame(&mut self, eof_timestamp: Self::Timestamp) {
+        self.control.speech_mut().next_frame(eof_timestamp.into());
+        self.bus.next_frame(eof_timestamp)
+    }
+
+    #[inline]
+    fn read_io(&mut self, port: u16, timestamp: Self::Timestamp) -> Option<(u8, Option<NonZeroU16>)> {
+        self.bus.read_io(port, timestamp)
+    }
+
+    #[inline]
+    fn write_io(&mut self, port: u16, data: u8, timestamp: Self::Timestamp) -> Option<u16> {
+        self.bus.write_io(port, data, timestamp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use spectrusty_core::bus::NullDevice;
+    use super::*;
+
+    const FRAME_TS: FTs = 69888;
+
+    #[derive(Default)]
+    struct TestBlep(Vec<(usize, FTs, i32)>);
+
+    impl Blep for TestBlep {
+        type SampleDelta = i32;
+        fn ensure_frame_time(&mut self, _sample_rate: u32, _ts_rate: f64, _frame_ts: FTs, _margin_ts: FTs) {}
+        fn end_frame(&mut self, _timestamp: FTs) -> usize { 0 }
+        fn add_step(&mut self, channel: usize, timestamp: FTs, delta: i32) {
+            self.0.push((channel, timestamp, delta));
+        }
+    }
+
+    struct TestAmps;
+
+    impl AmpLevels<i32> for TestAmps {
+        fn amp_level(level: u32) -> i32 {
+            level as i32 * 100
+        }
+    }
+
+    #[test]
+    fn uspeech_bus_device_works() {
+        let mut uspeech = CurrahMicroSpeech::<NullDevice<FTs>>::default();
+        assert_eq!(uspeech.to_string(), "Currah µSpeech");
+        let mut memext = CurrahMicroSpeechMemExt::default();
+        assert!(!uspeech.control().is_shared_with(memext.control()));
+        uspeech.set_control(memext.control().clone());
+        assert!(uspeech.control().is_shared_with(memext.control()));
+        assert_eq!(uspeech.read_io(0x1000, 0), None);
+        assert_eq!(uspeech.write_io(0x1000, 24, 0), None);
+        // AA
+        assert!(memext.speech_mut().write_allophone(24, 1000));
+        let mut blep = TestBlep::default();
+        uspeech.render_audio::<TestAmps, _>(&mut blep, FRAME_TS, 1);
+        assert!(blep.0.len() > 10);
+        assert!(blep.0.iter().all(|&(ch, ts, delta)| ch == 1 && (1000..FRAME_TS).contains(&ts) && delta != 0));
+        // the speech continues in the next frame
+        uspeech.next_frame(FRAME_TS);
+        assert!(memext.speech_ref().is_speaking(0));
+        blep.0.clear();
+        uspeech.render_audio::<TestAmps, _>(&mut blep, FRAME_TS, 1);
+        assert!(!blep.0.is_empty());
+        uspeech.next_frame(FRAME_TS);
+        // reset silences the speech processor
+        assert!(memext.speech_mut().write_allophone(24, 0));
+        uspeech.reset(0);
+        assert!(!memext.speech_ref().is_speaking(0));
+    }
+
+    #[cfg(feature = "snapshot")]
+    #[test]
+    fn uspeech_serde_works() {
+        let mut uspeech = CurrahMicroSpeech::<NullDevice<FTs>>::default();
+        let memext = CurrahMicroSpeechMemExt::default();
+        uspeech.set_control(memext.control().clone());
+        assert!(memext.control().speech_mut().write_allophone(24, 1000));
+        let memext_json = serde_json::to_string(&memext).unwrap();
+        let uspeech_json = serde_json::to_string(&uspeech).unwrap();
+        let memext: CurrahMicroSpeechMemExt = serde_json::from_str(&memext_json).unwrap();
+        let mut uspeech: CurrahMicroSpeech<NullDevice<FTs>> = serde_json::from_str(&uspeech_json).unwrap();
+        assert!(memext.speech_ref().is_speaking(1000));
+        assert!(!uspeech.control().speech_ref().is_speaking(1000));
+        // re-link
+        uspeech.set_control(memext.control().clone());
+        assert!(uspeech.control().speech_ref().is_speaking(1000));
+    }
+}
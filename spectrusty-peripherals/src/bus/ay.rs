@@ -23,6 +23,8 @@ use spectrusty_core::{
     clock::FTs
 };
 
+use super::fuller::FullerBox;
+
 pub use crate::ay::{
     audio::Ay3_891xAudio,
     Ay3_8910Io, Ay3_8912Io, Ay3_8913Io, AyIoPort, AyIoNullPort, AyRegister,
@@ -156,7 +158,7 @@ impl<T> AyAudioBusDevice for dyn NamedBusDevice<T>
 {
     /// # Note
     /// Because we need to guess the concrete type of the dynamic `BusDevice` we can currently handle
-    /// only the most common cases: [Ay3_891xMelodik] and [Ay3_891xFullerBox]. If you use a customized
+    /// only the most common cases: [Ay3_891xMelodik], [Ay3_891xFullerBox] and [FullerBox]. If you use a customized
     /// [Ay3_891xBusDevice] for a dynamic `BusDevice` you need to render audio directly on the device
     /// downcasted to your custom type.
     #[inline]
@@ -170,6 +172,9 @@ impl<T> AyAudioBusDevice for dyn NamedBusDevice<T>
         else if let Some(ay_dev) = self.downcast_mut::<Ay3_891xFullerBox<NullDevice<T>>>() {
             ay_dev.render_ay_audio::<L, B>(blep, end_ts, frame_tstates, chans)
         }
+        else if let Some(fuller_box) = self.downcast_mut::<FullerBox<NullDevice<T>>>() {
+            fuller_box.render_ay_audio::<L, B>(blep, end_ts, frame_tstates, chans)
+        }
     }
 }
 
@@ -236,6 +241,15 @@ impl<T: Into<FTs> + fmt::Debug> AyAudioBusDevice for NullDevice<T> {
     {}
 }
 
+impl<P, A, B, D: BusDevice> Ay3_891xBusDevice<P, A, B, D> {
+    /// Sets the clock frequency of the sound generator in relation to the CPU clock frequency.
+    ///
+    /// See [Ay3_891xAudio::set_ay_clock_hz].
+    pub fn set_ay_clock_hz(&mut self, ay_clock_hz: u32, cpu_hz: u32) {
+        self.ay_sound.set_ay_clock_hz(ay_clock_hz, cpu_hz)
+    }
+}
+
 impl<P, A, B, D> BusDevice for Ay3_891xBusDevice<P, A, B, D>
     where P: AyPortDecode,
           A: AyIoPort<Timestamp=D::Timestamp> + Debug,
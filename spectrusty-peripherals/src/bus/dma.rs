@@ -0,0 +1,758 @@
+/*
+    Copyright (C) 2020-2023  Rafal Michalski
+
+    This file is part of SPECTRUSTY, a Rust library for building emulators.
+
+    For the full copyright notice, see the lib.rs file.
+*/
+//! The Zilog Z80 DMA controller bus device, used by the DATAGEAR and MB-02 interfaces and some clones.
+//!
+//! The DMA is programmed by the CPU writing to its control port. When the transfer is enabled, the DMA
+//! requests the bus with [BusDevice::is_bus_requested] and the control unit performs the memory and I/O
+//! cycles returned by [BusDevice::bus_request] on its behalf, advancing its clock by the cycle lengths,
+//! before executing the next instruction.
+//!
+//! The limits of the emulation:
+//!
+//! * The ports are always ready, so the transfer in both the continuous and burst modes proceeds without
+//!   releasing the bus until the end of the block. In the byte mode the bus is being released after each
+//!   transferred byte for the duration of a single instruction.
+//! * Only the cycle length of the port timing bytes is being emulated. The control signals' early ending
+//!   bits and the prescaler byte following the port B timing byte are being accepted and ignored,
+//!   so the transfers always run at the full speed.
+use core::fmt::{self, Debug};
+use core::marker::PhantomData;
+use core::num::NonZeroU16;
+
+#[cfg(feature = "snapshot")]
+use serde::{Serialize, Deserialize};
+
+use spectrusty_core::bus::{BusDevice, BusCycle, PortAddress};
+use super::ay::PassByAyAudioBusDevice;
+
+/// The [Z80Dma] bus device connected via the DATAGEAR port `0x6B`.
+pub type Z80DmaDatagear<D> = Z80Dma<DatagearPortAddress, D>;
+/// The [Z80Dma] bus device connected via the MB-02 port `0x0B`.
+pub type Z80DmaMb02<D> = Z80Dma<Mb02DmaPortAddress, D>;
+
+/// The DATAGEAR DMA port address.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct DatagearPortAddress;
+impl PortAddress for DatagearPortAddress {
+    const ADDRESS_MASK: u16 = 0x00ff;
+    const ADDRESS_BITS: u16 = 0x006b;
+}
+
+/// The MB-02 DMA port address.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Mb02DmaPortAddress;
+impl PortAddress for Mb02DmaPortAddress {
+    const ADDRESS_MASK: u16 = 0x00ff;
+    const ADDRESS_BITS: u16 = 0x000b;
+}
+
+/// The address counter mode of the DMA port.
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum DmaAddressMode {
+    /// The address is being decremented after each transferred byte.
+    Decrement,
+    /// The address is being incremented after each transferred byte.
+    #[default]
+    Increment,
+    /// The address is not being changed.
+    Fixed
+}
+
+/// The transfer mode of the DMA.
+///
+/// The ports of the emulated DMA are always ready, so the transfer in both [DmaTransferMode::Continuous]
+/// and [DmaTransferMode::Burst] modes proceeds without releasing the bus until the end of the block.
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum DmaTransferMode {
+    /// The bus is being released after each transferred byte.
+    Byte,
+    /// The bus is being held until the end of the block.
+    Continuous,
+    /// The bus is being held until the port is not ready or the end of the block.
+    #[default]
+    Burst
+}
+
+/// One side of the DMA transfer.
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct DmaEndpoint {
+    /// The starting memory address or I/O port.
+    pub address: u16,
+    /// `true` if the `address` is an I/O port, otherwise it is a memory address.
+    pub is_io: bool,
+    /// The address counter mode.
+    pub mode: DmaAddressMode
+}
+
+/// A data transfer being performed by the [Z80Dma].
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct DmaTransfer {
+    /// The source of the data.
+    pub source: DmaEndpoint,
+    /// The destination of the data.
+    pub destination: DmaEndpoint,
+    /// The number of bytes to transfer.
+    pub length: u32
+}
+
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum DmaCycle {
+    Read,
+    Write(u8),
+    Release
+}
+
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+struct DmaPort {
+    address: u16,
+    is_io: bool,
+    mode: DmaAddressMode,
+    timing: Option<u8>
+}
+
+bitflags! {
+    /// The parameter bytes expected to be written to the DMA port, in the order of writing.
+    #[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+    #[cfg_attr(feature = "snapshot", serde(from = "u16", into = "u16"))]
+    #[derive(Default, Debug, PartialEq, Eq, Hash, Clone, Copy)]
+    struct DmaParams: u16 {
+        const PORT_A_LO   = 0x0001;
+        const PORT_A_HI   = 0x0002;
+        const LENGTH_LO   = 0x0004;
+        const LENGTH_HI   = 0x0008;
+        const TIMING_A    = 0x0010;
+        const TIMING_B    = 0x0020;
+        const MASK        = 0x0040;
+        const MATCH       = 0x0080;
+        const PORT_B_LO   = 0x0100;
+        const PORT_B_HI   = 0x0200;
+        const INT_CONTROL = 0x0400;
+        const PULSE       = 0x0800;
+        const VECTOR      = 0x1000;
+        const READ_MASK   = 0x2000;
+        const PRESCALER   = 0x4000;
+    }
+}
+
+const DEFAULT_READ_MASK: u8 = 0x7f;
+const STATUS_BASE: u8 = 0b0001_1010;
+const STATUS_TRANSFERRED: u8 = 0b0000_0001;
+const STATUS_NOT_END_OF_BLOCK: u8 = 0b0010_0000;
+
+/// The Zilog Z80 DMA controller as a [BusDevice].
+///
+/// Supports memory to memory, memory to I/O and I/O to memory transfers in the byte, continuous and burst modes.
+/// Searching, interrupts and the auto restart mode are not being emulated.
+///
+/// Each transferred byte costs the sum of the read and write cycle lengths of the source and the destination
+/// ports. The cycle lengths are 3 T-states for memory and 4 T-states for I/O ports, unless programmed
+/// differently with the timing parameters. See the [module][self] documentation for the limits of the emulation.
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "snapshot", serde(rename_all = "camelCase"))]
+#[derive(Clone, Debug)]
+pub struct Z80Dma<P, D> {
+    port_a: DmaPort,
+    port_b: DmaPort,
+    block_length: u16,
+    a_to_b: bool,
+    mode: DmaTransferMode,
+    counter_a: u16,
+    counter_b: u16,
+    transferred: u32,
+    status: u8,
+    read_mask: u8,
+    read_index: u8,
+    params: DmaParams,
+    interrupt_control: u8,
+    #[cfg_attr(feature = "snapshot", serde(default))]
+    transfer: Option<(DmaTransfer, u32)>,
+    #[cfg_attr(feature = "snapshot", serde(default = "dma_cycle_default"))]
+    cycle: DmaCycle,
+    #[cfg_attr(feature = "snapshot", serde(skip))]
+    _port_decode: PhantomData<P>,
+    #[cfg_attr(feature = "snapshot", serde(default))]
+    bus: D
+}
+
+impl DmaAddressMode {
+    #[inline]
+    fn step(self) -> u16 {
+        match self {
+            DmaAddressMode::Decrement => u16::MAX,
+            DmaAddressMode::Increment => 1,
+            DmaAddressMode::Fixed => 0
+        }
+    }
+
+    fn from_bits(bits: u8) -> Self {
+        match bits & 0b11 {
+            0b00 => DmaAddressMode::Decrement,
+            0b01 => DmaAddressMode::Increment,
+            _ => DmaAddressMode::Fixed
+        }
+    }
+}
+
+impl DmaEndpoint {
+    /// Returns the address of the byte at the given `offset` from the start of the transfer.
+    #[inline]
+    pub fn address_at(&self, offset: u32) -> u16 {
+        self.address.wrapping_add(self.mode.step().wrapping_mul(offset as u16))
+    }
+}
+
+impl DmaPort {
+    fn cycle_length(&self) -> u8 {
+        match self.timing {
+            Some(timing) => match timing & 0b11 {
+                0b01 => 3,
+                0b10 => 2,
+                _ => 4
+            }
+            None if self.is_io => 4,
+            None => 3
+        }
+    }
+
+    fn program(&mut self, data: u8) -> DmaParams {
+        self.is_io = data & 0b0000_1000 != 0;
+        self.mode = DmaAddressMode::from_bits(data >> 4);
+        if data & 0b0100_0000 != 0 { DmaParams::TIMING_A } else { DmaParams::empty() }
+    }
+
+    fn endpoint(&self, address: u16) -> DmaEndpoint {
+        DmaEndpoint { address, is_io: self.is_io, mode: self.mode }
+    }
+}
+
+impl<P, D: Default> Default for Z80Dma<P, D> {
+    fn default() -> Self {
+        Z80Dma {
+            port_a: DmaPort::default(),
+            port_b: DmaPort::default(),
+            block_length: 0,
+            a_to_b: false,
+            mode: DmaTransferMode::default(),
+            counter_a: 0,
+            counter_b: 0,
+            transferred: 0,
+            status: STATUS_BASE | STATUS_NOT_END_OF_BLOCK,
+            read_mask: DEFAULT_READ_MASK,
+            read_index: 0,
+            params: DmaParams::empty(),
+            interrupt_control: 0,
+            transfer: None,
+            cycle: DmaCycle::Read,
+            _port_decode: PhantomData,
+            bus: D::default()
+        }
+    }
+}
+
+impl<P, D> Z80Dma<P, D> {
+    /// Returns the transfer being performed and the number of bytes already transferred.
+    pub fn transfer(&self) -> Option<(&DmaTransfer, u32)> {
+        self.transfer.as_ref().map(|(transfer, offset)| (transfer, *offset))
+    }
+    /// Returns the current transfer mode.
+    pub fn transfer_mode(&self) -> DmaTransferMode {
+        self.mode
+    }
+    /// Resets the DMA controller to its initial state, aborting the transfer being performed.
+    pub fn reset_dma(&mut self) {
+        self.dma_reset();
+        self.block_length = 0;
+        self.a_to_b = false;
+        self.port_a = DmaPort::default();
+        self.port_b = DmaPort::default();
+        self.mode = DmaTransferMode::default();
+        self.counter_a = 0;
+        self.counter_b = 0;
+        self.transferred = 0;
+    }
+
+    fn dma_reset(&mut self) {
+        self.transfer = None;
+        self.cycle = DmaCycle::Read;
+        self.port_a.timing = None;
+        self.port_b.timing = None;
+        self.status = STATUS_BASE | STATUS_NOT_END_OF_BLOCK;
+        self.read_mask = DEFAULT_READ_MASK;
+        self.read_index = 0;
+        self.params = DmaParams::empty();
+        self.interrupt_control = 0;
+    }
+
+    /// Starts the transfer.
+    fn enable(&mut self) {
+        let block_size = u32::from(self.block_length) + 1;
+        let length = block_size.saturating_sub(self.transferred);
+        if length == 0 || self.transfer.is_some() {
+            return
+        }
+        let (source, destination) = if self.a_to_b {
+            (self.port_a.endpoint(self.counter_a), self.port_b.endpoint(self.counter_b))
+        }
+        else {
+            (self.port_b.endpoint(self.counter_b), self.port_a.endpoint(self.counter_a))
+        };
+        self.status |= STATUS_TRANSFERRED;
+        self.transfer = Some((DmaTransfer { source, destination, length }, 0));
+        self.cycle = DmaCycle::Read;
+    }
+
+    /// Advances the counters after a byte has been transferred.
+    fn byte_transferred(&mut self) {
+        self.counter_a = self.port_a.endpoint(self.counter_a).address_at(1);
+        self.counter_b = self.port_b.endpoint(self.counter_b).address_at(1);
+        self.transferred += 1;
+        if let Some((transfer, offset)) = &mut self.transfer {
+            *offset += 1;
+            if *offset >= transfer.length {
+                self.transfer = None;
+                self.status &= !STATUS_NOT_END_OF_BLOCK;
+            }
+        }
+    }
+
+    fn load(&mut self) {
+        self.transfer = None;
+        self.counter_a = self.port_a.address;
+        self.counter_b = self.port_b.address;
+        self.transferred = 0;
+        self.status |= STATUS_NOT_END_OF_BLOCK;
+    }
+
+    fn command(&mut self, data: u8) {
+        match data {
+            0xC3 => self.dma_reset(),
+            0xC7 => self.port_a.timing = None,
+            0xCB => self.port_b.timing = None,
+            0xCF => self.load(),
+            0xD3 => {
+                self.transferred = 0;
+                self.status |= STATUS_NOT_END_OF_BLOCK;
+            }
+            0x8B => self.status = STATUS_BASE | STATUS_NOT_END_OF_BLOCK,
+            0xA7 => self.read_index = 0,
+            0xBB => self.params = DmaParams::READ_MASK,
+            0x83 => self.transfer = None,
+            0x87 => self.enable(),
+            _ => {}
+        }
+    }
+
+    fn write_param(&mut self, param: DmaParams, data: u8) {
+        fn set_lo(value: &mut u16, data: u8) {
+            *value = (*value & 0xff00) | u16::from(data)
+        }
+        fn set_hi(value: &mut u16, data: u8) {
+            *value = (*value & 0x00ff) | (u16::from(data) << 8)
+        }
+        match param {
+            DmaParams::PORT_A_LO => set_lo(&mut self.port_a.address, data),
+            DmaParams::PORT_A_HI => set_hi(&mut self.port_a.address, data),
+            DmaParams::LENGTH_LO => set_lo(&mut self.block_length, data),
+            DmaParams::LENGTH_HI => set_hi(&mut self.block_length, data),
+            DmaParams::TIMING_A => self.port_a.timing = Some(data),
+            DmaParams::TIMING_B => {
+                self.port_b.timing = Some(data);
+                // the prescaler is ignored
+                self.params.set(DmaParams::PRESCALER, data & 0b0010_0000 != 0);
+            }
+            DmaParams::PORT_B_LO => set_lo(&mut self.port_b.address, data),
+            DmaParams::PORT_B_HI => set_hi(&mut self.port_b.address, data),
+            DmaParams::INT_CONTROL => {
+                self.interrupt_control = data;
+                self.params.set(DmaParams::PULSE, data & 0b0000_1000 != 0);
+                self.params.set(DmaParams::VECTOR, data & 0b0001_0000 != 0);
+            }
+            DmaParams::READ_MASK => {
+                self.read_mask = data & DEFAULT_READ_MASK;
+                self.read_index = 0;
+            }
+            _ => {}
+        }
+    }
+
+    fn write_control(&mut self, data: u8) {
+        if !self.params.is_empty() {
+            let param = DmaParams::from_bits_retain(1 << self.params.bits().trailing_zeros());
+            self.params.remove(param);
+            self.write_param(param, data);
+            return
+        }
+        match data {
+            // WR0
+            d if d & 0x80 == 0 && d & 0b11 != 0 => {
+                self.a_to_b = d & 0b0000_0100 != 0;
+                self.params = DmaParams::from_bits_retain(u16::from(d >> 3) & 0b1111);
+            }
+            // WR1
+            d if d & 0x87 == 0b100 => {
+                self.params = self.port_a.program(d);
+            }
+            // WR2
+            d if d & 0x87 == 0b000 => {
+                if !self.port_b.program(d).is_empty() {
+                    self.params = DmaParams::TIMING_B;
+                }
+            }
+            // WR3
+            d if d & 0x83 == 0x80 => {
+                self.params.set(DmaParams::MASK, d & 0b0000_1000 != 0);
+                self.params.set(DmaParams::MATCH, d & 0b0001_0000 != 0);
+                if d & 0b0100_0000 != 0 {
+                    self.enable()
+                }
+            }
+            // WR4
+            d if d & 0x83 == 0x81 => {
+                self.mode = match (d >> 5) & 0b11 {
+                    0b00 => DmaTransferMode::Byte,
+                    0b01 => DmaTransferMode::Continuous,
+                    0b10 => DmaTransferMode::Burst,
+                    _ => self.mode
+                };
+                self.params.set(DmaParams::PORT_B_LO, d & 0b0000_0100 != 0);
+                self.params.set(DmaParams::PORT_B_HI, d & 0b0000_1000 != 0);
+                self.params.set(DmaParams::INT_CONTROL, d & 0b0001_0000 != 0);
+            }
+            // WR5
+            d if d & 0xC7 == 0x82 => {}
+            // WR6
+            d => self.command(d)
+        }
+    }
+
+    fn read_status(&mut self) -> u8 {
+        if self.read_mask == 0 {
+            return self.status
+        }
+        loop {
+            let index = self.read_index;
+            self.read_index = (index + 1) % 7;
+            if self.read_mask & (1 << index) != 0 {
+                let [cnt_lo, cnt_hi] = (self.transferred as u16).to_le_bytes();
+                let [a_lo, a_hi] = self.counter_a.to_le_bytes();
+                let [b_lo, b_hi] = self.counter_b.to_le_bytes();
+                return [self.status, cnt_lo, cnt_hi, a_lo, a_hi, b_lo, b_hi][index as usize]
+            }
+        }
+    }
+}
+
+impl<P, D> fmt::Display for Z80Dma<P, D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Z80 DMA")
+    }
+}
+
+impl<P, D> PassByAyAudioBusDevice for Z80Dma<P, D> {}
+
+impl<P, D> BusDevice for Z80Dma<P, D>
+    where P: PortAddress,
+          D: BusDevice,
+          D::Timestamp: Copy
+{
+    type Timestamp = D::Timestamp;
+    type NextDevice = D;
+
+    #[inline]
+    fn next_device_mut(&mut self) -> &mut Self::NextDevice {
+        &mut self.bus
+    }
+
+    #[inline]
+    fn next_device_ref(&self) -> &Self::NextDevice {
+        &self.bus
+    }
+
+    #[inline]
+    fn into_next_device(self) -> Self::NextDevice {
+        self.bus
+    }
+
+    #[inline]
+    fn reset(&mut self, timestamp: Self::Timestamp) {
+        self.reset_dma();
+        self.bus.reset(timestamp);
+    }
+
+    #[inline]
+    fn read_io(&mut self, port: u16, timestamp: Self::Timestamp) -> Option<(u8, Option<NonZeroU16>)> {
+        if P::match_port(port) {
+            return Some((self.read_status(), None))
+        }
+        self.bus.read_io(port, timestamp)
+    }
+
+    #[inline]
+    fn write_io(&mut self, port: u16, data: u8, timestamp: Self::Timestamp) -> Option<u16> {
+        if P::match_port(port) {
+            self.write_control(data);
+            return Some(0)
+        }
+        self.bus.write_io(port, data, timestamp)
+    }
+
+    #[inline]
+    fn is_bus_requested(&self) -> bool {
+        self.transfer.is_some() || self.bus.is_bus_requested()
+    }
+
+    fn bus_request(&mut self) -> Option<(BusCycle, u8)> {
+        let (transfer, offset) = match &self.transfer {
+            Some((transfer, offset)) => (*transfer, *offset),
+            None => return self.bus.bus_request()
+        };
+        let (source, destination) = if self.a_to_b {
+            (&self.port_a, &self.port_b)
+        }
+        else {
+            (&self.port_b, &self.port_a)
+        };
+        match self.cycle {
+            DmaCycle::Read => {
+                let address = transfer.source.address_at(offset);
+                let cycle = if transfer.source.is_io {
+                    BusCycle::ReadIo(address)
+                }
+                else {
+                    BusCycle::ReadMemory(address)
+                };
+                Some((cycle, source.cycle_length()))
+            }
+            DmaCycle::Write(data) => {
+                let address = transfer.destination.address_at(offset);
+                let cycle = if transfer.destination.is_io {
+                    BusCycle::WriteIo(address, data)
+                }
+                else {
+                    BusCycle::WriteMemory(address, data)
+                };
+                let length = destination.cycle_length();
+                self.cycle = match self.mode {
+                    DmaTransferMode::Byte => DmaCycle::Release,
+                    _ => DmaCycle::Read
+                };
+                self.byte_transferred();
+                Some((cycle, length))
+            }
+            DmaCycle::Release => {
+                self.cycle = DmaCycle::Read;
+                None
+            }
+        }
+    }
+
+    #[inline]
+    fn bus_read_data(&mut self, data: u8) {
+        if self.transfer.is_some() && self.cycle == DmaCycle::Read {
+            self.cycle = DmaCycle::Write(data);
+        }
+        self.bus.bus_read_data(data);
+    }
+}
+
+#[cfg(feature = "snapshot")]
+fn dma_cycle_default() -> DmaCycle {
+    DmaCycle::Read
+}
+
+impl From<DmaParams> for u16 {
+    #[inline]
+    fn from(params: DmaParams) -> u16 {
+        params.bits()
+    }
+}
+
+impl From<u16> for DmaParams {
+    #[inline]
+    fn from(params: u16) -> DmaParams {
+        DmaParams::from_bits_truncate(params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use spectrusty_core::bus::NullDevice;
+    use spectrusty_core::clock::FTs;
+    use super::*;
+
+    type TestDma = Z80DmaDatagear<NullDevice<FTs>>;
+
+    struct TestBus {
+        memory: Vec<u8>,
+        io: Vec<(u16, u8)>,
+        ts: u32
+    }
+
+    impl TestBus {
+        fn new() -> Self {
+            TestBus { memory: vec![0; 0x10000], io: Vec::new(), ts: 0 }
+        }
+        /// Performs the requested bus cycles until the bus is released, returns the number of transferred bytes.
+        fn run(&mut self, dma: &mut TestDma) -> u32 {
+            let mut count = 0;
+            while let Some((cycle, length)) = dma.bus_request() {
+                self.ts += u32::from(length);
+                match cycle {
+                    BusCycle::ReadMemory(address) => dma.bus_read_data(self.memory[usize::from(address)]),
+                    BusCycle::ReadIo(port) => dma.bus_read_data(port as u8),
+                    BusCycle::WriteMemory(address, data) => {
+                        self.memory[usize::from(address)] = data;
+                        count += 1;
+                    }
+                    BusCycle::WriteIo(port, data) => {
+                        self.io.push((port, data));
+                        count += 1;
+                    }
+                }
+            }
+            count
+        }
+    }
+
+    fn program(dma: &mut TestDma, bytes: &[u8]) {
+        for &data in bytes.iter() {
+            assert_eq!(dma.write_io(0x6b, data, 0), Some(0));
+        }
+    }
+
+    #[test]
+    fn z80dma_memory_to_memory_works() {
+        let mut bus = TestBus::new();
+        for (i, p) in bus.memory[0x8000..0x8100].iter_mut().enumerate() {
+            *p = i as u8;
+        }
+        let mut dma = TestDma::default();
+        dma.reset(0);
+        assert!(!dma.is_bus_requested());
+        program(&mut dma, &[
+            0xC3,                   // reset
+            0x7D, 0x00, 0x80, 0xFF, 0x00, // WR0: A->B, port A: 0x8000, length: 0xFF
+            0x14,                   // WR1: port A memory, increment
+            0x10,                   // WR2: port B memory, increment
+            0xAD, 0x00, 0x90,       // WR4: continuous, port B: 0x9000
+            0xCF]);                 // load
+        assert_eq!(dma.transfer_mode(), DmaTransferMode::Continuous);
+        assert!(!dma.is_bus_requested());
+        assert_eq!(dma.bus_request(), None);
+        // enable DMA
+        program(&mut dma, &[0x87]);
+        assert!(dma.is_bus_requested());
+        assert_eq!(dma.transfer(), Some((&DmaTransfer {
+            source: DmaEndpoint { address: 0x8000, is_io: false, mode: DmaAddressMode::Increment },
+            destination: DmaEndpoint { address: 0x9000, is_io: false, mode: DmaAddressMode::Increment },
+            length: 256
+        }, 0)));
+        // 256 bytes * (3 + 3) T-states
+        assert_eq!(bus.run(&mut dma), 256);
+        assert_eq!(bus.ts, 256 * 6);
+        assert!(!dma.is_bus_requested());
+        assert_eq!(dma.transfer(), None);
+        assert_eq!(bus.memory[0x9000..0x9100], bus.memory[0x8000..0x8100]);
+        assert!(bus.memory[0x9100..].iter().all(|&b| b == 0));
+        // read status, byte counter and port addresses
+        program(&mut dma, &[0xA7]);
+        let status: Vec<u8> = (0..8).map(|_| dma.read_io(0x6b, 0).unwrap().0).collect();
+        assert_eq!(status, [0b0001_1011, 0x00, 0x01, 0x00, 0x81, 0x00, 0x91, 0b0001_1011]);
+        // the block has ended
+        program(&mut dma, &[0x87]);
+        assert!(!dma.is_bus_requested());
+        // continue from the current addresses
+        program(&mut dma, &[0xD3, 0x87]);
+        assert_eq!(dma.transfer().unwrap().0.source.address, 0x8100);
+        assert_eq!(dma.transfer().unwrap().0.destination.address, 0x9100);
+        // disable DMA
+        program(&mut dma, &[0x83]);
+        assert!(!dma.is_bus_requested());
+        // reset aborts the transfer
+        program(&mut dma, &[0xCF, 0x87]);
+        assert!(dma.is_bus_requested());
+        dma.reset(0);
+        assert!(!dma.is_bus_requested());
+    }
+
+    #[test]
+    fn z80dma_byte_mode_works() {
+        let mut bus = TestBus::new();
+        bus.memory[0x8000..0x8003].copy_from_slice(&[1, 2, 3]);
+        let mut dma = TestDma::default();
+        program(&mut dma, &[
+            0xC3,
+            0x7D, 0x00, 0x80, 0x02, 0x00, // WR0: A->B, port A: 0x8000, length: 2
+            0x14, 0x10,             // WR1, WR2: memory, increment
+            0x8D, 0x00, 0x90,       // WR4: byte, port B: 0x9000
+            0xCF, 0x87]);
+        assert_eq!(dma.transfer_mode(), DmaTransferMode::Byte);
+        // the bus is released after each byte
+        for n in 1..=3 {
+            assert!(dma.is_bus_requested());
+            assert_eq!(bus.run(&mut dma), 1);
+            assert_eq!(bus.ts, n * 6);
+            assert_eq!(bus.memory[0x9000..0x9003][..n as usize], bus.memory[0x8000..0x8003][..n as usize]);
+            // the status reports the byte counter
+            program(&mut dma, &[0xBB, 0b0000_0010, 0xA7]);
+            assert_eq!(dma.read_io(0x6b, 0), Some((n as u8, None)));
+        }
+        assert!(!dma.is_bus_requested());
+        assert_eq!(bus.run(&mut dma), 0);
+    }
+
+    #[test]
+    fn z80dma_memory_to_io_works() {
+        let mut bus = TestBus::new();
+        bus.memory[0x4000..0x4004].copy_from_slice(&[1, 2, 3, 4]);
+        let mut dma = TestDma::default();
+        assert_eq!(dma.read_io(0x6b, 0), Some((0b0011_1010, None)));
+        program(&mut dma, &[
+            0xC3,
+            0x79, 0xFE, 0x00, 0x03, 0x00, // WR0: B->A, port A: 0x00FE, length: 3
+            0x2C,                   // WR1: port A I/O, fixed
+            0x00,                   // WR2: port B memory, decrement
+            0xCD, 0x03, 0x40,       // WR4: burst, port B: 0x4003
+            0xCF, 0x87]);
+        assert_eq!(dma.transfer_mode(), DmaTransferMode::Burst);
+        assert_eq!(bus.run(&mut dma), 4);
+        assert_eq!(bus.ts, 4 * (4 + 3));
+        assert_eq!(bus.io, [(0xFE, 4), (0xFE, 3), (0xFE, 2), (0xFE, 1)]);
+        // custom timing: port A 2 T-states, port B 4 T-states
+        bus.ts = 0;
+        program(&mut dma, &[0x6C, 0x02, 0x40, 0x00, 0xCF, 0x87]);
+        bus.run(&mut dma);
+        assert_eq!(bus.ts, 4 * (2 + 4));
+        // port B 3 T-states, the prescaler byte following the timing byte is ignored
+        program(&mut dma, &[0x40, 0x21, 0x10]);
+        assert!(!dma.is_bus_requested());
+        bus.ts = 0;
+        program(&mut dma, &[0xCF, 0x87]);
+        bus.run(&mut dma);
+        assert_eq!(bus.ts, 4 * (2 + 3));
+        // standard timing
+        bus.ts = 0;
+        program(&mut dma, &[0xC7, 0xCB, 0xCF, 0x87]);
+        bus.run(&mut dma);
+        assert_eq!(bus.ts, 4 * (4 + 3));
+        // I/O to memory
+        bus.ts = 0;
+        program(&mut dma, &[0x7D, 0x00, 0x80, 0x01, 0x00, 0x2C, 0x10, 0xAD, 0x00, 0x50, 0xCF, 0x87]);
+        bus.run(&mut dma);
+        assert_eq!(bus.memory[0x5000..0x5003], [0x00, 0x00, 0x00]);
+        // other ports are not affected
+        assert_eq!(dma.write_io(0x6a, 0x87, 0), None);
+        assert_eq!(dma.read_io(0x0b, 0), None);
+    }
+}
@@ -0,0 +1,191 @@
+/*
+    Copyright (C) 2020-2023  Rafal Michalski
+
+    This file is part of SPECTRUSTY, a Rust library for building emulators.
+
+    For the full copyright notice, see the lib.rs file.
+*/
+//! The *Fuller Box* interface with the `AY-3-8912` sound generator and the Fuller Joystick.
+use core::fmt::{self, Debug};
+use core::num::NonZeroU16;
+use core::ops::{Deref, DerefMut};
+
+#[cfg(feature = "snapshot")]
+use serde::{Serialize, Deserialize};
+
+use spectrusty_core::{
+    audio::{Blep, AmpLevels},
+    bus::{BusDevice, PortAddress},
+    clock::FTs
+};
+
+use super::ay::{Ay3_891xFullerBox, AyAudioBusDevice};
+use super::joystick::{FullerJoyPortAddress, FullerJoystickDevice, JoystickDevice};
+
+/// The *Fuller Box* interface as a single [BusDevice].
+///
+/// Combines the [Ay3_891xFullerBox] sound generator, available on ports `0x3F` (register
+/// selection and reading) and `0x5F` (register writing), with the [FullerJoystickDevice]
+/// available on port `0x7F`.
+///
+/// The joystick interface is accessible via dereferencing the device.
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "snapshot", serde(bound(
+    serialize = "Ay3_891xFullerBox<D>: Serialize",
+    deserialize = "Ay3_891xFullerBox<D>: Deserialize<'de>")))]
+pub struct FullerBox<D: BusDevice> {
+    /// Provides direct access to the Fuller Joystick device.
+    #[cfg_attr(feature = "snapshot", serde(skip))]
+    pub joystick: FullerJoystickDevice,
+    /// Provides direct access to the sound generator bus device.
+    pub ay: Ay3_891xFullerBox<D>
+}
+
+impl<D: BusDevice> Default for FullerBox<D> where Ay3_891xFullerBox<D>: Default {
+    fn default() -> Self {
+        FullerBox { joystick: Default::default(), ay: Default::default() }
+    }
+}
+
+impl<D: BusDevice> Clone for FullerBox<D> where Ay3_891xFullerBox<D>: Clone {
+    fn clone(&self) -> Self {
+        FullerBox { joystick: self.joystick, ay: self.ay.clone() }
+    }
+}
+
+impl<D: BusDevice> Debug for FullerBox<D> where Ay3_891xFullerBox<D>: Debug {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FullerBox")
+            .field("joystick", &self.joystick)
+            .field("ay", &self.ay)
+            .finish()
+    }
+}
+
+impl<D: BusDevice> fmt::Display for FullerBox<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Fuller Box")
+    }
+}
+
+impl<D: BusDevice> Deref for FullerBox<D> {
+    type Target = FullerJoystickDevice;
+    fn deref(&self) -> &Self::Target {
+        &self.joystick
+    }
+}
+
+impl<D: BusDevice> DerefMut for FullerBox<D> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.joystick
+    }
+}
+
+impl<D> AyAudioBusDevice for FullerBox<D>
+    where D: BusDevice,
+          D::Timestamp: Into<FTs> + Debug + Copy
+{
+    #[inline(always)]
+    fn render_ay_audio<L, B>(&mut self, blep: &mut B, end_ts: D::Timestamp, frame_tstates: FTs, chans: [usize; 3])
+        where B: Blep,
+              L: AmpLevels<B::SampleDelta>
+    {
+        self.ay.render_ay_audio::<L, B>(blep, end_ts, frame_tstates, chans)
+    }
+}
+
+impl<D> AyAudioBusDevice for Box<FullerBox<D>>
+    where D: BusDevice,
+          D::Timestamp: Into<FTs> + Debug + Copy
+{
+    fn render_ay_audio<L, B>(&mut self, blep: &mut B, end_ts: D::Timestamp, frame_tstates: FTs, chans: [usize; 3])
+        where B: Blep,
+              L: AmpLevels<B::SampleDelta>
+    {
+        (**self).render_ay_audio::<L, B>(blep, end_ts, frame_tstates, chans)
+    }
+}
+
+impl<D> BusDevice for FullerBox<D>
+    where D: BusDevice,
+          D::Timestamp: Debug + Copy
+{
+    type Timestamp = D::Timestamp;
+    type NextDevice = D;
+
+    #[inline]
+    fn next_device_mut(&mut self) -> &mut Self::NextDevice {
+        self.ay.next_device_mut()
+    }
+
+    #[inline]
+    fn next_device_ref(&self) -> &Self::NextDevice {
+        self.ay.next_device_ref()
+    }
+
+    #[inline]
+    fn into_next_device(self) -> Self::NextDevice {
+        self.ay.into_next_device()
+    }
+
+    #[inline]
+    fn reset(&mut self, timestamp: Self::Timestamp) {
+        self.ay.reset(timestamp)
+    }
+
+    #[inline]
+    fn read_io(&mut self, port: u16, timestamp: Self::Timestamp) -> Option<(u8, Option<NonZeroU16>)> {
+        // the AY data port is decoded by the sound generator device, so it never reaches the joystick
+        let bus_data = self.ay.read_io(port, timestamp);
+        if FullerJoyPortAddress::match_port(port) {
+            let joy_data = self.joystick.port_read(port);
+            if let Some((data, ws)) = bus_data {
+                return Some((data & joy_data, ws))
+            }
+            return Some((joy_data, None))
+        }
+        bus_data
+    }
+
+    #[inline]
+    fn write_io(&mut self, port: u16, data: u8, timestamp: Self::Timestamp) -> Option<u16> {
+        self.ay.write_io(port, data, timestamp)
+    }
+
+    #[inline]
+    fn next_frame(&mut self, timestamp: Self::Timestamp) {
+        self.ay.next_frame(timestamp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use spectrusty_core::bus::NullDevice;
+    use crate::ay::AyRegister;
+    use crate::joystick::{JoystickInterface, Directions};
+    use super::*;
+
+    #[test]
+    fn fuller_box_works() {
+        let mut fuller = FullerBox::<NullDevice<FTs>>::default();
+        assert_eq!(fuller.to_string(), "Fuller Box");
+        assert_eq!(fuller.read_io(0x007f, 0), Some((0xff, None)));
+        fuller.set_directions(Directions::UP|Directions::RIGHT);
+        fuller.fire(0, true);
+        assert_eq!(fuller.read_io(0x007f, 0), Some((0b0111_0110, None)));
+        assert_eq!(fuller.read_io(0xff7f, 0), Some((0b0111_0110, None)));
+        // AY register round-trip
+        assert_eq!(fuller.write_io(0x003f, AyRegister::ToneFineA as u8, 1), Some(0));
+        assert_eq!(fuller.write_io(0x005f, 0xA5, 2), Some(0));
+        assert_eq!(fuller.ay.ay_io.get(AyRegister::ToneFineA), 0xA5);
+        assert_eq!(fuller.read_io(0x003f, 3), Some((0xA5, None)));
+        // the joystick state is unaffected by the AY
+        assert_eq!(fuller.read_io(0x007f, 4), Some((0b0111_0110, None)));
+        fuller.fire(0, false);
+        fuller.set_directions(Directions::empty());
+        assert_eq!(fuller.read_io(0x007f, 5), Some((0xff, None)));
+        // unrelated ports
+        assert_eq!(fuller.read_io(0x00fe, 6), None);
+        assert_eq!(fuller.write_io(0x007f, 0, 7), None);
+    }
+}
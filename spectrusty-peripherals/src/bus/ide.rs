@@ -0,0 +1,267 @@
+/*
+    Copyright (C) 2020-2023  Rafal Michalski
+
+    This file is part of SPECTRUSTY, a Rust library for building emulators.
+
+    For the full copyright notice, see the lib.rs file.
+*/
+//! A bus device emulating the DivIDE interface.
+use core::num::NonZeroU16;
+use core::fmt;
+use std::io::{Read, Write, Seek};
+
+#[cfg(feature = "snapshot")]
+use serde::{Serialize, Deserialize};
+
+use spectrusty_core::bus::BusDevice;
+
+use super::ay::PassByAyAudioBusDevice;
+
+pub use crate::memory::{DivIdeControl, DivIdeMemExt};
+pub use crate::storage::ata::{AtaDevice, AtaRegister};
+
+/// The port address mask of the DivIDE control register.
+pub const DIVIDE_CONTROL_PORT_MASK: u16 = 0b0000_0000_1110_0011;
+/// The port address bits of the DivIDE control register.
+pub const DIVIDE_CONTROL_PORT_BITS: u16 = 0b0000_0000_1110_0011;
+/// The port address mask of the DivIDE IDE registers.
+pub const DIVIDE_IDE_PORT_MASK: u16 = 0b0000_0000_1110_0011;
+/// The port address bits of the DivIDE IDE registers.
+pub const DIVIDE_IDE_PORT_BITS: u16 = 0b0000_0000_1010_0011;
+
+/// The DivIDE interface [BusDevice] with an 8-bit IDE/ATA interface.
+///
+/// The ATA device is backed by a raw disk or CompactFlash card image `F`, see [AtaDevice].
+///
+/// The DivIDE memory is emulated by [DivIdeMemExt], which should be installed as the memory extension
+/// of the chipset. Both components must share the same paging state:
+///
+/// ```text
+/// divide.set_control(memext.control().clone());
+/// ```
+///
+/// The IDE command block registers are being accessed via ports `0xA3`, `0xA7`, ..., `0xBF`, where bits A2-A4
+/// of the port address select the register. The control register is written to via port `0xE3`, partially
+/// decoded with bits A0, A1, A5, A6 and A7 of the port address.
+///
+/// The image is not being serialized and should be mounted again after the device is deserialized.
+/// The paging state is serialized with [DivIdeMemExt], so the link must be established again after both
+/// components are deserialized.
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+pub struct DivIde<F, D> {
+    /// The emulated ATA master device.
+    #[cfg_attr(feature = "snapshot", serde(skip))]
+    pub ata: AtaDevice<F>,
+    #[cfg_attr(feature = "snapshot", serde(skip))]
+    control: DivIdeControl,
+    #[cfg_attr(feature = "snapshot", serde(default))]
+    bus: D
+}
+
+impl<F, D: Default> Default for DivIde<F, D> {
+    fn default() -> Self {
+        DivIde {
+            ata: AtaDevice::default(),
+            control: DivIdeControl::default(),
+            bus: D::default()
+        }
+    }
+}
+
+impl<F, D: fmt::Debug> fmt::Debug for DivIde<F, D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DivIde")
+            .field("ata", &self.ata)
+            .field("control", &self.control)
+            .field("bus", &self.bus)
+            .finish()
+    }
+}
+
+impl<F, D> fmt::Display for DivIde<F, D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("DivIDE")
+    }
+}
+
+impl<F, D> DivIde<F, D> {
+    /// Attaches the paging state handle shared with [DivIdeMemExt].
+    pub fn set_control(&mut self, control: DivIdeControl) {
+        self.control = control;
+    }
+    /// Returns a reference to the paging state handle.
+    pub fn control(&self) -> &DivIdeControl {
+        &self.control
+    }
+}
+
+impl<F, D> PassByAyAudioBusDevice for DivIde<F, D> {}
+
+impl<F, D> BusDevice for DivIde<F, D>
+    where F: Read + Write + Seek,
+          D: BusDevice
+{
+    type Timestamp = D::Timestamp;
+    type NextDevice = D;
+
+    #[inline]
+    fn next_device_mut(&mut self) -> &mut Self::NextDevice {
+        &mut self.bus
+    }
+
+    #[inline]
+    fn next_device_ref(&self) -> &Self::NextDevice {
+        &self.bus
+    }
+
+    #[inline]
+    fn into_next_device(self) -> Self::NextDevice {
+        self.bus
+    }
+
+    fn reset(&mut self, timestamp: Self::Timestamp) {
+        self.control.reset();
+        self.ata.reset();
+        self.bus.reset(timestamp);
+    }
+
+    #[inline]
+    fn read_io(&mut self, port: u16, timestamp: Self::Timestamp) -> Option<(u8, Option<NonZeroU16>)> {
+        if port & DIVIDE_IDE_PORT_MASK == DIVIDE_IDE_PORT_BITS {
+            let data = self.ata.read_register(AtaRegister::from_index((port >> 2) as u8));
+            return Some((data, None))
+        }
+        self.bus.read_io(port, timestamp)
+    }
+
+    #[inline]
+    fn write_io(&mut self, port: u16, data: u8, timestamp: Self::Timestamp) -> Option<u16> {
+        if port & DIVIDE_CONTROL_PORT_MASK == DIVIDE_CONTROL_PORT_BITS {
+            self.control.write_control(data);
+            return Some(0)
+        }
+        if port & DIVIDE_IDE_PORT_MASK == DIVIDE_IDE_PORT_BITS {
+            self.ata.write_register(AtaRegister::from_index((port >> 2) as u8), data);
+            return Some(0)
+        }
+        self.bus.write_io(port, data, timestamp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use spectrusty_core::{bus::NullDevice, clock::FTs};
+    use crate::memory::DIVIDE_CONMEM;
+    use crate::storage::ata::*;
+    use super::*;
+
+    type TestDivIde = DivIde<Cursor<Vec<u8>>, NullDevice<FTs>>;
+
+    fn read_block(divide: &mut TestDivIde) -> Vec<u8> {
+        (0..SECTOR_SIZE).map(|_| divide.read_io(0xA3, 0).unwrap().0).collect()
+    }
+
+    #[test]
+    fn divide_identify_device_works() {
+        let mut divide = TestDivIde::default();
+        assert_eq!(divide.to_string(), "DivIDE");
+        let memext = DivIdeMemExt::default();
+        assert!(!divide.control().is_shared_with(memext.control()));
+        divide.set_control(memext.control().clone());
+        assert!(divide.control().is_shared_with(memext.control()));
+        assert_eq!(divide.write_io(0x00E3, DIVIDE_CONMEM|1, 0), Some(0));
+        assert!(memext.is_paged_in());
+        assert_eq!(memext.control().control(), DIVIDE_CONMEM|1);
+        // the control port is partially decoded
+        assert_eq!(divide.write_io(0x12FF, 2, 0), Some(0));
+        assert_eq!(memext.control().control(), 2);
+        assert_eq!(divide.write_io(0x00C3, DIVIDE_CONMEM, 0), None);
+        assert_eq!(memext.control().control(), 2);
+        assert_eq!(divide.write_io(0x00E3, DIVIDE_CONMEM|1, 0), Some(0));
+        // no image
+        assert_eq!(divide.read_io(0xBF, 0), Some((0, None)));
+        assert_eq!(divide.read_io(0xFE, 0), None);
+        let sectors = 2 * 16 * 63 + 5;
+        let image: Vec<u8> = (0..sectors * SECTOR_SIZE).map(|x| (x / SECTOR_SIZE) as u8).collect();
+        divide.ata.mount(Cursor::new(image)).unwrap();
+        assert_eq!(divide.ata.sector_count(), sectors as u32);
+        assert_eq!(divide.read_io(0xBF, 0), Some((STATUS_DRDY|STATUS_DSC, None)));
+        // IDENTIFY DEVICE
+        assert_eq!(divide.write_io(0xBB, 0xA0, 0), Some(0));
+        assert_eq!(divide.write_io(0xBF, CMD_IDENTIFY_DEVICE, 0), Some(0));
+        assert_eq!(divide.read_io(0xBF, 0), Some((STATUS_DRDY|STATUS_DSC|STATUS_DRQ, None)));
+        let block = read_block(&mut divide);
+        assert_eq!(divide.read_io(0xBF, 0), Some((STATUS_DRDY|STATUS_DSC, None)));
+        let word = |index: usize| u16::from_le_bytes([block[index * 2], block[index * 2 + 1]]);
+        assert_eq!(word(0), 0x848A);
+        assert_eq!((word(1), word(3), word(6)), (2, 16, 63));
+        assert_eq!(u32::from(word(60)) | u32::from(word(61)) << 16, sectors as u32);
+        assert_eq!(u32::from(word(7)) << 16 | u32::from(word(8)), sectors as u32);
+        assert_eq!(&block[54..64], b"PSCERTSUYT");
+        // the slave device is absent
+        assert_eq!(divide.write_io(0xBB, 0xB0, 0), Some(0));
+        assert_eq!(divide.read_io(0xBF, 0), Some((0, None)));
+        // READ SECTOR(S) in LBA mode
+        assert_eq!(divide.write_io(0xBB, 0xE0, 0), Some(0));
+        assert_eq!(divide.write_io(0xAB, 2, 0), Some(0));
+        assert_eq!(divide.write_io(0xAF, 7, 0), Some(0));
+        assert_eq!(divide.write_io(0xB3, 0, 0), Some(0));
+        assert_eq!(divide.write_io(0xB7, 0, 0), Some(0));
+        assert_eq!(divide.write_io(0xBF, CMD_READ_SECTORS, 0), Some(0));
+        assert!(read_block(&mut divide).iter().all(|&b| b == 7));
+        assert!(read_block(&mut divide).iter().all(|&b| b == 8));
+        assert_eq!(divide.read_io(0xAF, 0), Some((8, None)));
+        assert_eq!(divide.read_io(0xAB, 0), Some((0, None)));
+        // WRITE SECTOR(S) in CHS mode: cylinder 1, head 0, sector 1
+        assert_eq!(divide.write_io(0xBB, 0xA0, 0), Some(0));
+        assert_eq!(divide.write_io(0xAB, 1, 0), Some(0));
+        assert_eq!(divide.write_io(0xAF, 1, 0), Some(0));
+        assert_eq!(divide.write_io(0xB3, 1, 0), Some(0));
+        assert_eq!(divide.write_io(0xBF, CMD_WRITE_SECTORS, 0), Some(0));
+        for _ in 0..SECTOR_SIZE {
+            divide.write_io(0xA3, 0xAA, 0);
+        }
+        assert_eq!(divide.read_io(0xBF, 0), Some((STATUS_DRDY|STATUS_DSC, None)));
+        assert_eq!(divide.read_io(0xAF, 0), Some((1, None)));
+        assert_eq!(divide.read_io(0xAB, 0), Some((0, None)));
+        let image = divide.ata.image_ref().unwrap().get_ref();
+        assert!(image[16 * 63 * SECTOR_SIZE..][..SECTOR_SIZE].iter().all(|&b| b == 0xAA));
+        // sector out of range
+        assert_eq!(divide.write_io(0xBB, 0xE0, 0), Some(0));
+        assert_eq!(divide.write_io(0xB3, 0x10, 0), Some(0));
+        assert_eq!(divide.write_io(0xBF, CMD_READ_SECTORS, 0), Some(0));
+        assert_eq!(divide.read_io(0xBF, 0), Some((STATUS_DRDY|STATUS_DSC|STATUS_ERR, None)));
+        assert_eq!(divide.read_io(0xA7, 0), Some((ERROR_IDNF, None)));
+        // unsupported command
+        assert_eq!(divide.write_io(0xBF, 0xC8, 0), Some(0));
+        assert_eq!(divide.read_io(0xA7, 0), Some((ERROR_ABRT, None)));
+        divide.reset(0);
+        assert!(!memext.is_paged_in());
+        assert!(divide.ata.is_mounted());
+        assert!(divide.ata.eject().is_some());
+        assert_eq!(divide.read_io(0xBF, 0), Some((0, None)));
+    }
+
+    #[cfg(feature = "snapshot")]
+    #[test]
+    fn divide_serde_works() {
+        let mut divide = TestDivIde::default();
+        let memext = DivIdeMemExt::default();
+        divide.set_control(memext.control().clone());
+        divide.write_io(0x00E3, DIVIDE_CONMEM|1, 0);
+        let divide_json = serde_json::to_string(&divide).unwrap();
+        let memext_json = serde_json::to_string(&memext).unwrap();
+        let mut divide: TestDivIde = serde_json::from_str(&divide_json).unwrap();
+        let memext: DivIdeMemExt = serde_json::from_str(&memext_json).unwrap();
+        // the paging state is restored with the memory extension only
+        assert_eq!(memext.control().control(), DIVIDE_CONMEM|1);
+        assert!(memext.is_paged_in());
+        assert!(!divide.control().is_shared_with(memext.control()));
+        // re-link
+        divide.set_control(memext.control().clone());
+        assert!(divide.control().is_shared_with(memext.control()));
+        divide.write_io(0x00E3, 0, 0);
+        assert!(!memext.is_paged_in());
+    }
+}
@@ -25,15 +25,22 @@ use super::ay::PassByAyAudioBusDevice;
 
 pub use crate::joystick::{
     JoystickDevice, JoystickInterface, NullJoystickDevice,
+    autofire::AutoFireJoystick,
     kempston::*, fuller::*, sinclair::*, cursor::*
 };
 
 /// A convenient Kempston Joystick [BusDevice] type.
-pub type KempstonJoystick<D> = JoystickBusDevice<
-                                                KempstonJoyPortAddress,
+///
+/// By default the joystick port is partially decoded, as on the original Kempston interface. Substitute `P`
+/// with another [PortAddress], e.g. [CustomJoyPortAddress], to match the decoding of specific hardware.
+pub type KempstonJoystick<D, P=KempstonJoyPortAddress> = JoystickBusDevice<
+                                                P,
                                                 KempstonJoystickDevice,
                                                 D>;
 /// A convenient Fuller Joystick [BusDevice] type.
+///
+/// This device decodes only the joystick port `0x7F` and can be used on its own. For the joystick combined
+/// with the *Fuller Box* sound generator see [FullerBox][crate::bus::FullerBox].
 pub type FullerJoystick<D> = JoystickBusDevice<
                                                 FullerJoyPortAddress,
                                                 FullerJoystickDevice,
@@ -55,30 +62,42 @@ pub type CursorJoystick<D> = JoystickBusDevice<
                                                 CursorJoyPortAddress,
                                                 CursorJoystickDevice,
                                                 D>;
+/// A convenient Kempston Joystick [BusDevice] type with the auto-fire function.
+pub type AutoFireKempstonJoystick<D, P=KempstonJoyPortAddress> = AutoFireJoystickBusDevice<
+                                                P,
+                                                KempstonJoystickDevice,
+                                                D>;
 macro_rules! joystick_names {
-    ($($ty:ty: $name:expr),*) => { $(
-        impl<D> From<&$ty> for &str {
+    ($([$($gen:ident),*] $ty:ty: $name:expr),*) => { $(
+        impl<$($gen),*> From<&$ty> for &str {
            fn from(_joy: &$ty) -> Self {
                 $name
            }
         }
 
-        impl<D> From<$ty> for &str {
+        impl<$($gen),*> From<$ty> for &str {
             fn from(_joy: $ty) -> Self {
                 $name
             }
         }
 
-        impl<D> fmt::Display for $ty {
+        impl<$($gen),*> fmt::Display for $ty {
             fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
                 write!(f, "{} Joystick", <&str>::from(self))
             }
         }
     )*};
+    ($($ty:ty: $name:expr),*) => {
+        joystick_names! { $([D] $ty: $name),* }
+    };
+}
+
+joystick_names! {
+    [D, P] KempstonJoystick<D, P>: "Kempston",
+    [D, P] AutoFireKempstonJoystick<D, P>: "Kempston auto-fire"
 }
 
 joystick_names! {
-    KempstonJoystick<D>: "Kempston",
     FullerJoystick<D>: "Fuller",
     SinclairRightJoystick<D>: "Sinclair #1",
     SinclairLeftJoystick<D>: "Sinclair #2",
@@ -100,6 +119,30 @@ pub struct JoystickBusDevice<P, J, D>
     _port_decode: PhantomData<P>,
 }
 
+/// A joystick controller with the [auto-fire][AutoFireJoystick] function, providing a [BusDevice]
+/// implementation that can be used with [joystick devices][JoystickDevice].
+///
+/// Unlike [JoystickBusDevice], the auto-fire frequency is being serialized along with the device.
+///
+/// The auto-fire state should be updated once per frame, before the frame is being executed, e.g.:
+///
+/// ```text
+/// let frame = ula.current_frame();
+/// ula.bus_device_mut().update_autofire_with_frame(frame, U::CPU_HZ, U::FRAME_TSTATES);
+/// ```
+#[derive(Clone, Default, Debug)]
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+pub struct AutoFireJoystickBusDevice<P, J, D>
+{
+    /// An [AutoFireJoystick] wrapping a [JoystickDevice] implementation.
+    #[cfg_attr(feature = "snapshot", serde(default, bound(serialize = "", deserialize = "J: Default")))]
+    pub joystick: AutoFireJoystick<J>,
+    #[cfg_attr(feature = "snapshot", serde(default))]
+    bus: D,
+    #[cfg_attr(feature = "snapshot", serde(skip))]
+    _port_decode: PhantomData<P>,
+}
+
 /// Kempston Joystick [PortAddress].
 #[derive(Clone, Copy, Default, Debug)]
 pub struct KempstonJoyPortAddress;
@@ -107,6 +150,26 @@ impl PortAddress for KempstonJoyPortAddress {
     const ADDRESS_MASK: u16 = 0x0020;
     const ADDRESS_BITS: u16 = 0x001f;
 }
+/// Kempston Joystick [PortAddress] fully decoding the port `0x1F`.
+pub type KempstonFullJoyPortAddress = CustomJoyPortAddress<0x00ff, 0x001f>;
+/// Kempston Joystick [PortAddress] fully decoding the alternative port `0xDF`.
+pub type KempstonAltJoyPortAddress = CustomJoyPortAddress<0x00ff, 0x00df>;
+/// Kempston Joystick [PortAddress] decoding the port `0x1F` with A5, A6 and A7 bits of the port address.
+///
+/// Use it when the Kempston Joystick is attached together with the [Kempston Mouse], which otherwise
+/// share the mouse ports `0xFADF`, `0xFBDF` and `0xFFDF` with the partially decoded joystick.
+///
+/// [Kempston Mouse]: super::mouse::KempstonMousePortAddress
+pub type KempstonMouseJoyPortAddress = CustomJoyPortAddress<0x00e0, 0x001f>;
+/// A joystick [PortAddress] with the decoding mask and the address bits given as const parameters.
+///
+/// The port is matched when the address bits selected by the `MASK` are equal to the same bits of `BITS`.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct CustomJoyPortAddress<const MASK: u16, const BITS: u16>;
+impl<const MASK: u16, const BITS: u16> PortAddress for CustomJoyPortAddress<MASK, BITS> {
+    const ADDRESS_MASK: u16 = MASK;
+    const ADDRESS_BITS: u16 = BITS;
+}
 /// Fuller Joystick [PortAddress].
 #[derive(Clone, Copy, Default, Debug)]
 pub struct FullerJoyPortAddress;
@@ -201,6 +264,66 @@ impl<P, J, D> BusDevice for JoystickBusDevice<P, J, D>
     }
 }
 
+impl<P, J, D> Deref for AutoFireJoystickBusDevice<P, J, D> {
+    type Target = AutoFireJoystick<J>;
+    fn deref(&self) -> &Self::Target {
+        &self.joystick
+    }
+}
+
+impl<P, J, D> DerefMut for AutoFireJoystickBusDevice<P, J, D> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.joystick
+    }
+}
+
+impl<P, J, D> PassByAyAudioBusDevice for AutoFireJoystickBusDevice<P, J, D> {}
+
+impl<P, J, D> BusDevice for AutoFireJoystickBusDevice<P, J, D>
+    where P: PortAddress,
+          D: BusDevice,
+          J: JoystickDevice
+{
+    type Timestamp = D::Timestamp;
+    type NextDevice = D;
+
+    #[inline]
+    fn next_device_mut(&mut self) -> &mut Self::NextDevice {
+        &mut self.bus
+    }
+
+    #[inline]
+    fn next_device_ref(&self) -> &Self::NextDevice {
+        &self.bus
+    }
+
+    #[inline]
+    fn into_next_device(self) -> Self::NextDevice {
+        self.bus
+    }
+
+    #[inline]
+    fn read_io(&mut self, port: u16, timestamp: Self::Timestamp) -> Option<(u8, Option<NonZeroU16>)> {
+        let bus_data = self.bus.read_io(port, timestamp);
+        if P::match_port(port) {
+            let joy_data = self.joystick.port_read(port);
+            if let Some((data, ws)) = bus_data {
+                return Some((data & joy_data, ws))
+            }
+            return Some((joy_data, None))
+        }
+        bus_data
+    }
+
+    #[inline]
+    fn write_io(&mut self, port: u16, data: u8, timestamp: Self::Timestamp) -> Option<u16> {
+        if P::match_port(port) && self.joystick.port_write(port, data) {
+            return Some(0);
+        }
+        self.bus.write_io(port, data, timestamp)
+    }
+}
+
 /// A selectable joystick controller, providing a [BusDevice] implementation.
 ///
 /// This controller allows changing the implementation of the joystick device at run time.
@@ -537,6 +660,92 @@ impl<D> BusDevice for MultiJoystickBusDevice<D>
     }
 }
 
+/// The Sinclair ZX Interface 2 joystick ports, providing a [BusDevice] implementation.
+///
+/// The interface connects two joysticks to the keyboard matrix: the right joystick (Player 1) to the
+/// `6`-`0` keyboard half-row and the left joystick (Player 2) to the `1`-`5` half-row. Joystick movements
+/// are visible as pressed keys when the keyboard is read with the ULA port, also when more than one
+/// half-row is being scanned at once. Unlike [SinclairJoystick] no other ports are being decoded.
+#[derive(Clone, Copy, Default, Debug)]
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+pub struct SinclairInterface2<D> {
+    /// The right (Player 1) joystick mapped to keys `6`, `7`, `8`, `9` and `0`.
+    #[cfg_attr(feature = "snapshot", serde(skip))]
+    pub right: SinclairJoystickDevice<SinclairJoyRightMap>,
+    /// The left (Player 2) joystick mapped to keys `1`, `2`, `3`, `4` and `5`.
+    #[cfg_attr(feature = "snapshot", serde(skip))]
+    pub left: SinclairJoystickDevice<SinclairJoyLeftMap>,
+    #[cfg_attr(feature = "snapshot", serde(default))]
+    bus: D
+}
+
+impl<D> SinclairInterface2<D> {
+    /// The number of joysticks connected to the interface.
+    pub const JOYSTICKS_COUNT: usize = 2;
+    /// Provides a mutable reference to one of the joysticks via a dynamic trait.
+    ///
+    /// `index` 0 selects the right (Player 1) joystick and 1 the left (Player 2) one.
+    ///
+    /// Returns `None` if a joystick with the given index doesn't exist.
+    pub fn joystick_interface(&mut self, index: usize) -> Option<&mut (dyn JoystickInterface + 'static)> {
+        match index {
+            0 => Some(&mut self.right),
+            1 => Some(&mut self.left),
+            _ => None
+        }
+    }
+}
+
+impl<D> fmt::Display for SinclairInterface2<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ZX Interface 2")
+    }
+}
+
+impl<D> PassByAyAudioBusDevice for SinclairInterface2<D> {}
+
+impl<D> BusDevice for SinclairInterface2<D>
+    where D: BusDevice
+{
+    type Timestamp = D::Timestamp;
+    type NextDevice = D;
+
+    #[inline]
+    fn next_device_mut(&mut self) -> &mut Self::NextDevice {
+        &mut self.bus
+    }
+
+    #[inline]
+    fn next_device_ref(&self) -> &Self::NextDevice {
+        &self.bus
+    }
+
+    #[inline]
+    fn into_next_device(self) -> Self::NextDevice {
+        self.bus
+    }
+
+    #[inline]
+    fn read_io(&mut self, port: u16, timestamp: Self::Timestamp) -> Option<(u8, Option<NonZeroU16>)> {
+        let bus_data = self.bus.read_io(port, timestamp);
+        if port & 1 != 0 {
+            return bus_data
+        }
+        let mut joy_data = None;
+        if port & !SinclairRightJoyPortAddress::ADDRESS_BITS == 0 {
+            joy_data = Some(self.right.port_read(port));
+        }
+        if port & !SinclairLeftJoyPortAddress::ADDRESS_BITS == 0 {
+            joy_data = Some(joy_data.unwrap_or(!0) & self.left.port_read(port));
+        }
+        match (joy_data, bus_data) {
+            (Some(joy_data), Some((data, ws))) => Some((data & joy_data, ws)),
+            (Some(joy_data), None) => Some((joy_data, None)),
+            (None, bus_data) => bus_data
+        }
+    }
+}
+
 #[cfg(test)]
 #[cfg(feature = "snapshot")]
 mod tests {
@@ -564,6 +773,132 @@ mod tests {
             JoystickSelect::new_from_name("cursor").unwrap().0).to_string(), "Cursor Joystick");
     }
 
+    #[test]
+    fn autofire_joystick_bus_device_works() {
+        type TestJoystick = AutoFireKempstonJoystick<NullDevice<FTs>>;
+        const CPU_HZ: u32 = 3_500_000;
+        const FRAME_TSTATES: FTs = 69888;
+        let mut joy = TestJoystick::default();
+        assert_eq!(joy.to_string(), "Kempston auto-fire Joystick");
+        joy.set_autofire(25);
+        joy.fire(0, true);
+        joy.update_autofire_with_frame(100, CPU_HZ, FRAME_TSTATES);
+        assert_eq!(joy.read_io(0x001f, 0), Some((0b0001_0000, None)));
+        joy.update_autofire_with_frame(101, CPU_HZ, FRAME_TSTATES);
+        assert_eq!(joy.read_io(0x001f, 0), Some((0, None)));
+        assert_eq!(joy.read_io(0x003f, 0), None);
+        // only the auto-fire frequency is restored
+        let json = serde_json::to_string(&joy).unwrap();
+        let mut joy1: TestJoystick = serde_json::from_str(&json).unwrap();
+        assert_eq!(joy1.autofire(), 25);
+        assert!(!joy1.get_fire(0));
+        joy1.fire(0, true);
+        for frame in 102..106 {
+            joy.update_autofire_with_frame(frame, CPU_HZ, FRAME_TSTATES);
+            joy1.update_autofire_with_frame(frame, CPU_HZ, FRAME_TSTATES);
+            assert_eq!(joy1.read_io(0x001f, 0), joy.read_io(0x001f, 0));
+        }
+    }
+
+    #[test]
+    fn fuller_joystick_standalone() {
+        use crate::joystick::Directions;
+        let mut fuller = FullerJoystick::<NullDevice<FTs>>::default();
+        assert_eq!(fuller.read_io(0x007f, 0), Some((0xff, None)));
+        fuller.set_directions(Directions::DOWN|Directions::LEFT);
+        assert_eq!(fuller.read_io(0x007f, 0), Some((0b1111_1001, None)));
+        fuller.fire(0, true);
+        assert_eq!(fuller.read_io(0xfe7f, 0), Some((0b0111_1001, None)));
+        fuller.center();
+        assert_eq!(fuller.read_io(0x007f, 0), Some((0b0111_1111, None)));
+        // no AY ports are decoded
+        assert_eq!(fuller.read_io(0x003f, 0), None);
+        assert_eq!(fuller.write_io(0x003f, 7, 0), None);
+        assert_eq!(fuller.write_io(0x005f, 0, 0), None);
+    }
+
+    #[test]
+    fn joystick_chain_read_io() {
+        use spectrusty_core::bus::OptionalBusDevice;
+        use crate::joystick::Directions;
+        let mut chain = KempstonJoystick::<FullerJoystick<NullDevice<FTs>>>::default();
+        // the Kempston joystick is active high, the Fuller joystick is active low
+        chain.set_directions(Directions::RIGHT);
+        chain.next_device_mut().set_directions(Directions::DOWN);
+        assert_eq!(chain.read_io(0x001f, 0), Some((0b0000_0001, None)));
+        // the Kempston joystick doesn't respond, so it doesn't force the Fuller bits low
+        assert_eq!(chain.read_io(0x007f, 0), Some((0b1111_1101, None)));
+        // no device responds
+        assert_eq!(chain.read_io(0x00fe, 0), None);
+        // the same with the device being plugged in at run time
+        let mut chain = OptionalBusDevice::new(
+            Some(KempstonJoystick::<NullDevice<FTs>>::default()),
+            FullerJoystick::<NullDevice<FTs>>::default());
+        chain.as_mut().unwrap().set_directions(Directions::RIGHT);
+        chain.next_device_mut().set_directions(Directions::DOWN);
+        assert_eq!(chain.read_io(0x001f, 0), Some((0b0000_0001, None)));
+        assert_eq!(chain.read_io(0x007f, 0), Some((0b1111_1101, None)));
+        assert_eq!(chain.read_io(0x00fe, 0), None);
+        chain.device = None;
+        assert_eq!(chain.read_io(0x001f, 0), None);
+        assert_eq!(chain.read_io(0x007f, 0), Some((0b1111_1101, None)));
+    }
+
+    #[test]
+    fn sinclair_interface2_works() {
+        use crate::joystick::Directions;
+        let mut if2 = SinclairInterface2::<NullDevice<FTs>>::default();
+        assert_eq!(if2.to_string(), "ZX Interface 2");
+        for port in [0xeffe, 0xf7fe, 0x00fe, 0xfefe, 0x001f, 0xefff] {
+            assert_eq!(if2.read_io(port, 0), if port & 1 == 0 && port & 0x1800 != 0x1800 { Some((0xff, None)) } else { None });
+        }
+        // the right joystick: 9 (up)
+        if2.joystick_interface(0).unwrap().set_directions(Directions::UP);
+        assert_eq!(if2.read_io(0xeffe, 0), Some((0b1111_1101, None)));
+        assert_eq!(if2.read_io(0xf7fe, 0), Some((0b1111_1111, None)));
+        // the left joystick: 2 (right), 5 (fire)
+        if2.left.set_directions(Directions::RIGHT);
+        if2.joystick_interface(1).unwrap().fire(0, true);
+        assert_eq!(if2.read_io(0xeffe, 0), Some((0b1111_1101, None)));
+        assert_eq!(if2.read_io(0xf7fe, 0), Some((0b1110_1101, None)));
+        // both half-rows scanned at once
+        assert_eq!(if2.read_io(0xe7fe, 0), Some((0b1110_1101, None)));
+        assert_eq!(if2.read_io(0x00fe, 0), Some((0b1110_1101, None)));
+        // other half-rows and ports are not affected
+        assert_eq!(if2.read_io(0xfbfe, 0), None);
+        assert_eq!(if2.read_io(0xeeff, 0), None);
+        assert_eq!(if2.read_io(0x001f, 0), None);
+        assert!(if2.joystick_interface(2).is_none());
+    }
+
+    #[test]
+    fn kempston_joystick_custom_port() {
+        use crate::joystick::Directions;
+        let mut joy = KempstonJoystick::<NullDevice<FTs>>::default();
+        joy.set_directions(Directions::UP);
+        // partially decoded
+        for port in [0x001f, 0x00df, 0xff1f, 0x0000] {
+            assert_eq!(joy.read_io(port, 0), Some((0b0000_1000, None)));
+        }
+        let mut joy = KempstonJoystick::<NullDevice<FTs>, KempstonAltJoyPortAddress>::default();
+        assert_eq!(joy.to_string(), "Kempston Joystick");
+        joy.set_directions(Directions::UP);
+        assert_eq!(joy.read_io(0x00df, 0), Some((0b0000_1000, None)));
+        assert_eq!(joy.read_io(0xfedf, 0), Some((0b0000_1000, None)));
+        // the standard port is not decoded
+        assert_eq!(joy.read_io(0x001f, 0), None);
+        assert_eq!(joy.read_io(0x005f, 0), None);
+        let mut joy = KempstonJoystick::<NullDevice<FTs>, CustomJoyPortAddress<0xffff, 0x7f1f>>::default();
+        joy.set_directions(Directions::LEFT);
+        assert_eq!(joy.read_io(0x7f1f, 0), Some((0b0000_0010, None)));
+        assert_eq!(joy.read_io(0x001f, 0), None);
+        assert_eq!(joy.read_io(0xff1f, 0), None);
+        let mut joy = KempstonJoystick::<NullDevice<FTs>, KempstonFullJoyPortAddress>::default();
+        joy.set_directions(Directions::RIGHT);
+        assert_eq!(joy.read_io(0x001f, 0), Some((0b0000_0001, None)));
+        assert_eq!(joy.read_io(0x00df, 0), None);
+    }
+
     #[test]
     fn joystick_select_snapshot() {
         let (joy, len) = JoystickSelect::new_from_name("Sinclair").unwrap();
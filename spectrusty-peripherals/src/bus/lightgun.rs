@@ -0,0 +1,333 @@
+/*
+    Copyright (C) 2020-2023  Rafal Michalski
+
+    This file is part of SPECTRUSTY, a Rust library for building emulators.
+
+    For the full copyright notice, see the lib.rs file.
+*/
+//! A bus device for connecting light guns.
+//!
+//! The light gun sensor detects the light emitted by the screen when the video beam passes
+//! the point the gun is aimed at. The game software polls the light sensor bit while the screen is
+//! being drawn and determines the aim from the timing of the detection.
+use core::num::NonZeroU16;
+use core::fmt;
+use core::marker::PhantomData;
+
+#[cfg(feature = "snapshot")]
+use serde::{Serialize, Deserialize};
+
+use spectrusty_core::{
+    bus::{BusDevice, PortAddress},
+    clock::{Ts, VFrameTs, VideoTs},
+    memory::ScreenArray,
+    video::{VideoFrame, pixel_line_offset, color_line_offset}
+};
+
+use super::ay::PassByAyAudioBusDevice;
+use super::joystick::SinclairRightJoyPortAddress;
+
+/// The default bit of the port data which is reset while the trigger is being pressed: the fire
+/// button (key `0`) of the [Sinclair #1][SinclairRightJoyPortAddress] joystick.
+pub const DEFAULT_TRIGGER_MASK: u8 = 0b0000_0001;
+/// The default bit of the port data which is reset while the light is being detected: the down
+/// direction (key `8`) of the [Sinclair #1][SinclairRightJoyPortAddress] joystick.
+pub const DEFAULT_LIGHT_MASK: u8 = 0b0000_0100;
+/// The number of horizontal T-states the light is being detected for after the beam passes the aimed point.
+pub const LIGHT_PULSE_HTS: Ts = 16;
+/// The lowest color index (`0` - black, `7` - white) of a pixel bright enough to be detected by the sensor.
+pub const LIGHT_COLOR_THRESHOLD: u8 = 4;
+
+/// The Magnum Light Phaser plugged into the joystick port `1` of the ZX Spectrum +2.
+///
+/// The trigger is being read as the fire button and the light sensor as the down direction of
+/// the [Sinclair #1][SinclairRightJoyPortAddress] joystick.
+pub type MagnumLightPhaser<D> = LightGun<D, SinclairRightJoyPortAddress>;
+
+/// A generic light gun [BusDevice].
+///
+/// The light gun reports the state of its trigger and light sensor as active low bits of the data
+/// read from the port matched by `P`, the [Sinclair #1][SinclairRightJoyPortAddress] joystick port
+/// by default. The bits are [DEFAULT_TRIGGER_MASK] and [DEFAULT_LIGHT_MASK] by default, the same as
+/// of the [MagnumLightPhaser], and can be changed with [LightGun::set_masks].
+///
+/// The front-end provides the screen coordinates of the aim with [LightGun::set_aim].
+/// The light is being sampled from the screen memory provided by the control unit with
+/// [BusDevice::probe_screen] just before each port read, so the changes of the screen data during
+/// the frame are being detected.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+pub struct LightGun<D, P=SinclairRightJoyPortAddress> {
+    #[cfg_attr(feature = "snapshot", serde(default = "trigger_mask_default"))]
+    trigger_mask: u8,
+    #[cfg_attr(feature = "snapshot", serde(default = "light_mask_default"))]
+    light_mask: u8,
+    #[cfg_attr(feature = "snapshot", serde(skip))]
+    aim: Option<(u8, u8)>,
+    #[cfg_attr(feature = "snapshot", serde(skip))]
+    trigger: bool,
+    #[cfg_attr(feature = "snapshot", serde(skip))]
+    light: bool,
+    #[cfg_attr(feature = "snapshot", serde(default))]
+    bus: D,
+    #[cfg_attr(feature = "snapshot", serde(skip))]
+    _port_decode: PhantomData<P>,
+}
+
+#[cfg(feature = "snapshot")]
+fn trigger_mask_default() -> u8 {
+    DEFAULT_TRIGGER_MASK
+}
+
+#[cfg(feature = "snapshot")]
+fn light_mask_default() -> u8 {
+    DEFAULT_LIGHT_MASK
+}
+
+impl<D: Default, P> Default for LightGun<D, P> {
+    fn default() -> Self {
+        LightGun {
+            trigger_mask: DEFAULT_TRIGGER_MASK,
+            light_mask: DEFAULT_LIGHT_MASK,
+            aim: None,
+            trigger: false,
+            light: false,
+            bus: D::default(),
+            _port_decode: PhantomData
+        }
+    }
+}
+
+impl<D, P> fmt::Display for LightGun<D, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Light Gun")
+    }
+}
+
+/// Returns `true` if the pixel of the `screen` at the given coordinates is bright enough to be detected
+/// by the light sensor.
+///
+/// `x` is in the range: [0, 255] and `y` in the range: [0, 191], (0, 0) is the top left corner of the screen.
+/// The flashing attributes are not taken into account.
+///
+/// # Panics
+/// Panics if `y` is out of range.
+pub fn is_screen_pixel_lit(screen: &ScreenArray, x: u8, y: u8) -> bool {
+    assert!(y < 192, "is_screen_pixel_lit: y coordinate out of range");
+    let (x, y) = (u16::from(x), u16::from(y));
+    let col = x >> 3;
+    let ink = screen[usize::from(pixel_line_offset(y) + col)] & (0x80 >> (x & 7)) != 0;
+    let attr = screen[usize::from(0x1800 + color_line_offset(y) + col)];
+    let color = if ink { attr & 7 } else { (attr >> 3) & 7 };
+    color >= LIGHT_COLOR_THRESHOLD
+}
+
+impl<D, P> LightGun<D, P> {
+    /// Sets the bits of the port data which are reset while the trigger is being pressed and
+    /// while the light is being detected.
+    pub fn set_masks(&mut self, trigger_mask: u8, light_mask: u8) {
+        self.trigger_mask = trigger_mask;
+        self.light_mask = light_mask;
+    }
+    /// Returns the trigger and the light bit masks of the port data.
+    pub fn masks(&self) -> (u8, u8) {
+        (self.trigger_mask, self.light_mask)
+    }
+    /// Sets the screen coordinates the gun is aimed at or `None` if it's not aimed at the screen.
+    ///
+    /// `x` is in the range: [0, 255] and `y` in the range: [0, 191], (0, 0) is the top left corner
+    /// of the screen (excluding the border). Coordinates with `y` out of range are treated as `None`.
+    pub fn set_aim(&mut self, aim: Option<(u8, u8)>) {
+        self.aim = aim.filter(|&(_, y)| y < 192);
+        if self.aim.is_none() {
+            self.light = false;
+        }
+    }
+    /// Returns the screen coordinates the gun is aimed at.
+    pub fn aim(&self) -> Option<(u8, u8)> {
+        self.aim
+    }
+    /// Sets the state of the trigger.
+    pub fn set_trigger(&mut self, pressed: bool) {
+        self.trigger = pressed;
+    }
+    /// Returns `true` if the trigger is being pressed.
+    pub fn is_trigger_pressed(&self) -> bool {
+        self.trigger
+    }
+    /// Returns `true` if the light was detected at the last [BusDevice::probe_screen].
+    pub fn is_light_detected(&self) -> bool {
+        self.light
+    }
+    /// Returns `true` if the video beam is passing the aimed point at the given video timestamp.
+    pub fn is_beam_at_aim<V: VideoFrame>(&self, VideoTs { vc, hc }: VideoTs) -> bool {
+        match self.aim {
+            Some((x, y)) => {
+                let aim_hc = Ts::from(x) >> 1;
+                vc == V::VSL_PIXELS.start + Ts::from(y) &&
+                    (aim_hc..aim_hc + LIGHT_PULSE_HTS).contains(&hc)
+            }
+            None => false
+        }
+    }
+}
+
+impl<D, P> PassByAyAudioBusDevice for LightGun<D, P> {}
+
+impl<V, D, P> BusDevice for LightGun<D, P>
+    where V: VideoFrame,
+          P: PortAddress,
+          D: BusDevice<Timestamp=VFrameTs<V>>
+{
+    type Timestamp = VFrameTs<V>;
+    type NextDevice = D;
+
+    #[inline]
+    fn next_device_mut(&mut self) -> &mut Self::NextDevice {
+        &mut self.bus
+    }
+
+    #[inline]
+    fn next_device_ref(&self) -> &Self::NextDevice {
+        &self.bus
+    }
+
+    #[inline]
+    fn into_next_device(self) -> Self::NextDevice {
+        self.bus
+    }
+
+    #[inline]
+    fn probe_screen(&mut self, screen: &ScreenArray, timestamp: Self::Timestamp) {
+        self.light = match self.aim {
+            Some((x, y)) if self.is_beam_at_aim::<V>(timestamp.into()) => is_screen_pixel_lit(screen, x, y),
+            _ => false
+        };
+        self.bus.probe_screen(screen, timestamp)
+    }
+
+    #[inline]
+    fn read_io(&mut self, port: u16, timestamp: Self::Timestamp) -> Option<(u8, Option<NonZeroU16>)> {
+        let bus_data = self.bus.read_io(port, timestamp);
+        if P::match_port(port) {
+            let mut gun_data = !0;
+            if self.trigger {
+                gun_data &= !self.trigger_mask;
+            }
+            if self.light {
+                gun_data &= !self.light_mask;
+            }
+            if let Some((data, ws)) = bus_data {
+                return Some((data & gun_data, ws))
+            }
+            return Some((gun_data, None))
+        }
+        bus_data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use spectrusty_core::bus::NullDevice;
+    use spectrusty_core::video::{BorderSize, VideoFrame};
+    use super::*;
+
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+    struct TestVideoFrame;
+
+    impl VideoFrame for TestVideoFrame {
+        const HTS_RANGE: core::ops::Range<Ts> = -69..155;
+        const VSL_BORDER_TOP: Ts = 16;
+        const VSL_PIXELS: core::ops::Range<Ts> = 64..256;
+        const VSL_BORDER_BOT: Ts = 304;
+        const VSL_COUNT: Ts = 312;
+        type BorderHtsIter = core::iter::StepBy<core::ops::Range<Ts>>;
+        fn border_whole_line_hts_iter(_border_size: BorderSize) -> Self::BorderHtsIter {
+            (0..0).step_by(4)
+        }
+        fn border_left_hts_iter(_border_size: BorderSize) -> Self::BorderHtsIter {
+            (0..0).step_by(4)
+        }
+        fn border_right_hts_iter(_border_size: BorderSize) -> Self::BorderHtsIter {
+            (0..0).step_by(4)
+        }
+        fn contention(hc: Ts) -> Ts { hc }
+    }
+
+    type TestLightGun = LightGun<NullDevice<VFrameTs<TestVideoFrame>>>;
+
+    fn ts(vc: Ts, hc: Ts) -> VFrameTs<TestVideoFrame> {
+        VideoTs::new(vc, hc).into()
+    }
+
+    fn read_gun(gun: &mut TestLightGun, screen: &ScreenArray, port: u16, ts: VFrameTs<TestVideoFrame>) -> Option<(u8, Option<NonZeroU16>)> {
+        gun.probe_screen(screen, ts);
+        gun.read_io(port, ts)
+    }
+
+    #[test]
+    fn light_gun_works() {
+        let mut gun = TestLightGun::default();
+        assert_eq!(gun.to_string(), "Light Gun");
+        assert_eq!(gun.masks(), (DEFAULT_TRIGGER_MASK, DEFAULT_LIGHT_MASK));
+        let mut screen: ScreenArray = [0; 6912];
+        // the cell at column 4 and row 10 is white on black with the upper half of pixels lit
+        for y in 80..88u16 {
+            screen[usize::from(pixel_line_offset(y) + 4)] = 0b1111_0000;
+        }
+        screen[0x1800 + 10 * 32 + 4] = 0b00_000_111;
+        assert!(is_screen_pixel_lit(&screen, 32, 83));
+        assert!(is_screen_pixel_lit(&screen, 35, 83));
+        assert!(!is_screen_pixel_lit(&screen, 36, 83));
+        assert!(!is_screen_pixel_lit(&screen, 32, 88));
+        // not aimed at the screen
+        assert_eq!(read_gun(&mut gun, &screen, 0xeffe, ts(64 + 83, 17)), Some((0xff, None)));
+        assert!(!gun.is_light_detected());
+        // the bits are only present on the matching port
+        assert_eq!(read_gun(&mut gun, &screen, 0xfffe, ts(64 + 83, 17)), None);
+        gun.set_trigger(true);
+        assert_eq!(read_gun(&mut gun, &screen, 0xeffe, ts(0, 0)), Some((!DEFAULT_TRIGGER_MASK, None)));
+        gun.set_trigger(false);
+        // aimed at the lit pixel
+        gun.set_aim(Some((34, 83)));
+        for vc in 0..TestVideoFrame::VSL_COUNT {
+            for hc in TestVideoFrame::HTS_RANGE {
+                let (data, _) = read_gun(&mut gun, &screen, 0xeffe, ts(vc, hc)).unwrap();
+                let expected = vc == 64 + 83 && (17..17 + LIGHT_PULSE_HTS).contains(&hc);
+                assert_eq!(gun.is_beam_at_aim::<TestVideoFrame>(VideoTs::new(vc, hc)), expected);
+                assert_eq!(gun.is_light_detected(), expected);
+                assert_eq!(data & DEFAULT_LIGHT_MASK == 0, expected, "vc: {} hc: {}", vc, hc);
+                assert_eq!(data | DEFAULT_LIGHT_MASK, 0xff);
+            }
+        }
+        // the screen data is sampled at the time of reading
+        screen[0x1800 + 10 * 32 + 4] = 0b00_111_000;
+        assert_eq!(read_gun(&mut gun, &screen, 0xeffe, ts(64 + 83, 18)), Some((0xff, None)));
+        screen[0x1800 + 10 * 32 + 4] = 0b00_000_111;
+        assert_eq!(read_gun(&mut gun, &screen, 0xeffe, ts(64 + 83, 18)), Some((!DEFAULT_LIGHT_MASK, None)));
+        // aimed at the dark pixel
+        gun.set_aim(Some((36, 83)));
+        assert_eq!(read_gun(&mut gun, &screen, 0xeffe, ts(64 + 83, 18)), Some((0xff, None)));
+        // the aim out of the screen
+        gun.set_aim(Some((34, 192)));
+        assert_eq!(gun.aim(), None);
+        // the configured bits
+        gun.set_masks(0b0001_0000, 0b0000_0010);
+        gun.set_trigger(true);
+        gun.set_aim(Some((34, 83)));
+        assert_eq!(read_gun(&mut gun, &screen, 0xeffe, ts(64 + 83, 18)), Some((!0b0001_0010, None)));
+    }
+
+    #[test]
+    fn magnum_light_phaser_works() {
+        let mut gun = MagnumLightPhaser::<NullDevice<VFrameTs<TestVideoFrame>>>::default();
+        let mut screen: ScreenArray = [0; 6912];
+        screen[usize::from(pixel_line_offset(0u16))] = 0xff;
+        screen[0x1800] = 0b00_000_111;
+        gun.set_aim(Some((0, 0)));
+        gun.set_trigger(true);
+        assert!(SinclairRightJoyPortAddress::match_port(0xeffe));
+        assert_eq!(read_gun(&mut gun, &screen, 0xeffe, ts(64, 0)), Some((0b1111_1010, None)));
+        assert_eq!(read_gun(&mut gun, &screen, 0xeffe, ts(65, 0)), Some((0b1111_1110, None)));
+    }
+}
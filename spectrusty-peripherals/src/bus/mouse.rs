@@ -51,10 +51,16 @@ pub struct MouseBusDevice<P, M, D> {
 }
 
 /// Kempston Mouse [PortAddress].
+///
+/// The mouse is being selected when A5 bit of the port address is 0 and A7 is 1, so it responds to
+/// the ports `0xFADF`, `0xFBDF` and `0xFFDF`, but not to the Kempston Joystick port `0x1F`.
+/// To attach both devices, use the Kempston Joystick with [KempstonMouseJoyPortAddress].
+///
+/// [KempstonMouseJoyPortAddress]: super::joystick::KempstonMouseJoyPortAddress
 #[derive(Clone, Copy, Default, Debug)]
 pub struct KempstonMousePortAddress;
 impl PortAddress for KempstonMousePortAddress {
-    const ADDRESS_MASK: u16 = 0b0000_0000_0010_0000;
+    const ADDRESS_MASK: u16 = 0b0000_0000_1010_0000;
     const ADDRESS_BITS: u16 = 0b1111_1010_1101_1111;
 }
 
@@ -117,3 +123,39 @@ impl<P, M, D> BusDevice for MouseBusDevice<P, M, D>
         self.bus.write_io(port, data, timestamp)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use spectrusty_core::{bus::NullDevice, clock::FTs};
+    use crate::bus::joystick::{KempstonJoystick, KempstonMouseJoyPortAddress};
+    use crate::joystick::{Directions, JoystickInterface};
+    use crate::mouse::{MouseButtons, MouseMovement};
+    use super::*;
+
+    #[test]
+    fn kempston_mouse_with_joystick_works() {
+        let mut mouse = KempstonMouse::<
+                            KempstonJoystick<NullDevice<FTs>, KempstonMouseJoyPortAddress>
+                        >::default();
+        assert_eq!(mouse.to_string(), "Kempston Mouse");
+        assert_eq!(mouse.read_io(0x001f, 0), Some((0, None)));
+        assert_eq!(mouse.read_io(0xfadf, 0), Some((0xff, None)));
+        assert_eq!(mouse.read_io(0xfbdf, 0), Some((0xff, None)));
+        assert_eq!(mouse.read_io(0xffdf, 0), Some((0xff, None)));
+        mouse.next_device_mut().joystick.set_directions(Directions::UP);
+        mouse.next_device_mut().joystick.fire(0, true);
+        mouse.set_buttons(MouseButtons::LEFT);
+        mouse.move_mouse(MouseMovement { horizontal: 20, vertical: 0 });
+        mouse.move_mouse(MouseMovement { horizontal: 0, vertical: -40 });
+        // the joystick alone on its port
+        assert_eq!(mouse.read_io(0x001f, 0), Some((0b0001_1000, None)));
+        assert_eq!(mouse.read_io(0xff1f, 0), Some((0b0001_1000, None)));
+        // the mouse alone on its ports
+        assert_eq!(mouse.read_io(0xfadf, 0), Some((0b1111_1101, None)));
+        assert_eq!(mouse.read_io(0xfbdf, 0), Some((9, None)));
+        assert_eq!(mouse.read_io(0xffdf, 0), Some((19, None)));
+        // none of the devices
+        assert_eq!(mouse.read_io(0x003f, 0), None);
+        assert_eq!(mouse.read_io(0x00fe, 0), None);
+    }
+}
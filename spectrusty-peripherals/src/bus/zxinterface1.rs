@@ -54,6 +54,7 @@ the lowest written bit is *rxdata*.
 use core::num::NonZeroU16;
 use core::fmt;
 use std::io;
+use std::path::PathBuf;
 
 #[cfg(feature = "snapshot")]
 use serde::{Serialize, Deserialize};
@@ -61,7 +62,9 @@ use serde::{Serialize, Deserialize};
 use spectrusty_core::{
     bitflags_masks,
     clock::TimestampOps,
-    bus::BusDevice
+    bus::{BusDevice, RomTrapAccess, CpuMemoryTrapAccess},
+    memory::ZxMemory,
+    z80emu::{Cpu, Prefix}
 };
 
 use super::ay::PassByAyAudioBusDevice;
@@ -105,12 +108,14 @@ pub struct ZxInterface1BusDevice<R, W, N, D: BusDevice>
     #[cfg_attr(feature = "snapshot", serde(default))]
     pub serial: Rs232Io<D::Timestamp, R, W>,
     /// Direct access to the **ZX NET** implementation.
-    #[cfg_attr(feature = "snapshot", serde(skip))]
+    #[cfg_attr(feature = "snapshot", serde(default))]
     pub network: ZxNet<D::Timestamp, N>,
     sernet: If1SerNetIo,
     ctrl_in: If1ControlIn,
     ctrl_out: If1ControlOut,
     #[cfg_attr(feature = "snapshot", serde(default))]
+    instant_microdrive: bool,
+    #[cfg_attr(feature = "snapshot", serde(default))]
     bus: D
 }
 
@@ -285,6 +290,203 @@ const IF1_SERN_BITS: u16 = 0b0000_0000_0001_0000;
 const IF1_CTRL_BITS: u16 = 0b0000_0000_0000_1000;
 const IF1_DATA_BITS: u16 = 0b0000_0000_0000_0000;
 
+/// The hook code of the ZX Interface 1 ROM reading the sector with the number `CHREC` into the channel.
+pub const HOOK_RD_SECTOR: u8 = 0x28;
+/// The hook code of the ZX Interface 1 ROM reading the next sector into the channel.
+pub const HOOK_RD_NEXT: u8 = 0x29;
+/// The hook code of the ZX Interface 1 ROM writing the channel data block to the sector with the number `CHREC`.
+pub const HOOK_WR_SECTOR: u8 = 0x2A;
+
+// offsets of the microdrive channel fields
+const CHREC:  u16 = 0x0D;
+const CHDRIV: u16 = 0x19;
+const HDFLAG: u16 = 0x28;
+const RECFLG: u16 = 0x43;
+
+/// A microdrive sector hook code call detected by [InstantMicrodriveHook::detect].
+///
+/// The hook is being serviced by [ZxInterface1BusDevice::service_instant_microdrive_hook] and completed
+/// with [InstantMicrodriveHook::complete]:
+///
+/// ```text
+/// if let Some(mut hook) = InstantMicrodriveHook::detect(&cpu, ula.memory_ref()) {
+///     if ula.bus_device_mut().service_instant_microdrive_hook(&mut hook) {
+///         hook.complete(&mut cpu, ula.memory_mut());
+///     }
+/// }
+/// ```
+///
+/// While the instant microdrive mode is enabled, the control units do the above before each instruction
+/// via [BusDevice::rom_trap], so this is only needed when the execution is controlled otherwise.
+#[derive(Clone, Copy)]
+pub struct InstantMicrodriveHook {
+    code: u8,
+    drive_index: usize,
+    number: u8,
+    channel: u16,
+    ret: u16,
+    sector: Sector
+}
+
+impl fmt::Debug for InstantMicrodriveHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InstantMicrodriveHook")
+            .field("code", &self.code)
+            .field("drive_index", &self.drive_index)
+            .field("number", &self.number)
+            .field("channel", &self.channel)
+            .finish()
+    }
+}
+
+impl InstantMicrodriveHook {
+    /// Detects if the ZX Interface 1 hook code for reading or writing a microdrive sector is being called.
+    ///
+    /// The hook code is detected when the `cpu` is about to execute the instruction at `0x0008` (`RST 8`)
+    /// and the byte following the `RST 8` instruction, addressed by the return address on the machine stack,
+    /// is one of [HOOK_RD_SECTOR], [HOOK_RD_NEXT] or [HOOK_WR_SECTOR]. The `IX` register must point to the
+    /// microdrive channel, which determines the drive number (`CHDRIV`) and the sector number (`CHREC`).
+    /// This method should be called before each instruction at `0x0008`, e.g. on a breakpoint.
+    ///
+    /// Neither the state of the `cpu` nor the `memory` is being modified.
+    pub fn detect<C: Cpu, M: ZxMemory>(cpu: &C, memory: &M) -> Option<Self> {
+        Self::detect_with(cpu.get_pc(), cpu.get_sp(), cpu.get_index16(Prefix::Xdd), |addr| memory.read(addr))
+    }
+    /// Detects the hook code call the same way as [InstantMicrodriveHook::detect] with the given `access`.
+    pub fn detect_with_access(access: &dyn RomTrapAccess) -> Option<Self> {
+        Self::detect_with(access.get_pc(), access.get_sp(), access.get_index16(Prefix::Xdd),
+                          |addr| access.read_mem(addr))
+    }
+
+    fn detect_with<F: Fn(u16) -> u8>(pc: u16, sp: u16, channel: u16, read: F) -> Option<Self> {
+        if pc != 0x0008 {
+            return None
+        }
+        let ret = u16::from_le_bytes([read(sp), read(sp.wrapping_add(1))]);
+        let code = read(ret);
+        if !matches!(code, HOOK_RD_SECTOR|HOOK_RD_NEXT|HOOK_WR_SECTOR) {
+            return None
+        }
+        let drive_index = match read(channel.wrapping_add(CHDRIV)) {
+            drive @ 1..=8 => drive as usize - 1,
+            _ => return None
+        };
+        let number = read(channel.wrapping_add(CHREC));
+        let mut sector = Sector::default();
+        if code == HOOK_WR_SECTOR {
+            for (offset, p) in (RECFLG..).zip(sector.data.iter_mut()) {
+                *p = read(channel.wrapping_add(offset));
+            }
+        }
+        Some(InstantMicrodriveHook { code, drive_index, number, channel, ret, sector })
+    }
+    /// Returns the detected hook code.
+    pub fn code(&self) -> u8 {
+        self.code
+    }
+    /// Returns the index of the drive from 0 to 7.
+    pub fn drive_index(&self) -> usize {
+        self.drive_index
+    }
+    /// Completes the serviced hook call.
+    ///
+    /// When reading, the sector header and data block are copied into the channel. The `cpu` returns
+    /// to the address following the hook code byte, as if the ROM routine has finished.
+    pub fn complete<C: Cpu, M: ZxMemory>(self, cpu: &mut C, memory: &mut M) {
+        self.complete_with_access(&mut CpuMemoryTrapAccess { cpu, memory })
+    }
+    /// Completes the serviced hook call the same way as [InstantMicrodriveHook::complete] with the given `access`.
+    pub fn complete_with_access(self, access: &mut dyn RomTrapAccess) {
+        let InstantMicrodriveHook { code, channel, ret, sector, .. } = self;
+        if code != HOOK_WR_SECTOR {
+            for (offset, &data) in (HDFLAG..).zip(sector.head.iter()) {
+                access.write_mem(channel.wrapping_add(offset), data);
+            }
+            for (offset, &data) in (RECFLG..).zip(sector.data.iter()) {
+                access.write_mem(channel.wrapping_add(offset), data);
+            }
+        }
+        access.set_sp(access.get_sp().wrapping_add(2));
+        access.set_pc(ret.wrapping_add(1));
+    }
+}
+
+impl<R, W, N, D: BusDevice> ZxInterface1BusDevice<R, W, N, D> {
+    /// Returns `true` if the instant microdrive mode is enabled.
+    pub fn is_instant_microdrive(&self) -> bool {
+        self.instant_microdrive
+    }
+    /// Enables or disables the instant microdrive mode.
+    ///
+    /// When enabled, [ZxInterface1BusDevice::service_instant_microdrive_hook] services the sector read and
+    /// write hook codes directly against the [MicroCartridge] data, bypassing the tape timing.
+    ///
+    /// The control units trap the hook code calls with [BusDevice::rom_trap] while the mode is enabled,
+    /// so the instant microdrive also works with [ControlUnit::execute_next_frame].
+    ///
+    /// [ControlUnit::execute_next_frame]: spectrusty_core::chip::ControlUnit::execute_next_frame
+    pub fn set_instant_microdrive(&mut self, enabled: bool) {
+        self.instant_microdrive = enabled
+    }
+    /// Services the detected microdrive sector `hook` call instantly, provided the instant microdrive
+    /// mode is enabled.
+    ///
+    /// Returns `true` if the `hook` has been serviced and should be [completed][InstantMicrodriveHook::complete].
+    /// Otherwise the mode is disabled or the request could not be serviced (no cartridge, no matching sector,
+    /// or the cartridge is write protected), and the ROM routine should proceed normally.
+    pub fn service_instant_microdrive_hook(&mut self, hook: &mut InstantMicrodriveHook) -> bool {
+        if !self.instant_microdrive {
+            return false
+        }
+        match hook.code {
+            HOOK_WR_SECTOR => {
+                self.microdrives.instant_write_sector(hook.drive_index, hook.number, &hook.sector.data)
+            }
+            code => {
+                let number = if code == HOOK_RD_SECTOR { Some(hook.number) } else { None };
+                match self.microdrives.instant_read_sector(hook.drive_index, number) {
+                    Some(sector) => {
+                        hook.sector = *sector;
+                        true
+                    }
+                    None => false
+                }
+            }
+        }
+    }
+}
+
+impl<R, W, N, D> ZxInterface1BusDevice<R, W, N, D>
+    where N: ZxNetSocket,
+          D: BusDevice
+{
+    /// Opens a network channel to the `dest` station, the equivalent of `OPEN #s;"n";dest`.
+    ///
+    /// See [ZxNet::open_channel].
+    pub fn net_open_channel(&self, dest: u8) -> NetChannel {
+        self.network.open_channel(dest)
+    }
+    /// Sends a data record over the network `channel` without the emulated Spectrum.
+    ///
+    /// See [ZxNet::send_record].
+    pub fn net_send_record(&mut self, channel: &mut NetChannel, data: &[u8], eof: bool) -> bool {
+        self.network.send_record(channel, data, eof)
+    }
+    /// Receives a data record from the network `channel` without the emulated Spectrum.
+    ///
+    /// See [ZxNet::recv_record].
+    pub fn net_recv_record(&mut self, channel: &mut NetChannel) -> Option<NetRecord> {
+        self.network.recv_record(channel)
+    }
+    /// Enables the network file server mode serving files from the given host directory or disables it
+    /// if `path` is `None`.
+    ///
+    /// See [ZxNet::set_net_fileserver].
+    pub fn set_net_fileserver(&mut self, path: Option<PathBuf>) {
+        self.network.set_net_fileserver(path)
+    }
+}
+
 impl<R, W, N, D: BusDevice> PassByAyAudioBusDevice for ZxInterface1BusDevice<R, W, N, D> {}
 
 impl<R, W, N, D> BusDevice for ZxInterface1BusDevice<R, W, N, D>
@@ -324,6 +526,24 @@ impl<R, W, N, D> BusDevice for ZxInterface1BusDevice<R, W, N, D>
         self.ctrl_in.set_dtr(self.serial.poll_ready(timestamp));
     }
 
+    #[inline]
+    fn has_rom_traps(&self) -> bool {
+        self.instant_microdrive || self.bus.has_rom_traps()
+    }
+
+    #[inline]
+    fn rom_trap(&mut self, access: &mut dyn RomTrapAccess) -> bool {
+        if self.instant_microdrive {
+            if let Some(mut hook) = InstantMicrodriveHook::detect_with_access(access) {
+                if self.service_instant_microdrive_hook(&mut hook) {
+                    hook.complete_with_access(access);
+                    return true
+                }
+            }
+        }
+        self.bus.rom_trap(access)
+    }
+
     #[inline]
     fn next_frame(&mut self, eof_timestamp: Self::Timestamp) {
         // println!("frame ends");
@@ -401,6 +621,107 @@ mod tests {
     use super::*;
     use spectrusty_core::test_bitflags_all_bits_defined_no_masks;
 
+    use std::net::UdpSocket;
+    use std::time::Duration;
+    use std::thread;
+    use spectrusty_core::{bus::NullDevice, clock::FTs};
+    use crate::network::zxnet::tests::TestSocket;
+
+    type TestInterface1 = ZxInterface1BusDevice<io::Empty, io::Sink, TestSocket, NullDevice<FTs>>;
+    type TestUdpInterface1 = ZxInterface1BusDevice<io::Empty, io::Sink, ZxNetUdpSyncSocket, NullDevice<FTs>>;
+
+    #[test]
+    fn if1_net_records_udp_works() {
+        let remote = UdpSocket::bind("127.0.0.1:0").unwrap();
+        remote.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let mut if1 = TestUdpInterface1::default();
+        if1.network.socket.connect(remote.local_addr().unwrap()).unwrap();
+        // broadcast
+        let mut channel = if1.net_open_channel(0);
+        assert_eq!(channel, NetChannel { dest: 0, ours: DEFAULT_STATION, serial: 0 });
+        assert!(if1.net_send_record(&mut channel, b"Hello", false));
+        assert_eq!(channel.serial, 1);
+        let mut buf = [0u8;300];
+        let (len, local_addr) = remote.recv_from(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"ZXNET\x00\x00\x01\x00\x00\x00\x05\xf5\xfbHello");
+        // station 5 to station 7
+        assert!(if1.network.set_station(Some(5)));
+        let mut channel = if1.net_open_channel(7);
+        assert_eq!(channel, NetChannel { dest: 7, ours: 5, serial: 0 });
+        remote.connect(local_addr).unwrap();
+        let handle = thread::spawn(move || {
+            let mut buf = [0u8;300];
+            let len = remote.recv(&mut buf).unwrap();
+            let packet = buf[..len].to_vec();
+            // accept the packet
+            buf[5] = 1;
+            remote.send(&buf[..14]).unwrap();
+            (remote, packet)
+        });
+        assert!(if1.net_send_record(&mut channel, b"World!", true));
+        assert_eq!(channel.serial, 1);
+        let (remote, packet) = handle.join().unwrap();
+        assert_eq!(&packet[..6], b"ZXNET\x00");
+        assert_eq!(&packet[6..12], &[7, 5, 0, 0, 1, 6]);
+        assert_eq!(&packet[14..], b"World!");
+        // receive a reply from station 7
+        let data = b"Bye";
+        let head = [5u8, 7, 0, 0, 1, 3, 0x21, 0x31];
+        let mut packet = b"ZXNET\x00".to_vec();
+        packet.extend_from_slice(&head);
+        packet.extend_from_slice(data);
+        let mut channel = if1.net_open_channel(7);
+        remote.send(&packet).unwrap();
+        thread::sleep(Duration::from_millis(10));
+        let record = if1.net_recv_record(&mut channel).unwrap();
+        assert_eq!(record, NetRecord { eof: true, data: data.to_vec() });
+        assert_eq!(channel.serial, 1);
+        let len = remote.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], &[b"ZXNET\x01".as_ref(), &head].concat()[..]);
+        assert_eq!(if1.net_recv_record(&mut channel), None);
+    }
+
+    #[test]
+    fn if1_net_records_work() {
+        let mut if1 = TestInterface1::default();
+        // broadcast
+        let mut channel = if1.net_open_channel(0);
+        assert_eq!(channel, NetChannel { dest: 0, ours: DEFAULT_STATION, serial: 0 });
+        assert!(if1.net_send_record(&mut channel, b"Hello", false));
+        assert_eq!(channel.serial, 1);
+        assert_eq!(if1.network.socket.sent, [b"\x00\x01\x00\x00\x00\x05\xf5\xfbHello"]);
+        // station 5 to station 7
+        assert!(if1.network.set_station(Some(5)));
+        let mut channel = if1.net_open_channel(7);
+        assert_eq!(channel, NetChannel { dest: 7, ours: 5, serial: 0 });
+        assert!(if1.net_send_record(&mut channel, b"World!", true));
+        assert_eq!(channel.serial, 1);
+        let packet = &if1.network.socket.sent[1];
+        assert_eq!(&packet[..6], &[7, 5, 0, 0, 1, 6]);
+        assert_eq!(&packet[8..], b"World!");
+        // not accepted by station 7
+        if1.network.socket.reject = true;
+        assert!(!if1.net_send_record(&mut channel, b"Again", false));
+        assert_eq!(channel.serial, 1);
+        assert_eq!(if1.network.socket.sent.len(), 3);
+        // receive a reply from station 7
+        let data = b"Bye";
+        let head = [5u8, 7, 0, 0, 1, 3, 0x21, 0x31];
+        let packet = [&head[..], data].concat();
+        // a packet from another station is ignored
+        let other = [&[5u8, 8, 0, 0, 1, 3, 0x21, 0x32][..], data].concat();
+        if1.network.socket.inbound.extend([other, packet.clone(), packet]);
+        let mut channel = if1.net_open_channel(7);
+        let record = if1.net_recv_record(&mut channel).unwrap();
+        assert_eq!(record, NetRecord { eof: true, data: data.to_vec() });
+        assert_eq!(channel.serial, 1);
+        assert_eq!(if1.network.socket.accepted, [head]);
+        // the repeated packet has an unexpected block number
+        assert_eq!(if1.net_recv_record(&mut channel), None);
+        assert!(if1.network.socket.inbound.is_empty());
+        assert_eq!(if1.network.socket.accepted.len(), 1);
+    }
+
     #[test]
     fn flags_all_bits_defined() {
         test_bitflags_all_bits_defined_no_masks!(If1SerNetIo, 8);
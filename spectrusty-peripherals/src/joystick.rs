@@ -8,9 +8,11 @@
 //! A joystick communication interface and emulators of various joysticks.
 use core::fmt::Debug;
 
+pub mod autofire;
 pub mod cursor;
 pub mod fuller;
 pub mod kempston;
+pub mod programmable;
 pub mod sinclair;
 
 bitflags! {
@@ -0,0 +1,175 @@
+/*
+    Copyright (C) 2020-2023  Rafal Michalski
+
+    This file is part of SPECTRUSTY, a Rust library for building emulators.
+
+    For the full copyright notice, see the lib.rs file.
+*/
+//! Joystick auto-fire implementation.
+#[cfg(feature = "snapshot")]
+use serde::{Serialize, Deserialize};
+
+use spectrusty_core::chip::{FrameState, HostConfig};
+use spectrusty_core::clock::FTs;
+
+use super::{JoystickDevice, Directions, JoystickInterface};
+
+/// A joystick device wrapper adding the auto-fire function to any joystick device implementing
+/// [JoystickInterface].
+///
+/// While the auto-fire is enabled and the `FIRE` button is being held, the fire state of the wrapped
+/// joystick device alternates at the configured frequency. The alternation is determined solely by the
+/// frame counter of the emulated computer, so it's fully deterministic and is restored along with the
+/// emulator's state.
+///
+/// [AutoFireJoystick::update_autofire] should be called once per frame, before the frame is being executed.
+///
+/// Only the auto-fire frequency is being serialized. The state of the wrapped joystick device and
+/// of the `FIRE` button is the user input and is being restored to its default state.
+///
+/// See [AutoFireJoystickBusDevice][crate::bus::joystick::AutoFireJoystickBusDevice] for the [BusDevice][spectrusty_core::bus::BusDevice] form.
+#[derive(Clone, Copy, Default, Debug)]
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "snapshot", serde(rename_all = "camelCase"))]
+pub struct AutoFireJoystick<J> {
+    /// The wrapped joystick device.
+    #[cfg_attr(feature = "snapshot", serde(skip))]
+    pub joystick: J,
+    #[cfg_attr(feature = "snapshot", serde(default))]
+    autofire_hz: u32,
+    #[cfg_attr(feature = "snapshot", serde(skip))]
+    fire_held: bool
+}
+
+impl<J: JoystickInterface> AutoFireJoystick<J> {
+    /// Creates a new auto-fire joystick wrapping the provided `joystick` device with the auto-fire disabled.
+    pub fn new(joystick: J) -> Self {
+        AutoFireJoystick { joystick, autofire_hz: 0, fire_held: false }
+    }
+    /// Sets the auto-fire frequency in Hz. Providing `0` disables the auto-fire.
+    ///
+    /// The frequency is a number of complete press-release cycles per second.
+    pub fn set_autofire(&mut self, hz: u32) {
+        self.autofire_hz = hz;
+        if hz == 0 {
+            self.joystick.fire(0, self.fire_held);
+        }
+    }
+    /// Returns the auto-fire frequency in Hz. Returns `0` if the auto-fire is disabled.
+    pub fn autofire(&self) -> u32 {
+        self.autofire_hz
+    }
+    /// Returns `true` if the auto-fire is enabled.
+    pub fn is_autofire(&self) -> bool {
+        self.autofire_hz != 0
+    }
+    /// Updates the fire state of the wrapped joystick device from the current frame counter of `ula`.
+    ///
+    /// Does nothing if the auto-fire is disabled.
+    pub fn update_autofire<U: FrameState + HostConfig>(&mut self, ula: &U) {
+        let frame = ula.current_frame();
+        self.update_autofire_with_frame(frame, U::CPU_HZ, U::FRAME_TSTATES);
+    }
+    /// Updates the fire state of the wrapped joystick device from the provided `frame` counter value.
+    ///
+    /// `cpu_hz` and `frame_tstates` define the frame rate of the emulated computer.
+    ///
+    /// Does nothing if the auto-fire is disabled.
+    pub fn update_autofire_with_frame(&mut self, frame: u64, cpu_hz: u32, frame_tstates: FTs) {
+        if self.autofire_hz == 0 {
+            return
+        }
+        // the number of frames per half of the auto-fire cycle, rounded
+        let divisor = 2 * self.autofire_hz as u64 * frame_tstates as u64;
+        let half_period = ((2 * cpu_hz as u64 + divisor) / (2 * divisor)).max(1);
+        let pressed = self.fire_held && (frame / half_period) & 1 == 0;
+        self.joystick.fire(0, pressed);
+    }
+}
+
+impl<J: JoystickDevice> JoystickDevice for AutoFireJoystick<J> {
+    #[inline]
+    fn port_read(&self, port: u16) -> u8 {
+        self.joystick.port_read(port)
+    }
+
+    #[inline]
+    fn port_write(&mut self, port: u16, data: u8) -> bool {
+        self.joystick.port_write(port, data)
+    }
+}
+
+impl<J: JoystickInterface> JoystickInterface for AutoFireJoystick<J> {
+    /// Presses or releases the `FIRE` button.
+    ///
+    /// If the auto-fire is enabled, pressing the button takes effect on the next call to
+    /// [AutoFireJoystick::update_autofire].
+    fn fire(&mut self, btn: u8, pressed: bool) {
+        self.fire_held = pressed;
+        if self.autofire_hz == 0 || !pressed {
+            self.joystick.fire(btn, pressed);
+        }
+    }
+    /// Returns `true` if the `FIRE` button is being held, regardless of the auto-fire state.
+    fn get_fire(&self, _btn: u8) -> bool {
+        self.fire_held
+    }
+
+    fn set_directions(&mut self, dir: Directions) {
+        self.joystick.set_directions(dir)
+    }
+
+    fn get_directions(&self) -> Directions {
+        self.joystick.get_directions()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::joystick::kempston::KempstonJoystickDevice;
+    use super::*;
+
+    const CPU_HZ: u32 = 3_500_000;
+    const FRAME_TSTATES: FTs = 69888;
+
+    #[test]
+    fn autofire_works() {
+        let mut joy = AutoFireJoystick::new(KempstonJoystickDevice::default());
+        assert!(!joy.is_autofire());
+        joy.fire(0, true);
+        assert!(joy.joystick.get_fire(0));
+        joy.fire(0, false);
+        assert!(!joy.joystick.get_fire(0));
+        joy.set_autofire(25);
+        assert!(joy.is_autofire());
+        assert_eq!(joy.autofire(), 25);
+        joy.fire(0, true);
+        assert!(joy.get_fire(0));
+        let fires: Vec<bool> = (100..108).map(|frame| {
+            joy.update_autofire_with_frame(frame, CPU_HZ, FRAME_TSTATES);
+            joy.joystick.get_fire(0)
+        }).collect();
+        assert_eq!(fires, [true, false, true, false, true, false, true, false]);
+        assert_eq!(joy.port_read(0x1f) & 0x10, 0);
+        // deterministic
+        joy.update_autofire_with_frame(103, CPU_HZ, FRAME_TSTATES);
+        assert!(!joy.joystick.get_fire(0));
+        assert_eq!(joy.port_read(0x1f) & 0x10, 0);
+        // 12Hz, rounded to 2 frames per half cycle
+        joy.set_autofire(12);
+        let fires: Vec<bool> = (100..108).map(|frame| {
+            joy.update_autofire_with_frame(frame, CPU_HZ, FRAME_TSTATES);
+            joy.joystick.get_fire(0)
+        }).collect();
+        assert_eq!(fires, [true, true, false, false, true, true, false, false]);
+        // released
+        joy.fire(0, false);
+        assert!(!joy.joystick.get_fire(0));
+        joy.update_autofire_with_frame(100, CPU_HZ, FRAME_TSTATES);
+        assert!(!joy.joystick.get_fire(0));
+        // disabled while being held
+        joy.fire(0, true);
+        joy.set_autofire(0);
+        assert!(joy.joystick.get_fire(0));
+    }
+}
@@ -0,0 +1,81 @@
+/*
+    Copyright (C) 2020-2023  Rafal Michalski
+
+    This file is part of SPECTRUSTY, a Rust library for building emulators.
+
+    For the full copyright notice, see the lib.rs file.
+*/
+//! Programmable Joystick implementation.
+use core::ops::BitOr;
+
+use super::{Directions, JoystickInterface};
+
+/// The index of the `UP` direction in the [ProgrammableJoystickDevice] key mapping.
+pub const PROG_JOY_UP: usize = 0;
+/// The index of the `RIGHT` direction in the [ProgrammableJoystickDevice] key mapping.
+pub const PROG_JOY_RIGHT: usize = 1;
+/// The index of the `DOWN` direction in the [ProgrammableJoystickDevice] key mapping.
+pub const PROG_JOY_DOWN: usize = 2;
+/// The index of the `LEFT` direction in the [ProgrammableJoystickDevice] key mapping.
+pub const PROG_JOY_LEFT: usize = 3;
+/// The index of the `FIRE` button in the [ProgrammableJoystickDevice] key mapping.
+pub const PROG_JOY_FIRE: usize = 4;
+
+/// The Programmable Joystick device implements [JoystickInterface].
+///
+/// Instead of providing its state via a dedicated I/O port this joystick presses keyboard keys
+/// configured for each of the directions and the fire button, emulating interfaces that inject
+/// the state of the joystick into the keyboard matrix.
+///
+/// `K` is the keyboard state type, e.g. `ZXKeyboardMap`. The mapping is indexed with one of the
+/// `PROG_JOY_*` constants: `[up, right, down, left, fire]`.
+///
+/// Use [ProgrammableJoystickDevice::update_key_state] to merge the joystick state with the keyboard
+/// state before providing it to the emulated computer.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct ProgrammableJoystickDevice<K> {
+    /// The keyboard keys pressed by each direction and the fire button.
+    pub keys: [K; 5],
+    directions: Directions,
+    fire: bool
+}
+
+impl<K: Copy + Default + BitOr<Output=K>> ProgrammableJoystickDevice<K> {
+    /// Creates a new joystick with the provided key mapping: `[up, right, down, left, fire]`.
+    pub fn new(keys: [K; 5]) -> Self {
+        ProgrammableJoystickDevice { keys, directions: Directions::empty(), fire: false }
+    }
+    /// Returns the keyboard keys currently being pressed by the joystick.
+    pub fn key_state(&self) -> K {
+        let dirs = self.directions;
+        [(Directions::UP, PROG_JOY_UP), (Directions::RIGHT, PROG_JOY_RIGHT),
+         (Directions::DOWN, PROG_JOY_DOWN), (Directions::LEFT, PROG_JOY_LEFT)]
+        .iter().filter(|&&(dir, _)| dirs.intersects(dir))
+        .map(|&(_, index)| self.keys[index])
+        .chain(if self.fire { Some(self.keys[PROG_JOY_FIRE]) } else { None })
+        .fold(K::default(), |acc, keys| acc | keys)
+    }
+    /// Returns the keyboard state `keys` with the keys pressed by the joystick added.
+    #[inline]
+    pub fn update_key_state(&self, keys: K) -> K {
+        keys | self.key_state()
+    }
+}
+
+impl<K> JoystickInterface for ProgrammableJoystickDevice<K> {
+    fn fire(&mut self, _btn: u8, pressed: bool) {
+        self.fire = pressed;
+    }
+
+    fn get_fire(&self, _btn: u8) -> bool {
+        self.fire
+    }
+
+    fn set_directions(&mut self, dir: Directions) {
+        self.directions = dir;
+    }
+
+    fn get_directions(&self) -> Directions {
+        self.directions
+    }
+}
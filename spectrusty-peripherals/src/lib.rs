@@ -30,5 +30,6 @@ pub mod mouse;
 pub mod network;
 pub mod parallel;
 pub mod serial;
+pub mod speech;
 pub mod storage;
 pub mod zxprinter;
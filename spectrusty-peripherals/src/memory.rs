@@ -6,6 +6,10 @@
     For the full copyright notice, see the lib.rs file.
 */
 //! Memory extensions.
+mod divide;
+mod uspeech;
 mod zxinterface1;
 
+pub use divide::*;
+pub use uspeech::*;
 pub use zxinterface1::*;
@@ -0,0 +1,295 @@
+/*
+    Copyright (C) 2020-2023  Rafal Michalski
+
+    This file is part of SPECTRUSTY, a Rust library for building emulators.
+
+    For the full copyright notice, see the lib.rs file.
+*/
+use core::cell::Cell;
+use std::rc::Rc;
+use std::io::{self, Read, Write};
+
+use spectrusty_core::clock::FTs;
+use spectrusty_core::memory::{MemoryExtension, ExRom, ZxMemory};
+#[cfg(feature = "snapshot")]
+use spectrusty_core::memory::serde::{serialize_mem, deserialize_mem};
+#[cfg(feature = "snapshot")]
+use serde::{Serialize, Deserialize};
+
+/// The size of the DivIDE EEPROM in bytes.
+pub const DIVIDE_EEPROM_SIZE: usize = 0x2000;
+/// The size of the DivIDE RAM in bytes.
+pub const DIVIDE_RAM_SIZE: usize = 0x8000;
+/// The bit of the DivIDE control register which pages in the EEPROM and the selected RAM bank.
+pub const DIVIDE_CONMEM: u8 = 0b1000_0000;
+/// The bit of the DivIDE control register which replaces the EEPROM with the RAM bank 3.
+///
+/// Once set, it can't be reset by the software.
+pub const DIVIDE_MAPRAM: u8 = 0b0100_0000;
+/// The bits of the DivIDE control register selecting the RAM bank paged in at `0x2000`.
+pub const DIVIDE_BANK_MASK: u8 = 0b0000_0011;
+
+const BANK_SIZE: usize = 0x2000;
+const MAPRAM_BANK: u8 = 3;
+
+/// The paging state of the DivIDE interface.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "snapshot", serde(rename_all = "camelCase"))]
+pub struct DivIdePaging {
+    /// The last value written to the control register.
+    pub control: u8,
+    /// Is the DivIDE memory paged in by one of the entry points.
+    pub automap: bool
+}
+
+/// The handle to the paging state of the DivIDE interface shared between [DivIdeMemExt] and the DivIDE
+/// bus device.
+///
+/// Clones of the handle refer to the same state. The handle serializes as the paging state, so the deserialized
+/// handle is not shared with any other.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "snapshot", serde(from = "DivIdePaging", into = "DivIdePaging"))]
+pub struct DivIdeControl(Rc<Cell<DivIdePaging>>);
+
+/// The DivIDE memory [extension][MemoryExtension].
+///
+/// The DivIDE EEPROM and RAM are paged in at addresses `[0x0000, 0x3FFF]` when the [DIVIDE_CONMEM] bit
+/// of the control register is set or automatically, when the processor fetches the instruction at one
+/// of the entry points: `0x0000`, `0x0008`, `0x0038`, `0x0066`, `0x04C6`, `0x0562`, or at any address in the
+/// range `[0x3D00, 0x3DFF]`. The memory is paged out automatically after the processor fetches the
+/// instruction in the range `[0x1FF8, 0x1FFF]`.
+///
+/// The automatic paging is only active while the EEPROM data is loaded. The EEPROM is write-protected
+/// unless the write-enable jumper is set with [DivIdeMemExt::set_eeprom_write_enabled], in which case
+/// it can be written to at addresses `[0x0000, 0x1FFF]` while the [DIVIDE_CONMEM] bit is set, e.g. by the
+/// firmware flashing utilities. The EEPROM content is a part of the serialized state and can be saved
+/// with [DivIdeMemExt::save_eeprom], so the flashed firmware persists across sessions.
+///
+/// The control register is written to by the DivIDE bus device, which should be attached to the same
+/// [DivIdeControl] as the one returned from [DivIdeMemExt::control].
+///
+/// The paging state is serialized with the extension. The link has to be established again
+/// after the extension and the bus device are deserialized.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+pub struct DivIdeMemExt {
+    #[cfg_attr(feature = "snapshot",
+        serde(serialize_with = "serialize_mem", deserialize_with = "deserialize_mem"))]
+    #[cfg_attr(feature = "snapshot", serde(default = "exrom_default"))]
+    eeprom: ExRom,
+    #[cfg_attr(feature = "snapshot",
+        serde(serialize_with = "serialize_mem", deserialize_with = "deserialize_mem"))]
+    #[cfg_attr(feature = "snapshot", serde(default = "ram_default"))]
+    ram: Box<[u8;DIVIDE_RAM_SIZE]>,
+    #[cfg_attr(feature = "snapshot", serde(default))]
+    eeprom_write_enabled: bool,
+    #[cfg_attr(feature = "snapshot", serde(default))]
+    control: DivIdeControl
+}
+
+impl From<DivIdePaging> for DivIdeControl {
+    fn from(paging: DivIdePaging) -> Self {
+        DivIdeControl(Rc::new(Cell::new(paging)))
+    }
+}
+
+impl From<DivIdeControl> for DivIdePaging {
+    fn from(control: DivIdeControl) -> Self {
+        control.paging()
+    }
+}
+
+impl DivIdeControl {
+    /// Returns the current paging state.
+    pub fn paging(&self) -> DivIdePaging {
+        self.0.get()
+    }
+    /// Returns `true` if both handles refer to the same paging state.
+    pub fn is_shared_with(&self, other: &DivIdeControl) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+    /// Returns the last value written to the control register.
+    pub fn control(&self) -> u8 {
+        self.paging().control
+    }
+    /// Writes a value to the control register. The [DIVIDE_MAPRAM] bit can't be reset this way.
+    pub fn write_control(&self, data: u8) {
+        let paging = self.paging();
+        let control = data | paging.control & DIVIDE_MAPRAM;
+        self.0.set(DivIdePaging { control, ..paging });
+    }
+    /// Returns `true` if the DivIDE memory is paged in by one of the entry points.
+    pub fn is_automap(&self) -> bool {
+        self.paging().automap
+    }
+    /// Pages the DivIDE memory in or out as if one of the entry points was encountered.
+    pub fn set_automap(&self, automap: bool) {
+        let paging = self.paging();
+        self.0.set(DivIdePaging { automap, ..paging });
+    }
+    /// Returns `true` if the DivIDE memory is currently paged in.
+    pub fn is_paged_in(&self) -> bool {
+        let DivIdePaging { control, automap } = self.paging();
+        automap || control & DIVIDE_CONMEM != 0
+    }
+    /// Resets the paging state as on power on.
+    pub fn reset(&self) {
+        self.0.set(DivIdePaging::default());
+    }
+}
+
+impl Default for DivIdeMemExt {
+    fn default() -> Self {
+        DivIdeMemExt {
+            eeprom: Rc::new([]),
+            ram: Box::new([0;DIVIDE_RAM_SIZE]),
+            eeprom_write_enabled: false,
+            control: DivIdeControl::default()
+        }
+    }
+}
+
+impl MemoryExtension for DivIdeMemExt {
+    #[inline]
+    fn read_opcode<M: ZxMemory>(&mut self, pc: u16, memory: &mut M) -> u8 {
+        if self.eeprom.is_empty() {
+            return memory.read(pc)
+        }
+        if pc & 0xFF00 == 0x3D00 {
+            self.control.set_automap(true);
+        }
+        let res = self.read_byte(pc, memory);
+        match pc {
+            0x0000|0x0008|0x0038|0x0066|0x04C6|0x0562 => self.control.set_automap(true),
+            0x1FF8..=0x1FFF => self.control.set_automap(false),
+            _ => {}
+        }
+        res
+    }
+
+    #[inline]
+    fn read_mem<M: ZxMemory>(&self, addr: u16, _ts: FTs, memory: &M) -> u8 {
+        self.read_byte(addr, memory)
+    }
+
+    #[inline]
+    fn read_mem16<M: ZxMemory>(&self, addr: u16, _ts: FTs, memory: &M) -> u16 {
+        if (addr < 0x4000 || addr == u16::MAX) && self.control.is_paged_in() {
+            u16::from_le_bytes([self.read_byte(addr, memory),
+                                self.read_byte(addr.wrapping_add(1), memory)])
+        }
+        else {
+            memory.read16(addr)
+        }
+    }
+
+    #[inline]
+    fn write_mem<M: ZxMemory>(&mut self, addr: u16, val: u8, _ts: FTs, memory: &mut M) {
+        if addr < 0x4000 && self.control.is_paged_in() {
+            if addr < 0x2000 {
+                if self.eeprom_write_enabled && self.control.control() & DIVIDE_CONMEM != 0 {
+                    self.write_eeprom(addr, val);
+                }
+            }
+            else {
+                let control = self.control.control();
+                let bank = control & DIVIDE_BANK_MASK;
+                if !(control & (DIVIDE_CONMEM|DIVIDE_MAPRAM) == DIVIDE_MAPRAM && bank == MAPRAM_BANK) {
+                    self.ram[usize::from(bank) * BANK_SIZE + usize::from(addr - 0x2000)] = val;
+                }
+            }
+        }
+        else {
+            memory.write(addr, val)
+        }
+    }
+}
+
+impl DivIdeMemExt {
+    /// Provide a reader with 8kb of the DivIDE EEPROM data, e.g. esxDOS or FATware.
+    pub fn load_eeprom<R: Read>(&mut self, mut rd: R) -> io::Result<()> {
+        let mut eeprom = Rc::new([!0u8;DIVIDE_EEPROM_SIZE]);
+        rd.read_exact(Rc::get_mut(&mut eeprom).unwrap())?;
+        self.eeprom = eeprom;
+        Ok(())
+    }
+    /// Writes the current EEPROM data to the provided writer, e.g. to preserve the flashed firmware.
+    ///
+    /// Nothing is written if there is no EEPROM data loaded.
+    pub fn save_eeprom<W: Write>(&self, mut wr: W) -> io::Result<()> {
+        wr.write_all(&self.eeprom)
+    }
+    /// Returns a reference to the EEPROM data.
+    pub fn eeprom(&self) -> &ExRom {
+        &self.eeprom
+    }
+    /// Removes the EEPROM data, disabling the automatic paging.
+    pub fn clear_eeprom(&mut self) {
+        self.eeprom = Rc::new([]);
+    }
+    /// Returns `true` if the EEPROM write-enable jumper is set.
+    pub fn is_eeprom_write_enabled(&self) -> bool {
+        self.eeprom_write_enabled
+    }
+    /// Sets or removes the EEPROM write-enable jumper.
+    pub fn set_eeprom_write_enabled(&mut self, enabled: bool) {
+        self.eeprom_write_enabled = enabled;
+    }
+    /// Returns a reference to the DivIDE RAM.
+    pub fn ram_ref(&self) -> &[u8;DIVIDE_RAM_SIZE] {
+        &self.ram
+    }
+    /// Returns a mutable reference to the DivIDE RAM.
+    pub fn ram_mut(&mut self) -> &mut [u8;DIVIDE_RAM_SIZE] {
+        &mut self.ram
+    }
+    /// Returns a reference to the paging state handle that should be passed to the DivIDE bus device.
+    pub fn control(&self) -> &DivIdeControl {
+        &self.control
+    }
+    /// Returns `true` if the DivIDE memory is currently paged in.
+    pub fn is_paged_in(&self) -> bool {
+        self.control.is_paged_in()
+    }
+
+    fn write_eeprom(&mut self, addr: u16, val: u8) {
+        let offset = usize::from(addr & 0x1FFF);
+        if self.eeprom.get(offset).copied().unwrap_or(val) == val {
+            return
+        }
+        if Rc::get_mut(&mut self.eeprom).is_none() {
+            self.eeprom = Rc::from(&self.eeprom[..]);
+        }
+        Rc::get_mut(&mut self.eeprom).unwrap()[offset] = val;
+    }
+
+    #[inline]
+    fn read_byte<M: ZxMemory>(&self, addr: u16, memory: &M) -> u8 {
+        if addr >= 0x4000 || !self.control.is_paged_in() {
+            return memory.read(addr)
+        }
+        let control = self.control.control();
+        let offset = usize::from(addr & 0x1FFF);
+        if addr >= 0x2000 {
+            self.ram[usize::from(control & DIVIDE_BANK_MASK) * BANK_SIZE + offset]
+        }
+        else if control & (DIVIDE_CONMEM|DIVIDE_MAPRAM) == DIVIDE_MAPRAM {
+            self.ram[usize::from(MAPRAM_BANK) * BANK_SIZE + offset]
+        }
+        else {
+            self.eeprom.get(offset).copied().unwrap_or(u8::MAX)
+        }
+    }
+}
+
+#[cfg(feature = "snapshot")]
+fn exrom_default() -> ExRom {
+    Rc::new([])
+}
+
+#[cfg(feature = "snapshot")]
+fn ram_default() -> Box<[u8;DIVIDE_RAM_SIZE]> {
+    Box::new([0;DIVIDE_RAM_SIZE])
+}
@@ -0,0 +1,211 @@
+/*
+    Copyright (C) 2020-2022  Rafal Michalski
+
+    This file is part of SPECTRUSTY, a Rust library for building emulators.
+
+    For the full copyright notice, see the lib.rs file.
+*/
+use core::cell::{Ref, RefCell, RefMut};
+use std::rc::Rc;
+use std::io::{self, Read};
+
+use spectrusty_core::clock::FTs;
+use spectrusty_core::memory::{
+    MemoryExtension, ExRom, ZxMemory, ZxMemoryError
+};
+#[cfg(feature = "snapshot")]
+use spectrusty_core::memory::serde::{serialize_mem, deserialize_mem};
+#[cfg(feature = "snapshot")]
+use serde::{Serialize, Deserialize};
+
+use crate::speech::Sp0256;
+
+/// The size of the Currah µSpeech ROM in bytes.
+pub const USPEECH_ROM_SIZE: usize = 0x0800;
+
+const ALLOPHONE_ADDRESS: u16 = 0x1000;
+const INTONATION_LOW_ADDRESS: u16 = 0x3000;
+const INTONATION_HIGH_ADDRESS: u16 = 0x3001;
+
+/// The handle to the [Sp0256] speech processor shared between [CurrahMicroSpeechMemExt] and
+/// [CurrahMicroSpeech][crate::bus::audio::CurrahMicroSpeech].
+///
+/// Cloning the handle doesn't clone the speech processor. The handle serializes as the state of the speech
+/// processor, so the deserialized handle is not shared with any other.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "snapshot", serde(from = "Sp0256", into = "Sp0256"))]
+pub struct CurrahMicroSpeechControl(Rc<RefCell<Sp0256>>);
+
+impl From<Sp0256> for CurrahMicroSpeechControl {
+    fn from(speech: Sp0256) -> Self {
+        CurrahMicroSpeechControl(Rc::new(RefCell::new(speech)))
+    }
+}
+
+impl From<CurrahMicroSpeechControl> for Sp0256 {
+    fn from(control: CurrahMicroSpeechControl) -> Self {
+        control.speech_ref().clone()
+    }
+}
+
+impl CurrahMicroSpeechControl {
+    /// Returns a reference to the speech processor.
+    ///
+    /// # Panics
+    /// Panics if the speech processor is currently mutably borrowed.
+    pub fn speech_ref(&self) -> Ref<'_, Sp0256> {
+        self.0.borrow()
+    }
+    /// Returns a mutable reference to the speech processor.
+    ///
+    /// # Panics
+    /// Panics if the speech processor is currently borrowed.
+    pub fn speech_mut(&self) -> RefMut<'_, Sp0256> {
+        self.0.borrow_mut()
+    }
+    /// Returns `true` if both handles refer to the same speech processor.
+    pub fn is_shared_with(&self, other: &CurrahMicroSpeechControl) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// The Currah µSpeech memory [extension][MemoryExtension] with the [Sp0256] speech processor.
+///
+/// The µSpeech ROM is paged in or out each time the processor fetches the instruction at address `0x0038`,
+/// the maskable interrupt handler.
+///
+/// While the µSpeech ROM is paged in:
+///
+/// * writing to address `0x1000` sends an allophone to the speech processor,
+/// * writing to address `0x3000` selects the normal intonation,
+/// * writing to address `0x3001` selects the high intonation,
+/// * bit 0 of the value read from address `0x1000` indicates that the speech processor is busy, and the allophones
+///   written while it is busy are ignored.
+///
+/// The speech is rendered by the [CurrahMicroSpeech][crate::bus::audio::CurrahMicroSpeech] bus device,
+/// which must share the speech processor with the extension:
+///
+/// ```text
+/// uspeech.set_control(memext.control().clone());
+/// ```
+///
+/// The speech processor is serialized with the extension. The link has to be established again
+/// after the extension and the bus device are deserialized.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+pub struct CurrahMicroSpeechMemExt {
+    #[cfg_attr(feature = "snapshot",
+        serde(serialize_with = "serialize_mem", deserialize_with = "deserialize_mem"))]
+    #[cfg_attr(feature = "snapshot", serde(default = "exrom_default"))]
+    exrom: ExRom,
+    #[cfg_attr(feature = "snapshot", serde(default))]
+    speech: CurrahMicroSpeechControl
+}
+
+impl Default for CurrahMicroSpeechMemExt {
+    fn default() -> Self {
+        let exrom = Rc::new([]);
+        CurrahMicroSpeechMemExt { exrom, speech: CurrahMicroSpeechControl::default() }
+    }
+}
+
+impl MemoryExtension for CurrahMicroSpeechMemExt {
+    #[inline(always)]
+    fn read_opcode<M: ZxMemory>(&mut self, pc: u16, memory: &mut M) -> u8 {
+        let res = memory.read(pc);
+        if pc == 0x0038 {
+            if memory.has_mapped_exrom(&self.exrom) {
+                memory.unmap_exrom(&self.exrom);
+            }
+            else {
+                let _ = memory.map_exrom(Rc::clone(&self.exrom), 0);
+            }
+        }
+        res
+    }
+
+    #[inline]
+    fn read_mem<M: ZxMemory>(&self, addr: u16, ts: FTs, memory: &M) -> u8 {
+        let res = memory.read(addr);
+        if addr == ALLOPHONE_ADDRESS && memory.has_mapped_exrom(&self.exrom) {
+            return (res & !1) | self.speech.speech_ref().is_busy(ts) as u8
+        }
+        res
+    }
+
+    #[inline]
+    fn write_mem<M: ZxMemory>(&mut self, addr: u16, val: u8, ts: FTs, memory: &mut M) {
+        match addr {
+            ALLOPHONE_ADDRESS|INTONATION_LOW_ADDRESS|INTONATION_HIGH_ADDRESS
+                    if memory.has_mapped_exrom(&self.exrom) => {
+                let mut speech = self.speech.speech_mut();
+                match addr {
+                    ALLOPHONE_ADDRESS => { speech.write_allophone(val, ts); }
+                    INTONATION_LOW_ADDRESS => speech.set_intonation(false),
+                    _ => speech.set_intonation(true)
+                }
+            }
+            _ => memory.write(addr, val)
+        }
+    }
+}
+
+impl CurrahMicroSpeechMemExt {
+    /// Provide a reader with 2kb of Currah µSpeech ROM program code.
+    pub fn load_uspeech_rom<R: Read>(&mut self, mut rd: R) -> io::Result<()> {
+        let mut exrom = Rc::new([0u8;0x4000]);
+        let exrom_slice = Rc::get_mut(&mut exrom).unwrap();
+        rd.read_exact(&mut exrom_slice[0..USPEECH_ROM_SIZE])?;
+        // the ROM is not fully decoded, so it's mirrored through the whole page
+        for offset in (USPEECH_ROM_SIZE..0x4000).step_by(USPEECH_ROM_SIZE) {
+            exrom_slice.copy_within(0..USPEECH_ROM_SIZE, offset);
+        }
+        self.exrom = exrom;
+        Ok(())
+    }
+    /// Returns a reference to the EX-ROM bank.
+    pub fn exrom(&self) -> &ExRom {
+        &self.exrom
+    }
+    /// Removes data from the EX-ROM bank.
+    ///
+    /// # Note
+    /// If the EX-ROM bank has been paged in, it won't be paged out automatically after the EX-ROM data
+    /// is cleared from the extension.
+    pub fn clear_exrom(&mut self) {
+        self.exrom = Rc::new([]);
+    }
+    /// Maps EX-ROM into `memory` page `0`.
+    ///
+    /// # Errors
+    /// Returns an error if the extension's EX-ROM bank is not populated with ROM data.
+    pub fn map_exrom<M: ZxMemory>(&self, memory: &mut M) -> Result<(), ZxMemoryError> {
+        memory.map_exrom(Rc::clone(&self.exrom), 0)
+    }
+    /// Unmaps EX-ROM from `memory`.
+    pub fn unmap_exrom<M: ZxMemory>(&self, memory: &mut M) {
+        memory.unmap_exrom(&self.exrom)
+    }
+    /// Returns `true` if EX-ROM is currently paged in.
+    pub fn is_mapped_exrom<M: ZxMemory>(&self, memory: &M) -> bool {
+        memory.has_mapped_exrom(&self.exrom)
+    }
+    /// Returns a reference to the speech processor handle.
+    pub fn control(&self) -> &CurrahMicroSpeechControl {
+        &self.speech
+    }
+    /// Returns a reference to the speech processor.
+    pub fn speech_ref(&self) -> Ref<'_, Sp0256> {
+        self.speech.speech_ref()
+    }
+    /// Returns a mutable reference to the speech processor.
+    pub fn speech_mut(&mut self) -> RefMut<'_, Sp0256> {
+        self.speech.speech_mut()
+    }
+}
+
+#[cfg(feature = "snapshot")]
+fn exrom_default() -> ExRom {
+    Rc::new([])
+}
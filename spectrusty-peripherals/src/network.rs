@@ -8,3 +8,4 @@
 //! Network related.
 pub mod zxnet;
 pub(self) mod zxnet_udp;
+pub(self) mod zxnet_server;
@@ -7,13 +7,22 @@
 */
 //! *ZX Net* coders for the ZX Interface 1.
 use core::mem;
+use core::ops::RangeInclusive;
+use std::path::PathBuf;
 use std::time::{Instant};
 
+use rand::{Rng, SeedableRng};
+use rand::rngs::SmallRng;
+
+#[cfg(feature = "snapshot")]
+use serde::{Serialize, Deserialize};
+
 #[allow(unused_imports)]
 use log::{error, warn, info, debug, trace};
 
 use spectrusty_core::clock::{FTs, TimestampOps};
 pub use super::zxnet_udp::*;
+pub use super::zxnet_server::*;
 
 const CPU_HZ: f32 = 3_500_000.0;
 
@@ -45,20 +54,89 @@ pub trait ZxNetSocket {
     fn send_accept(&mut self);
 }
 
+/// Configures how the local [ZxNet] station behaves on the emulated network.
+///
+/// The default profile assumes a cooperative environment: the station number is taken as written
+/// by the ROM, packets are offered to the Spectrum as soon as they arrive and no collisions ever occur.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "snapshot", serde(default, rename_all = "camelCase"))]
+pub struct NetworkProfile {
+    /// The local station number (1 - 64) to be put in the `NCSELF` field of each outgoing packet header.
+    ///
+    /// If `None` the station number set by the ROM (`FORMAT "n";x`) is being used.
+    pub station: Option<u8>,
+    /// The minimum number of T-states that needs to pass after the end of a packet transmission before
+    /// the next incoming packet is being offered to the Spectrum.
+    pub inter_packet_gap: FTs,
+    /// The probability in the range `0.0..=1.0` that an outgoing packet collides with a transmission from
+    /// another station and is being lost on the wire.
+    pub collision_probability: f32,
+    /// A seed of the pseudo-random generator used for collisions, so they can be reproduced deterministically.
+    pub collision_seed: u64
+}
+
+/// The state of a network channel, the equivalent of the `"n"` channel opened by the ZX Interface 1 ROM.
+///
+/// Used with [ZxNet::send_record] and [ZxNet::recv_record] to exchange data records with the remote
+/// stations directly, without the emulated Spectrum.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NetChannel {
+    /// `NCIRIS` The remote station number, `0` for broadcast.
+    pub dest: u8,
+    /// `NCSELF` This station's number.
+    pub ours: u8,
+    /// `NCNUMB` The number of the next block to be sent or received.
+    pub serial: u16
+}
+
+/// A data record received with [ZxNet::recv_record].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NetRecord {
+    /// `NCTYPE` Is this the last record of the transmission.
+    pub eof: bool,
+    /// The record data.
+    pub data: Vec<u8>
+}
+
+/// The default station number set by the ZX Interface 1 ROM.
+pub const DEFAULT_STATION: u8 = 1;
+/// The range of the valid station numbers.
+pub const STATION_RANGE: RangeInclusive<u8> = 1..=64;
+
+fn is_valid_station(station: Option<u8>) -> bool {
+    match station {
+        Some(n) => STATION_RANGE.contains(&n),
+        None => true
+    }
+}
+
 /// Implementation of this struct decodes and encodes ZX-NET packets from Spectrum's I/O port signals.
 ///
 /// An implementation of [ZxNetSocket] should be provided as its `S` type parameter.
 #[derive(Debug)]
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "snapshot", serde(rename_all = "camelCase"))]
 pub struct ZxNet<T, S> {
     /// Direct access to the underlying [ZxNetSocket] implementation.
+    #[cfg_attr(feature = "snapshot", serde(skip))]
     pub socket: S,
     event_ts: T,
+    gap_ts: T,
     dir_io: NetDir,
     io: NetState,
-    net_state: bool
+    net_state: bool,
+    #[cfg_attr(feature = "snapshot", serde(default))]
+    profile: NetworkProfile,
+    // seeded with the profile's collision seed on first use, also after deserialization
+    #[cfg_attr(feature = "snapshot", serde(skip))]
+    rng: Option<SmallRng>,
+    #[cfg_attr(feature = "snapshot", serde(skip))]
+    fileserver: Option<ZxNetFileServer>
 }
 
 /// A helper struct for reading ZX-NET header information.
+#[derive(Clone, Copy)]
 #[repr(C, packed)]
 pub struct ZxNetHead {
     /// `NCIRIS` The destination station number.
@@ -78,6 +156,8 @@ pub struct ZxNetHead {
 }
 
 pub(super) const HEAD_SIZE: usize = mem::size_of::<ZxNetHead>();
+const HEAD_OURS_INDEX: usize = 1;
+const HEAD_HCHK_INDEX: usize = HEAD_SIZE - 1;
 
 /// A trait for converting data to references of [ZxNetHead].
 pub trait DataAsZxNetHead {
@@ -109,12 +189,14 @@ const BROADCAST_DATA_DELAY: i32 = 530;
 // const MAX_PACKET_SIZE: usize = 256 + BODY_INDEX;
 
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
 enum NetDir {
     Inbound,
     Outbound
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
 enum NetState {
     Idle(u8),
     InputScout,
@@ -126,6 +208,170 @@ enum NetState {
     OutputStop(u8),
     OutputEnd
 }
+impl Default for NetworkProfile {
+    fn default() -> Self {
+        NetworkProfile {
+            station: None,
+            inter_packet_gap: 0,
+            collision_probability: 0.0,
+            collision_seed: 0
+        }
+    }
+}
+
+/// Checksum calculating routine used by the ZX Interface 1 ROM for network packets.
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |acc, &x| {
+        let (mut acc, carry) = acc.overflowing_add(x);
+        acc = acc.wrapping_add(carry as u8);
+        if acc == u8::MAX { 0 } else { acc }
+    })
+}
+
+/// Returns the packet header of a data record sent over the network `channel`.
+pub(super) fn encode_head(channel: &NetChannel, data: &[u8], eof: bool) -> [u8;HEAD_SIZE] {
+    let [serial_lo, serial_hi] = channel.serial.to_le_bytes();
+    let mut head = [channel.dest, channel.ours, serial_lo, serial_hi,
+                    eof.into(), data.len() as u8, checksum(data), 0];
+    head[HEAD_HCHK_INDEX] = checksum(&head[..HEAD_HCHK_INDEX]);
+    head
+}
+
+impl<T, S> ZxNet<T, S> {
+    /// Returns a reference to the current network profile.
+    pub fn network_profile(&self) -> &NetworkProfile {
+        &self.profile
+    }
+    /// Changes the network profile and re-seeds the collision generator with
+    /// [NetworkProfile::collision_seed].
+    ///
+    /// Returns `false` and leaves the profile unchanged if [NetworkProfile::station] is not in [STATION_RANGE].
+    pub fn set_network_profile(&mut self, profile: NetworkProfile) -> bool {
+        if !is_valid_station(profile.station) {
+            return false
+        }
+        self.rng = None;
+        self.profile = profile;
+        true
+    }
+    /// Sets the local station number to be used in outgoing packet headers.
+    ///
+    /// Pass `None` to use the station number set by the ROM.
+    ///
+    /// Returns `false` and leaves the station number unchanged if `station` is not in [STATION_RANGE].
+    pub fn set_station(&mut self, station: Option<u8>) -> bool {
+        if !is_valid_station(station) {
+            return false
+        }
+        self.profile.station = station;
+        true
+    }
+
+    /// Enables the file server mode serving files from the given host directory or disables it
+    /// if `path` is `None`.
+    ///
+    /// While enabled, the packets sent by the emulated Spectrum are being handled by the [ZxNetFileServer]
+    /// instead of the underlying socket. See [ZxNetFileServer] for the details of the file request.
+    pub fn set_net_fileserver(&mut self, path: Option<PathBuf>) {
+        self.fileserver = path.map(ZxNetFileServer::new);
+    }
+    /// Returns a reference to the file server if the file server mode is enabled.
+    pub fn net_fileserver(&self) -> Option<&ZxNetFileServer> {
+        self.fileserver.as_ref()
+    }
+    /// Returns a mutable reference to the file server if the file server mode is enabled.
+    pub fn net_fileserver_mut(&mut self) -> Option<&mut ZxNetFileServer> {
+        self.fileserver.as_mut()
+    }
+
+    fn is_collision(&mut self) -> bool {
+        let probability = self.profile.collision_probability;
+        let seed = self.profile.collision_seed;
+        probability > 0.0 &&
+            self.rng.get_or_insert_with(|| SmallRng::seed_from_u64(seed)).gen::<f32>() < probability
+    }
+}
+
+impl<T, S: ZxNetSocket> ZxNet<T, S> {
+    // the socket used by the emulated Spectrum
+    fn net_socket(&mut self) -> &mut dyn ZxNetSocket {
+        match self.fileserver.as_mut() {
+            Some(fileserver) => fileserver,
+            None => &mut self.socket
+        }
+    }
+
+    /// Returns a new network channel for exchanging records with the `dest` station,
+    /// the equivalent of `OPEN #s;"n";dest`.
+    ///
+    /// This station's number is taken from the [NetworkProfile::station] or [DEFAULT_STATION] is used.
+    pub fn open_channel(&self, dest: u8) -> NetChannel {
+        let ours = self.profile.station.unwrap_or(DEFAULT_STATION);
+        NetChannel { dest, ours, serial: 0 }
+    }
+    /// Sends a data record over the network `channel` directly via the underlying socket.
+    ///
+    /// Returns `true` if the record was broadcasted or accepted by the remote station, in this
+    /// instance the channel's block number is incremented.
+    ///
+    /// This method bypasses the emulated I/O and it should not be used while the emulated Spectrum
+    /// is using the network.
+    ///
+    /// # Panics
+    /// Panics if `data` is larger than 255 bytes.
+    pub fn send_record(&mut self, channel: &mut NetChannel, data: &[u8], eof: bool) -> bool {
+        assert!(data.len() <= u8::MAX as usize, "a record must not be larger than 255 bytes");
+        let head = encode_head(channel, data, eof);
+        self.socket.begin_packet();
+        for &byte in head.iter().chain(data) {
+            self.socket.push_byte(byte);
+        }
+        if self.is_collision() {
+            return false
+        }
+        self.socket.send_packet();
+        let accepted = channel.dest == 0 || self.socket.recv_accept();
+        if accepted {
+            channel.serial = channel.serial.wrapping_add(1);
+        }
+        accepted
+    }
+    /// Receives the next data record from the network `channel` directly via the underlying socket.
+    ///
+    /// Returns `None` if there are no valid packets with the expected block number from the remote
+    /// station waiting. Otherwise, accepts the packet and increments the channel's block number.
+    ///
+    /// This method bypasses the emulated I/O and it should not be used while the emulated Spectrum
+    /// is using the network.
+    pub fn recv_record(&mut self, channel: &mut NetChannel) -> Option<NetRecord> {
+        while self.socket.recv_packet() {
+            let packet = self.socket.packet_data();
+            if packet.len() < HEAD_SIZE {
+                continue
+            }
+            let (head, data) = packet.split_at(HEAD_SIZE);
+            let ZxNetHead { dest, ours, serial, eof, size, dchk, hchk } = *packet.as_zxnet_header();
+            if hchk != checksum(&head[..HEAD_HCHK_INDEX]) ||
+               size as usize != data.len() || dchk != checksum(data) ||
+               u16::from_le_bytes(serial) != channel.serial
+            {
+                continue
+            }
+            let broadcast = channel.dest == 0;
+            if broadcast && dest != 0 || !broadcast && (dest != channel.ours || ours != channel.dest) {
+                continue
+            }
+            let record = NetRecord { eof: eof != 0, data: data.to_vec() };
+            if !broadcast {
+                self.socket.send_accept();
+            }
+            channel.serial = channel.serial.wrapping_add(1);
+            return Some(record)
+        }
+        None
+    }
+}
+
 // https://scratchpad.fandom.com/wiki/ZX_Net
 // scout: 1 x x x x x x x 0 
 //(2.5ms) 1 [ 0 x x x x x x x x 1 * bytes ] 0
@@ -139,7 +385,7 @@ impl<T: TimestampOps, S: ZxNetSocket> ZxNet<T, S> {
             }
             NetState::OutputScout if net && !self.net_state => { // OUTPAK will start
                 // println!("OUTPAK start from scout");
-                self.socket.begin_packet();
+                self.net_socket().begin_packet();
                 self.dir_io = NetDir::Outbound;
                 self.event_ts = timestamp + OUTPAK_START_DELAY;
                 self.io = NetState::OutputStart;
@@ -174,9 +420,9 @@ impl<T: TimestampOps, S: ZxNetSocket> ZxNet<T, S> {
                 match self.dir_io {
                     NetDir::Inbound if byte == 1 => {
                         // println!("got send resp 1");
-                        if self.socket.inbound_index() == HEAD_SIZE {
+                        if self.net_socket().inbound_index() == HEAD_SIZE {
                             // let now = Instant::now();
-                            self.socket.send_accept();
+                            self.net_socket().send_accept();
                             // println!("sent accept in {:?}", now.elapsed());
                         }
                         self.io = NetState::OutputEnd;
@@ -187,15 +433,22 @@ impl<T: TimestampOps, S: ZxNetSocket> ZxNet<T, S> {
                         self.io = NetState::Idle(0); // end of packet transmission                        
                     }
                     NetDir::Outbound => {
-                        let len = self.socket.push_byte(byte);
+                        let byte = match (self.net_socket().outbound_index(), self.profile.station) {
+                            (HEAD_OURS_INDEX, Some(station)) => station,
+                            (HEAD_HCHK_INDEX, Some(_)) => checksum(self.net_socket().packet_data()),
+                            _ => byte
+                        };
+                        let len = self.net_socket().push_byte(byte);
                         self.io = if len == HEAD_SIZE {
                             // println!("outbound header end");
                             NetState::OutputEnd
                         }
                         else if len > HEAD_SIZE
-                             && len - HEAD_SIZE == self.socket.packet_data().as_zxnet_header().size as usize {
+                             && len - HEAD_SIZE == self.net_socket().packet_data().as_zxnet_header().size as usize {
                             // println!("outbound data end");
-                            self.socket.send_packet();
+                            if !self.is_collision() {
+                                self.net_socket().send_packet();
+                            }
                             NetState::OutputEnd
                         }
                         else {
@@ -207,10 +460,11 @@ impl<T: TimestampOps, S: ZxNetSocket> ZxNet<T, S> {
             }
             NetState::OutputEnd if !net && timestamp < self.event_ts => { // end outpack
                 self.event_ts = timestamp; // TODO: SOME TIMEOUT
-                let head = self.socket.packet_data().as_zxnet_header();
+                self.gap_ts = timestamp + self.profile.inter_packet_gap;
+                let head = *self.net_socket().packet_data().as_zxnet_header();
                 match self.dir_io {
                     NetDir::Inbound => { // end of outpak resp
-                        self.io = if self.socket.inbound_index() == head.size as usize + HEAD_SIZE {
+                        self.io = if self.net_socket().inbound_index() == head.size as usize + HEAD_SIZE {
                             self.net_state = false;
                             // println!("end of response and transmission");
                             NetState::Idle(0) // end of packet transmission                        
@@ -222,7 +476,7 @@ impl<T: TimestampOps, S: ZxNetSocket> ZxNet<T, S> {
                         }
                     }
                     NetDir::Outbound => {
-                        self.io = if self.socket.outbound_index() == head.size as usize + HEAD_SIZE {
+                        self.io = if self.net_socket().outbound_index() == head.size as usize + HEAD_SIZE {
                             if head.dest == 0 { // BROADCAST - no response checking
                                 // println!("end of BROADCAST");
                                 self.net_state = false;
@@ -267,9 +521,9 @@ impl<T: TimestampOps, S: ZxNetSocket> ZxNet<T, S> {
                         }
                         PROBE_DELAY_MIN..=PROBE_DELAY_MAX if cnt < 191 => { // WAIT SCOUT
                             // let now = Instant::now();
-                            if self.socket.recv_packet() {
+                            if timestamp >= self.gap_ts && self.net_socket().recv_packet() {
                                 // println!("{} got packet let it REST: {} {:?} {:?}", cnt,
-                                //             self.socket.packet_data().len(), now.elapsed(), &self.socket.packet_data()[0..8]);
+                                //             self.net_socket().packet_data().len(), now.elapsed(), &self.net_socket().packet_data()[0..8]);
                                 // got a packet, so regardless of what spectrums wants we will try to shove it
                                 self.event_ts = timestamp;
                                 self.io = NetState::InputScout;
@@ -303,7 +557,7 @@ impl<T: TimestampOps, S: ZxNetSocket> ZxNet<T, S> {
             }
             NetState::InputScout => {
                 let now = Instant::now();
-                if self.socket.recv_accept() {
+                if self.net_socket().recv_accept() {
                     // println!("got accept: {} {:?}", V::vts_diff(self.event_ts, timestamp), now.elapsed());
                     self.event_ts = timestamp;
                     self.io = NetState::InputStart;
@@ -352,11 +606,11 @@ impl<T: TimestampOps, S: ZxNetSocket> ZxNet<T, S> {
         // println!("wait: {}", V::vts_to_tstates(timestamp));
         if let Some(byte) = match (self.io, self.dir_io) { // Spectrum wants a byte
                 (NetState::InputStart, NetDir::Outbound) => Some(1),
-                (NetState::InputStart, NetDir::Inbound) => self.socket.pull_byte(),
+                (NetState::InputStart, NetDir::Inbound) => self.net_socket().pull_byte(),
                 (NetState::InputData(0), NetDir::Inbound) if timestamp > self.event_ts &&
                                         timestamp < self.event_ts + BIT_DELAY => {
                     // the whole byte has been transerred
-                    self.socket.pull_byte()
+                    self.net_socket().pull_byte()
                 }
                 _ => None
             }
@@ -375,6 +629,7 @@ impl<T: TimestampOps, S: ZxNetSocket> ZxNet<T, S> {
 
     pub fn next_frame(&mut self, eof_timestamp: T) {
         self.event_ts = self.event_ts.saturating_sub(eof_timestamp);
+        self.gap_ts = self.gap_ts.saturating_sub(eof_timestamp);
     }
 
     fn setup_event_time(&mut self, timestamp: T, start: Instant) {
@@ -389,9 +644,186 @@ impl<T: Default, S: Default> Default for ZxNet<T, S> {
     fn default() -> Self {
         let socket = S::default();
         let event_ts = T::default();
+        let gap_ts = T::default();
         let net_state = false;
         let dir_io = NetDir::Inbound;
         let io = NetState::Idle(0);
-        ZxNet { socket, event_ts, net_state, dir_io, io }
+        let profile = NetworkProfile::default();
+        let rng = None;
+        let fileserver = None;
+        ZxNet { socket, event_ts, gap_ts, net_state, dir_io, io, profile, rng, fileserver }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::collections::VecDeque;
+    use super::*;
+
+    /// An in-memory [ZxNetSocket] recording the sent packets and serving the queued inbound packets.
+    #[derive(Default, Debug)]
+    pub(crate) struct TestSocket {
+        pub packet: Vec<u8>,
+        pub sent: Vec<Vec<u8>>,
+        /// Packets to be received, without the socket's own framing.
+        pub inbound: VecDeque<Vec<u8>>,
+        /// Headers of the accepted inbound packets.
+        pub accepted: Vec<Vec<u8>>,
+        /// Whether the remote party rejects the sent packets.
+        pub reject: bool,
+        index: usize
+    }
+
+    impl ZxNetSocket for TestSocket {
+        fn packet_data(&self) -> &[u8] { &self.packet }
+        fn begin_packet(&mut self) { self.packet.clear() }
+        fn push_byte(&mut self, byte: u8) -> usize {
+            self.packet.push(byte);
+            self.packet.len()
+        }
+        fn outbound_index(&self) -> usize { self.packet.len() }
+        fn send_packet(&mut self) { self.sent.push(self.packet.clone()) }
+        fn recv_accept(&mut self) -> bool { !self.reject }
+        fn recv_packet(&mut self) -> bool {
+            self.index = 0;
+            match self.inbound.pop_front() {
+                Some(packet) => {
+                    self.packet = packet;
+                    true
+                }
+                None => false
+            }
+        }
+        fn pull_byte(&mut self) -> Option<u8> {
+            let byte = self.packet.get(self.index).copied();
+            self.index += byte.is_some() as usize;
+            byte
+        }
+        fn inbound_index(&self) -> usize { self.index }
+        fn send_accept(&mut self) {
+            let size = self.packet.len().min(HEAD_SIZE);
+            self.accepted.push(self.packet[..size].to_vec())
+        }
+    }
+
+    fn send_packet(net: &mut ZxNet<FTs, TestSocket>, packet: &[u8]) {
+        let mut ts = 1000;
+        // scout
+        net.send_state(false, ts);
+        net.send_state(true, ts);
+        for (i, &byte) in packet.iter().enumerate() {
+            ts += 10;
+            if i == HEAD_SIZE {
+                // end of the header, a broadcast goes on with the data without a response
+                net.send_state(false, ts);
+                ts += 10;
+                net.send_state(true, ts);
+            }
+            // start bit
+            net.send_state(false, ts);
+            for bit in 0..8 {
+                ts += 10;
+                net.send_state(byte & (1 << bit) != 0, ts);
+            }
+            // stop bit
+            ts += 10;
+            net.send_state(true, ts);
+        }
+        ts += 10;
+        net.send_state(false, ts);
+    }
+
+    fn broadcast_packet(data: &[u8]) -> Vec<u8> {
+        let mut packet = vec![0, 1, 1, 0, 1, data.len() as u8, checksum(data), 0];
+        packet[HEAD_HCHK_INDEX] = checksum(&packet[..HEAD_HCHK_INDEX]);
+        packet.extend_from_slice(data);
+        packet
+    }
+
+    #[test]
+    fn zxnet_station_works() {
+        let packet = broadcast_packet(b"hello");
+        let mut net = ZxNet::<FTs, TestSocket>::default();
+        send_packet(&mut net, &packet);
+        assert_eq!(net.socket.sent.len(), 1);
+        assert_eq!(net.socket.sent[0], packet);
+
+        let mut net = ZxNet::<FTs, TestSocket>::default();
+        assert!(!net.set_network_profile(NetworkProfile { station: Some(0), ..Default::default() }));
+        assert!(!net.set_station(Some(65)));
+        assert_eq!(net.network_profile().station, None);
+        assert!(net.set_network_profile(NetworkProfile { station: Some(5), ..Default::default() }));
+        send_packet(&mut net, &packet);
+        assert_eq!(net.socket.sent.len(), 1);
+        let sent = &net.socket.sent[0];
+        let head = sent.as_zxnet_header();
+        assert_eq!(head.ours, 5);
+        assert_eq!(head.dest, 0);
+        assert_eq!(head.hchk, checksum(&sent[..HEAD_HCHK_INDEX]));
+        assert_ne!(head.hchk, packet[HEAD_HCHK_INDEX]);
+        assert_eq!(&sent[HEAD_SIZE..], b"hello");
+    }
+
+    #[test]
+    fn zxnet_fileserver_mode_works() {
+        let packet = broadcast_packet(b"hello");
+        let mut net = ZxNet::<FTs, TestSocket>::default();
+        assert!(net.net_fileserver().is_none());
+        net.set_net_fileserver(Some(PathBuf::from("files")));
+        assert_eq!(net.net_fileserver().unwrap().dir(), std::path::Path::new("files"));
+        assert!(!net.net_fileserver_mut().unwrap().set_station(0));
+        assert!(!net.net_fileserver_mut().unwrap().set_station(65));
+        assert!(net.net_fileserver_mut().unwrap().set_station(7));
+        assert_eq!(net.net_fileserver().unwrap().station(), 7);
+        // the packets are being handled by the file server instead of the socket
+        send_packet(&mut net, &packet);
+        assert!(net.socket.sent.is_empty());
+        assert_eq!(net.net_fileserver().unwrap().packet_data(), &packet[..]);
+        net.set_net_fileserver(None);
+        assert!(net.net_fileserver().is_none());
+        send_packet(&mut net, &packet);
+        assert_eq!(net.socket.sent, [packet]);
+    }
+
+    #[test]
+    fn zxnet_collisions_work() {
+        let packet = broadcast_packet(b"spam");
+        let profile = NetworkProfile { collision_probability: 1.0, ..Default::default() };
+        let mut net = ZxNet::<FTs, TestSocket>::default();
+        net.set_network_profile(profile);
+        send_packet(&mut net, &packet);
+        assert!(net.socket.sent.is_empty());
+
+        let profile = NetworkProfile { collision_probability: 0.5, collision_seed: 42, ..Default::default() };
+        let mut sent = Vec::new();
+        for _ in 0..2 {
+            let mut net = ZxNet::<FTs, TestSocket>::default();
+            net.set_network_profile(profile);
+            for _ in 0..32 {
+                send_packet(&mut net, &packet);
+            }
+            sent.push(net.socket.sent.len());
+        }
+        assert_eq!(sent[0], sent[1]);
+        assert!(sent[0] > 0 && sent[0] < 32);
+    }
+
+    #[cfg(feature = "snapshot")]
+    #[test]
+    fn zxnet_collisions_serde_works() {
+        let packet = broadcast_packet(b"spam");
+        let profile = NetworkProfile { collision_probability: 0.5, collision_seed: 42, ..Default::default() };
+        let mut net = ZxNet::<FTs, TestSocket>::default();
+        net.set_network_profile(profile);
+        let json = serde_json::to_string(&net).unwrap();
+        let mut net1: ZxNet<FTs, TestSocket> = serde_json::from_str(&json).unwrap();
+        assert_eq!(net1.network_profile(), &profile);
+        // the deserialized generator is seeded with the profile's seed
+        for _ in 0..32 {
+            send_packet(&mut net, &packet);
+            send_packet(&mut net1, &packet);
+        }
+        assert_eq!(net1.socket.sent, net.socket.sent);
+        assert!(!net.socket.sent.is_empty() && net.socket.sent.len() < 32);
     }
 }
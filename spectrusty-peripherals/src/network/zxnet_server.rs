@@ -0,0 +1,299 @@
+/*
+    Copyright (C) 2020-2023  Rafal Michalski
+
+    This file is part of SPECTRUSTY, a Rust library for building emulators.
+
+    For the full copyright notice, see the lib.rs file.
+*/
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[allow(unused_imports)]
+use log::{error, warn, info, debug, trace};
+
+use super::zxnet::{STATION_RANGE, HEAD_SIZE, ZxNetSocket, ZxNetHead, DataAsZxNetHead, NetChannel, encode_head};
+
+/// The default station number of the [ZxNetFileServer].
+pub const FILESERVER_STATION: u8 = 64;
+
+/// Implements [ZxNetSocket] serving files from a host directory to the emulated Spectrum.
+///
+/// The file server acts as a remote station (by default [FILESERVER_STATION]) that accepts all packets
+/// addressed to it. The data of the packets sent to the server until the one with the `EOF` flag
+/// is being interpreted as a file name. Trailing `ENTER` characters and spaces are ignored.
+///
+/// After the request is complete, the server responds with the packets containing the content of
+/// the requested file, addressed to the requesting station, split into records of up to 255 bytes,
+/// the last one having the `EOF` flag set.
+///
+/// From the Spectrum's perspective, requesting a file `"name"` from the server looks like:
+///
+/// ```text
+/// OPEN #4;"n";64: PRINT #4;"name": CLOSE #4
+/// ```
+///
+/// Then the file content can be read from the `"n";64` channel, e.g. with `LOAD *"n";64` if the file
+/// contains data saved with `SAVE *"n"`.
+///
+/// Only files present directly in the served directory can be requested.
+#[derive(Clone, Debug)]
+pub struct ZxNetFileServer {
+    dir: PathBuf,
+    station: u8,
+    packet: Vec<u8>,
+    inbound_index: usize,
+    accepted: bool,
+    request: Vec<u8>,
+    request_serial: u16,
+    responses: VecDeque<Vec<u8>>
+}
+
+impl ZxNetFileServer {
+    /// Creates a new file server serving files from the given directory.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        ZxNetFileServer {
+            dir: dir.into(),
+            station: FILESERVER_STATION,
+            packet: Vec::new(),
+            inbound_index: 0,
+            accepted: false,
+            request: Vec::new(),
+            request_serial: 0,
+            responses: VecDeque::new()
+        }
+    }
+    /// Returns the served directory.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+    /// Returns the station number of the server.
+    pub fn station(&self) -> u8 {
+        self.station
+    }
+    /// Changes the station number of the server.
+    ///
+    /// Returns `false` and leaves the station number unchanged if `station` is not in [STATION_RANGE].
+    pub fn set_station(&mut self, station: u8) -> bool {
+        if !STATION_RANGE.contains(&station) {
+            return false
+        }
+        self.station = station;
+        true
+    }
+    /// Returns the number of response packets waiting to be received by the Spectrum.
+    pub fn pending_packets(&self) -> usize {
+        self.responses.len()
+    }
+
+    fn process_request(&mut self) -> bool {
+        let packet = &self.packet;
+        if packet.len() < HEAD_SIZE {
+            return false
+        }
+        let (head, data) = packet.split_at(HEAD_SIZE);
+        let ZxNetHead { dest, ours, serial, eof, size, .. } = *packet.as_zxnet_header();
+        if dest != self.station || size as usize != data.len() || head != encode_head(
+            &NetChannel { dest, ours, serial: u16::from_le_bytes(serial) }, data, eof != 0)
+        {
+            return false
+        }
+        let serial = u16::from_le_bytes(serial);
+        if serial == 0 {
+            self.request.clear();
+            self.request_serial = 0;
+        }
+        if serial != self.request_serial {
+            // accept the repeated packet in case the previous acceptance was lost
+            return serial < self.request_serial
+        }
+        self.request.extend_from_slice(data);
+        self.request_serial = serial.wrapping_add(1);
+        if eof != 0 {
+            self.respond(ours);
+            self.request.clear();
+            self.request_serial = 0;
+        }
+        true
+    }
+
+    fn respond(&mut self, dest: u8) {
+        self.responses.clear();
+        let name = match self.request_file_name() {
+            Some(name) => name,
+            None => {
+                warn!("ZX-NET file server: invalid request: {:?}", self.request);
+                return
+            }
+        };
+        let content = match fs::read(self.dir.join(name)) {
+            Ok(content) => content,
+            Err(err) => {
+                warn!("ZX-NET file server: {}: {}", name, err);
+                return
+            }
+        };
+        debug!("ZX-NET file server: serving {} ({} bytes) to {}", name, content.len(), dest);
+        let mut channel = NetChannel { dest, ours: self.station, serial: 0 };
+        let mut chunks = content.chunks(u8::MAX as usize).peekable();
+        if chunks.peek().is_none() {
+            self.responses.push_back(encode_head(&channel, &[], true).to_vec());
+        }
+        while let Some(chunk) = chunks.next() {
+            let eof = chunks.peek().is_none();
+            let mut packet = encode_head(&channel, chunk, eof).to_vec();
+            packet.extend_from_slice(chunk);
+            self.responses.push_back(packet);
+            channel.serial = channel.serial.wrapping_add(1);
+        }
+    }
+
+    fn request_file_name(&self) -> Option<&str> {
+        let end = self.request.iter().rposition(|&c| c != b'\r' && c != b' ')? + 1;
+        let name = core::str::from_utf8(&self.request[..end]).ok()?;
+        if Path::new(name).file_name()? == name {
+            Some(name)
+        }
+        else {
+            None
+        }
+    }
+}
+
+impl ZxNetSocket for ZxNetFileServer {
+    fn packet_data(&self) -> &[u8] {
+        &self.packet
+    }
+
+    fn begin_packet(&mut self) {
+        self.packet.clear();
+        self.accepted = false;
+    }
+
+    fn push_byte(&mut self, byte: u8) -> usize {
+        self.packet.push(byte);
+        self.packet.len()
+    }
+
+    fn outbound_index(&self) -> usize {
+        self.packet.len()
+    }
+
+    fn send_packet(&mut self) {
+        self.accepted = self.process_request();
+    }
+
+    fn recv_accept(&mut self) -> bool {
+        core::mem::replace(&mut self.accepted, false)
+    }
+
+    fn recv_packet(&mut self) -> bool {
+        match self.responses.front() {
+            Some(packet) => {
+                self.packet.clone_from(packet);
+                self.inbound_index = 0;
+                true
+            }
+            None => false
+        }
+    }
+
+    fn pull_byte(&mut self) -> Option<u8> {
+        let byte = self.packet.get(self.inbound_index).copied();
+        if byte.is_some() {
+            self.inbound_index += 1;
+        }
+        byte
+    }
+
+    fn inbound_index(&self) -> usize {
+        self.inbound_index
+    }
+
+    fn send_accept(&mut self) {
+        self.responses.pop_front();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request_packet(ours: u8, serial: u16, name: &[u8], eof: bool) -> Vec<u8> {
+        let channel = NetChannel { dest: FILESERVER_STATION, ours, serial };
+        let mut packet = encode_head(&channel, name, eof).to_vec();
+        packet.extend_from_slice(name);
+        packet
+    }
+
+    fn send(server: &mut ZxNetFileServer, packet: &[u8]) -> bool {
+        server.begin_packet();
+        for &byte in packet {
+            server.push_byte(byte);
+        }
+        server.send_packet();
+        server.recv_accept()
+    }
+
+    fn recv(server: &mut ZxNetFileServer) -> Option<Vec<u8>> {
+        if !server.recv_packet() {
+            return None
+        }
+        let mut packet = Vec::new();
+        while let Some(byte) = server.pull_byte() {
+            packet.push(byte);
+        }
+        assert_eq!(server.inbound_index(), packet.len());
+        server.send_accept();
+        Some(packet)
+    }
+
+    #[test]
+    fn zxnet_file_server_works() {
+        let dir = std::env::temp_dir().join(format!("spectrusty-zxnet-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let content: Vec<u8> = (0..300u16).map(|x| x as u8).collect();
+        fs::write(dir.join("game"), &content).unwrap();
+        let mut server = ZxNetFileServer::new(&dir);
+        assert_eq!(server.dir(), dir);
+        assert_eq!(server.station(), FILESERVER_STATION);
+        // packets to other stations and broadcasts are ignored
+        let mut packet = request_packet(1, 0, b"game\r", true);
+        packet[0] = 3;
+        assert!(!send(&mut server, &packet));
+        packet[0] = 0;
+        assert!(!send(&mut server, &packet));
+        // bad checksum
+        let mut packet = request_packet(1, 0, b"game\r", true);
+        packet[HEAD_SIZE] ^= 1;
+        assert!(!send(&mut server, &packet));
+        assert_eq!(recv(&mut server), None);
+        // the request split into two records
+        assert!(send(&mut server, &request_packet(5, 0, b"ga", false)));
+        assert!(send(&mut server, &request_packet(5, 0, b"ga", false)));
+        assert_eq!(server.pending_packets(), 0);
+        assert!(send(&mut server, &request_packet(5, 1, b"me\r", true)));
+        assert_eq!(server.pending_packets(), 2);
+        for (serial, &(data, eof)) in [(&content[..255], false), (&content[255..], true)].iter().enumerate() {
+            // the packet is being offered until it's accepted
+            assert!(server.recv_packet());
+            let packet = recv(&mut server).unwrap();
+            let (head, body) = packet.split_at(HEAD_SIZE);
+            let channel = NetChannel { dest: 5, ours: FILESERVER_STATION, serial: serial as u16 };
+            assert_eq!(head, encode_head(&channel, data, eof));
+            assert_eq!(body, data);
+        }
+        assert_eq!(recv(&mut server), None);
+        // an empty file
+        fs::write(dir.join("empty"), []).unwrap();
+        assert!(send(&mut server, &request_packet(2, 0, b"empty", true)));
+        let packet = recv(&mut server).unwrap();
+        let channel = NetChannel { dest: 2, ours: FILESERVER_STATION, serial: 0 };
+        assert_eq!(packet, encode_head(&channel, &[], true));
+        // missing files and files outside of the directory are not served
+        assert!(send(&mut server, &request_packet(2, 0, b"missing", true)));
+        assert!(send(&mut server, &request_packet(2, 0, b"../game", true)));
+        assert_eq!(recv(&mut server), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
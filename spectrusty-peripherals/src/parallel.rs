@@ -7,7 +7,9 @@
 */
 //! Parallel port device designed for parallel printer but other devices can be also emulated.
 //!
+use std::fs::File;
 use std::io;
+use std::path::Path;
 
 use spectrusty_core::clock::{TimestampOps, FTs};
 
@@ -50,6 +52,16 @@ pub struct ParallelPortWriter<T, W> {
     last_ts: T,
 }
 
+/// A parallel port device writing the received bytes to a host file.
+///
+/// Use [FileSink::create] to create a new instance.
+pub type FileSink<T> = ParallelPortWriter<T, io::BufWriter<File>>;
+
+/// A parallel port device writing the received bytes to the host's standard output.
+///
+/// Use [StdoutSink::stdout] to create a new instance.
+pub type StdoutSink<T> = ParallelPortWriter<T, io::Stdout>;
+
 /// A parallel port device that does nothing and provides a constant low `BUSY` signal.
 #[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
@@ -57,6 +69,28 @@ pub struct NullParallelPort<T>(core::marker::PhantomData<T>);
 
 const STROBE_TSTATES_MAX: FTs = 10000;
 
+impl<T: TimestampOps, W> ParallelPortWriter<T, W> {
+    /// Returns a new instance with the given `writer`.
+    pub fn new(writer: W) -> Self {
+        ParallelPortWriter { writer, busy: false, data: 0, last_ts: T::min_value() }
+    }
+}
+
+impl<T: TimestampOps> FileSink<T> {
+    /// Creates a file at the given `path`, truncating it if it exists, and returns a new instance
+    /// writing to the file.
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        File::create(path).map(|file| Self::new(io::BufWriter::new(file)))
+    }
+}
+
+impl<T: TimestampOps> StdoutSink<T> {
+    /// Returns a new instance writing to the standard output.
+    pub fn stdout() -> Self {
+        Self::new(io::stdout())
+    }
+}
+
 impl<V, W: io::Write> ParallelPortWriter<V, W> {
     fn write_byte_to_writer(&mut self) -> bool {
         let buf = core::slice::from_ref(&self.data);
@@ -119,3 +153,50 @@ impl<T> ParallelPortDevice for NullParallelPort<T> {
     #[inline(always)]
     fn next_frame(&mut self, _eof_timestamp: Self::Timestamp) {}
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use super::*;
+
+    fn print<P: ParallelPortDevice<Timestamp=FTs>>(device: &mut P, data: &[u8], mut ts: FTs) -> FTs {
+        for &byte in data {
+            device.write_data(byte, ts);
+            assert!(!device.write_strobe(true, ts + 10));
+            assert!(!device.write_strobe(false, ts + 20));
+            ts += 100;
+        }
+        ts
+    }
+
+    #[test]
+    fn parallel_port_writer_works() {
+        let mut printer = ParallelPortWriter::<FTs, Vec<u8>>::default();
+        let ts = print(&mut printer, b"HELLO", 0);
+        assert_eq!(printer.writer, b"HELLO");
+        // no strobe
+        printer.write_data(b'X', ts);
+        assert!(!printer.poll_busy());
+        // strobe too late
+        assert!(!printer.write_strobe(true, ts + STROBE_TSTATES_MAX));
+        assert!(!printer.write_strobe(false, ts + STROBE_TSTATES_MAX));
+        assert_eq!(printer.writer, b"HELLO");
+        printer.next_frame(ts);
+        print(&mut printer, b"\r\n", 0);
+        assert_eq!(printer.writer, b"HELLO\r\n");
+    }
+
+    #[test]
+    fn parallel_file_sink_works() {
+        let path = std::env::temp_dir().join(format!("spectrusty-parallel-{}.txt", std::process::id()));
+        let mut sink = FileSink::<FTs>::create(&path).unwrap();
+        print(&mut sink, b"10 LPRINT \"SPECTRUSTY\"\n", 0);
+        sink.writer.flush().unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"10 LPRINT \"SPECTRUSTY\"\n");
+        drop(sink);
+        std::fs::remove_file(&path).unwrap();
+        let mut sink = StdoutSink::<FTs>::stdout();
+        assert!(!sink.poll_busy());
+        sink.next_frame(0);
+    }
+}
@@ -42,6 +42,11 @@ const CPU_HZ: u32 = 3_500_000;
 ///
 /// You may read the currently transmitted data baud rate for reading and writing using [Rs232Io::baud_rate].
 ///
+/// To emulate a device transmitting data at the fixed rate instead, set the baud rate with
+/// [Rs232Io::set_baud_rate]. In this instance the bits are being sent to Spectrum at the intervals
+/// of the bit period regardless of how often Spectrum reads them, and the bits sent by Spectrum are
+/// being accepted only if they are spaced by the bit period.
+///
 /// # Panics
 /// The [Read] and [Write] implementation methods must not return any error other than [ErrorKind::Interrupted].
 /// If any other error is returned the [SerialPortDevice] implementation will panic.
@@ -61,7 +66,9 @@ pub struct Rs232Io<T, R, W> {
     read_event_ts: T,
     write_io: WriteStatus,
     write_max_delay: FTs,
-    write_event_ts: T
+    write_event_ts: T,
+    #[cfg_attr(feature = "snapshot", serde(default))]
+    fixed_baud_rate: Option<u32>
 }
 
 /// Spectrum's *BAUD RATES*.
@@ -70,6 +77,9 @@ pub const BAUD_RATES: &[u32;9] = &[50, 110, 300, 600, 1200, 2400, 4800, 9600, 19
 /// A default *BAUD RATE* used by Spectrum.
 pub const DEFAULT_BAUD_RATE: u32 = 9600;
 
+/// The number of bits of a single frame transmitted at the fixed baud rate: START, 8 data bits and 2 STOP bits.
+const FRAME_BITS: FTs = 11;
+
 const MIN_STOP_BIT_DELAY: FTs = CPU_HZ as FTs / 19200;
 const MAX_STOP_BIT_DELAY: FTs = CPU_HZ as FTs / 49;
 const STOP_BIT_GRACE_DELAY: FTs = 50;
@@ -92,7 +102,8 @@ impl<T: Default, R: Default, W: Default> Default for Rs232Io<T, R, W> {
             read_event_ts,
             write_io,
             write_max_delay,
-            write_event_ts
+            write_event_ts,
+            fixed_baud_rate: None
         }
     }
 }
@@ -139,13 +150,44 @@ enum ReadStatus {
     StartBit(u8),
     Synchronize(u8),
     SendingData(u8),
+    Transmitting(u8),
+}
+
+/// Returns the duration of a single bit in T-states for the given `baud_rate`.
+///
+/// # Panics
+/// Panics if `baud_rate` is `0`.
+pub fn bit_period(baud_rate: u32) -> FTs {
+    (CPU_HZ / baud_rate) as FTs
+}
+
+impl<T, R, W> Rs232Io<T, R, W> {
+    /// Sets the fixed *BAUD RATE* of the data sent to and received from Spectrum or `None` to have it
+    /// synchronized with the Spectrum's reading and writing routines.
+    ///
+    /// The baud rate must match the one set in Spectrum, e.g. with `FORMAT "b";9600` on the ZX Interface 1.
+    ///
+    /// # Panics
+    /// Panics if `baud_rate` is `Some(0)`.
+    pub fn set_baud_rate(&mut self, baud_rate: Option<u32>) {
+        assert_ne!(baud_rate, Some(0), "Rs232Io::set_baud_rate: baud rate must not be 0");
+        self.fixed_baud_rate = baud_rate;
+    }
+    /// Returns the fixed *BAUD RATE* set with [Rs232Io::set_baud_rate].
+    pub fn fixed_baud_rate(&self) -> Option<u32> {
+        self.fixed_baud_rate
+    }
 }
 
 impl<T: TimestampOps, R: Read, W: Write> Rs232Io<T, R, W> {
-    /// Returns the detected *BAUD RATE* of the current or the last transmission.
+    /// Returns the fixed *BAUD RATE* if one was set, otherwise the detected *BAUD RATE* of the current
+    /// or the last transmission.
     ///
     /// If there was no transmission since the start of the emulator, returns the default.
     pub fn baud_rate(&self) -> u32 {
+        if let Some(baud_rate) = self.fixed_baud_rate {
+            return baud_rate
+        }
         let bit_period = if self.write_event_ts > self.read_event_ts {
             self.write_max_delay
         }
@@ -213,6 +255,25 @@ impl<T: TimestampOps, R: Read, W: Write> Rs232Io<T, R, W> {
     fn process_read(&mut self, timestamp: T) -> DataState { // -> txd
         match self.read_io {
             ReadStatus::NotReady => DataState::Mark,
+            ReadStatus::StartBit(byte) if self.fixed_baud_rate.is_some() => {
+                self.read_event_ts = timestamp;
+                self.read_io = ReadStatus::Transmitting(byte);
+                DataState::Space
+            }
+            ReadStatus::Transmitting(byte) => {
+                let period = bit_period(self.fixed_baud_rate.unwrap_or(DEFAULT_BAUD_RATE));
+                let index = timestamp.diff_from(self.read_event_ts) as FTs / period;
+                match index {
+                    0 => DataState::Space,
+                    1..=8 => (byte >> (index - 1) & 1 == 1).into(),
+                    _ => {
+                        if index >= FRAME_BITS {
+                            self.read_io = ReadStatus::NotReady;
+                        }
+                        DataState::Mark
+                    }
+                }
+            }
             ReadStatus::StartBit(byte) => {
                 self.read_event_ts = timestamp + MIN_STOP_BIT_DELAY;
                 self.read_io = ReadStatus::Synchronize(byte);
@@ -253,6 +314,17 @@ impl<T: TimestampOps, R: Read, W: Write> Rs232Io<T, R, W> {
         }
     }
 
+    // Returns `true` if the next bit written at `timestamp` is not late or, with the fixed baud rate,
+    // if it's not earlier than half of the bit period after the previous bit.
+    #[inline]
+    fn is_write_bit_in_time(&self, timestamp: T) -> bool {
+        let delta_fts = timestamp.diff_from(self.write_event_ts);
+        match self.fixed_baud_rate {
+            Some(baud_rate) => delta_fts < 0 && delta_fts > -bit_period(baud_rate),
+            None => delta_fts < 0
+        }
+    }
+
     #[inline]
     fn write_failed(&mut self, timestamp: T) -> ControlState {
         self.write_io = WriteStatus::Idle(ControlState::Inactive);
@@ -290,8 +362,15 @@ impl<T: TimestampOps, R: Read, W: Write> Rs232Io<T, R, W> {
             WriteStatus::Idle(dtr) => {
                 if timestamp >= self.write_event_ts {
                     if rxd.is_space() { // START
-                        self.write_event_ts = timestamp + MIN_STOP_BIT_DELAY;
-                        self.write_io = WriteStatus::StartBit;
+                        if let Some(baud_rate) = self.fixed_baud_rate {
+                            self.write_max_delay = bit_period(baud_rate) * 3 / 2;
+                            self.write_event_ts = timestamp + self.write_max_delay;
+                            self.write_io = WriteStatus::ReceivingData(0x80);
+                        }
+                        else {
+                            self.write_event_ts = timestamp + MIN_STOP_BIT_DELAY;
+                            self.write_io = WriteStatus::StartBit;
+                        }
                     }
                     ControlState::Active
                 }
@@ -314,7 +393,7 @@ impl<T: TimestampOps, R: Read, W: Write> Rs232Io<T, R, W> {
                 self.write_failed(timestamp)
             }
             WriteStatus::ReceivingData(prev_bits) => {
-                if timestamp < self.write_event_ts {
+                if self.is_write_bit_in_time(timestamp) {
                     let bit: u8 = rxd.into();
                     let next_bits = (prev_bits & !1 | bit).rotate_right(1);
                     self.write_event_ts = timestamp + self.write_max_delay;
@@ -331,7 +410,7 @@ impl<T: TimestampOps, R: Read, W: Write> Rs232Io<T, R, W> {
                 }
             }
             WriteStatus::StopBits(data) => {
-                if rxd.is_mark() && timestamp < self.write_event_ts {
+                if rxd.is_mark() && self.is_write_bit_in_time(timestamp) {
                     if self.write_byte_to_writer(data) {
                         self.write_event_ts = timestamp + self.write_max_delay * 4 / 3 + STOP_BIT_GRACE_DELAY;
                         self.write_io = WriteStatus::Idle(ControlState::Active);
@@ -350,3 +429,83 @@ impl<T: TimestampOps, R: Read, W: Write> Rs232Io<T, R, W> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use core::iter;
+    use std::io::{self, Cursor};
+    use super::*;
+
+    #[test]
+    fn rs232_fixed_baud_rate_works() {
+        let mut rs = Rs232Io::<FTs, _, _> {
+            reader: Cursor::new(vec![0b0101_1001]),
+            writer: io::sink(),
+            ..Default::default()
+        };
+        assert_eq!(rs.fixed_baud_rate(), None);
+        rs.set_baud_rate(Some(9600));
+        assert_eq!(rs.fixed_baud_rate(), Some(9600));
+        assert_eq!(rs.baud_rate(), 9600);
+        let period = bit_period(9600);
+        assert_eq!(period, 364);
+        assert_eq!(rs.read_data(1000), DataState::Mark);
+        rs.update_cts(ControlState::Active, 1000);
+        // the start bit begins with the first read
+        let start = 1100;
+        let mut edges = Vec::new();
+        let mut last = DataState::Mark;
+        let mut bits = Vec::new();
+        for ts in (start..start + 12 * period).step_by(2) {
+            let data = rs.read_data(ts);
+            if data != last {
+                edges.push(ts - start);
+                last = data;
+            }
+            if (ts - start) % period == period / 2 {
+                bits.push(data.is_mark());
+            }
+        }
+        // START, 1, 0, 0, 1, 1, 0, 1, 0, STOP, STOP
+        assert_eq!(bits, [false, true, false, false, true, true, false, true, false, true, true, true]);
+        assert_eq!(edges, [0, period, 2 * period, 4 * period, 6 * period, 7 * period,
+                           8 * period, 9 * period]);
+        assert!(edges.iter().all(|edge| edge % period == 0));
+        // no more data
+        rs.update_cts(ControlState::Active, start + 12 * period);
+        assert_eq!(rs.read_data(start + 13 * period), DataState::Mark);
+    }
+
+    // writes the byte with the bits spaced by `spacing` T-states, returns the bytes received by the writer
+    fn write_byte(baud_rate: Option<u32>, byte: u8, spacing: FTs) -> Vec<u8> {
+        let mut rs = Rs232Io::<FTs, io::Empty, Vec<u8>>::default();
+        rs.set_baud_rate(baud_rate);
+        let mut ts = 1000;
+        assert_eq!(rs.poll_ready(ts), ControlState::Active);
+        // START, 8 data bits, STOP
+        let bits = iter::once(DataState::Space)
+                   .chain((0..8).map(|n| (byte >> n & 1 == 1).into()))
+                   .chain(iter::once(DataState::Mark));
+        for bit in bits {
+            rs.write_data(bit, ts);
+            ts += spacing;
+        }
+        rs.writer
+    }
+
+    #[test]
+    fn rs232_fixed_baud_rate_write_works() {
+        let period = bit_period(9600);
+        // the bits spaced by the bit period are accepted
+        assert_eq!(write_byte(Some(9600), 0b0101_1001, period), [0b0101_1001]);
+        assert_eq!(write_byte(Some(9600), 0b1010_0110, period + period / 4), [0b1010_0110]);
+        assert_eq!(write_byte(Some(9600), 0b1010_0110, period - period / 4), [0b1010_0110]);
+        // the bits spaced by the bit period of other baud rates are rejected
+        assert!(write_byte(Some(9600), 0b0101_1001, bit_period(4800)).is_empty());
+        assert!(write_byte(Some(9600), 0b0101_1001, bit_period(19200)).is_empty());
+        assert!(write_byte(Some(4800), 0b0101_1001, period).is_empty());
+        // while the baud rate is being auto-detected without the fixed rate
+        assert_eq!(write_byte(None, 0b0101_1001, bit_period(4800)), [0b0101_1001]);
+        assert_eq!(write_byte(None, 0b0101_1001, period), [0b0101_1001]);
+    }
+}
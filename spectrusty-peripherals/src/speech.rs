@@ -0,0 +1,382 @@
+/*
+    Copyright (C) 2020-2022  Rafal Michalski
+
+    This file is part of SPECTRUSTY, a Rust library for building emulators.
+
+    For the full copyright notice, see the lib.rs file.
+*/
+//! The **SP0256-AL2** allophone speech processor.
+//!
+//! The synthesizer implemented here doesn't execute the internal chip's ROM. Instead, each allophone
+//! is rendered as a square-wave approximation of its voicing (a glottal pulse train gating the first
+//! formant), frication (pseudo-random noise) and silence, with the allophone durations and timing
+//! of the original chip.
+#[cfg(feature = "snapshot")]
+use serde::{Serialize, Deserialize};
+
+use spectrusty_core::{
+    audio::{Blep, AmpLevels, render_audio_frame_ts},
+    clock::FTs
+};
+
+/// The default CPU clock in Hz used to calculate the allophone timing.
+pub const DEFAULT_CPU_HZ: u32 = 3_500_000;
+/// The number of allophones.
+pub const ALLOPHONES_COUNT: u8 = 64;
+/// The rate of the SP0256 output samples per second.
+const SAMPLE_RATE: u32 = 10_000;
+/// The glottal pulse frequency in Hz with the normal intonation.
+const PITCH_HZ: u32 = 100;
+/// The SP0256 clock in kHz with the normal intonation.
+const CLOCK_NORMAL_KHZ: u32 = 3050;
+/// The SP0256 clock in kHz with the high intonation.
+const CLOCK_HIGH_KHZ: u32 = 3260;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Sound {
+    /// Silence.
+    Pause,
+    /// A voiced sound with the first formant frequency in Hz.
+    Voiced(u16),
+    /// An unvoiced fricative.
+    Noise,
+    /// A voiced fricative with the first formant frequency in Hz.
+    VoicedNoise(u16),
+    /// An unvoiced plosive: a closure followed by a burst of noise.
+    Stop,
+    /// A voiced plosive: a closure followed by a voiced release with the first formant frequency in Hz.
+    VoicedStop(u16),
+}
+
+use Sound::*;
+
+/// Allophones: (name, duration in ms, sound, peak level).
+const ALLOPHONES: [(&str, u16, Sound, u8); ALLOPHONES_COUNT as usize] = [
+    ("PA1",  10, Pause,             0), ("PA2",  30, Pause,             0),
+    ("PA3",  50, Pause,             0), ("PA4", 100, Pause,             0),
+    ("PA5", 200, Pause,             0), ("OY",  420, Voiced(650),      15),
+    ("AY",  260, Voiced(700),      15), ("EH",   70, Voiced(550),      14),
+    ("KK3", 120, Stop,              7), ("PP",  210, Stop,              6),
+    ("JH",  140, VoicedNoise(300),  9), ("NN1", 140, Voiced(250),      10),
+    ("IH",   70, Voiced(400),      14), ("TT2", 140, Stop,              7),
+    ("RR1", 170, Voiced(350),      11), ("AX",   70, Voiced(600),      14),
+    ("MM",  180, Voiced(250),      10), ("TT1", 100, Stop,              7),
+    ("DH1", 290, VoicedNoise(300),  8), ("IY",  250, Voiced(300),      14),
+    ("EY",  280, Voiced(500),      15), ("DD1",  70, VoicedStop(300),  9),
+    ("UW1", 100, Voiced(300),      13), ("AO",  100, Voiced(600),      15),
+    ("AA",  100, Voiced(750),      15), ("YY2", 180, Voiced(300),      11),
+    ("AE",  120, Voiced(700),      15), ("HH1", 130, Noise,             5),
+    ("BB1",  80, VoicedStop(300),   9), ("TH",  180, Noise,             5),
+    ("UH",  100, Voiced(450),      14), ("UW2", 260, Voiced(300),      13),
+    ("AW",  370, Voiced(650),      15), ("DD2", 160, VoicedStop(300),  9),
+    ("GG3", 140, VoicedStop(300),   9), ("VV",  190, VoicedNoise(300),  8),
+    ("GG1",  80, VoicedStop(300),   9), ("SH",  160, Noise,             8),
+    ("ZH",  190, VoicedNoise(300),  8), ("RR2", 120, Voiced(350),      11),
+    ("FF",  150, Noise,             5), ("KK2", 190, Stop,              7),
+    ("KK1", 160, Stop,              7), ("ZZ",  210, VoicedNoise(300),  8),
+    ("NG",  220, Voiced(250),      10), ("LL",  110, Voiced(350),      11),
+    ("WW",  180, Voiced(300),      11), ("XR",  360, Voiced(500),      13),
+    ("WH",  200, Noise,             5), ("YY1", 130, Voiced(300),      11),
+    ("CH",  190, Noise,             8), ("ER1", 160, Voiced(500),      13),
+    ("ER2", 300, Voiced(500),      13), ("OW",  240, Voiced(500),      15),
+    ("DH2", 240, VoicedNoise(300),  8), ("SS",   90, Noise,             8),
+    ("NN2", 190, Voiced(250),      10), ("HH2", 180, Noise,             5),
+    ("OR",  330, Voiced(550),      14), ("AR",  290, Voiced(700),      15),
+    ("YR",  350, Voiced(450),      14), ("GG2",  40, VoicedStop(300),   9),
+    ("EL",  190, Voiced(350),      11), ("BB2",  50, VoicedStop(300),   9),
+];
+
+/// Returns the name of the allophone with the given `code`.
+///
+/// Only the lowest 6 bits of `code` are taken into account.
+pub fn allophone_name(code: u8) -> &'static str {
+    ALLOPHONES[(code & 0x3F) as usize].0
+}
+
+/// The allophone queued for playback in the [Sp0256] speech processor.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+struct Utterance {
+    code: u8,
+    high: bool,
+    start: FTs,
+    end: FTs
+}
+
+/// The **SP0256-AL2** allophone speech processor emulator.
+///
+/// The processor has a single allophone input buffer. While an allophone is being spoken the next one can
+/// be written and it will be spoken immediately after the current one ends. Until that happens
+/// the processor [is busy][Sp0256::is_busy] and ignores any further allophones written.
+///
+/// Timestamps are the frame T-state counter values. The speech is rendered as square-wave pulses via
+/// [Sp0256::render_audio] and [Sp0256::next_frame] should be called after each frame.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "snapshot", serde(default))]
+pub struct Sp0256 {
+    cpu_hz: u32,
+    high_intonation: bool,
+    utterances: Vec<Utterance>,
+    tick_ts: FTs,
+    tick: u32,
+    noise: u16,
+    level: u8
+}
+
+impl Default for Sp0256 {
+    fn default() -> Self {
+        Sp0256::new(DEFAULT_CPU_HZ)
+    }
+}
+
+impl Sp0256 {
+    /// Creates a new instance with the allophone timing calculated for the given CPU clock in Hz.
+    ///
+    /// # Panics
+    /// Panics if `cpu_hz` is less than the rate of the processor output samples: 10 kHz.
+    pub fn new(cpu_hz: u32) -> Self {
+        assert!(cpu_hz >= SAMPLE_RATE, "the CPU clock is too low");
+        Sp0256 {
+            cpu_hz,
+            high_intonation: false,
+            utterances: Vec::new(),
+            tick_ts: 0,
+            tick: 0,
+            noise: 1,
+            level: 0
+        }
+    }
+    /// Returns the CPU clock in Hz used to calculate the allophone timing.
+    pub fn cpu_hz(&self) -> u32 {
+        self.cpu_hz
+    }
+    /// Returns `true` if the high intonation is selected.
+    pub fn is_high_intonation(&self) -> bool {
+        self.high_intonation
+    }
+    /// Selects the high (`true`) or the normal (`false`) intonation for the subsequently written allophones.
+    ///
+    /// The high intonation is achieved by increasing the processor clock, so the allophones are also
+    /// spoken slightly faster.
+    pub fn set_intonation(&mut self, high: bool) {
+        self.high_intonation = high;
+    }
+    /// Stops speaking and clears the input buffer.
+    pub fn reset(&mut self) {
+        self.utterances.clear();
+    }
+    /// Writes the allophone `code` at the given `timestamp`.
+    ///
+    /// Only the lowest 6 bits of `code` are taken into account. If the processor is speaking the allophone
+    /// is latched in the input buffer and will be spoken after the current one ends.
+    ///
+    /// Returns `false` and ignores the allophone if the processor [is busy][Sp0256::is_busy], that is
+    /// the input buffer already holds the allophone waiting to be spoken. Otherwise returns `true`.
+    pub fn write_allophone(&mut self, code: u8, timestamp: FTs) -> bool {
+        if self.is_busy(timestamp) {
+            return false
+        }
+        let code = code & 0x3F;
+        let high = self.high_intonation;
+        let start = self.utterances.last().map_or(timestamp, |utt| utt.end.max(timestamp));
+        let end = start + self.allophone_tstates(code, high);
+        self.utterances.push(Utterance { code, high, start, end });
+        true
+    }
+    /// Returns `true` if the input buffer is full at the given `timestamp`, that is while the last
+    /// written allophone is waiting for the current one to end.
+    ///
+    /// This is the inverted state of the processor's `LRQ` (load request) output.
+    pub fn is_busy(&self, timestamp: FTs) -> bool {
+        match self.utterances.last() {
+            Some(utt) => utt.start > timestamp,
+            None => false
+        }
+    }
+    /// Returns `true` if the processor is speaking at the given `timestamp`.
+    pub fn is_speaking(&self, timestamp: FTs) -> bool {
+        self.utterances.iter().any(|utt| (utt.start..utt.end).contains(&timestamp))
+    }
+    /// Renders the speech up to `end_ts` as square-wave pulses via [Blep] interface.
+    ///
+    /// Provide [AmpLevels] that can handle `level` values from 0 to 15 (4-bits).
+    /// `channel` - target [Blep] audio channel.
+    pub fn render_audio<L: AmpLevels<B::SampleDelta>, B: Blep>(
+            &mut self,
+            blep: &mut B,
+            end_ts: FTs,
+            channel: usize
+        )
+    {
+        let prev_level = self.level;
+        let mut changes: Vec<(FTs, u8)> = Vec::new();
+        let tick_tstates = self.tick_tstates();
+        while self.tick_ts < end_ts {
+            let ts = self.tick_ts;
+            let utterance = self.utterances.iter().find(|utt| ts < utt.end).copied();
+            match utterance {
+                Some(utt) if utt.start <= ts => {
+                    let level = self.sample(&utt, ts);
+                    if level != self.level {
+                        self.level = level;
+                        changes.push((ts, level));
+                    }
+                    self.tick_ts += tick_tstates;
+                    self.tick = self.tick.wrapping_add(1);
+                    self.noise = next_noise(self.noise);
+                }
+                next => {
+                    if self.level != 0 {
+                        self.level = 0;
+                        changes.push((ts, 0));
+                    }
+                    // skip the silence
+                    let silence_end = next.map_or(end_ts, |utt| utt.start.min(end_ts));
+                    let ticks = (silence_end - ts + tick_tstates - 1) / tick_tstates;
+                    self.tick_ts += ticks * tick_tstates;
+                }
+            }
+        }
+        render_audio_frame_ts::<L,_,_,_>(prev_level, None, &changes, blep, channel);
+    }
+    /// Should be called after each frame to prepare for the next one.
+    ///
+    /// The allophones being spoken are carried over to the next frame.
+    /// Any unrendered audio of the ending frame is skipped.
+    pub fn next_frame(&mut self, eof_timestamp: FTs) {
+        self.tick_ts = self.tick_ts.max(eof_timestamp) - eof_timestamp;
+        self.utterances.retain(|utt| utt.end > eof_timestamp);
+        for utt in self.utterances.iter_mut() {
+            utt.start -= eof_timestamp;
+            utt.end -= eof_timestamp;
+        }
+    }
+
+    fn clock_khz(high: bool) -> u32 {
+        if high { CLOCK_HIGH_KHZ } else { CLOCK_NORMAL_KHZ }
+    }
+
+    fn allophone_tstates(&self, code: u8, high: bool) -> FTs {
+        let ms = ALLOPHONES[code as usize].1 as u64;
+        let tstates = ms * self.cpu_hz as u64 * CLOCK_NORMAL_KHZ as u64
+                      / (1000 * Self::clock_khz(high) as u64);
+        tstates as FTs
+    }
+
+    fn tick_tstates(&self) -> FTs {
+        ((self.cpu_hz + SAMPLE_RATE / 2) / SAMPLE_RATE) as FTs
+    }
+
+    fn sample(&self, utt: &Utterance, ts: FTs) -> u8 {
+        let (_, _, sound, peak) = ALLOPHONES[utt.code as usize];
+        let pitch_ticks = SAMPLE_RATE * CLOCK_NORMAL_KHZ / (PITCH_HZ * Self::clock_khz(utt.high));
+        let pitch_phase = self.tick % pitch_ticks;
+        let is_glottis_open = pitch_phase < pitch_ticks * 3 / 5;
+        let voiced = |f1: u16| {
+            let half_period = (SAMPLE_RATE / (2 * f1 as u32)).max(1);
+            if is_glottis_open && (pitch_phase / half_period) & 1 == 0 {
+                // the glottal pulse decays
+                peak - (peak as u32 * pitch_phase / pitch_ticks / 2) as u8
+            }
+            else {
+                0
+            }
+        };
+        let noise = || if self.noise & 1 == 1 { peak } else { 0 };
+        let is_released = (ts - utt.start) * 5 >= (utt.end - utt.start) * 3;
+        match sound {
+            Pause => 0,
+            Voiced(f1) => voiced(f1),
+            Noise => noise(),
+            VoicedNoise(f1) if is_glottis_open => voiced(f1).max(noise()),
+            VoicedNoise(_) => noise() / 2,
+            Stop if is_released => noise(),
+            VoicedStop(f1) if is_released => voiced(f1),
+            Stop|VoicedStop(_) => 0
+        }
+    }
+}
+
+/// Returns the next state of the 15-bit noise generator.
+#[inline]
+fn next_noise(noise: u16) -> u16 {
+    let bit = (noise ^ (noise >> 1)) & 1;
+    (noise >> 1) | (bit << 14)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct TestBlep(Vec<(usize, FTs, i32)>);
+
+    impl Blep for TestBlep {
+        type SampleDelta = i32;
+        fn ensure_frame_time(&mut self, _sample_rate: u32, _ts_rate: f64, _frame_ts: FTs, _margin_ts: FTs) {}
+        fn end_frame(&mut self, _timestamp: FTs) -> usize { 0 }
+        fn add_step(&mut self, channel: usize, timestamp: FTs, delta: i32) {
+            self.0.push((channel, timestamp, delta));
+        }
+    }
+
+    struct TestAmps;
+
+    impl AmpLevels<i32> for TestAmps {
+        fn amp_level(level: u32) -> i32 {
+            level as i32 * 100
+        }
+    }
+
+    #[test]
+    fn sp0256_works() {
+        const FRAME_TS: FTs = 69888;
+        assert_eq!("AA", allophone_name(24));
+        assert_eq!("PA1", allophone_name(64));
+        let mut sp = Sp0256::default();
+        assert!(!sp.is_busy(0));
+        assert!(!sp.is_speaking(0));
+        let mut blep = TestBlep::default();
+        sp.render_audio::<TestAmps,_>(&mut blep, FRAME_TS, 1);
+        assert!(blep.0.is_empty());
+        sp.next_frame(FRAME_TS);
+        // AA 100 ms
+        assert!(sp.write_allophone(24, 1000));
+        assert!(!sp.is_busy(1000));
+        assert!(sp.is_speaking(1000));
+        // PA1 10 ms
+        assert!(sp.write_allophone(0, 2000));
+        assert!(sp.is_busy(2000));
+        // the input buffer is full
+        assert!(!sp.write_allophone(24, 3000));
+        assert!(sp.is_busy(3000));
+        assert_eq!(sp.utterances.len(), 2);
+        sp.render_audio::<TestAmps,_>(&mut blep, FRAME_TS, 1);
+        assert!(blep.0.len() > 10);
+        assert!(blep.0.windows(2).all(|w| w[0].1 < w[1].1));
+        assert!(blep.0.iter().all(|&(_, ts, _)| (1000..FRAME_TS).contains(&ts)));
+        sp.next_frame(FRAME_TS);
+        let mut offset = FRAME_TS;
+        let aa_end = 1000 + 350_000;
+        for _ in 0..5 {
+            let ts = aa_end - offset;
+            if (0..FRAME_TS).contains(&ts) {
+                assert!(sp.is_busy(ts - 1));
+                assert!(!sp.is_busy(ts));
+                assert!(sp.is_speaking(ts));
+            }
+            sp.render_audio::<TestAmps,_>(&mut blep, FRAME_TS, 1);
+            sp.next_frame(FRAME_TS);
+            offset += FRAME_TS;
+        }
+        assert!(!sp.is_busy(0));
+        assert!(!sp.is_speaking(0));
+        assert!(blep.0.iter().all(|&(ch, _, delta)| ch == 1 && delta != 0));
+        // the speech ends with silence
+        assert_eq!(0, blep.0.iter().map(|&(_, _, delta)| delta).sum::<i32>());
+        assert!(sp.write_allophone(24, 0));
+        sp.reset();
+        assert!(!sp.is_speaking(0));
+    }
+}
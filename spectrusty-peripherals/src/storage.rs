@@ -6,4 +6,5 @@
     For the full copyright notice, see the lib.rs file.
 */
 //! Data storage related.
+pub mod ata;
 pub mod microdrives;
@@ -0,0 +1,434 @@
+/*
+    Copyright (C) 2020-2023  Rafal Michalski
+
+    This file is part of SPECTRUSTY, a Rust library for building emulators.
+
+    For the full copyright notice, see the lib.rs file.
+*/
+//! An emulator of the ATA (IDE) device backed by a raw disk or CompactFlash card image.
+use core::fmt;
+use std::io::{self, Read, Write, Seek, SeekFrom};
+
+#[allow(unused_imports)]
+use log::{error, warn, info, debug, trace};
+
+/// The size of the ATA sector in bytes.
+pub const SECTOR_SIZE: usize = 512;
+/// The number of heads of the emulated drive geometry.
+pub const ATA_HEADS: u16 = 16;
+/// The number of sectors per track of the emulated drive geometry.
+pub const ATA_SECTORS_PER_TRACK: u16 = 63;
+/// The maximum number of cylinders of the emulated drive geometry.
+pub const ATA_MAX_CYLINDERS: u16 = 16383;
+
+/// The `IDENTIFY DEVICE` command code.
+pub const CMD_IDENTIFY_DEVICE: u8 = 0xEC;
+/// The `READ SECTOR(S)` command code.
+pub const CMD_READ_SECTORS: u8 = 0x20;
+/// The `WRITE SECTOR(S)` command code.
+pub const CMD_WRITE_SECTORS: u8 = 0x30;
+
+/// The status register bit: the device is busy.
+pub const STATUS_BSY: u8 = 0b1000_0000;
+/// The status register bit: the device is ready.
+pub const STATUS_DRDY: u8 = 0b0100_0000;
+/// The status register bit: the device seek is complete.
+pub const STATUS_DSC: u8 = 0b0001_0000;
+/// The status register bit: the device is ready to transfer data.
+pub const STATUS_DRQ: u8 = 0b0000_1000;
+/// The status register bit: an error occurred, see the error register.
+pub const STATUS_ERR: u8 = 0b0000_0001;
+
+/// The error register bit: an uncorrectable data error.
+pub const ERROR_UNC: u8 = 0b0100_0000;
+/// The error register bit: the requested sector was not found.
+pub const ERROR_IDNF: u8 = 0b0001_0000;
+/// The error register bit: the command was aborted.
+pub const ERROR_ABRT: u8 = 0b0000_0100;
+
+const DEVICE_LBA: u8 = 0b0100_0000;
+const DEVICE_DRV: u8 = 0b0001_0000;
+const DEVICE_HEAD_MASK: u8 = 0b0000_1111;
+
+const IDENTIFY_MODEL: &[u8;40] = b"SPECTRUSTY CF CARD                      ";
+const IDENTIFY_SERIAL: &[u8;20] = b"00000000000000000001";
+const IDENTIFY_FIRMWARE: &[u8;8] = b"0.4     ";
+
+/// The ATA command block registers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AtaRegister {
+    /// The 16-bit data register, accessed 8 bits at a time, the lower byte first.
+    Data,
+    /// The error register when read, the features register when written to.
+    ErrorFeatures,
+    /// The sector count register.
+    SectorCount,
+    /// The sector number or LBA bits 0-7 register.
+    LbaLow,
+    /// The cylinder low or LBA bits 8-15 register.
+    LbaMid,
+    /// The cylinder high or LBA bits 16-23 register.
+    LbaHigh,
+    /// The device/head or LBA bits 24-27 register.
+    Device,
+    /// The status register when read, the command register when written to.
+    StatusCommand
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Transfer {
+    None,
+    Identify,
+    Read(u16),
+    Write(u16)
+}
+
+/// The ATA (IDE) master device backed by the raw image of a disk or a CompactFlash card.
+///
+/// The image `F` should implement [Read], [Write] and [Seek]. The image data is being accessed in
+/// 512 byte sectors and its size should be a multiple of the sector size.
+///
+/// Only the commands needed by the 8-bit interfaces' firmware are being implemented: `IDENTIFY DEVICE`,
+/// `READ SECTOR(S)`, `WRITE SECTOR(S)` and a few commands that are being accepted without any effect,
+/// e.g. `INITIALIZE DEVICE PARAMETERS` or `SET FEATURES`. The commands are being completed instantly.
+/// Both LBA and CHS addressing modes are supported.
+pub struct AtaDevice<F> {
+    image: Option<F>,
+    sectors: u32,
+    error: u8,
+    features: u8,
+    sector_count: u8,
+    lba: [u8;3],
+    device: u8,
+    status: u8,
+    transfer: Transfer,
+    index: usize,
+    buffer: Box<[u8;SECTOR_SIZE]>
+}
+
+impl<F> Default for AtaDevice<F> {
+    fn default() -> Self {
+        AtaDevice {
+            image: None,
+            sectors: 0,
+            error: 0,
+            features: 0,
+            sector_count: 1,
+            lba: [1, 0, 0],
+            device: 0,
+            status: STATUS_DRDY|STATUS_DSC,
+            transfer: Transfer::None,
+            index: 0,
+            buffer: Box::new([0;SECTOR_SIZE])
+        }
+    }
+}
+
+impl<F> fmt::Debug for AtaDevice<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AtaDevice")
+            .field("image", &self.image.is_some())
+            .field("sectors", &self.sectors)
+            .field("error", &self.error)
+            .field("sector_count", &self.sector_count)
+            .field("lba", &self.lba)
+            .field("device", &self.device)
+            .field("status", &self.status)
+            .field("transfer", &self.transfer)
+            .finish()
+    }
+}
+
+impl AtaRegister {
+    /// Returns the register addressed by the 3 lowest bits of `index`.
+    pub fn from_index(index: u8) -> Self {
+        match index & 7 {
+            0 => AtaRegister::Data,
+            1 => AtaRegister::ErrorFeatures,
+            2 => AtaRegister::SectorCount,
+            3 => AtaRegister::LbaLow,
+            4 => AtaRegister::LbaMid,
+            5 => AtaRegister::LbaHigh,
+            6 => AtaRegister::Device,
+            _ => AtaRegister::StatusCommand
+        }
+    }
+}
+
+impl<F> AtaDevice<F> {
+    /// Returns `true` if the image is mounted.
+    pub fn is_mounted(&self) -> bool {
+        self.image.is_some()
+    }
+    /// Removes the mounted image and returns it.
+    pub fn eject(&mut self) -> Option<F> {
+        self.sectors = 0;
+        self.reset();
+        self.image.take()
+    }
+    /// Returns a reference to the mounted image.
+    pub fn image_ref(&self) -> Option<&F> {
+        self.image.as_ref()
+    }
+    /// Returns the number of sectors of the mounted image.
+    pub fn sector_count(&self) -> u32 {
+        self.sectors
+    }
+    /// Returns the emulated drive geometry as a tuple of the number of cylinders, heads and sectors per track.
+    pub fn geometry(&self) -> (u16, u16, u16) {
+        let cylinders = self.sectors / (u32::from(ATA_HEADS) * u32::from(ATA_SECTORS_PER_TRACK));
+        (cylinders.min(ATA_MAX_CYLINDERS.into()) as u16, ATA_HEADS, ATA_SECTORS_PER_TRACK)
+    }
+    /// Resets the device registers and aborts any data transfer in progress.
+    pub fn reset(&mut self) {
+        let image = self.image.take();
+        let sectors = self.sectors;
+        *self = AtaDevice { image, sectors, ..Default::default() }
+    }
+
+    fn is_selected(&self) -> bool {
+        self.image.is_some() && self.device & DEVICE_DRV == 0
+    }
+
+    fn address(&self) -> Option<u32> {
+        let [low, mid, high] = self.lba;
+        let head = self.device & DEVICE_HEAD_MASK;
+        if self.device & DEVICE_LBA != 0 {
+            Some(u32::from_le_bytes([low, mid, high, head]))
+        }
+        else {
+            let cylinder = u32::from(u16::from_le_bytes([mid, high]));
+            let sector = u32::from(low).checked_sub(1)?;
+            if sector >= ATA_SECTORS_PER_TRACK.into() {
+                return None
+            }
+            Some((cylinder * u32::from(ATA_HEADS) + u32::from(head)) * u32::from(ATA_SECTORS_PER_TRACK) + sector)
+        }
+    }
+
+    fn set_address(&mut self, lba: u32) {
+        let [low, mid, high, head] = if self.device & DEVICE_LBA != 0 {
+            lba.to_le_bytes()
+        }
+        else {
+            let spt = u32::from(ATA_SECTORS_PER_TRACK);
+            let heads = u32::from(ATA_HEADS);
+            let [mid, high] = ((lba / spt / heads) as u16).to_le_bytes();
+            [(lba % spt + 1) as u8, mid, high, (lba / spt % heads) as u8]
+        };
+        self.lba = [low, mid, high];
+        self.device = (self.device & !DEVICE_HEAD_MASK) | (head & DEVICE_HEAD_MASK);
+    }
+
+    fn abort(&mut self, error: u8) {
+        self.error = error;
+        self.status = STATUS_DRDY|STATUS_DSC|STATUS_ERR;
+        self.transfer = Transfer::None;
+    }
+
+    fn identify(&mut self) {
+        fn put_str(buffer: &mut [u8], text: &[u8]) {
+            for (word, chars) in buffer.chunks_exact_mut(2).zip(text.chunks_exact(2)) {
+                word[0] = chars[1];
+                word[1] = chars[0];
+            }
+        }
+        let (cylinders, heads, spt) = self.geometry();
+        let capacity = u32::from(cylinders) * u32::from(heads) * u32::from(spt);
+        let [sectors_lo, sectors_hi] = [self.sectors as u16, (self.sectors >> 16) as u16];
+        let [capacity_lo, capacity_hi] = [capacity as u16, (capacity >> 16) as u16];
+        let buffer = &mut self.buffer[..];
+        buffer.fill(0);
+        for &(index, word) in &[
+            (0, 0x848A), (1, cylinders), (3, heads), (6, spt), (7, sectors_hi), (8, sectors_lo),
+            (47, 0x8001), (49, 0x0200), (51, 0x0200), (53, 0x0001),
+            (54, cylinders), (55, heads), (56, spt), (57, capacity_lo), (58, capacity_hi),
+            (60, sectors_lo), (61, sectors_hi)]
+        {
+            buffer[index * 2..index * 2 + 2].copy_from_slice(&u16::to_le_bytes(word));
+        }
+        put_str(&mut buffer[20..40], IDENTIFY_SERIAL);
+        put_str(&mut buffer[46..54], IDENTIFY_FIRMWARE);
+        put_str(&mut buffer[54..94], IDENTIFY_MODEL);
+        self.index = 0;
+        self.transfer = Transfer::Identify;
+        self.status = STATUS_DRDY|STATUS_DSC|STATUS_DRQ;
+    }
+
+    fn sector_position(&self) -> Option<(u32, u64)> {
+        let lba = self.address()?;
+        if lba < self.sectors {
+            Some((lba, u64::from(lba) * SECTOR_SIZE as u64))
+        }
+        else {
+            None
+        }
+    }
+}
+
+impl<F: Read + Write + Seek> AtaDevice<F> {
+    /// Mounts the image, replacing the previously mounted one.
+    ///
+    /// # Errors
+    /// Returns an error if the size of the image can't be determined.
+    pub fn mount(&mut self, mut image: F) -> io::Result<()> {
+        let size = image.seek(SeekFrom::End(0))?;
+        self.sectors = (size / SECTOR_SIZE as u64).min(u64::from(u32::MAX >> 4)) as u32;
+        self.image = Some(image);
+        self.reset();
+        Ok(())
+    }
+    /// Reads the given register.
+    pub fn read_register(&mut self, reg: AtaRegister) -> u8 {
+        if !self.is_selected() {
+            return 0
+        }
+        match reg {
+            AtaRegister::Data => self.read_data(),
+            AtaRegister::ErrorFeatures => self.error,
+            AtaRegister::SectorCount => self.sector_count,
+            AtaRegister::LbaLow => self.lba[0],
+            AtaRegister::LbaMid => self.lba[1],
+            AtaRegister::LbaHigh => self.lba[2],
+            AtaRegister::Device => self.device,
+            AtaRegister::StatusCommand => self.status
+        }
+    }
+    /// Writes the given register.
+    pub fn write_register(&mut self, reg: AtaRegister, data: u8) {
+        match reg {
+            AtaRegister::Data => if self.is_selected() { self.write_data(data) },
+            AtaRegister::ErrorFeatures => self.features = data,
+            AtaRegister::SectorCount => self.sector_count = data,
+            AtaRegister::LbaLow => self.lba[0] = data,
+            AtaRegister::LbaMid => self.lba[1] = data,
+            AtaRegister::LbaHigh => self.lba[2] = data,
+            AtaRegister::Device => self.device = data,
+            AtaRegister::StatusCommand => if self.is_selected() { self.command(data) }
+        }
+    }
+
+    fn command(&mut self, command: u8) {
+        self.error = 0;
+        self.status = STATUS_DRDY|STATUS_DSC;
+        self.transfer = Transfer::None;
+        let count = match self.sector_count {
+            0 => 256,
+            count => u16::from(count)
+        };
+        match command {
+            CMD_IDENTIFY_DEVICE => self.identify(),
+            0x20|0x21 => {
+                self.transfer = Transfer::Read(count);
+                self.read_sector();
+            }
+            0x30|0x31 => {
+                if self.sector_position().is_some() {
+                    self.index = 0;
+                    self.transfer = Transfer::Write(count);
+                    self.status |= STATUS_DRQ;
+                }
+                else {
+                    self.abort(ERROR_IDNF);
+                }
+            }
+            // RECALIBRATE, SEEK, INITIALIZE DEVICE PARAMETERS, power management, SET FEATURES
+            0x10..=0x1F|0x70..=0x7F|0x91|0xE0..=0xE5|0xEF => {}
+            _ => {
+                debug!("ATA: unsupported command: {:02x}", command);
+                self.abort(ERROR_ABRT);
+            }
+        }
+    }
+
+    fn read_sector(&mut self) {
+        let (lba, pos) = match self.sector_position() {
+            Some(position) => position,
+            None => return self.abort(ERROR_IDNF)
+        };
+        let (image, buffer) = (self.image.as_mut().unwrap(), &mut self.buffer[..]);
+        if let Err(err) = image.seek(SeekFrom::Start(pos))
+                               .and_then(|_| image.read_exact(buffer)) {
+            warn!("ATA: error reading sector {}: {}", lba, err);
+            return self.abort(ERROR_UNC)
+        }
+        self.index = 0;
+        self.status |= STATUS_DRQ;
+    }
+
+    fn write_sector(&mut self) -> bool {
+        let (lba, pos) = match self.sector_position() {
+            Some(position) => position,
+            None => {
+                self.abort(ERROR_IDNF);
+                return false
+            }
+        };
+        let (image, buffer) = (self.image.as_mut().unwrap(), &self.buffer[..]);
+        if let Err(err) = image.seek(SeekFrom::Start(pos))
+                               .and_then(|_| image.write_all(buffer)) {
+            warn!("ATA: error writing sector {}: {}", lba, err);
+            self.abort(ERROR_UNC);
+            return false
+        }
+        true
+    }
+
+    fn next_sector(&mut self, remaining: u16) {
+        self.sector_count = self.sector_count.wrapping_sub(1);
+        // the address registers point to the last transferred sector after the command is completed
+        if remaining > 1 {
+            if let Some(lba) = self.address() {
+                self.set_address(lba.wrapping_add(1));
+            }
+        }
+    }
+
+    fn read_data(&mut self) -> u8 {
+        let remaining = match self.transfer {
+            Transfer::Read(remaining) => Some(remaining),
+            Transfer::Identify => None,
+            _ => return u8::MAX
+        };
+        let data = self.buffer[self.index];
+        self.index += 1;
+        if self.index == SECTOR_SIZE {
+            self.status &= !STATUS_DRQ;
+            self.transfer = Transfer::None;
+            if let Some(remaining) = remaining {
+                self.next_sector(remaining);
+                if remaining > 1 {
+                    self.transfer = Transfer::Read(remaining - 1);
+                    self.read_sector();
+                }
+            }
+        }
+        data
+    }
+
+    fn write_data(&mut self, data: u8) {
+        let remaining = match self.transfer {
+            Transfer::Write(remaining) => remaining,
+            _ => return
+        };
+        self.buffer[self.index] = data;
+        self.index += 1;
+        if self.index == SECTOR_SIZE {
+            self.index = 0;
+            self.status &= !STATUS_DRQ;
+            self.transfer = Transfer::None;
+            if self.write_sector() {
+                self.next_sector(remaining);
+                if remaining > 1 {
+                    if self.sector_position().is_some() {
+                        self.transfer = Transfer::Write(remaining - 1);
+                        self.status |= STATUS_DRQ;
+                    }
+                    else {
+                        self.abort(ERROR_IDNF);
+                    }
+                }
+            }
+        }
+    }
+}
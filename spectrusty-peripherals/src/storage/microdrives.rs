@@ -73,6 +73,8 @@ pub const MAX_SECTORS: usize = 256;
 pub const MAX_USABLE_SECTORS: usize = 254;
 /// The maximum number of drives that the ZX Interface 1 software can handle.
 pub const MAX_DRIVES: usize = 8;
+/// The nominal rotation speed of the Microdrive tape.
+pub const NOMINAL_ROTATION_SPEED: f64 = 1.0;
 
 /// The size of the sector header in bytes, excluding the 12 preamble bytes.
 pub const HEAD_SIZE: usize = 15;
@@ -128,7 +130,11 @@ pub struct MicroCartridge {
 /// Implementation of this type emulates ZX Microdrives.
 ///
 /// Used by [ZX Interface 1][crate::bus::zxinterface1::ZxInterface1BusDevice] emulator.
-#[derive(Clone, Default, Debug)]
+///
+/// The tape moves under the drive head with the speed given by [ZxMicrodrives::rotation_speed].
+/// Sector headers and data blocks become available only after the preceding gaps and preambles have
+/// passed, so the number of T-states needed to reach the next sector is proportional to the tape speed.
+#[derive(Clone, Debug)]
 #[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
 #[cfg_attr(feature = "snapshot", serde(rename_all = "camelCase"))]
 pub struct ZxMicrodrives<T> {
@@ -137,7 +143,16 @@ pub struct ZxMicrodrives<T> {
     erase: bool,
     comms_clk: bool,
     motor_on_drive: Option<NonZeroU8>,
-    last_ts: T
+    last_ts: T,
+    #[cfg_attr(feature = "snapshot", serde(default = "nominal_rotation_speed"))]
+    rotation_speed: f64,
+    #[cfg_attr(feature = "snapshot", serde(default))]
+    tape_ts_fract: f64
+}
+
+#[cfg(feature = "snapshot")]
+fn nominal_rotation_speed() -> f64 {
+    NOMINAL_ROTATION_SPEED
 }
 
 #[derive(Clone, Copy, Default, Debug, PartialEq)]
@@ -175,6 +190,21 @@ impl Default for Sector {
     }
 }
 
+impl<T: Default> Default for ZxMicrodrives<T> {
+    fn default() -> Self {
+        ZxMicrodrives {
+            drives: Default::default(),
+            write: false,
+            erase: false,
+            comms_clk: false,
+            motor_on_drive: None,
+            last_ts: T::default(),
+            rotation_speed: NOMINAL_ROTATION_SPEED,
+            tape_ts_fract: 0.0
+        }
+    }
+}
+
 impl Default for TapeCursor {
     fn default() -> Self {
         TapeCursor {
@@ -240,6 +270,9 @@ impl MicroCartridge {
         self.protec
     }
     /// Changes the write protected flag of the cartridge.
+    ///
+    /// Emulates the physical write-protect tab: the [ZxMicrodrives] will not erase nor write any data
+    /// to the protected cartridge and the ZX Interface 1 status port will report its protection.
     #[inline]
     pub fn set_write_protected(&mut self, protect: bool) {
         self.protec = protect;
@@ -402,6 +435,9 @@ impl MicroCartridge {
     // called when erasing began
     fn erase_start(&mut self, delta_ts: u32) {
         self.forward(delta_ts);
+        if self.protec { // the write-protect tab disables the erase head
+            return
+        }
         let TapeCursor { sector, secpos, .. } = self.tape_cursor;
         self.written = None;
         if self.is_sector_formatted(sector) {
@@ -419,6 +455,9 @@ impl MicroCartridge {
     fn erase_forward(&mut self, delta_ts: u32) {
         let prev_cursor = self.tape_cursor;
         self.forward(delta_ts);
+        if self.protec {
+            return
+        }
         let TapeCursor { sector, secpos, .. } = self.tape_cursor;
         if prev_cursor.sector != sector { // clear all previous sectors
             if delta_ts >= SECTOR_TS * (self.sectors.len() as u32 - 1) {
@@ -481,6 +520,10 @@ impl MicroCartridge {
     }
 
     fn write_data_forward(&mut self, data: u8, delta_ts: u32) -> u16 {
+        if self.protec { // data is being shifted out but nothing gets recorded
+            self.forward(delta_ts);
+            return (BYTE_TS - self.tape_cursor.cursor % BYTE_TS) as u16
+        }
         if let Some(written) = self.written {
             self.written = NonZeroU16::new(written.get().saturating_add(1));
             self.forward(delta_ts);
@@ -602,6 +645,25 @@ impl MicroCartridge {
         self.tape_cursor.forward(delta_ts, self.sectors.len() as u32);
     }
 
+    // finds the first formatted sector passing under the head with the given header number
+    // or just the next formatted sector, and moves the tape just past its data block
+    fn instant_seek_sector(&mut self, number: Option<u8>) -> Option<u8> {
+        let TapeCursor { sector, secpos, .. } = self.tape_cursor;
+        let seclen = self.sectors.len() as u32;
+        let start = match secpos {
+            SecPosition::Preamble1(..) => sector,
+            _ => TapeCursor::add_sectors(sector, 1, seclen)
+        };
+        let sector = (0..seclen).map(|delta| TapeCursor::add_sectors(start, delta, seclen))
+            .find(|&sector| self.is_sector_formatted(sector) && match number {
+                Some(n) => self.sectors[sector as usize].head[1] == n,
+                None => true
+            })?;
+        self.tape_cursor = TapeCursor { cursor: GAP2_START, sector, secpos: SecPosition::Gap2 };
+        self.written = None;
+        Some(sector)
+    }
+
     #[inline]
     fn gap_syn_protect(&self) -> CartridgeState {
         let mut gap = false;
@@ -628,6 +690,28 @@ impl MicroCartridge {
 }
 
 impl<T> ZxMicrodrives<T> {
+    /// Returns the current tape rotation speed relative to the nominal speed.
+    #[inline]
+    pub fn rotation_speed(&self) -> f64 {
+        self.rotation_speed
+    }
+    /// Changes the tape rotation speed relative to the [nominal][NOMINAL_ROTATION_SPEED] speed.
+    ///
+    /// E.g. `2.0` makes the tape move twice as fast, so sectors become available in half of the
+    /// nominal time, and `0.5` makes the tape move twice as slow.
+    ///
+    /// # Panics
+    /// Panics if `speed` is not a finite positive number.
+    pub fn set_rotation_speed(&mut self, speed: f64) {
+        assert!(speed.is_finite() && speed > 0.0, "rotation speed must be a finite positive number");
+        self.rotation_speed = speed;
+        self.tape_ts_fract = 0.0;
+    }
+    /// Returns the number of T-states needed for a single sector to pass under the drive head
+    /// with the current tape rotation speed.
+    pub fn sector_ts(&self) -> FTs {
+        (SECTOR_TS as f64 / self.rotation_speed).round() as FTs
+    }
     /// Inserts a `cartridge` into the `drive_index` optionally returning a cartridge
     /// that was previously in the same drive.
     ///
@@ -689,6 +773,47 @@ impl<T> ZxMicrodrives<T> {
         })
     }
 
+    /// Instantly reads a sector from a cartridge in the `drive_index`, bypassing the tape timing.
+    ///
+    /// If `number` is `Some` the sector with a matching header number is being looked for, otherwise
+    /// the next formatted sector is read. The sectors are searched in the order they would pass
+    /// under the drive head. The tape is moved just past the data block of the found sector.
+    ///
+    /// Returns `None` if there is no cartridge in the drive or no matching sector could be found.
+    ///
+    /// # Panics
+    /// Panics if the `drive_index` is above 7.
+    pub fn instant_read_sector(&mut self, drive_index: usize, number: Option<u8>) -> Option<&Sector> {
+        assert!(drive_index < MAX_DRIVES);
+        let cartridge = self.drives[drive_index].as_mut()?;
+        let sector = cartridge.instant_seek_sector(number)?;
+        Some(&cartridge.sectors[sector as usize])
+    }
+    /// Instantly writes the `data` block to a sector with a matching header `number` of a cartridge
+    /// in the `drive_index`, bypassing the tape timing.
+    ///
+    /// The sector header is left unmodified. The tape is moved just past the data block of the written
+    /// sector.
+    ///
+    /// Returns `false` if there is no cartridge in the drive, the cartridge is write protected or no
+    /// matching sector could be found.
+    ///
+    /// # Panics
+    /// Panics if the `drive_index` is above 7.
+    pub fn instant_write_sector(&mut self, drive_index: usize, number: u8, data: &[u8;DATA_SIZE]) -> bool {
+        assert!(drive_index < MAX_DRIVES);
+        match self.drives[drive_index].as_mut() {
+            Some(cartridge) if !cartridge.protec => {
+                if let Some(sector) = cartridge.instant_seek_sector(Some(number)) {
+                    cartridge.sectors[sector as usize].data = *data;
+                    return true
+                }
+                false
+            }
+            _ => false
+        }
+    }
+
     fn current_drive(&mut self) -> Option<&mut MicroCartridge> {
         self.motor_on_drive.and_then(move |drive_on|
             self.drives[(drive_on.get() - 1) as usize & 7].as_mut()
@@ -709,11 +834,24 @@ impl<T> ZxMicrodrives<T> {
 }
 
 impl<T: TimestampOps> ZxMicrodrives<T> {
+    // returns the number of T-states the tape has moved since the last update
     fn vts_diff_update(&mut self, timestamp: T) -> u32 {
         let delta_ts = timestamp.diff_from(self.last_ts);
         self.last_ts = timestamp;
         debug_assert!(delta_ts >= 0);
-        delta_ts as u32
+        if self.rotation_speed == NOMINAL_ROTATION_SPEED {
+            return delta_ts as u32
+        }
+        let tape_ts = delta_ts as f64 * self.rotation_speed + self.tape_ts_fract;
+        self.tape_ts_fract = tape_ts.fract();
+        tape_ts.trunc() as u32
+    }
+    // converts the tape delay to the CPU delay
+    fn cpu_delay(&self, delay: u16) -> u16 {
+        if self.rotation_speed == NOMINAL_ROTATION_SPEED {
+            return delay
+        }
+        (delay as f64 / self.rotation_speed).ceil().min(u16::MAX as f64) as u16
     }
 
     pub(crate) fn reset(&mut self, timestamp: T) {
@@ -815,7 +953,8 @@ impl<T: TimestampOps> ZxMicrodrives<T> {
         let delta_ts = self.vts_diff_update(timestamp);
         if self.write && self.erase { // what happens when write is on and erase off?
             if let Some(cartridge) = self.current_drive() {
-                return cartridge.write_data_forward(data, delta_ts);
+                let delay = cartridge.write_data_forward(data, delta_ts);
+                return self.cpu_delay(delay)
             }
         }
         0
@@ -831,7 +970,7 @@ impl<T: TimestampOps> ZxMicrodrives<T> {
         if !(self.write || self.erase) {
             if let Some(cartridge) = self.current_drive() {
                 let (data, delay) = cartridge.read_data_forward(delta_ts);
-                return (data, NonZeroU16::new(delay))
+                return (data, NonZeroU16::new(self.cpu_delay(delay)))
             }
         }
         // we could hang Spectrum here according to ZX Interface 1 IN 0 bug.
@@ -872,6 +1011,148 @@ mod tests {
         }
     }
 
+    // returns timestamps of the rising edges of the SYNC signal
+    fn find_sync_edges(drive: &mut TestMicrodrives, mut ts: FTs, count: usize) -> Vec<FTs> {
+        let mut edges = Vec::new();
+        let mut syn = drive.read_state(ts).syn;
+        while edges.len() < count {
+            ts += 20;
+            let state = drive.read_state(ts);
+            if state.syn && !syn {
+                edges.push(ts);
+            }
+            syn = state.syn;
+        }
+        edges
+    }
+
+    #[test]
+    fn microdrives_write_protect_works() {
+        let mut drive: TestMicrodrives = Default::default();
+        let mut sector = Sector::default();
+        sector.head[1] = 0x55;
+        sector.data[0] = 0xAA;
+        let mut cartridge = MicroCartridge::new_with_sectors(vec![sector;3], false, 3);
+        assert!(!cartridge.is_write_protected());
+        cartridge.set_write_protected(true);
+        assert!(cartridge.is_write_protected());
+        drive.replace_cartridge(0, cartridge);
+        drive.write_control(0, false, false, true, true);
+        assert!(drive.read_state(10).write_protect);
+        // attempt to erase and write over the whole tape
+        drive.write_control(20, true, false, false, false);
+        drive.write_control(30, true, true, false, false);
+        let mut ts = 30;
+        for _ in 0..SECTOR_TS as FTs * 3 / BYTE_TS as FTs {
+            let delay = drive.write_data(0, ts);
+            assert!(delay > 0 && delay as u32 <= BYTE_TS);
+            ts += delay as FTs;
+        }
+        drive.write_control(ts, true, false, false, false);
+        drive.write_control(ts + SECTOR_TS as FTs * 3, false, false, false, false);
+        let cartridge = drive.current_drive().unwrap();
+        assert_eq!(cartridge.count_formatted(), 3);
+        for sector in cartridge.iter_with_indices().map(|(_, sector)| sector) {
+            assert_eq!(sector.head[1], 0x55);
+            assert_eq!(sector.data[0], 0xAA);
+        }
+        // the data can still be read
+        let edges = find_sync_edges(&mut drive, ts + SECTOR_TS as FTs * 3, 1);
+        let (_, delay) = drive.read_data(edges[0] + 1);
+        let ts = edges[0] + 1 + delay.unwrap().get() as FTs;
+        let (data, _) = drive.read_data(ts);
+        assert_eq!(data, 0x55);
+        let cartridge = drive.current_drive().unwrap();
+        cartridge.set_write_protected(false);
+        assert!(!drive.read_state(ts + 1).write_protect);
+    }
+
+    #[test]
+    fn microdrives_instant_sector_access_works() {
+        let sectors: Vec<Sector> = (0..5u8).map(|i| {
+            let mut sector = Sector::default();
+            sector.head[1] = 5 - i;
+            for (j, p) in sector.data.iter_mut().enumerate() {
+                *p = (j as u8) ^ i;
+            }
+            sector
+        }).collect();
+        // the timed path: wait for the data block of the sector number 2 and read it
+        let mut drive: TestMicrodrives = Default::default();
+        drive.replace_cartridge(0, MicroCartridge::new_with_sectors(sectors.clone(), false, 5));
+        drive.write_control(0, false, false, true, true);
+        let edges = find_sync_edges(&mut drive, 0, 8);
+        let mut ts = edges[7] + 1;
+        let mut data = Vec::new();
+        for _ in 0..DATA_SIZE {
+            let (byte, delay) = drive.read_data(ts);
+            data.push(byte);
+            ts += delay.unwrap().get() as FTs;
+        }
+        assert_eq!(&data[..], &sectors[3].data[..]);
+        assert!(ts > 3 * SECTOR_TS as FTs);
+        // the instant path doesn't need the motor and takes no time
+        let mut drive: TestMicrodrives = Default::default();
+        assert!(drive.instant_read_sector(0, None).is_none());
+        drive.replace_cartridge(1, MicroCartridge::new_with_sectors(sectors.clone(), false, 5));
+        let sector = drive.instant_read_sector(1, Some(2)).unwrap();
+        assert_eq!(&sector.data[..], &sectors[3].data[..]);
+        assert_eq!(drive.last_ts, 0);
+        assert!(drive.instant_read_sector(1, Some(6)).is_none());
+        // the next sectors in the order of the tape movement
+        assert_eq!(drive.instant_read_sector(1, None).unwrap().head[1], 1);
+        assert_eq!(drive.instant_read_sector(1, None).unwrap().head[1], 5);
+        let cartridge = drive.cartridge_at(1).unwrap();
+        assert!(cartridge.head_at() > 0.5 && cartridge.head_at() < 1.0);
+        let mut block = [0u8;DATA_SIZE];
+        block[0] = 0xA5;
+        assert!(drive.instant_write_sector(1, 2, &block));
+        assert_eq!(drive.instant_read_sector(1, None).unwrap().head[1], 1);
+        assert_eq!(&drive.instant_read_sector(1, Some(2)).unwrap().data[..], &block[..]);
+        assert!(!drive.instant_write_sector(1, 6, &block));
+        drive.take_cartridge(1).unwrap();
+        let mut cartridge = MicroCartridge::new_with_sectors(sectors.clone(), false, 5);
+        cartridge.set_write_protected(true);
+        drive.replace_cartridge(1, cartridge);
+        assert!(!drive.instant_write_sector(1, 2, &block));
+        assert_eq!(&drive.instant_read_sector(1, Some(2)).unwrap().data[..], &sectors[3].data[..]);
+    }
+
+    #[test]
+    fn microdrives_rotation_speed_works() {
+        for &speed in &[1.0, 2.0, 0.5, 1.5] {
+            let mut drive: TestMicrodrives = Default::default();
+            assert_eq!(drive.rotation_speed(), NOMINAL_ROTATION_SPEED);
+            drive.set_rotation_speed(speed);
+            assert_eq!(drive.rotation_speed(), speed);
+            let sector_ts = drive.sector_ts();
+            assert_eq!(sector_ts, (SECTOR_TS as f64 / speed).round() as FTs);
+            let cartridge = MicroCartridge::new_with_sectors(vec![Sector::default();3], false, 3);
+            drive.replace_cartridge(0, cartridge);
+            // turn on the motor of the first drive
+            drive.write_control(0, false, false, true, true);
+            // header and data preambles of each sector
+            let edges = find_sync_edges(&mut drive, 0, 5);
+            // the signals are sampled with a single byte precision
+            let tolerance = (BYTE_TS as f64 / speed).ceil() as FTs;
+            for (head, next_head) in edges.iter().step_by(2).zip(edges.iter().skip(2).step_by(2)) {
+                let delta = next_head - head;
+                assert!((delta - sector_ts).abs() <= tolerance, "{} {} {}", speed, delta, sector_ts);
+            }
+            let head_to_data = edges[1] - edges[0];
+            let expected = ((DATA_SYN_END - HEAD_SYN_END) as f64 / speed).round() as FTs;
+            assert!((head_to_data - expected).abs() <= tolerance, "{} {} {}", speed, head_to_data, expected);
+            // reading a byte takes a proportional amount of time
+            let ts = edges[4] + 1;
+            let (_, delay) = drive.read_data(ts);
+            let ts = ts + delay.unwrap().get() as FTs;
+            let (_, delay) = drive.read_data(ts);
+            let delay = delay.unwrap().get() as FTs;
+            let expected = (BYTE_TS as f64 / speed).ceil() as FTs;
+            assert!((delay - expected).abs() <= 1, "{} {} {}", speed, delay, expected);
+        }
+    }
+
     #[test]
     fn microdrives_works() {
         let mut drive: TestMicrodrives = Default::default();
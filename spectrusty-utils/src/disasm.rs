@@ -0,0 +1,114 @@
+/*
+    Copyright (C) 2020-2023  Rafal Michalski
+
+    This file is part of SPECTRUSTY, a Rust library for building emulators.
+
+    For the full copyright notice, see the lib.rs file.
+*/
+//! Disassembling of the Z80 machine code directly from the emulated memory.
+//!
+//! The functions in this module follow the current memory paging, so they can be used by debuggers
+//! to show the code as the CPU would see it.
+//!
+//! ```
+//! use spectrusty::memory::{Memory48k, ZxMemory};
+//! use spectrusty_utils::disasm::disassemble;
+//!
+//! let mut mem = Memory48k::default();
+//! mem.load_into(0x8000, &[0xDD, 0x21, 0x34, 0x12]).unwrap();
+//! assert_eq!(disassemble(&mem, 0x8000), ("LD IX, 1234h".to_string(), 4));
+//! ```
+use core::ops::Range;
+use spectrusty::memory::ZxMemory;
+use spectrusty::z80emu::{Z80NMOS, CpuDebug, disasm::disasm_memory};
+
+/// The maximum number of bytes a single Z80 instruction may occupy.
+pub const MAX_INSTRUCTION_SIZE: usize = 4;
+
+/// The mnemonic returned for a prefix byte which is not followed by an instruction it could modify.
+///
+/// Such a prefix is ignored by the CPU and behaves like `NOP` which doesn't accept interrupts.
+pub const IGNORED_PREFIX_MNEMONIC: &str = "NONI";
+
+/// Returns the debug information of an instruction at the given `address` of the paged-in `memory`.
+///
+/// Returns `None` if the byte at `address` is a prefix being ignored by the CPU.
+pub fn disassemble_debug<M: ZxMemory + ?Sized>(memory: &M, address: u16) -> Option<CpuDebug> {
+    let mut code = [0u8; MAX_INSTRUCTION_SIZE];
+    for (addr, p) in (0..).map(|offs| address.wrapping_add(offs)).zip(code.iter_mut()) {
+        *p = memory.read(addr);
+    }
+    match disasm_memory::<Z80NMOS, _, _>(address, &code, Err) {
+        Err(deb) if deb.pc == address => Some(deb),
+        _ => None
+    }
+}
+
+/// Disassembles an instruction at the given `address` of the paged-in `memory`.
+///
+/// Returns the instruction text and the number of bytes the instruction occupies.
+/// The instruction arguments are formatted as hexadecimal numbers.
+///
+/// Prefix bytes being ignored by the CPU are disassembled as [IGNORED_PREFIX_MNEMONIC] with the size of 1.
+pub fn disassemble<M: ZxMemory + ?Sized>(memory: &M, address: u16) -> (String, usize) {
+    match disassemble_debug(memory, address) {
+        Some(deb) => {
+            let mut text = format!("{} {:x}", deb.mnemonic, deb.args);
+            text.truncate(text.trim_end().len());
+            (text, deb.code.len())
+        }
+        None => (IGNORED_PREFIX_MNEMONIC.to_string(), 1)
+    }
+}
+
+/// Disassembles instructions starting from the given address `range.start`, until the address
+/// of the next instruction reaches `range.end`.
+///
+/// Returns a vector of instruction addresses and texts.
+pub fn disassemble_range<M: ZxMemory + ?Sized>(memory: &M, range: Range<u16>) -> Vec<(u16, String)> {
+    let mut lines = Vec::new();
+    let mut address = range.start;
+    while address < range.end {
+        let (text, len) = disassemble(memory, address);
+        lines.push((address, text));
+        match address.checked_add(len as u16) {
+            Some(next) => address = next,
+            None => break
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use spectrusty::memory::Memory64k;
+    use super::*;
+
+    fn disasm(code: &[u8]) -> (String, usize) {
+        let mut mem = Memory64k::default();
+        mem.load_into(0xfffe, &code[..2]).unwrap();
+        mem.load_into(0, &code[2..]).unwrap();
+        disassemble(&mem, 0xfffe)
+    }
+
+    #[test]
+    fn disassemble_works() {
+        assert_eq!(disasm(&[0x00, 0x00, 0x00, 0x00]), ("NOP".into(), 1));
+        assert_eq!(disasm(&[0x3E, 0x7F, 0x00, 0x00]), ("LD A, 7fh".into(), 2));
+        assert_eq!(disasm(&[0xC3, 0x00, 0x80, 0x00]), ("JP 8000h".into(), 3));
+        assert_eq!(disasm(&[0xDD, 0x09, 0x00, 0x00]), ("ADD IX, BC".into(), 2));
+        assert_eq!(disasm(&[0xDD, 0x7E, 0x05, 0x00]), ("LD A, (IX+05h)".into(), 3));
+        assert_eq!(disasm(&[0xFD, 0xCB, 0xFE, 0x46]), ("BIT 0, (IY-02h)".into(), 4));
+        assert_eq!(disasm(&[0xED, 0xB0, 0x00, 0x00]), ("LDIR".into(), 2));
+        assert_eq!(disasm(&[0xCB, 0x37, 0x00, 0x00]), ("SLL A".into(), 2));
+        assert_eq!(disasm(&[0xDD, 0x7C, 0x00, 0x00]), ("LD A, IXH".into(), 2));
+        assert_eq!(disasm(&[0xDD, 0xFD, 0x09, 0x00]), (IGNORED_PREFIX_MNEMONIC.into(), 1));
+        assert_eq!(disasm(&[0xDD, 0x00, 0x00, 0x00]), (IGNORED_PREFIX_MNEMONIC.into(), 1));
+        let mut mem = Memory64k::default();
+        mem.load_into(0x8000, &[0xDD, 0xFD, 0x09, 0x76]).unwrap();
+        assert_eq!(disassemble_range(&mem, 0x8000..0x8004), [
+            (0x8000, IGNORED_PREFIX_MNEMONIC.to_string()),
+            (0x8001, "ADD IY, BC".to_string()),
+            (0x8003, "HALT".to_string())]);
+    }
+}
@@ -0,0 +1,133 @@
+/*
+    Copyright (C) 2020-2023  Rafal Michalski
+
+    This file is part of SPECTRUSTY, a Rust library for building emulators.
+
+    For the full copyright notice, see the lib.rs file.
+*/
+//! Joystick related utilities.
+//!
+//! Converts the analog axes of host game controllers into the digital 8-direction joystick state.
+use core::f32::consts::FRAC_PI_4;
+
+use spectrusty::peripherals::joystick::{JoystickInterface, Directions};
+
+/// The default dead zone radius of the analog stick.
+pub const DEFAULT_DEADZONE: f32 = 0.25;
+/// The default diagonal sensitivity, making all 8 directional sectors equal in size.
+pub const DEFAULT_DIAGONAL_SENSITIVITY: f32 = 0.5;
+
+/// Converts the analog stick axes to the digital joystick directions.
+///
+/// The `x` and `y` axis values are expected in the range: `[-1.0, 1.0]`, where negative values
+/// indicate the left and the up direction respectively, as reported e.g. by SDL2 and the Web
+/// Gamepad API. Values outside of this range are clamped and `NaN` is treated as `0.0`.
+///
+/// Returns no directions when the stick is inside the `deadzone` radius. This way the joystick
+/// snaps back to the center as soon as the stick is released.
+///
+/// The 8 directional sectors are equal in size. See [axes_to_directions_with_sensitivity].
+pub fn axes_to_directions(x: f32, y: f32, deadzone: f32) -> Directions {
+    axes_to_directions_with_sensitivity(x, y, deadzone, DEFAULT_DIAGONAL_SENSITIVITY)
+}
+
+/// Converts the analog stick axes to the digital joystick directions with the custom
+/// `diagonal` sensitivity.
+///
+/// The `diagonal` sensitivity in the range: `[0.0, 1.0]` is the fraction of the full stick
+/// rotation angle that activates the diagonal directions:
+///
+/// * `0.0` - only the exact diagonals activate two directions,
+/// * `0.5` - all 8 sectors are equal in size, see [DEFAULT_DIAGONAL_SENSITIVITY],
+/// * `1.0` - only the exact horizontal and vertical stick positions activate a single direction.
+///
+/// See [axes_to_directions] for the description of the remaining arguments.
+pub fn axes_to_directions_with_sensitivity(x: f32, y: f32, deadzone: f32, diagonal: f32) -> Directions {
+    let x = clamp_axis(x);
+    let y = clamp_axis(y);
+    let mut directions = Directions::empty();
+    if x.hypot(y) <= deadzone.max(0.0) {
+        return directions
+    }
+    let (ax, ay) = (x.abs(), y.abs());
+    let (min, max) = if ax < ay { (ax, ay) } else { (ay, ax) };
+    let diagonal = clamp_axis(diagonal).max(0.0);
+    // the tangent of the angle from the nearest axis at which the diagonal sector begins
+    let is_diagonal = min > 0.0 && min >= max * (FRAC_PI_4 * (1.0 - diagonal)).tan();
+    if is_diagonal || ax > ay {
+        directions |= if x < 0.0 { Directions::LEFT } else { Directions::RIGHT };
+    }
+    if is_diagonal || ay > ax {
+        directions |= if y < 0.0 { Directions::UP } else { Directions::DOWN };
+    }
+    directions
+}
+
+/// Updates the state of the joystick device via [JoystickInterface] from the analog stick axes.
+///
+/// Returns `true` if the state of the joystick directions has changed.
+///
+/// See [axes_to_directions] for the description of the arguments.
+pub fn update_joystick_from_axes<J: JoystickInterface + ?Sized>(
+        joy: &mut J,
+        x: f32,
+        y: f32,
+        deadzone: f32
+    ) -> bool
+{
+    let directions = axes_to_directions(x, y, deadzone);
+    if joy.get_directions() != directions {
+        joy.set_directions(directions);
+        return true
+    }
+    false
+}
+
+#[inline]
+fn clamp_axis(value: f32) -> f32 {
+    if value.is_nan() { 0.0 } else { value.clamp(-1.0, 1.0) }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::f32::consts::FRAC_1_SQRT_2;
+    use spectrusty::peripherals::joystick::kempston::KempstonJoystickDevice;
+    use super::*;
+
+    #[test]
+    fn axes_to_directions_works() {
+        let dz = DEFAULT_DEADZONE;
+        // inside the dead zone
+        for &(x, y) in &[(0.0, 0.0), (0.2, 0.0), (0.0, -0.2), (-0.17, 0.17), (f32::NAN, 0.1)] {
+            assert_eq!(axes_to_directions(x, y, dz), Directions::empty(), "{} {}", x, y);
+        }
+        // horizontal and vertical
+        assert_eq!(axes_to_directions(0.3, 0.0, dz), Directions::RIGHT);
+        assert_eq!(axes_to_directions(-1.0, 0.1, dz), Directions::LEFT);
+        assert_eq!(axes_to_directions(0.1, -1.0, dz), Directions::UP);
+        assert_eq!(axes_to_directions(0.0, 5.0, dz), Directions::DOWN);
+        // 45° pushes
+        let d = FRAC_1_SQRT_2;
+        assert_eq!(axes_to_directions(d, -d, dz), Directions::UP|Directions::RIGHT);
+        assert_eq!(axes_to_directions(d, d, dz), Directions::DOWN|Directions::RIGHT);
+        assert_eq!(axes_to_directions(-d, d, dz), Directions::DOWN|Directions::LEFT);
+        assert_eq!(axes_to_directions(-d, -d, dz), Directions::UP|Directions::LEFT);
+        // the sector boundaries at 22.5°
+        assert_eq!(axes_to_directions(1.0, 0.4, dz), Directions::RIGHT);
+        assert_eq!(axes_to_directions(1.0, 0.43, dz), Directions::DOWN|Directions::RIGHT);
+        assert_eq!(axes_to_directions(-0.4, -1.0, dz), Directions::UP);
+        assert_eq!(axes_to_directions(-0.43, -1.0, dz), Directions::UP|Directions::LEFT);
+        // diagonal sensitivity
+        assert_eq!(axes_to_directions_with_sensitivity(1.0, 0.9, dz, 0.0), Directions::RIGHT);
+        assert_eq!(axes_to_directions_with_sensitivity(1.0, 1.0, dz, 0.0), Directions::DOWN|Directions::RIGHT);
+        assert_eq!(axes_to_directions_with_sensitivity(1.0, 0.1, dz, 1.0), Directions::DOWN|Directions::RIGHT);
+        assert_eq!(axes_to_directions_with_sensitivity(1.0, 0.0, dz, 1.0), Directions::RIGHT);
+        // snap back
+        let mut joy = KempstonJoystickDevice::default();
+        assert!(update_joystick_from_axes(&mut joy, -d, -d, dz));
+        assert_eq!(joy.get_directions(), Directions::UP|Directions::LEFT);
+        assert!(!update_joystick_from_axes(&mut joy, -0.8, -0.6, dz));
+        assert!(update_joystick_from_axes(&mut joy, -0.1, 0.0, dz));
+        assert_eq!(joy.get_directions(), Directions::empty());
+    }
+}
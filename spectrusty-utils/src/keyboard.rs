@@ -13,6 +13,13 @@ use spectrusty::peripherals::{
     joystick::{JoystickInterface, Directions}
 };
 
+pub mod keymap;
+pub mod layout;
+pub mod recorder;
+mod text;
+
+pub use text::*;
+
 #[cfg(feature = "minifb")]
 pub mod minifb;
 
@@ -0,0 +1,224 @@
+/*
+    Copyright (C) 2020-2023  Rafal Michalski
+
+    This file is part of SPECTRUSTY, a Rust library for building emulators.
+
+    For the full copyright notice, see the lib.rs file.
+*/
+//! A configurable mapping of host keys to Spectrum keys and emulator actions.
+//!
+//! With the "snapshot" feature enabled [KeyMap] can be serialized and deserialized with `serde`.
+//! With the "json" feature enabled [KeyMap] can be also loaded and saved directly as JSON.
+//!
+//! The Spectrum keys are serialized as lists of [ZXKeyboardMap] flag names, e.g.:
+//!
+//! ```text
+//! {
+//!   "keys": { "30": ["A"], "80": ["CS", "N5"] },
+//!   "meta": { "69": "Pause", "70": { "Custom": "screenshot" } }
+//! }
+//! ```
+use std::collections::BTreeMap;
+#[cfg(feature = "json")]
+use std::io::{Read, Write};
+
+#[cfg(feature = "snapshot")]
+use serde::{Serialize, Deserialize};
+
+use spectrusty::peripherals::ZXKeyboardMap;
+
+/// The host key code type used by [KeyMap].
+///
+/// Frontends should convert their native scancodes or key codes to this type.
+pub type Scancode = u32;
+
+/// An emulator action that can be bound to a host key by [KeyMap].
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+pub enum MetaBinding {
+    /// Pauses or resumes the emulation.
+    Pause,
+    /// Resets the emulated computer.
+    SoftReset,
+    /// Power cycles the emulated computer.
+    HardReset,
+    /// Triggers the non-maskable interrupt.
+    TriggerNmi,
+    /// Toggles the turbo mode.
+    ToggleTurbo,
+    /// Toggles the audio output.
+    ToggleAudio,
+    /// Presses the joystick's `FIRE` button.
+    JoystickFire,
+    /// Moves the joystick up.
+    JoystickUp,
+    /// Moves the joystick right.
+    JoystickRight,
+    /// Moves the joystick down.
+    JoystickDown,
+    /// Moves the joystick left.
+    JoystickLeft,
+    /// A frontend specific action identified by name.
+    Custom(String)
+}
+
+/// A mapping of host key codes to Spectrum keys and [emulator actions][MetaBinding].
+///
+/// Each host key can be mapped to one or more Spectrum keys pressed together, e.g. the cursor
+/// left key can be mapped to `CAPS SHIFT` + `5`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+pub struct KeyMap {
+    /// Host keys mapped to Spectrum keys.
+    #[cfg_attr(feature = "snapshot", serde(default, with = "serde_keys"))]
+    pub keys: BTreeMap<Scancode, ZXKeyboardMap>,
+    /// Host keys mapped to emulator actions.
+    #[cfg_attr(feature = "snapshot", serde(default))]
+    pub meta: BTreeMap<Scancode, MetaBinding>
+}
+
+impl KeyMap {
+    /// Creates an empty keymap.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Maps the host `key` to Spectrum `keys`, returning the previous mapping of `key` if any.
+    ///
+    /// Providing an empty set of `keys` removes the mapping.
+    pub fn bind_key(&mut self, key: Scancode, keys: ZXKeyboardMap) -> Option<ZXKeyboardMap> {
+        if keys.is_empty() {
+            self.keys.remove(&key)
+        }
+        else {
+            self.keys.insert(key, keys)
+        }
+    }
+    /// Maps the host `key` to the emulator `action`, returning the previous action bound to `key` if any.
+    pub fn bind_meta(&mut self, key: Scancode, action: MetaBinding) -> Option<MetaBinding> {
+        self.meta.insert(key, action)
+    }
+    /// Removes the emulator action bound to the host `key`, returning it if any.
+    pub fn unbind_meta(&mut self, key: Scancode) -> Option<MetaBinding> {
+        self.meta.remove(&key)
+    }
+    /// Returns Spectrum keys mapped to the host `key`.
+    ///
+    /// Returns an empty set if the `key` isn't mapped.
+    pub fn map_key(&self, key: Scancode) -> ZXKeyboardMap {
+        self.keys.get(&key).copied().unwrap_or_else(ZXKeyboardMap::empty)
+    }
+    /// Returns the emulator action bound to the host `key` if any.
+    pub fn meta_binding(&self, key: Scancode) -> Option<&MetaBinding> {
+        self.meta.get(&key)
+    }
+    /// Returns an updated Spectrum keymap state from a host `key` down or up event.
+    ///
+    /// * `cur` is the current keymap state.
+    /// * `key` is the host key code.
+    /// * `pressed` should be `true` if the `key` has been pressed down and `false` if it has been released.
+    pub fn update_keymap(&self, mut cur: ZXKeyboardMap, key: Scancode, pressed: bool) -> ZXKeyboardMap {
+        cur.set(self.map_key(key), pressed);
+        cur
+    }
+}
+
+#[cfg(feature = "json")]
+impl KeyMap {
+    /// Loads the keymap from the JSON data read from `rd`.
+    pub fn load_json<R: Read>(rd: R) -> serde_json::Result<Self> {
+        serde_json::from_reader(rd)
+    }
+    /// Saves the keymap as JSON data, writing it to `wr`.
+    pub fn save_json<W: Write>(&self, wr: W) -> serde_json::Result<()> {
+        serde_json::to_writer_pretty(wr, self)
+    }
+    /// Parses the keymap from a JSON string.
+    pub fn from_json_str(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+    /// Returns the keymap as a JSON string.
+    pub fn to_json_string(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+#[cfg(feature = "snapshot")]
+mod serde_keys {
+    use std::borrow::Cow;
+    use std::collections::BTreeMap;
+    use serde::{Serialize, Serializer, Deserialize, Deserializer, de};
+    use spectrusty::peripherals::ZXKeyboardMap;
+    use super::Scancode;
+
+    pub fn serialize<S: Serializer>(
+            keys: &BTreeMap<Scancode, ZXKeyboardMap>,
+            serializer: S
+        ) -> Result<S::Ok, S::Error>
+    {
+        let names: BTreeMap<Scancode, Vec<&str>> = keys.iter().map(|(&key, zxk)| {
+            (key, zxk.iter_names().map(|(name, _)| name).collect())
+        }).collect();
+        names.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D
+        ) -> Result<BTreeMap<Scancode, ZXKeyboardMap>, D::Error>
+    {
+        let names = BTreeMap::<Scancode, Vec<Cow<'de, str>>>::deserialize(deserializer)?;
+        names.into_iter().map(|(key, names)| {
+            let zxk = names.iter().try_fold(ZXKeyboardMap::empty(), |zxk, name| {
+                ZXKeyboardMap::from_name(name).map(|k| zxk | k)
+                .ok_or_else(|| de::Error::custom(format!("unknown Spectrum key: {}", name)))
+            })?;
+            Ok((key, zxk))
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "json")]
+mod tests {
+    use super::*;
+
+    type ZXk = ZXKeyboardMap;
+
+    #[test]
+    fn keymap_json_works() {
+        let mut keymap = KeyMap::new();
+        assert_eq!(keymap.bind_key(30, ZXk::A), None);
+        assert_eq!(keymap.bind_key(80, ZXk::CS|ZXk::N5), None);
+        assert_eq!(keymap.bind_meta(69, MetaBinding::Pause), None);
+        assert_eq!(keymap.bind_meta(70, MetaBinding::Custom("screenshot".into())), None);
+        let json = keymap.to_json_string().unwrap();
+        let keymap1 = KeyMap::from_json_str(&json).unwrap();
+        assert_eq!(keymap, keymap1);
+        let mut buf = Vec::new();
+        keymap.save_json(&mut buf).unwrap();
+        let keymap2 = KeyMap::load_json(&buf[..]).unwrap();
+        assert_eq!(keymap, keymap2);
+
+        let mut custom = KeyMap::from_json_str(r#"{
+            "keys": {"30": ["Q"], "80": ["CS", "N5"], "57": ["SS", "BR"]},
+            "meta": {"88": {"Custom": "quit"}, "87": "JoystickFire"}
+        }"#).unwrap();
+        assert_eq!(custom.map_key(30), ZXk::Q);
+        assert_eq!(custom.map_key(31), ZXk::empty());
+        let cur = custom.update_keymap(ZXk::empty(), 80, true);
+        assert_eq!(cur, ZXk::CS|ZXk::N5);
+        let cur = custom.update_keymap(cur, 57, true);
+        assert_eq!(cur, ZXk::CS|ZXk::N5|ZXk::SS|ZXk::BR);
+        let cur = custom.update_keymap(cur, 80, false);
+        assert_eq!(cur, ZXk::SS|ZXk::BR);
+        assert_eq!(custom.meta_binding(88), Some(&MetaBinding::Custom("quit".into())));
+        assert_eq!(custom.meta_binding(87), Some(&MetaBinding::JoystickFire));
+        assert_eq!(custom.unbind_meta(87), Some(MetaBinding::JoystickFire));
+        assert_eq!(custom.meta_binding(87), None);
+        assert_eq!(custom.bind_key(30, ZXk::empty()), Some(ZXk::Q));
+        assert_eq!(custom.map_key(30), ZXk::empty());
+
+        assert!(KeyMap::from_json_str(r#"{"keys": {"30": ["QQ"]}}"#).is_err());
+        assert_eq!(KeyMap::from_json_str("{}").unwrap(), KeyMap::default());
+    }
+}
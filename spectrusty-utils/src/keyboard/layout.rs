@@ -0,0 +1,186 @@
+/*
+    Copyright (C) 2020-2023  Rafal Michalski
+
+    This file is part of SPECTRUSTY, a Rust library for building emulators.
+
+    For the full copyright notice, see the lib.rs file.
+*/
+//! National keyboard layouts.
+//!
+//! The event loop dependent keyboard utilities map host keys to the Spectrum keys assuming
+//! the US keyboard layout. When the host key codes reflect the physical position of keys, e.g.
+//! `KeyboardEvent.code` in web browsers, the keys of other national layouts land on the wrong Spectrum
+//! keys. [KeyboardLayout] remaps the Spectrum keys resulting from the US layout mapping to the keys
+//! intended by the national layout and is consulted by `update_keymap_with_layout` functions.
+//! The `sdl2` and `winit` key codes already reflect the host keyboard layout, so their
+//! `update_keymap_with_layout` functions identify the keys by their physical scancodes instead.
+use std::collections::BTreeMap;
+
+use spectrusty::peripherals::ZXKeyboardMap;
+
+type ZXk = ZXKeyboardMap;
+
+/// A remapping of Spectrum keys produced by the US keyboard layout mapping to the keys
+/// of a national keyboard layout.
+///
+/// The default layout is the US (and UK) layout, which doesn't remap any keys.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct KeyboardLayout {
+    remap: BTreeMap<ZXKeyboardMap, ZXKeyboardMap>
+}
+
+impl KeyboardLayout {
+    /// Returns the US keyboard layout, which doesn't remap any keys.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Returns the German QWERTZ keyboard layout.
+    ///
+    /// Swaps `Y` with `Z`, maps the `/` key as `-` (`_` with `SHIFT`) and the `-` key with `SHIFT` as `?`.
+    pub fn german() -> Self {
+        let mut layout = Self::new();
+        layout.swap_keys(ZXk::Y, ZXk::Z);
+        layout.remap_keys(ZXk::SS|ZXk::V, ZXk::SS|ZXk::J);
+        layout.remap_keys(ZXk::SS|ZXk::C, ZXk::SS|ZXk::N0);
+        layout.remap_keys(ZXk::SS|ZXk::N0, ZXk::SS|ZXk::C);
+        layout
+    }
+    /// Returns the French AZERTY keyboard layout.
+    ///
+    /// Swaps `A` with `Q` and `Z` with `W`, maps the `;` key as `M`, the `M` key as `,`
+    /// and the `,` key as `;`.
+    pub fn french() -> Self {
+        let mut layout = Self::new();
+        layout.swap_keys(ZXk::A, ZXk::Q);
+        layout.swap_keys(ZXk::Z, ZXk::W);
+        layout.remap_keys(ZXk::SS|ZXk::O, ZXk::M);
+        layout.remap_keys(ZXk::M, ZXk::SS|ZXk::N);
+        layout.remap_keys(ZXk::SS|ZXk::N, ZXk::SS|ZXk::O);
+        layout
+    }
+    /// Returns one of the predefined layouts by its language or country code.
+    ///
+    /// Recognizes `"us"`, `"uk"`, `"de"` and `"fr"` case-insensitively.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "us"|"uk"|"gb"|"en" => Some(Self::new()),
+            "de" => Some(Self::german()),
+            "fr" => Some(Self::french()),
+            _ => None
+        }
+    }
+    /// Remaps the Spectrum keys `from` to the keys `to`, returning the previous mapping of `from` if any.
+    ///
+    /// Providing `to` equal to `from` removes the mapping.
+    pub fn remap_keys(&mut self, from: ZXKeyboardMap, to: ZXKeyboardMap) -> Option<ZXKeyboardMap> {
+        if from == to {
+            self.remap.remove(&from)
+        }
+        else {
+            self.remap.insert(from, to)
+        }
+    }
+    /// Remaps the Spectrum keys `a` to `b` and `b` to `a`.
+    pub fn swap_keys(&mut self, a: ZXKeyboardMap, b: ZXKeyboardMap) {
+        self.remap_keys(a, b);
+        self.remap_keys(b, a);
+    }
+    /// Returns `true` if the layout doesn't remap any keys.
+    pub fn is_identity(&self) -> bool {
+        self.remap.is_empty()
+    }
+    /// Returns the Spectrum keys intended by this layout for `keys` resulting from the US layout mapping.
+    ///
+    /// If `keys` match one of the remapped key combinations exactly, the remapped keys are returned.
+    /// Otherwise every remapped key combination contained in `keys` is being replaced, which is useful
+    /// when releasing keys, as the release of a key is always mapped to all the keys its press could
+    /// have resulted in. The key combinations with [SYMBOL SHIFT][ZXKeyboardMap::SS] are only being
+    /// replaced in `keys` containing `SYMBOL SHIFT` and the ones without it only in `keys` without
+    /// `SYMBOL SHIFT`, so e.g. swapped letters don't affect symbols.
+    pub fn map_keys(&self, keys: ZXKeyboardMap) -> ZXKeyboardMap {
+        if let Some(&mapped) = self.remap.get(&keys) {
+            return mapped
+        }
+        let symbol = keys.intersects(ZXk::SS);
+        let mut mapped = ZXk::empty();
+        let mut covered = ZXk::empty();
+        for (&from, &to) in self.remap.iter() {
+            if !from.is_empty() && from.intersects(ZXk::SS) == symbol && keys.contains(from) {
+                mapped |= to;
+                covered |= from;
+            }
+        }
+        let mut rest = keys - covered;
+        if symbol && !rest.is_empty() {
+            rest |= ZXk::SS;
+        }
+        mapped | rest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keyboard_layout_works() {
+        let layout = KeyboardLayout::default();
+        assert!(layout.is_identity());
+        assert_eq!(layout.map_keys(ZXk::Y), ZXk::Y);
+        assert_eq!(layout.map_keys(ZXk::SS|ZXk::V), ZXk::SS|ZXk::V);
+        let mut layout = KeyboardLayout::new();
+        layout.swap_keys(ZXk::Y, ZXk::Z);
+        assert!(!layout.is_identity());
+        assert_eq!(layout.map_keys(ZXk::Y), ZXk::Z);
+        assert_eq!(layout.map_keys(ZXk::Z), ZXk::Y);
+        assert_eq!(layout.map_keys(ZXk::CS|ZXk::Y), ZXk::CS|ZXk::Z);
+        assert_eq!(layout.map_keys(ZXk::Y|ZXk::Z), ZXk::Y|ZXk::Z);
+        assert_eq!(layout.map_keys(ZXk::A), ZXk::A);
+        assert_eq!(layout.remap_keys(ZXk::Y, ZXk::Y), Some(ZXk::Z));
+        assert_eq!(layout.map_keys(ZXk::Y), ZXk::Y);
+        assert_eq!(KeyboardLayout::from_name("DE"), Some(KeyboardLayout::german()));
+        assert_eq!(KeyboardLayout::from_name("uk"), Some(KeyboardLayout::new()));
+        assert_eq!(KeyboardLayout::from_name("xx"), None);
+        let layout = KeyboardLayout::french();
+        assert_eq!(layout.map_keys(ZXk::SS|ZXk::O), ZXk::M);
+        assert_eq!(layout.map_keys(ZXk::M), ZXk::SS|ZXk::N);
+        // letters are not swapped in symbols
+        assert_eq!(layout.map_keys(ZXk::SS|ZXk::Z), ZXk::SS|ZXk::Z);
+        assert_eq!(layout.map_keys(ZXk::SS|ZXk::A), ZXk::SS|ZXk::A);
+        // a release of the ; key covers both of its presses
+        assert_eq!(layout.map_keys(ZXk::SS|ZXk::Z|ZXk::O), ZXk::M|ZXk::SS|ZXk::Z);
+        let layout = KeyboardLayout::german();
+        // : is not turned into SYMBOL SHIFT + Y
+        assert_eq!(layout.map_keys(ZXk::SS|ZXk::Z), ZXk::SS|ZXk::Z);
+        assert_eq!(layout.map_keys(ZXk::CS|ZXk::Z), ZXk::CS|ZXk::Y);
+        // releases of the - and / keys
+        assert_eq!(layout.map_keys(ZXk::SS|ZXk::J|ZXk::N0), ZXk::SS|ZXk::J|ZXk::C);
+        assert_eq!(layout.map_keys(ZXk::SS|ZXk::C|ZXk::V), ZXk::SS|ZXk::J|ZXk::N0);
+    }
+
+    #[test]
+    fn keyboard_layout_press_release_symmetry_works() {
+        // (pressed with SHIFT, pressed without SHIFT, released)
+        let keys = [
+            (ZXk::SS|ZXk::N0, ZXk::SS|ZXk::J, ZXk::SS|ZXk::J|ZXk::N0),
+            (ZXk::SS|ZXk::K, ZXk::SS|ZXk::L, ZXk::SS|ZXk::K|ZXk::L),
+            (ZXk::SS|ZXk::R, ZXk::SS|ZXk::N, ZXk::SS|ZXk::R|ZXk::N),
+            (ZXk::SS|ZXk::T, ZXk::SS|ZXk::M, ZXk::SS|ZXk::T|ZXk::M),
+            (ZXk::SS|ZXk::P, ZXk::SS|ZXk::N7, ZXk::SS|ZXk::P|ZXk::N7),
+            (ZXk::SS|ZXk::C, ZXk::SS|ZXk::V, ZXk::SS|ZXk::C|ZXk::V),
+            (ZXk::SS|ZXk::Z, ZXk::SS|ZXk::O, ZXk::SS|ZXk::Z|ZXk::O),
+            (ZXk::CS|ZXk::Z, ZXk::Z, ZXk::Z),
+            (ZXk::CS|ZXk::M, ZXk::M, ZXk::M),
+        ];
+        for layout in [KeyboardLayout::german(), KeyboardLayout::french()] {
+            for &(shifted, unshifted, released) in keys.iter() {
+                let mut cur = ZXk::CS|layout.map_keys(shifted);
+                cur.remove(layout.map_keys(released));
+                assert!(!cur.intersects(ZXk::SS), "{:?} {:?}", layout, shifted);
+                let mut cur = layout.map_keys(unshifted);
+                cur.remove(layout.map_keys(released));
+                assert!(cur.is_empty(), "{:?} {:?}", layout, unshifted);
+            }
+        }
+    }
+}
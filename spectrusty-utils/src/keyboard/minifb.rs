@@ -14,6 +14,8 @@ use spectrusty::peripherals::{ZXKeyboardMap,
     serial::KeypadKeys
 };
 
+use super::layout::KeyboardLayout;
+
 type ZXk = ZXKeyboardMap;
 
 /// Returns Spectrum keymap flags with a single bit set corresponding to the provided `key` code
@@ -174,14 +176,35 @@ pub fn map_combined_keys(key: Key, pressed: bool, shift_down: bool) -> (ZXKeyboa
 /// * `shift_down` should be `true` if one of the `SHIFT` key modifiers has been held down and `false` otherwise.
 /// * `ctrl_down` should be `true` if one of the `CTRL` key modifiers has been held down and `false` otherwise.
 pub fn update_keymap(
-        mut cur: ZXKeyboardMap,
+        cur: ZXKeyboardMap,
         key: Key,
         pressed: bool,
         shift_down: bool,
         ctrl_down: bool
     ) -> ZXKeyboardMap
+{
+    update_keymap_with_layout(cur, key, pressed, shift_down, ctrl_down, &KeyboardLayout::default())
+}
+
+/// Returns an updated Spectrum keymap state, with keys remapped by the `layout`, from a `key` down or up event.
+///
+/// * `cur` is the current keymap state.
+/// * `key` is the key code.
+/// * `pressed` should be `true` if the `key` has been pressed down and `false` if it has been released.
+/// * `shift_down` should be `true` if one of the `SHIFT` key modifiers has been held down and `false` otherwise.
+/// * `ctrl_down` should be `true` if one of the `CTRL` key modifiers has been held down and `false` otherwise.
+/// * `layout` is the national keyboard layout remapping the Spectrum keys.
+pub fn update_keymap_with_layout(
+        mut cur: ZXKeyboardMap,
+        key: Key,
+        pressed: bool,
+        shift_down: bool,
+        ctrl_down: bool,
+        layout: &KeyboardLayout
+    ) -> ZXKeyboardMap
 {
     let (chg, removecs) = map_combined_keys(key, pressed, shift_down);
+    let chg = layout.map_keys(chg);
     if pressed {
         cur.insert(chg);
         if removecs {
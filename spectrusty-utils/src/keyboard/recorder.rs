@@ -0,0 +1,237 @@
+/*
+    Copyright (C) 2020-2023  Rafal Michalski
+
+    This file is part of SPECTRUSTY, a Rust library for building emulators.
+
+    For the full copyright notice, see the lib.rs file.
+*/
+//! Recording and playback of the Spectrum keyboard state.
+//!
+//! [KeyboardRecorder] logs the transitions of the keyboard state for each emulated frame and can replay
+//! them later, e.g. to repeat a cheat code entry or a demo input.
+//!
+//! ```
+//! use spectrusty::chip::{FrameState, ControlUnit};
+//! use spectrusty::peripherals::KeyboardInterface;
+//! use spectrusty_utils::keyboard::recorder::KeyboardRecorder;
+//! # use spectrusty::z80emu::Z80NMOS;
+//! # use spectrusty::chip::ula::UlaPAL;
+//! # use spectrusty::memory::Memory16k;
+//! # let mut cpu = Z80NMOS::default();
+//! # let mut ula = UlaPAL::<Memory16k>::default();
+//!
+//! let mut recorder = KeyboardRecorder::new();
+//! recorder.record_start(ula.current_frame());
+//! // ... before each frame, after the keyboard state has been updated from the host events
+//! recorder.update_keyboard(&mut ula);
+//! ula.execute_next_frame(&mut cpu);
+//! // ... later
+//! let recording = recorder.take_recording(ula.current_frame()).unwrap();
+//! recorder.play(ula.current_frame(), recording);
+//! ```
+use spectrusty::chip::FrameState;
+use spectrusty::peripherals::{KeyboardInterface, ZXKeyboardMap};
+
+/// A recorded sequence of the Spectrum keyboard state transitions.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct KeyRecording {
+    /// The keyboard state transitions as `(frame offset, keyboard state)` pairs sorted by the frame offset.
+    ///
+    /// The frame offset is counted from the first frame of the recording.
+    pub events: Vec<(u64, ZXKeyboardMap)>,
+    /// The duration of the recording in frames.
+    pub frames: u64
+}
+
+/// Records and plays back the Spectrum keyboard state transitions driven by the frame counter.
+#[derive(Clone, Debug, Default)]
+pub struct KeyboardRecorder {
+    recording: Option<Recording>,
+    playback: Option<Playback>,
+    released: ZXKeyboardMap,
+}
+
+#[derive(Clone, Debug)]
+struct Recording {
+    start: u64,
+    last: Option<ZXKeyboardMap>,
+    events: Vec<(u64, ZXKeyboardMap)>,
+}
+
+#[derive(Clone, Debug)]
+struct Playback {
+    start: u64,
+    index: usize,
+    keymap: ZXKeyboardMap,
+    recording: KeyRecording,
+}
+
+impl KeyboardRecorder {
+    /// Returns a new idle instance of `KeyboardRecorder`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Returns `true` if the keyboard state is being recorded.
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+    /// Returns `true` if a recording is being played back.
+    pub fn is_playing(&self) -> bool {
+        self.playback.is_some()
+    }
+    /// Starts a new recording at the given `frame`, discarding the current one.
+    pub fn record_start(&mut self, frame: u64) {
+        self.recording = Some(Recording { start: frame, last: None, events: Vec::new() });
+    }
+    /// Stops recording and returns the recorded transitions if the recording has been started.
+    ///
+    /// `frame` is the frame number at which the recording ends, not included in the recording.
+    pub fn take_recording(&mut self, frame: u64) -> Option<KeyRecording> {
+        self.recording.take().map(|Recording { start, events, .. }| {
+            KeyRecording { events, frames: frame.wrapping_sub(start) }
+        })
+    }
+    /// Starts playing back the `recording` from the given `frame`, replacing the current playback.
+    pub fn play(&mut self, frame: u64, recording: KeyRecording) {
+        let playback = Playback { start: frame, index: 0, keymap: ZXKeyboardMap::empty(), recording };
+        if let Some(Playback { keymap, .. }) = self.playback.replace(playback) {
+            self.released |= keymap;
+        }
+    }
+    /// Stops the playback.
+    ///
+    /// The keys pressed by the playback are released by the next call to [KeyboardRecorder::update_keyboard].
+    pub fn stop_playing(&mut self) {
+        if let Some(Playback { keymap, .. }) = self.playback.take() {
+            self.released |= keymap;
+        }
+    }
+    /// Returns the keyboard state for the given `frame`.
+    ///
+    /// `keymap` is the keyboard state from the host input. While a recording is being played back, the
+    /// played state is returned instead. The returned state is being recorded if the recording is active.
+    ///
+    /// This method should be called once before each emulated frame.
+    pub fn next_keymap(&mut self, frame: u64, keymap: ZXKeyboardMap) -> ZXKeyboardMap {
+        self.released = ZXKeyboardMap::empty();
+        let keymap = match self.playback.as_mut() {
+            Some(playback) => {
+                let offset = frame.wrapping_sub(playback.start);
+                if offset < playback.recording.frames {
+                    let events = &playback.recording.events;
+                    while let Some(&(_, keys)) = events.get(playback.index)
+                                                 .filter(|&&(ts, _)| ts <= offset) {
+                        playback.keymap = keys;
+                        playback.index += 1;
+                    }
+                    playback.keymap
+                }
+                else {
+                    self.playback = None;
+                    keymap
+                }
+            }
+            None => keymap
+        };
+        if let Some(recording) = self.recording.as_mut() {
+            if recording.last != Some(keymap) {
+                recording.events.push((frame.wrapping_sub(recording.start), keymap));
+                recording.last = Some(keymap);
+            }
+        }
+        keymap
+    }
+    /// Updates the keyboard state of the `ula` with [KeyboardRecorder::next_keymap] for its current frame.
+    ///
+    /// The keyboard state of the `ula` is being used as the host input. The keys pressed by the playback
+    /// are released from it when the playback ends or is stopped.
+    ///
+    /// This method should be called once before each emulated frame.
+    pub fn update_keyboard<U: KeyboardInterface + FrameState>(&mut self, ula: &mut U) {
+        let mut released = self.released;
+        if let Some(playback) = self.playback.as_ref() {
+            released |= playback.keymap;
+        }
+        let keymap = self.next_keymap(ula.current_frame(), ula.get_key_state() & !released);
+        ula.set_key_state(keymap);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use spectrusty::chip::ula::UlaPAL;
+    use spectrusty::memory::Memory16k;
+    use super::*;
+
+    type ZXk = ZXKeyboardMap;
+
+    #[test]
+    fn keyboard_recorder_works() {
+        let mut recorder = KeyboardRecorder::new();
+        assert!(!recorder.is_recording());
+        assert_eq!(recorder.take_recording(0), None);
+        recorder.record_start(10);
+        assert!(recorder.is_recording());
+        assert_eq!(recorder.next_keymap(10, ZXk::A), ZXk::A);
+        assert_eq!(recorder.next_keymap(11, ZXk::A|ZXk::B), ZXk::A|ZXk::B);
+        assert_eq!(recorder.next_keymap(12, ZXk::empty()), ZXk::empty());
+        let recording = recorder.take_recording(13).unwrap();
+        assert!(!recorder.is_recording());
+        assert_eq!(recording, KeyRecording {
+            events: vec![(0, ZXk::A), (1, ZXk::A|ZXk::B), (2, ZXk::empty())],
+            frames: 3
+        });
+        // host keys are ignored while playing
+        recorder.play(100, recording.clone());
+        assert!(recorder.is_playing());
+        assert_eq!(recorder.next_keymap(100, ZXk::P), ZXk::A);
+        assert_eq!(recorder.next_keymap(101, ZXk::P), ZXk::A|ZXk::B);
+        assert_eq!(recorder.next_keymap(102, ZXk::P), ZXk::empty());
+        assert!(recorder.is_playing());
+        assert_eq!(recorder.next_keymap(103, ZXk::P), ZXk::P);
+        assert!(!recorder.is_playing());
+        // repeated states are recorded only once, the played back keys are recorded too
+        recorder.record_start(200);
+        recorder.play(200, recording.clone());
+        for frame in 200..206 {
+            recorder.next_keymap(frame, ZXk::P);
+        }
+        assert_eq!(recorder.take_recording(206).unwrap(), KeyRecording {
+            events: vec![(0, ZXk::A), (1, ZXk::A|ZXk::B), (2, ZXk::empty()), (3, ZXk::P)],
+            frames: 6
+        });
+        recorder.play(300, recording);
+        recorder.next_keymap(300, ZXk::P);
+        recorder.stop_playing();
+        assert_eq!(recorder.next_keymap(301, ZXk::P), ZXk::P);
+    }
+
+    #[test]
+    fn keyboard_recorder_update_keyboard_works() {
+        let mut ula = UlaPAL::<Memory16k>::default();
+        let mut recorder = KeyboardRecorder::new();
+        let recording = KeyRecording { events: vec![(0, ZXk::A|ZXk::B)], frames: 2 };
+        recorder.play(0, recording.clone());
+        for frame in 0..2 {
+            ula.set_frame_counter(frame);
+            ula.set_key_state(ZXk::P);
+            recorder.update_keyboard(&mut ula);
+            assert_eq!(ula.get_key_state(), ZXk::A|ZXk::B);
+        }
+        // the played keys are released when the playback ends, the host keys are preserved
+        ula.set_frame_counter(2);
+        ula.set_key_state(ula.get_key_state()|ZXk::P);
+        recorder.update_keyboard(&mut ula);
+        assert!(!recorder.is_playing());
+        assert_eq!(ula.get_key_state(), ZXk::P);
+        // the played keys are released when the playback is stopped
+        recorder.play(3, recording);
+        ula.set_frame_counter(3);
+        recorder.update_keyboard(&mut ula);
+        assert_eq!(ula.get_key_state(), ZXk::A|ZXk::B);
+        recorder.stop_playing();
+        ula.set_frame_counter(4);
+        recorder.update_keyboard(&mut ula);
+        assert_eq!(ula.get_key_state(), ZXk::empty());
+    }
+}
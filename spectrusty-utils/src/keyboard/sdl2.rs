@@ -8,12 +8,14 @@
 //! Keyboard related functions to be used with [SDL2](https://crates.io/crates/sdl2).
 //!
 //! Requires "sdl2" feature to be enabled.
-use sdl2::keyboard::{Mod as Modifier, Keycode};
+use sdl2::keyboard::{Mod as Modifier, Keycode, Scancode};
 use spectrusty::peripherals::{ZXKeyboardMap,
     joystick::{JoystickInterface, Directions},
     serial::KeypadKeys
 };
 
+use super::layout::KeyboardLayout;
+
 type ZXk = ZXKeyboardMap;
 
 /// Returns Spectrum keymap flags with a single bit set corresponding to the provided `key` code
@@ -166,6 +168,63 @@ pub fn map_combined_keys(key: Keycode, pressed: bool, shift_down: bool) -> (ZXKe
     (zxk, removecs)
 }
 
+/// Returns the key code of the key found at the physical position of the `scancode` on the US keyboard layout.
+///
+/// Only the keys of the main alphanumeric block are being recognized, as only their positions differ
+/// between the national keyboard layouts. Otherwise returns `None`.
+pub fn us_layout_keycode(scancode: Scancode) -> Option<Keycode> {
+    Some(match scancode {
+        Scancode::Num1 => Keycode::Num1,
+        Scancode::Num2 => Keycode::Num2,
+        Scancode::Num3 => Keycode::Num3,
+        Scancode::Num4 => Keycode::Num4,
+        Scancode::Num5 => Keycode::Num5,
+        Scancode::Num6 => Keycode::Num6,
+        Scancode::Num7 => Keycode::Num7,
+        Scancode::Num8 => Keycode::Num8,
+        Scancode::Num9 => Keycode::Num9,
+        Scancode::Num0 => Keycode::Num0,
+        Scancode::A => Keycode::A,
+        Scancode::B => Keycode::B,
+        Scancode::C => Keycode::C,
+        Scancode::D => Keycode::D,
+        Scancode::E => Keycode::E,
+        Scancode::F => Keycode::F,
+        Scancode::G => Keycode::G,
+        Scancode::H => Keycode::H,
+        Scancode::I => Keycode::I,
+        Scancode::J => Keycode::J,
+        Scancode::K => Keycode::K,
+        Scancode::L => Keycode::L,
+        Scancode::M => Keycode::M,
+        Scancode::N => Keycode::N,
+        Scancode::O => Keycode::O,
+        Scancode::P => Keycode::P,
+        Scancode::Q => Keycode::Q,
+        Scancode::R => Keycode::R,
+        Scancode::S => Keycode::S,
+        Scancode::T => Keycode::T,
+        Scancode::U => Keycode::U,
+        Scancode::V => Keycode::V,
+        Scancode::W => Keycode::W,
+        Scancode::X => Keycode::X,
+        Scancode::Y => Keycode::Y,
+        Scancode::Z => Keycode::Z,
+        Scancode::Minus => Keycode::Minus,
+        Scancode::Equals => Keycode::Equals,
+        Scancode::LeftBracket => Keycode::LeftBracket,
+        Scancode::RightBracket => Keycode::RightBracket,
+        Scancode::Backslash => Keycode::Backslash,
+        Scancode::Semicolon => Keycode::Semicolon,
+        Scancode::Apostrophe => Keycode::Quote,
+        Scancode::Grave => Keycode::Backquote,
+        Scancode::Comma => Keycode::Comma,
+        Scancode::Period => Keycode::Period,
+        Scancode::Slash => Keycode::Slash,
+        _ => return None
+    })
+}
+
 /// Returns an updated Spectrum keymap state from a `key` down or up event.
 ///
 /// * `cur` is the current keymap state.
@@ -174,7 +233,7 @@ pub fn map_combined_keys(key: Keycode, pressed: bool, shift_down: bool) -> (ZXKe
 /// * `shift_down` should be `true` if one of the `SHIFT` key modifiers has been held down and `false` otherwise.
 /// * `ctrl_down` should be `true` if one of the `CTRL` key modifiers has been held down and `false` otherwise.
 pub fn update_keymap(
-        mut cur: ZXKeyboardMap,
+        cur: ZXKeyboardMap,
         key: Keycode,
         pressed: bool,
         shift_down: bool,
@@ -182,6 +241,47 @@ pub fn update_keymap(
     ) -> ZXKeyboardMap
 {
     let (chg, removecs) = map_combined_keys(key, pressed, shift_down);
+    update_keymap_with_change(cur, chg, removecs, pressed, shift_down, ctrl_down)
+}
+
+/// Returns an updated Spectrum keymap state, with keys remapped by the `layout`, from a key down or up event.
+///
+/// The `layout` refers to the physical positions of the keys, so the keys of the main alphanumeric block
+/// are being identified by their `scancode`, instead of the key code, which already reflects the host
+/// keyboard layout. The key code is used for the remaining keys.
+///
+/// * `cur` is the current keymap state.
+/// * `scancode` is the physical key code.
+/// * `key` is the key code.
+/// * `pressed` should be `true` if the key has been pressed down and `false` if it has been released.
+/// * `shift_down` should be `true` if one of the `SHIFT` key modifiers has been held down and `false` otherwise.
+/// * `ctrl_down` should be `true` if one of the `CTRL` key modifiers has been held down and `false` otherwise.
+/// * `layout` is the national keyboard layout remapping the Spectrum keys.
+pub fn update_keymap_with_layout(
+        cur: ZXKeyboardMap,
+        scancode: Scancode,
+        key: Keycode,
+        pressed: bool,
+        shift_down: bool,
+        ctrl_down: bool,
+        layout: &KeyboardLayout
+    ) -> ZXKeyboardMap
+{
+    let key = us_layout_keycode(scancode).unwrap_or(key);
+    let (chg, removecs) = map_combined_keys(key, pressed, shift_down);
+    let chg = layout.map_keys(chg);
+    update_keymap_with_change(cur, chg, removecs, pressed, shift_down, ctrl_down)
+}
+
+fn update_keymap_with_change(
+        mut cur: ZXKeyboardMap,
+        chg: ZXKeyboardMap,
+        removecs: bool,
+        pressed: bool,
+        shift_down: bool,
+        ctrl_down: bool
+    ) -> ZXKeyboardMap
+{
     if pressed {
         cur.insert(chg);
         if removecs {
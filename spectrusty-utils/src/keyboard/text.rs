@@ -0,0 +1,361 @@
+/*
+    Copyright (C) 2020-2023  Rafal Michalski
+
+    This file is part of SPECTRUSTY, a Rust library for building emulators.
+
+    For the full copyright notice, see the lib.rs file.
+*/
+use spectrusty::peripherals::ZXKeyboardMap;
+
+type ZXk = ZXKeyboardMap;
+
+/// The number of frames each keystroke is being held down by [type_text].
+pub const TYPE_TEXT_PRESS_FRAMES: usize = 2;
+/// The number of frames all keys are being released between keystrokes by [type_text].
+pub const TYPE_TEXT_RELEASE_FRAMES: usize = 2;
+/// The number of frames all keys are being released between keystrokes of the same key by [type_text].
+///
+/// The ROM keyboard routine needs 5 interrupts without the key being pressed to accept it again
+/// as a new keystroke rather than a held down key.
+pub const TYPE_TEXT_REPEAT_RELEASE_FRAMES: usize = 6;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Entry {
+    /// Keys available in every mode.
+    Key(ZXKeyboardMap),
+    /// Keys available only in the keyword `K` mode.
+    Command(ZXKeyboardMap),
+    /// Keys pressed in the extended `E` mode.
+    Extended(ZXKeyboardMap)
+}
+
+const fn ss(key: ZXKeyboardMap) -> ZXKeyboardMap {
+    ZXk::SS.union(key)
+}
+
+static KEYWORDS: &[(&str, Entry)] = &[
+    ("NEW",       Entry::Command(ZXk::A)),
+    ("BORDER",    Entry::Command(ZXk::B)),
+    ("CONTINUE",  Entry::Command(ZXk::C)),
+    ("DIM",       Entry::Command(ZXk::D)),
+    ("REM",       Entry::Command(ZXk::E)),
+    ("FOR",       Entry::Command(ZXk::F)),
+    ("GO TO",     Entry::Command(ZXk::G)),
+    ("GOTO",      Entry::Command(ZXk::G)),
+    ("GO SUB",    Entry::Command(ZXk::H)),
+    ("GOSUB",     Entry::Command(ZXk::H)),
+    ("INPUT",     Entry::Command(ZXk::I)),
+    ("LOAD",      Entry::Command(ZXk::J)),
+    ("LIST",      Entry::Command(ZXk::K)),
+    ("LET",       Entry::Command(ZXk::L)),
+    ("PAUSE",     Entry::Command(ZXk::M)),
+    ("NEXT",      Entry::Command(ZXk::N)),
+    ("POKE",      Entry::Command(ZXk::O)),
+    ("PRINT",     Entry::Command(ZXk::P)),
+    ("PLOT",      Entry::Command(ZXk::Q)),
+    ("RUN",       Entry::Command(ZXk::R)),
+    ("SAVE",      Entry::Command(ZXk::S)),
+    ("RANDOMIZE", Entry::Command(ZXk::T)),
+    ("IF",        Entry::Command(ZXk::U)),
+    ("CLS",       Entry::Command(ZXk::V)),
+    ("DRAW",      Entry::Command(ZXk::W)),
+    ("CLEAR",     Entry::Command(ZXk::X)),
+    ("RETURN",    Entry::Command(ZXk::Y)),
+    ("COPY",      Entry::Command(ZXk::Z)),
+    ("STOP",      Entry::Key(ss(ZXk::A))),
+    ("NOT",       Entry::Key(ss(ZXk::S))),
+    ("STEP",      Entry::Key(ss(ZXk::D))),
+    ("TO",        Entry::Key(ss(ZXk::F))),
+    ("THEN",      Entry::Key(ss(ZXk::G))),
+    ("AND",       Entry::Key(ss(ZXk::Y))),
+    ("OR",        Entry::Key(ss(ZXk::U))),
+    ("AT",        Entry::Key(ss(ZXk::I))),
+    ("<=",        Entry::Key(ss(ZXk::Q))),
+    ("<>",        Entry::Key(ss(ZXk::W))),
+    (">=",        Entry::Key(ss(ZXk::E))),
+    ("READ",      Entry::Extended(ZXk::A)),
+    ("BIN",       Entry::Extended(ZXk::B)),
+    ("LPRINT",    Entry::Extended(ZXk::C)),
+    ("DATA",      Entry::Extended(ZXk::D)),
+    ("TAN",       Entry::Extended(ZXk::E)),
+    ("SGN",       Entry::Extended(ZXk::F)),
+    ("ABS",       Entry::Extended(ZXk::G)),
+    ("SQR",       Entry::Extended(ZXk::H)),
+    ("CODE",      Entry::Extended(ZXk::I)),
+    ("VAL",       Entry::Extended(ZXk::J)),
+    ("LEN",       Entry::Extended(ZXk::K)),
+    ("USR",       Entry::Extended(ZXk::L)),
+    ("PI",        Entry::Extended(ZXk::M)),
+    ("INKEY$",    Entry::Extended(ZXk::N)),
+    ("PEEK",      Entry::Extended(ZXk::O)),
+    ("TAB",       Entry::Extended(ZXk::P)),
+    ("SIN",       Entry::Extended(ZXk::Q)),
+    ("INT",       Entry::Extended(ZXk::R)),
+    ("RESTORE",   Entry::Extended(ZXk::S)),
+    ("RND",       Entry::Extended(ZXk::T)),
+    ("CHR$",      Entry::Extended(ZXk::U)),
+    ("LLIST",     Entry::Extended(ZXk::V)),
+    ("COS",       Entry::Extended(ZXk::W)),
+    ("EXP",       Entry::Extended(ZXk::X)),
+    ("STR$",      Entry::Extended(ZXk::Y)),
+    ("LN",        Entry::Extended(ZXk::Z)),
+    ("ASN",       Entry::Extended(ss(ZXk::Q))),
+    ("ACS",       Entry::Extended(ss(ZXk::W))),
+    ("ATN",       Entry::Extended(ss(ZXk::E))),
+    ("VERIFY",    Entry::Extended(ss(ZXk::R))),
+    ("MERGE",     Entry::Extended(ss(ZXk::T))),
+    ("IN",        Entry::Extended(ss(ZXk::I))),
+    ("OUT",       Entry::Extended(ss(ZXk::O))),
+    ("CIRCLE",    Entry::Extended(ss(ZXk::H))),
+    ("VAL$",      Entry::Extended(ss(ZXk::J))),
+    ("SCREEN$",   Entry::Extended(ss(ZXk::K))),
+    ("ATTR",      Entry::Extended(ss(ZXk::L))),
+    ("BEEP",      Entry::Extended(ss(ZXk::Z))),
+    ("INK",       Entry::Extended(ss(ZXk::X))),
+    ("PAPER",     Entry::Extended(ss(ZXk::C))),
+    ("FLASH",     Entry::Extended(ss(ZXk::V))),
+    ("BRIGHT",    Entry::Extended(ss(ZXk::B))),
+    ("OVER",      Entry::Extended(ss(ZXk::N))),
+    ("INVERSE",   Entry::Extended(ss(ZXk::M))),
+    ("DEF FN",    Entry::Extended(ss(ZXk::N1))),
+    ("FN",        Entry::Extended(ss(ZXk::N2))),
+    ("LINE",      Entry::Extended(ss(ZXk::N3))),
+    ("OPEN #",    Entry::Extended(ss(ZXk::N4))),
+    ("CLOSE #",   Entry::Extended(ss(ZXk::N5))),
+    ("MOVE",      Entry::Extended(ss(ZXk::N6))),
+    ("ERASE",     Entry::Extended(ss(ZXk::N7))),
+    ("POINT",     Entry::Extended(ss(ZXk::N8))),
+    ("CAT",       Entry::Extended(ss(ZXk::N9))),
+    ("FORMAT",    Entry::Extended(ss(ZXk::N0))),
+];
+
+/// Returns the key entry of a single character in the letter `L` mode.
+fn char_entry(ch: char) -> Option<Entry> {
+    let key = match ch.to_ascii_uppercase() {
+        'A' => ZXk::A, 'B' => ZXk::B, 'C' => ZXk::C, 'D' => ZXk::D, 'E' => ZXk::E,
+        'F' => ZXk::F, 'G' => ZXk::G, 'H' => ZXk::H, 'I' => ZXk::I, 'J' => ZXk::J,
+        'K' => ZXk::K, 'L' => ZXk::L, 'M' => ZXk::M, 'N' => ZXk::N, 'O' => ZXk::O,
+        'P' => ZXk::P, 'Q' => ZXk::Q, 'R' => ZXk::R, 'S' => ZXk::S, 'T' => ZXk::T,
+        'U' => ZXk::U, 'V' => ZXk::V, 'W' => ZXk::W, 'X' => ZXk::X, 'Y' => ZXk::Y,
+        'Z' => ZXk::Z,
+        '0' => ZXk::N0, '1' => ZXk::N1, '2' => ZXk::N2, '3' => ZXk::N3, '4' => ZXk::N4,
+        '5' => ZXk::N5, '6' => ZXk::N6, '7' => ZXk::N7, '8' => ZXk::N8, '9' => ZXk::N9,
+        ' ' => ZXk::BR,
+        '\n' => ZXk::EN,
+        '!' => ss(ZXk::N1), '@' => ss(ZXk::N2), '#' => ss(ZXk::N3), '$' => ss(ZXk::N4),
+        '%' => ss(ZXk::N5), '&' => ss(ZXk::N6), '\'' => ss(ZXk::N7), '(' => ss(ZXk::N8),
+        ')' => ss(ZXk::N9), '_' => ss(ZXk::N0),
+        '<' => ss(ZXk::R), '>' => ss(ZXk::T), ';' => ss(ZXk::O), '"' => ss(ZXk::P),
+        '^'|'↑' => ss(ZXk::H), '-' => ss(ZXk::J), '+' => ss(ZXk::K), '=' => ss(ZXk::L),
+        ':' => ss(ZXk::Z), '£' => ss(ZXk::X), '?' => ss(ZXk::C), '/' => ss(ZXk::V),
+        '*' => ss(ZXk::B), ',' => ss(ZXk::N), '.' => ss(ZXk::M),
+        '[' => return Some(Entry::Extended(ss(ZXk::Y))),
+        ']' => return Some(Entry::Extended(ss(ZXk::U))),
+        '©' => return Some(Entry::Extended(ss(ZXk::P))),
+        '~' => return Some(Entry::Extended(ss(ZXk::A))),
+        '|' => return Some(Entry::Extended(ss(ZXk::S))),
+        '\\' => return Some(Entry::Extended(ss(ZXk::D))),
+        '{' => return Some(Entry::Extended(ss(ZXk::F))),
+        '}' => return Some(Entry::Extended(ss(ZXk::G))),
+        _ => return None
+    };
+    if ch.is_ascii_uppercase() {
+        Some(Entry::Key(ZXk::CS|key))
+    }
+    else {
+        Some(Entry::Key(key))
+    }
+}
+
+fn is_word_char(ch: char) -> bool {
+    ch.is_ascii_alphanumeric() || ch == '$'
+}
+
+/// Returns the longest keyword matching the beginning of `text`.
+fn match_keyword(text: &str, prev: Option<char>, k_mode: bool) -> Option<(&'static str, Entry)> {
+    let after_word = matches!(prev, Some(ch) if ch.is_ascii_alphabetic() || ch == '$');
+    KEYWORDS.iter().filter(|&&(keyword, entry)| {
+        if (!k_mode && matches!(entry, Entry::Command(..))) || !text.starts_with(keyword) {
+            return false
+        }
+        if after_word && keyword.starts_with(|ch: char| ch.is_ascii_alphabetic()) {
+            return false
+        }
+        !keyword.ends_with(|ch: char| ch.is_ascii_alphabetic()) ||
+            !text[keyword.len()..].starts_with(is_word_char)
+    })
+    .max_by_key(|(keyword, _)| keyword.len())
+    .copied()
+}
+
+/// Returns keystrokes typing `text`, tokenizing the BASIC keywords if `tokenize` is `true`.
+fn text_keystrokes(text: &str, tokenize: bool) -> Vec<ZXKeyboardMap> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum Literal { None, Quote, Rem }
+
+    let mut strokes = Vec::new();
+    let mut push_entry = |entry| match entry {
+        Entry::Key(keys)|Entry::Command(keys) => strokes.push(keys),
+        Entry::Extended(keys) => strokes.extend_from_slice(&[ZXk::CS|ZXk::SS, keys])
+    };
+    let mut k_mode = true;
+    let mut literal = Literal::None;
+    let mut after_keyword = false;
+    let mut spaces = 0;
+    let mut prev = None;
+    let mut index = 0;
+    while let Some(ch) = text[index..].chars().next() {
+        if tokenize && ch == ' ' && (after_keyword || literal == Literal::None) {
+            if !after_keyword {
+                spaces += 1;
+            }
+            prev = Some(ch);
+            index += 1;
+            continue
+        }
+        if tokenize && literal == Literal::None {
+            if let Some((keyword, entry)) = match_keyword(&text[index..], prev, k_mode) {
+                // the ROM lists keywords surrounded by spaces on its own
+                spaces = 0;
+                push_entry(entry);
+                k_mode = keyword == "THEN";
+                if keyword == "REM" {
+                    literal = Literal::Rem;
+                }
+                after_keyword = true;
+                prev = keyword.chars().last();
+                index += keyword.len();
+                continue
+            }
+            for _ in 0..spaces {
+                push_entry(Entry::Key(ZXk::BR));
+            }
+            spaces = 0;
+        }
+        after_keyword = false;
+        if let Some(entry) = char_entry(ch) {
+            push_entry(entry);
+        }
+        match ch {
+            '\n' => {
+                k_mode = true;
+                literal = Literal::None;
+            }
+            '"' if literal != Literal::Rem => {
+                literal = if literal == Literal::Quote { Literal::None } else { Literal::Quote };
+                k_mode = false;
+            }
+            ':' if literal == Literal::None => k_mode = true,
+            ' '|'0'..='9' => {}
+            _ => k_mode = false
+        }
+        prev = Some(ch);
+        index += ch.len_utf8();
+    }
+    if tokenize {
+        for _ in 0..spaces {
+            push_entry(Entry::Key(ZXk::BR));
+        }
+    }
+    strokes
+}
+
+/// Returns `keys` without the shift keys, unless only the shift keys are pressed.
+fn main_keys(keys: ZXKeyboardMap) -> ZXKeyboardMap {
+    let main = keys - ZXk::CS - ZXk::SS;
+    if main.is_empty() { keys } else { main }
+}
+
+/// Converts keystrokes to the keyboard states for consecutive frames.
+fn keystroke_frames(strokes: &[ZXKeyboardMap]) -> Vec<ZXKeyboardMap> {
+    let mut frames = Vec::with_capacity(strokes.len() * (TYPE_TEXT_PRESS_FRAMES + TYPE_TEXT_RELEASE_FRAMES));
+    for (index, &keys) in strokes.iter().enumerate() {
+        frames.resize(frames.len() + TYPE_TEXT_PRESS_FRAMES, keys);
+        let release = match strokes.get(index + 1) {
+            Some(&next) if main_keys(next) == main_keys(keys) => TYPE_TEXT_REPEAT_RELEASE_FRAMES,
+            _ => TYPE_TEXT_RELEASE_FRAMES
+        };
+        frames.resize(frames.len() + release, ZXk::empty());
+    }
+    frames
+}
+
+/// Returns the sequence of the Spectrum keyboard states "typing" the given `text` into the 48K BASIC editor.
+///
+/// Each element of the returned vector is the keyboard state for one consecutive emulated frame.
+/// Every keystroke is held down for [TYPE_TEXT_PRESS_FRAMES] frames, followed by the released keyboard
+/// for [TYPE_TEXT_RELEASE_FRAMES] frames, or [TYPE_TEXT_REPEAT_RELEASE_FRAMES] if the next keystroke
+/// is of the same key, to respect the ROM's key debouncing.
+///
+/// Characters requiring `SYMBOL SHIFT` or `CAPS SHIFT` are typed with the shift keys held down together
+/// with the character key, and characters available only in the extended mode are preceded with the
+/// `CAPS SHIFT` + `SYMBOL SHIFT` keystroke. New line characters are typed as `ENTER`. Characters without
+/// a Spectrum key are skipped.
+///
+/// Upper case BASIC keywords outside of string literals and `REM` comments are typed as keyword tokens,
+/// following the keyword `K` mode of the editor at the beginning of each statement, and spaces around
+/// them are omitted. Keywords that can be only entered in the `K` mode, found elsewhere, are typed
+/// letter by letter.
+pub fn type_text(text: &str) -> Vec<ZXKeyboardMap> {
+    keystroke_frames(&text_keystrokes(text, true))
+}
+
+/// Returns the sequence of the Spectrum keyboard states "typing" the given `text` character by character.
+///
+/// The same as [type_text] but without tokenizing the BASIC keywords, e.g. for the 128K BASIC editor
+/// or for answering `INPUT` prompts.
+pub fn type_text_plain(text: &str) -> Vec<ZXKeyboardMap> {
+    keystroke_frames(&text_keystrokes(text, false))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keystrokes(frames: &[ZXKeyboardMap]) -> Vec<ZXKeyboardMap> {
+        let mut strokes = Vec::new();
+        let mut last = ZXk::empty();
+        for &keys in frames {
+            if keys != last && !keys.is_empty() {
+                strokes.push(keys);
+            }
+            last = keys;
+        }
+        strokes
+    }
+
+    #[test]
+    fn type_text_works() {
+        let frames = type_text("10 PRINT \"HI\"\n");
+        assert_eq!(keystrokes(&frames), [
+            ZXk::N1, ZXk::N0, ZXk::P,
+            ZXk::SS|ZXk::P, ZXk::CS|ZXk::H, ZXk::CS|ZXk::I, ZXk::SS|ZXk::P,
+            ZXk::EN]);
+        // PRINT and " share the same P key
+        assert_eq!(frames.len(), 8 * (TYPE_TEXT_PRESS_FRAMES + TYPE_TEXT_RELEASE_FRAMES)
+                                 + TYPE_TEXT_REPEAT_RELEASE_FRAMES - TYPE_TEXT_RELEASE_FRAMES);
+        assert_eq!(&frames[..5], [ZXk::N1, ZXk::N1, ZXk::empty(), ZXk::empty(), ZXk::N0]);
+
+        let frames = type_text("20 IF a<=1 THEN LET b=INT PI: REM PRINT [x]");
+        assert_eq!(keystrokes(&frames), [
+            ZXk::N2, ZXk::N0, ZXk::U, ZXk::A, ZXk::SS|ZXk::Q, ZXk::N1, ZXk::SS|ZXk::G,
+            ZXk::L, ZXk::B, ZXk::SS|ZXk::L, ZXk::CS|ZXk::SS, ZXk::R, ZXk::CS|ZXk::SS, ZXk::M,
+            ZXk::SS|ZXk::Z, ZXk::E,
+            ZXk::CS|ZXk::P, ZXk::CS|ZXk::R, ZXk::CS|ZXk::I, ZXk::CS|ZXk::N, ZXk::CS|ZXk::T,
+            ZXk::BR, ZXk::CS|ZXk::SS, ZXk::SS|ZXk::Y, ZXk::X, ZXk::CS|ZXk::SS, ZXk::SS|ZXk::U]);
+        // command keywords outside of the K mode and parts of identifiers are not tokenized
+        let frames = type_text("LET TOTAL=PRINT");
+        assert_eq!(keystrokes(&frames)[..3], [ZXk::L, ZXk::CS|ZXk::T, ZXk::CS|ZXk::O]);
+        assert_eq!(keystrokes(&frames).len(), 12);
+
+        let frames = type_text_plain("PRINT 1");
+        assert_eq!(keystrokes(&frames), [
+            ZXk::CS|ZXk::P, ZXk::CS|ZXk::R, ZXk::CS|ZXk::I, ZXk::CS|ZXk::N, ZXk::CS|ZXk::T,
+            ZXk::BR, ZXk::N1]);
+        // the same key typed twice is released for longer
+        let frames = type_text_plain("aA");
+        assert_eq!(frames.len(), 2 * TYPE_TEXT_PRESS_FRAMES + TYPE_TEXT_REPEAT_RELEASE_FRAMES
+                                 + TYPE_TEXT_RELEASE_FRAMES);
+        assert_eq!(frames[TYPE_TEXT_PRESS_FRAMES + TYPE_TEXT_REPEAT_RELEASE_FRAMES], ZXk::CS|ZXk::A);
+    }
+}
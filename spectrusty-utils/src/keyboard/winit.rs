@@ -8,12 +8,14 @@
 //! Keyboard related functions to be used with [winit](https://crates.io/crates/winit).
 //!
 //! Requires "winit" feature to be enabled.
-use winit::event::{VirtualKeyCode, ElementState, ModifiersState};
+use winit::event::{ScanCode, VirtualKeyCode, ElementState, ModifiersState};
 use spectrusty::peripherals::{ZXKeyboardMap,
     joystick::{JoystickInterface, Directions},
     serial::KeypadKeys
 };
 
+use super::layout::KeyboardLayout;
+
 type ZXk = ZXKeyboardMap;
 
 /// Returns Spectrum keymap flags with a single bit set corresponding to the provided `key` code
@@ -167,15 +169,137 @@ pub fn map_combined_keys(key: VirtualKeyCode, state: ElementState, shift_down: b
     (zxk, removecs)
 }
 
+/// Returns the key code of the key found at the physical position of the `scancode` on the US keyboard layout.
+///
+/// The `scancode` is expected to be the PC set 1 key code, reported on Windows and Linux,
+/// or the virtual key code reported on macOS.
+///
+/// Only the keys of the main alphanumeric block are being recognized, as only their positions differ
+/// between the national keyboard layouts. Otherwise returns `None`.
+#[cfg(not(target_os = "macos"))]
+pub fn us_layout_keycode(scancode: ScanCode) -> Option<VirtualKeyCode> {
+    Some(match scancode {
+        0x02 => VirtualKeyCode::Key1,
+        0x03 => VirtualKeyCode::Key2,
+        0x04 => VirtualKeyCode::Key3,
+        0x05 => VirtualKeyCode::Key4,
+        0x06 => VirtualKeyCode::Key5,
+        0x07 => VirtualKeyCode::Key6,
+        0x08 => VirtualKeyCode::Key7,
+        0x09 => VirtualKeyCode::Key8,
+        0x0a => VirtualKeyCode::Key9,
+        0x0b => VirtualKeyCode::Key0,
+        0x0c => VirtualKeyCode::Minus,
+        0x0d => VirtualKeyCode::Equals,
+        0x10 => VirtualKeyCode::Q,
+        0x11 => VirtualKeyCode::W,
+        0x12 => VirtualKeyCode::E,
+        0x13 => VirtualKeyCode::R,
+        0x14 => VirtualKeyCode::T,
+        0x15 => VirtualKeyCode::Y,
+        0x16 => VirtualKeyCode::U,
+        0x17 => VirtualKeyCode::I,
+        0x18 => VirtualKeyCode::O,
+        0x19 => VirtualKeyCode::P,
+        0x1a => VirtualKeyCode::LBracket,
+        0x1b => VirtualKeyCode::RBracket,
+        0x1e => VirtualKeyCode::A,
+        0x1f => VirtualKeyCode::S,
+        0x20 => VirtualKeyCode::D,
+        0x21 => VirtualKeyCode::F,
+        0x22 => VirtualKeyCode::G,
+        0x23 => VirtualKeyCode::H,
+        0x24 => VirtualKeyCode::J,
+        0x25 => VirtualKeyCode::K,
+        0x26 => VirtualKeyCode::L,
+        0x27 => VirtualKeyCode::Semicolon,
+        0x28 => VirtualKeyCode::Apostrophe,
+        0x29 => VirtualKeyCode::Grave,
+        0x2b => VirtualKeyCode::Backslash,
+        0x2c => VirtualKeyCode::Z,
+        0x2d => VirtualKeyCode::X,
+        0x2e => VirtualKeyCode::C,
+        0x2f => VirtualKeyCode::V,
+        0x30 => VirtualKeyCode::B,
+        0x31 => VirtualKeyCode::N,
+        0x32 => VirtualKeyCode::M,
+        0x33 => VirtualKeyCode::Comma,
+        0x34 => VirtualKeyCode::Period,
+        0x35 => VirtualKeyCode::Slash,
+        _ => return None
+    })
+}
+
+/// Returns the key code of the key found at the physical position of the `scancode` on the US keyboard layout.
+///
+/// The `scancode` is expected to be the PC set 1 key code, reported on Windows and Linux,
+/// or the virtual key code reported on macOS.
+///
+/// Only the keys of the main alphanumeric block are being recognized, as only their positions differ
+/// between the national keyboard layouts. Otherwise returns `None`.
+#[cfg(target_os = "macos")]
+pub fn us_layout_keycode(scancode: ScanCode) -> Option<VirtualKeyCode> {
+    Some(match scancode {
+        0x00 => VirtualKeyCode::A,
+        0x01 => VirtualKeyCode::S,
+        0x02 => VirtualKeyCode::D,
+        0x03 => VirtualKeyCode::F,
+        0x04 => VirtualKeyCode::H,
+        0x05 => VirtualKeyCode::G,
+        0x06 => VirtualKeyCode::Z,
+        0x07 => VirtualKeyCode::X,
+        0x08 => VirtualKeyCode::C,
+        0x09 => VirtualKeyCode::V,
+        0x0b => VirtualKeyCode::B,
+        0x0c => VirtualKeyCode::Q,
+        0x0d => VirtualKeyCode::W,
+        0x0e => VirtualKeyCode::E,
+        0x0f => VirtualKeyCode::R,
+        0x10 => VirtualKeyCode::Y,
+        0x11 => VirtualKeyCode::T,
+        0x12 => VirtualKeyCode::Key1,
+        0x13 => VirtualKeyCode::Key2,
+        0x14 => VirtualKeyCode::Key3,
+        0x15 => VirtualKeyCode::Key4,
+        0x16 => VirtualKeyCode::Key6,
+        0x17 => VirtualKeyCode::Key5,
+        0x18 => VirtualKeyCode::Equals,
+        0x19 => VirtualKeyCode::Key9,
+        0x1a => VirtualKeyCode::Key7,
+        0x1b => VirtualKeyCode::Minus,
+        0x1c => VirtualKeyCode::Key8,
+        0x1d => VirtualKeyCode::Key0,
+        0x1e => VirtualKeyCode::RBracket,
+        0x1f => VirtualKeyCode::O,
+        0x20 => VirtualKeyCode::U,
+        0x21 => VirtualKeyCode::LBracket,
+        0x22 => VirtualKeyCode::I,
+        0x23 => VirtualKeyCode::P,
+        0x25 => VirtualKeyCode::L,
+        0x26 => VirtualKeyCode::J,
+        0x27 => VirtualKeyCode::Apostrophe,
+        0x28 => VirtualKeyCode::K,
+        0x29 => VirtualKeyCode::Semicolon,
+        0x2a => VirtualKeyCode::Backslash,
+        0x2b => VirtualKeyCode::Comma,
+        0x2c => VirtualKeyCode::Slash,
+        0x2d => VirtualKeyCode::N,
+        0x2e => VirtualKeyCode::M,
+        0x2f => VirtualKeyCode::Period,
+        0x32 => VirtualKeyCode::Grave,
+        _ => return None
+    })
+}
+
 /// Returns an updated Spectrum keymap state from a keyboard input event.
 ///
 /// * `cur` is the current keymap state.
 /// * `key` is the key code.
-/// * `pressed` should be `true` if the `key` has been pressed down and `false` if it has been released.
+/// * `state` should be `Pressed` if the `key` has been pressed down or `Released` if it has been released.
 /// * `shift_down` should be `true` if one of the `SHIFT` key modifiers has been held down and `false` otherwise.
 /// * `ctrl_down` should be `true` if one of the `CTRL` key modifiers has been held down and `false` otherwise.
 pub fn update_keymap(
-        mut cur: ZXKeyboardMap,
+        cur: ZXKeyboardMap,
         key: VirtualKeyCode,
         state: ElementState,
         shift_down: bool,
@@ -183,6 +307,49 @@ pub fn update_keymap(
     ) -> ZXKeyboardMap
 {
     let (chg, removecs) = map_combined_keys(key, state, shift_down);
+    update_keymap_with_change(cur, chg, removecs, state, shift_down, ctrl_down)
+}
+
+/// Returns an updated Spectrum keymap state, with keys remapped by the `layout`, from a keyboard input event.
+///
+/// The `layout` refers to the physical positions of the keys, so the keys of the main alphanumeric block
+/// are being identified by their `scancode`, instead of the virtual key code, which already reflects
+/// the host keyboard layout. The virtual key code is used for the remaining keys.
+///
+/// * `cur` is the current keymap state.
+/// * `scancode` is the `scancode` property from the `KeyboardInput` events.
+/// * `key` is the `virtual_keycode` property from the `KeyboardInput` events.
+/// * `state` should be `Pressed` if the key has been pressed down or `Released` if it has been released.
+/// * `shift_down` should be `true` if one of the `SHIFT` key modifiers has been held down and `false` otherwise.
+/// * `ctrl_down` should be `true` if one of the `CTRL` key modifiers has been held down and `false` otherwise.
+/// * `layout` is the national keyboard layout remapping the Spectrum keys.
+pub fn update_keymap_with_layout(
+        cur: ZXKeyboardMap,
+        scancode: ScanCode,
+        key: Option<VirtualKeyCode>,
+        state: ElementState,
+        shift_down: bool,
+        ctrl_down: bool,
+        layout: &KeyboardLayout
+    ) -> ZXKeyboardMap
+{
+    let (chg, removecs) = match us_layout_keycode(scancode).or(key) {
+        Some(key) => map_combined_keys(key, state, shift_down),
+        None => (ZXk::empty(), false)
+    };
+    let chg = layout.map_keys(chg);
+    update_keymap_with_change(cur, chg, removecs, state, shift_down, ctrl_down)
+}
+
+fn update_keymap_with_change(
+        mut cur: ZXKeyboardMap,
+        chg: ZXKeyboardMap,
+        removecs: bool,
+        state: ElementState,
+        shift_down: bool,
+        ctrl_down: bool
+    ) -> ZXKeyboardMap
+{
     if let ElementState::Pressed = state {
         cur.insert(chg);
         if removecs {
@@ -208,7 +375,7 @@ pub fn update_keymap(
 ///
 /// * `cur` is the current keymap state.
 /// * `key` is the key code.
-/// * `pressed` should be `true` if the `key` has been pressed down and `false` if it has been released.
+/// * `state` should be `Pressed` if the `key` has been pressed down or `Released` if it has been released.
 /// * `modifier` is the `modifiers` property from the `KeyboardInput` events.
 pub fn update_keymap_with_modifier(
         cur: ZXKeyboardMap,
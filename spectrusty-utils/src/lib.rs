@@ -20,6 +20,8 @@
 */
 //! Additional utilities for the emulators, based on the SPECTRUSTY library.
 // pub mod dynamic;
+pub mod disasm;
+pub mod joystick;
 pub mod keyboard;
 pub mod io;
 pub mod printer;
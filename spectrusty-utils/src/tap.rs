@@ -8,6 +8,7 @@
 //! **TAP** format related utilities for sweetening the handling of **TAP** files.
 use core::fmt;
 use core::convert::TryFrom;
+use std::collections::BTreeMap;
 use std::io::{Read, Write, Result, Seek, SeekFrom};
 
 use spectrusty::formats::tap::*;
@@ -32,15 +33,27 @@ pub enum Tap<F> {
 }
 
 /// The struct that emulates a simple tape recorder.
+///
+/// Prefer [Tape::set_tap] over assigning [Tape::tap] directly, so the tape recorder options, like
+/// [Tape::auto_continue], are applied to the inserted [Tap].
+/// Use [Tape::default] or [Tape::new_with_tape] to create new instances.
 #[derive(Debug)]
 pub struct Tape<F> {
     /// `true` if the tape is playing, depending on the [Tap] variant it may indicate tape playback or recording.
     /// `false` then the tape has stopped.
     pub running: bool,
     /// `Some(tap)` indicates the tape cassette is inserted, `None` - there is no tape.
-    pub tap: Option<Tap<F>>
+    pub tap: Option<Tap<F>>,
+    auto_continue: bool,
+    auto_stop: bool,
+    auto_stop_frames: u32,
+    idle_frames: Option<u32>,
+    bookmarks: BTreeMap<String, u32>
 }
 
+/// The default number of frames after which the tape is stopped by [Tape::update_auto_stop], 2 seconds at 50 Hz.
+pub const DEFAULT_AUTO_STOP_FRAMES: u32 = 100;
+
 impl<F> fmt::Debug for Tap<F> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -52,7 +65,15 @@ impl<F> fmt::Debug for Tap<F> {
 
 impl<F> Default for Tape<F> {
     fn default() -> Self {
-        Tape { running: false, tap: None }
+        Tape {
+            running: false,
+            tap: None,
+            auto_continue: true,
+            auto_stop: false,
+            auto_stop_frames: DEFAULT_AUTO_STOP_FRAMES,
+            idle_frames: None,
+            bookmarks: BTreeMap::new()
+        }
     }
 }
 
@@ -207,32 +228,82 @@ impl<F: Write + Read + Seek> Tap<F> {
     pub fn rewind_nth_chunk(&mut self, chunk_no: u32) -> Result<Option<bool>> {
         self.reader_mut().map(|rd| rd.rewind_nth_chunk(chunk_no)).transpose()
     }
+
+    /// Returns the value of the tape counter if a tape is inserted and its variant is [Tap::Reader].
+    /// Otherwise returns `None`.
+    ///
+    /// See [TapChunkPulseIter::tape_counter].
+    pub fn tape_counter(&self) -> Option<u64> {
+        self.reader_ref().map(|rd| rd.tape_counter())
+    }
+
+    /// Conditionally returns the overall progress of reading a tape as a percentage if its variant is
+    /// [Tap::Reader]. In this instance returns `Some(percent)`. Otherwise returns `None`.
+    ///
+    /// See [TapChunkReader::tape_overall_progress].
+    pub fn tape_overall_progress(&self) -> Option<f32> {
+        self.reader_ref().map(|rd| rd.as_ref().tape_overall_progress())
+    }
 }
 
 impl<F: Write + Read + Seek> Tape<F> {
     /// Returns a new instance of [Tape] with the tape file inserted.
     pub fn new_with_tape(file: F) -> Self {
         let tap = Tap::new_reader(file);
-        Tape { running: false, tap: Some(tap) }
+        let mut tape = Tape { tap: Some(tap), ..Default::default() };
+        tape.update_auto_continue();
+        tape
     }
 
     /// Inserts the tape file as a [Tap::Reader].
     /// Returns the previously inserted [Tap] instance.
+    ///
+    /// Removes all bookmarks.
     pub fn insert_as_reader(&mut self, file: F) -> Option<Tap<F>> {
         let tap = Tap::new_reader(file);
-        self.tap.replace(tap)
+        let prev = self.tap.replace(tap);
+        self.bookmarks.clear();
+        self.update_auto_continue();
+        prev
     }
 
     /// Tries to insert the tape file as a [Tap::Writer].
     /// Returns the previously inserted [Tap] instance.
+    ///
+    /// Removes all bookmarks on success.
     pub fn try_insert_as_writer(&mut self, file: F) -> Result<Option<Tap<F>>> {
         let tap = Tap::try_new_writer(file)?;
+        self.bookmarks.clear();
         Ok(self.tap.replace(tap))
     }
 
+    /// Returns a reference to the inserted [Tap] instance.
+    pub fn tap_ref(&self) -> Option<&Tap<F>> {
+        self.tap.as_ref()
+    }
+
+    /// Returns a mutable reference to the inserted [Tap] instance.
+    pub fn tap_mut(&mut self) -> Option<&mut Tap<F>> {
+        self.tap.as_mut()
+    }
+
+    /// Replaces the inserted [Tap] instance with `tap` or ejects it if `tap` is `None`.
+    /// Returns the previously inserted [Tap] instance.
+    ///
+    /// Removes all bookmarks.
+    pub fn set_tap(&mut self, tap: Option<Tap<F>>) -> Option<Tap<F>> {
+        let prev = core::mem::replace(&mut self.tap, tap);
+        self.bookmarks.clear();
+        self.update_auto_continue();
+        prev
+    }
+
     /// Ejects and returns the previously inserted [Tap] instance.
+    ///
+    /// Removes all bookmarks.
     pub fn eject(&mut self) -> Option<Tap<F>> {
         self.running = false;
+        self.bookmarks.clear();
         self.tap.take()
     }
 
@@ -250,6 +321,7 @@ impl<F: Write + Read + Seek> Tape<F> {
             if tap.is_writer() {
                 let tap = self.tap.take().unwrap().try_into_reader()?;
                 self.tap = Some(tap);
+                self.update_auto_continue();
                 return Ok(true)
             }
         }
@@ -328,22 +400,22 @@ impl<F: Write + Read + Seek> Tape<F> {
         false
     }
 
-    /// Returns a mutable reference to the pulse iterator if the current variant of [Tape::tap] is [Tap::Reader].
+    /// Returns a mutable reference to the pulse iterator if the variant of the inserted [Tap] is [Tap::Reader].
     pub fn reader_mut(&mut self) -> Option<&mut TapChunkPulseIter<F>> {
         self.tap.as_mut().and_then(|tap| tap.reader_mut())
     }
 
-    /// Returns a mutable reference to the tap chunk writer if the current variant of [Tape::tap] is [Tap::Writer].
+    /// Returns a mutable reference to the tap chunk writer if the variant of the inserted [Tap] is [Tap::Writer].
     pub fn writer_mut(&mut self) -> Option<&mut TapChunkWriter<F>> {
         self.tap.as_mut().and_then(|tap| tap.writer_mut())
     }
 
-    /// Returns a reference to the pulse iterator if the current variant of [Tape::tap] is [Tap::Reader].
+    /// Returns a reference to the pulse iterator if the variant of the inserted [Tap] is [Tap::Reader].
     pub fn reader_ref(&self) -> Option<&TapChunkPulseIter<F>> {
         self.tap.as_ref().and_then(|tap| tap.reader_ref())
     }
 
-    /// Returns a reference to the tap chunk writer if the current variant of [Tape::tap] is [Tap::Writer].
+    /// Returns a reference to the tap chunk writer if the variant of the inserted [Tap] is [Tap::Writer].
     pub fn writer_ref(&self) -> Option<&TapChunkWriter<F>> {
         self.tap.as_ref().and_then(|tap| tap.writer_ref())
     }
@@ -375,11 +447,100 @@ impl<F: Write + Read + Seek> Tape<F> {
 
     /// Sets [Tape::running] to `true` and ensures the inserted variant is a [Tap::Reader].
     ///
+    /// If [Tape::auto_continue] is `false` and the current chunk has already been played, the tape
+    /// is forwarded to the next chunk first.
+    ///
     /// Returns `Ok(true)` if the state of `self` changes.
     pub fn play(&mut self) -> Result<bool> {
         let running = self.running;
         self.running = true;
-        Ok(self.make_reader()? || !running)
+        let mut changed = self.make_reader()? || !running;
+        if !self.auto_continue {
+            if let Some(reader) = self.reader_mut().filter(|rd| rd.is_done()) {
+                changed |= reader.forward_chunk()?;
+            }
+        }
+        Ok(changed)
+    }
+
+    /// Returns `true` if the tape continues playing the next chunk after the current one ends.
+    ///
+    /// By default it's `true`.
+    pub fn auto_continue(&self) -> bool {
+        self.auto_continue
+    }
+
+    /// Determines if the tape should continue playing the next chunk after the current one ends.
+    ///
+    /// * `true` the playback continues with the next chunk without any intervention, which allows
+    ///   loading multi-part programs hands-free.
+    /// * `false` the pulses end with each chunk and the tape needs to be forwarded with
+    ///   [Tape::forward_chunk] or started again with [Tape::play] to play the next chunk.
+    pub fn set_auto_continue(&mut self, auto_continue: bool) {
+        self.auto_continue = auto_continue;
+        self.update_auto_continue();
+    }
+
+    /// Returns `true` if the tape stops automatically after the program finishes loading.
+    ///
+    /// By default it's `false`.
+    pub fn auto_stop(&self) -> bool {
+        self.auto_stop
+    }
+
+    /// Determines if the tape should stop automatically after the program finishes loading.
+    ///
+    /// The detection relies on [Tape::update_auto_stop] being called after each emulated frame.
+    pub fn set_auto_stop(&mut self, auto_stop: bool) {
+        self.auto_stop = auto_stop;
+        self.idle_frames = None;
+    }
+
+    /// Returns the number of frames without loading activity after which the tape is stopped.
+    pub fn auto_stop_frames(&self) -> u32 {
+        self.auto_stop_frames
+    }
+
+    /// Changes the number of frames without loading activity after which the tape is stopped.
+    ///
+    /// By default it's [DEFAULT_AUTO_STOP_FRAMES].
+    pub fn set_auto_stop_frames(&mut self, frames: u32) {
+        self.auto_stop_frames = frames;
+    }
+
+    /// Updates the auto-stop heuristics and stops the tape if the program has finished loading.
+    ///
+    /// Should be called once after each emulated frame with `loading` indicating if the loading routine
+    /// was active during that frame, e.g. if the `EAR IN` has been probed by the CPU more frequently
+    /// than the keyboard scanning routine would do (it also reads the same port), or if the
+    /// [ROM loading routine][romload] has been detected.
+    ///
+    /// The frames without loading activity are counted only after the loading routine has been active
+    /// since the tape started playing, and only when the tape is between blocks. The tape stops when
+    /// the counter reaches [Tape::auto_stop_frames].
+    ///
+    /// Returns `true` if the tape has been stopped.
+    pub fn update_auto_stop(&mut self, loading: bool) -> bool {
+        if !(self.auto_stop && self.is_playing()) {
+            self.idle_frames = None;
+            return false
+        }
+        if loading {
+            self.idle_frames = Some(0);
+            return false
+        }
+        let between_blocks = self.reader_ref().map(|rd| {
+            let state = rd.get_ref().state();
+            state.is_lead() || state.is_done()
+        }).unwrap_or(false);
+        if let Some(frames) = self.idle_frames.as_mut().filter(|_| between_blocks) {
+            *frames += 1;
+            if *frames >= self.auto_stop_frames {
+                self.stop();
+                return true
+            }
+        }
+        false
     }
 
     /// Sets [Tape::running] to `true` and ensures the inserted variant is a [Tap::Writer].
@@ -394,6 +555,14 @@ impl<F: Write + Read + Seek> Tape<F> {
     /// Sets [Tape::running] to `false`.
     pub fn stop(&mut self) {
         self.running = false;
+        self.idle_frames = None;
+    }
+
+    fn update_auto_continue(&mut self) {
+        let auto_continue = self.auto_continue;
+        if let Some(reader) = self.reader_mut() {
+            reader.auto_next = auto_continue;
+        }
     }
 
     /// Conditionally rewinds a tape if it's inserted and its variant is [Tap::Reader].
@@ -428,4 +597,237 @@ impl<F: Write + Read + Seek> Tape<F> {
     pub fn rewind_nth_chunk(&mut self, chunk_no: u32) -> Result<Option<bool>> {
         self.reader_mut().map(|rd| rd.rewind_nth_chunk(chunk_no)).transpose()
     }
+
+    /// Returns the value of the tape counter if a tape is inserted and its variant is [Tap::Reader].
+    /// Otherwise returns `None`.
+    ///
+    /// See [TapChunkPulseIter::tape_counter].
+    pub fn tape_counter(&self) -> Option<u64> {
+        self.reader_ref().map(|rd| rd.tape_counter())
+    }
+
+    /// Conditionally returns the overall progress of reading a tape as a percentage if it's inserted
+    /// and its variant is [Tap::Reader]. In this instance returns `Some(percent)`. Otherwise returns `None`.
+    ///
+    /// Front-ends can use it to display a single progress bar of the whole tape.
+    ///
+    /// See [TapChunkReader::tape_overall_progress].
+    pub fn tape_overall_progress(&self) -> Option<f32> {
+        self.reader_ref().map(|rd| rd.as_ref().tape_overall_progress())
+    }
+
+    /// Labels the chunk `chunk_no` of the inserted tape with the `name`, so it can be found
+    /// later with [Tape::rewind_to_bookmark].
+    ///
+    /// The chunks are numbered from 1, just like [TapChunkRead::chunk_no].
+    ///
+    /// Returns the chunk number previously labeled with the same `name`.
+    ///
+    /// The bookmarks are removed when the tape is ejected or another one is inserted.
+    pub fn set_bookmark<S: Into<String>>(&mut self, name: S, chunk_no: u32) -> Option<u32> {
+        self.bookmarks.insert(name.into(), chunk_no)
+    }
+
+    /// Removes the bookmark with the given `name`. Returns the chunk number it was labeling.
+    pub fn remove_bookmark(&mut self, name: &str) -> Option<u32> {
+        self.bookmarks.remove(name)
+    }
+
+    /// Returns an iterator of `(name, chunk_no)` pairs over the bookmarks, ordered by their names.
+    pub fn bookmarks(&'_ self) -> impl Iterator<Item=(&'_ str, u32)> + '_ {
+        self.bookmarks.iter().map(|(name, &chunk_no)| (name.as_str(), chunk_no))
+    }
+
+    /// Conditionally rewinds or forwards a tape to the chunk labeled with the bookmark `name`
+    /// if it's inserted and its variant is [Tap::Reader]. In this instance returns `Ok(Some(was_a_chunk))`.
+    /// Otherwise, or if there is no such bookmark, returns `Ok(None)`.
+    pub fn rewind_to_bookmark(&mut self, name: &str) -> Result<Option<bool>> {
+        match self.bookmarks.get(name) {
+            Some(&chunk_no) => self.rewind_nth_chunk(chunk_no),
+            None => Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use super::*;
+
+    fn two_chunks_tap() -> Cursor<Vec<u8>> {
+        let mut tap = Vec::new();
+        for data in [&[0x00u8, 1, 2, 3][..], &[0xFF, 4, 5, 6, 7]] {
+            tap.extend_from_slice(&(data.len() as u16 + 1).to_le_bytes());
+            tap.extend_from_slice(data);
+            tap.push(data.iter().fold(0, |sum, b| sum ^ b));
+        }
+        Cursor::new(tap)
+    }
+
+    #[test]
+    fn tape_bookmarks_work() {
+        let mut tap = Vec::new();
+        for n in 0..5u8 {
+            let data = [0xFF, n, n, n];
+            tap.extend_from_slice(&(data.len() as u16 + 1).to_le_bytes());
+            tap.extend_from_slice(&data);
+            tap.push(data.iter().fold(0, |sum, b| sum ^ b));
+        }
+        let mut tape = Tape::new_with_tape(Cursor::new(tap));
+        assert_eq!(tape.rewind_to_bookmark("game 3").unwrap(), None);
+        assert_eq!(tape.set_bookmark("game 3", 3), None);
+        assert_eq!(tape.set_bookmark("game 5", 5), None);
+        assert_eq!(tape.set_bookmark("extra", 7), None);
+        assert_eq!(tape.set_bookmark("extra", 6), Some(7));
+        assert_eq!(tape.bookmarks().collect::<Vec<_>>(), [("extra", 6), ("game 3", 3), ("game 5", 5)]);
+        assert_eq!(tape.forward_chunk().unwrap(), Some(true));
+        assert_eq!(tape.reader_ref().unwrap().chunk_no(), 1);
+        assert_eq!(tape.rewind_to_bookmark("game 3").unwrap(), Some(true));
+        assert_eq!(tape.reader_ref().unwrap().chunk_no(), 3);
+        assert_eq!(tape.rewind_to_bookmark("game 5").unwrap(), Some(true));
+        assert_eq!(tape.reader_ref().unwrap().chunk_no(), 5);
+        assert_eq!(tape.rewind_to_bookmark("game 3").unwrap(), Some(true));
+        assert_eq!(tape.reader_ref().unwrap().chunk_no(), 3);
+        assert_eq!(tape.rewind_to_bookmark("extra").unwrap(), Some(false));
+        assert_eq!(tape.remove_bookmark("extra"), Some(6));
+        assert_eq!(tape.rewind_to_bookmark("extra").unwrap(), None);
+        // bookmarks are removed with the tape
+        tape.eject();
+        assert_eq!(tape.bookmarks().count(), 0);
+    }
+
+    #[test]
+    fn tape_overall_progress_works() {
+        let mut tap = Vec::new();
+        for size in [17usize, 300, 2, 1000] {
+            let data: Vec<u8> = (0..size).map(|n| n as u8).collect();
+            tap.extend_from_slice(&(data.len() as u16 + 1).to_le_bytes());
+            tap.extend_from_slice(&data);
+            tap.push(data.iter().fold(0, |sum, b| sum ^ b));
+        }
+        let total = tap.len();
+        let mut tape = Tape::<Cursor<Vec<u8>>>::default();
+        assert_eq!(tape.tape_overall_progress(), None);
+        tape.insert_as_reader(Cursor::new(tap));
+        assert_eq!(tape.tape_overall_progress(), Some(0.0));
+        assert!(tape.play().unwrap());
+        let mut progress = 0.0;
+        let mut chunk_progress = vec![];
+        loop {
+            let reader = tape.playing_reader_mut().unwrap();
+            if reader.next().is_none() {
+                break
+            }
+            let chunk_no = reader.chunk_no();
+            let next_progress = tape.tape_overall_progress().unwrap();
+            assert!(next_progress >= progress);
+            progress = next_progress;
+            if chunk_progress.len() < chunk_no as usize {
+                chunk_progress.push(progress);
+            }
+        }
+        assert_eq!(progress, 100.0);
+        // the progress accounts for the chunk sizes
+        assert_eq!(chunk_progress.len(), 4);
+        assert!(chunk_progress[2] - chunk_progress[1] > 20.0);
+        assert!(chunk_progress[3] - chunk_progress[2] < 1.0);
+        assert!(tape.rewind());
+        assert_eq!(tape.tape_overall_progress(), Some(0.0));
+        assert_eq!(tape.rewind_nth_chunk(2).unwrap(), Some(true));
+        let expected = (19 + 2 + 2) as f32 * 100.0 / total as f32;
+        assert!((tape.tape_overall_progress().unwrap() - expected).abs() < 1e-3);
+    }
+
+    #[test]
+    fn tape_auto_continue_works() {
+        let mut tape = Tape::new_with_tape(two_chunks_tap());
+        assert!(tape.auto_continue());
+        assert!(tape.play().unwrap());
+        let reader = tape.playing_reader_mut().unwrap();
+        assert!(reader.auto_next);
+        assert!(reader.by_ref().count() > 0);
+        assert!(reader.is_done());
+        assert_eq!(reader.chunk_no(), 2);
+
+        tape.stop();
+        assert!(tape.rewind());
+        tape.set_auto_continue(false);
+        assert!(!tape.auto_continue());
+        assert!(!tape.reader_ref().unwrap().auto_next);
+        assert!(tape.play().unwrap());
+        let reader = tape.playing_reader_mut().unwrap();
+        assert_eq!(reader.chunk_no(), 1);
+        assert!(reader.by_ref().count() > 0);
+        assert!(reader.is_done());
+        assert_eq!(reader.chunk_no(), 1);
+        assert_eq!(reader.next(), None);
+        // the next chunk is played after starting the tape again
+        assert!(tape.play().unwrap());
+        let reader = tape.playing_reader_mut().unwrap();
+        assert_eq!(reader.chunk_no(), 2);
+        assert!(reader.by_ref().count() > 0);
+        assert!(!tape.play().unwrap());
+        // re-inserted tapes keep the setting
+        tape.insert_as_reader(two_chunks_tap());
+        assert!(!tape.reader_ref().unwrap().auto_next);
+        let tap = tape.set_tap(Some(Tap::new_reader(two_chunks_tap())));
+        assert!(tap.unwrap().reader_ref().is_some());
+        assert!(!tape.tap_ref().unwrap().reader_ref().unwrap().auto_next);
+        tape.set_auto_continue(true);
+        assert!(tape.reader_ref().unwrap().auto_next);
+        assert!(tape.set_tap(None).is_some());
+        assert!(tape.tap_mut().is_none());
+    }
+
+    #[test]
+    fn tape_auto_stop_works() {
+        let mut tape = Tape::new_with_tape(two_chunks_tap());
+        assert!(!tape.auto_stop());
+        assert_eq!(tape.auto_stop_frames(), DEFAULT_AUTO_STOP_FRAMES);
+        tape.set_auto_stop(true);
+        tape.set_auto_stop_frames(10);
+        assert!(tape.play().unwrap());
+        // the loader hasn't been active yet
+        for _ in 0..20 {
+            assert!(!tape.update_auto_stop(false));
+        }
+        assert_eq!(tape.tap_state(), TapState::Playing);
+        // loading the first block
+        let reader = tape.playing_reader_mut().unwrap();
+        while !reader.get_ref().state().is_data() {
+            reader.next().unwrap();
+        }
+        assert!(!tape.update_auto_stop(true));
+        // the loader stopped in the middle of the block
+        for _ in 0..20 {
+            assert!(!tape.update_auto_stop(false));
+        }
+        // the first block completes and the lead of the second block begins
+        let reader = tape.playing_reader_mut().unwrap();
+        while !reader.get_ref().state().is_lead() {
+            reader.next().unwrap();
+        }
+        assert_eq!(reader.chunk_no(), 2);
+        assert!(!tape.update_auto_stop(true));
+        for _ in 1..10 {
+            assert!(!tape.update_auto_stop(false));
+            assert_eq!(tape.tap_state(), TapState::Playing);
+        }
+        // the loader re-entered in time
+        assert!(!tape.update_auto_stop(true));
+        for _ in 1..10 {
+            assert!(!tape.update_auto_stop(false));
+        }
+        assert!(tape.update_auto_stop(false));
+        assert_eq!(tape.tap_state(), TapState::Idle);
+        assert!(!tape.update_auto_stop(false));
+        // disabled
+        tape.set_auto_stop(false);
+        assert!(tape.play().unwrap());
+        assert!(!tape.update_auto_stop(true));
+        for _ in 0..20 {
+            assert!(!tape.update_auto_stop(false));
+        }
+        assert_eq!(tape.tap_state(), TapState::Playing);
+    }
 }
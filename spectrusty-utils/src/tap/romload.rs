@@ -6,6 +6,7 @@
     For the full copyright notice, see the lib.rs file.
 */
 //! Tools for instant **TAP** loading and verifying via ROM loading routines.
+use core::fmt;
 use std::num::Wrapping;
 use std::io::{self, Read};
 use spectrusty::z80emu::{Cpu, CpuFlags, Prefix, StkReg16, Reg8};
@@ -15,6 +16,13 @@ use spectrusty::memory::ZxMemory;
 /// instantly load (or verify) data directly into the emulator's memory, reading from the
 /// provided data source.
 ///
+/// The loading routine is detected when the `cpu` is waiting for the signal edges inside the
+/// `LD-BYTES` ROM routine, called from `0x0556` and with the interrupts disabled. The code of
+/// the routine is verified in the memory, so this works on 128k models only when the 48k BASIC
+/// ROM is paged in, as it is during the tape loading. Custom loaders are not detected, so their
+/// data should be provided by the **TAPE** pulse playback. Custom loaders calling the ROM routine
+/// can be excluded with [TapeLoaderDetector::try_instant_tape_load_or_verify].
+///
 /// Provide a mutable reference to a `cpu` and `memory` instances of your emulator.
 ///
 /// Provide a closure that will be called only if loading or verifying will be performed.
@@ -112,6 +120,148 @@ pub fn try_instant_rom_tape_load_or_verify<C: Cpu, M: ZxMemory, R: Read, F: FnOn
     Ok(Some((de - limit) as u32 + 2))
 }
 
+/// The type of the tape loader recognized by [TapeLoaderDetector].
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TapeLoaderKind {
+    /// The standard ROM loading routine.
+    Rom,
+    /// A specific loader named by the user when adding its signature with
+    /// [TapeLoaderDetector::with_signature].
+    Named(&'static str),
+    /// An other custom loader.
+    Custom,
+}
+
+impl fmt::Display for TapeLoaderKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TapeLoaderKind::Rom => "ROM",
+            TapeLoaderKind::Named(name) => name,
+            TapeLoaderKind::Custom => "custom"
+        })
+    }
+}
+
+/// The edge sampling loop of the ROM `LD-EDGE-1` routine: `IN A,(0xFE), RRA, RET NC, XOR C, AND 0x20, JR Z,...`.
+///
+/// Most custom loaders are derived from the ROM routine, so its relocated copy in RAM indicates a custom loader.
+pub const CUSTOM_EDGE_LOOP_SIGNATURE: &[u8] = &[0xDB, 0xFE, 0x1F, 0xD0, 0xA9, 0xE6, 0x20, 0x28];
+
+/// Recognizes the type of the tape loader by scanning RAM for the signatures of the loading routines,
+/// so the instant loading can be restricted to the standard ROM loader.
+///
+/// By default only the [CUSTOM_EDGE_LOOP_SIGNATURE] is being recognized as [TapeLoaderKind::Custom].
+/// The signatures of specific loaders can be added with [TapeLoaderDetector::with_signature] as
+/// [TapeLoaderKind::Named]. No signatures of the specific loaders are provided by this crate.
+#[derive(Clone, Debug)]
+pub struct TapeLoaderDetector {
+    signatures: Vec<(TapeLoaderKind, Vec<u8>)>
+}
+
+impl Default for TapeLoaderDetector {
+    fn default() -> Self {
+        TapeLoaderDetector {
+            signatures: vec![(TapeLoaderKind::Custom, CUSTOM_EDGE_LOOP_SIGNATURE.to_vec())]
+        }
+    }
+}
+
+impl TapeLoaderDetector {
+    /// Creates a detector without any signatures of the custom loaders.
+    pub fn new() -> Self {
+        TapeLoaderDetector { signatures: Vec::new() }
+    }
+    /// Adds the `code` signature of the loader `kind`, builder style.
+    ///
+    /// # Panics
+    /// Panics if `code` is empty.
+    pub fn with_signature<S: Into<Vec<u8>>>(mut self, kind: TapeLoaderKind, code: S) -> Self {
+        let code = code.into();
+        assert!(!code.is_empty(), "TapeLoaderDetector: the signature can't be empty");
+        self.signatures.push((kind, code));
+        self
+    }
+    /// Returns an iterator of the loader signatures.
+    pub fn signatures(&self) -> impl Iterator<Item=(TapeLoaderKind, &[u8])> + '_ {
+        self.signatures.iter().map(|(kind, code)| (*kind, code.as_slice()))
+    }
+    /// Returns the type of the custom loader found in the paged-in RAM of the `memory` (addresses
+    /// from `0x4000`).
+    ///
+    /// The signatures of the specific loaders take precedence over the [TapeLoaderKind::Custom] ones.
+    /// Returns `None` if none of the signatures are found.
+    pub fn detect_custom<M: ZxMemory>(&self, memory: &M) -> Option<TapeLoaderKind> {
+        let mut found = None;
+        for &(kind, ref code) in self.signatures.iter() {
+            if found.is_some() && kind == TapeLoaderKind::Custom {
+                continue
+            }
+            if contains_in_ram(memory, code) {
+                if kind != TapeLoaderKind::Custom {
+                    return Some(kind)
+                }
+                found = Some(kind);
+            }
+        }
+        found
+    }
+    /// Returns the type of the active tape loader.
+    ///
+    /// Returns [TapeLoaderKind::Rom] only if the ROM loading routine is currently waiting for the data
+    /// (see [try_instant_rom_tape_load_or_verify]) and none of the custom loader signatures are present
+    /// in RAM. Otherwise returns the type of the custom loader found in RAM or `None`.
+    pub fn detect<C: Cpu, M: ZxMemory>(&self, cpu: &C, memory: &M) -> Option<TapeLoaderKind> {
+        match self.detect_custom(memory) {
+            None if is_rom_loading(cpu, memory).is_some() => Some(TapeLoaderKind::Rom),
+            kind => kind
+        }
+    }
+    /// Performs the instant loading with [try_instant_rom_tape_load_or_verify] only if the standard
+    /// ROM loader has been [detected][TapeLoaderDetector::detect].
+    ///
+    /// Otherwise returns `Ok(None)` without calling the closure, so the data should be provided
+    /// by the **TAPE** pulse playback.
+    pub fn try_instant_tape_load_or_verify<C: Cpu, M: ZxMemory, R: Read, F: FnOnce() -> io::Result<R>>(
+            &self,
+            cpu: &mut C,
+            memory: &mut M,
+            acquire_reader: F
+        ) -> io::Result<Option<u32>>
+    {
+        if is_rom_loading(cpu, memory).is_none() || self.detect_custom(memory).is_some() {
+            return Ok(None)
+        }
+        try_instant_rom_tape_load_or_verify(cpu, memory, acquire_reader)
+    }
+}
+
+/// Searches the paged-in RAM in place, including the `code` spanning the memory page boundaries.
+fn contains_in_ram<M: ZxMemory>(memory: &M, code: &[u8]) -> bool {
+    const RAM_START: u16 = 0x4000;
+    let pages = match memory.iter_pages(RAM_START..=M::RAMTOP) {
+        Ok(pages) => pages,
+        Err(_) => return false
+    };
+    let len = code.len() as u32;
+    let mut page_end = u32::from(RAM_START);
+    for page in pages {
+        if page.windows(code.len()).any(|window| window == code) {
+            return true
+        }
+        let page_start = page_end;
+        page_end += page.len() as u32;
+        let boundary_start = page_end.saturating_sub(len - 1).max(page_start + 1);
+        for addr in boundary_start..page_end {
+            if addr + len <= u32::from(M::RAMTOP) + 1 &&
+               code.iter().zip(addr..).all(|(&octet, addr)| memory.read(addr as u16) == octet) {
+                return true
+            }
+        }
+    }
+    false
+}
+
 /*
     tape too short
     PC: 0x05CD
@@ -186,6 +336,20 @@ fn head_mismatch_exit<C: Cpu>(cpu: &mut C, head: u8) {
     cpu.set_reg(Reg8::B, None, 0xB0);
 }
 
+/// LD-BREAK: RET NZ, LD-START: CALL LD-EDGE-1, JR NC,LD-BREAK
+const LD_BREAK_CODE: (u16, &[u8]) = (0x056B, &[0xC0, 0xCD, 0xE7, 0x05, 0x30, 0xFA]);
+/// LD-EDGE-1: LD A,0x16, LD-DELAY: DEC A, JR NZ,LD-DELAY, AND A, LD-SAMPLE: INC B, RET Z, ...
+const LD_EDGE_1_CODE: (u16, &[u8]) = (0x05E7, &[
+    0x3E, 0x16, 0x3D, 0x20, 0xFD, 0xA7, 0x04, 0xC8, 0x3E, 0x7F,
+    0xDB, 0xFE, 0x1F, 0xD0, 0xA9, 0xE6, 0x20, 0x28, 0xF3, 0x79]);
+
+/// Returns `true` if the memory contains the code of the ROM loading routine.
+fn has_rom_loader<M: ZxMemory>(mem: &M) -> bool {
+    [LD_BREAK_CODE, LD_EDGE_1_CODE].iter().all(|&(addr, code)| {
+        code.iter().zip(addr..).all(|(&octet, addr)| mem.read(addr) == octet)
+    })
+}
+
 /*
     DI
     PC: 0x056B..0x0571, (SP): 0x053F
@@ -230,6 +394,10 @@ fn is_rom_loading<C: Cpu, M: ZxMemory>(cpu: &C, mem: &M) -> Option<(u16, u16, u8
         return None;
     }
 
+    if !has_rom_loader(mem) {
+        return None;
+    }
+
     let (head_match, flags) = cpu.get_alt_reg2(StkReg16::AF);
     let flags = CpuFlags::from_bits_retain(flags);
     if flags.zf() {
@@ -238,3 +406,177 @@ fn is_rom_loading<C: Cpu, M: ZxMemory>(cpu: &C, mem: &M) -> Option<(u16, u16, u8
 
     Some((sp, de, head_match, flags))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::Cursor;
+    use spectrusty::z80emu::{Z80NMOS, CpuDebug};
+    use spectrusty::chip::{ControlUnit, MemoryAccess, ula::UlaPAL, ula128::Ula128};
+    use spectrusty::memory::{Memory128k, Memory48k, ZxMemory};
+    use super::*;
+
+    fn tap_block(flag: u8, data: &[u8]) -> Vec<u8> {
+        let mut block = vec![flag];
+        block.extend_from_slice(data);
+        block.push(data.iter().fold(flag, |sum, &b| sum ^ b));
+        block
+    }
+
+    #[test]
+    fn instant_rom_tape_load_works() -> io::Result<()> {
+        let mut ula = UlaPAL::<Memory48k>::default();
+        ula.memory_mut().load_into_rom(File::open("../resources/roms/48.rom")?).unwrap();
+        let data: Vec<u8> = (0..200u8).map(|b| b.wrapping_mul(7)).collect();
+        let block = tap_block(0xFF, &data);
+        // LD-BYTES entry: load a data block of 200 bytes into 0x8000
+        let mut cpu = Z80NMOS::default();
+        cpu.set_sp(0xFF00);
+        cpu.set_pc(0x0556);
+        cpu.set_acc(0xFF);
+        cpu.set_flags(CpuFlags::C);
+        cpu.set_index16(Prefix::Xdd, 0x8000);
+        cpu.set_reg16(StkReg16::DE, 200);
+        let mut steps = 0;
+        let read_len = loop {
+            if let Some(read_len) = try_instant_rom_tape_load_or_verify(
+                    &mut cpu, ula.memory_mut(), || Ok(Cursor::new(&block)))? {
+                break read_len
+            }
+            ula.execute_single_step(&mut cpu, None::<fn(CpuDebug)>).unwrap();
+            steps += 1;
+            assert!(steps < 100, "the ROM loader was not detected");
+        };
+        assert_eq!(read_len, 202);
+        assert_eq!(&ula.memory_ref().mem_ref()[0x8000..0x8000 + 200], &data[..]);
+        // the checksum is not written
+        assert_eq!(ula.memory_ref().read(0x8000 + 200), 0);
+        assert_eq!(cpu.get_pc(), 0x05DB);
+        assert_eq!(cpu.get_index16(Prefix::Xdd), 0x8000 + 200);
+        assert_eq!(cpu.get_reg16(StkReg16::DE), 0);
+        assert_eq!(cpu.get_reg2(StkReg16::HL), (0, *block.last().unwrap()));
+        assert_eq!(cpu.get_reg(Reg8::B, None), 0xB0);
+        // the ROM routine returns with the carry flag set on success
+        while cpu.get_pc() != 0x053F {
+            ula.execute_single_step(&mut cpu, None::<fn(CpuDebug)>).unwrap();
+            steps += 1;
+            assert!(steps < 200, "the ROM loader did not return");
+        }
+        assert!(cpu.get_flags().cf());
+        assert_eq!(cpu.get_sp(), 0xFF00);
+        Ok(())
+    }
+
+    #[test]
+    fn instant_rom_tape_load_detects_loader() -> io::Result<()> {
+        let mut ula: Ula128 = Ula128::default();
+        ula.memory_mut().load_into_rom_bank(0, File::open("../resources/roms/128-0.rom")?).unwrap();
+        ula.memory_mut().load_into_rom_bank(1, File::open("../resources/roms/128-1.rom")?).unwrap();
+        let block = tap_block(0x00, &[1, 2, 4]);
+        let mut cpu = Z80NMOS::default();
+        // the state inside of LD-BYTES waiting for the edge
+        cpu.set_sp(0xFF00 - 2);
+        ula.memory_mut().write16(0xFF00 - 2, 0x053F);
+        cpu.set_pc(0x056B);
+        cpu.set_index16(Prefix::Xdd, 0xC000);
+        cpu.set_reg16(StkReg16::DE, 3);
+        cpu.ex_af_af();
+        cpu.set_acc(0x00);
+        cpu.set_flags(CpuFlags::C);
+        cpu.ex_af_af();
+        // the 128k editor ROM is paged in
+        assert_eq!(try_instant_rom_tape_load_or_verify(
+            &mut cpu, ula.memory_mut(), || -> io::Result<Cursor<&[u8]>> { panic!("not a loader") })?, None);
+        // the 48k BASIC ROM is paged in
+        ula.memory_mut().map_rom_bank(1, 0).unwrap();
+        // a custom loader
+        cpu.set_sp(0xFF00);
+        assert_eq!(try_instant_rom_tape_load_or_verify(
+            &mut cpu, ula.memory_mut(), || -> io::Result<Cursor<&[u8]>> { panic!("not a loader") })?, None);
+        cpu.set_sp(0xFF00 - 2);
+        assert_eq!(try_instant_rom_tape_load_or_verify(
+            &mut cpu, ula.memory_mut(), || Ok(Cursor::new(&block)))?, Some(5));
+        assert_eq!(ula.memory_ref().page_ref(3).unwrap()[0..3], [1, 2, 4]);
+        assert_eq!(cpu.get_pc(), 0x05DB);
+        assert_eq!(cpu.get_sp(), 0xFF00 - 2);
+        assert_eq!(cpu.get_index16(Prefix::Xdd), 0xC003);
+        assert_eq!(cpu.get_reg16(StkReg16::DE), 0);
+        assert_eq!(cpu.get_reg2(StkReg16::HL), (0, *block.last().unwrap()));
+        Ok(())
+    }
+
+    #[test]
+    fn tape_loader_detector_works() -> io::Result<()> {
+        let mut ula = UlaPAL::<Memory48k>::default();
+        ula.memory_mut().load_into_rom(File::open("../resources/roms/48.rom")?).unwrap();
+        let block = tap_block(0xFF, &[1, 2, 3]);
+        let mut cpu = Z80NMOS::default();
+        // the state inside of LD-BYTES waiting for the edge
+        cpu.set_sp(0xFF00 - 2);
+        ula.memory_mut().write16(0xFF00 - 2, 0x053F);
+        cpu.set_pc(0x056B);
+        cpu.set_index16(Prefix::Xdd, 0x8000);
+        cpu.set_reg16(StkReg16::DE, 3);
+        cpu.ex_af_af();
+        cpu.set_acc(0xFF);
+        cpu.set_flags(CpuFlags::C);
+        cpu.ex_af_af();
+        // a synthetic signature
+        let named = [0xDD, 0x21, 0x00, 0x40, 0x11, 0x00, 0x1B, 0x3E, 0xAA, 0x37];
+        let detector = TapeLoaderDetector::default()
+                       .with_signature(TapeLoaderKind::Named("synthetic"), &named[..]);
+        assert_eq!(detector.signatures().count(), 2);
+        // the standard loader
+        assert_eq!(detector.detect(&cpu, ula.memory_ref()), Some(TapeLoaderKind::Rom));
+        assert_eq!(TapeLoaderKind::Rom.to_string(), "ROM");
+        let mut cpu_custom = cpu.clone();
+        assert_eq!(detector.try_instant_tape_load_or_verify(
+            &mut cpu, ula.memory_mut(), || Ok(Cursor::new(&block)))?, Some(5));
+        assert_eq!(ula.memory_ref().mem_ref()[0x8000..0x8003], [1, 2, 3]);
+        assert_eq!(cpu.get_pc(), 0x05DB);
+        // a custom loader resident in RAM
+        for (addr, &octet) in (0x6000..).zip(CUSTOM_EDGE_LOOP_SIGNATURE.iter()) {
+            ula.memory_mut().write(addr, octet);
+        }
+        assert_eq!(detector.detect(&cpu, ula.memory_ref()), Some(TapeLoaderKind::Custom));
+        assert_eq!(detector.detect(&cpu_custom, ula.memory_ref()), Some(TapeLoaderKind::Custom));
+        assert_eq!(detector.try_instant_tape_load_or_verify(
+            &mut cpu_custom, ula.memory_mut(), || -> io::Result<Cursor<&[u8]>> { panic!("not a ROM loader") })?,
+            None);
+        assert_eq!(cpu_custom.get_pc(), 0x056B);
+        // the specific signature takes precedence
+        for (addr, &octet) in (0x7000..).zip(named.iter()) {
+            ula.memory_mut().write(addr, octet);
+        }
+        assert_eq!(detector.detect(&cpu, ula.memory_ref()), Some(TapeLoaderKind::Named("synthetic")));
+        assert_eq!(TapeLoaderKind::Named("synthetic").to_string(), "synthetic");
+        // without signatures only the ROM loader is detected
+        let detector = TapeLoaderDetector::new();
+        assert_eq!(detector.detect(&cpu, ula.memory_ref()), None);
+        assert_eq!(detector.detect(&cpu_custom, ula.memory_ref()), Some(TapeLoaderKind::Rom));
+        assert_eq!(detector.try_instant_tape_load_or_verify(
+            &mut cpu_custom, ula.memory_mut(), || Ok(Cursor::new(&block)))?, Some(5));
+        Ok(())
+    }
+
+    #[test]
+    fn tape_loader_detector_finds_signature_across_pages() {
+        let detector = TapeLoaderDetector::default();
+        let mut mem = Memory128k::default();
+        assert_eq!(detector.detect_custom(&mem), None);
+        // spanning RAM banks 2 and 0 paged in at 0x8000 and 0xC000
+        for (addr, &octet) in (0xBFFC..).zip(CUSTOM_EDGE_LOOP_SIGNATURE.iter()) {
+            mem.write(addr, octet);
+        }
+        assert_eq!(detector.detect_custom(&mem), Some(TapeLoaderKind::Custom));
+        // swapping bank 0 out breaks the signature
+        mem.map_ram_bank(1, 3).unwrap();
+        assert_eq!(detector.detect_custom(&mem), None);
+        // at the very end of RAM
+        let start = 0x10000 - CUSTOM_EDGE_LOOP_SIGNATURE.len();
+        for (addr, &octet) in (start..).zip(CUSTOM_EDGE_LOOP_SIGNATURE.iter()) {
+            mem.write(addr as u16, octet);
+        }
+        assert_eq!(detector.detect_custom(&mem), Some(TapeLoaderKind::Custom));
+    }
+}
@@ -11,17 +11,25 @@ pub mod ula128;
 pub mod ula3;
 pub mod scld;
 pub mod plus;
+pub mod scorpion;
+pub mod trace;
 #[cfg(feature = "peripherals")]
 pub mod ay_player;
+#[cfg(feature = "snapshot")]
+use serde::{Serialize, Deserialize};
+
 use crate::memory::{ZxMemory, PagedMemory8k};
 use crate::video::{VideoFrame, Video};
-use crate::clock::FTs;
+use crate::clock::{FTs, VFrameTsCounter};
+use crate::z80emu::{Clock, host::cycles::IO_CYCLE_TS};
 use crate::peripherals::KeyboardInterface;
-use ula::{Ula, UlaVideoFrame, UlaNTSC, UlaNTSCVidFrame};
+use ula::{Ula, UlaVideoFrame, UlaNTSC, UlaNTSCVidFrame, UlaInves};
 use ula128::{Ula128, Ula128VidFrame};
 use ula3::Ula3;
 use scld::Scld;
+use scorpion::Scorpion;
 use plus::UlaPlus;
+use trace::TraceEntry;
 pub use spectrusty_core::chip::*;
 
 /// ZX Spectrum PAL configuration parameters.
@@ -57,6 +65,73 @@ pub trait UlaCommon: UlaControl
                    + EarIn
                    + for<'a> MicOut<'a> {}
 
+/// An interface for classifying I/O port accesses in terms of the ULA contention.
+///
+/// Implemented for all chipsets implementing [Video], it can be used e.g. by profilers and debuggers to
+/// display whether a given `IN` or `OUT` instruction will incur the contention on the emulated model.
+///
+/// The port is contended when its address points to the contended memory or when it's the ULA port
+/// (an even address). The contended memory depends on the model and, for 128k and +2A/+3 models,
+/// on the currently paged-in memory banks. The I/O cycles are never contended on +2A/+3 models.
+pub trait PortContention {
+    /// Returns `true` if the I/O cycle accessing the given `port` is subject to the ULA contention
+    /// with the current memory paging.
+    fn is_port_contended(&self, port: u16) -> bool;
+    /// Returns the number of T-states the I/O cycle accessing the given `port` would be delayed
+    /// by the ULA contention, if started at the given frame T-state `ts` with the current memory paging.
+    fn port_contention_tstates(&self, port: u16, ts: FTs) -> u32;
+}
+
+/// The valid CPU clock multipliers, see [UlaControl::set_cpu_turbo].
+pub const CPU_TURBO_MULTIPLIERS: [u8; 4] = [1, 2, 4, 8];
+
+/// The default number of T-states the frame interrupt request is being held active by the 16k/48k ULA.
+pub const DEFAULT_INTERRUPT_LENGTH: u8 = 32;
+
+/// The default number of T-states the frame interrupt request is being held active by the 128k/+2/+2A/+3 ULA.
+pub const DEFAULT_INTERRUPT_LENGTH_128: u8 = 36;
+
+/// The ULA timing variant of the emulated machine.
+///
+/// The exact T-state of the frame interrupt in relation to the memory and I/O contention and the
+/// video beam position differs between individual machines. Some software relying on the precise
+/// timing works correctly only on one of the variants.
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UlaTimingVariant {
+    /// The "early timings" variant - the default.
+    #[default]
+    Early,
+    /// The "late timings" variant - the contention and all other ULA timings are one T-state
+    /// later in relation to the interrupt.
+    Late
+}
+
+impl UlaTimingVariant {
+    /// Returns `true` if `self` is the [UlaTimingVariant::Late] variant.
+    pub fn is_late(self) -> bool {
+        self == UlaTimingVariant::Late
+    }
+}
+
+impl From<bool> for UlaTimingVariant {
+    /// Converts the state of the "late timings" mode to the timing variant.
+    fn from(late_timings: bool) -> Self {
+        if late_timings {
+            UlaTimingVariant::Late
+        }
+        else {
+            UlaTimingVariant::Early
+        }
+    }
+}
+
+impl From<UlaTimingVariant> for bool {
+    fn from(variant: UlaTimingVariant) -> bool {
+        variant.is_late()
+    }
+}
+
 /// Specialized ULA functionality access methods.
 pub trait UlaControl {
     /// Returns the state of the "late timings" mode.
@@ -66,6 +141,64 @@ pub trait UlaControl {
     /// In this mode interrupts are being requested just one T-state earlier than normally.
     /// This results in all other timings being one T-state later.
     fn set_late_timings(&mut self, late_timings: bool);
+    /// Returns the current ULA timing variant.
+    fn timing_variant(&self) -> UlaTimingVariant {
+        self.has_late_timings().into()
+    }
+    /// Selects the ULA timing variant.
+    ///
+    /// This is an alternative to [UlaControl::set_late_timings].
+    fn set_timing_variant(&mut self, variant: UlaTimingVariant) {
+        self.set_late_timings(variant.is_late())
+    }
+    /// Returns the state of the experimental "attribute clash reduction" rendering mode if supported.
+    fn has_attr_clash_reduction(&self) -> Option<bool> { None }
+    /// Sets the experimental "attribute clash reduction" rendering mode on or off.
+    /// Returns `true` if supported. Otherwise, returns `false` and the mode is not changed.
+    ///
+    /// In this mode changes to the screen attributes made while the video beam is drawing the
+    /// attribute row become visible from the currently drawn pixel line, even if the beam has
+    /// already passed the changed cell. Software changing attributes rapidly for the 8x1 color
+    /// resolution effects, but not precisely in sync with the video beam of the emulated model,
+    /// will be rendered with the intended 8x1 attribute resolution.
+    ///
+    /// This is a rendering enhancement and it does not reflect the behavior of the real hardware.
+    fn set_attr_clash_reduction(&mut self, _reduce_clash: bool) -> bool { false }
+    /// Returns the state of the floating bus emulation if supported.
+    fn has_floating_bus(&self) -> Option<bool> { None }
+    /// Enables or disables the floating bus emulation.
+    /// Returns `true` if supported. Otherwise, returns `false` and the state is not changed.
+    ///
+    /// When enabled, reading from unattached I/O ports returns the byte of the screen memory being
+    /// fetched by the ULA at the moment of the reading, or `0xFF` when the ULA is idle.
+    /// When disabled, such reads always return `0xFF`.
+    ///
+    /// Some software depends on the floating bus to synchronize with the video beam.
+    fn set_floating_bus(&mut self, _enabled: bool) -> bool { false }
+    /// Returns the state of the "snow" effect emulation if supported.
+    fn has_snow_effect(&self) -> Option<bool> { None }
+    /// Enables or disables the "snow" effect emulation.
+    /// Returns `true` if supported. Otherwise, returns `false` and the state is not changed.
+    ///
+    /// When enabled, and the `I` register points to the contended memory, the ULA fetches the wrong
+    /// bitmap and attribute bytes of the screen cell being drawn when the memory refresh address
+    /// collides with the video fetch. The low byte of the screen address is then replaced with the
+    /// value of the `R` register. Some demos exploit this effect.
+    ///
+    /// The effect is enabled by default on the chipsets that exhibit it.
+    fn set_snow_effect(&mut self, _enabled: bool) -> bool { false }
+    /// Returns the number of T-states the frame interrupt request is being held active if supported.
+    fn interrupt_length(&self) -> Option<u8> { None }
+    /// Sets the number of T-states the frame interrupt request is being held active.
+    /// Returns `true` if supported. Otherwise, returns `false` and the length is not changed.
+    ///
+    /// The CPU accepts the interrupt only if it samples the request between instructions, so software
+    /// relying on the interrupt being accepted after instructions of a certain length depends on this value.
+    /// The interrupt is being held for 32 T-states by the original ULAs of the 16k/48k Spectrum and 36
+    /// T-states by the ULAs of the 128k models, while it may differ on clones. The default value
+    /// is [DEFAULT_INTERRUPT_LENGTH_128] for the 128k/+2/+2A/+3 chipsets and [DEFAULT_INTERRUPT_LENGTH]
+    /// for the other models. Snapshots lacking this value are restored with the default value of the chipset.
+    fn set_interrupt_length(&mut self, _length: u8) -> bool { false }
     /// Returns the last value sent to the memory port `0x7FFD` if supported.
     fn ula128_mem_port_value(&self) -> Option<Ula128MemFlags> { None }
     /// Sets the current value of the memory port `0x7FFD`. Returns `true` if supported.
@@ -96,6 +229,52 @@ pub trait UlaControl {
     /// Sets the current value of the memory port `0xFF3B`. Returns `true` if supported.
     /// Otherwise, returns `false` and no writing is performed.
     fn set_ulaplus_data_port_value(&mut self, _value: u8) -> bool { false }
+    /// Returns the capacity of the instruction trace log if supported and enabled.
+    fn instruction_trace_capacity(&self) -> Option<usize> { None }
+    /// Enables the instruction trace log holding up to `capacity` of the most recently executed
+    /// instructions or disables it if `capacity` is `None`. Any previously recorded entries are discarded.
+    /// Returns `true` if supported. Otherwise, returns `false` and the state is not changed.
+    ///
+    /// While enabled, the program counter, the instruction bytes and the T-state timestamp of each
+    /// executed instruction are being recorded. The instructions executed by the interrupt requests and
+    /// the `HALT` state are not recorded. The trace is disabled by default and the frame execution
+    /// is slower while enabled.
+    fn set_instruction_trace(&mut self, _capacity: Option<usize>) -> bool { false }
+    /// Returns the recorded instruction trace log entries, from the oldest to the most recent one,
+    /// if supported and enabled.
+    fn instruction_trace(&self) -> Option<Vec<TraceEntry>> { None }
+    /// Returns the state of the EAR/MIC loopback if supported.
+    fn has_ear_mic_loopback(&self) -> Option<bool> { None }
+    /// Enables or disables the EAR/MIC loopback, a virtual cable connecting the MIC output with
+    /// the EAR input through a tape recorder. Disabling it discards any recorded data.
+    /// Returns `true` if supported. Otherwise, returns `false` and the state is not changed.
+    ///
+    /// While enabled, the MIC output pulses are being recorded, e.g. when the program SAVEs data, and
+    /// played back to the EAR input when the program starts probing the EAR input frequently, e.g. when
+    /// it LOADs data, so the saved data can be loaded back without a tape file. The playback pauses when
+    /// the program stops probing the EAR input and the periods of silence between the recorded pulses
+    /// are shortened to the duration of a single frame.
+    fn set_ear_mic_loopback(&mut self, _enabled: bool) -> bool { false }
+    /// Returns the gain of the audible tape signals if supported.
+    fn tape_audio_gain(&self) -> Option<f32> { None }
+    /// Sets the gain of the audible tape signals, clamped to the range `[0.0, 1.0]`.
+    /// Returns `true` if supported. Otherwise, returns `false` and the gain is not changed.
+    ///
+    /// The gain scales the EAR input pulses rendered with [EarInAudioFrame][crate::audio::EarInAudioFrame]
+    /// and the MIC output component of the pulses rendered with
+    /// [EarMicOutAudioFrame][crate::audio::EarMicOutAudioFrame], so the tape sound can be attenuated
+    /// independently of the beeper (EAR output). The default gain is `1.0`.
+    fn set_tape_audio_gain(&mut self, _gain: f32) -> bool { false }
+    /// Returns the CPU clock multiplier if the accelerated CPU clock is supported.
+    fn cpu_turbo(&self) -> Option<u8> { None }
+    /// Sets the CPU clock multiplier, one of [CPU_TURBO_MULTIPLIERS].
+    /// Returns `true` if supported and the `multiplier` is valid. Otherwise, returns `false` and the state
+    /// is not changed.
+    ///
+    /// With the multiplier greater than 1, the CPU executes as many times more T-states per frame, while
+    /// the video timing and the frame duration are not affected. The memory and I/O contention and the
+    /// wait states still apply in the T-states of the base clock.
+    fn set_cpu_turbo(&mut self, _multiplier: u8) -> bool { false }
 }
 
 impl<M: ZxMemory, B, X> HostConfig for Ula<M, B, X, UlaVideoFrame> {
@@ -108,6 +287,11 @@ impl<M: PagedMemory8k, B, X> HostConfig for Scld<M, B, X, UlaVideoFrame> {
     const FRAME_TSTATES: FTs = <Self as Video>::VideoFrame::FRAME_TSTATES_COUNT;
 }
 
+impl<M: ZxMemory, B, X> HostConfig for UlaInves<M, B, X> {
+    const CPU_HZ: u32 = ZxSpectrumPALConfig::CPU_HZ;
+    const FRAME_TSTATES: FTs = <Self as Video>::VideoFrame::FRAME_TSTATES_COUNT;
+}
+
 impl<M: ZxMemory, B, X> HostConfig for UlaNTSC<M, B, X> {
     const CPU_HZ: u32 = ZxSpectrumNTSCConfig::CPU_HZ;
     const FRAME_TSTATES: FTs = <Self as Video>::VideoFrame::FRAME_TSTATES_COUNT;
@@ -123,11 +307,29 @@ impl<B, X> HostConfig for Ula3<B, X> {
     const FRAME_TSTATES: FTs = <Self as Video>::VideoFrame::FRAME_TSTATES_COUNT;
 }
 
+impl<B, X> HostConfig for Scorpion<B, X> {
+    const CPU_HZ: u32 = ZxSpectrumPALConfig::CPU_HZ;
+    const FRAME_TSTATES: FTs = ZxSpectrumPALConfig::FRAME_TSTATES;
+}
+
 impl<U: HostConfig + Video> HostConfig for UlaPlus<U> {
     const CPU_HZ: u32 = U::CPU_HZ;
     const FRAME_TSTATES: FTs = U::FRAME_TSTATES;
 }
 
+impl<U: Video> PortContention for U {
+    fn is_port_contended(&self, port: u16) -> bool {
+        self.current_video_clock().is_contended_port(port)
+    }
+
+    fn port_contention_tstates(&self, port: u16, ts: FTs) -> u32 {
+        let contention = self.current_video_clock().contention;
+        let mut clock = VFrameTsCounter::<U::VideoFrame, _>::from_tstates(ts, contention);
+        clock.add_io(port);
+        (clock.into_tstates() - ts) as u32 - IO_CYCLE_TS as u32
+    }
+}
+
 impl<U> UlaCommon for U
     where U: UlaControl
            + FrameState
@@ -190,4 +392,50 @@ mod tests {
             assert!(size_of::<UlaPlus<Ula3>>() > size_of::<UlaFrameCache<Ula3VidFrame>>() * 4);
         }
     }
+
+    #[test]
+    fn test_port_contention() {
+        use super::{PortContention, UlaControl, Ula128MemFlags};
+        let ula = UlaPAL::<Memory48k>::default();
+        // the ULA port or the high byte of the port address in the range [0x40, 0x7F]
+        for port in [0x00FE, 0x40FE, 0x40FF, 0x7FFD, 0xFEFE, 0xFFFE] {
+            assert!(ula.is_port_contended(port), "{:04x}", port);
+        }
+        for port in [0x00FF, 0x3FFF, 0x80FF, 0xBFFD, 0xFFFD, 0xFFFF] {
+            assert!(!ula.is_port_contended(port), "{:04x}", port);
+        }
+        // the first contended T-state: N:1 C:3, C:1 C:3, C:1 C:1 C:1 C:1, N:4
+        assert_eq!(ula.port_contention_tstates(0x00FE, 14335), 5);
+        assert_eq!(ula.port_contention_tstates(0x40FE, 14335), 6);
+        assert_eq!(ula.port_contention_tstates(0x40FF, 14335), 12);
+        assert_eq!(ula.port_contention_tstates(0x00FF, 14335), 0);
+        // no contention in the border area
+        for port in [0x00FE, 0x40FE, 0x40FF, 0x00FF] {
+            assert_eq!(ula.port_contention_tstates(port, 0), 0);
+        }
+        let mut ula: Ula128 = Ula128::default();
+        for port in [0x00FE, 0x7FFD, 0x4000 | 0x1FFD] {
+            assert!(ula.is_port_contended(port), "{:04x}", port);
+        }
+        // the bank 0 is paged in at 0xC000
+        for port in [0x1FFD, 0xBFFD, 0xC0FF, 0xFFFD] {
+            assert!(!ula.is_port_contended(port), "{:04x}", port);
+        }
+        assert_eq!(ula.port_contention_tstates(0xFFFD, 14361), 0);
+        // the odd banks are contended on 128k
+        assert!(ula.set_ula128_mem_port_value(Ula128MemFlags::RAM_BANK0));
+        for port in [0xC0FF, 0xFFFD] {
+            assert!(ula.is_port_contended(port), "{:04x}", port);
+        }
+        assert_eq!(ula.port_contention_tstates(0xFFFD, 14361), 12);
+        assert!(ula.set_ula128_mem_port_value(Ula128MemFlags::RAM_BANK1));
+        assert!(!ula.is_port_contended(0xFFFD));
+        // no I/O contention on +2A/+3 regardless of the memory paging
+        let mut ula: Ula3 = Ula3::default();
+        assert!(ula.set_ula128_mem_port_value(Ula128MemFlags::RAM_BANK0));
+        for port in [0x00FE, 0x40FE, 0x40FF, 0x7FFD, 0xC0FF, 0xFFFD] {
+            assert!(!ula.is_port_contended(port), "{:04x}", port);
+            assert_eq!(ula.port_contention_tstates(port, 14361), 0);
+        }
+    }
 }
@@ -6,6 +6,9 @@
     For the full copyright notice, see the lib.rs file.
 */
 //! An emulator of a video-less chip for ZX Spectrum to be used in a music player.
+#[cfg(all(feature = "audio", feature = "formats"))]
+pub mod render;
+
 use core::num::NonZeroU16;
 use core::marker::PhantomData;
 use core::num::Wrapping;
@@ -28,6 +31,11 @@ use crate::chip::{
     nanos_from_frame_tc_cpu_hz
 };
 
+/// The amplitude filter of the player's audio output with all AY channels mixed into a single channel.
+pub const MONO_AMP_FILTER: f32 = 2.0/3.0;
+/// The amplitude filter of the player's stereo audio output.
+pub const STEREO_AMP_FILTER: f32 = 0.777;
+
 #[derive(Clone)]
 #[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
 #[cfg_attr(feature = "snapshot", serde(rename_all = "camelCase"))]
@@ -262,6 +270,29 @@ impl<P: AyPortDecode> AyPlayer<P> {
         self.cpu_rate = cpu_rate;
         self.frame_tstates = frame_tstates;
     }
+    /// Fast-forwards the player by running frames until the number of the executed frames reaches
+    /// the `frames` target. Returns the number of frames run.
+    ///
+    /// The audio of the skipped frames is discarded, while the state of the sound generator follows
+    /// the recorded AY register changes, so the playback may continue seamlessly from the target frame.
+    ///
+    /// The player can't seek backwards: if the target has been already reached nothing happens.
+    /// In this instance, initialize the song again and seek from the start.
+    pub fn seek<C: Cpu>(&mut self, cpu: &mut C, frames: u64) -> u64 {
+        let mut count = 0;
+        while self.frame_tstate().0 < frames {
+            self.execute_next_frame(cpu);
+            for (_, reg, val) in self.ay_io.recorder.iter() {
+                self.ay_sound.update_register(*reg, *val);
+            }
+            count += 1;
+        }
+        // the changes of the skipped frames must not be rendered with the next frame
+        self.ay_io.recorder.clear();
+        self.earmic_changes.clear();
+        self.prev_earmic = self.last_earmic;
+        count
+    }
     /// Resets the frames counter.
     pub fn reset_frames(&mut self) {
         self.frames.0 = 0;
@@ -344,3 +375,29 @@ impl<P> Memory for AyPlayer<P> {
         self.memory.write(addr, val);
     }
 }
+
+#[cfg(all(test, feature = "formats"))]
+mod tests {
+    use crate::z80emu::Z80NMOS;
+    use crate::formats::ay::read_ay;
+    use crate::peripherals::ay::Ay128kPortDecode;
+    use super::*;
+
+    #[test]
+    fn ay_player_seek_works() {
+        let data = std::fs::read("resources/nodes_of_yesod.ay").unwrap();
+        let ay_file = read_ay(&data[..]).unwrap();
+        let mut cpu = Z80NMOS::default();
+        let mut player = AyPlayer::<Ay128kPortDecode>::default();
+        player.reset(&mut cpu, true);
+        player.reset_frames();
+        ay_file.initialize_player(&mut cpu, &mut player.memory, 0);
+        let target = 4625;
+        assert_eq!(player.seek(&mut cpu, target), target);
+        assert_eq!(player.frame_tstate(), (target, 0));
+        assert!(player.ay_io.recorder.is_empty());
+        assert_eq!(player.seek(&mut cpu, target), 0);
+        assert_eq!(player.seek(&mut cpu, target + 10), 10);
+        assert_eq!(player.frame_tstate(), (target + 10, 0));
+    }
+}
@@ -0,0 +1,213 @@
+/*
+    Copyright (C) 2020-2023  Rafal Michalski
+
+    This file is part of SPECTRUSTY, a Rust library for building emulators.
+
+    For the full copyright notice, see the lib.rs file.
+*/
+//! Headless rendering of the **AY** file songs to audio sample buffers and **WAV** files.
+//!
+//! The songs are played by the [AyPlayer] to a fixed length without the need of any audio host,
+//! so collections of **AY** files can be converted in bulk, e.g.:
+//!
+//! ```no_run
+//! use std::{fs::File, io::BufWriter, time::Duration};
+//! use spectrusty::audio::synth::BandLimWide;
+//! use spectrusty::chip::ay_player::render::{AyRenderMode, write_ay_song_wav};
+//! use spectrusty::formats::ay::read_ay;
+//! use spectrusty::peripherals::ay::audio::AyAmps;
+//!
+//! let ay_file = read_ay(File::open("music.ay")?)?;
+//! for index in 0..ay_file.songs.len() {
+//!     let wav = BufWriter::new(File::create(format!("music_{:02}.wav", index + 1))?);
+//!     write_ay_song_wav::<AyAmps<f32>, BandLimWide, _>(wav, &ay_file, index, 44100,
+//!                                         Duration::from_secs(180), AyRenderMode::default())?;
+//! }
+//! # Ok::<(), std::io::Error>(())
+//! ```
+use core::time::Duration;
+use std::io::{self, Write};
+
+use crate::z80emu::Z80NMOS;
+use crate::audio::{*, synth::{BandLimited, BandLimOpt, ext::BandLimitedExt}};
+use crate::formats::{ay::AyFile, wav::write_wav_pcm16};
+use crate::peripherals::ay::{Ay128kPortDecode, audio::AyAudioFrame};
+use crate::chip::{ControlUnit, FrameState};
+use super::{AyPlayer, MONO_AMP_FILTER, STEREO_AMP_FILTER};
+
+/// Selects the output channels of the rendered songs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AyRenderMode {
+    /// All AY channels are mixed into a single output channel.
+    Mono,
+    /// Two output channels are rendered.
+    ///
+    /// The array maps the AY channels `[A, B, C]` to: `0` - left, `1` - right, `2` - center.
+    Stereo([usize;3])
+}
+
+impl Default for AyRenderMode {
+    /// The `ACB` stereo.
+    fn default() -> Self {
+        AyRenderMode::Stereo([0, 1, 2])
+    }
+}
+
+impl AyRenderMode {
+    /// Returns the number of the rendered output channels.
+    pub fn nchannels(self) -> usize {
+        match self {
+            AyRenderMode::Mono => 1,
+            AyRenderMode::Stereo(..) => 2
+        }
+    }
+}
+
+/// Returns the number of samples per channel rendered in the given `duration`.
+pub fn sample_count(duration: Duration, sample_rate: u32) -> usize {
+    (duration.as_nanos() * u128::from(sample_rate) / 1_000_000_000) as usize
+}
+
+/// Renders the song at `song_index` of the `ay_file` for the given `duration` and returns
+/// the channel-interleaved audio samples.
+///
+/// The length of the returned buffer is always equal to [sample_count] multiplied by
+/// [AyRenderMode::nchannels].
+///
+/// The song is faded out after its duration as reported by [AyFile::song_amplitude] and silence
+/// is rendered past the end of the fade.
+///
+/// `V` selects the AY amplitude levels and `F` the band-pass filter of the [BandLimited] synthesizer.
+///
+/// Returns `None` if there is no song at `song_index` or the file requires a special player.
+pub fn render_ay_song<V, F>(
+        ay_file: &AyFile,
+        song_index: usize,
+        sample_rate: u32,
+        duration: Duration,
+        mode: AyRenderMode
+    ) -> Option<Vec<f32>>
+    where V: AmpLevels<f32>,
+          F: BandLimOpt
+{
+    if song_index >= ay_file.songs.len() || ay_file.meta.special_player {
+        return None
+    }
+    let (amp_filter, channels) = match mode {
+        AyRenderMode::Mono => (MONO_AMP_FILTER, [2, 2, 2]),
+        AyRenderMode::Stereo(channels) => (STEREO_AMP_FILTER, channels)
+    };
+    let nchannels = mode.nchannels();
+    let mut bandlim = BlepAmpFilter::new(amp_filter,
+                            BlepStereo::new(0.5,
+                                BandLimited::<f32, F>::new(2)));
+    let mut cpu = Z80NMOS::default();
+    let mut player = AyPlayer::<Ay128kPortDecode>::default();
+    player.ensure_audio_frame_time(&mut bandlim, sample_rate);
+    player.reset(&mut cpu, true);
+    player.reset_frames();
+    ay_file.initialize_player(&mut cpu, &mut player.memory, song_index);
+
+    let total = sample_count(duration, sample_rate) * nchannels;
+    let mut output = Vec::with_capacity(total);
+    let mut buffer = Vec::new();
+    while output.len() < total {
+        let frame = player.frame_tstate().0;
+        let amplitude = ay_file.song_amplitude(song_index, frame).unwrap_or(0.0);
+        bandlim.filter = amp_filter * amplitude;
+        player.execute_next_frame(&mut cpu);
+        player.render_ay_audio_frame::<V>(&mut bandlim, channels);
+        player.render_earmic_out_audio_frame::<EarOutAmps4<f32>>(&mut bandlim, 2);
+        let frame_sample_count = player.end_audio_frame(&mut bandlim);
+        buffer.resize(frame_sample_count * nchannels, 0.0);
+        if nchannels == 1 {
+            bandlim.render_audio_channel(&mut buffer, 0);
+        }
+        else {
+            bandlim.render_audio_map_interleaved(&mut buffer, nchannels, &[0, 1]);
+        }
+        bandlim.next_frame();
+        output.extend_from_slice(&buffer);
+    }
+    output.truncate(total);
+    Some(output)
+}
+
+/// Renders the song at `song_index` of the `ay_file` with [render_ay_song] and writes it
+/// to `wr` with [write_wav_pcm16].
+///
+/// # Errors
+/// This function will return an error of [io::ErrorKind::InvalidInput] kind if the song can't be
+/// rendered. Other errors may also be returned from attempts to write the file.
+pub fn write_ay_song_wav<V, F, W>(
+        wr: W,
+        ay_file: &AyFile,
+        song_index: usize,
+        sample_rate: u32,
+        duration: Duration,
+        mode: AyRenderMode
+    ) -> io::Result<()>
+    where V: AmpLevels<f32>,
+          F: BandLimOpt,
+          W: Write
+{
+    let samples = render_ay_song::<V, F>(ay_file, song_index, sample_rate, duration, mode)
+                  .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput,
+                                        "AY: the song can't be rendered"))?;
+    write_wav_pcm16(wr, sample_rate, mode.nchannels() as u16, &samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audio::synth::BandLimWide;
+    use crate::formats::ay::read_ay;
+    use crate::peripherals::ay::audio::AyAmps;
+    use super::*;
+
+    #[test]
+    fn render_ay_song_works() {
+        let ay_file = read_ay(std::fs::File::open("resources/nodes_of_yesod.ay").unwrap()).unwrap();
+        let duration = Duration::from_millis(500);
+        let samples = render_ay_song::<AyAmps<f32>, BandLimWide>(&ay_file, 0, 44100, duration,
+                                            AyRenderMode::Mono).unwrap();
+        assert_eq!(samples.len(), 22050);
+        assert!(samples.iter().any(|&s| s != 0.0));
+        let samples = render_ay_song::<AyAmps<f32>, BandLimWide>(&ay_file, 0, 22050, duration,
+                                            AyRenderMode::default()).unwrap();
+        assert_eq!(samples.len(), 2 * 11025);
+        assert!(render_ay_song::<AyAmps<f32>, BandLimWide>(&ay_file, ay_file.songs.len(), 44100,
+                                            duration, AyRenderMode::Mono).is_none());
+        let mut wav = Vec::new();
+        write_ay_song_wav::<AyAmps<f32>, BandLimWide, _>(&mut wav, &ay_file, 0, 22050,
+                                            duration, AyRenderMode::default()).unwrap();
+        assert_eq!(wav.len(), 44 + 4 * 11025);
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(&wav[22..24], &2u16.to_le_bytes());
+        assert_eq!(&wav[24..28], &22050u32.to_le_bytes());
+        assert_eq!(&wav[40..44], &(4u32 * 11025).to_le_bytes());
+    }
+
+    #[test]
+    fn render_ay_song_fade_works() {
+        let mut ay_file = read_ay(std::fs::File::open("resources/nodes_of_yesod.ay").unwrap()).unwrap();
+        // 25 frames of the song, 25 frames of fade, then 25 frames of silence
+        assert!(ay_file.as_mut().set_song_duration(0, 25, 25));
+        let samples = render_ay_song::<AyAmps<f32>, BandLimWide>(&ay_file, 0, 50000,
+                                            Duration::from_millis(1500), AyRenderMode::Mono).unwrap();
+        // the number of samples per frame for the 128k clocking
+        let frame_samples = 50000 * 70908 / 3546900;
+        let peak = |frames: core::ops::Range<usize>| {
+            samples[frames.start * frame_samples..frames.end * frame_samples].iter()
+                .fold(0.0f32, |peak, &s| peak.max(s.abs()))
+        };
+        let song_peak = peak(5..25);
+        let fade_start_peak = peak(26..31);
+        let fade_end_peak = peak(44..49);
+        assert!(song_peak > 0.0);
+        assert!(fade_end_peak > 0.0 && fade_end_peak < fade_start_peak && fade_start_peak <= song_peak);
+        assert!(fade_end_peak < song_peak * 0.25);
+        // only the decaying tail of the last step is rendered past the fade
+        assert!(peak(52..75) < 1e-3);
+        assert!(peak(65..75) < 1e-6);
+    }
+}
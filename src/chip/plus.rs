@@ -84,7 +84,7 @@ use crate::clock::{
 use crate::chip::{
     ControlUnit, MemoryAccess,
     UlaPortFlags, ScldCtrlFlags, UlaPlusRegFlags, ColorMode, Ula128MemFlags, Ula3CtrlFlags,
-    UlaControl,
+    UlaControl, trace::{InstructionTrace, TraceEntry},
     InnerAccess,
     scld::frame_cache::SourceMode,
     ula::{
@@ -108,6 +108,9 @@ use crate::memory::{ZxMemory, MemoryExtension};
 
 /// Provides ULAplus screen and color modes for [Ula], [Ula128] and [Ula3].
 ///
+/// The changed character rows are not being tracked, so [Video::dirty_lines] returns `None`: the palette
+/// and the screen mode changes affect the whole screen.
+///
 /// [Ula]: crate::chip::ula::Ula
 /// [Ula128]: crate::chip::ula128::Ula128
 /// [Ula3]: crate::chip::ula3::Ula3
@@ -195,6 +198,12 @@ pub trait UlaPlusInner<'a>: Video + MemoryAccess {
     fn beg_screen_shadow(&self) -> bool;
     /// Returns true if the shadow screen is currently being displayed.
     fn cur_screen_shadow(&self) -> bool;
+    /// Returns true if the "attribute clash reduction" rendering mode is on.
+    ///
+    /// The default implementation returns `false`.
+    fn is_attr_clash_reduced(&self) -> bool {
+        false
+    }
     /// Returns references to components necessary for video rendering.
     fn video_render_data_view(
         &'a mut self
@@ -281,6 +290,74 @@ impl<'a, U> UlaControl for UlaPlus<U>
         self.ula.set_late_timings(late_timings)
     }
 
+    fn interrupt_length(&self) -> Option<u8> {
+        self.ula.interrupt_length()
+    }
+
+    fn set_interrupt_length(&mut self, length: u8) -> bool {
+        self.ula.set_interrupt_length(length)
+    }
+
+    fn instruction_trace_capacity(&self) -> Option<usize> {
+        self.ula.instruction_trace_capacity()
+    }
+
+    fn set_instruction_trace(&mut self, capacity: Option<usize>) -> bool {
+        self.ula.set_instruction_trace(capacity)
+    }
+
+    fn instruction_trace(&self) -> Option<Vec<TraceEntry>> {
+        self.ula.instruction_trace()
+    }
+
+    fn cpu_turbo(&self) -> Option<u8> {
+        self.ula.cpu_turbo()
+    }
+
+    fn set_cpu_turbo(&mut self, multiplier: u8) -> bool {
+        self.ula.set_cpu_turbo(multiplier)
+    }
+
+    fn has_ear_mic_loopback(&self) -> Option<bool> {
+        self.ula.has_ear_mic_loopback()
+    }
+
+    fn set_ear_mic_loopback(&mut self, enabled: bool) -> bool {
+        self.ula.set_ear_mic_loopback(enabled)
+    }
+
+    fn tape_audio_gain(&self) -> Option<f32> {
+        self.ula.tape_audio_gain()
+    }
+
+    fn set_tape_audio_gain(&mut self, gain: f32) -> bool {
+        self.ula.set_tape_audio_gain(gain)
+    }
+
+    fn has_attr_clash_reduction(&self) -> Option<bool> {
+        self.ula.has_attr_clash_reduction()
+    }
+
+    fn set_attr_clash_reduction(&mut self, reduce_clash: bool) -> bool {
+        self.ula.set_attr_clash_reduction(reduce_clash)
+    }
+
+    fn has_floating_bus(&self) -> Option<bool> {
+        self.ula.has_floating_bus()
+    }
+
+    fn set_floating_bus(&mut self, enabled: bool) -> bool {
+        self.ula.set_floating_bus(enabled)
+    }
+
+    fn has_snow_effect(&self) -> Option<bool> {
+        self.ula.has_snow_effect()
+    }
+
+    fn set_snow_effect(&mut self, enabled: bool) -> bool {
+        self.ula.set_snow_effect(enabled)
+    }
+
     fn ula128_mem_port_value(&self) -> Option<Ula128MemFlags> {
         self.ula.ula128_mem_port_value()
     }
@@ -389,6 +466,26 @@ impl<'a, U> UlaPlus<U>
     pub fn ulaplus_palette_mut(&mut self) -> &mut UlaPlusPalette {
         &mut self.cur_palette
     }
+    /// Returns all 64 entries of the current palette in the `GRB8` (`G3R3B2`) format.
+    ///
+    /// The palette entries can be saved with e.g. `formats::scr::write_scr_palette`.
+    pub fn palette_entries(&self) -> &[u8;64] {
+        &self.cur_palette.0
+    }
+    /// Changes the palette entry at `index` to the `grb8` (`G3R3B2`) color at the current video timestamp.
+    ///
+    /// Unlike modifying the palette via [UlaPlus::ulaplus_palette_mut], the change is being recorded,
+    /// so it will be visible in the rendered frame from the current beam position, as if the entry has been
+    /// written to the ULAplus data port. Only the lowest 6 bits of the `index` are being used.
+    pub fn set_palette_entry(&mut self, index: u8, grb8: u8) {
+        let index = index & 0x3F;
+        let entry = &mut self.cur_palette[index as usize];
+        if grb8 != *entry {
+            *entry = grb8;
+            let ts = self.ula.current_video_ts();
+            self.palette_changes.push((ts, index, grb8).into());
+        }
+    }
 
     fn push_mode_change(&mut self, ts: VideoTs) {
         self.mode_changes.push((ts, self.cur_render_mode.bits()).into());
@@ -589,4 +686,74 @@ impl<U> UlaControlExt for UlaPlus<U>
         self.mode_changes.clear();
         self.ula.prepare_next_frame(vtsc)
     }
+
+    fn cpu_turbo_multiplier(&self) -> u8 {
+        self.ula.cpu_turbo_multiplier()
+    }
+
+    fn cpu_turbo_remainder_mut(&mut self) -> &mut u8 {
+        self.ula.cpu_turbo_remainder_mut()
+    }
+
+    fn instruction_trace_mut(&mut self) -> &mut Option<InstructionTrace> {
+        self.ula.instruction_trace_mut()
+    }
+
+    fn rom_trap<C: Cpu>(&mut self, cpu: &mut C) -> bool {
+        self.ula.rom_trap(cpu)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chip::ula::UlaPAL;
+    use crate::memory::{Memory16k, ZxMemory};
+    use crate::video::{
+        BorderSize, Palette,
+        pixel::{PixelBufA24, SpectrumPalRGB24}
+    };
+    use super::*;
+
+    type TestUlaPlus = UlaPlus<UlaPAL<Memory16k>>;
+
+    #[allow(clippy::unusual_byte_groupings)]
+    #[test]
+    fn test_ulaplus_palette_entries() {
+        let mut ula = TestUlaPlus::default();
+        let (width, height) = <TestUlaPlus as Video>::render_size_pixels(BorderSize::Full);
+        let border = <TestUlaPlus as Video>::VideoFrame::border_size_pixels(BorderSize::Full) as usize;
+        let density = <TestUlaPlus as Video>::pixel_density() as usize;
+        let pitch = width as usize * 3;
+        let mut buffer = vec![0u8; pitch * height as usize];
+        // the first cell: ink 2 on paper 0, CLUT 0
+        ula.memory_mut().write(0x4000, 0xFF);
+        ula.memory_mut().write(0x5800, 0b00_000_010);
+        assert!(ula.set_ulaplus_reg_port_value(UlaPlusRegFlags::MODE_GROUP));
+        assert!(ula.set_ulaplus_data_port_value(ColorMode::PALETTE.bits()));
+        assert!(ula.render_mode().is_palette());
+        assert_eq!(ula.palette_entries(), &[0u8;64]);
+        ula.set_palette_entry(2, 0b111_000_00);
+        ula.set_palette_entry(64 + 8, 0b000_000_11);
+        let mut expected = [0u8;64];
+        expected[2] = 0b111_000_00;
+        expected[8] = 0b000_000_11;
+        assert_eq!(ula.palette_entries(), &expected);
+        assert_eq!(ula.ulaplus_palette().0, expected);
+        // no change is being recorded if the entry is left unmodified
+        ula.set_palette_entry(2, 0b111_000_00);
+        assert_eq!(ula.palette_changes.len(), 2);
+        ula.render_video_frame::<PixelBufA24, SpectrumPalRGB24>(&mut buffer, pitch, BorderSize::Full);
+        let pixel = |buffer: &[u8], x: usize, y: usize| -> [u8;3] {
+            let offs = y * pitch + x * 3;
+            buffer[offs..offs + 3].try_into().unwrap()
+        };
+        let ink = SpectrumPalRGB24::get_pixel_grb8(0b111_000_00);
+        let paper = SpectrumPalRGB24::get_pixel_grb8(0b000_000_11);
+        let left = border * density;
+        for x in left..left + 8 * density {
+            assert_eq!(pixel(&buffer, x, border), ink);
+        }
+        assert_eq!(pixel(&buffer, left + 8 * density, border), paper);
+        assert_eq!(pixel(&buffer, left, border + 1), paper);
+    }
 }
@@ -74,6 +74,7 @@ impl<'a, U> UlaPlus<U>
 {
     #[inline]
     pub(super) fn update_frame_cache(&mut self, addr: u16, ts: VideoTs) {
+        let reduce_clash = self.ula.is_attr_clash_reduced();
         let (frame_cache, memory_ref): (&mut UlaFrameCache<_>, _) = match addr {
             0x4000..=0x5AFF => match self.ula.page1_screen0_shadow_bank() {
                 Some(false) => self.ula.frame_cache_mut_mem_ref(),
@@ -104,7 +105,7 @@ impl<'a, U> UlaPlus<U>
         }
         else {
             let coords = color_address_coords(addr);
-            frame_cache.update_frame_colors(memory_ref, coords, addr, ts);
+            frame_cache.update_frame_colors_with_mode(memory_ref, coords, addr, ts, reduce_clash);
         }
     }
 
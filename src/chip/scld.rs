@@ -36,7 +36,7 @@ use crate::clock::{
 };
 use crate::bus::{BusDevice};
 use crate::chip::{
-    ScldCtrlFlags, UlaControl,
+    ScldCtrlFlags, UlaControl, trace::{InstructionTrace, TraceEntry},
     InnerAccess, EarIn, ReadEarMode, ControlUnit, MemoryAccess,
     ula::{
         Ula,
@@ -63,6 +63,9 @@ use frame_cache::SourceMode;
 
 /// This is the emulator of SCLD chip used with Timex's TC2048 / TC2068 / TS2068 models.
 ///
+/// The changed character rows are not being tracked, so [Video::dirty_lines] returns `None`: the screen
+/// mode changes and the alternate screen affect the whole screen.
+///
 /// The generic type `M` must implement [PagedMemory8k] trait in addition to [ZxMemory].
 ///
 /// See [Ula] for description of other generic parameters.
@@ -165,6 +168,58 @@ impl<M, B, X, V> UlaControl for Scld<M, B, X, V>
         self.ula.set_late_timings(late_timings)
     }
 
+    fn interrupt_length(&self) -> Option<u8> {
+        self.ula.interrupt_length()
+    }
+
+    fn set_interrupt_length(&mut self, length: u8) -> bool {
+        self.ula.set_interrupt_length(length)
+    }
+
+    fn instruction_trace_capacity(&self) -> Option<usize> {
+        self.ula.instruction_trace_capacity()
+    }
+
+    fn set_instruction_trace(&mut self, capacity: Option<usize>) -> bool {
+        self.ula.set_instruction_trace(capacity)
+    }
+
+    fn instruction_trace(&self) -> Option<Vec<TraceEntry>> {
+        self.ula.instruction_trace()
+    }
+
+    fn cpu_turbo(&self) -> Option<u8> {
+        self.ula.cpu_turbo()
+    }
+
+    fn set_cpu_turbo(&mut self, multiplier: u8) -> bool {
+        self.ula.set_cpu_turbo(multiplier)
+    }
+
+    fn has_ear_mic_loopback(&self) -> Option<bool> {
+        self.ula.has_ear_mic_loopback()
+    }
+
+    fn set_ear_mic_loopback(&mut self, enabled: bool) -> bool {
+        self.ula.set_ear_mic_loopback(enabled)
+    }
+
+    fn tape_audio_gain(&self) -> Option<f32> {
+        self.ula.tape_audio_gain()
+    }
+
+    fn set_tape_audio_gain(&mut self, gain: f32) -> bool {
+        self.ula.set_tape_audio_gain(gain)
+    }
+
+    fn has_attr_clash_reduction(&self) -> Option<bool> {
+        self.ula.has_attr_clash_reduction()
+    }
+
+    fn set_attr_clash_reduction(&mut self, reduce_clash: bool) -> bool {
+        self.ula.set_attr_clash_reduction(reduce_clash)
+    }
+
     fn scld_ctrl_port_value(&self) -> Option<ScldCtrlFlags> {
         Some(self.cur_ctrl_flags)
     }
@@ -380,4 +435,20 @@ impl<M, B, X, V> UlaControlExt for Scld<M, B, X, V>
         self.source_changes.clear();
         self.ula.prepare_next_frame(vtsc)
     }
+
+    fn cpu_turbo_multiplier(&self) -> u8 {
+        self.ula.cpu_turbo_multiplier()
+    }
+
+    fn cpu_turbo_remainder_mut(&mut self) -> &mut u8 {
+        self.ula.cpu_turbo_remainder_mut()
+    }
+
+    fn instruction_trace_mut(&mut self) -> &mut Option<InstructionTrace> {
+        self.ula.instruction_trace_mut()
+    }
+
+    fn rom_trap<C: Cpu>(&mut self, cpu: &mut C) -> bool {
+        self.ula.rom_trap(cpu)
+    }
 }
@@ -53,6 +53,7 @@ impl<A, M, B, X, V> AudioFrame<A> for Scld<M, B, X, V>
 
 impl<A, M, B, X, F> EarMicOutAudioFrame<A> for Scld<M, B, X, F>
     where A: Blep,
+          A::SampleDelta: MulNorm + FromSample<f32>,
           M: PagedMemory8k,
           F: VideoFrame
 {
@@ -64,6 +65,7 @@ impl<A, M, B, X, F> EarMicOutAudioFrame<A> for Scld<M, B, X, F>
 
 impl<A, M, B, X, F> EarInAudioFrame<A> for Scld<M, B, X, F>
     where A: Blep,
+          A::SampleDelta: MulNorm + FromSample<f32>,
           M: PagedMemory8k,
           F: VideoFrame
 {
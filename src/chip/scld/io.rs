@@ -60,6 +60,7 @@ impl<M, B, X, V> Io for Scld<M, B, X, V>
             (self.mem_paged, None)
         }
         else {
+            self.ula.ula_probe_screen(0, ts);
             let bus_data = self.ula.bus.read_io(port, VFrameTs::from(ts).into());
             if UlaPortAddress::match_port(port) {
                 let ula_data = self.ula.ula_io_data(port, ts) & 0b0101_1111;
@@ -90,7 +91,9 @@ impl<M, B, X, V> Io for Scld<M, B, X, V>
             self.set_mmu_flags_value(data);
         }
         else if let Some(ws) = self.ula.bus.write_io(port, data, VFrameTs::from(ts).into()) {
-            return (None, NonZeroU16::new(ws))
+            // break to let the bus master take the bus
+            let res = if self.ula.bus.is_bus_requested() { Some(()) } else { None };
+            return (res, NonZeroU16::new(ws))
         }
         (None, None)
     }
@@ -109,13 +112,13 @@ impl<M, B, X, V> Memory for Scld<M, B, X, V>
     }
 
     #[inline(always)]
-    fn read_mem(&self, addr: u16, _ts: VideoTs) -> u8 {
-        self.ula.memory.read(addr)
+    fn read_mem(&self, addr: u16, ts: VideoTs) -> u8 {
+        self.ula.memext.read_mem(addr, V::vc_hc_to_tstates(ts.vc, ts.hc), &self.ula.memory)
     }
 
     #[inline(always)]
-    fn read_mem16(&self, addr: u16, _ts: VideoTs) -> u16 {
-        self.ula.memory.read16(addr)
+    fn read_mem16(&self, addr: u16, ts: VideoTs) -> u16 {
+        self.ula.memext.read_mem16(addr, V::vc_hc_to_tstates(ts.vc, ts.hc), &self.ula.memory)
     }
 
     #[inline]
@@ -126,7 +129,7 @@ impl<M, B, X, V> Memory for Scld<M, B, X, V>
     #[inline]
     fn write_mem(&mut self, addr: u16, val: u8, ts: VideoTs) {
         self.update_frame_cache(addr, ts);
-        self.ula.memory.write(addr, val);
+        self.ula.memext.write_mem(addr, val, V::vc_hc_to_tstates(ts.vc, ts.hc), &mut self.ula.memory);
     }
 }
 
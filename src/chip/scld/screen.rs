@@ -67,3 +67,78 @@ impl<M, B, X, V> ScreenDataProvider for Scld<M, B, X, V>
         self.memory_mut().screen_mut(1).unwrap()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use crate::bus::NullDevice;
+    use crate::chip::{UlaControl, ula::UlaVideoFrame};
+    use crate::clock::VideoTs;
+    use crate::memory::{Memory48kDock64kEx, NoMemoryExtension};
+    use crate::video::{
+        BorderSize, Palette,
+        pixel::{PixelBufA24, SpectrumPalRGB24}
+    };
+    use super::*;
+
+    type TestScld = Scld<Memory48kDock64kEx, NullDevice<VideoTs>, NoMemoryExtension, UlaVideoFrame>;
+
+    fn render_line(scld: &mut TestScld) -> Vec<[u8;3]> {
+        let (width, height) = <TestScld as Video>::render_size_pixels(BorderSize::Full);
+        let border = <TestScld as Video>::VideoFrame::border_size_pixels(BorderSize::Full) as usize;
+        let pitch = width as usize * 3;
+        let mut buffer = vec![0u8; pitch * height as usize];
+        scld.render_video_frame::<PixelBufA24, SpectrumPalRGB24>(&mut buffer, pitch, BorderSize::Full);
+        buffer[border * pitch..(border + 1) * pitch].chunks(3).map(|p| p.try_into().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_scld_load_scr() {
+        let density = <TestScld as Video>::pixel_density() as usize;
+        let left = <TestScld as Video>::VideoFrame::border_size_pixels(BorderSize::Full) as usize * density;
+        // hi-color
+        let mut scr = vec![0u8; 12288];
+        scr[0] = 0b1000_0001;
+        scr[6144] = 0b01_010_110;
+        let mut scld = TestScld::default();
+        scld.load_scr(Cursor::new(&scr)).unwrap();
+        assert_eq!(scld.get_screen_mode(), ScrMode::HighColor(false));
+        let flags = scld.scld_ctrl_port_value().unwrap();
+        assert!(flags.is_screen_hi_attrs() && !flags.is_screen_hi_res());
+        assert_eq!(scld.memory_ref().read(0x4000), 0b1000_0001);
+        assert_eq!(scld.memory_ref().read(0x6000), 0b01_010_110);
+        // bright yellow ink on bright red paper
+        let (ink, paper) = (SpectrumPalRGB24::get_pixel(14), SpectrumPalRGB24::get_pixel(10));
+        let line = render_line(&mut scld);
+        assert_eq!(&line[left..left + density], &vec![ink; density][..]);
+        assert_eq!(&line[left + density..left + 7 * density], &vec![paper; 6 * density][..]);
+        assert_eq!(&line[left + 7 * density..left + 8 * density], &vec![ink; density][..]);
+        let mut saved = Vec::new();
+        scld.save_scr(&mut saved).unwrap();
+        assert_eq!(saved, scr);
+        // hi-res
+        let mut scr = vec![0u8; 12289];
+        scr[0] = 0b1000_0000;
+        scr[6144] = 0b0000_0001;
+        scr[12288] = 0b0011_1110;
+        let mut scld = TestScld::default();
+        scld.load_scr(Cursor::new(&scr)).unwrap();
+        let flags = scld.scld_ctrl_port_value().unwrap();
+        assert!(flags.is_screen_hi_res());
+        assert_eq!(flags & ScldCtrlFlags::HIRES_COLOR_MASK, ScldCtrlFlags::from(0b0011_1000));
+        assert!(matches!(scld.get_screen_mode(), ScrMode::HighRes(mode, false) if mode & 0b0011_1111 == 0b0011_1110));
+        // the hi-res color scheme 7: white ink on black paper, pixels from both screens interleaved
+        let (ink, paper) = (SpectrumPalRGB24::get_pixel(15), SpectrumPalRGB24::get_pixel(0));
+        let line = render_line(&mut scld);
+        assert_eq!(line[left], ink);
+        assert_eq!(&line[left + 1..left + 15], &[paper; 14][..]);
+        assert_eq!(line[left + 15], ink);
+        let mut saved = Vec::new();
+        scld.save_scr(&mut saved).unwrap();
+        assert_eq!(&saved[..12288], &scr[..12288]);
+        assert_eq!(saved[12288] & 0b0011_1111, 0b0011_1110);
+        // the palette variants are not supported without ULAplus
+        let scr = vec![0u8; 6912 + 64];
+        assert!(scld.load_scr(Cursor::new(&scr)).is_err());
+    }
+}
@@ -88,7 +88,7 @@ impl<M, D, X, V> Scld<M, D, X, V>
         }
         else {
             let coords = color_address_coords(addr);
-            frame_cache.update_frame_colors(&self.ula.memory, coords, addr, ts);
+            frame_cache.update_frame_colors_with_mode(&self.ula.memory, coords, addr, ts, self.ula.reduce_clash);
         }
     }
 
@@ -0,0 +1,557 @@
+/*
+    Copyright (C) 2020-2023  Rafal Michalski
+
+    This file is part of SPECTRUSTY, a Rust library for building emulators.
+
+    For the full copyright notice, see the lib.rs file.
+*/
+//! An emulator of the Scorpion ZS-256 computer chipset.
+//!
+//! Scorpion ZS-256 extends the 128k paging of port `0x7ffd` with the [ScorpionCtrlFlags] of port `0x1ffd`,
+//! giving access to 256kb of RAM in [Memory256kPlus], a service ROM and RAM bank `0` mapped at the first
+//! memory page.
+//!
+//! ROM banks of [Memory256kPlus] are expected to be loaded in the following order:
+//!
+//! | Bank | ROM            |
+//! |------|----------------|
+//! | 0    | 128k editor    |
+//! | 1    | 48k BASIC      |
+//! | 2    | service ROM    |
+//! | 3    | TR-DOS         |
+//!
+//! The TR-DOS bank is never selected by the paging ports. It should be mapped at the first memory page
+//! by the disk interface emulation instead.
+//!
+//! The video timings are the same as of the 48k ZX Spectrum, but there is no memory or I/O contention,
+//! no floating bus and no snow effect.
+//!
+//! [Memory256kPlus]: crate::memory::Memory256kPlus
+#![macro_use]
+mod audio_earmic;
+mod io;
+mod video;
+#[cfg(feature = "formats")]
+mod screen;
+
+#[cfg(feature = "snapshot")]
+use serde::{Serialize, Deserialize};
+
+use crate::z80emu::*;
+use crate::bus::{BusDevice, PortAddress, VFNullDevice};
+use crate::clock::{VFrameTs, VideoTs, VFrameTsCounter, MemoryContention};
+use crate::chip::{
+    Ula128MemFlags, ScorpionCtrlFlags,
+    InnerAccess, ControlUnit, MemoryAccess, UlaControl, trace::{InstructionTrace, TraceEntry},
+    ula::{
+        Ula, UlaControlExt, UlaCpuExt,
+        frame_cache::UlaFrameCache
+    }
+};
+use crate::memory::{ZxMemory, ZxMemoryError, Memory256kPlus, MemoryExtension, NoMemoryExtension};
+use crate::video::Video;
+pub use video::ScorpionVidFrame;
+
+/// A struct implementing [MemoryContention] for the Scorpion ZS-256 with no memory being contended.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ScorpionMemContention;
+
+impl MemoryContention for ScorpionMemContention {
+    #[inline(always)]
+    fn is_contended_address(self, _address: u16) -> bool {
+        false
+    }
+}
+
+type InnerUla<B, X> = Ula<Memory256kPlus, B, X, ScorpionVidFrame>;
+
+/// Matches the Scorpion ZS-256 `0x7ffd` memory port.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct ScorpionMemPortAddress;
+impl PortAddress for ScorpionMemPortAddress {
+    const ADDRESS_MASK: u16 = 0b1100_0000_0000_0010;
+    const ADDRESS_BITS: u16 = 0b0111_1111_1111_1101;
+}
+
+/// Matches the Scorpion ZS-256 `0x1ffd` extended memory control port.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct ScorpionCtrlPortAddress;
+impl PortAddress for ScorpionCtrlPortAddress {
+    const ADDRESS_MASK: u16 = 0b1111_0000_0000_0010;
+    const ADDRESS_BITS: u16 = 0b0001_1111_1111_1101;
+}
+
+/// The state of the Scorpion ZS-256 memory paging ports.
+///
+/// Writes to the paging ports should be forwarded to [ScorpionPaging::write_mem_port] and
+/// [ScorpionPaging::write_ctrl_port], which map the selected banks into the provided memory.
+/// The [Scorpion] chipset does that on its own.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "snapshot", serde(rename_all = "camelCase"))]
+pub struct ScorpionPaging {
+    mem_port: Ula128MemFlags,
+    ctrl_port: ScorpionCtrlFlags,
+}
+
+impl ScorpionPaging {
+    /// Returns the last value written to the `0x7ffd` port.
+    pub fn mem_port_value(&self) -> Ula128MemFlags {
+        self.mem_port
+    }
+    /// Returns the last value written to the `0x1ffd` port.
+    pub fn ctrl_port_value(&self) -> ScorpionCtrlFlags {
+        self.ctrl_port
+    }
+    /// Returns a RAM bank index mapped at the last memory page.
+    pub fn last_ram_page_bank(&self) -> usize {
+        self.mem_port.last_ram_page_bank() | self.ctrl_port.last_ram_page_bank_hi()
+    }
+    /// Returns a ROM bank index mapped at the first memory page, or `None` if RAM bank `0` is mapped there.
+    pub fn rom_page_bank(&self) -> Option<usize> {
+        if self.ctrl_port.is_ram_at_page0() {
+            None
+        }
+        else if self.ctrl_port.is_service_rom() {
+            Some(ScorpionCtrlFlags::SERVICE_ROM_BANK)
+        }
+        else {
+            Some(self.mem_port.rom_page_bank())
+        }
+    }
+    /// Returns `true` if the shadow screen bank is selected.
+    pub fn is_shadow_screen(&self) -> bool {
+        self.mem_port.is_shadow_screen()
+    }
+    /// Returns `true` if the `0x7ffd` port is locked.
+    pub fn is_mmu_locked(&self) -> bool {
+        self.mem_port.is_mmu_locked()
+    }
+    /// Updates the `0x7ffd` port value unless the port is locked and maps the selected banks into `memory`.
+    ///
+    /// Returns `true` if the port value has been updated.
+    pub fn write_mem_port<M: ZxMemory>(&mut self, data: u8, memory: &mut M) -> Result<bool, ZxMemoryError> {
+        if self.is_mmu_locked() {
+            return Ok(false)
+        }
+        self.set_mem_port_value(Ula128MemFlags::from_data(data), memory).map(|_| true)
+    }
+    /// Updates the `0x1ffd` port value and maps the selected banks into `memory`.
+    pub fn write_ctrl_port<M: ZxMemory>(&mut self, data: u8, memory: &mut M) -> Result<(), ZxMemoryError> {
+        self.set_ctrl_port_value(ScorpionCtrlFlags::from_data(data), memory)
+    }
+    /// Sets the `0x7ffd` port value regardless of the port being locked and maps the selected banks
+    /// into `memory`.
+    pub fn set_mem_port_value<M: ZxMemory>(&mut self, flags: Ula128MemFlags, memory: &mut M) -> Result<(), ZxMemoryError> {
+        self.mem_port = flags;
+        self.map_banks(memory)
+    }
+    /// Sets the `0x1ffd` port value and maps the selected banks into `memory`.
+    pub fn set_ctrl_port_value<M: ZxMemory>(&mut self, flags: ScorpionCtrlFlags, memory: &mut M) -> Result<(), ZxMemoryError> {
+        self.ctrl_port = flags;
+        self.map_banks(memory)
+    }
+    /// Maps the memory banks selected by the current state of the paging ports into `memory`.
+    pub fn map_banks<M: ZxMemory>(&self, memory: &mut M) -> Result<(), ZxMemoryError> {
+        match self.rom_page_bank() {
+            Some(rom_bank) => memory.map_rom_bank(rom_bank, 0)?,
+            None => memory.map_ram_bank(0, 0)?
+        }
+        memory.map_ram_bank(self.last_ram_page_bank(), 3)
+    }
+    /// Resets the paging ports and maps the default banks into `memory`.
+    pub fn reset<M: ZxMemory>(&mut self, memory: &mut M) -> Result<(), ZxMemoryError> {
+        *self = Default::default();
+        self.map_banks(memory)
+    }
+}
+
+/// The Scorpion ZS-256 chipset.
+///
+/// The memory paging is controlled by [ScorpionPaging]. The ROM banks should be loaded into the memory
+/// as described in the [module][self] documentation.
+///
+/// See [Ula] for description of generic parameters.
+#[derive(Clone)]
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "snapshot", serde(rename_all = "camelCase"))]
+pub struct Scorpion<B=VFNullDevice<ScorpionVidFrame>, X=NoMemoryExtension> {
+    ula: InnerUla<B, X>,
+    paging: ScorpionPaging,
+    beg_screen_shadow: bool, // shadow screen when a frame began
+
+    #[cfg(feature = "boxed_frame_cache")]
+    #[cfg_attr(feature = "snapshot", serde(skip))]
+    shadow_frame_cache: Box<UlaFrameCache<ScorpionVidFrame>>,
+
+    #[cfg(not(feature = "boxed_frame_cache"))]
+    #[cfg_attr(feature = "snapshot", serde(skip))]
+    shadow_frame_cache: UlaFrameCache<ScorpionVidFrame>,
+
+    #[cfg_attr(feature = "snapshot", serde(skip))]
+    screen_changes: Vec<VideoTs>
+}
+
+impl<B: Default, X: Default> Default for Scorpion<B, X> {
+    fn default() -> Self {
+        Scorpion {
+            ula: Ula::default(),
+            paging: ScorpionPaging::default(),
+            beg_screen_shadow: false,
+            shadow_frame_cache: Default::default(),
+            screen_changes: Vec::new()
+        }
+    }
+}
+
+impl<B, X> core::fmt::Debug for Scorpion<B, X>
+    where B: BusDevice, X: MemoryExtension
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Scorpion")
+            .field("ula", &self.ula)
+            .field("paging", &self.paging)
+            .field("beg_screen_shadow", &self.beg_screen_shadow)
+            .field("shadow_frame_cache", &self.shadow_frame_cache)
+            .field("screen_changes", &self.screen_changes.len())
+            .finish()
+    }
+}
+
+impl<B, X> InnerAccess for Scorpion<B, X> {
+    type Inner = InnerUla<B, X>;
+
+    fn inner_ref(&self) -> &Self::Inner {
+        &self.ula
+    }
+
+    fn inner_mut(&mut self) -> &mut Self::Inner {
+        &mut self.ula
+    }
+
+    fn into_inner(self) -> Self::Inner {
+        self.ula
+    }
+}
+
+impl<B, X> UlaControl for Scorpion<B, X> {
+    fn has_late_timings(&self) -> bool {
+        self.ula.has_late_timings()
+    }
+
+    fn set_late_timings(&mut self, late_timings: bool) {
+        self.ula.set_late_timings(late_timings)
+    }
+
+    fn interrupt_length(&self) -> Option<u8> {
+        self.ula.interrupt_length()
+    }
+
+    fn set_interrupt_length(&mut self, length: u8) -> bool {
+        self.ula.set_interrupt_length(length)
+    }
+
+    fn instruction_trace_capacity(&self) -> Option<usize> {
+        self.ula.instruction_trace_capacity()
+    }
+
+    fn set_instruction_trace(&mut self, capacity: Option<usize>) -> bool {
+        self.ula.set_instruction_trace(capacity)
+    }
+
+    fn instruction_trace(&self) -> Option<Vec<TraceEntry>> {
+        self.ula.instruction_trace()
+    }
+
+    fn cpu_turbo(&self) -> Option<u8> {
+        self.ula.cpu_turbo()
+    }
+
+    fn set_cpu_turbo(&mut self, multiplier: u8) -> bool {
+        self.ula.set_cpu_turbo(multiplier)
+    }
+
+    fn has_ear_mic_loopback(&self) -> Option<bool> {
+        self.ula.has_ear_mic_loopback()
+    }
+
+    fn set_ear_mic_loopback(&mut self, enabled: bool) -> bool {
+        self.ula.set_ear_mic_loopback(enabled)
+    }
+
+    fn tape_audio_gain(&self) -> Option<f32> {
+        self.ula.tape_audio_gain()
+    }
+
+    fn set_tape_audio_gain(&mut self, gain: f32) -> bool {
+        self.ula.set_tape_audio_gain(gain)
+    }
+
+    fn has_attr_clash_reduction(&self) -> Option<bool> {
+        self.ula.has_attr_clash_reduction()
+    }
+
+    fn set_attr_clash_reduction(&mut self, reduce_clash: bool) -> bool {
+        self.ula.set_attr_clash_reduction(reduce_clash)
+    }
+
+    fn ula128_mem_port_value(&self) -> Option<Ula128MemFlags> {
+        Some(self.paging.mem_port_value())
+    }
+
+    fn set_ula128_mem_port_value(&mut self, value: Ula128MemFlags) -> bool {
+        self.set_mem_port_value(value, self.ula.current_video_ts());
+        true
+    }
+}
+
+impl<B, X> Scorpion<B, X> {
+    /// Returns the state of the memory paging ports.
+    pub fn paging(&self) -> &ScorpionPaging {
+        &self.paging
+    }
+    /// Returns the last value written to the `0x1ffd` port.
+    pub fn scorpion_ctrl_port_value(&self) -> ScorpionCtrlFlags {
+        self.paging.ctrl_port_value()
+    }
+    /// Sets the `0x1ffd` port value and maps the selected memory banks.
+    pub fn set_scorpion_ctrl_port_value(&mut self, value: ScorpionCtrlFlags) {
+        self.paging.set_ctrl_port_value(value, &mut self.ula.memory).unwrap();
+    }
+
+    #[inline(always)]
+    fn page3_screen_shadow_bank(&self) -> Option<bool> {
+        match self.paging.last_ram_page_bank() {
+            5 => Some(false),
+            7 => Some(true),
+            _ => None
+        }
+    }
+
+    fn set_mem_port_value(&mut self, flags: Ula128MemFlags, ts: VideoTs) {
+        if self.paging.is_shadow_screen() != flags.is_shadow_screen() {
+            self.screen_changes.push(ts);
+            self.ula.mark_all_lines_dirty();
+        }
+        self.paging.set_mem_port_value(flags, &mut self.ula.memory).unwrap();
+    }
+}
+
+impl<B, X> MemoryAccess for Scorpion<B, X>
+    where X: MemoryExtension
+{
+    type Memory = Memory256kPlus;
+    type MemoryExt = X;
+
+    #[inline(always)]
+    fn memory_ext_ref(&self) -> &Self::MemoryExt {
+        &self.ula.memext
+    }
+    #[inline(always)]
+    fn memory_ext_mut(&mut self) -> &mut Self::MemoryExt {
+        &mut self.ula.memext
+    }
+    #[inline(always)]
+    fn memory_mut(&mut self) -> &mut Self::Memory {
+        &mut self.ula.memory
+    }
+    #[inline(always)]
+    fn memory_ref(&self) -> &Self::Memory {
+        &self.ula.memory
+    }
+
+    fn memory_with_ext_mut(&mut self) -> (&mut Self::Memory, &mut Self::MemoryExt) {
+        (&mut self.ula.memory, &mut self.ula.memext)
+    }
+}
+
+impl<B, X> ControlUnit for Scorpion<B, X>
+    where B: BusDevice,
+          B::Timestamp: From<VFrameTs<ScorpionVidFrame>>,
+          X: MemoryExtension
+{
+    type BusDevice = B;
+
+    #[inline]
+    fn bus_device_mut(&mut self) -> &mut Self::BusDevice {
+        self.ula.bus_device_mut()
+    }
+    #[inline]
+    fn bus_device_ref(&self) -> &Self::BusDevice {
+        self.ula.bus_device_ref()
+    }
+    #[inline]
+    fn into_bus_device(self) -> Self::BusDevice {
+        self.ula.into_bus_device()
+    }
+
+    fn reset<C: Cpu>(&mut self, cpu: &mut C, hard: bool) {
+        self.ula.reset(cpu, hard);
+        if hard {
+            if self.paging.is_shadow_screen() {
+                self.screen_changes.push(self.current_video_ts());
+            }
+            self.paging.reset(&mut self.ula.memory).unwrap();
+        }
+    }
+
+    fn nmi<C: Cpu>(&mut self, cpu: &mut C) -> bool {
+        self.ula_nmi(cpu)
+    }
+
+    fn execute_next_frame<C: Cpu>(&mut self, cpu: &mut C) {
+        while !self.ula_execute_next_frame_with_breaks(cpu) {}
+    }
+
+    fn ensure_next_frame(&mut self) {
+        self.ensure_next_frame_vtsc();
+    }
+
+    fn execute_single_step<C: Cpu, F: FnOnce(CpuDebug)>(
+            &mut self,
+            cpu: &mut C,
+            debug: Option<F>
+        ) -> host::Result<(),()>
+    {
+        self.ula_execute_single_step(cpu, debug)
+    }
+}
+
+impl<B, X> UlaControlExt for Scorpion<B, X>
+    where B: BusDevice,
+          B::Timestamp: From<VFrameTs<ScorpionVidFrame>>
+{
+    fn prepare_next_frame<C: MemoryContention>(
+            &mut self,
+            vtsc: VFrameTsCounter<ScorpionVidFrame, C>
+        ) -> VFrameTsCounter<ScorpionVidFrame, C>
+    {
+        self.beg_screen_shadow = self.paging.is_shadow_screen();
+        self.shadow_frame_cache.clear();
+        self.screen_changes.clear();
+        self.ula.prepare_next_frame(vtsc)
+    }
+
+    fn cpu_turbo_multiplier(&self) -> u8 {
+        self.ula.cpu_turbo_multiplier()
+    }
+
+    fn cpu_turbo_remainder_mut(&mut self) -> &mut u8 {
+        self.ula.cpu_turbo_remainder_mut()
+    }
+
+    fn instruction_trace_mut(&mut self) -> &mut Option<InstructionTrace> {
+        self.ula.instruction_trace_mut()
+    }
+
+    fn rom_trap<C: Cpu>(&mut self, cpu: &mut C) -> bool {
+        self.ula.rom_trap(cpu)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::memory::{Memory256kPlus, MemoryKind};
+    use crate::video::VideoFrame;
+    use super::*;
+
+    #[test]
+    fn scorpion_paging_works() {
+        let mut mem = Memory256kPlus::default();
+        for bank in 0..16 {
+            mem.ram_bank_mut(bank).unwrap().fill(bank as u8);
+        }
+        for bank in 0..4 {
+            mem.rom_bank_mut(bank).unwrap().fill(0x80 | bank as u8);
+        }
+        let mut paging = ScorpionPaging::default();
+        paging.reset(&mut mem).unwrap();
+        assert_eq!(mem.page_bank(0).unwrap(), (MemoryKind::Rom, 0));
+        assert_eq!(mem.page_bank(3).unwrap(), (MemoryKind::Ram, 0));
+        assert!(ScorpionMemPortAddress::match_port(0x7ffd));
+        assert!(ScorpionCtrlPortAddress::match_port(0x1ffd));
+        assert!(!ScorpionCtrlPortAddress::match_port(0x7ffd));
+        // extended RAM bank 11: 0x1ffd b4 + 0x7ffd bank 3
+        paging.write_ctrl_port(0b1_0000, &mut mem).unwrap();
+        assert!(paging.write_mem_port(0b0_0011, &mut mem).unwrap());
+        assert_eq!(paging.last_ram_page_bank(), 11);
+        assert_eq!(mem.page_bank(3).unwrap(), (MemoryKind::Ram, 11));
+        assert_eq!(mem.read(0xC000), 11);
+        assert_eq!(mem.read(0xFFFF), 11);
+        mem.write(0xC000, 0xAA);
+        assert_eq!(mem.ram_bank_ref(11).unwrap()[0], 0xAA);
+        assert_eq!(mem.ram_bank_ref(3).unwrap()[0], 3);
+        // the regular 128k bank when the extended bit is cleared
+        paging.write_ctrl_port(0, &mut mem).unwrap();
+        assert_eq!(mem.read(0xC001), 3);
+        // the service ROM takes precedence over the 48k ROM
+        paging.write_ctrl_port(0b1_0010, &mut mem).unwrap();
+        paging.write_mem_port(0b1_0111, &mut mem).unwrap();
+        assert_eq!(paging.rom_page_bank(), Some(2));
+        assert_eq!(mem.read(0x0000), 0x82);
+        assert_eq!(mem.read(0xC001), 15);
+        // RAM bank 0 at the first page takes precedence over the service ROM
+        paging.write_ctrl_port(0b0_0011, &mut mem).unwrap();
+        assert_eq!(paging.rom_page_bank(), None);
+        assert_eq!(mem.page_bank(0).unwrap(), (MemoryKind::Ram, 0));
+        mem.write(0x0000, 0x55);
+        assert_eq!(mem.ram_bank_ref(0).unwrap()[0], 0x55);
+        paging.write_ctrl_port(0, &mut mem).unwrap();
+        assert_eq!(mem.read(0x0000), 0x81);
+        // the locked 0x7ffd port ignores writes
+        assert!(paging.write_mem_port(0b10_0001, &mut mem).unwrap());
+        assert!(!paging.write_mem_port(0b00_0100, &mut mem).unwrap());
+        assert!(paging.is_mmu_locked());
+        assert_eq!(mem.read(0x0000), 0x80);
+        assert_eq!(mem.read(0xC001), 1);
+        // but the 0x1ffd port is still active
+        paging.write_ctrl_port(0b1_0000, &mut mem).unwrap();
+        assert_eq!(mem.read(0xC001), 9);
+        paging.reset(&mut mem).unwrap();
+        assert!(!paging.is_mmu_locked());
+        assert_eq!(mem.read(0xC001), 0);
+    }
+
+    #[test]
+    fn scorpion_chip_works() {
+        let mut scorpion = Scorpion::<VFNullDevice<ScorpionVidFrame>>::default();
+        assert_eq!(ScorpionVidFrame::FRAME_TSTATES_COUNT, 69888);
+        assert_eq!(<Scorpion as crate::chip::HostConfig>::FRAME_TSTATES, 69888);
+        for addr in [0x0000, 0x4000, 0x8000, 0xC000] {
+            assert!(!ScorpionMemContention.is_contended_address(addr));
+        }
+        for bank in 0..16 {
+            scorpion.memory_mut().ram_bank_mut(bank).unwrap().fill(bank as u8);
+        }
+        let ts = VideoTs::new(0, 0);
+        assert_eq!(scorpion.write_io(0x7ffd, 0b0_1111, ts), (Some(()), None));
+        assert_eq!(scorpion.read_debug(0xC000), 7);
+        assert_eq!(scorpion.visible_screen_bank(), 1);
+        assert_eq!(scorpion.ula128_mem_port_value(), Some(Ula128MemFlags::from_data(0b0_1111)));
+        assert_eq!(scorpion.write_io(0x1ffd, 0b1_0000, ts), (Some(()), None));
+        assert_eq!(scorpion.read_debug(0xC000), 15);
+        assert_eq!(scorpion.scorpion_ctrl_port_value(), ScorpionCtrlFlags::from_data(0b1_0000));
+        // lock the paging
+        assert_eq!(scorpion.write_io(0x7ffd, 0b10_0000, ts), (Some(()), None));
+        assert_eq!(scorpion.visible_screen_bank(), 0);
+        assert_eq!(scorpion.write_io(0x7ffd, 0b00_0001, ts), (None, None));
+        assert_eq!(scorpion.read_debug(0xC000), 8);
+        assert_eq!(scorpion.read_io(0x7ffd, ts), (0xFF, None));
+        let mut cpu = Z80NMOS::default();
+        scorpion.reset(&mut cpu, true);
+        assert_eq!(*scorpion.paging(), ScorpionPaging::default());
+        assert_eq!(scorpion.read_debug(0xC000), 0);
+    }
+
+    #[cfg(feature = "snapshot")]
+    #[test]
+    fn scorpion_serde_works() {
+        let mut scorpion = Scorpion::<VFNullDevice<ScorpionVidFrame>>::default();
+        scorpion.write_io(0x1ffd, 0b1_0001, VideoTs::new(0, 0));
+        scorpion.write_io(0x7ffd, 0b1_1010, VideoTs::new(0, 0));
+        let json = serde_json::to_string(&scorpion).unwrap();
+        let restored: Scorpion = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.paging(), scorpion.paging());
+        assert_eq!(restored.read_debug(0xC000), scorpion.read_debug(0xC000));
+    }
+}
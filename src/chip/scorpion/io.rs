@@ -0,0 +1,99 @@
+/*
+    Copyright (C) 2020-2023  Rafal Michalski
+
+    This file is part of SPECTRUSTY, a Rust library for building emulators.
+
+    For the full copyright notice, see the lib.rs file.
+*/
+use core::num::NonZeroU16;
+
+use crate::z80emu::{Io, Memory};
+use crate::bus::{BusDevice, PortAddress};
+use crate::clock::{VideoTs, VFrameTs};
+use crate::chip::Ula128MemFlags;
+use crate::peripherals::{KeyboardInterface, ZXKeyboardMap};
+use crate::memory::{ZxMemory, MemoryExtension};
+use crate::video::VideoFrame;
+use super::{Scorpion, ScorpionVidFrame, ScorpionMemPortAddress, ScorpionCtrlPortAddress};
+
+impl<B, X> Io for Scorpion<B, X>
+    where B: BusDevice,
+          B::Timestamp: From<VFrameTs<ScorpionVidFrame>>
+{
+    type Timestamp = VideoTs;
+    type WrIoBreak = ();
+    type RetiBreak = ();
+
+    #[inline(always)]
+    fn is_irq(&mut self, ts: VideoTs) -> bool {
+        self.ula.is_irq(ts)
+    }
+
+    fn read_io(&mut self, port: u16, ts: VideoTs) -> (u8, Option<NonZeroU16>) {
+        self.ula.ula_read_io(port, ts, self.paging.is_shadow_screen().into())
+                .unwrap_or((u8::MAX, None))
+    }
+
+    fn write_io(&mut self, port: u16, data: u8, ts: VideoTs) -> (Option<()>, Option<NonZeroU16>) {
+        if ScorpionMemPortAddress::match_port(port) {
+            if !self.paging.is_mmu_locked() {
+                self.set_mem_port_value(Ula128MemFlags::from_data(data), ts);
+                return (Some(()), None)
+            }
+            (None, None)
+        }
+        else {
+            let (mut res, ws) = self.ula.write_io(port, data, ts);
+            if ScorpionCtrlPortAddress::match_port(port) {
+                self.paging.write_ctrl_port(data, &mut self.ula.memory).unwrap();
+                res = Some(());
+            }
+            (res, ws)
+        }
+    }
+}
+
+impl<B, X> Memory for Scorpion<B, X>
+    where B: BusDevice,
+          B::Timestamp: From<VFrameTs<ScorpionVidFrame>>,
+          X: MemoryExtension
+{
+    type Timestamp = VideoTs;
+
+    #[inline(always)]
+    fn read_debug(&self, addr: u16) -> u8 {
+        self.ula.memory.read(addr)
+    }
+
+    #[inline(always)]
+    fn read_mem(&self, addr: u16, ts: VideoTs) -> u8 {
+        self.ula.memext.read_mem(addr, ScorpionVidFrame::vc_hc_to_tstates(ts.vc, ts.hc), &self.ula.memory)
+    }
+
+    #[inline(always)]
+    fn read_mem16(&self, addr: u16, ts: VideoTs) -> u16 {
+        self.ula.memext.read_mem16(addr, ScorpionVidFrame::vc_hc_to_tstates(ts.vc, ts.hc), &self.ula.memory)
+    }
+
+    #[inline(always)]
+    fn read_opcode(&mut self, pc: u16, _ir: u16, _ts: VideoTs) -> u8 {
+        self.ula.memext.read_opcode(pc, &mut self.ula.memory)
+    }
+
+    #[inline]
+    fn write_mem(&mut self, addr: u16, val: u8, ts: VideoTs) {
+        self.update_frame_cache(addr, ts);
+        self.ula.memext.write_mem(addr, val, ScorpionVidFrame::vc_hc_to_tstates(ts.vc, ts.hc), &mut self.ula.memory);
+    }
+}
+
+impl<B, X> KeyboardInterface for Scorpion<B, X> {
+    #[inline(always)]
+    fn get_key_state(&self) -> ZXKeyboardMap {
+        self.ula.get_key_state()
+    }
+    #[inline(always)]
+    fn set_key_state(&mut self, keymap: ZXKeyboardMap)  {
+        self.ula.set_key_state(keymap);
+    }
+}
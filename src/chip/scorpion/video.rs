@@ -0,0 +1,147 @@
+/*
+    Copyright (C) 2020-2023  Rafal Michalski
+
+    This file is part of SPECTRUSTY, a Rust library for building emulators.
+
+    For the full copyright notice, see the lib.rs file.
+*/
+use core::iter::StepBy;
+use core::ops::Range;
+
+#[cfg(feature = "snapshot")]
+use serde::{Serialize, Deserialize};
+
+use crate::clock::{VideoTs, Ts, VFrameTsCounter};
+use crate::chip::{
+    ula::UlaVideoFrame,
+    ula128::video::create_ula128_renderer
+};
+use crate::video::{
+    BorderSize, BorderColor, PixelBuffer, Palette,
+    VideoFrame, Video,
+    frame_cache::{pixel_address_coords, color_address_coords}
+};
+use super::{Scorpion, ScorpionMemContention};
+
+/// Implements [VideoFrame] for the Scorpion ZS-256.
+///
+/// The video timings are the same as of [UlaVideoFrame], but there is no contention, no floating bus
+/// and no snow effect.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+pub struct ScorpionVidFrame;
+
+impl VideoFrame for ScorpionVidFrame {
+    const HTS_RANGE: Range<Ts> = UlaVideoFrame::HTS_RANGE;
+    const VSL_BORDER_TOP: Ts = UlaVideoFrame::VSL_BORDER_TOP;
+    const VSL_PIXELS: Range<Ts> = UlaVideoFrame::VSL_PIXELS;
+    const VSL_BORDER_BOT: Ts = UlaVideoFrame::VSL_BORDER_BOT;
+    const VSL_COUNT: Ts = UlaVideoFrame::VSL_COUNT;
+
+    type BorderHtsIter = StepBy<Range<Ts>>;
+
+    fn border_whole_line_hts_iter(border_size: BorderSize) -> Self::BorderHtsIter {
+        UlaVideoFrame::border_whole_line_hts_iter(border_size)
+    }
+
+    fn border_left_hts_iter(border_size: BorderSize) -> Self::BorderHtsIter {
+        UlaVideoFrame::border_left_hts_iter(border_size)
+    }
+
+    fn border_right_hts_iter(border_size: BorderSize) -> Self::BorderHtsIter {
+        UlaVideoFrame::border_right_hts_iter(border_size)
+    }
+
+    #[inline(always)]
+    fn is_contended_line_mreq(_vsl: Ts) -> bool {
+        false
+    }
+
+    #[inline(always)]
+    fn is_contended_line_no_mreq(_vsl: Ts) -> bool {
+        false
+    }
+
+    #[inline(always)]
+    fn contention(hc: Ts) -> Ts {
+        hc
+    }
+}
+
+impl<D, X> Video for Scorpion<D, X> {
+    type VideoFrame = ScorpionVidFrame;
+    type Contention = ScorpionMemContention;
+
+    #[inline]
+    fn border_color(&self) -> BorderColor {
+        self.ula.border_color()
+    }
+
+    fn set_border_color(&mut self, border: BorderColor) {
+        self.ula.set_border_color(border)
+    }
+
+    fn render_video_frame<'a, B: PixelBuffer<'a>, P: Palette<Pixel=B::Pixel>>(
+            &mut self,
+            buffer: &'a mut [u8],
+            pitch: usize,
+            border_size: BorderSize
+        )
+    {
+        create_ula128_renderer(border_size,
+                               &mut self.ula,
+                               self.beg_screen_shadow,
+                               &self.shadow_frame_cache,
+                               &mut self.screen_changes)
+        .render_pixels::<B, P, Self::VideoFrame>(buffer, pitch)
+    }
+
+    fn visible_screen_bank(&self) -> usize {
+        self.paging.is_shadow_screen().into()
+    }
+
+    fn current_video_ts(&self) -> VideoTs {
+        self.ula.current_video_ts()
+    }
+
+    fn current_video_clock(&self) -> VFrameTsCounter<Self::VideoFrame, Self::Contention> {
+        VFrameTsCounter::from_video_ts(self.ula.current_video_ts(), ScorpionMemContention)
+    }
+
+    fn set_video_ts(&mut self, vts: VideoTs) {
+        self.ula.set_video_ts(vts);
+    }
+
+    fn flash_state(&self) -> bool {
+        self.ula.flash_state()
+    }
+
+    fn dirty_lines(&self) -> Option<u32> {
+        self.ula.dirty_lines()
+    }
+}
+
+impl<B, X> Scorpion<B, X> {
+    #[inline]
+    pub(super) fn update_frame_cache(&mut self, addr: u16, ts: VideoTs) {
+        let maybe_shadow = match addr {
+            0x4000..=0x5AFF => Some(false),
+            0xC000..=0xDAFF => self.page3_screen_shadow_bank(),
+            _ => return
+        };
+        let frame_cache = match maybe_shadow {
+            Some(false) => &mut self.ula.frame_cache,
+            Some(true)  => &mut self.shadow_frame_cache,
+            None => return
+        };
+        if addr & 0x1800 != 0x1800 {
+            let coords = pixel_address_coords(addr);
+            frame_cache.update_frame_pixels(&self.ula.memory, coords, addr, ts);
+        }
+        else {
+            let coords = color_address_coords(addr);
+            frame_cache.update_frame_colors_with_mode(&self.ula.memory, coords, addr, ts, self.ula.reduce_clash);
+        }
+        self.ula.mark_dirty_screen_address(addr);
+    }
+}
@@ -0,0 +1,84 @@
+/*
+    Copyright (C) 2020-2023  Rafal Michalski
+
+    This file is part of SPECTRUSTY, a Rust library for building emulators.
+
+    For the full copyright notice, see the lib.rs file.
+*/
+//! A recent history log of the executed instructions.
+use std::collections::VecDeque;
+
+use crate::z80emu::{CpuDebug, CpuDebugCode};
+use crate::clock::FTs;
+
+/// A single entry of the [InstructionTrace] log.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TraceEntry {
+    /// A program counter addressing the executed instruction.
+    pub pc: u16,
+    /// The bytes that assemble the executed instruction, including prefixes.
+    pub code: CpuDebugCode,
+    /// The frame T-state counter value at which the execution of the instruction has started.
+    pub fts: FTs
+}
+
+/// A ring buffer holding the entries of the recently executed instructions.
+///
+/// When the buffer is full, the oldest entry is being dropped for each new one.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct InstructionTrace {
+    entries: VecDeque<TraceEntry>,
+    capacity: usize,
+    start_fts: FTs
+}
+
+impl InstructionTrace {
+    /// Creates a new empty trace log holding up to `capacity` entries.
+    pub fn new(capacity: usize) -> Self {
+        let entries = VecDeque::with_capacity(capacity);
+        InstructionTrace { entries, capacity, start_fts: 0 }
+    }
+    /// Returns the maximum number of entries held in the log.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+    /// Returns the number of entries currently held in the log.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    /// Returns `true` if there are no entries in the log.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    /// Removes all entries from the log.
+    pub fn clear(&mut self) {
+        self.entries.clear()
+    }
+    /// Appends a new entry, dropping the oldest one if the log is full.
+    pub fn push(&mut self, entry: TraceEntry) {
+        if self.capacity == 0 {
+            return
+        }
+        if self.entries.len() >= self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+    /// Records the T-state counter value at which the execution of the next instruction starts.
+    pub(crate) fn mark_start(&mut self, fts: FTs) {
+        self.start_fts = fts;
+    }
+    /// Appends a new entry from the debug information of the executed instruction.
+    pub(crate) fn push_debug(&mut self, deb: CpuDebug) {
+        let fts = self.start_fts;
+        self.push(TraceEntry { pc: deb.pc, code: deb.code, fts })
+    }
+    /// Returns an iterator over the entries from the oldest to the most recent one.
+    pub fn iter(&self) -> impl Iterator<Item=&TraceEntry> + '_ {
+        self.entries.iter()
+    }
+    /// Returns a vector of the entries from the oldest to the most recent one.
+    pub fn to_vec(&self) -> Vec<TraceEntry> {
+        self.entries.iter().cloned().collect()
+    }
+}
@@ -15,12 +15,15 @@ pub mod frame_cache;
 mod io;
 mod video;
 mod video_ntsc;
+mod video_inves;
 mod plus;
 mod cpuext;
+mod turbo;
 #[cfg(feature = "formats")]
 mod screen;
 
-use core::num::Wrapping;
+use core::num::{NonZeroU32, Wrapping};
+use std::collections::VecDeque;
 
 #[allow(unused_imports)]
 use log::{error, warn, info, debug, trace};
@@ -28,12 +31,16 @@ use log::{error, warn, info, debug, trace};
 use crate::z80emu::{*, host::Result};
 #[cfg(feature = "snapshot")]
 use serde::{Serialize, Deserialize};
+#[cfg(feature = "snapshot")]
+use crate::chip::{ula128::Ula128VidFrame, DEFAULT_INTERRUPT_LENGTH_128};
 
-use crate::bus::{BusDevice, VFNullDevice};
+use crate::bus::{BusDevice, CpuMemoryTrapAccess, VFNullDevice};
 use crate::chip::{
-    UlaControl, FrameState, ControlUnit, MemoryAccess, EarMic, ReadEarMode
+    UlaControl, FrameState, ControlUnit, MemoryAccess, EarMic, ReadEarMode,
+    DEFAULT_INTERRUPT_LENGTH, CPU_TURBO_MULTIPLIERS,
+    trace::{InstructionTrace, TraceEntry}
 };
-use crate::video::{BorderColor, VideoFrame};
+use crate::video::{BorderColor, VideoFrame, DIRTY_LINES_ALL};
 use crate::memory::{ZxMemory, MemoryExtension, NoMemoryExtension};
 use crate::peripherals::ZXKeyboardMap;
 use crate::clock::{
@@ -45,11 +52,20 @@ use frame_cache::UlaFrameCache;
 pub use cpuext::*;
 pub use video::UlaVideoFrame;
 pub use video_ntsc::UlaNTSCVidFrame;
+pub use video_inves::UlaInvesVidFrame;
 
 /// NTSC 16k/48k ULA (Uncommitted Logic Array).
 pub type UlaNTSC<M, B=VFNullDevice<UlaNTSCVidFrame>, X=NoMemoryExtension> = Ula<M, B, X, UlaNTSCVidFrame>;
 /// PAL 16k/48k ULA (Uncommitted Logic Array).
 pub type UlaPAL<M, B=VFNullDevice<UlaVideoFrame>, X=NoMemoryExtension> = Ula<M, B, X, UlaVideoFrame>;
+/// The ULA clone of the Inves Spectrum+, a Spanish 48k ZX Spectrum compatible computer.
+///
+/// Apart from the lack of the floating bus (see [UlaInvesVidFrame]) it behaves as [UlaPAL].
+/// The Inves Spectrum+ ROM should be loaded into the memory.
+///
+/// Only the floating bus difference is emulated. Other hardware quirks of the Inves Spectrum+,
+/// e.g. the interaction of the `EAR` input with the border port writes, are not.
+pub type UlaInves<M, B=VFNullDevice<UlaInvesVidFrame>, X=NoMemoryExtension> = Ula<M, B, X, UlaInvesVidFrame>;
 
 /// A struct implementing [MemoryContention] for addresses in the range: [0x4000, 0x7FFF] being contended.
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
@@ -80,6 +96,22 @@ pub struct Ula<M, B, X, V> {
     keyboard: ZXKeyboardMap,
     read_ear_mode: ReadEarMode,
     late_timings: bool,
+    #[cfg_attr(feature = "snapshot", serde(default))]
+    pub(super) reduce_clash: bool,
+    #[cfg_attr(feature = "snapshot", serde(default = "floating_bus_default"))]
+    pub(super) floating_bus: bool,
+    #[cfg_attr(feature = "snapshot", serde(default = "snow_effect_default"))]
+    pub(super) snow_effect: bool,
+    #[cfg_attr(feature = "snapshot", serde(default = "interrupt_length_default::<V>"))]
+    pub(super) interrupt_length: u8,
+    #[cfg_attr(feature = "snapshot", serde(default = "cpu_turbo_default"))]
+    cpu_turbo: u8,
+    #[cfg_attr(feature = "snapshot", serde(skip))]
+    cpu_turbo_remainder: u8, // the remainder of the CPU cycles divided by cpu_turbo
+    #[cfg_attr(feature = "snapshot", serde(skip, default = "dirty_lines_default"))]
+    dirty_lines: u32, // character rows modified in the current frame
+    #[cfg_attr(feature = "snapshot", serde(skip))]
+    prev_dirty_lines: u32, // character rows modified in the previous frame
     // video related
     #[cfg(feature = "boxed_frame_cache")]
     #[cfg_attr(feature = "snapshot", serde(skip))]
@@ -104,6 +136,12 @@ pub struct Ula<M, B, X, V> {
     prev_earmic_ts: FTs, // previously recorded change timestamp
     prev_earmic_data: EarMic, // previous frame last recorded data
     last_earmic_data: EarMic, // last recorded data
+    #[cfg_attr(feature = "snapshot", serde(default = "tape_audio_gain_default"))]
+    tape_audio_gain: f32, // the gain of the audible EAR IN and MIC OUT signals
+    #[cfg_attr(feature = "snapshot", serde(skip))]
+    trace: Option<InstructionTrace>, // optional instruction trace log
+    #[cfg_attr(feature = "snapshot", serde(skip))]
+    loopback: Option<VecDeque<NonZeroU32>>, // MIC out pulses recorded for EAR in
 }
 
 impl MemoryContention for UlaMemoryContention {
@@ -149,6 +187,127 @@ impl<M, B, X, V> UlaControl for Ula<M, B, X, V> {
     fn set_late_timings(&mut self, late_timings: bool) {
         self.late_timings = late_timings;
     }
+
+    fn has_attr_clash_reduction(&self) -> Option<bool> {
+        Some(self.reduce_clash)
+    }
+
+    fn set_attr_clash_reduction(&mut self, reduce_clash: bool) -> bool {
+        self.reduce_clash = reduce_clash;
+        true
+    }
+
+    fn has_floating_bus(&self) -> Option<bool> {
+        Some(self.floating_bus)
+    }
+
+    fn set_floating_bus(&mut self, enabled: bool) -> bool {
+        self.floating_bus = enabled;
+        true
+    }
+
+    fn has_snow_effect(&self) -> Option<bool> {
+        Some(self.snow_effect)
+    }
+
+    fn set_snow_effect(&mut self, enabled: bool) -> bool {
+        self.snow_effect = enabled;
+        true
+    }
+
+    fn interrupt_length(&self) -> Option<u8> {
+        Some(self.interrupt_length)
+    }
+
+    fn set_interrupt_length(&mut self, length: u8) -> bool {
+        self.interrupt_length = length;
+        true
+    }
+
+    fn cpu_turbo(&self) -> Option<u8> {
+        Some(self.cpu_turbo)
+    }
+
+    fn set_cpu_turbo(&mut self, multiplier: u8) -> bool {
+        if CPU_TURBO_MULTIPLIERS.contains(&multiplier) {
+            self.cpu_turbo = multiplier;
+            self.cpu_turbo_remainder = 0;
+            true
+        }
+        else {
+            false
+        }
+    }
+
+    fn instruction_trace_capacity(&self) -> Option<usize> {
+        self.trace.as_ref().map(|trace| trace.capacity())
+    }
+
+    fn set_instruction_trace(&mut self, capacity: Option<usize>) -> bool {
+        self.trace = capacity.map(InstructionTrace::new);
+        true
+    }
+
+    fn instruction_trace(&self) -> Option<Vec<TraceEntry>> {
+        self.trace.as_ref().map(|trace| trace.to_vec())
+    }
+
+    fn has_ear_mic_loopback(&self) -> Option<bool> {
+        Some(self.loopback.is_some())
+    }
+
+    fn set_ear_mic_loopback(&mut self, enabled: bool) -> bool {
+        self.loopback = if enabled {
+            self.loopback.take().or_else(|| Some(VecDeque::new()))
+        }
+        else {
+            None
+        };
+        true
+    }
+
+    fn tape_audio_gain(&self) -> Option<f32> {
+        Some(self.tape_audio_gain)
+    }
+
+    fn set_tape_audio_gain(&mut self, gain: f32) -> bool {
+        self.tape_audio_gain = gain.clamp(0.0, 1.0);
+        true
+    }
+}
+
+#[cfg(feature = "snapshot")]
+fn floating_bus_default() -> bool {
+    true
+}
+
+#[cfg(feature = "snapshot")]
+fn snow_effect_default() -> bool {
+    true
+}
+
+#[cfg(feature = "snapshot")]
+fn interrupt_length_default<V: VideoFrame>() -> u8 {
+    // the 128k/+2/+2A/+3 ULAs are recognized by their longer video lines
+    if V::HTS_COUNT == Ula128VidFrame::HTS_COUNT {
+        DEFAULT_INTERRUPT_LENGTH_128
+    }
+    else {
+        DEFAULT_INTERRUPT_LENGTH
+    }
+}
+
+#[cfg(feature = "snapshot")]
+fn dirty_lines_default() -> u32 {
+    DIRTY_LINES_ALL
+}
+
+fn cpu_turbo_default() -> u8 {
+    1
+}
+
+fn tape_audio_gain_default() -> f32 {
+    1.0
 }
 
 impl<M, B, X, V> Default for Ula<M, B, X, V>
@@ -167,6 +326,14 @@ where M: Default,
             keyboard: ZXKeyboardMap::empty(),
             read_ear_mode: ReadEarMode::Issue3,
             late_timings: false,
+            reduce_clash: false,
+            floating_bus: true,
+            snow_effect: true,
+            interrupt_length: DEFAULT_INTERRUPT_LENGTH,
+            cpu_turbo: cpu_turbo_default(),
+            cpu_turbo_remainder: 0,
+            dirty_lines: DIRTY_LINES_ALL,
+            prev_dirty_lines: 0,
             // video related
             frame_cache: Default::default(),
             border_out_changes: Vec::new(),
@@ -181,6 +348,9 @@ where M: Default,
             prev_earmic_ts: FTs::min_value(),
             prev_earmic_data: EarMic::empty(),
             last_earmic_data: EarMic::empty(),
+            tape_audio_gain: tape_audio_gain_default(),
+            trace: None,
+            loopback: None,
         }
     }
 }
@@ -201,6 +371,11 @@ impl<M, B, X, V> fmt::Debug for Ula<M, B, X, V>
             .field("keyboard", &self.keyboard)
             .field("read_ear_mode", &self.read_ear_mode)
             .field("late_timings", &self.late_timings)
+            .field("reduce_clash", &self.reduce_clash)
+            .field("floating_bus", &self.floating_bus)
+            .field("snow_effect", &self.snow_effect)
+            .field("interrupt_length", &self.interrupt_length)
+            .field("cpu_turbo", &self.cpu_turbo)
             .field("frame_cache", &self.frame_cache)
             .field("border_out_changes", &self.border_out_changes.len())
             .field("border", &self.border)
@@ -211,6 +386,9 @@ impl<M, B, X, V> fmt::Debug for Ula<M, B, X, V>
             .field("earmic_out_changes", &self.earmic_out_changes.len())
             .field("prev_earmic_data", &self.prev_earmic_data)
             .field("last_earmic_data", &self.last_earmic_data)
+            .field("tape_audio_gain", &self.tape_audio_gain)
+            .field("trace", &self.trace.as_ref().map(|trace| trace.len()))
+            .field("loopback", &self.loopback.as_ref().map(|loopback| loopback.len()))
             .finish()
     }
 }
@@ -269,6 +447,7 @@ impl<M, B, X, V> ControlUnit for Ula<M, B, X, V>
             cpu.reset();
             self.bus.reset(self.tsc.into());
             self.memory.reset();
+            self.mark_all_lines_dirty();
         }
         else {
             const DEBUG: Option<CpuDebugFn> = None;
@@ -313,11 +492,32 @@ impl<M, B, X, V> UlaControlExt for Ula<M, B, X, V>
         self.bus.next_frame(VFrameTs::<V>::EOF.into());
         self.frames += Wrapping(1);
         self.cleanup_video_frame_data();
+        let ear_in_reads = self.read_ear_in_count.0;
+        let mic_out = self.record_ear_mic_loopback();
         self.cleanup_earmic_frame_data();
         vtsc.wrap_frame();
         self.tsc = vtsc.into();
+        if !mic_out {
+            self.play_ear_mic_loopback(ear_in_reads);
+        }
         vtsc
     }
+
+    fn cpu_turbo_multiplier(&self) -> u8 {
+        self.cpu_turbo
+    }
+
+    fn cpu_turbo_remainder_mut(&mut self) -> &mut u8 {
+        &mut self.cpu_turbo_remainder
+    }
+
+    fn instruction_trace_mut(&mut self) -> &mut Option<InstructionTrace> {
+        &mut self.trace
+    }
+
+    fn rom_trap<C: Cpu>(&mut self, cpu: &mut C) -> bool {
+        self.bus.rom_trap(&mut CpuMemoryTrapAccess { cpu, memory: &mut self.memory })
+    }
 }
 
 #[cfg(test)]
@@ -339,4 +539,1022 @@ mod tests {
             assert_eq!(clock.is_contended_address(addr), false);
         }
     }
+
+    #[test]
+    fn test_custom_contention() {
+        use crate::clock::{CustomContention, ULA_CONTENTION_PATTERN};
+        type TestCounter<C> = VFrameTsCounter<UlaVideoFrame, C>;
+        // the default pattern matches the ULA contention
+        for ts in 14300..14500 {
+            for addr in [0x0000, 0x4000, 0x8000] {
+                let mut ula_clock = TestCounter::from_tstates(ts, UlaMemoryContention);
+                let mut custom_clock = TestCounter::from_tstates(ts, CustomContention::default());
+                assert_eq!(ula_clock.add_mreq(addr), custom_clock.add_mreq(addr));
+                assert_eq!(ula_clock.add_io(addr|1), custom_clock.add_io(addr|1));
+                assert_eq!(ula_clock, TestCounter::from_tstates(custom_clock.into_tstates(), UlaMemoryContention));
+            }
+        }
+        let contention = CustomContention::new([1, 7, 6, 5, 4, 3, 2, 0])
+                                          .with_hc_range(0, 128)
+                                          .with_contended_pages(0b1100);
+        assert_eq!(contention.pattern(), [1, 7, 6, 5, 4, 3, 2, 0]);
+        assert_eq!(contention.hc_range(), (0, 128));
+        assert_eq!(contention.contended_pages(), 0b1100);
+        assert_ne!(contention.pattern(), ULA_CONTENTION_PATTERN);
+        assert!(!contention.is_contended_address(0x4000));
+        assert!(contention.is_contended_address(0x8000));
+        assert!(contention.is_contended_address(0xFFFF));
+        // the first contended T-state of the frame
+        const TS0: FTs = 14336;
+        for (offset, delay) in [(-1, 0), (0, 1), (1, 7), (2, 6), (6, 2), (7, 0), (8, 1), (127, 0), (128, 0)] {
+            let mut clock = TestCounter::from_tstates(TS0 + offset, contention);
+            clock.add_mreq(0x8000);
+            assert_eq!(clock.into_tstates(), TS0 + offset + delay + 3);
+            let mut clock = TestCounter::from_tstates(TS0 + offset, contention);
+            clock.add_mreq(0x4000);
+            assert_eq!(clock.into_tstates(), TS0 + offset + 3);
+        }
+    }
+
+    #[test]
+    fn test_ula_interrupt_length() {
+        use crate::z80emu::{Cpu, Z80NMOS, InterruptMode};
+        use crate::chip::{Breakpoints, DebugBreak};
+        const FRAME_TS: FTs = <TestUla as Video>::VideoFrame::FRAME_TSTATES_COUNT;
+        const IRQ_TS: FTs = 13;
+        // returns the T-state of the interrupt being accepted during the frame
+        fn halt_until_interrupt(interrupt_length: Option<u8>, late_timings: bool) -> Option<FTs> {
+            let mut ula = TestUla::default();
+            assert_eq!(ula.interrupt_length(), Some(DEFAULT_INTERRUPT_LENGTH));
+            if let Some(length) = interrupt_length {
+                assert!(ula.set_interrupt_length(length));
+                assert_eq!(ula.interrupt_length(), Some(length));
+            }
+            ula.set_late_timings(late_timings);
+            let mut cpu = Z80NMOS::default();
+            // LD A,(0x9000); NOP; NOP; NOP; EI; HALT; the interrupt is first sampled at 33
+            ula.memory_mut().load_into(0x8000, &[0x3A, 0x00, 0x90, 0x00, 0x00, 0x00, 0xFB, 0x76]).unwrap();
+            cpu.set_im(InterruptMode::Mode1);
+            cpu.set_pc(0x8000);
+            cpu.set_sp(0xA000);
+            let mut breakpoints = Breakpoints::new();
+            breakpoints.add_breakpoint(0x0038);
+            match ula.run_frame(&mut cpu, &mut breakpoints) {
+                DebugBreak::Breakpoint { pc: 0x0038, remaining } => Some(FRAME_TS - remaining - IRQ_TS),
+                DebugBreak::FrameEnd => None,
+                res => panic!("unexpected break: {:?}", res)
+            }
+        }
+        assert_eq!(halt_until_interrupt(None, false), None);
+        assert_eq!(halt_until_interrupt(Some(33), false), None);
+        assert_eq!(halt_until_interrupt(Some(34), false), Some(33));
+        // the interrupt is requested one T-state earlier
+        assert_eq!(halt_until_interrupt(Some(34), true), None);
+        assert_eq!(halt_until_interrupt(Some(35), true), Some(33));
+        assert_eq!(halt_until_interrupt(Some(36), false), Some(33));
+        assert_eq!(halt_until_interrupt(Some(36), true), Some(33));
+        assert_eq!(halt_until_interrupt(Some(255), false), Some(33));
+        assert_eq!(halt_until_interrupt(Some(0), false), None);
+        // the 128k chipsets hold the interrupt longer
+        use crate::chip::{ula128::Ula128, ula3::Ula3, DEFAULT_INTERRUPT_LENGTH_128};
+        assert_eq!(Ula128::<()>::default().interrupt_length(), Some(DEFAULT_INTERRUPT_LENGTH_128));
+        assert_eq!(Ula3::<()>::default().interrupt_length(), Some(DEFAULT_INTERRUPT_LENGTH_128));
+    }
+
+    #[cfg(feature = "snapshot")]
+    #[test]
+    fn test_ula_interrupt_length_snapshot_default() {
+        use crate::chip::{ula128::Ula128, ula3::Ula3, DEFAULT_INTERRUPT_LENGTH_128};
+        fn restore_without_length<U: Serialize + serde::de::DeserializeOwned>(ula: &U, path: &[&str]) -> U {
+            let mut value = serde_json::to_value(ula).unwrap();
+            let mut obj = &mut value;
+            for key in path {
+                obj = &mut obj[key];
+            }
+            assert!(obj.as_object_mut().unwrap().remove("interruptLength").is_some());
+            serde_json::from_value(value).unwrap()
+        }
+        let mut ula = TestUla::default();
+        ula.set_interrupt_length(40);
+        assert_eq!(restore_without_length(&ula, &[]).interrupt_length(), Some(DEFAULT_INTERRUPT_LENGTH));
+        let mut ula = Ula128::<()>::default();
+        ula.set_interrupt_length(40);
+        assert_eq!(restore_without_length(&ula, &["ula"]).interrupt_length(), Some(DEFAULT_INTERRUPT_LENGTH_128));
+        let mut ula = Ula3::<()>::default();
+        ula.set_interrupt_length(40);
+        assert_eq!(restore_without_length(&ula, &["ula"]).interrupt_length(), Some(DEFAULT_INTERRUPT_LENGTH_128));
+    }
+
+    #[test]
+    fn test_ula_cpu_turbo() {
+        use crate::z80emu::{Cpu, Z80NMOS, StkReg16};
+        use crate::chip::Breakpoints;
+        const FRAME_TS: FTs = <TestUla as Video>::VideoFrame::FRAME_TSTATES_COUNT;
+        let mut ula = TestUla::default();
+        let mut cpu = Z80NMOS::default();
+        // DI; LD HL,0x0000; INC HL; JR -3
+        ula.memory_mut().load_into_mem(0x8000..=0x8006, &[0xF3, 0x21, 0x00, 0x00, 0x23, 0x18, 0xFD][..]).unwrap();
+        let mut run_frame = |ula: &mut TestUla, stepping: bool| {
+            cpu.set_pc(0x8000);
+            if stepping {
+                ula.run_frame(&mut cpu, &mut Breakpoints::new());
+            }
+            else {
+                ula.execute_next_frame(&mut cpu);
+            }
+            assert!(ula.is_frame_over());
+            assert!(ula.current_tstate() - FRAME_TS < 20);
+            cpu.get_reg16(StkReg16::HL)
+        };
+        assert_eq!(ula.cpu_turbo(), Some(1));
+        ula.ensure_next_frame();
+        run_frame(&mut ula, false);
+        let count = run_frame(&mut ula, false);
+        // INC HL: 6, JR: 12
+        assert!(count > (FRAME_TS / 18 - 10) as u16);
+        for multiplier in [0, 3, 5, 16] {
+            assert!(!ula.set_cpu_turbo(multiplier));
+        }
+        assert!(ula.set_cpu_turbo(2));
+        assert_eq!(ula.cpu_turbo(), Some(2));
+        for stepping in [false, true] {
+            let count2 = run_frame(&mut ula, stepping);
+            assert!((i32::from(count2) - 2 * i32::from(count)).abs() <= 2, "{} {}", count2, count);
+        }
+        assert!(ula.set_cpu_turbo(4));
+        let count4 = run_frame(&mut ula, false);
+        assert!((i32::from(count4) - 4 * i32::from(count)).abs() <= 4, "{} {}", count4, count);
+        assert!(ula.set_cpu_turbo(1));
+        assert_eq!(run_frame(&mut ula, false), count);
+    }
+
+    #[cfg(feature = "peripherals")]
+    #[test]
+    fn test_ula_dma() {
+        use crate::z80emu::{Cpu, Z80NMOS};
+        use crate::bus::VFNullDevice;
+        use crate::peripherals::bus::dma::{Z80DmaDatagear, DmaTransferMode};
+        type DmaUla = UlaPAL<Memory64k, Z80DmaDatagear<VFNullDevice<UlaVideoFrame>>>;
+        // DI; LD HL,0x8100; LD BC,0x0D6B; OTIR; HALT
+        const CODE: &[u8] = &[0xF3, 0x21, 0x00, 0x81, 0x01, 0x6B, 0x0D, 0xED, 0xB3, 0x76];
+        // reset; WR0: A->B, port A: 0xC000, length: 0xFF; WR1, WR2: memory, increment;
+        // WR4: port B: 0x5000, mode; load; enable
+        fn new_ula(mode: u8) -> DmaUla {
+            let mut ula = DmaUla::default();
+            ula.memory_mut().load_into(0x8000, CODE).unwrap();
+            ula.memory_mut().load_into(0x8100, &[0xC3, 0x7D, 0x00, 0xC0, 0xFF, 0x00, 0x14, 0x10,
+                                                 mode, 0x00, 0x50, 0xCF, 0x87]).unwrap();
+            for addr in 0xC000..0xC100 {
+                ula.memory_mut().write(addr, addr as u8);
+                ula.memory_mut().write(addr - 0x7000, 0);
+            }
+            ula
+        }
+        fn assert_copied(ula: &DmaUla) {
+            for addr in 0x5000..0x5100 {
+                assert_eq!(ula.memory_ref().read(addr), addr as u8);
+            }
+            assert_eq!(ula.memory_ref().read(0x5100), 0);
+        }
+        // continuous mode takes the bus for the whole block after the enabling OUT
+        let mut ula = new_ula(0xAD);
+        let mut cpu = Z80NMOS::default();
+        cpu.set_pc(0x8000);
+        while cpu.get_pc() != 0x8009 {
+            let _ = ula.execute_single_step(&mut cpu, None::<fn(_)>);
+        }
+        assert_eq!(ula.bus_device_ref().transfer_mode(), DmaTransferMode::Continuous);
+        assert!(ula.bus_device_ref().transfer().is_some());
+        assert_eq!(ula.memory_ref().read(0x5000), 0);
+        let ts = ula.current_tstate();
+        let _ = ula.execute_single_step(&mut cpu, None::<fn(_)>);
+        // 256 * (3 + 3) T-states with the contended writes delayed, then HALT
+        let elapsed = ula.current_tstate() - ts;
+        assert!(elapsed >= 256 * 6 + 4, "{}", elapsed);
+        assert!(elapsed < 256 * 12 + 4, "{}", elapsed);
+        assert!(ula.bus_device_ref().transfer().is_none());
+        assert!(cpu.is_halt());
+        assert_copied(&ula);
+        // the transfer is performed in the frame loop
+        for mode in [0xAD, 0x8D] {
+            let mut ula = new_ula(mode);
+            let mut cpu = Z80NMOS::default();
+            cpu.set_pc(0x8000);
+            ula.execute_next_frame(&mut cpu);
+            assert!(cpu.is_halt());
+            assert!(ula.bus_device_ref().transfer().is_none());
+            assert_copied(&ula);
+        }
+        // the byte mode releases the bus for an instruction after each byte
+        let mut ula = new_ula(0x8D);
+        let mut cpu = Z80NMOS::default();
+        cpu.set_pc(0x8000);
+        while cpu.get_pc() != 0x8009 {
+            let _ = ula.execute_single_step(&mut cpu, None::<fn(_)>);
+        }
+        assert_eq!(ula.bus_device_ref().transfer_mode(), DmaTransferMode::Byte);
+        for count in 1..=3 {
+            let _ = ula.execute_single_step(&mut cpu, None::<fn(_)>);
+            assert_eq!(ula.bus_device_ref().transfer().unwrap().1, count);
+            assert!(cpu.is_halt());
+        }
+    }
+
+    #[test]
+    fn test_ula_run_cycles() {
+        use crate::z80emu::{Cpu, Z80NMOS};
+        const FRAME_TS: FTs = <TestUla as Video>::VideoFrame::FRAME_TSTATES_COUNT;
+        let mut ula = TestUla::default();
+        let mut cpu = Z80NMOS::default();
+        // LD HL,0x9000; INC (HL); JR -3
+        ula.memory_mut().load_into_mem(0x8000..=0x8005, &[0x21, 0x00, 0x90, 0x34, 0x18, 0xFD][..]).unwrap();
+        cpu.set_pc(0x8000);
+        assert_eq!(ula.run_cycles(&mut cpu, 0), 0);
+        let elapsed = ula.run_cycles(&mut cpu, 1000);
+        assert!((1000..1000 + 12).contains(&elapsed));
+        assert_eq!(ula.current_tstate() as u64, elapsed);
+        // across the frame boundary
+        ula.set_frame_tstate(FRAME_TS - 500);
+        let start_ts = ula.current_tstate();
+        let elapsed = ula.run_cycles(&mut cpu, 1000);
+        assert!((1000..1000 + 12).contains(&elapsed));
+        assert_eq!(ula.current_frame(), 1);
+        assert_eq!(ula.current_tstate() as i64, (start_ts - FRAME_TS) as i64 + elapsed as i64);
+        assert!(ula.memory_ref().read(0x9000) > 0);
+    }
+    #[test]
+    fn test_ula_step_instruction() {
+        use crate::z80emu::{Cpu, Z80NMOS};
+        // LD A,1; CALL 0x8010; INC A; CALL 0x8020; ...; 0x8010: ADD A,A; RET; ...; 0x8020: JR -2
+        fn new_ula() -> TestUla {
+            let mut ula = TestUla::default();
+            ula.memory_mut().load_into(0x8000, &[0x3E, 0x01, 0xCD, 0x10, 0x80, 0x3C, 0xCD, 0x20, 0x80]).unwrap();
+            ula.memory_mut().load_into(0x8010, &[0x87, 0xC9]).unwrap();
+            ula.memory_mut().load_into(0x8020, &[0x18, 0xFE]).unwrap();
+            ula
+        }
+        let mut ula = new_ula();
+        let mut cpu = Z80NMOS::default();
+        cpu.set_pc(0x8000);
+        cpu.set_sp(0xA000);
+        for &(pc, ts) in &[(0x8002, 7), (0x8010, 24), (0x8011, 28), (0x8005, 38), (0x8006, 42)] {
+            assert_eq!(ula.step_instruction(&mut cpu), pc);
+            assert_eq!(ula.current_tstate(), ts);
+        }
+        assert_eq!(cpu.get_acc(), 3);
+        let mut ula = new_ula();
+        cpu.set_pc(0x8000);
+        assert_eq!(ula.step_over(&mut cpu, 1000), Some(0x8002));
+        assert_eq!(ula.current_tstate(), 7);
+        assert_eq!(ula.step_over(&mut cpu, 1000), Some(0x8005));
+        assert_eq!(ula.current_tstate(), 38);
+        assert_eq!(cpu.get_acc(), 2);
+        assert_eq!(cpu.get_sp(), 0xA000);
+        assert_eq!(ula.step_over(&mut cpu, 1000), Some(0x8006));
+        // never returns
+        assert_eq!(ula.step_over(&mut cpu, 1000), None);
+        assert_eq!(cpu.get_pc(), 0x8020);
+        assert!((42 + 17 + 1000..42 + 17 + 1000 + 12).contains(&ula.current_tstate()));
+    }
+    #[test]
+    fn test_ula_instruction_trace() {
+        use crate::z80emu::{Cpu, Z80NMOS};
+        const FRAME_TS: FTs = <TestUla as Video>::VideoFrame::FRAME_TSTATES_COUNT;
+        fn entry(pc: u16, code: &[u8], fts: FTs) -> TraceEntry {
+            TraceEntry { pc, code: code.iter().copied().collect(), fts }
+        }
+        let mut ula = TestUla::default();
+        let mut cpu = Z80NMOS::default();
+        // LD A,1; LD IX,0x1234; INC A; NOP; JR -2
+        ula.memory_mut().load_into(0x8000, &[0x3E, 0x01, 0xDD, 0x21, 0x34, 0x12, 0x3C, 0x00, 0x18, 0xFE]).unwrap();
+        cpu.set_pc(0x8000);
+        assert_eq!(ula.instruction_trace_capacity(), None);
+        assert_eq!(ula.instruction_trace(), None);
+        ula.step_instruction(&mut cpu);
+        assert!(ula.set_instruction_trace(Some(8)));
+        assert_eq!(ula.instruction_trace_capacity(), Some(8));
+        assert_eq!(ula.instruction_trace(), Some(vec![]));
+        // the prefix is being executed in a separate step
+        for _ in 0..5 {
+            ula.step_instruction(&mut cpu);
+        }
+        assert_eq!(ula.instruction_trace().unwrap(), [
+            entry(0x8002, &[0xDD, 0x21, 0x34, 0x12], 7),
+            entry(0x8006, &[0x3C], 21),
+            entry(0x8007, &[0x00], 25),
+            entry(0x8008, &[0x18, 0xFE], 29)
+        ]);
+        assert_eq!(cpu.get_acc(), 2);
+        assert_eq!(cpu.get_index16(crate::z80emu::Prefix::Xdd), 0x1234);
+        // the ring buffer keeps only the most recent entries
+        assert!(ula.set_instruction_trace(Some(3)));
+        assert_eq!(ula.instruction_trace(), Some(vec![]));
+        ula.execute_next_frame(&mut cpu);
+        assert!(ula.is_frame_over());
+        let end_ts = ula.current_tstate();
+        // the frame ends 69 T-states before FRAME_TS (at the start of the horizontal range)
+        assert_eq!(end_ts, 41 + (FRAME_TS - 69 - 41 + 11) / 12 * 12);
+        let trace = ula.instruction_trace().unwrap();
+        assert_eq!(trace.len(), 3);
+        for (i, TraceEntry { pc, code, fts }) in trace.into_iter().enumerate() {
+            assert_eq!(pc, 0x8008);
+            assert_eq!(&code[..], &[0x18, 0xFE]);
+            assert_eq!(fts, end_ts - 12 * (3 - i as FTs));
+        }
+        assert!(ula.set_instruction_trace(None));
+        assert_eq!(ula.instruction_trace(), None);
+        ula.execute_next_frame(&mut cpu);
+        assert_eq!(ula.instruction_trace(), None);
+    }
+    #[test]
+    fn test_ula_run_frame_breakpoints() {
+        use crate::z80emu::{Cpu, Z80NMOS};
+        use crate::chip::{Breakpoints, DebugBreak, WatchAccess};
+        const FRAME_TS: FTs = <TestUla as Video>::VideoFrame::FRAME_TSTATES_COUNT;
+        let mut ula = TestUla::default();
+        let mut cpu = Z80NMOS::default();
+        // LD HL,0x9000; INC (HL); LD A,(0x9100); PUSH HL; POP BC; JR -10
+        ula.memory_mut().load_into(0x8000, &[0x21, 0x00, 0x90, 0x34, 0x3A, 0x00, 0x91, 0xE5, 0xC1, 0x18, 0xF8]).unwrap();
+        ula.memory_mut().write(0x9000, 0);
+        cpu.set_pc(0x8000);
+        cpu.set_sp(0xA000);
+        let mut breakpoints = Breakpoints::new();
+        assert!(breakpoints.is_empty());
+        assert!(breakpoints.add_breakpoint(0x8004));
+        assert!(!breakpoints.add_breakpoint(0x8004));
+        assert_eq!(ula.run_frame(&mut cpu, &mut breakpoints), DebugBreak::Breakpoint { pc: 0x8004, remaining: FRAME_TS - 21 });
+        assert_eq!(cpu.get_pc(), 0x8004);
+        assert_eq!(ula.current_tstate(), 21);
+        assert_eq!(ula.memory_ref().read(0x9000), 1);
+        // resumes past the breakpoint
+        assert_eq!(ula.run_frame(&mut cpu, &mut breakpoints), DebugBreak::Breakpoint { pc: 0x8004, remaining: FRAME_TS - 78 });
+        assert_eq!(ula.memory_ref().read(0x9000), 2);
+        assert!(breakpoints.remove_breakpoint(0x8004));
+        breakpoints.add_watchpoint(0x9100, WatchAccess::READ);
+        assert_eq!(ula.run_frame(&mut cpu, &mut breakpoints), DebugBreak::Watchpoint {
+            pc: 0x8004, address: 0x9100, access: WatchAccess::READ, remaining: FRAME_TS - 91 });
+        breakpoints.add_watchpoint(0x9000, WatchAccess::WRITE);
+        assert_eq!(ula.run_frame(&mut cpu, &mut breakpoints), DebugBreak::Watchpoint {
+            pc: 0x8003, address: 0x9000, access: WatchAccess::WRITE, remaining: FRAME_TS - 135 });
+        assert_eq!(breakpoints.remove_watchpoint(0x9000), Some(WatchAccess::WRITE));
+        assert_eq!(breakpoints.remove_watchpoint(0x9100), Some(WatchAccess::READ));
+        breakpoints.add_watchpoint(0x9FFF, WatchAccess::READ_WRITE);
+        assert_eq!(ula.run_frame(&mut cpu, &mut breakpoints), DebugBreak::Watchpoint {
+            pc: 0x8007, address: 0x9FFF, access: WatchAccess::WRITE, remaining: FRAME_TS - 159 });
+        assert_eq!(ula.run_frame(&mut cpu, &mut breakpoints), DebugBreak::Watchpoint {
+            pc: 0x8008, address: 0x9FFF, access: WatchAccess::READ, remaining: FRAME_TS - 169 });
+        breakpoints.clear();
+        assert_eq!(ula.run_frame(&mut cpu, &mut breakpoints), DebugBreak::FrameEnd);
+        assert!(ula.is_frame_over());
+        assert_eq!(ula.current_frame(), 0);
+        // a breakpoint at the starting PC is hit unless resuming from it
+        breakpoints.add_breakpoint(0x8004);
+        cpu.set_pc(0x8004);
+        let ts = match ula.run_frame(&mut cpu, &mut breakpoints) {
+            DebugBreak::Breakpoint { pc: 0x8004, remaining } => FRAME_TS - remaining,
+            res => panic!("unexpected break: {:?}", res)
+        };
+        assert_eq!(ula.current_frame(), 1);
+        assert_eq!(ula.current_tstate(), ts);
+        assert_eq!(cpu.get_pc(), 0x8004);
+        // the breakpoints cleared in the meantime don't resume from it
+        let mut other = breakpoints.clone();
+        assert_eq!(other, breakpoints);
+        other.clear();
+        other.add_breakpoint(0x8004);
+        assert_eq!(ula.run_frame(&mut cpu, &mut other), DebugBreak::Breakpoint { pc: 0x8004, remaining: FRAME_TS - ts });
+        assert_eq!(ula.run_frame(&mut cpu, &mut breakpoints), DebugBreak::Breakpoint { pc: 0x8004, remaining: FRAME_TS - ts - 57 });
+    }
+    #[test]
+    fn test_ula_floating_bus() {
+        use crate::z80emu::Io;
+        use crate::clock::VideoTs;
+        let mut ula = TestUla::default();
+        // the cell at column 2 of the pixel line 8
+        ula.memory_mut().write(0x4022, 0b1010_0101);
+        ula.memory_mut().write(0x4023, 0b0101_1010);
+        ula.memory_mut().write(0x5822, 0x38);
+        ula.memory_mut().write(0x5823, 0x47);
+        assert_eq!(ula.has_floating_bus(), Some(true));
+        let vc = <TestUla as Video>::VideoFrame::VSL_PIXELS.start + 8;
+        let items = [(7, 0xFF), (8, 0b1010_0101), (9, 0x38), (10, 0b0101_1010), (11, 0x47),
+                     (12, 0xFF), (15, 0xFF)];
+        for (hc, data) in items.iter().copied() {
+            assert_eq!(ula.read_io(0x00FF, VideoTs::new(vc, hc)).0, data);
+        }
+        // the border area
+        assert_eq!(ula.read_io(0x00FF, VideoTs::new(vc - 9, 9)).0, 0xFF);
+        assert!(ula.set_floating_bus(false));
+        assert_eq!(ula.has_floating_bus(), Some(false));
+        for (hc, _) in items.iter().copied() {
+            assert_eq!(ula.read_io(0x00FF, VideoTs::new(vc, hc)).0, 0xFF);
+        }
+    }
+    #[test]
+    fn test_ula_read_ear_mode() {
+        use crate::z80emu::Io;
+        use crate::clock::VideoTs;
+        use core::num::NonZeroU32;
+        use crate::chip::{EarIn, ReadEarMode};
+        // OUT (0xFE) value, bit 6 of IN (0xFE): Issue 3, Issue 2, Clear, Set
+        let table = [(0b0000_0000, [0, 0, 0, 1]),
+                     (0b0000_1000, [0, 1, 0, 1]),
+                     (0b0001_0000, [1, 1, 0, 1]),
+                     (0b0001_1000, [1, 1, 0, 1]),
+                     (0b1110_0111, [0, 0, 0, 1]),
+                     (0b1110_1111, [0, 1, 0, 1])];
+        let modes = [ReadEarMode::Issue3, ReadEarMode::Issue2, ReadEarMode::Clear, ReadEarMode::Set];
+        let mut ula = TestUla::default();
+        assert_eq!(ula.read_ear_mode(), ReadEarMode::Issue3);
+        for (mode_index, mode) in modes.iter().copied().enumerate() {
+            ula.set_read_ear_mode(mode);
+            for (hc, (out, bits)) in table.iter().copied().enumerate() {
+                let ts = VideoTs::new(10, hc as i16 * 20);
+                ula.write_io(0xFE, out, ts);
+                let data = ula.read_io(0xFE, VideoTs::new(10, hc as i16 * 20 + 10)).0;
+                assert_eq!((data >> 6) & 1, bits[mode_index], "{:?} OUT {:08b}", mode, out);
+                // the remaining bits aren't affected without keys being pressed
+                assert_eq!(data | 0b0100_0000, 0xFF);
+            }
+        }
+        // the EAR input overrides the read mode
+        ula.set_read_ear_mode(ReadEarMode::Set);
+        ula.write_io(0xFE, 0b0001_1000, VideoTs::new(0, 0));
+        ula.feed_ear_in(core::iter::once(NonZeroU32::new(100).unwrap()), Some(1));
+        assert_eq!(ula.read_io(0xFE, VideoTs::new(0, 10)).0 & 0b0100_0000, 0);
+        assert_eq!(ula.read_io(0xFE, VideoTs::new(1, 0)).0 & 0b0100_0000, 0b0100_0000);
+    }
+    #[test]
+    fn test_ula_timing_variant() {
+        use crate::z80emu::{Cpu, Z80NMOS, CpuDebug, InterruptMode, host::cycles::M1_CYCLE_TS};
+        use crate::clock::{VideoTs, Ts};
+        use crate::chip::UlaTimingVariant;
+        // returns the frame T-states of entering the interrupt routine and of finishing the loop
+        fn run_contended_io_loop(variant: UlaTimingVariant, hc: Ts) -> (FTs, FTs) {
+            let mut ula = TestUla::default();
+            ula.set_timing_variant(variant);
+            // JP 0x8100
+            ula.memory_mut().load_into_mem(0x0038..=0x003A, &[0xC3, 0x00, 0x81][..]).unwrap();
+            // EI; HALT
+            ula.memory_mut().load_into_mem(0x8000..=0x8001, &[0xFB, 0x76][..]).unwrap();
+            // LD BC,0x40FE; LD DE,600; IN A,(C); DEC DE; LD A,D; OR E; JR NZ,-7; HALT
+            ula.memory_mut().load_into_mem(0x8100..=0x810D, &[
+                0x01, 0xFE, 0x40, 0x11, 0x58, 0x02,
+                0xED, 0x78, 0x1B, 0x7A, 0xB3, 0x20, 0xF9, 0x76][..]).unwrap();
+            let mut cpu = Z80NMOS::default();
+            cpu.set_im(InterruptMode::Mode1);
+            cpu.set_pc(0x8000);
+            ula.set_video_ts(VideoTs::new(0, hc));
+            let mut irq_ts = None;
+            while cpu.get_pc() != 0x810D {
+                let _ = ula.execute_single_step(&mut cpu, None::<fn(CpuDebug)>);
+                if irq_ts.is_none() && cpu.get_pc() == 0x8100 {
+                    irq_ts = Some(ula.current_tstate());
+                }
+            }
+            (irq_ts.unwrap(), ula.current_tstate())
+        }
+
+        let mut ula = TestUla::default();
+        assert_eq!(ula.timing_variant(), UlaTimingVariant::Early);
+        ula.set_timing_variant(UlaTimingVariant::Late);
+        assert!(ula.has_late_timings());
+        assert_eq!(ula.timing_variant(), UlaTimingVariant::Late);
+        ula.set_late_timings(false);
+        assert_eq!(ula.timing_variant(), UlaTimingVariant::Early);
+        // the halted CPU probes the interrupt at the end of each M1 cycle, here at hc = -1, 3, ...
+        let (early_irq, early_end) = run_contended_io_loop(UlaTimingVariant::Early, -37);
+        let (late_irq, late_end) = run_contended_io_loop(UlaTimingVariant::Late, -37);
+        // the late variant requests the interrupt one T-state earlier, at hc = -1
+        assert_eq!(early_irq - late_irq, M1_CYCLE_TS as FTs);
+        // the contended I/O synchronizes the loop with the ULA
+        assert_eq!(early_end, late_end);
+        assert!(early_end > <TestUla as Video>::VideoFrame::vc_hc_to_tstates(
+                                <TestUla as Video>::VideoFrame::VSL_PIXELS.start, 0));
+        // so the loop of the late variant takes longer
+        assert_eq!((late_end - late_irq) - (early_end - early_irq), M1_CYCLE_TS as FTs);
+        // with the M1 cycles ending at hc = 0, 4, ... both variants accept the interrupt at once
+        assert_eq!(run_contended_io_loop(UlaTimingVariant::Early, -36),
+                   run_contended_io_loop(UlaTimingVariant::Late, -36));
+    }
+    #[test]
+    fn test_ula_r_register_bit7() {
+        use crate::z80emu::{Cpu, Z80NMOS, CpuDebug};
+        let mut ula = TestUla::default();
+        let mut cpu = Z80NMOS::default();
+        // LD A,0xFD; LD R,A; NOP x 4; LD A,R; HALT
+        ula.memory_mut().load_into_mem(0x8000..=0x800A,
+            &[0x3E, 0xFD, 0xED, 0x4F, 0x00, 0x00, 0x00, 0x00, 0xED, 0x5F, 0x76][..]).unwrap();
+        cpu.set_pc(0x8000);
+        for _ in 0..2 {
+            ula.execute_single_step(&mut cpu, None::<fn(CpuDebug)>).unwrap();
+        }
+        assert_eq!(cpu.get_r(), 0xFD);
+        for _ in 0..5 {
+            ula.execute_single_step(&mut cpu, None::<fn(CpuDebug)>).unwrap();
+        }
+        // only the lower 7 bits are incremented and wrap around
+        assert_eq!(cpu.get_acc(), 0x83);
+        assert_eq!(cpu.get_r(), 0x83);
+        // HALT until the end of the frame
+        ula.execute_next_frame(&mut cpu);
+        assert!(cpu.is_halt());
+        assert_eq!(cpu.get_r() & 0x80, 0x80);
+        // a cleared bit 7 stays cleared
+        cpu.set_r(0x7F);
+        cpu.inc_r();
+        assert_eq!(cpu.get_r(), 0x00);
+    }
+    #[test]
+    fn test_ula_earmic_out_at_frame_end() {
+        use crate::z80emu::{Cpu, Z80NMOS, CpuDebug};
+        use crate::audio::{Blep, AmpLevels, AudioFrame, EarMicOutAudioFrame};
+        use crate::video::VideoFrame;
+        use crate::chip::{ControlUnit, FrameState, MemoryAccess};
+        use crate::memory::ZxMemory;
+
+        #[derive(Default)]
+        struct TestBlep(Vec<(FTs, i32)>);
+        impl Blep for TestBlep {
+            type SampleDelta = i32;
+            fn ensure_frame_time(&mut self, _: u32, _: f64, _: FTs, _: FTs) {}
+            fn end_frame(&mut self, _timestamp: FTs) -> usize { 0 }
+            fn add_step(&mut self, _channel: usize, timestamp: FTs, delta: i32) {
+                self.0.push((timestamp, delta));
+            }
+        }
+        struct TestAmps;
+        impl AmpLevels<i32> for TestAmps {
+            fn amp_level(level: u32) -> i32 { level as i32 }
+        }
+
+        type V = <TestUla as Video>::VideoFrame;
+        // the frame is over when the vertical counter reaches VSL_COUNT
+        const FRAME_OVER_TS: FTs = V::FRAME_TSTATES_COUNT + V::HTS_RANGE.start as FTs;
+        let mut ula = TestUla::default();
+        let mut cpu = Z80NMOS::default();
+        // LD A,0x10; OUT (0xFE),A; OUT (0xFE),A
+        ula.memory_mut().load_into_mem(0x0000..=0x0005, &[0x3E, 0x10, 0xD3, 0xFE, 0xD3, 0xFE][..]).unwrap();
+        ula.execute_single_step(&mut cpu, None::<fn(CpuDebug)>).unwrap();
+        // the last instruction of the frame, starting at the last T-state before the frame is over
+        ula.set_frame_tstate(FRAME_OVER_TS - 1);
+        assert!(!ula.is_frame_over());
+        ula.execute_single_step(&mut cpu, None::<fn(CpuDebug)>).unwrap();
+        assert!(ula.is_frame_over());
+        let end_ts = AudioFrame::<TestBlep>::get_audio_frame_end_time(&ula);
+        assert_eq!(FRAME_OVER_TS + 10, end_ts);
+        let mut blep = TestBlep::default();
+        ula.render_earmic_out_audio_frame::<TestAmps>(&mut blep, 0);
+        // OUT (n),A writes to the port at its 8th T-state
+        assert_eq!(vec![(FRAME_OVER_TS + 7, 2)], blep.0);
+        assert!(blep.0[0].0 < end_ts);
+        // the step doesn't leak into the next frame
+        ula.ensure_next_frame();
+        blep.0.clear();
+        ula.render_earmic_out_audio_frame::<TestAmps>(&mut blep, 0);
+        assert!(blep.0.is_empty());
+        // the next frame continues from the last EAR/MIC state
+        cpu.set_acc(0);
+        ula.execute_single_step(&mut cpu, None::<fn(CpuDebug)>).unwrap();
+        ula.render_earmic_out_audio_frame::<TestAmps>(&mut blep, 0);
+        assert_eq!(vec![(ula.current_tstate() - 3, -2)], blep.0);
+    }
+
+    #[test]
+    fn test_ula_tape_audio_gain() {
+        use crate::z80emu::Io;
+        use crate::clock::VideoTs;
+        use crate::audio::{Blep, AmpLevels, EarMicOutAudioFrame, EarInAudioFrame};
+        use crate::chip::EarIn;
+        type V = <TestUla as Video>::VideoFrame;
+
+        #[derive(Default)]
+        struct TestBlep(Vec<(FTs, f32)>);
+        impl Blep for TestBlep {
+            type SampleDelta = f32;
+            fn ensure_frame_time(&mut self, _: u32, _: f64, _: FTs, _: FTs) {}
+            fn end_frame(&mut self, _timestamp: FTs) -> usize { 0 }
+            fn add_step(&mut self, _channel: usize, timestamp: FTs, delta: f32) {
+                self.0.push((timestamp, delta));
+            }
+        }
+        // EAR: 1.0, MIC: 0.25
+        struct TestAmps;
+        impl AmpLevels<f32> for TestAmps {
+            fn amp_level(level: u32) -> f32 { (level >> 1) as f32 + (level & 1) as f32 * 0.25 }
+        }
+        // collects the sum of deltas at each timestamp
+        fn steps(mut blep: TestBlep) -> Vec<(FTs, f32)> {
+            blep.0.sort_by_key(|&(ts, _)| ts);
+            let mut steps: Vec<(FTs, f32)> = Vec::new();
+            for (ts, delta) in blep.0 {
+                match steps.last_mut() {
+                    Some((last_ts, sum)) if *last_ts == ts => *sum += delta,
+                    _ => steps.push((ts, delta))
+                }
+            }
+            steps
+        }
+        let vts = |ts: FTs| -> VideoTs { VFrameTs::<V>::from_tstates(ts).into() };
+        let render = |gain: f32| {
+            let mut ula = TestUla::default();
+            assert_eq!(ula.tape_audio_gain(), Some(1.0));
+            assert!(ula.set_tape_audio_gain(gain));
+            assert_eq!(ula.tape_audio_gain(), Some(gain));
+            // EAR on, MIC on, EAR off, MIC off
+            for (ts, data) in [(1000, 0x10), (2000, 0x18), (3000, 0x08), (4000, 0x00)] {
+                ula.write_io(0xFE, data, vts(ts));
+            }
+            // a tape pulse
+            ula.set_ear_in(true, 5000);
+            ula.set_ear_in(false, 1000);
+            ula.tsc = VFrameTs::EOF;
+            let mut earmic = TestBlep::default();
+            ula.render_earmic_out_audio_frame::<TestAmps>(&mut earmic, 0);
+            let mut ear_in = TestBlep::default();
+            ula.render_ear_in_audio_frame::<TestAmps>(&mut ear_in, 0);
+            (steps(earmic), steps(ear_in))
+        };
+        let (earmic, ear_in) = render(1.0);
+        assert_eq!(earmic, [(1000, 1.0), (2000, 0.25), (3000, -1.0), (4000, -0.25)]);
+        assert_eq!(ear_in, [(5000, 0.25), (6000, -0.25)]);
+        // only the tape signals are attenuated, the beeper is not
+        let (earmic, ear_in) = render(0.5);
+        assert_eq!(earmic, [(1000, 1.0), (2000, 0.125), (3000, -1.0), (4000, -0.125)]);
+        assert_eq!(ear_in, [(5000, 0.125), (6000, -0.125)]);
+        // the gain is clamped
+        let mut ula = TestUla::default();
+        assert!(ula.set_tape_audio_gain(2.0));
+        assert_eq!(ula.tape_audio_gain(), Some(1.0));
+        assert!(ula.set_tape_audio_gain(-1.0));
+        assert_eq!(ula.tape_audio_gain(), Some(0.0));
+    }
+
+    #[test]
+    fn test_ula_ear_mic_loopback() {
+        use crate::z80emu::Io;
+        use crate::clock::VideoTs;
+        use crate::video::VideoFrame;
+        type V = <TestUla as Video>::VideoFrame;
+        const FRAME_TS: FTs = V::FRAME_TSTATES_COUNT;
+        let data = [0x13u8, 0x37, 0xA5];
+        // the MIC out edge intervals: a pilot tone, sync pulses and two pulses per each bit
+        let mut pulses = vec![2168; 64];
+        pulses.extend([667, 735]);
+        for byte in data.iter() {
+            for bit in (0..8).rev() {
+                let pulse = if byte & (1 << bit) != 0 { 1710 } else { 855 };
+                pulses.extend([pulse, pulse]);
+            }
+        }
+        let vts = |ts: FTs| -> VideoTs { VFrameTs::<V>::from_tstates(ts).into() };
+        let next_frame = |ula: &mut TestUla| {
+            ula.tsc = VFrameTs::EOF;
+            ula.ensure_next_frame();
+        };
+        let mut ula = TestUla::default();
+        assert_eq!(ula.has_ear_mic_loopback(), Some(false));
+        assert!(ula.set_ear_mic_loopback(true));
+        assert_eq!(ula.has_ear_mic_loopback(), Some(true));
+        // SAVE
+        let mut ts: FTs = 1000;
+        let mut mic = 0;
+        for pulse in pulses.iter() {
+            ts += pulse;
+            while ts >= FRAME_TS {
+                next_frame(&mut ula);
+                ts -= FRAME_TS;
+            }
+            mic ^= 0b0000_1000;
+            ula.write_io(0xFE, mic, vts(ts));
+        }
+        // a few idle frames
+        for _ in 0..3 {
+            next_frame(&mut ula);
+        }
+        // LOAD: collect the times of the EAR in edges
+        let mut edges = Vec::new();
+        let mut ear = None;
+        for frame in 0..10 {
+            for ts in (0..FRAME_TS).step_by(20) {
+                let bit = ula.read_io(0xFE, vts(ts)).0 & 0b0100_0000;
+                if matches!(ear, Some(ear) if ear != bit) {
+                    edges.push(frame * FRAME_TS + ts);
+                }
+                ear = Some(bit);
+            }
+            next_frame(&mut ula);
+        }
+        let intervals: Vec<FTs> = edges.windows(2).map(|w| w[1] - w[0]).collect();
+        // skip the pilot tone and the sync pulses
+        let start = intervals.iter().rposition(|&pulse| pulse > 2000).unwrap() + 3;
+        let bits: Vec<bool> = intervals[start..].chunks(2)
+                                .map(|pair| {
+                                    assert_eq!(pair.len(), 2);
+                                    assert!((pair[0] - pair[1]).abs() <= 40);
+                                    pair[0] > 1280
+                                }).collect();
+        let loaded: Vec<u8> = bits.chunks(8)
+                                  .map(|bits| bits.iter().fold(0, |byte, &bit| byte << 1 | bit as u8))
+                                  .collect();
+        assert_eq!(loaded, data);
+        assert!(ula.set_ear_mic_loopback(false));
+        assert_eq!(ula.has_ear_mic_loopback(), Some(false));
+    }
+
+    #[cfg(feature = "audio")]
+    #[test]
+    fn test_ula_border_port_audio_steps() {
+        use crate::z80emu::{Z80NMOS, CpuDebug};
+        use crate::audio::{AudioFrame, EarMicOutAudioFrame, EarOutAmps4, synth::BandLimited};
+        use crate::chip::{ControlUnit, FrameState, MemoryAccess, HostConfig, ZxSpectrumPALConfig};
+        use crate::memory::ZxMemory;
+
+        const SAMPLE_RATE: u32 = 44100;
+        // the writes in the top and the bottom border, so they are not delayed by the I/O contention
+        const WRITES: [FTs; 2] = [3_500, 63_000];
+        let mut ula = TestUla::default();
+        let mut cpu = Z80NMOS::default();
+        // LD A,0x17; OUT (0xFE),A; LD A,0x02; OUT (0xFE),A; HALT
+        ula.memory_mut().load_into_mem(0x0000..=0x0008,
+            &[0x3E, 0x17, 0xD3, 0xFE, 0x3E, 0x02, 0xD3, 0xFE, 0x76][..]).unwrap();
+        for &ts in WRITES.iter() {
+            ula.execute_single_step(&mut cpu, None::<fn(CpuDebug)>).unwrap();
+            // OUT (n),A writes to the port at its 8th T-state
+            ula.set_frame_tstate(ts - 7);
+            ula.execute_single_step(&mut cpu, None::<fn(CpuDebug)>).unwrap();
+        }
+        ula.execute_next_frame(&mut cpu);
+        assert!(ula.is_frame_over());
+        let mut blep = BandLimited::<f32>::new(1);
+        let cpu_hz = ZxSpectrumPALConfig::effective_cpu_rate(1.0);
+        AudioFrame::<BandLimited<f32>>::ensure_audio_frame_time(&ula, &mut blep, SAMPLE_RATE, cpu_hz);
+        ula.render_earmic_out_audio_frame::<EarOutAmps4<f32>>(&mut blep, 0);
+        let nsamples = ula.end_audio_frame(&mut blep);
+        let samples: Vec<f32> = blep.sum_iter(0).collect();
+        assert_eq!(samples.len(), nsamples);
+        // the band-limited step is centered half of its kernel width (24 samples) after its time
+        const STEP_DELAY: f64 = 12.0;
+        let mut crossings = Vec::new();
+        // the EAR output goes high at the first write and low at the second one
+        for (&ts, rising) in WRITES.iter().zip([true, false]) {
+            let offset = ts as f64 * SAMPLE_RATE as f64 / ZxSpectrumPALConfig::CPU_HZ as f64 + STEP_DELAY;
+            let index = offset.round() as usize;
+            let (before, after) = (samples[index - 8], samples[index + 8]);
+            assert_eq!(after > before, rising);
+            let middle = (before + after) / 2.0;
+            let crossing = (index - 8..index + 8).find(|&i| (samples[i] > middle) == rising).unwrap();
+            assert!((crossing as f64 - offset).abs() <= 1.0);
+            crossings.push(crossing);
+        }
+        let distance = (WRITES[1] - WRITES[0]) as f64 * SAMPLE_RATE as f64 / ZxSpectrumPALConfig::CPU_HZ as f64;
+        assert!((((crossings[1] - crossings[0]) as f64) - distance).abs() <= 1.0);
+    }
+
+    #[cfg(feature = "peripherals")]
+    #[test]
+    fn test_ula_memory_extension_uspeech() {
+        use crate::z80emu::Memory;
+        use crate::clock::VideoTs;
+        use crate::memory::{Memory48kEx, ZxMemory};
+        use crate::bus::VFNullDevice;
+        use crate::peripherals::memory::CurrahMicroSpeechMemExt;
+        let mut ula = UlaPAL::<Memory48kEx, VFNullDevice<UlaVideoFrame>, CurrahMicroSpeechMemExt>::default();
+        ula.memory_ext_mut().load_uspeech_rom(&[0xAAu8;0x800][..]).unwrap();
+        let ts = VideoTs::new(0, 0);
+        // not paged in, writes go to the memory
+        ula.write_mem(0x1000, 24, ts);
+        assert_eq!(false, ula.memory_ext_ref().speech_ref().is_speaking(0));
+        assert_eq!(0xFF, ula.read_mem(0x1000, ts));
+        assert_eq!(0xFF, ula.read_opcode(0x0038, 0, ts));
+        assert_eq!(0xAA, ula.read_mem(0x0038, ts));
+        ula.write_mem(0x1000, 24, VideoTs::new(0, 100));
+        ula.write_mem(0x1000, 0, VideoTs::new(0, 200));
+        assert_eq!(0xAB, ula.read_mem(0x1000, VideoTs::new(0, 200)));
+        {
+            let speech = ula.memory_ext_ref().speech_ref();
+            assert_eq!(true, speech.is_speaking(100));
+            assert_eq!(false, speech.is_speaking(99));
+        }
+        assert_eq!(0xAA, ula.read_opcode(0x0038, 0, ts));
+        assert_eq!(0xFF, ula.read_mem(0x0038, ts));
+        assert_eq!(0xFF, ula.read_mem(0x1000, VideoTs::new(0, 200)));
+        assert_eq!(0xFF, ula.memory_ref().read(0x1000));
+    }
+
+    #[cfg(feature = "peripherals")]
+    #[test]
+    fn test_ula_memory_extension_divide() {
+        use crate::z80emu::Memory;
+        use crate::clock::VideoTs;
+        use crate::memory::Memory48kEx;
+        use crate::bus::VFNullDevice;
+        use crate::peripherals::memory::*;
+        let mut ula = UlaPAL::<Memory48kEx, VFNullDevice<UlaVideoFrame>, DivIdeMemExt>::default();
+        let ts = VideoTs::new(0, 0);
+        // no automatic paging without the EEPROM
+        assert_eq!(0xFF, ula.read_opcode(0x0008, 0, ts));
+        assert!(!ula.memory_ext_ref().is_paged_in());
+        let eeprom: Vec<u8> = (0..DIVIDE_EEPROM_SIZE).map(|x| x as u8).collect();
+        ula.memory_ext_mut().load_eeprom(&eeprom[..]).unwrap();
+        // the entry point is fetched from the ROM and the memory is paged in after the fetch
+        assert_eq!(0xFF, ula.read_opcode(0x0008, 0, ts));
+        assert!(ula.memory_ext_ref().is_paged_in());
+        assert_eq!(0x09, ula.read_mem(0x0009, ts));
+        assert_eq!(0x1110, ula.read_mem16(0x0010, ts));
+        ula.write_mem(0x2000, 42, ts);
+        assert_eq!(42, ula.read_mem(0x2000, ts));
+        assert_eq!(42, ula.memory_ext_ref().ram_ref()[0]);
+        // the EEPROM is write-protected
+        ula.write_mem(0x0001, 42, ts);
+        assert_eq!(0x01, ula.read_mem(0x0001, ts));
+        // the bank selection
+        let control = ula.memory_ext_ref().control().clone();
+        control.write_control(2);
+        ula.write_mem(0x2001, 43, ts);
+        assert_eq!(43, ula.memory_ext_ref().ram_ref()[0x4001]);
+        // the exit area is fetched from the EEPROM and the memory is paged out after the fetch
+        assert_eq!(0xF8, ula.read_opcode(0x1FF8, 0, ts));
+        assert!(!ula.memory_ext_ref().is_paged_in());
+        assert_eq!(0xFF, ula.read_mem(0x2001, ts));
+        // the instant paging
+        assert_eq!(0x00, ula.read_opcode(0x3D00, 0, ts));
+        assert!(control.is_automap());
+        control.set_automap(false);
+        // the MAPRAM mode replaces the EEPROM with the write-protected RAM bank 3
+        control.write_control(DIVIDE_MAPRAM|3);
+        assert!(!ula.memory_ext_ref().is_paged_in());
+        control.write_control(DIVIDE_CONMEM|3);
+        assert_eq!(control.control(), DIVIDE_CONMEM|DIVIDE_MAPRAM|3);
+        ula.write_mem(0x2002, 44, ts);
+        assert_eq!(0x02, ula.read_mem(0x0002, ts));
+        control.write_control(3);
+        assert_eq!(control.control(), DIVIDE_MAPRAM|3);
+        control.set_automap(true);
+        assert_eq!(44, ula.read_mem(0x0002, ts));
+        ula.write_mem(0x2002, 45, ts);
+        assert_eq!(44, ula.read_mem(0x2002, ts));
+        control.reset();
+        assert_eq!(control.paging(), DivIdePaging::default());
+        assert_eq!(0xFF, ula.read_mem(0x0002, ts));
+    }
+
+    #[test]
+    fn test_ula_memory_extension_divide_eeprom_persists() {
+        use crate::clock::VideoTs;
+        use crate::memory::Memory48kEx;
+        use crate::bus::VFNullDevice;
+        use crate::peripherals::memory::*;
+        let mut ula = UlaPAL::<Memory48kEx, VFNullDevice<UlaVideoFrame>, DivIdeMemExt>::default();
+        let ts = VideoTs::new(0, 0);
+        let eeprom: Vec<u8> = (0..DIVIDE_EEPROM_SIZE).map(|x| x as u8).collect();
+        ula.memory_ext_mut().load_eeprom(&eeprom[..]).unwrap();
+        let shared = ula.memory_ext_ref().eeprom().clone();
+        let control = ula.memory_ext_ref().control().clone();
+        control.write_control(DIVIDE_CONMEM);
+        // the EEPROM is write-protected by default
+        ula.write_mem(0x0001, 42, ts);
+        assert_eq!(0x01, ula.read_mem(0x0001, ts));
+        ula.memory_ext_mut().set_eeprom_write_enabled(true);
+        assert!(ula.memory_ext_ref().is_eeprom_write_enabled());
+        ula.write_mem(0x0001, 42, ts);
+        ula.write_mem(0x1FFF, 43, ts);
+        assert_eq!(42, ula.read_mem(0x0001, ts));
+        assert_eq!(43, ula.read_mem(0x1FFF, ts));
+        // the EEPROM is only writable while paged in with CONMEM
+        control.write_control(0);
+        control.set_automap(true);
+        ula.write_mem(0x0002, 44, ts);
+        assert_eq!(0x02, ula.read_mem(0x0002, ts));
+        // the shared copy is left intact
+        assert_eq!(0x01, shared[1]);
+        let mut saved = Vec::new();
+        ula.memory_ext_ref().save_eeprom(&mut saved).unwrap();
+        assert_eq!(DIVIDE_EEPROM_SIZE, saved.len());
+        assert_eq!(&[0x00, 42, 0x02], &saved[..3]);
+        assert_eq!(43, saved[DIVIDE_EEPROM_SIZE - 1]);
+        let mut divide = DivIdeMemExt::default();
+        divide.load_eeprom(&saved[..]).unwrap();
+        assert_eq!(ula.memory_ext_ref().eeprom(), divide.eeprom());
+        // the EEPROM content is a part of the serialized state
+        let json = serde_json::to_string(ula.memory_ext_ref()).unwrap();
+        let restored: DivIdeMemExt = serde_json::from_str(&json).unwrap();
+        assert_eq!(ula.memory_ext_ref().eeprom(), restored.eeprom());
+        assert!(restored.is_eeprom_write_enabled());
+        assert_eq!(control.paging(), restored.control().paging());
+        let json = serde_json::to_string(&ula).unwrap();
+        let restored: UlaPAL::<Memory48kEx, VFNullDevice<UlaVideoFrame>, DivIdeMemExt> = serde_json::from_str(&json).unwrap();
+        assert_eq!(42, restored.read_mem(0x0001, ts));
+        assert_eq!(43, restored.memory_ext_ref().eeprom()[DIVIDE_EEPROM_SIZE - 1]);
+    }
+
+    #[test]
+    fn test_ula_attr_clash_reduction() {
+        use crate::z80emu::Memory;
+        use crate::clock::VideoTs;
+        use crate::video::frame_cache::VideoFrameDataIterator;
+        use frame_cache::UlaFrameProducer;
+        // returns attributes of the first column of the first attribute row as rendered
+        fn render_attrs(reduce_clash: bool) -> Vec<u8> {
+            let mut ula = TestUla::default();
+            assert_eq!(ula.has_attr_clash_reduction(), Some(false));
+            assert!(ula.set_attr_clash_reduction(reduce_clash));
+            assert_eq!(ula.has_attr_clash_reduction(), Some(reduce_clash));
+            // change the attribute on each line just after the beam passed the cell
+            for line in 0..8 {
+                let ts = VideoTs::new(UlaVideoFrame::VSL_PIXELS.start + line, 10);
+                ula.write_mem(0x5800, 0x40 | (line as u8 + 1) << 3, ts);
+            }
+            let screen = ula.memory.screen_ref(0).unwrap();
+            let mut producer = UlaFrameProducer::new(screen, &ula.frame_cache);
+            (0..8).map(|_| {
+                let (_, attr) = producer.next().unwrap();
+                producer.next_line();
+                attr
+            }).collect()
+        }
+        let written: Vec<u8> = (1..=8).map(|n| 0x40 | n << 3).collect();
+        let count_written = |attrs: &[u8]| attrs.iter().filter(|a| written.contains(a)).count();
+        let standard = render_attrs(false);
+        assert_eq!(standard[0], 0);
+        assert_eq!(&standard[1..], &written[..7]);
+        let enhanced = render_attrs(true);
+        assert_eq!(enhanced, written);
+        assert!(count_written(&enhanced) > count_written(&standard));
+    }
+
+    #[test]
+    fn test_ula_snow_effect() {
+        use crate::z80emu::Memory;
+        use crate::clock::VideoTs;
+        use frame_cache::UlaFrameProducer;
+        // returns the bitmap and the attribute of the first screen cell as rendered
+        fn render_cell(snow_effect: bool, ir: u16) -> (u8, u8) {
+            let mut ula = TestUla::default();
+            assert_eq!(ula.has_snow_effect(), Some(true));
+            assert!(ula.set_snow_effect(snow_effect));
+            assert_eq!(ula.has_snow_effect(), Some(snow_effect));
+            ula.memory.write(0x4000, 0xAA);
+            ula.memory.write(0x4021, 0x55);
+            ula.memory.write(0x5800, 0x38);
+            ula.memory.write(0x5821, 0x47);
+            // the opcode fetch while the ULA fetches the first cell of the screen
+            let ts = VideoTs::new(UlaVideoFrame::VSL_PIXELS.start, 2);
+            ula.read_opcode(0x8000, ir, ts);
+            let screen = ula.memory.screen_ref(0).unwrap();
+            let mut producer = UlaFrameProducer::new(screen, &ula.frame_cache);
+            producer.next().unwrap()
+        }
+        // I points to the contended memory
+        assert_eq!(render_cell(true, 0x4021), (0x55, 0x47));
+        assert_eq!(render_cell(false, 0x4021), (0xAA, 0x38));
+        // I points to the uncontended memory
+        assert_eq!(render_cell(true, 0x8021), (0xAA, 0x38));
+    }
+
+    #[test]
+    fn test_ula_dirty_lines() {
+        use crate::z80emu::Memory;
+        use crate::clock::VideoTs;
+        use crate::video::{Video, BorderSize, DIRTY_LINES_ALL};
+        use crate::video::pixel::{PixelBufA24, SpectrumPalRGB24};
+        fn next_frame(ula: &mut TestUla) -> Option<u32> {
+            ula.tsc = VFrameTs::EOF;
+            ula.ensure_next_frame();
+            ula.tsc = VFrameTs::EOF;
+            let dirty = ula.dirty_lines();
+            let (width, height) = <TestUla as Video>::render_size_pixels(BorderSize::Full);
+            let pitch = width as usize * 3;
+            let mut buffer = vec![0u8; pitch * height as usize];
+            ula.render_video_frame::<PixelBufA24, SpectrumPalRGB24>(&mut buffer, pitch, BorderSize::Full);
+            dirty
+        }
+        let mut ula = TestUla::default();
+        // the whole screen is initially changed
+        assert_eq!(ula.dirty_lines(), Some(DIRTY_LINES_ALL));
+        assert_eq!(next_frame(&mut ula), Some(DIRTY_LINES_ALL));
+        assert_eq!(next_frame(&mut ula), Some(0));
+        // modify the bitmap of the character row 9
+        ula.write_mem(0x4820, 0xFF, VideoTs::new(0, 0));
+        assert_eq!(ula.dirty_lines(), Some(1 << 9));
+        // the change is reported in the next frame as well, since it was made after the beam passed
+        assert_eq!(next_frame(&mut ula), Some(1 << 9));
+        // modify the attributes of the character row 23
+        ula.write_mem(0x5AFF, 0x38, VideoTs::new(0, 0));
+        assert_eq!(ula.dirty_lines(), Some(1 << 9 | 1 << 23));
+        assert_eq!(next_frame(&mut ula), Some(1 << 23));
+        assert_eq!(next_frame(&mut ula), Some(0));
+        // writes outside of the screen memory are not reported
+        ula.write_mem(0x5B00, 0xFF, VideoTs::new(0, 0));
+        assert_eq!(next_frame(&mut ula), Some(0));
+        // the flash attribute toggling
+        let flash_state = ula.flash_state();
+        while ula.flash_state() == flash_state {
+            assert_eq!(ula.dirty_lines(), Some(0));
+            next_frame(&mut ula);
+        }
+        assert_eq!(ula.dirty_lines(), Some(DIRTY_LINES_ALL));
+        assert_eq!(next_frame(&mut ula), Some(DIRTY_LINES_ALL));
+        assert_eq!(next_frame(&mut ula), Some(0));
+    }
 }
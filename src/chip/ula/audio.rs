@@ -5,6 +5,8 @@
 
     For the full copyright notice, see the lib.rs file.
 */
+use core::marker::PhantomData;
+
 use crate::audio::*;
 
 #[cfg(feature = "peripherals")]
@@ -48,28 +50,75 @@ impl<A, M, B, X, V> AudioFrame<A> for Ula<M, B, X, V>
 
 impl<A, M, B, X, V> EarMicOutAudioFrame<A> for Ula<M, B, X, V>
     where A: Blep,
+          A::SampleDelta: MulNorm + FromSample<f32>,
           V: VideoFrame
 {
     #[inline(always)]
     fn render_earmic_out_audio_frame<L: AmpLevels<A::SampleDelta>>(&self, blep: &mut A, channel: usize) {
-        render_audio_frame_vts::<V,L,A::SampleDelta,A,_>(
-                                        self.prev_earmic_data.into(),
-                                        None,
-                                        &self.earmic_out_changes,
-                                        blep, channel)
+        if self.tape_audio_gain == 1.0 {
+            render_audio_frame_vts::<V,L,A::SampleDelta,A,_>(
+                                            self.prev_earmic_data.into(),
+                                            None,
+                                            &self.earmic_out_changes,
+                                            blep, channel)
+        }
+        else {
+            // the EAR and the MIC components are rendered separately, the latter one attenuated
+            render_audio_frame_vts::<V,EarOnlyAmps<L>,A::SampleDelta,A,_>(
+                                            self.prev_earmic_data.into(),
+                                            None,
+                                            &self.earmic_out_changes,
+                                            blep, channel);
+            let gain = A::SampleDelta::from_sample(self.tape_audio_gain);
+            render_audio_frame_vts::<V,MicOnlyAmps<L>,A::SampleDelta,_,_>(
+                                            self.prev_earmic_data.into(),
+                                            None,
+                                            &self.earmic_out_changes,
+                                            &mut BlepAmpFilter::new(gain, blep), channel)
+        }
     }
 }
 
 impl<A, M, B, X, V> EarInAudioFrame<A> for Ula<M, B, X, V>
     where A: Blep,
+          A::SampleDelta: MulNorm + FromSample<f32>,
           V: VideoFrame
 {
     #[inline(always)]
     fn render_ear_in_audio_frame<L: AmpLevels<A::SampleDelta>>(&self, blep: &mut A, channel: usize) {
-        render_audio_frame_vts::<V,L,A::SampleDelta,A,_>(
-                                        self.prev_ear_in.into(),
-                                        Some(self.tsc),
-                                        &self.ear_in_changes,
-                                        blep, channel)
+        if self.tape_audio_gain == 1.0 {
+            render_audio_frame_vts::<V,L,A::SampleDelta,A,_>(
+                                            self.prev_ear_in.into(),
+                                            Some(self.tsc),
+                                            &self.ear_in_changes,
+                                            blep, channel)
+        }
+        else {
+            let gain = A::SampleDelta::from_sample(self.tape_audio_gain);
+            render_audio_frame_vts::<V,L,A::SampleDelta,_,_>(
+                                            self.prev_ear_in.into(),
+                                            Some(self.tsc),
+                                            &self.ear_in_changes,
+                                            &mut BlepAmpFilter::new(gain, blep), channel)
+        }
+    }
+}
+
+/// Amplitude levels of the EAR output component of the EAR/MIC levels.
+struct EarOnlyAmps<L>(PhantomData<L>);
+/// Amplitude level differences between the EAR/MIC and the EAR output levels.
+struct MicOnlyAmps<L>(PhantomData<L>);
+
+impl<T: SampleDelta, L: AmpLevels<T>> AmpLevels<T> for EarOnlyAmps<L> {
+    #[inline(always)]
+    fn amp_level(level: u32) -> T {
+        L::amp_level(level & 2)
+    }
+}
+
+impl<T: SampleDelta, L: AmpLevels<T>> AmpLevels<T> for MicOnlyAmps<L> {
+    #[inline(always)]
+    fn amp_level(level: u32) -> T {
+        L::amp_level(level & 2).sample_delta(L::amp_level(level)).unwrap_or_default()
     }
 }
@@ -9,19 +9,21 @@
 use log::{error, warn, info, debug, trace};
 
 use crate::z80emu::{
-    Cpu, CpuDebug, CpuDebugFn, Memory, Io, BreakCause,
+    Cpu, CpuDebug, CpuDebugFn, Memory, Io, Clock, BreakCause,
     host::{
         cycles::M1_CYCLE_TS, Result
     }
 };
-use crate::bus::BusDevice;
+use crate::bus::{BusDevice, BusCycle};
 use crate::chip::{MemoryAccess, ControlUnit};
 use crate::clock::{
     HALT_VC_THRESHOLD,
     VideoTs, VFrameTs, Ts, VFrameTsCounter, MemoryContention
 };
+use crate::chip::trace::InstructionTrace;
 use crate::memory::MemoryExtension;
 use crate::video::{Video, VideoFrame};
+use super::turbo::TurboClock;
 
 pub trait UlaControlExt: Video {
     /// This method is used by wrappers with a different contention scheme.
@@ -37,6 +39,14 @@ pub trait UlaControlExt: Video {
         }
         vtsc
     }
+    /// Returns the CPU clock multiplier.
+    fn cpu_turbo_multiplier(&self) -> u8;
+    /// Returns a mutable reference to the remainder of the CPU cycles divided by the CPU clock multiplier.
+    fn cpu_turbo_remainder_mut(&mut self) -> &mut u8;
+    /// Returns a mutable reference to the optional instruction trace log.
+    fn instruction_trace_mut(&mut self) -> &mut Option<InstructionTrace>;
+    /// Calls [BusDevice::rom_trap] with the access to the `cpu` and the memory.
+    fn rom_trap<C: Cpu>(&mut self, cpu: &mut C) -> bool;
 }
 
 pub(crate) trait UlaCpuExt: UlaControlExt {
@@ -45,16 +55,42 @@ pub(crate) trait UlaCpuExt: UlaControlExt {
             &mut self,
             cpu: &mut C
         ) -> bool;
+    fn ula_execute_next_frame_traced<C: Cpu>(
+            &mut self,
+            cpu: &mut C,
+            trace: &mut InstructionTrace
+        ) -> bool;
+    fn ula_execute_next_frame_turbo<C: Cpu>(
+            &mut self,
+            cpu: &mut C,
+            multiplier: u8
+        ) -> bool;
     fn ula_execute_single_step<C: Cpu, F: FnOnce(CpuDebug)>(
             &mut self,
             cpu: &mut C,
             debug: Option<F>
         ) -> Result<(),()>;
+    fn ula_execute_next_traced<C: Cpu, T, F: FnOnce(CpuDebug)>(
+            &mut self,
+            cpu: &mut C,
+            clock: &mut T,
+            trace: &mut InstructionTrace,
+            debug: Option<F>
+        ) -> Result<(),()>
+        where T: Clock<Limit=Ts, Timestamp=VideoTs>;
     fn ula_execute_instruction<C: Cpu>(
             &mut self,
             cpu: &mut C,
             code: u8
         ) -> Result<(), ()>;
+    /// Performs the bus cycles requested by the bus master devices until the bus is released or
+    /// the end of frame is reached.
+    ///
+    /// Returns `true` if the bus is still being requested.
+    fn ula_bus_master_cycles<T: MemoryContention>(
+            &mut self,
+            vtsc: &mut VFrameTsCounter<Self::VideoFrame, T>
+        ) -> bool;
 
     #[inline]
     fn ula_check_halt<C: Cpu>(mut vts: VideoTs, cpu: &mut C) -> VideoTs {
@@ -91,8 +127,47 @@ impl<U, B, X> UlaCpuExt for U
             cpu: &mut C
         ) -> bool
     {
+        let multiplier = self.cpu_turbo_multiplier();
+        if multiplier > 1 {
+            return self.ula_execute_next_frame_turbo(cpu, multiplier)
+        }
+        if let Some(mut trace) = self.instruction_trace_mut().take() {
+            let res = self.ula_execute_next_frame_traced(cpu, &mut trace);
+            *self.instruction_trace_mut() = Some(trace);
+            return res
+        }
         let mut vtsc = self.ensure_next_frame_vtsc();
         while !vtsc.is_eof() {
+            let is_bus_requested = self.bus_device_ref().is_bus_requested();
+            if is_bus_requested && (!self.ula_bus_master_cycles(&mut vtsc) || vtsc.is_eof()) {
+                continue
+            }
+            let has_rom_traps = self.bus_device_ref().has_rom_traps();
+            if has_rom_traps && self.rom_trap(cpu) {
+                continue
+            }
+            if is_bus_requested || has_rom_traps {
+                // the bus is released for a single instruction or the ROM traps are checked before each one
+                const DEBUG: Option<CpuDebugFn> = None;
+                match cpu.execute_next(self, &mut vtsc, DEBUG) {
+                    Ok(()) | Err(BreakCause::Halt) => {
+                        **vtsc = Self::ula_check_halt(vtsc.into(), cpu);
+                    }
+                    Err(_) => {
+                        **vtsc = Self::ula_check_halt(vtsc.into(), cpu);
+                        if vtsc.is_eof() {
+                            break
+                        }
+                        self.set_video_ts(vtsc.into());
+                        return false
+                    }
+                }
+                if cpu.is_halt() && vtsc.vc >= 1 && !self.bus_device_ref().is_bus_requested() {
+                    vtsc = execute_halted_state_until_eof(vtsc, cpu);
+                    break;
+                }
+                continue
+            }
             let vc_limit = if vtsc.vc < 1 { 1 }
             else {
                 Self::VideoFrame::VSL_COUNT
@@ -126,6 +201,94 @@ impl<U, B, X> UlaCpuExt for U
         true
     }
 
+    fn ula_execute_next_frame_traced<C: Cpu>(
+            &mut self,
+            cpu: &mut C,
+            trace: &mut InstructionTrace
+        ) -> bool
+    {
+        const DEBUG: Option<CpuDebugFn> = None;
+        let mut vtsc = self.ensure_next_frame_vtsc();
+        while !vtsc.is_eof() {
+            if self.bus_device_ref().is_bus_requested() {
+                self.ula_bus_master_cycles(&mut vtsc);
+                if vtsc.is_eof() {
+                    break
+                }
+            }
+            if self.bus_device_ref().has_rom_traps() && self.rom_trap(cpu) {
+                continue
+            }
+            match self.ula_execute_next_traced(cpu, &mut vtsc, trace, DEBUG) {
+                Ok(()) | Err(BreakCause::Halt) => {
+                    **vtsc = Self::ula_check_halt(vtsc.into(), cpu);
+                }
+                Err(_) => {
+                    **vtsc = Self::ula_check_halt(vtsc.into(), cpu);
+                    if vtsc.is_eof() {
+                        break
+                    }
+                    self.set_video_ts(vtsc.into());
+                    return false
+                }
+            }
+            // the halted state before the frame interrupt is being executed step by step
+            if cpu.is_halt() && vtsc.vc >= 1 {
+                vtsc = execute_halted_state_until_eof(vtsc, cpu);
+                break;
+            }
+        }
+        self.set_video_ts(vtsc.into());
+        self.bus_device_mut().update_timestamp(vtsc.vts.into());
+        true
+    }
+
+    fn ula_execute_next_frame_turbo<C: Cpu>(
+            &mut self,
+            cpu: &mut C,
+            multiplier: u8
+        ) -> bool
+    {
+        const DEBUG: Option<CpuDebugFn> = None;
+        let mut trace = self.instruction_trace_mut().take();
+        let remainder = *self.cpu_turbo_remainder_mut();
+        let mut clock = TurboClock::new(self.ensure_next_frame_vtsc(), multiplier, remainder);
+        // the instructions are being executed step by step
+        let finished = loop {
+            if clock.vtsc.is_eof() {
+                break true
+            }
+            if self.bus_device_ref().is_bus_requested() {
+                self.ula_bus_master_cycles(&mut clock.vtsc);
+                if clock.vtsc.is_eof() {
+                    break true
+                }
+            }
+            if self.bus_device_ref().has_rom_traps() && self.rom_trap(cpu) {
+                continue
+            }
+            let res = match trace.as_mut() {
+                Some(trace) => self.ula_execute_next_traced(cpu, &mut clock, trace, DEBUG),
+                None => cpu.execute_next(self, &mut clock, DEBUG)
+            };
+            **clock.vtsc = Self::ula_check_halt(clock.vtsc.into(), cpu);
+            if !matches!(res, Ok(()) | Err(BreakCause::Halt)) {
+                break clock.vtsc.is_eof()
+            }
+            if cpu.is_halt() && clock.vtsc.vc >= 1 {
+                clock.vtsc = execute_halted_state_until_eof(clock.vtsc, cpu);
+                break true
+            }
+        };
+        *self.instruction_trace_mut() = trace;
+        *self.cpu_turbo_remainder_mut() = clock.remainder();
+        self.set_video_ts(clock.vtsc.into());
+        if finished {
+            self.bus_device_mut().update_timestamp(clock.vtsc.vts.into());
+        }
+        finished
+    }
+
     fn ula_execute_single_step<C: Cpu, F>(
             &mut self,
             cpu: &mut C,
@@ -134,13 +297,72 @@ impl<U, B, X> UlaCpuExt for U
         where F: FnOnce(CpuDebug),
     {
         let mut vtsc = self.ensure_next_frame_vtsc();
-        let res = cpu.execute_next(self, &mut vtsc, debug);
+        if self.bus_device_ref().is_bus_requested() {
+            self.ula_bus_master_cycles(&mut vtsc);
+            if vtsc.is_eof() {
+                self.set_video_ts(vtsc.into());
+                self.bus_device_mut().update_timestamp(vtsc.vts.into());
+                return Ok(())
+            }
+        }
+        if self.bus_device_ref().has_rom_traps() && self.rom_trap(cpu) {
+            // the serviced trap counts as a step
+            self.set_video_ts(vtsc.into());
+            self.bus_device_mut().update_timestamp(vtsc.vts.into());
+            return Ok(())
+        }
+        let multiplier = self.cpu_turbo_multiplier();
+        let res = match self.instruction_trace_mut().take() {
+            Some(mut trace) => {
+                let res = if multiplier > 1 {
+                    let mut clock = TurboClock::new(vtsc, multiplier, *self.cpu_turbo_remainder_mut());
+                    let res = self.ula_execute_next_traced(cpu, &mut clock, &mut trace, debug);
+                    *self.cpu_turbo_remainder_mut() = clock.remainder();
+                    vtsc = clock.vtsc;
+                    res
+                }
+                else {
+                    self.ula_execute_next_traced(cpu, &mut vtsc, &mut trace, debug)
+                };
+                *self.instruction_trace_mut() = Some(trace);
+                res
+            }
+            None if multiplier > 1 => {
+                let mut clock = TurboClock::new(vtsc, multiplier, *self.cpu_turbo_remainder_mut());
+                let res = cpu.execute_next(self, &mut clock, debug);
+                *self.cpu_turbo_remainder_mut() = clock.remainder();
+                vtsc = clock.vtsc;
+                res
+            }
+            None => cpu.execute_next(self, &mut vtsc, debug)
+        };
         **vtsc = Self::ula_check_halt(vtsc.into(), cpu);
         self.set_video_ts(vtsc.into());
         self.bus_device_mut().update_timestamp(vtsc.vts.into());
         res
     }
 
+    fn ula_execute_next_traced<C: Cpu, T, F: FnOnce(CpuDebug)>(
+            &mut self,
+            cpu: &mut C,
+            clock: &mut T,
+            trace: &mut InstructionTrace,
+            debug: Option<F>
+        ) -> Result<(),()>
+        where T: Clock<Limit=Ts, Timestamp=VideoTs>
+    {
+        // an instruction with prefixes may be executed in several steps
+        if !cpu.is_after_prefix() {
+            trace.mark_start(VFrameTs::<Self::VideoFrame>::from(clock.as_timestamp()).into_tstates());
+        }
+        cpu.execute_next(self, clock, Some(|deb: CpuDebug| {
+            if let Some(debug) = debug {
+                debug(deb.clone());
+            }
+            trace.push_debug(deb);
+        }))
+    }
+
     fn ula_execute_instruction<C: Cpu>(
             &mut self,
             cpu: &mut C,
@@ -155,6 +377,46 @@ impl<U, B, X> UlaCpuExt for U
         self.bus_device_mut().update_timestamp(vtsc.vts.into());
         res
     }
+
+    fn ula_bus_master_cycles<T: MemoryContention>(
+            &mut self,
+            vtsc: &mut VFrameTsCounter<Self::VideoFrame, T>
+        ) -> bool
+    {
+        while !vtsc.is_eof() {
+            let (cycle, length) = match self.bus_device_mut().bus_request() {
+                Some(request) => request,
+                None => break
+            };
+            match cycle {
+                BusCycle::ReadMemory(address) | BusCycle::WriteMemory(address, _)
+                    if Self::VideoFrame::is_contended_line_mreq(vtsc.vc) && vtsc.is_contended_address(address) =>
+                {
+                    vtsc.hc = vtsc.contention.contention::<Self::VideoFrame>(vtsc.hc);
+                }
+                _ => {}
+            }
+            let ts = vtsc.as_timestamp();
+            *vtsc += u32::from(length);
+            match cycle {
+                BusCycle::ReadMemory(address) => {
+                    let data = self.read_mem(address, ts);
+                    self.bus_device_mut().bus_read_data(data);
+                }
+                BusCycle::WriteMemory(address, data) => {
+                    self.write_mem(address, data, ts);
+                }
+                BusCycle::ReadIo(port) => {
+                    let (data, _) = self.read_io(port, ts);
+                    self.bus_device_mut().bus_read_data(data);
+                }
+                BusCycle::WriteIo(port, data) => {
+                    self.write_io(port, data, ts);
+                }
+            }
+        }
+        self.bus_device_ref().is_bus_requested()
+    }
 }
 
 /// Emulates the CPU's halted state at the given video timestamp.
@@ -201,7 +463,7 @@ pub fn execute_halted_state_until_eof<V, T, C>(
             let hc0 = hc - V::HTS_COUNT;
             let r_incr0 = r_incr;
             while hc < V::HTS_RANGE.end {
-                hc = V::contention(hc) + M1_CYCLE_TS as Ts;
+                hc = vtsc.contention.contention::<V>(hc) + M1_CYCLE_TS as Ts;
                 r_incr += 1;
             }
             vc += 1;
@@ -214,7 +476,7 @@ pub fn execute_halted_state_until_eof<V, T, C>(
         if vc < V::VSL_PIXELS.end {
             let mut r_line = 0;
             while hc < V::HTS_RANGE.end {
-                hc = V::contention(hc) + M1_CYCLE_TS as Ts;
+                hc = vtsc.contention.contention::<V>(hc) + M1_CYCLE_TS as Ts;
                 r_line += 1;
             }
             hc -= V::HTS_COUNT;
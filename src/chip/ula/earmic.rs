@@ -154,6 +154,37 @@ impl<'a, I, V> Iterator for MicPulseIter<I, V>
 impl<M, B, X, V> Ula<M, B, X, V>
     where V: VideoFrame
 {
+    /// Records the MIC out pulses of the current frame in the EAR/MIC loopback buffer if enabled.
+    ///
+    /// Returns `true` if any pulse was recorded.
+    pub(super) fn record_ear_mic_loopback(&mut self) -> bool {
+        let loopback = match self.loopback.as_mut() {
+            Some(loopback) => loopback,
+            None => return false
+        };
+        // the periods of silence are shortened to a single frame
+        let max_pulse = NonZeroU32::new(V::FRAME_TSTATES_COUNT as u32).unwrap();
+        let len = loopback.len();
+        loopback.extend(
+            MicPulseIter::<_, V>::new(self.prev_earmic_ts, self.prev_earmic_data, self.earmic_out_changes.iter())
+                             .map(|pulse| pulse.min(max_pulse)));
+        loopback.len() != len
+    }
+    /// Feeds the EAR in with the pulses recorded in the EAR/MIC loopback buffer for the next frame
+    /// if the EAR in was being probed frequently enough during the last frame.
+    pub(super) fn play_ear_mic_loopback(&mut self, ear_in_reads: u32) {
+        const PROBE_THRESHOLD: u32 = 1000;
+        if ear_in_reads <= V::FRAME_TSTATES_COUNT as u32 / PROBE_THRESHOLD {
+            return
+        }
+        if let Some(mut loopback) = self.loopback.take() {
+            if !matches!(self.ear_in_changes.last(), Some(&vtsd) if VFrameTs::<V>::from(vtsd).vc >= V::VSL_COUNT) {
+                self.feed_ear_in(core::iter::from_fn(|| loopback.pop_front()), Some(1));
+            }
+            self.loopback = Some(loopback);
+        }
+    }
+
     pub(super) fn cleanup_earmic_frame_data(&mut self) {
         // FIXME! (but how?)
         self.prev_earmic_ts = match self.earmic_out_changes.last() {
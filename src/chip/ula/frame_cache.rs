@@ -234,23 +234,45 @@ impl<V: VideoFrame> UlaFrameCache<V> {
     /// Compares the given attribute cell coordinates with the video timestamp and depending
     /// on the result of that comparison caches (or not) the attribute cell or cells with
     /// the value from the memory at the given address.
-    #[inline(never)]
+    #[inline]
     pub fn update_frame_colors<M: ZxMemory>(
             &mut self,
             memory: &M,
-            CellCoords { column, row }: CellCoords,
+            coords: CellCoords,
             addr: u16,
             ts: VideoTs
         )
+    {
+        self.update_frame_colors_with_mode(memory, coords, addr, ts, false)
+    }
+    /// Compares the given attribute cell coordinates with the video timestamp and depending
+    /// on the result of that comparison caches (or not) the attribute cell or cells with
+    /// the value from the memory at the given address.
+    ///
+    /// If `reduce_clash` is `true` the attribute cell being modified while the video beam is
+    /// in its attribute row becomes visible from the current pixel line even if the beam has
+    /// already passed the cell. See [UlaControl::set_attr_clash_reduction].
+    ///
+    /// [UlaControl::set_attr_clash_reduction]: crate::chip::UlaControl::set_attr_clash_reduction
+    #[inline(never)]
+    pub fn update_frame_colors_with_mode<M: ZxMemory>(
+            &mut self,
+            memory: &M,
+            CellCoords { column, row }: CellCoords,
+            addr: u16,
+            ts: VideoTs,
+            reduce_clash: bool
+        )
     {
         let column = column as usize & 31;
         let vy = ts.vc - V::VSL_PIXELS.start;
         let coarse_vy = vy >> 3;
         let coarse_y = Ts::from(row);
+        let passed = ts.hc > COL_ATTR_HTS[column] && !reduce_clash;
         if coarse_y < coarse_vy ||
                 coarse_y == coarse_vy &&
                 vy & 0b111 == 0b111 &&
-                ts.hc > COL_ATTR_HTS[column] {
+                passed {
             let (mask, colors) = &mut self.frame_colors_coarse[row as usize];
             let mbit = 1 << column;
             if *mask & mbit == 0 {
@@ -260,7 +282,7 @@ impl<V: VideoFrame> UlaFrameCache<V> {
         }
         else if coarse_y == coarse_vy {
             let line_top = (coarse_vy << 3) as usize;
-            let line_bot = if ts.hc > COL_ATTR_HTS[column] {
+            let line_bot = if passed {
                 vy + 1
             } else {
                 vy
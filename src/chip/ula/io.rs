@@ -28,11 +28,11 @@ impl<M, B, X, V> Io for Ula<M, B, X, V>
 
     #[inline(always)]
     fn is_irq(&mut self, VideoTs{ vc, hc }: VideoTs) -> bool {
-        vc == 0 && (hc + Ts::from(self.late_timings)) & !31 == 0
+        vc == 0 && (0..Ts::from(self.interrupt_length)).contains(&(hc + Ts::from(self.late_timings)))
     }
 
     fn read_io(&mut self, port: u16, ts: VideoTs) -> (u8, Option<NonZeroU16>) {
-        self.ula_read_io(port, ts)
+        self.ula_read_io(port, ts, 0)
             .unwrap_or_else(|| (self.floating_bus(ts), None))
     }
 
@@ -47,7 +47,9 @@ impl<M, B, X, V> Io for Ula<M, B, X, V>
             self.ula_write_earmic(flags, ts);
         }
         else if let Some(ws) = self.bus.write_io(port, data, VFrameTs::from(ts).into()) {
-            return (None, NonZeroU16::new(ws))
+            // break to let the bus master take the bus
+            let res = if self.bus.is_bus_requested() { Some(()) } else { None };
+            return (res, NonZeroU16::new(ws))
         }
         (None, None)
     }
@@ -66,13 +68,13 @@ impl<M, B, X, V> Memory for Ula<M, B, X, V>
     }
 
     #[inline(always)]
-    fn read_mem(&self, addr: u16, _ts: VideoTs) -> u8 {
-        self.memory.read(addr)
+    fn read_mem(&self, addr: u16, ts: VideoTs) -> u8 {
+        self.memext.read_mem(addr, V::vc_hc_to_tstates(ts.vc, ts.hc), &self.memory)
     }
 
     #[inline(always)]
-    fn read_mem16(&self, addr: u16, _ts: VideoTs) -> u16 {
-        self.memory.read16(addr)
+    fn read_mem16(&self, addr: u16, ts: VideoTs) -> u16 {
+        self.memext.read_mem16(addr, V::vc_hc_to_tstates(ts.vc, ts.hc), &self.memory)
     }
 
     #[inline(always)]
@@ -84,7 +86,7 @@ impl<M, B, X, V> Memory for Ula<M, B, X, V>
     #[inline(always)]
     fn write_mem(&mut self, addr: u16, val: u8, ts: VideoTs) {
         self.update_frame_cache(addr, ts);
-        self.memory.write(addr, val);
+        self.memext.write_mem(addr, val, V::vc_hc_to_tstates(ts.vc, ts.hc), &mut self.memory);
     }
 }
 
@@ -116,10 +118,23 @@ impl<M, B, X, V> Ula<M, B, X, V>
     }
 
     #[inline(always)]
-    pub(crate) fn ula_read_io(&mut self, port: u16, ts: VideoTs) -> Option<(u8, Option<NonZeroU16>)>
-        where B: BusDevice,
+    pub(crate) fn ula_probe_screen(&mut self, screen_bank: usize, ts: VideoTs)
+        where M: ZxMemory,
+              B: BusDevice,
+              B::Timestamp: From<VFrameTs<V>>
+    {
+        if let Ok(screen) = self.memory.screen_ref(screen_bank) {
+            self.bus.probe_screen(screen, VFrameTs::from(ts).into());
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn ula_read_io(&mut self, port: u16, ts: VideoTs, screen_bank: usize) -> Option<(u8, Option<NonZeroU16>)>
+        where M: ZxMemory,
+              B: BusDevice,
               B::Timestamp: From<VFrameTs<V>>
     {
+        self.ula_probe_screen(screen_bank, ts);
         let bus_data = self.bus.read_io(port, VFrameTs::from(ts).into());
         if port & 1 == 0 {
             let ula_data = self.ula_io_data(port, ts);
@@ -137,7 +152,10 @@ impl<M, B, X, V> Ula<M, B, X, V>
     fn floating_bus(&self, ts: VideoTs) -> u8
         where M: ZxMemory
     {
-        if let Some(addr) = V::floating_bus_screen_address(ts) {
+        if !self.floating_bus {
+            u8::MAX
+        }
+        else if let Some(addr) = V::floating_bus_screen_address(ts) {
             self.memory.read_screen(0, addr)
         }
         else {
@@ -70,6 +70,10 @@ impl<'a, M, B, X, V> UlaPlusInner<'a> for Ula<M, B, X, V>
         false
     }
 
+    fn is_attr_clash_reduced(&self) -> bool {
+        self.reduce_clash
+    }
+
     fn video_render_data_view(
         &mut self
     ) -> VideoRenderDataView<'_, Self::ScreenSwapIter, Self::Memory, Self::VideoFrame>
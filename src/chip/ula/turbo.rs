@@ -0,0 +1,98 @@
+/*
+    Copyright (C) 2020-2023  Rafal Michalski
+
+    This file is part of SPECTRUSTY, a Rust library for building emulators.
+
+    For the full copyright notice, see the lib.rs file.
+*/
+use core::num::{NonZeroU8, NonZeroU16};
+
+use crate::z80emu::{
+    Clock,
+    host::cycles::{M1_CYCLE_TS, MEMRW_CYCLE_TS, IO_CYCLE_TS, IRQ_ACK_CYCLE_TS}
+};
+use crate::clock::{
+    FTs, Ts, VideoTs, VFrameTs, VFrameTsCounter, MemoryContention
+};
+use crate::video::VideoFrame;
+
+/// A [Clock] wrapper running the CPU `multiplier` times faster than the video clock.
+///
+/// The regular CPU cycles are being divided by the `multiplier`, while the memory and I/O contention
+/// delays and the wait states are being added in the T-states of the base (video) clock.
+/// The remainders of the divided cycles are being carried over to the subsequent cycles.
+pub(crate) struct TurboClock<V, C> {
+    pub vtsc: VFrameTsCounter<V, C>,
+    multiplier: FTs,
+    remainder: FTs
+}
+
+impl<V: VideoFrame, C: MemoryContention> TurboClock<V, C> {
+    pub fn new(vtsc: VFrameTsCounter<V, C>, multiplier: u8, remainder: u8) -> Self {
+        let multiplier = FTs::from(multiplier.max(1));
+        TurboClock { vtsc, multiplier, remainder: FTs::from(remainder) % multiplier }
+    }
+    /// Returns the remainder of the divided CPU cycles, to be carried over to the next execution.
+    pub fn remainder(&self) -> u8 {
+        self.remainder as u8
+    }
+
+    #[inline]
+    fn add_cycles<F>(&mut self, cycles: u8, add: F) -> VideoTs
+        where F: FnOnce(&mut VFrameTsCounter<V, C>) -> VideoTs
+    {
+        let start = self.vtsc.vts.into_tstates();
+        let ts = VFrameTs::<V>::from(add(&mut self.vtsc)).into_tstates();
+        let cycles = FTs::from(cycles);
+        // the contention delay in the base clock T-states
+        let delay = self.vtsc.vts.into_tstates() - start - cycles;
+        let cycles = self.remainder + cycles;
+        self.remainder = cycles % self.multiplier;
+        let end = start + delay + cycles / self.multiplier;
+        self.vtsc.vts = VFrameTs::from_tstates(end);
+        VFrameTs::<V>::from_tstates(ts.min(end)).into()
+    }
+}
+
+impl<V: VideoFrame, C: MemoryContention> Clock for TurboClock<V, C> {
+    type Limit = Ts;
+    type Timestamp = VideoTs;
+
+    #[inline(always)]
+    fn is_past_limit(&self, limit: Self::Limit) -> bool {
+        self.vtsc.is_past_limit(limit)
+    }
+
+    fn add_irq(&mut self, pc: u16) -> Self::Timestamp {
+        self.add_cycles(IRQ_ACK_CYCLE_TS, |vtsc| vtsc.add_irq(pc))
+    }
+
+    fn add_no_mreq(&mut self, address: u16, add_ts: NonZeroU8) {
+        self.add_cycles(add_ts.get(), |vtsc| {
+            vtsc.add_no_mreq(address, add_ts);
+            vtsc.as_timestamp()
+        });
+    }
+
+    fn add_m1(&mut self, address: u16) -> Self::Timestamp {
+        self.add_cycles(M1_CYCLE_TS, |vtsc| vtsc.add_m1(address))
+    }
+
+    fn add_mreq(&mut self, address: u16) -> Self::Timestamp {
+        self.add_cycles(MEMRW_CYCLE_TS, |vtsc| vtsc.add_mreq(address))
+    }
+
+    fn add_io(&mut self, port: u16) -> Self::Timestamp {
+        self.add_cycles(IO_CYCLE_TS, |vtsc| vtsc.add_io(port))
+    }
+
+    /// The wait states are being added in the T-states of the base clock.
+    fn add_wait_states(&mut self, bus: u16, wait_states: NonZeroU16) {
+        self.vtsc.add_wait_states(bus, wait_states)
+    }
+
+    #[inline(always)]
+    fn as_timestamp(&self) -> Self::Timestamp {
+        self.vtsc.as_timestamp()
+    }
+}
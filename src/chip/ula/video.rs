@@ -15,7 +15,7 @@ use crate::memory::ZxMemory;
 use crate::clock::{VideoTs, Ts, VFrameTsCounter, VideoTsData3, MemoryContention};
 use crate::video::{
     Renderer, BorderSize, BorderColor, PixelBuffer, Palette,
-    VideoFrame, Video, CellCoords, MAX_BORDER_SIZE,
+    VideoFrame, Video, CellCoords, MAX_BORDER_SIZE, DIRTY_LINES_ALL,
     frame_cache::{
         pixel_address_coords, color_address_coords
     }
@@ -142,6 +142,10 @@ impl<M: ZxMemory, D, X, V: VideoFrame> Video for Ula<M, D, X, V> {
     fn flash_state(&self) -> bool {
         self.frames.0 & 16 != 0
     }
+
+    fn dirty_lines(&self) -> Option<u32> {
+        Some(self.dirty_lines | self.prev_dirty_lines)
+    }
 }
 
 impl<M: ZxMemory, B, X, V: VideoFrame> Ula<M, B, X, V> {
@@ -151,10 +155,12 @@ impl<M: ZxMemory, B, X, V: VideoFrame> Ula<M, B, X, V> {
             0x4000..=0x57FF => {
                 let coords = pixel_address_coords(addr);
                 self.frame_cache.update_frame_pixels(&self.memory, coords, addr, ts);
+                self.mark_dirty_screen_address(addr);
             }
             0x5800..=0x5AFF => {
                 let coords = color_address_coords(addr);
-                self.frame_cache.update_frame_colors(&self.memory, coords, addr, ts);
+                self.frame_cache.update_frame_colors_with_mode(&self.memory, coords, addr, ts, self.reduce_clash);
+                self.mark_dirty_screen_address(addr);
             }
             _ => {}
         }
@@ -162,20 +168,49 @@ impl<M: ZxMemory, B, X, V: VideoFrame> Ula<M, B, X, V> {
 
     #[inline(always)]
     pub(super) fn update_snow_interference(&mut self, ts: VideoTs, ir: u16) {
-        if UlaMemoryContention.is_contended_address(ir) {
+        if self.snow_effect && UlaMemoryContention.is_contended_address(ir) {
             if let Some(coords) = V::snow_interference_coords(ts) {
                 let screen = self.memory.screen_ref(0).unwrap();
-                self.frame_cache.apply_snow_interference(screen, coords, ir as u8)
+                self.frame_cache.apply_snow_interference(screen, coords, ir as u8);
+                self.mark_dirty_pixel_row(coords.row);
             }
         }
     }
 }
 
+impl<M, B, X, V> Ula<M, B, X, V> {
+    /// Marks the character row of the given screen bitmap or attribute address as changed.
+    #[inline(always)]
+    pub(crate) fn mark_dirty_screen_address(&mut self, addr: u16) {
+        let row = if addr & 0x1800 != 0x1800 {
+            pixel_address_coords(addr).row >> 3
+        }
+        else {
+            color_address_coords(addr).row
+        };
+        self.dirty_lines |= 1 << row;
+    }
+
+    /// Marks the character row of the given INK/PAPER pixel line as changed.
+    #[inline(always)]
+    pub(crate) fn mark_dirty_pixel_row(&mut self, row: u8) {
+        self.dirty_lines |= 1 << (row >> 3);
+    }
+
+    pub(crate) fn mark_all_lines_dirty(&mut self) {
+        self.dirty_lines = DIRTY_LINES_ALL;
+    }
+}
+
 impl<M: ZxMemory, B, X, V> Ula<M, B, X, V> {
     pub(super) fn cleanup_video_frame_data(&mut self) {
         self.border = self.last_border;
         self.border_out_changes.clear();
         self.frame_cache.clear();
+        // the changes made after the beam passed show up in the next frame
+        self.prev_dirty_lines = self.dirty_lines;
+        // the flash attribute state toggles every 16 frames
+        self.dirty_lines = if self.frames.0 & 15 == 0 { DIRTY_LINES_ALL } else { 0 };
     }
 
     pub(crate) fn video_render_data_view(&mut self) -> (&mut Vec<VideoTsData3>, &M, &UlaFrameCache<V>) {
@@ -305,4 +340,22 @@ mod tests {
             assert_eq!(vts1.saturating_add(vts0), addvts);
         }
     }
+
+    #[test]
+    fn test_ts_to_pixel_position() {
+        let items = [((  0,   0), None),
+                     (( 15,   0), None),
+                     (( 16, -25), None),
+                     (( 16, -24), Some((  0,   0))),
+                     (( 64,   0), Some(( 48,  48))),
+                     ((100, 140), Some((328,  84))),
+                     ((255, 127), Some((302, 239))),
+                     ((303, 151), Some((350, 287))),
+                     ((303, 152), None),
+                     ((304,   0), None),
+                     ((311, 154), None)];
+        for ((vc, hc), pos) in items.iter().copied() {
+            assert_eq!(TestVideoFrame::ts_to_pixel_position(VideoTs::new(vc, hc)), pos);
+        }
+    }
 }
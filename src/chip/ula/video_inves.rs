@@ -0,0 +1,96 @@
+/*
+    Copyright (C) 2020-2023  Rafal Michalski
+
+    This file is part of SPECTRUSTY, a Rust library for building emulators.
+
+    For the full copyright notice, see the lib.rs file.
+*/
+use core::iter::StepBy;
+use core::ops::Range;
+
+#[cfg(feature = "snapshot")]
+use serde::{Serialize, Deserialize};
+
+use crate::clock::{VideoTs, Ts};
+use crate::video::{BorderSize, VideoFrame, CellCoords};
+use super::UlaVideoFrame;
+
+/// Implements [VideoFrame] for the ULA clone of the Inves Spectrum+.
+///
+/// The video timings are the same as of [UlaVideoFrame], but there is no floating bus: reading from
+/// unattached ports always returns `0xFF`.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+pub struct UlaInvesVidFrame;
+
+impl VideoFrame for UlaInvesVidFrame {
+    const HTS_RANGE: Range<Ts> = UlaVideoFrame::HTS_RANGE;
+    const VSL_BORDER_TOP: Ts = UlaVideoFrame::VSL_BORDER_TOP;
+    const VSL_PIXELS: Range<Ts> = UlaVideoFrame::VSL_PIXELS;
+    const VSL_BORDER_BOT: Ts = UlaVideoFrame::VSL_BORDER_BOT;
+    const VSL_COUNT: Ts = UlaVideoFrame::VSL_COUNT;
+
+    type BorderHtsIter = StepBy<Range<Ts>>;
+
+    fn border_whole_line_hts_iter(border_size: BorderSize) -> Self::BorderHtsIter {
+        UlaVideoFrame::border_whole_line_hts_iter(border_size)
+    }
+
+    fn border_left_hts_iter(border_size: BorderSize) -> Self::BorderHtsIter {
+        UlaVideoFrame::border_left_hts_iter(border_size)
+    }
+
+    fn border_right_hts_iter(border_size: BorderSize) -> Self::BorderHtsIter {
+        UlaVideoFrame::border_right_hts_iter(border_size)
+    }
+
+    #[inline]
+    fn contention(hc: Ts) -> Ts {
+        UlaVideoFrame::contention(hc)
+    }
+
+    #[inline(always)]
+    fn floating_bus_offset(_hc: Ts) -> Option<u16> {
+        None
+    }
+
+    #[inline(always)]
+    fn snow_interference_coords(vts: VideoTs) -> Option<CellCoords> {
+        UlaVideoFrame::snow_interference_coords(vts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::z80emu::{Cpu, Io, Z80NMOS};
+    use crate::chip::{ControlUnit, MemoryAccess, ula::{Ula, UlaPAL, UlaInves}};
+    use crate::bus::VFNullDevice;
+    use crate::memory::{Memory48k, NoMemoryExtension, ZxMemory};
+    use super::*;
+
+    fn boot<V: VideoFrame>(ula: &mut Ula<Memory48k, VFNullDevice<V>, NoMemoryExtension, V>) {
+        let mut rom = vec![0u8; 0x4000];
+        // LD A,0x55; LD (0x4000),A; HALT
+        rom[..6].copy_from_slice(&[0x3E, 0x55, 0x32, 0x00, 0x40, 0x76]);
+        ula.memory_mut().load_into_rom(&rom[..]).unwrap();
+        let mut cpu = Z80NMOS::default();
+        ula.execute_next_frame(&mut cpu);
+        assert!(cpu.is_halt());
+        assert_eq!(ula.memory_ref().read(0x4000), 0x55);
+    }
+
+    #[test]
+    fn test_inves_floating_bus() {
+        let vts = VideoTs::new(UlaInvesVidFrame::VSL_PIXELS.start, 0);
+        let mut ula = UlaPAL::<Memory48k>::default();
+        boot(&mut ula);
+        assert_eq!(ula.read_io(0xFF, vts).0, 0x55);
+        let mut inves = UlaInves::<Memory48k>::default();
+        boot(&mut inves);
+        assert_eq!(inves.read_io(0xFF, vts).0, 0xFF);
+        for hc in UlaInvesVidFrame::HTS_RANGE {
+            assert_eq!(UlaInvesVidFrame::floating_bus_offset(hc), None);
+            assert_eq!(UlaInvesVidFrame::contention(hc), UlaVideoFrame::contention(hc));
+        }
+    }
+}
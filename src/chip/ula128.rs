@@ -25,7 +25,7 @@ use serde::{Serialize, Deserialize};
 use crate::bus::{BusDevice, VFNullDevice};
 use crate::clock::{VFrameTs, VideoTs, VFrameTsCounter, MemoryContention};
 use crate::chip::{
-    InnerAccess, ControlUnit, MemoryAccess, Ula128MemFlags, UlaControl,
+    InnerAccess, ControlUnit, MemoryAccess, Ula128MemFlags, UlaControl, DEFAULT_INTERRUPT_LENGTH_128, trace::{InstructionTrace, TraceEntry},
     ula::{
         Ula, UlaControlExt, UlaCpuExt,
         frame_cache::UlaFrameCache
@@ -100,8 +100,10 @@ impl MemoryContention for Ula128MemContention {
 
 impl<B: Default, X: Default> Default for Ula128<B, X> {
     fn default() -> Self {
+        let mut ula = InnerUla::default();
+        ula.set_interrupt_length(DEFAULT_INTERRUPT_LENGTH_128);
         Ula128 {
-            ula: Default::default(),
+            ula,
             mem_page3_bank: MemPage8::Bank0,
             beg_screen_shadow: false,
             cur_screen_shadow: false,
@@ -202,6 +204,74 @@ impl<B, X> UlaControl for Ula128<B, X> {
         self.ula.set_late_timings(late_timings)
     }
 
+    fn interrupt_length(&self) -> Option<u8> {
+        self.ula.interrupt_length()
+    }
+
+    fn set_interrupt_length(&mut self, length: u8) -> bool {
+        self.ula.set_interrupt_length(length)
+    }
+
+    fn instruction_trace_capacity(&self) -> Option<usize> {
+        self.ula.instruction_trace_capacity()
+    }
+
+    fn set_instruction_trace(&mut self, capacity: Option<usize>) -> bool {
+        self.ula.set_instruction_trace(capacity)
+    }
+
+    fn instruction_trace(&self) -> Option<Vec<TraceEntry>> {
+        self.ula.instruction_trace()
+    }
+
+    fn cpu_turbo(&self) -> Option<u8> {
+        self.ula.cpu_turbo()
+    }
+
+    fn set_cpu_turbo(&mut self, multiplier: u8) -> bool {
+        self.ula.set_cpu_turbo(multiplier)
+    }
+
+    fn has_ear_mic_loopback(&self) -> Option<bool> {
+        self.ula.has_ear_mic_loopback()
+    }
+
+    fn set_ear_mic_loopback(&mut self, enabled: bool) -> bool {
+        self.ula.set_ear_mic_loopback(enabled)
+    }
+
+    fn tape_audio_gain(&self) -> Option<f32> {
+        self.ula.tape_audio_gain()
+    }
+
+    fn set_tape_audio_gain(&mut self, gain: f32) -> bool {
+        self.ula.set_tape_audio_gain(gain)
+    }
+
+    fn has_attr_clash_reduction(&self) -> Option<bool> {
+        self.ula.has_attr_clash_reduction()
+    }
+
+    fn set_attr_clash_reduction(&mut self, reduce_clash: bool) -> bool {
+        self.ula.set_attr_clash_reduction(reduce_clash)
+    }
+
+    fn has_floating_bus(&self) -> Option<bool> {
+        self.ula.has_floating_bus()
+    }
+
+    fn set_floating_bus(&mut self, enabled: bool) -> bool {
+        self.ula.set_floating_bus(enabled)
+    }
+
+    fn has_snow_effect(&self) -> Option<bool> {
+        self.ula.has_snow_effect()
+    }
+
+    fn set_snow_effect(&mut self, enabled: bool) -> bool {
+        self.ula.set_snow_effect(enabled)
+    }
+
     fn ula128_mem_port_value(&self) -> Option<Ula128MemFlags> {
         let mut flags = Ula128MemFlags::empty()
                         .with_last_ram_page_bank(self.mem_page3_bank.into());
@@ -262,6 +332,7 @@ impl<B, X> Ula128<B, X> {
         if self.cur_screen_shadow != cur_screen_shadow {
             self.cur_screen_shadow = cur_screen_shadow;
             self.screen_changes.push(ts);
+            self.ula.mark_all_lines_dirty();
         }
         let rom_bank = flags.rom_page_bank();
         self.ula.memory.map_rom_bank(rom_bank, 0).unwrap();
@@ -367,6 +438,22 @@ impl<B, X> UlaControlExt for Ula128<B, X>
         self.screen_changes.clear();
         self.ula.prepare_next_frame(vtsc)
     }
+
+    fn cpu_turbo_multiplier(&self) -> u8 {
+        self.ula.cpu_turbo_multiplier()
+    }
+
+    fn cpu_turbo_remainder_mut(&mut self) -> &mut u8 {
+        self.ula.cpu_turbo_remainder_mut()
+    }
+
+    fn instruction_trace_mut(&mut self) -> &mut Option<InstructionTrace> {
+        self.ula.instruction_trace_mut()
+    }
+
+    fn rom_trap<C: Cpu>(&mut self, cpu: &mut C) -> bool {
+        self.ula.rom_trap(cpu)
+    }
 }
 
 #[cfg(test)]
@@ -374,6 +461,24 @@ mod tests {
     use crate::video::{Video, VideoFrame};
     use super::*;
 
+    #[test]
+    fn test_ula128_snow_dirty_lines() {
+        use crate::z80emu::Memory;
+        use crate::clock::{VFrameTs, VideoTs};
+        let mut ula: Ula128 = Default::default();
+        for _ in 0..2 {
+            ula.ula.tsc = VFrameTs::EOF;
+            ula.ensure_next_frame();
+        }
+        assert_eq!(ula.dirty_lines(), Some(0));
+        // the opcode fetch while the ULA fetches the first cell of the character row 9
+        let ts = VideoTs::new(Ula128VidFrame::VSL_PIXELS.start + 9 * 8 + 3, 2);
+        ula.read_opcode(0x8000, 0x8021, ts);
+        assert_eq!(ula.dirty_lines(), Some(0));
+        ula.read_opcode(0x8000, 0x4021, ts);
+        assert_eq!(ula.dirty_lines(), Some(1 << 9));
+    }
+
     #[test]
     fn test_ula128() {
         assert_eq!(<Ula128 as Video>::VideoFrame::FRAME_TSTATES_COUNT, 70908);
@@ -393,4 +498,99 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_ula128_floating_bus() {
+        use crate::z80emu::Io;
+        let mut ula: Ula128 = Default::default();
+        // the cell at column 2 of the pixel line 8
+        ula.memory_mut().write(0x4022, 0b1010_0101);
+        ula.memory_mut().write(0x5822, 0x38);
+        ula.memory_mut().write(0x5823, 0x47);
+        let vc = <Ula128 as Video>::VideoFrame::VSL_PIXELS.start + 8;
+        let items = [(5, 0xFF), (6, 0b1010_0101), (7, 0x38), (9, 0x47), (10, 0xFF)];
+        for (hc, data) in items.iter().copied() {
+            assert_eq!(ula.read_io(0x00FF, VideoTs::new(vc, hc)).0, data);
+        }
+        // the shadow screen in the memory bank 7
+        let flags = Ula128MemFlags::with_last_ram_page_bank(Ula128MemFlags::SCREEN_BANK, 7);
+        assert!(ula.set_ula128_mem_port_value(flags));
+        ula.memory_mut().write(0xD822, 0x16);
+        assert_eq!(ula.read_io(0x00FF, VideoTs::new(vc, 7)).0, 0x16);
+        assert_eq!(ula.has_floating_bus(), Some(true));
+        assert!(ula.set_floating_bus(false));
+        assert_eq!(ula.has_floating_bus(), Some(false));
+        assert_eq!(ula.read_io(0x00FF, VideoTs::new(vc, 7)).0, 0xFF);
+    }
+
+    #[cfg(feature = "peripherals")]
+    #[test]
+    fn test_ula128_light_gun() {
+        use crate::z80emu::Io;
+        use crate::bus::VFNullDevice;
+        use crate::peripherals::bus::lightgun::{MagnumLightPhaser, DEFAULT_LIGHT_MASK};
+        let mut ula: Ula128<MagnumLightPhaser<VFNullDevice<Ula128VidFrame>>> = Default::default();
+        ula.bus_device_mut().set_aim(Some((16, 0)));
+        for screen_bank in 0..2 {
+            ula.memory_mut().screen_mut(screen_bank).unwrap().fill(0);
+        }
+        let ts = VideoTs::new(<Ula128 as Video>::VideoFrame::VSL_PIXELS.start, 9);
+        assert_eq!(ula.read_io(0xEFFE, ts).0 & DEFAULT_LIGHT_MASK, DEFAULT_LIGHT_MASK);
+        // the white ink pixel at the aim written before reading the port
+        ula.memory_mut().write(0x4002, 0x80);
+        ula.memory_mut().write(0x5802, 0x07);
+        assert_eq!(ula.read_io(0xEFFE, ts).0 & DEFAULT_LIGHT_MASK, 0);
+        // the same pixel is dark on the shadow screen
+        let flags = Ula128MemFlags::with_last_ram_page_bank(Ula128MemFlags::SCREEN_BANK, 7);
+        assert!(ula.set_ula128_mem_port_value(flags));
+        assert_eq!(ula.read_io(0xEFFE, ts).0 & DEFAULT_LIGHT_MASK, DEFAULT_LIGHT_MASK);
+        ula.memory_mut().write(0xC002, 0x80);
+        ula.memory_mut().write(0xD802, 0x07);
+        assert_eq!(ula.read_io(0xEFFE, ts).0 & DEFAULT_LIGHT_MASK, 0);
+    }
+
+    #[cfg(feature = "snapshot")]
+    #[test]
+    fn test_ula128_delta_memory_snapshot() {
+        use crate::memory::{MemoryKind, PowerOnMemory};
+        let mut ula: Ula128 = Default::default();
+        let base = PowerOnMemory::Random(42);
+        ula.memory_mut().fill_ram_power_on(base);
+        let flags = Ula128MemFlags::with_last_ram_page_bank(Ula128MemFlags::empty(), 3);
+        ula.set_ula128_mem_port_value(flags);
+        ula.memory_mut().load_into(0xC000, b"delta snapshot").unwrap();
+        let full = serde_json::to_string(&ula).unwrap();
+        assert!(ula.memory_mut().set_serde_delta_base(Some(base)));
+        assert_eq!(ula.memory_ref().serde_delta_base(), Some(base));
+        let delta = serde_json::to_string(&ula).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&delta).unwrap();
+        let mem = &value["ula"]["memory"]["mem"];
+        assert_eq!(mem["base"], serde_json::json!({"random": 42}));
+        // only the modified bank is stored: RAM bank 3 follows 2 ROM banks
+        let banks: Vec<_> = mem["banks"].as_object().unwrap().keys().collect();
+        assert_eq!(banks, ["5"]);
+        assert!(delta.len() < full.len());
+        #[cfg(not(feature = "compression"))]
+        assert!(delta.len() * 8 < full.len());
+        for json in [&delta, &full] {
+            let restored: Ula128 = serde_json::from_str(json).unwrap();
+            assert_eq!(restored.memory_ref().serde_delta_base(), None);
+            assert_eq!(restored.memory_ref().mem_ref(), ula.memory_ref().mem_ref());
+            assert_eq!(restored.ula128_mem_port_value(), Some(flags));
+            for bank in 0..8 {
+                assert_eq!(restored.memory_ref().bank_checksum(MemoryKind::Ram, bank).unwrap(),
+                           ula.memory_ref().bank_checksum(MemoryKind::Ram, bank).unwrap());
+            }
+        }
+        // the delta differs from a reference with another pattern
+        assert!(ula.memory_mut().set_serde_delta_base(Some(PowerOnMemory::Zeroed)));
+        let value: serde_json::Value = serde_json::to_value(&ula).unwrap();
+        assert_eq!(value["ula"]["memory"]["mem"]["banks"].as_object().unwrap().len(), 8);
+        let restored: Ula128 = serde_json::from_value(value).unwrap();
+        assert_eq!(restored.memory_ref().mem_ref(), ula.memory_ref().mem_ref());
+        // binary serializers always store the whole memory
+        let bin = bincode::serialize(&ula).unwrap();
+        ula.memory_mut().set_serde_delta_base(None);
+        assert_eq!(bin, bincode::serialize(&ula).unwrap());
+    }
 }
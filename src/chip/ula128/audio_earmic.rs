@@ -45,7 +45,8 @@ impl<B, D, X> AudioFrame<B> for Ula128<D, X>
 }
 
 impl<B, D, X> EarMicOutAudioFrame<B> for Ula128<D, X>
-    where B: Blep
+    where B: Blep,
+          B::SampleDelta: MulNorm + FromSample<f32>
 {
     #[inline(always)]
     fn render_earmic_out_audio_frame<L: AmpLevels<B::SampleDelta>>(&self, blep: &mut B, channel: usize) {
@@ -54,7 +55,8 @@ impl<B, D, X> EarMicOutAudioFrame<B> for Ula128<D, X>
 }
 
 impl<B, D, X> EarInAudioFrame<B> for Ula128<D, X>
-    where B: Blep
+    where B: Blep,
+          B::SampleDelta: MulNorm + FromSample<f32>
 {
     #[inline(always)]
     fn render_ear_in_audio_frame<L: AmpLevels<B::SampleDelta>>(&self, blep: &mut B, channel: usize) {
@@ -46,7 +46,7 @@ impl<B, X> Io for Ula128<B, X>
             (data, None)
         }
         else {
-            self.ula.ula_read_io(port, ts)
+            self.ula.ula_read_io(port, ts, self.cur_screen_shadow.into())
                     .unwrap_or_else(|| (self.floating_bus(ts), None))
         }
     }
@@ -76,13 +76,13 @@ impl<B, X> Memory for Ula128<B, X>
     }
 
     #[inline(always)]
-    fn read_mem(&self, addr: u16, _ts: VideoTs) -> u8 {
-        self.ula.memory.read(addr)
+    fn read_mem(&self, addr: u16, ts: VideoTs) -> u8 {
+        self.ula.memext.read_mem(addr, Ula128VidFrame::vc_hc_to_tstates(ts.vc, ts.hc), &self.ula.memory)
     }
 
     #[inline(always)]
-    fn read_mem16(&self, addr: u16, _ts: VideoTs) -> u16 {
-        self.ula.memory.read16(addr)
+    fn read_mem16(&self, addr: u16, ts: VideoTs) -> u16 {
+        self.ula.memext.read_mem16(addr, Ula128VidFrame::vc_hc_to_tstates(ts.vc, ts.hc), &self.ula.memory)
     }
 
     #[inline]
@@ -94,7 +94,7 @@ impl<B, X> Memory for Ula128<B, X>
     #[inline]
     fn write_mem(&mut self, addr: u16, val: u8, ts: VideoTs) {
         self.update_frame_cache(addr, ts);
-        self.ula.memory.write(addr, val);
+        self.ula.memext.write_mem(addr, val, Ula128VidFrame::vc_hc_to_tstates(ts.vc, ts.hc), &mut self.ula.memory);
     }
 }
 
@@ -121,7 +121,10 @@ impl<B, X> Ula128<B, X> {
 
     #[inline]
     fn floating_bus(&self, ts: VideoTs) -> u8 {
-        if let Some(addr) = Ula128VidFrame::floating_bus_screen_address(ts) {
+        if !self.ula.floating_bus {
+            u8::MAX
+        }
+        else if let Some(addr) = Ula128VidFrame::floating_bus_screen_address(ts) {
             self.ula.memory.read_screen(self.cur_screen_shadow.into(), addr)
         }
         else {
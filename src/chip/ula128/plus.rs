@@ -66,6 +66,10 @@ impl<'a, B, X> UlaPlusInner<'a> for Ula128<B, X>
         self.cur_screen_shadow
     }
 
+    fn is_attr_clash_reduced(&self) -> bool {
+        self.ula.reduce_clash
+    }
+
 
     fn video_render_data_view(
         &mut self
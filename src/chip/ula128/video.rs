@@ -146,6 +146,10 @@ impl<D, X> Video for Ula128<D, X> {
     fn flash_state(&self) -> bool {
         self.ula.flash_state()
     }
+
+    fn dirty_lines(&self) -> Option<u32> {
+        self.ula.dirty_lines()
+    }
 }
 
 impl<B, X> Ula128<B, X> {
@@ -166,13 +170,14 @@ impl<B, X> Ula128<B, X> {
         }
         else {
             let coords = color_address_coords(addr);
-            frame_cache.update_frame_colors(&self.ula.memory, coords, addr, ts);
+            frame_cache.update_frame_colors_with_mode(&self.ula.memory, coords, addr, ts, self.ula.reduce_clash);
         }
+        self.ula.mark_dirty_screen_address(addr);
     }
 
     #[inline(always)]
     pub(super) fn update_snow_interference(&mut self, ts: VideoTs, ir: u16) {
-        if self.memory_contention().is_contended_address(ir) {
+        if self.ula.snow_effect && self.memory_contention().is_contended_address(ir) {
             if let Some(coords) = Ula128VidFrame::snow_interference_coords(ts) {
                 let (screen, frame_cache) = if self.cur_screen_shadow {
                     (self.ula.memory.screen_ref(1).unwrap(), &mut self.shadow_frame_cache)
@@ -181,6 +186,7 @@ impl<B, X> Ula128<B, X> {
                     (self.ula.memory.screen_ref(0).unwrap(), &mut self.ula.frame_cache)
                 };
                 frame_cache.apply_snow_interference(screen, coords, ir as u8);
+                self.ula.mark_dirty_pixel_row(coords.row);
             }
         }
     }
@@ -325,4 +331,22 @@ mod tests {
             assert_eq!(vts1.saturating_add(vts0), addvts);
         }
     }
+
+    #[test]
+    fn test_ts_to_pixel_position() {
+        let items = [((  0, -73), None),
+                     (( 14,   0), None),
+                     (( 15, -25), None),
+                     (( 15, -24), Some((  0,   0))),
+                     (( 63,   0), Some(( 48,  48))),
+                     ((100, -10), Some(( 28,  85))),
+                     ((254, 127), Some((302, 239))),
+                     ((302, 151), Some((350, 287))),
+                     ((302, 152), None),
+                     ((303,   0), None),
+                     ((310, 154), None)];
+        for ((vc, hc), pos) in items.iter().copied() {
+            assert_eq!(TestVideoFrame::ts_to_pixel_position(VideoTs::new(vc, hc)), pos);
+        }
+    }
 }
@@ -24,7 +24,7 @@ use crate::z80emu::{*, host::Result};
 use crate::bus::{BusDevice, VFNullDevice};
 use crate::clock::{VFrameTs, VideoTs, VFrameTsCounter, MemoryContention};
 use crate::chip::{
-    Ula128MemFlags, Ula3CtrlFlags, Ula3Paging, UlaControl,
+    Ula128MemFlags, Ula3CtrlFlags, Ula3Paging, UlaControl, DEFAULT_INTERRUPT_LENGTH_128, trace::{InstructionTrace, TraceEntry},
     InnerAccess, EarIn, ReadEarMode, ControlUnit, MemoryAccess,
     ula::{
         Ula, UlaControlExt, UlaCpuExt,
@@ -78,6 +78,10 @@ pub struct Ula3<B=VFNullDevice<Ula3VidFrame>, X=NoMemoryExtension> {
     beg_screen_shadow: bool,  // shadow screen when a frame began
     cur_screen_shadow: bool,  // current shadow screen
     mem_locked: bool,
+    #[cfg_attr(feature = "snapshot", serde(default))]
+    disc_motor: bool,         // the last DISC_MOTOR bit of 0x1FFD
+    #[cfg_attr(feature = "snapshot", serde(default))]
+    printer_strobe: bool,     // the last PRINTER_STROBE bit of 0x1FFD
 
     #[cfg(feature = "boxed_frame_cache")]
     #[cfg_attr(feature = "snapshot", serde(skip))]
@@ -102,6 +106,7 @@ impl<B: Default, X: Default> Default for Ula3<B, X> {
     fn default() -> Self {
         let mut ula = Ula::default();
         ula.set_read_ear_mode(ReadEarMode::Clear);
+        ula.set_interrupt_length(DEFAULT_INTERRUPT_LENGTH_128);
         Ula3 {
             ula,
             mem_special_paging: None,
@@ -110,6 +115,8 @@ impl<B: Default, X: Default> Default for Ula3<B, X> {
             beg_screen_shadow: false,
             cur_screen_shadow: false,
             mem_locked: false,
+            disc_motor: false,
+            printer_strobe: false,
             shadow_frame_cache: Default::default(),
             screen_changes: Vec::new()
         }
@@ -128,6 +135,8 @@ impl<B, X> core::fmt::Debug for Ula3<B, X>
             .field("beg_screen_shadow", &self.beg_screen_shadow)
             .field("cur_screen_shadow", &self.cur_screen_shadow)
             .field("mem_locked", &self.mem_locked)
+            .field("disc_motor", &self.disc_motor)
+            .field("printer_strobe", &self.printer_strobe)
             .field("shadow_frame_cache", &self.shadow_frame_cache)
             .field("screen_changes", &self.screen_changes.len())
             .finish()
@@ -192,6 +201,58 @@ impl<B, X> UlaControl for Ula3<B, X> {
         self.ula.set_late_timings(late_timings)
     }
 
+    fn interrupt_length(&self) -> Option<u8> {
+        self.ula.interrupt_length()
+    }
+
+    fn set_interrupt_length(&mut self, length: u8) -> bool {
+        self.ula.set_interrupt_length(length)
+    }
+
+    fn instruction_trace_capacity(&self) -> Option<usize> {
+        self.ula.instruction_trace_capacity()
+    }
+
+    fn set_instruction_trace(&mut self, capacity: Option<usize>) -> bool {
+        self.ula.set_instruction_trace(capacity)
+    }
+
+    fn instruction_trace(&self) -> Option<Vec<TraceEntry>> {
+        self.ula.instruction_trace()
+    }
+
+    fn cpu_turbo(&self) -> Option<u8> {
+        self.ula.cpu_turbo()
+    }
+
+    fn set_cpu_turbo(&mut self, multiplier: u8) -> bool {
+        self.ula.set_cpu_turbo(multiplier)
+    }
+
+    fn has_ear_mic_loopback(&self) -> Option<bool> {
+        self.ula.has_ear_mic_loopback()
+    }
+
+    fn set_ear_mic_loopback(&mut self, enabled: bool) -> bool {
+        self.ula.set_ear_mic_loopback(enabled)
+    }
+
+    fn tape_audio_gain(&self) -> Option<f32> {
+        self.ula.tape_audio_gain()
+    }
+
+    fn set_tape_audio_gain(&mut self, gain: f32) -> bool {
+        self.ula.set_tape_audio_gain(gain)
+    }
+
+    fn has_attr_clash_reduction(&self) -> Option<bool> {
+        self.ula.has_attr_clash_reduction()
+    }
+
+    fn set_attr_clash_reduction(&mut self, reduce_clash: bool) -> bool {
+        self.ula.set_attr_clash_reduction(reduce_clash)
+    }
+
     fn ula128_mem_port_value(&self) -> Option<Ula128MemFlags> {
         let mut flags = Ula128MemFlags::empty()
                         .with_last_ram_page_bank(self.mem_page3_bank.into());
@@ -214,13 +275,15 @@ impl<B, X> UlaControl for Ula3<B, X> {
     }
 
     fn ula3_ctrl_port_value(&self) -> Option<Ula3CtrlFlags> {
-        let flags = if let Some(paging) = self.mem_special_paging {
+        let mut flags = if let Some(paging) = self.mem_special_paging {
             Ula3CtrlFlags::with_special_paging(Ula3CtrlFlags::empty(), paging)
         }
         else {
             Ula3CtrlFlags::with_rom_page_bank_hi(Ula3CtrlFlags::empty(),
                 self.rom_bank.into())
         };
+        flags.set(Ula3CtrlFlags::DISC_MOTOR, self.disc_motor);
+        flags.set(Ula3CtrlFlags::PRINTER_STROBE, self.printer_strobe);
         Some(flags)
     }
 
@@ -332,6 +395,7 @@ impl<B, X> Ula3<B, X> {
         if self.cur_screen_shadow != cur_screen_shadow {
             self.cur_screen_shadow = cur_screen_shadow;
             self.screen_changes.push(ts);
+            self.ula.mark_all_lines_dirty();
         }
         let rom_lo = flags.intersects(Ula128MemFlags::ROM_BANK);
         let page3_bank = MemPage8::from(flags);
@@ -340,6 +404,8 @@ impl<B, X> Ula3<B, X> {
     }
     // Returns `true` if the memory contention has changed.
     fn set_mem2_port_value(&mut self, flags: Ula3CtrlFlags) -> bool {
+        self.disc_motor = flags.is_disc_motor_on();
+        self.printer_strobe = flags.is_printer_strobe_on();
         if let Some(paging) = flags.special_paging() {
             self.set_mem_special_paging(paging)
         }
@@ -409,6 +475,8 @@ impl<B, X> ControlUnit for Ula3<B, X>
             }
             self.cur_screen_shadow = false;
             self.mem_locked = false;
+            self.disc_motor = false;
+            self.printer_strobe = false;
         }
     }
 
@@ -448,11 +516,28 @@ impl<B, X> UlaControlExt for Ula3<B, X>
         self.screen_changes.clear();
         self.ula.prepare_next_frame(vtsc)
     }
+
+    fn cpu_turbo_multiplier(&self) -> u8 {
+        self.ula.cpu_turbo_multiplier()
+    }
+
+    fn cpu_turbo_remainder_mut(&mut self) -> &mut u8 {
+        self.ula.cpu_turbo_remainder_mut()
+    }
+
+    fn instruction_trace_mut(&mut self) -> &mut Option<InstructionTrace> {
+        self.ula.instruction_trace_mut()
+    }
+
+    fn rom_trap<C: Cpu>(&mut self, cpu: &mut C) -> bool {
+        self.ula.rom_trap(cpu)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use core::iter;
+    use crate::memory::MemoryKind;
     use crate::video::{Video, VideoFrame};
     use super::*;
 
@@ -499,4 +584,76 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_ula3_special_paging() {
+        let mut ula: Ula3 = Default::default();
+        for bank in 0..8 {
+            ula.memory_mut().ram_bank_mut(bank).unwrap().fill(bank as u8);
+        }
+        let ts = VideoTs::default();
+        // RAM bank 6 at the last page, ROM 3 selected by 0x7ffd b4 and 0x1ffd b2 with no special paging
+        assert_eq!(ula.write_io(0x7ffd, 0b1_0110, ts), (Some(()), None));
+        ula.write_io(0x1ffd, 0b0_0100, ts);
+        assert_eq!(ula.memory_ref().page_bank(0).unwrap(), (MemoryKind::Rom, 3));
+        for (paging, banks, data) in [(Ula3Paging::Banks0123, [0, 1, 2, 3], 0b0_0001),
+                                      (Ula3Paging::Banks4567, [4, 5, 6, 7], 0b0_0011),
+                                      (Ula3Paging::Banks4563, [4, 5, 6, 3], 0b0_0101),
+                                      (Ula3Paging::Banks4763, [4, 7, 6, 3], 0b0_0111)]
+        {
+            assert_eq!(Ula3CtrlFlags::from_data(data).special_paging(), Some(paging));
+            ula.write_io(0x1ffd, data, ts);
+            assert_eq!(ula.mem_special_paging, Some(paging));
+            for (page, bank) in (0..4).zip(banks) {
+                let addr = page as u16 * 0x4000;
+                assert_eq!(ula.memory_ref().page_bank(page).unwrap(), (MemoryKind::Ram, bank));
+                assert_eq!(ula.memory_ref().read(addr + 1), bank as u8);
+                // all pages are writable
+                ula.memory_mut().write(addr + 2, 0x80 | bank as u8);
+                assert_eq!(ula.memory_ref().ram_bank_ref(bank).unwrap()[2], 0x80 | bank as u8);
+            }
+        }
+        // back to the normal paging with the previous ROM and RAM selection
+        ula.write_io(0x1ffd, 0b0_0100, ts);
+        assert_eq!(ula.mem_special_paging, None);
+        assert_eq!(ula.memory_ref().page_bank(0).unwrap(), (MemoryKind::Rom, 3));
+        for (page, bank) in (1..4).zip([5, 2, 6]) {
+            assert_eq!(ula.memory_ref().page_bank(page).unwrap(), (MemoryKind::Ram, bank));
+        }
+    }
+
+    #[cfg(feature = "snapshot")]
+    #[test]
+    fn test_ula3_ctrl_port_snapshot() {
+        let mut ula: Ula3 = Default::default();
+        let mem1 = Ula128MemFlags::with_last_ram_page_bank(Ula128MemFlags::empty(), 6)
+                   | Ula128MemFlags::SCREEN_BANK | Ula128MemFlags::ROM_BANK;
+        ula.set_ula128_mem_port_value(mem1);
+        let mem2 = Ula3CtrlFlags::with_special_paging(Ula3CtrlFlags::empty(), Ula3Paging::Banks4763)
+                   | Ula3CtrlFlags::DISC_MOTOR | Ula3CtrlFlags::PRINTER_STROBE;
+        ula.set_ula3_ctrl_port_value(mem2);
+        assert_eq!(ula.ula128_mem_port_value(), Some(mem1));
+        assert_eq!(ula.ula3_ctrl_port_value(), Some(mem2));
+
+        let json = serde_json::to_string(&ula).unwrap();
+        let restored: Ula3 = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.mem_special_paging, Some(Ula3Paging::Banks4763));
+        assert_eq!(restored.ula128_mem_port_value(), Some(mem1));
+        assert_eq!(restored.ula3_ctrl_port_value(), Some(mem2));
+        assert_eq!(restored.memory_contention(), ula.memory_contention());
+        for addr in (0x0000..=0xFFFF).step_by(0x100) {
+            assert_eq!(restored.memory_ref().read(addr), ula.memory_ref().read(addr));
+        }
+        // the same configuration restored from the port values only
+        let mut restored: Ula3 = Default::default();
+        restored.set_ula128_mem_port_value(mem1);
+        restored.set_ula3_ctrl_port_value(mem2);
+        assert_eq!(restored.ula3_ctrl_port_value(), Some(mem2));
+        assert_eq!(restored.memory_contention(), ula.memory_contention());
+        // motor off and back to the normal paging
+        let mem2 = Ula3CtrlFlags::with_rom_page_bank_hi(Ula3CtrlFlags::empty(), 2);
+        restored.set_ula3_ctrl_port_value(mem2);
+        assert_eq!(restored.ula3_ctrl_port_value(), Some(mem2));
+        assert_eq!(restored.ula128_mem_port_value(), Some(mem1));
+    }
 }
@@ -13,6 +13,7 @@ use crate::clock::{VideoTs, VFrameTs};
 use crate::chip::{Ula128MemFlags, Ula3CtrlFlags};
 use crate::peripherals::{KeyboardInterface, ZXKeyboardMap};
 use crate::memory::{ZxMemory, MemoryExtension};
+use crate::video::VideoFrame;
 use super::{Ula3, Ula3VidFrame};
 
 #[derive(Clone, Copy, Default, Debug)]
@@ -64,7 +65,7 @@ impl<B, X> Io for Ula3<B, X>
     }
 
     fn read_io(&mut self, port: u16, ts: VideoTs) -> (u8, Option<NonZeroU16>) {
-        self.ula.ula_read_io(port, ts)
+        self.ula.ula_read_io(port, ts, self.cur_screen_shadow.into())
                 .unwrap_or((u8::max_value(), None))
     }
 
@@ -104,13 +105,13 @@ impl<B, X> Memory for Ula3<B, X>
     }
 
     #[inline(always)]
-    fn read_mem(&self, addr: u16, _ts: VideoTs) -> u8 {
-        self.ula.memory.read(addr)
+    fn read_mem(&self, addr: u16, ts: VideoTs) -> u8 {
+        self.ula.memext.read_mem(addr, Ula3VidFrame::vc_hc_to_tstates(ts.vc, ts.hc), &self.ula.memory)
     }
 
     #[inline(always)]
-    fn read_mem16(&self, addr: u16, _ts: VideoTs) -> u16 {
-        self.ula.memory.read16(addr)
+    fn read_mem16(&self, addr: u16, ts: VideoTs) -> u16 {
+        self.ula.memext.read_mem16(addr, Ula3VidFrame::vc_hc_to_tstates(ts.vc, ts.hc), &self.ula.memory)
     }
 
     #[inline(always)]
@@ -121,7 +122,7 @@ impl<B, X> Memory for Ula3<B, X>
     #[inline]
     fn write_mem(&mut self, addr: u16, val: u8, ts: VideoTs) {
         self.update_frame_cache(addr, ts);
-        self.ula.memory.write(addr, val);
+        self.ula.memext.write_mem(addr, val, Ula3VidFrame::vc_hc_to_tstates(ts.vc, ts.hc), &mut self.ula.memory);
     }
 }
 
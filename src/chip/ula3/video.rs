@@ -119,6 +119,10 @@ impl<D, X> Video for Ula3<D, X> {
     fn flash_state(&self) -> bool {
         self.ula.flash_state()
     }
+
+    fn dirty_lines(&self) -> Option<u32> {
+        self.ula.dirty_lines()
+    }
 }
 
 impl<B, X> Ula3<B, X> {
@@ -140,8 +144,9 @@ impl<B, X> Ula3<B, X> {
         }
         else {
             let coords = color_address_coords(addr);
-            frame_cache.update_frame_colors(&self.ula.memory, coords, addr, ts);
+            frame_cache.update_frame_colors_with_mode(&self.ula.memory, coords, addr, ts, self.ula.reduce_clash);
         }
+        self.ula.mark_dirty_screen_address(addr);
     }
 }
 
@@ -57,6 +57,7 @@ Additional features:
 * `"compression"` - enables gzip compression/decompression of memory chunks stored in snapshots.
 * `"boxed_frame_cache"` - chipset implementations will have significantly reduced struct sizes
   by boxing their internal [UlaFrameCache] instances at the cost of a minimal performance penalty.
+* `"image"` - enables saving of the [captured][video::capture] video frames as **PNG** images.
 * `"sdl2"` - enables audio implementation for [SDL2] hardware abstraction layer.
 * `"cpal"` - enables audio implementation for [cpal] native audio library.
 
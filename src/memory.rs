@@ -69,7 +69,7 @@ Page 1 +--------+  +--------+ Page 1 0x4000
 RAMTOP |        |                    0xffff
        +--------+
 ```
-A memory map with example banks switched in for [Memory128k] or [Memory128kPlus]:
+A memory map with example banks switched in for [Memory128k], [Memory128kPlus] or [Memory256kPlus]:
 ```text
 Page 0 +--------+ 0x1000
        |        |
@@ -21,6 +21,7 @@ use super::{
     ZxMemory,
     ZxMemoryError,
     MemoryKind,
+    PowerOnMemory,
     ExRom,
     SCREEN_SIZE,
     ScreenArray,
@@ -35,6 +36,8 @@ pub type Memory48kEx = MemPageableRomRamExRom<MEM64K_SIZE, MEM16K_SIZE, {MEM64K_
 pub type Memory128k = MemPageableRomRamExRom<{MEM32K_SIZE + MEM128K_SIZE}, MEM16K_SIZE, {MEM64K_SIZE/MEM16K_SIZE}>;
 /// An EX-ROM attachable, paged (16k) memory type with 128kb RAM and 64kb ROM.
 pub type Memory128kPlus = MemPageableRomRamExRom<{MEM64K_SIZE + MEM128K_SIZE}, MEM16K_SIZE, {MEM64K_SIZE/MEM16K_SIZE}>;
+/// An EX-ROM attachable, paged (16k) memory type with 256kb RAM and 64kb ROM.
+pub type Memory256kPlus = MemPageableRomRamExRom<{MEM64K_SIZE + MEM128K_SIZE + MEM128K_SIZE}, MEM16K_SIZE, {MEM64K_SIZE/MEM16K_SIZE}>;
 /// An EX-ROM attachable, paged (8k) memory type with 48kb RAM and 96kb ROM (64kb DOCK, 8kB EX-ROM, 16kB ROM).
 pub type Memory48kDock64kEx = MemPageableRomRamExRom<{MEM8K_SIZE + MEM128K_SIZE}, MEM8K_SIZE, {MEM64K_SIZE/MEM8K_SIZE}>;
 /// An EX-ROM attachable, paged (8k) memory type with 272kb RAM and 32kb ROM.
@@ -47,10 +50,13 @@ impl PagedMemory16k for Memory16kEx {}
 impl PagedMemory16k for Memory48kEx {}
 impl PagedMemory16k for Memory128k {}
 impl PagedMemory16k for Memory128kPlus {}
+impl PagedMemory16k for Memory256kPlus {}
 impl PagedMemory8k for Memory48kDock64kEx {}
 impl PagedMemory8k for Memory272k {}
 
 const SCLD_SCREEN_OFFSET: usize = 0x2000;
+/// The value of each memory byte of the newly created memory.
+const POWER_ON_BYTE: u8 = !0;
 
 // NUM_PAGES is covariant to PAGE_SIZE but const generics with complex expressions aren't available yet
 type Memory<const MEM_SIZE: usize> = [u8; MEM_SIZE];
@@ -67,6 +73,7 @@ pub struct MemPageableRomRamExRom<const MEM_SIZE: usize, const PAGE_SIZE: usize,
     ro_pages: u8,
     pages: MemoryPages<PAGE_SIZE, NUM_PAGES>,
     ex_rom: Option<ExRomAttachment<PAGE_SIZE>>,
+    delta_base: Option<PowerOnMemory>,
 }
 
 #[doc(hidden)]
@@ -132,6 +139,7 @@ memory_config!(MEM16K_SIZE, MEM48K_SIZE, 2, 1, [ROM 0, RAM 0, ROM 1, ROM 1], [0]
 memory_config!(MEM16K_SIZE, MEM64K_SIZE, 1, 3, [ROM 0, RAM 0, RAM 1, RAM 2], [0]);
 memory_config!(MEM16K_SIZE, MEM32K_SIZE + MEM128K_SIZE, 2, 8, [ROM 0, RAM 5, RAM 2, RAM 0], [5, 7]);
 memory_config!(MEM16K_SIZE, MEM64K_SIZE + MEM128K_SIZE, 4, 8, [ROM 0, RAM 5, RAM 2, RAM 0], [5, 7]);
+memory_config!(MEM16K_SIZE, MEM64K_SIZE + MEM128K_SIZE + MEM128K_SIZE, 4, 16, [ROM 0, RAM 5, RAM 2, RAM 0], [5, 7]);
 memory_config!(MEM8K_SIZE, MEM8K_SIZE + MEM128K_SIZE, 11, 6,
                                         [ROM 9, ROM 10, RAM 0, RAM 1, RAM 2, RAM 3, RAM 4, RAM 5], [0]);
 memory_config!(MEM8K_SIZE, MEM48K_SIZE + MEM128K_SIZE + MEM128K_SIZE, 4, 34,
@@ -160,10 +168,11 @@ impl<const MEM_SIZE: usize, const PAGE_SIZE: usize, const NUM_PAGES: usize
 {
     fn default() -> Self {
         let mut mem = MemPageableRomRamExRom {
-            mem: Box::new([!0; MEM_SIZE]),
+            mem: Box::new([POWER_ON_BYTE; MEM_SIZE]),
             pages: MemoryPages::new(),
             ex_rom: None,
-            ro_pages: 0
+            ro_pages: 0,
+            delta_base: None
         };
         mem.reset_banks();
         mem
@@ -200,7 +209,7 @@ impl<const MEM_SIZE: usize,
         if let Some(exr) = ex_rom.as_mut() { // now swap pointers back
             core::mem::swap(&mut exr.ptr, pages.page_mut(exr.page));
         }
-        MemPageableRomRamExRom { mem, pages, ex_rom, ro_pages }
+        MemPageableRomRamExRom { mem, pages, ex_rom, ro_pages, delta_base: self.delta_base }
     }
 }
 
@@ -580,13 +589,20 @@ impl<const MEM_SIZE: usize,
     fn has_mapped_exrom(&self, exrom_bank: &ExRom) -> bool {
         self.is_exrom_attached(exrom_bank)
     }
+    fn set_serde_delta_base(&mut self, base: Option<PowerOnMemory>) -> bool {
+        self.delta_base = base;
+        true
+    }
+    fn serde_delta_base(&self) -> Option<PowerOnMemory> {
+        self.delta_base
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use rand::{Rng, SeedableRng};
     use rand::rngs::SmallRng;
-    use crate::memory::MemPageOffset;
+    use crate::memory::{MemPageOffset, PowerOnMemory};
     use super::*;
 
     fn page_mem_offset(mem: &Memory128k, page: u8) -> usize {
@@ -1184,4 +1200,89 @@ mod tests {
         test_page(&mem3, 2, b"RAM2");
         test_page(&mem3, 3, b"RAM0");
     }
+
+    #[test]
+    fn memory_load_into_dump_range_work() {
+        let mut mem = Memory128k::default();
+        let rom = mem.rom_ref().to_vec();
+        let data: Vec<u8> = (1..=32).collect();
+        mem.load_into(0x7ff0, &data).unwrap();
+        assert_eq!(mem.dump_range(0x7ff0..0x8010).unwrap(), data);
+        assert_eq!(&mem.ram_bank_ref(5).unwrap()[0x3ff0..], &data[..16]);
+        assert_eq!(&mem.ram_bank_ref(2).unwrap()[..16], &data[16..]);
+        mem.load_into(0x3ff0, &data).unwrap();
+        assert_eq!(mem.dump_range(0x3ff0..0x4010).unwrap()[..16], rom[0x3ff0..0x4000]);
+        assert_eq!(mem.dump_range(0x3ff0..0x4010).unwrap()[16..], data[16..]);
+        assert!(mem.rom_ref() == rom);
+        let top = mem.dump_range(0xfff0..).unwrap();
+        assert_eq!(top.len(), 16);
+        assert!(matches!(mem.load_into(0xfff0, &data), Err(ZxMemoryError::UnsupportedAddressRange)));
+        assert_eq!(mem.dump_range(0xfff0..).unwrap(), top);
+        mem.load_into(0xffe0, &data).unwrap();
+        assert_eq!(mem.dump_range(0xffe0..=0xffff).unwrap(), data);
+        assert!(mem.dump_range(0x8000..0x8000).unwrap().is_empty());
+    }
+
+    #[test]
+    fn memory_find_bytes_work() {
+        let mut mem = Memory128k::default();
+        let pattern = b"\x13\x37\xC0\xDE";
+        mem.load_into(0x6000, pattern).unwrap();
+        mem.load_into(0x7ffe, pattern).unwrap();
+        assert_eq!(mem.find_bytes(pattern, ..).unwrap(), [0x6000, 0x7ffe]);
+        assert_eq!(mem.find_bytes(pattern, 0x6001..).unwrap(), [0x7ffe]);
+        assert_eq!(mem.find_bytes(pattern, 0x6000..0x8001).unwrap(), [0x6000]);
+        assert!(mem.find_bytes(b"\x13\x37\xC0\xDF", ..).unwrap().is_empty());
+        assert!(mem.find_bytes(b"", ..).unwrap().is_empty());
+        assert_eq!(mem.find_bytes_in_ram_bank(pattern, 5).unwrap(), [0x2000]);
+        assert_eq!(mem.find_bytes_in_ram_bank(&pattern[2..], 2).unwrap(), [0]);
+        // bank 7 is not paged in
+        mem.ram_bank_mut(7).unwrap()[0x100..0x104].copy_from_slice(pattern);
+        assert_eq!(mem.find_bytes(pattern, ..).unwrap(), [0x6000, 0x7ffe]);
+        assert_eq!(mem.find_bytes_in_ram_bank(pattern, 7).unwrap(), [0x100]);
+        assert!(mem.find_bytes_in_ram_bank(pattern, 8).is_err());
+    }
+
+    #[test]
+    fn memory_bank_checksum_works() {
+        let mut mem = Memory128k::default();
+        let rom0 = mem.bank_checksum(MemoryKind::Rom, 0).unwrap();
+        let ram2 = mem.bank_checksum(MemoryKind::Ram, 2).unwrap();
+        let ram5 = mem.bank_checksum(MemoryKind::Ram, 5).unwrap();
+        assert_eq!(mem.bank_checksum(MemoryKind::Ram, 5).unwrap(), ram5);
+        let byte = mem.read(0x4000);
+        mem.write(0x4000, !byte);
+        let ram5_mod = mem.bank_checksum(MemoryKind::Ram, 5).unwrap();
+        assert_ne!(ram5_mod, ram5);
+        assert_eq!(mem.bank_checksum(MemoryKind::Ram, 2).unwrap(), ram2);
+        assert_eq!(mem.bank_checksum(MemoryKind::Rom, 0).unwrap(), rom0);
+        mem.write(0x4000, byte);
+        assert_eq!(mem.bank_checksum(MemoryKind::Ram, 5).unwrap(), ram5);
+        let ram7 = mem.bank_checksum(MemoryKind::Ram, 7).unwrap();
+        mem.ram_bank_mut(7).unwrap()[0x3fff] ^= 0x80;
+        assert_ne!(mem.bank_checksum(MemoryKind::Ram, 7).unwrap(), ram7);
+        assert!(mem.bank_checksum(MemoryKind::Ram, 8).is_err());
+        assert!(mem.bank_checksum(MemoryKind::Rom, 2).is_err());
+    }
+
+    #[test]
+    fn memory_fill_ram_power_on_work() {
+        let mut mem1 = Memory128k::default();
+        let mut mem2 = Memory128k::default();
+        let rom = mem1.rom_ref().to_vec();
+        mem1.fill_ram_power_on(PowerOnMemory::Random(42));
+        mem2.fill_ram_power_on(PowerOnMemory::Random(42));
+        assert!(mem1.ram_ref() == mem2.ram_ref());
+        assert!(mem1.rom_ref() == rom);
+        assert!(mem1.ram_ref().iter().any(|&b| b != mem1.ram_ref()[0]));
+        mem2.fill_ram_power_on(PowerOnMemory::Random(43));
+        assert!(mem1.ram_ref() != mem2.ram_ref());
+        mem1.fill_ram_power_on(PowerOnMemory::Zeroed);
+        assert!(mem1.ram_ref().iter().all(|&b| b == 0));
+        mem1.fill_ram_power_on(PowerOnMemory::Ones);
+        assert!(mem1.ram_ref().iter().all(|&b| b == 0xFF));
+        mem1.fill_ram_power_on(PowerOnMemory::Checkerboard);
+        assert_eq!(&mem1.ram_ref()[..4], [0x55, 0xAA, 0x55, 0xAA]);
+        assert!(mem1.rom_ref() == rom);
+    }
 }
@@ -5,28 +5,129 @@
 
     For the full copyright notice, see the lib.rs file.
 */
-use core::convert::TryFrom;
+use core::convert::{TryFrom, TryInto};
+use core::fmt;
+use std::collections::BTreeMap;
 use serde::{Serialize, Deserialize};
-use serde::ser::{self, Serializer, SerializeStruct};
-use serde::de::{self, Deserializer};
+use serde::ser::{self, Serializer, SerializeStruct, SerializeMap};
+use serde::de::{self, Deserializer, Visitor, MapAccess};
 use super::super::arrays;
 use super::{
     ExRom, MemoryConfig, MemoryBox, MemoryPages, MemPageableRomRamExRom, MemSerExt,
+    PowerOnMemory, ZxMemory,
+    POWER_ON_BYTE,
     cast_slice_as_bank_ptr,
     ro_flag_mask, serialize_mem, deserialize_mem};
 
+#[derive(Serialize)]
+#[serde(transparent)]
+struct MemSerWrap<'a, M>(
+    #[serde(serialize_with = "serialize_mem")] &'a M
+) where &'a M: MemSerExt;
+
+/// The memory banks that differ from the `reference`.
+struct MemDeltaBanks<'a, const PAGE_SIZE: usize> {
+    mem: &'a [u8],
+    reference: &'a [u8]
+}
+
+impl<'a, const PAGE_SIZE: usize> Serialize for MemDeltaBanks<'a, PAGE_SIZE> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        let reference = self.reference.chunks_exact(PAGE_SIZE);
+        for (index, (bank, ref_bank)) in self.mem.chunks_exact(PAGE_SIZE).zip(reference).enumerate() {
+            if bank != ref_bank {
+                let bank: &[u8;PAGE_SIZE] = bank.try_into().unwrap();
+                map.serialize_entry(&index, &MemSerWrap(bank))?;
+            }
+        }
+        map.end()
+    }
+}
+
+/// The delta memory format: the reference RAM pattern and the memory banks that differ from it.
+#[derive(Serialize)]
+struct MemDeltaSer<'a, const PAGE_SIZE: usize> {
+    base: PowerOnMemory,
+    banks: MemDeltaBanks<'a, PAGE_SIZE>
+}
+
+#[derive(Deserialize)]
+struct MemDeltaTemp<const PAGE_SIZE: usize> {
+    base: PowerOnMemory,
+    banks: BTreeMap<usize, MemBankTemp<PAGE_SIZE>>
+}
+
+/// The memory content deserialized from either format.
+enum MemOrDelta<const MEM_SIZE: usize, const PAGE_SIZE: usize> {
+    Mem(MemoryBox<MEM_SIZE>),
+    Delta(MemDeltaTemp<PAGE_SIZE>)
+}
+
+impl<const MEM_SIZE: usize, const PAGE_SIZE: usize> MemOrDelta<MEM_SIZE, PAGE_SIZE> {
+    fn into_mem<E: de::Error>(self, rom_size: usize) -> Result<MemoryBox<MEM_SIZE>, E> {
+        let MemDeltaTemp { base, banks } = match self {
+            MemOrDelta::Mem(mem) => return Ok(mem),
+            MemOrDelta::Delta(delta) => delta
+        };
+        let mut mem = Box::new([POWER_ON_BYTE;MEM_SIZE]);
+        base.fill(&mut mem[rom_size..]);
+        for (index, MemBankTemp(bank)) in banks {
+            let offset = index * PAGE_SIZE;
+            if offset >= MEM_SIZE {
+                return Err(de::Error::custom(format!("memory delta bank: {} larger than max: {}",
+                                                        index, MEM_SIZE / PAGE_SIZE - 1)));
+            }
+            mem[offset..offset + PAGE_SIZE].copy_from_slice(&bank[..]);
+        }
+        Ok(mem)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(transparent)]
+struct MemBankTemp<const PAGE_SIZE: usize>(
+    #[serde(deserialize_with = "deserialize_mem")]
+    Box<[u8;PAGE_SIZE]>
+);
+
+struct MemOrDeltaVisitor<const MEM_SIZE: usize, const PAGE_SIZE: usize>;
+
+impl<'de, const MEM_SIZE: usize, const PAGE_SIZE: usize> Visitor<'de> for MemOrDeltaVisitor<MEM_SIZE, PAGE_SIZE> {
+    type Value = MemOrDelta<MEM_SIZE, PAGE_SIZE>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a memory content string or a memory delta map")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        deserialize_mem(de::value::StrDeserializer::<E>::new(v)).map(MemOrDelta::Mem)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+        Deserialize::deserialize(de::value::MapAccessDeserializer::new(map)).map(MemOrDelta::Delta)
+    }
+}
+
+fn deserialize_mem_or_delta<'de, D, const MEM_SIZE: usize, const PAGE_SIZE: usize>(
+        deserializer: D
+    ) -> Result<MemOrDelta<MEM_SIZE, PAGE_SIZE>, D::Error>
+    where D: Deserializer<'de>
+{
+    if deserializer.is_human_readable() {
+        deserializer.deserialize_any(MemOrDeltaVisitor::<MEM_SIZE, PAGE_SIZE>)
+    }
+    else {
+        deserialize_mem(deserializer).map(MemOrDelta::Mem)
+    }
+}
+
 impl<const MEM_SIZE: usize, const PAGE_SIZE: usize, const NUM_PAGES: usize
     > Serialize for MemPageableRomRamExRom<MEM_SIZE, PAGE_SIZE, NUM_PAGES>
     where Self: MemoryConfig
 {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
 
-        #[derive(Serialize)]
-        #[serde(transparent)]
-        struct MemSerWrap<'a, M>(
-            #[serde(serialize_with = "serialize_mem")] &'a M
-        ) where &'a M: MemSerExt;
-
         #[derive(Serialize)]
         #[serde(transparent)]
         struct BanksWrap<const NUM_PAGES: usize>(
@@ -46,8 +147,19 @@ impl<const MEM_SIZE: usize, const PAGE_SIZE: usize, const NUM_PAGES: usize
             *b = u8::try_from(offset / PAGE_SIZE as isize)
                     .map_err(|err| ser::Error::custom(err.to_string()))?;
         }
+        let delta_base = self.delta_base.filter(|_| serializer.is_human_readable());
         let mut state = serializer.serialize_struct("MemPageableRomRamExRom", 3)?;
-        state.serialize_field("mem", &MemSerWrap(&self.mem))?;
+        if let Some(base) = delta_base {
+            let mut reference = vec![POWER_ON_BYTE; MEM_SIZE];
+            base.fill(&mut reference[Self::ROM_SIZE..]);
+            state.serialize_field("mem", &MemDeltaSer::<PAGE_SIZE> {
+                base,
+                banks: MemDeltaBanks { mem: &self.mem[..], reference: &reference }
+            })?;
+        }
+        else {
+            state.serialize_field("mem", &MemSerWrap(&self.mem))?;
+        }
         state.serialize_field("pages", &banks)?;
         state.serialize_field("exrom", &self.ex_rom)?;
         state.end()
@@ -63,9 +175,9 @@ impl<'de,
         where D: Deserializer<'de>
     {
         #[derive(Deserialize)]
-        struct MemTemp<const MEM_SIZE: usize, const NUM_PAGES: usize> {
-            #[serde(deserialize_with = "deserialize_mem")]
-            mem: MemoryBox<MEM_SIZE>,
+        struct MemTemp<const MEM_SIZE: usize, const PAGE_SIZE: usize, const NUM_PAGES: usize> {
+            #[serde(deserialize_with = "deserialize_mem_or_delta::<_, MEM_SIZE, PAGE_SIZE>")]
+            mem: MemOrDelta<MEM_SIZE, PAGE_SIZE>,
             #[serde(with = "arrays",rename(deserialize = "pages"))]
             banks: [u8;NUM_PAGES],
             exrom: Option<ExRomTemp>
@@ -78,7 +190,8 @@ impl<'de,
             rom: ExRom
         }
 
-        let MemTemp::<MEM_SIZE, NUM_PAGES> { mem, banks, exrom } = Deserialize::deserialize(deserializer)?;
+        let MemTemp::<MEM_SIZE, PAGE_SIZE, NUM_PAGES> { mem, banks, exrom } = Deserialize::deserialize(deserializer)?;
+        let mem = mem.into_mem(Self::ROM_SIZE)?;
 
         let mut pages = MemoryPages::<PAGE_SIZE, NUM_PAGES>::new();
         let mut ro_pages = 0;
@@ -95,7 +208,7 @@ impl<'de,
             *p = cast_slice_as_bank_ptr(&mem[offset..offset + PAGE_SIZE]);
         }
 
-        let mut res = MemPageableRomRamExRom { mem, pages, ro_pages, ex_rom: None};
+        let mut res = MemPageableRomRamExRom { mem, pages, ro_pages, ex_rom: None, delta_base: None };
         if let Some(ExRomTemp { page, rom }) = exrom {
             if rom.len() != PAGE_SIZE {
                 return Err(de::Error::custom(format!("attached ex-rom size incorrect: {} != {}",
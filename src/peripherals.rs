@@ -15,6 +15,10 @@ use bitflags::bitflags;
 #[cfg(feature = "peripherals")]
 pub use spectrusty_peripherals::*;
 
+/// The [Programmable Joystick][joystick::programmable::ProgrammableJoystickDevice] pressing Spectrum keys.
+#[cfg(feature = "peripherals")]
+pub type ProgrammableJoystick = joystick::programmable::ProgrammableJoystickDevice<ZXKeyboardMap>;
+
 bitflags! {
     /// Every key's state is encoded as a single bit on this 40-bit flag type.
     /// * Bit = 1 a key is being pressed.
@@ -135,4 +139,127 @@ mod tests {
     fn flags_all_bits_defined() {
         test_bitflags_all_bits_defined_no_masks!(ZXKeyboardMap, 40);
     }
+
+    #[cfg(feature = "peripherals")]
+    #[test]
+    fn programmable_joystick_works() {
+        use crate::chip::ula::UlaPAL;
+        use crate::memory::Memory48k;
+        use joystick::JoystickInterface;
+        type ZXk = ZXKeyboardMap;
+        let mut ula = UlaPAL::<Memory48k>::default();
+        // the Sinclair #1 mapping with the FIRE button on SPACE
+        let mut joy = ProgrammableJoystick::new([ZXk::N9, ZXk::N7, ZXk::N8, ZXk::N6, ZXk::BR]);
+        assert!(joy.key_state().is_empty());
+        joy.fire(0, true);
+        ula.set_key_state(joy.update_key_state(ZXk::A));
+        assert_eq!(ula.get_key_state(), ZXk::A|ZXk::BR);
+        assert_eq!(ula.get_key_state().read_keyboard(0x7f), 0b1111_1110);
+        joy.set_directions(joystick::Directions::UP|joystick::Directions::LEFT);
+        assert_eq!(joy.key_state(), ZXk::BR|ZXk::N9|ZXk::N6);
+        joy.fire(0, false);
+        joy.center();
+        ula.set_key_state(joy.update_key_state(ZXk::empty()));
+        assert!(ula.get_key_state().is_empty());
+    }
+
+    #[cfg(feature = "peripherals")]
+    #[test]
+    fn instant_microdrive_works() {
+        use std::io;
+        use crate::bus::{DynamicBus, VFNullDevice};
+        use crate::chip::{ControlUnit, FrameState, MemoryAccess, Breakpoints, DebugBreak};
+        use crate::chip::ula::{UlaPAL, UlaVideoFrame};
+        use crate::memory::{Memory48k, ZxMemory};
+        use crate::z80emu::{Cpu, Z80NMOS};
+        use bus::zxinterface1::*;
+        type If1 = ZxInterface1BusDevice<io::Empty, io::Sink, ZxNetUdpSyncSocket, VFNullDevice<UlaVideoFrame>>;
+        let sectors: Vec<Sector> = (0..5u8).map(|i| {
+            let mut sector = Sector::default();
+            sector.head[0] = 1;
+            sector.head[1] = 5 - i;
+            for (j, p) in sector.data.iter_mut().enumerate() {
+                *p = (j as u8) ^ (i * 16);
+            }
+            sector
+        }).collect();
+        let mut ula = UlaPAL::<Memory48k, If1>::default();
+        ula.bus_device_mut().microdrives.replace_cartridge(1, MicroCartridge::new_with_sectors(sectors.clone(), false, 5));
+        // LD IX,0x9000; RST 8; DEFB RD_SECTOR; LD (IX+CHREC),1; RST 8; DEFB WR_SECTOR; HALT
+        let code = [0xDD, 0x21, 0x00, 0x90,
+                    0xCF, HOOK_RD_SECTOR,
+                    0xDD, 0x36, 0x0D, 0x01,
+                    0xCF, HOOK_WR_SECTOR,
+                    0x76];
+        ula.memory_mut().load_into(0x8000, &code[..]).unwrap();
+        // CHREC = 2, CHDRIV = 2
+        ula.memory_mut().write(0x900D, 2);
+        ula.memory_mut().write(0x9019, 2);
+        let mut cpu = Z80NMOS::default();
+        cpu.set_pc(0x8000);
+        cpu.set_sp(0xA000);
+        let mut breakpoints = Breakpoints::new();
+        breakpoints.add_breakpoint(0x0008);
+        breakpoints.add_breakpoint(0x800C);
+        // the mode is disabled by default
+        assert!(!ula.bus_device_ref().is_instant_microdrive());
+        assert_eq!(ula.run_frame(&mut cpu, &mut breakpoints), DebugBreak::Breakpoint { pc: 0x0008, remaining: 69888 - 25 });
+        let mut hook = InstantMicrodriveHook::detect(&cpu, ula.memory_ref()).unwrap();
+        assert_eq!(hook.code(), HOOK_RD_SECTOR);
+        assert_eq!(hook.drive_index(), 1);
+        assert!(!ula.bus_device_mut().service_instant_microdrive_hook(&mut hook));
+        ula.bus_device_mut().set_instant_microdrive(true);
+        assert!(ula.bus_device_mut().service_instant_microdrive_hook(&mut hook));
+        hook.complete(&mut cpu, ula.memory_mut());
+        let mut hooks = vec![hook.code()];
+        loop {
+            match ula.run_frame(&mut cpu, &mut breakpoints) {
+                DebugBreak::Breakpoint { pc: 0x0008, .. } => {
+                    let mut hook = InstantMicrodriveHook::detect(&cpu, ula.memory_ref()).unwrap();
+                    assert!(ula.bus_device_mut().service_instant_microdrive_hook(&mut hook));
+                    hook.complete(&mut cpu, ula.memory_mut());
+                    hooks.push(hook.code());
+                }
+                DebugBreak::Breakpoint { pc: 0x800C, .. } => break,
+                res => panic!("unexpected: {:?}", res)
+            }
+        }
+        assert_eq!(hooks, [HOOK_RD_SECTOR, HOOK_WR_SECTOR]);
+        // both sectors have been serviced within a fraction of the time needed for a single sector
+        // to pass under the drive head
+        let (frame, ts) = ula.frame_tstate();
+        assert_eq!(frame, 0);
+        assert!(ts < ula.bus_device_ref().microdrives.sector_ts() / 1000);
+        // the sector number 2 was read into the channel
+        let channel: Vec<u8> = (0x9000..0x9253).map(|addr| ula.memory_ref().read(addr)).collect();
+        assert_eq!(&channel[0x28..0x37], &sectors[3].head[..]);
+        assert_eq!(&channel[0x43..], &sectors[3].data[..]);
+        // and written to the sector number 1
+        let cartridge = ula.bus_device_ref().microdrives.cartridge_at(1).unwrap();
+        let (_, sector) = cartridge.iter_with_indices().find(|(_, sec)| sec.head[1] == 1).unwrap();
+        assert_eq!(&sector.head[..], &sectors[4].head[..]);
+        assert_eq!(&sector.data[..], &sectors[3].data[..]);
+        // the hook codes are trapped in the frame loop with the device in a dynamic bus
+        let mut ula = UlaPAL::<Memory48k, DynamicBus<VFNullDevice<UlaVideoFrame>>>::default();
+        let mut if1 = If1::default();
+        if1.microdrives.replace_cartridge(1, MicroCartridge::new_with_sectors(sectors.clone(), false, 5));
+        if1.set_instant_microdrive(true);
+        ula.bus_device_mut().append_device(if1);
+        ula.memory_mut().load_into(0x8000, &code[..]).unwrap();
+        ula.memory_mut().write(0x900D, 2);
+        ula.memory_mut().write(0x9019, 2);
+        let mut cpu = Z80NMOS::default();
+        cpu.set_pc(0x8000);
+        cpu.set_sp(0xA000);
+        ula.execute_next_frame(&mut cpu);
+        assert!(cpu.is_halt());
+        assert_eq!(cpu.get_sp(), 0xA000);
+        let channel: Vec<u8> = (0x9000..0x9253).map(|addr| ula.memory_ref().read(addr)).collect();
+        assert_eq!(&channel[0x28..0x37], &sectors[3].head[..]);
+        assert_eq!(&channel[0x43..], &sectors[3].data[..]);
+        let if1 = ula.bus_device_ref().find_device_ref::<If1>().unwrap();
+        let cartridge = if1.microdrives.cartridge_at(1).unwrap();
+        let (_, sector) = cartridge.iter_with_indices().find(|(_, sec)| sec.head[1] == 1).unwrap();
+        assert_eq!(&sector.data[..], &sectors[3].data[..]);
+    }
 }
@@ -6,9 +6,17 @@
     For the full copyright notice, see the lib.rs file.
 */
 //! # Video API.
+pub mod capture;
+pub mod filter;
 pub mod frame_cache;
+mod frame_blend;
+mod frame_skip;
 mod render_pixels;
 mod render_pixels_plus;
+mod status_indicators;
 pub use spectrusty_core::video::*;
+pub use frame_blend::*;
+pub use frame_skip::*;
 pub use render_pixels::Renderer;
 pub use render_pixels_plus::*;
+pub use status_indicators::*;
@@ -0,0 +1,169 @@
+/*
+    Copyright (C) 2020-2023  Rafal Michalski
+
+    This file is part of SPECTRUSTY, a Rust library for building emulators.
+
+    For the full copyright notice, see the lib.rs file.
+*/
+//! Capturing rendered video frames as images.
+//!
+//! With the "image" feature enabled the captured frames can be also saved as **PNG** images.
+use std::io::{self, Write};
+
+use crate::video::{BorderSize, Palette, Video, pixel::PixelBufA32};
+
+const BMP_FILE_HEADER_SIZE: u32 = 14;
+const BMP_INFO_HEADER_SIZE: u32 = 40;
+
+/// A video frame captured as RGBA pixels, with 8 bits per color channel.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CapturedFrame {
+    /// The width of the image in pixels.
+    pub width: u32,
+    /// The height of the image in pixels.
+    pub height: u32,
+    /// The image pixels as consecutive `[r, g, b, a]` values, line after line.
+    pub rgba: Vec<u8>
+}
+
+/// Renders the current video frame of `chip`, including the border of the given `border_size`, and returns
+/// it as a [CapturedFrame].
+///
+/// `P` should be one of the palettes with the `RGBA` pixel layout, e.g. [SpectrumPalRGBA32] or
+/// [GrayscalePalRGBA32].
+///
+/// **NOTE**: Rendering consumes the changes recorded during the current frame, so the same frame can't be
+/// rendered twice. This function should be called instead of [Video::render_video_frame]. Frontends that
+/// already render into an `RGBA` buffer may create the [CapturedFrame] directly from a copy of that buffer.
+///
+/// [SpectrumPalRGBA32]: crate::video::pixel::SpectrumPalRGBA32
+/// [GrayscalePalRGBA32]: crate::video::pixel::GrayscalePalRGBA32
+pub fn capture_frame<V, P>(chip: &mut V, border_size: BorderSize) -> CapturedFrame
+    where V: Video,
+          P: Palette<Pixel=[u8;4]>
+{
+    let (width, height) = V::render_size_pixels(border_size);
+    let pitch = width as usize * 4;
+    let mut rgba = vec![0u8; pitch * height as usize];
+    chip.render_video_frame::<PixelBufA32, P>(&mut rgba, pitch, border_size);
+    CapturedFrame { width, height, rgba }
+}
+
+impl CapturedFrame {
+    /// Returns the `[r, g, b, a]` pixel at the given coordinates.
+    ///
+    /// # Panics
+    /// Panics if the coordinates are out of bounds.
+    pub fn pixel(&self, x: u32, y: u32) -> [u8;4] {
+        assert!(x < self.width && y < self.height, "pixel out of bounds");
+        let offset = (y * self.width + x) as usize * 4;
+        self.rgba[offset..offset + 4].try_into().unwrap()
+    }
+    /// Writes the frame as an uncompressed 24-bit **BMP** image to `dst`. The alpha channel is ignored.
+    ///
+    /// # Errors
+    /// This function may return an error from attempts to write the file.
+    pub fn write_bmp<W: Write>(&self, mut dst: W) -> io::Result<()> {
+        let line_size = (self.width * 3 + 3) & !3;
+        let data_size = line_size * self.height;
+        let data_offset = BMP_FILE_HEADER_SIZE + BMP_INFO_HEADER_SIZE;
+        let mut header = Vec::with_capacity(data_offset as usize);
+        header.extend_from_slice(b"BM");
+        header.extend_from_slice(&(data_offset + data_size).to_le_bytes());
+        header.extend_from_slice(&[0;4]);
+        header.extend_from_slice(&data_offset.to_le_bytes());
+        header.extend_from_slice(&BMP_INFO_HEADER_SIZE.to_le_bytes());
+        header.extend_from_slice(&(self.width as i32).to_le_bytes());
+        header.extend_from_slice(&(self.height as i32).to_le_bytes());
+        header.extend_from_slice(&1u16.to_le_bytes()); // planes
+        header.extend_from_slice(&24u16.to_le_bytes()); // bits per pixel
+        header.extend_from_slice(&0u32.to_le_bytes()); // BI_RGB
+        header.extend_from_slice(&data_size.to_le_bytes());
+        header.extend_from_slice(&2835i32.to_le_bytes()); // 72 DPI
+        header.extend_from_slice(&2835i32.to_le_bytes());
+        header.extend_from_slice(&[0;8]); // palette colors
+        dst.write_all(&header)?;
+        let mut line = Vec::with_capacity(line_size as usize);
+        // BMP lines are stored bottom-up
+        for rgba_line in self.rgba.chunks_exact(self.width as usize * 4).rev() {
+            line.clear();
+            for rgba in rgba_line.chunks_exact(4) {
+                line.extend_from_slice(&[rgba[2], rgba[1], rgba[0]]);
+            }
+            line.resize(line_size as usize, 0);
+            dst.write_all(&line)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "image")]
+impl CapturedFrame {
+    /// Returns the frame as an [image::RgbaImage].
+    pub fn to_image(&self) -> image::RgbaImage {
+        image::RgbaImage::from_raw(self.width, self.height, self.rgba.clone())
+            .expect("the buffer should match the frame dimensions")
+    }
+    /// Writes the frame as a **PNG** image to `dst`.
+    ///
+    /// # Errors
+    /// This function may return an error from attempts to encode or write the file.
+    pub fn write_png<W: Write>(&self, dst: W) -> image::ImageResult<()> {
+        use image::ImageEncoder;
+        image::codecs::png::PngEncoder::new(dst)
+            .write_image(&self.rgba, self.width, self.height, image::ColorType::Rgba8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chip::{MemoryAccess, ula::UlaPAL};
+    use crate::memory::{Memory16k, ZxMemory};
+    use crate::video::{BorderColor, pixel::SpectrumPalRGBA32};
+    use super::*;
+
+    type TestUla = UlaPAL<Memory16k>;
+
+    fn solid_blue_frame() -> CapturedFrame {
+        let mut ula = TestUla::default();
+        ula.set_border_color(BorderColor::BLUE);
+        for addr in 0x5800..0x5B00 {
+            ula.memory_mut().write(addr, 0b00_001_000);
+        }
+        capture_frame::<_, SpectrumPalRGBA32>(&mut ula, BorderSize::Full)
+    }
+
+    #[test]
+    fn capture_bmp_works() {
+        let frame = solid_blue_frame();
+        let (width, height) = <TestUla as Video>::render_size_pixels(BorderSize::Full);
+        assert_eq!((frame.width, frame.height), (width, height));
+        let blue = SpectrumPalRGBA32::get_pixel(BorderColor::BLUE.into());
+        assert!(frame.rgba.chunks(4).all(|p| p == blue));
+        assert_eq!(frame.pixel(0, 0), blue);
+        assert_eq!(frame.pixel(width - 1, height - 1), blue);
+        let mut bmp = Vec::new();
+        frame.write_bmp(&mut bmp).unwrap();
+        let line_size = (width as usize * 3 + 3) & !3;
+        assert_eq!(bmp.len(), 54 + line_size * height as usize);
+        assert_eq!(&bmp[0..2], b"BM");
+        assert_eq!(u32::from_le_bytes(bmp[2..6].try_into().unwrap()), bmp.len() as u32);
+        assert_eq!(i32::from_le_bytes(bmp[18..22].try_into().unwrap()), width as i32);
+        assert_eq!(i32::from_le_bytes(bmp[22..26].try_into().unwrap()), height as i32);
+        assert_eq!(&bmp[54..57], &[blue[2], blue[1], blue[0]]);
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn capture_png_works() {
+        let frame = solid_blue_frame();
+        let mut png = Vec::new();
+        frame.write_png(&mut png).unwrap();
+        let image = image::load_from_memory_with_format(&png, image::ImageFormat::Png).unwrap().to_rgba8();
+        assert_eq!(image.dimensions(), (frame.width, frame.height));
+        let blue = SpectrumPalRGBA32::get_pixel(BorderColor::BLUE.into());
+        assert_eq!(image.get_pixel(0, 0).0, blue);
+        assert_eq!(image.get_pixel(frame.width - 1, frame.height - 1).0, blue);
+        assert_eq!(frame.to_image(), image);
+    }
+}
@@ -0,0 +1,196 @@
+/*
+    Copyright (C) 2020-2023  Rafal Michalski
+
+    This file is part of SPECTRUSTY, a Rust library for building emulators.
+
+    For the full copyright notice, see the lib.rs file.
+*/
+//! Post-processing filters for the rendered video frames.
+//!
+//! The filters operate solely on the pixel buffers, so they can be applied to the frames rendered by
+//! any chipset.
+#[cfg(feature = "snapshot")]
+use serde::{Serialize, Deserialize};
+
+use crate::video::{Palette, pixel::PixelBytes};
+
+/// Implemented by pixel types that can be darkened by [Scanlines].
+pub trait ScanlinePixel: PixelBytes {
+    /// Should return the pixel with each of its channels moved towards the corresponding channel of
+    /// the `dark` pixel by `intensity / 255` of the distance between them.
+    fn darken(self, dark: Self, intensity: u8) -> Self;
+}
+
+/// A filter approximating the look of a CRT display by darkening every other line of the rendered frame.
+///
+/// `P` must be the same type as [PixelBuffer::Pixel] of the pixel buffer used for rendering.
+///
+/// [PixelBuffer::Pixel]: crate::video::PixelBuffer::Pixel
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+pub struct Scanlines<P> {
+    intensity: u8,
+    dark: P
+}
+
+impl<P: ScanlinePixel> Scanlines<P> {
+    /// Creates a new filter with the given darkening `intensity`, from `0` (lines are left intact)
+    /// to `255` (lines become black).
+    ///
+    /// The black color of the `C` palette is used for darkening, which preserves the alpha channel
+    /// of the pixels.
+    pub fn new<C: Palette<Pixel=P>>(intensity: u8) -> Self {
+        Scanlines { intensity, dark: C::get_pixel(0) }
+    }
+    /// Returns the current darkening intensity.
+    pub fn intensity(&self) -> u8 {
+        self.intensity
+    }
+    /// Changes the darkening intensity.
+    pub fn set_intensity(&mut self, intensity: u8) {
+        self.intensity = intensity;
+    }
+    /// Darkens every other line of the frame rendered into the pixel `buffer`, starting from the second one.
+    ///
+    /// `pitch` is the number of bytes of each line in the `buffer`. The `buffer` doesn't need to be
+    /// aligned. The trailing bytes of each line not forming a whole pixel are left unmodified.
+    pub fn apply(&self, buffer: &mut [u8], pitch: usize) {
+        for line in buffer.chunks_exact_mut(pitch).skip(1).step_by(2) {
+            for bytes in line.chunks_exact_mut(P::SIZE) {
+                P::from_ne_bytes(bytes).darken(self.dark, self.intensity).write_ne_bytes(bytes);
+            }
+        }
+    }
+    /// Scales the frame rendered into the pixel `src` buffer twice in both dimensions and writes it into
+    /// the `dst` buffer. Each line of the `src` becomes two lines of the `dst`, the second of them darkened.
+    ///
+    /// `pitch` is the number of bytes of each line in the `src` buffer. The lines of the `dst` buffer
+    /// should be `2 * pitch` bytes long. The buffers don't need to be aligned. The trailing bytes of each
+    /// `src` line not forming a whole pixel are not being scaled.
+    ///
+    /// # Panics
+    /// Panics if the `dst` buffer is shorter than `4 * src.len()` bytes.
+    pub fn apply_scale2x(&self, src: &[u8], pitch: usize, dst: &mut [u8]) {
+        assert!(dst.len() >= 4 * src.len(), "the destination buffer is too small");
+        let dst_lines = dst.chunks_exact_mut(4 * pitch);
+        for (src_line, dst_lines) in src.chunks_exact(pitch).zip(dst_lines) {
+            let (upper, lower) = dst_lines.split_at_mut(2 * pitch);
+            for ((bytes, up), low) in src_line.chunks_exact(P::SIZE)
+                                              .zip(upper.chunks_exact_mut(2 * P::SIZE))
+                                              .zip(lower.chunks_exact_mut(2 * P::SIZE))
+            {
+                let pixel = P::from_ne_bytes(bytes);
+                let dark = pixel.darken(self.dark, self.intensity);
+                for (up, low) in up.chunks_exact_mut(P::SIZE).zip(low.chunks_exact_mut(P::SIZE)) {
+                    pixel.write_ne_bytes(up);
+                    dark.write_ne_bytes(low);
+                }
+            }
+        }
+    }
+}
+
+#[inline(always)]
+fn darken_u8(c: u8, d: u8, intensity: u8) -> u8 {
+    ((c as u32 * (255 - intensity as u32) + d as u32 * intensity as u32) / 255) as u8
+}
+
+#[inline(always)]
+fn darken_masked(c: u32, d: u32, intensity: u8, masks: &[u32]) -> u32 {
+    let (i, ni) = (intensity as u64, 255 - intensity as u64);
+    masks.iter().fold(0, |res, &m| {
+        res | (((c & m) as u64 * ni + (d & m) as u64 * i) / 255) as u32 & m
+    })
+}
+
+impl ScanlinePixel for u8 {
+    /// Darkens pixels with the `3-3-2` bit color channels layout.
+    #[inline]
+    fn darken(self, dark: Self, intensity: u8) -> Self {
+        darken_masked(self as u32, dark as u32, intensity, &[0b1110_0000, 0b0001_1100, 0b0000_0011]) as u8
+    }
+}
+
+impl ScanlinePixel for u16 {
+    /// Darkens pixels with the `5-6-5` bit color channels layout.
+    #[inline]
+    fn darken(self, dark: Self, intensity: u8) -> Self {
+        darken_masked(self as u32, dark as u32, intensity, &[0xF800, 0x07E0, 0x001F]) as u16
+    }
+}
+
+impl ScanlinePixel for u32 {
+    /// Darkens pixels with the `8-8-8-8` bit color channels layout.
+    #[inline]
+    fn darken(self, dark: Self, intensity: u8) -> Self {
+        darken_masked(self, dark, intensity, &[0xFF00_0000, 0x00FF_0000, 0x0000_FF00, 0x0000_00FF])
+    }
+}
+
+impl<const N: usize> ScanlinePixel for [u8;N] {
+    #[inline]
+    fn darken(mut self, dark: Self, intensity: u8) -> Self {
+        for (c, d) in self.iter_mut().zip(dark.iter()) {
+            *c = darken_u8(*c, *d, intensity);
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::video::pixel::{SpectrumPalRGB24, SpectrumPalRGBA32, SpectrumPalA8R8G8B8, SpectrumPalR5G6B5};
+    use super::*;
+
+    #[test]
+    fn scanlines_works() {
+        assert_eq!(0xFFFF_FFFFu32.darken(SpectrumPalA8R8G8B8::get_pixel(0), 128), 0xFF7F_7F7F);
+        assert_eq!(0xFFFFu16.darken(SpectrumPalR5G6B5::get_pixel(0), 255), 0);
+        assert_eq!([200u8, 100, 0, 255].darken(SpectrumPalRGBA32::get_pixel(0), 0), [200, 100, 0, 255]);
+
+        let (width, height) = (16, 10);
+        let pitch = width * 3;
+        let pixel = [200u8, 100, 50];
+        let buffer: Vec<u8> = pixel.iter().copied().cycle().take(pitch * height).collect();
+        let mut scanlines = Scanlines::new::<SpectrumPalRGB24>(64);
+        assert_eq!(scanlines.intensity(), 64);
+        scanlines.set_intensity(128);
+        assert_eq!(scanlines.intensity(), 128);
+        // each channel is dimmed by the factor of (255 - 128) / 255
+        let dimmed = pixel.map(|c| (c as u32 * 127 / 255) as u8);
+        assert_eq!(dimmed, [99, 49, 24]);
+        let mut filtered = buffer.clone();
+        scanlines.apply(&mut filtered, pitch);
+        for (y, line) in filtered.chunks_exact(pitch).enumerate() {
+            let expected = if y & 1 == 0 { pixel } else { dimmed };
+            assert!(line.chunks_exact(3).all(|p| p == expected), "line: {}", y);
+        }
+        // 2x scaler
+        let mut scaled = vec![0u8; 4 * buffer.len()];
+        scanlines.apply_scale2x(&buffer, pitch, &mut scaled);
+        let scaled_lines: Vec<_> = scaled.chunks_exact(2 * pitch).collect();
+        assert_eq!(scaled_lines.len(), 2 * height);
+        for (y, line) in scaled_lines.into_iter().enumerate() {
+            assert_eq!(line.len() / 3, 2 * width);
+            let expected = if y & 1 == 0 { pixel } else { dimmed };
+            assert!(line.chunks_exact(3).all(|p| p == expected), "line: {}", y);
+        }
+        // alpha is preserved
+        let mut rgba = vec![255u8; 4 * 4 * 2];
+        Scanlines::new::<SpectrumPalRGBA32>(255).apply(&mut rgba, 16);
+        assert!(rgba[..16].iter().all(|&c| c == 255));
+        assert!(rgba[16..].chunks_exact(4).all(|p| p == [0, 0, 0, 255]));
+        // unaligned buffers are filtered as a whole
+        let mut bytes = [0xFFu8; 1 + 2 * 10];
+        let buffer = &mut bytes[1..];
+        Scanlines::new::<SpectrumPalA8R8G8B8>(255).apply(buffer, 10);
+        assert!(buffer[..10].iter().all(|&b| b == 0xFF));
+        let black = 0xFF00_0000u32.to_ne_bytes();
+        assert!(buffer[10..18].chunks_exact(4).all(|p| p == black));
+        assert_eq!(&buffer[18..], &[0xFF, 0xFF]);
+        let mut scaled = [0u8; 1 + 4 * 2 * 8];
+        Scanlines::new::<SpectrumPalA8R8G8B8>(255).apply_scale2x(&bytes[1..17], 8, &mut scaled[1..]);
+        assert!(scaled[1..17].iter().all(|&b| b == 0xFF));
+        assert!(scaled[17..33].chunks_exact(4).all(|p| p == black));
+    }
+}
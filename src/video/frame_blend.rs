@@ -0,0 +1,261 @@
+/*
+    Copyright (C) 2020-2023  Rafal Michalski
+
+    This file is part of SPECTRUSTY, a Rust library for building emulators.
+
+    For the full copyright notice, see the lib.rs file.
+*/
+use core::fmt;
+use core::str::FromStr;
+
+#[cfg(feature = "snapshot")]
+use serde::{Serialize, Deserialize};
+
+use crate::video::pixel::PixelBytes;
+
+/// Implemented by pixel types that can be blended by [FrameBlender].
+pub trait BlendPixel: PixelBytes {
+    /// Should return the arithmetic mean of the color channels of both pixels.
+    fn blend_average(self, other: Self) -> Self;
+}
+
+/// Specifies how the consecutive rendered video frames are being blended by [FrameBlender].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "snapshot", serde(try_from = "&str", into = "&str"))]
+pub enum FrameBlendMode {
+    /// Each frame is presented as rendered.
+    #[default]
+    None,
+    /// Each frame is presented as the average of itself and the previous frame.
+    ///
+    /// This emulates the persistence of vision of a CRT viewer, which is exploited by the software
+    /// alternating two screens every frame (e.g. *gigascreen* or flicker multicolor) to fake additional
+    /// colors.
+    Average
+}
+
+/// An error returned when parsing a [FrameBlendMode] from a string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseFrameBlendModeError;
+
+impl fmt::Display for ParseFrameBlendModeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        "unrecognized frame blend mode".fmt(f)
+    }
+}
+
+impl std::error::Error for ParseFrameBlendModeError {}
+
+impl FromStr for FrameBlendMode {
+    type Err = ParseFrameBlendModeError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        if name.eq_ignore_ascii_case("none") {
+            Ok(FrameBlendMode::None)
+        }
+        else if name.eq_ignore_ascii_case("average") {
+            Ok(FrameBlendMode::Average)
+        }
+        else {
+            Err(ParseFrameBlendModeError)
+        }
+    }
+}
+
+impl<'a> core::convert::TryFrom<&'a str> for FrameBlendMode {
+    type Error = ParseFrameBlendModeError;
+
+    fn try_from(name: &'a str) -> Result<Self, Self::Error> {
+        name.parse()
+    }
+}
+
+impl From<FrameBlendMode> for &'static str {
+    fn from(mode: FrameBlendMode) -> &'static str {
+        match mode {
+            FrameBlendMode::None => "none",
+            FrameBlendMode::Average => "average"
+        }
+    }
+}
+
+impl fmt::Display for FrameBlendMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        <&str>::from(*self).fmt(f)
+    }
+}
+
+/// A tool for blending the consecutive video frames rendered with [Video::render_video_frame].
+///
+/// The blender retains a copy of the previously rendered frame. Call [FrameBlender::blend_frame] with
+/// the pixel `buffer` each time a new frame has been rendered into it.
+///
+/// `P` must be the same type as [PixelBuffer::Pixel] of the pixel buffer used for rendering.
+///
+/// [Video::render_video_frame]: crate::video::Video::render_video_frame
+/// [PixelBuffer::Pixel]: crate::video::PixelBuffer::Pixel
+#[derive(Clone, Debug, Default)]
+pub struct FrameBlender<P> {
+    mode: FrameBlendMode,
+    prev: Vec<P>
+}
+
+impl<P: BlendPixel> FrameBlender<P> {
+    /// Creates a new frame blender with the given `mode`.
+    pub fn new(mode: FrameBlendMode) -> Self {
+        FrameBlender { mode, prev: Vec::new() }
+    }
+    /// Returns the current blend mode.
+    pub fn mode(&self) -> FrameBlendMode {
+        self.mode
+    }
+    /// Changes the blend mode. Changing the mode discards the retained frame.
+    pub fn set_mode(&mut self, mode: FrameBlendMode) {
+        if mode != self.mode {
+            self.mode = mode;
+            self.reset();
+        }
+    }
+    /// Discards the retained frame, so the next frame will be presented as rendered.
+    ///
+    /// Call this method after the size of the rendered frames has been changed.
+    pub fn reset(&mut self) {
+        self.prev.clear();
+    }
+    /// Blends the freshly rendered frame in the pixel `buffer` with the previous frame, according
+    /// to the current blend mode.
+    ///
+    /// The unblended content of the `buffer` is retained for blending with the next frame.
+    /// If there is no retained frame or its size is different, `buffer` is left unmodified.
+    ///
+    /// The `buffer` doesn't need to be aligned. The trailing bytes not forming a whole pixel are left
+    /// unmodified.
+    pub fn blend_frame(&mut self, buffer: &mut [u8]) {
+        if self.mode == FrameBlendMode::None {
+            return
+        }
+        let pixels = buffer.chunks_exact_mut(P::SIZE);
+        if pixels.len() != self.prev.len() {
+            self.prev.clear();
+            self.prev.extend(pixels.map(|bytes| P::from_ne_bytes(bytes)));
+            return
+        }
+        for (bytes, prev) in pixels.zip(self.prev.iter_mut()) {
+            let cur = P::from_ne_bytes(bytes);
+            cur.blend_average(*prev).write_ne_bytes(bytes);
+            *prev = cur;
+        }
+    }
+}
+
+#[inline(always)]
+fn avg_u8(a: u8, b: u8) -> u8 {
+    ((a as u16 + b as u16) >> 1) as u8
+}
+
+#[inline(always)]
+fn avg_masked(a: u32, b: u32, masks: &[u32]) -> u32 {
+    masks.iter().fold(0, |res, &m| {
+        res | (((a & m) as u64 + (b & m) as u64) >> 1) as u32 & m
+    })
+}
+
+impl BlendPixel for u8 {
+    /// Blends pixels with the `3-3-2` bit color channels layout.
+    #[inline]
+    fn blend_average(self, other: Self) -> Self {
+        avg_masked(self as u32, other as u32, &[0b1110_0000, 0b0001_1100, 0b0000_0011]) as u8
+    }
+}
+
+impl BlendPixel for u16 {
+    /// Blends pixels with the `5-6-5` bit color channels layout.
+    #[inline]
+    fn blend_average(self, other: Self) -> Self {
+        avg_masked(self as u32, other as u32, &[0xF800, 0x07E0, 0x001F]) as u16
+    }
+}
+
+impl BlendPixel for u32 {
+    /// Blends pixels with the `8-8-8-8` bit color channels layout.
+    #[inline]
+    fn blend_average(self, other: Self) -> Self {
+        avg_masked(self, other, &[0xFF00_0000, 0x00FF_0000, 0x0000_FF00, 0x0000_00FF])
+    }
+}
+
+impl<const N: usize> BlendPixel for [u8;N] {
+    #[inline]
+    fn blend_average(mut self, other: Self) -> Self {
+        for (a, b) in self.iter_mut().zip(other.iter()) {
+            *a = avg_u8(*a, *b);
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chip::{ControlUnit, ula::UlaPAL};
+    use crate::memory::Memory16k;
+    use crate::video::{
+        BorderColor, BorderSize, Video,
+        pixel::{PixelBufA24, SpectrumPalRGB24}
+    };
+    use super::*;
+
+    #[allow(clippy::unusual_byte_groupings)]
+    #[test]
+    fn frame_blend_works() {
+        assert_eq!(0b111_000_11u8.blend_average(0b000_111_01), 0b011_011_10);
+        assert_eq!(0xF800u16.blend_average(0x07FF), 0x7BEF);
+        assert_eq!(0xFF80_0001u32.blend_average(0x0080_FF03), 0x7F80_7F02);
+        assert_eq!([255u8, 0, 100, 255].blend_average([0, 255, 50, 255]), [127, 127, 75, 255]);
+
+        type TestUla = UlaPAL<Memory16k>;
+        let mut ula = TestUla::default();
+        let (width, height) = <TestUla as Video>::render_size_pixels(BorderSize::Full);
+        let pitch = width as usize * 3;
+        let mut buffer = vec![0u8; pitch * height as usize];
+        let mut blender = FrameBlender::<[u8;3]>::new(FrameBlendMode::Average);
+        assert_eq!(blender.mode(), FrameBlendMode::Average);
+        // render two contrasting frames
+        ula.set_border_color(BorderColor::WHITE);
+        ula.render_video_frame::<PixelBufA24, SpectrumPalRGB24>(&mut buffer, pitch, BorderSize::Full);
+        let white_frame = buffer.clone();
+        blender.blend_frame(&mut buffer);
+        assert_eq!(buffer, white_frame);
+        ula.ensure_next_frame();
+        ula.set_border_color(BorderColor::BLUE);
+        ula.render_video_frame::<PixelBufA24, SpectrumPalRGB24>(&mut buffer, pitch, BorderSize::Full);
+        let blue_frame = buffer.clone();
+        assert_ne!(blue_frame, white_frame);
+        blender.blend_frame(&mut buffer);
+        for ((blended, white), blue) in buffer.iter().zip(white_frame.iter()).zip(blue_frame.iter()) {
+            assert_eq!(*blended as u16, (*white as u16 + *blue as u16) / 2);
+        }
+        // the next frame is blended with the unblended previous one
+        let mut next = white_frame.clone();
+        blender.blend_frame(&mut next);
+        for ((blended, white), blue) in next.iter().zip(white_frame.iter()).zip(blue_frame.iter()) {
+            assert_eq!(*blended as u16, (*white as u16 + *blue as u16) / 2);
+        }
+        // no blending
+        blender.set_mode(FrameBlendMode::None);
+        let mut next = blue_frame.clone();
+        blender.blend_frame(&mut next);
+        assert_eq!(next, blue_frame);
+        assert_eq!("Average".parse::<FrameBlendMode>(), Ok(FrameBlendMode::Average));
+        assert_eq!(FrameBlendMode::None.to_string(), "none");
+        // the unaligned buffers are blended as a whole
+        let mut blender = FrameBlender::<u32>::new(FrameBlendMode::Average);
+        let mut bytes = [0u8; 1 + 4 * 4 + 2];
+        let buffer = &mut bytes[1..];
+        blender.blend_frame(buffer);
+        buffer.fill(0xFE);
+        blender.blend_frame(buffer);
+        assert!(buffer[..16].iter().all(|&b| b == 0x7F));
+        assert_eq!(&buffer[16..], &[0xFE, 0xFE]);
+    }
+}
@@ -0,0 +1,114 @@
+/*
+    Copyright (C) 2020-2023  Rafal Michalski
+
+    This file is part of SPECTRUSTY, a Rust library for building emulators.
+
+    For the full copyright notice, see the lib.rs file.
+*/
+#[cfg(feature = "snapshot")]
+use serde::{Serialize, Deserialize};
+
+use crate::chip::FrameState;
+use crate::video::{BorderSize, PixelBuffer, Palette, Video};
+
+/// A tool for skipping the rendering of video frames when the host can't keep up with the emulation.
+///
+/// The emulation should still run every frame, so the audio and the timing are maintained, but only
+/// every Nth frame is being rendered with [Video::render_video_frame].
+///
+/// Which frames are being rendered is determined solely by the frame counter of the emulated computer.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+pub struct FrameSkip {
+    render_frameskip: u32
+}
+
+impl FrameSkip {
+    /// Creates a new instance rendering every `n`th frame.
+    pub fn new(n: u32) -> Self {
+        FrameSkip { render_frameskip: n }
+    }
+    /// Returns the current frame skip value. Every `n`th frame is being rendered.
+    ///
+    /// A value of `0` or `1` means every frame is being rendered.
+    pub fn render_frameskip(&self) -> u32 {
+        self.render_frameskip
+    }
+    /// Sets the frame skip value, so every `n`th frame will be rendered.
+    ///
+    /// Providing `0` or `1` renders every frame.
+    pub fn set_render_frameskip(&mut self, n: u32) {
+        self.render_frameskip = n;
+    }
+    /// Returns `true` if the frame with the given `frame` counter value should be rendered.
+    #[allow(clippy::manual_is_multiple_of)]
+    #[inline]
+    pub fn is_render_frame(&self, frame: u64) -> bool {
+        self.render_frameskip < 2 || frame % self.render_frameskip as u64 == 0
+    }
+    /// Renders the current video frame of `chip` into the pixel `buffer` unless it should be skipped.
+    ///
+    /// Returns `true` if the frame has been rendered. The arguments are the same as for
+    /// [Video::render_video_frame].
+    ///
+    /// Call this method once after each emulated frame, in place of [Video::render_video_frame].
+    pub fn render_video_frame<'a, V, B, P>(
+            &self,
+            chip: &mut V,
+            buffer: &'a mut [u8],
+            pitch: usize,
+            border_size: BorderSize
+        ) -> bool
+        where V: Video + FrameState,
+              B: PixelBuffer<'a>,
+              P: Palette<Pixel=B::Pixel>
+    {
+        if self.is_render_frame(chip.current_frame()) {
+            chip.render_video_frame::<B, P>(buffer, pitch, border_size);
+            return true
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::z80emu::{Cpu, Z80NMOS};
+    use crate::chip::{ControlUnit, MemoryAccess, ula::UlaPAL};
+    use crate::memory::{Memory48k, ZxMemory};
+    use crate::video::pixel::{PixelBufA24, SpectrumPalRGB24};
+    use super::*;
+
+    #[test]
+    fn frame_skip_works() {
+        type TestUla = UlaPAL<Memory48k>;
+        let mut ula = TestUla::default();
+        let mut cpu = Z80NMOS::default();
+        // JR -2
+        ula.memory_mut().load_into_mem(0x8000..=0x8001, &[0x18, 0xFE][..]).unwrap();
+        cpu.set_pc(0x8000);
+        let (width, height) = <TestUla as Video>::render_size_pixels(BorderSize::Full);
+        let pitch = width as usize * 3;
+        let mut buffer = vec![0u8; pitch * height as usize];
+        let mut frameskip = FrameSkip::default();
+        assert_eq!(frameskip.render_frameskip(), 0);
+        assert!((0..10).all(|frame| frameskip.is_render_frame(frame)));
+        frameskip.set_render_frameskip(2);
+        assert_eq!(frameskip.render_frameskip(), 2);
+        let start_frame = ula.current_frame();
+        let mut renders = 0;
+        for _ in 0..10 {
+            ula.execute_next_frame(&mut cpu);
+            if frameskip.render_video_frame::<_, PixelBufA24, SpectrumPalRGB24>(
+                    &mut ula, &mut buffer, pitch, BorderSize::Full) {
+                renders += 1;
+            }
+        }
+        // the frame counter is advanced at the beginning of the next frame
+        ula.ensure_next_frame();
+        assert_eq!(ula.current_frame() - start_frame, 10);
+        assert_eq!(renders, 5);
+        assert_eq!(FrameSkip::new(1), FrameSkip { render_frameskip: 1 });
+        assert!((0..10).all(|frame| FrameSkip::new(1).is_render_frame(frame)));
+    }
+}
@@ -0,0 +1,186 @@
+/*
+    Copyright (C) 2020-2023  Rafal Michalski
+
+    This file is part of SPECTRUSTY, a Rust library for building emulators.
+
+    For the full copyright notice, see the lib.rs file.
+*/
+use bitflags::bitflags;
+
+#[cfg(feature = "snapshot")]
+use serde::{Serialize, Deserialize};
+
+use crate::video::{BorderSize, PixelBuffer, Palette, Video, VideoFrame};
+
+bitflags! {
+    /// Flags of the emulator status indicators rendered by [StatusOverlay].
+    #[derive(Default, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+    #[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+    #[cfg_attr(feature = "snapshot", serde(from = "u8", into = "u8"))]
+    pub struct StatusIndicators: u8 {
+        /// The tape is playing or recording.
+        const TAPE  = 0b001;
+        /// The emulation runs in the turbo mode.
+        const TURBO = 0b010;
+        /// The disk or the microdrive is active.
+        const DISK  = 0b100;
+    }
+}
+
+impl From<u8> for StatusIndicators {
+    fn from(flags: u8) -> Self {
+        StatusIndicators::from_bits_truncate(flags)
+    }
+}
+
+impl From<StatusIndicators> for u8 {
+    fn from(flags: StatusIndicators) -> Self {
+        flags.bits()
+    }
+}
+
+/// The width of the indicator marker in low-resolution pixels.
+pub const INDICATOR_WIDTH: u32 = 8;
+/// The height of the indicator marker in pixel lines.
+pub const INDICATOR_HEIGHT: u32 = 4;
+/// The distance between indicator markers and from the edges of the screen in low-resolution pixels.
+pub const INDICATOR_MARGIN: u32 = 2;
+
+/// The indicators in the order of their placement from the right edge of the screen, with their colors.
+const INDICATORS: [(StatusIndicators, u8); 3] = [
+    (StatusIndicators::TAPE,  12), // bright green
+    (StatusIndicators::TURBO, 14), // bright yellow
+    (StatusIndicators::DISK,  10), // bright red
+];
+
+/// A debug overlay rendering the emulator status indicators as small colored markers in the bottom
+/// border area of the rendered video frame.
+///
+/// Each indicator has a fixed position, counting from the right edge of the screen: tape (bright green),
+/// turbo (bright yellow) and disk (bright red).
+///
+/// Call [StatusOverlay::render_status_indicators] after [Video::render_video_frame] with the same arguments.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+pub struct StatusOverlay {
+    indicators: StatusIndicators
+}
+
+impl StatusOverlay {
+    /// Returns the currently enabled status indicators.
+    pub fn status_indicators(&self) -> StatusIndicators {
+        self.indicators
+    }
+    /// Sets the enabled status indicators.
+    pub fn set_status_indicators(&mut self, flags: StatusIndicators) {
+        self.indicators = flags;
+    }
+    /// Renders markers of the enabled status indicators into the bottom border area of the pixel `buffer`.
+    ///
+    /// The `buffer`, `pitch` and `border_size` should be the same as provided to [Video::render_video_frame]
+    /// of the chipset `V`.
+    ///
+    /// Nothing is rendered if the bottom border is too small to fit the markers.
+    pub fn render_status_indicators<'a, V, B, P>(
+            &self,
+            buffer: &'a mut [u8],
+            pitch: usize,
+            border_size: BorderSize
+        )
+        where V: Video,
+              B: PixelBuffer<'a>,
+              P: Palette<Pixel=B::Pixel>
+    {
+        if self.indicators.is_empty() ||
+           V::VideoFrame::border_size_pixels(border_size) < INDICATOR_HEIGHT + 2 * INDICATOR_MARGIN {
+            return
+        }
+        let (width, height) = V::render_size_pixels(border_size);
+        let density = V::pixel_density();
+        let stride = B::pixel_stride();
+        let top = (height - INDICATOR_HEIGHT - INDICATOR_MARGIN) as usize;
+        let marker_len = (INDICATOR_WIDTH * density) as usize;
+        for line in buffer.chunks_mut(pitch).skip(top).take(INDICATOR_HEIGHT as usize) {
+            // markers are being rendered from left to right, so the line can be split between them
+            let mut rest = line;
+            let mut rest_x = 0;
+            for (index, &(flag, color)) in INDICATORS.iter().enumerate().rev() {
+                if !self.indicators.intersects(flag) {
+                    continue
+                }
+                let x = (width - (index as u32 + 1) * (INDICATOR_MARGIN + INDICATOR_WIDTH) * density) as usize;
+                let start = ((x - rest_x) * stride).min(rest.len());
+                let end = (start + marker_len * stride).min(rest.len());
+                let (marker, tail) = rest[start..].split_at_mut(end - start);
+                let mut line_buffer = B::from_line(marker);
+                line_buffer.put_pixels(P::get_pixel(color), marker_len);
+                rest = tail;
+                rest_x = x + marker_len;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chip::ula::UlaPAL;
+    use crate::memory::Memory16k;
+    use crate::video::{
+        BorderColor,
+        pixel::{PixelBufA24, SpectrumPalRGB24}
+    };
+    use super::*;
+
+    type TestUla = UlaPAL<Memory16k>;
+
+    #[test]
+    fn status_indicators_works() {
+        let mut ula = TestUla::default();
+        let (width, height) = <TestUla as Video>::render_size_pixels(BorderSize::Full);
+        let (width, height) = (width as usize, height as usize);
+        let pitch = width * 3;
+        let mut buffer = vec![0u8; pitch * height];
+        let mut overlay = StatusOverlay::default();
+        assert!(overlay.status_indicators().is_empty());
+        overlay.set_status_indicators(StatusIndicators::TAPE);
+        assert_eq!(overlay.status_indicators(), StatusIndicators::TAPE);
+        ula.set_border_color(BorderColor::BLUE);
+        ula.render_video_frame::<PixelBufA24, SpectrumPalRGB24>(&mut buffer, pitch, BorderSize::Full);
+        overlay.render_status_indicators::<TestUla, PixelBufA24, SpectrumPalRGB24>(
+            &mut buffer, pitch, BorderSize::Full);
+        let border = SpectrumPalRGB24::get_pixel(BorderColor::BLUE.into());
+        let tape = SpectrumPalRGB24::get_pixel(12);
+        let pixel = |buffer: &[u8], x: usize, y: usize| -> [u8;3] {
+            let offs = y * pitch + x * 3;
+            buffer[offs..offs + 3].try_into().unwrap()
+        };
+        let (mw, mh, mm) = (INDICATOR_WIDTH as usize, INDICATOR_HEIGHT as usize, INDICATOR_MARGIN as usize);
+        for y in height - mh - mm..height - mm {
+            for x in width - mw - mm..width - mm {
+                assert_eq!(pixel(&buffer, x, y), tape);
+            }
+            assert_eq!(pixel(&buffer, width - mm, y), border);
+            assert_eq!(pixel(&buffer, width - mw - mm - 1, y), border);
+            // the turbo indicator is off
+            assert_eq!(pixel(&buffer, width - 2 * (mw + mm), y), border);
+        }
+        assert_eq!(pixel(&buffer, width - mm - 1, height - mh - mm - 1), border);
+        assert_eq!(pixel(&buffer, width - mm - 1, height - mm), border);
+        // multiple indicators
+        overlay.set_status_indicators(StatusIndicators::TAPE|StatusIndicators::DISK);
+        overlay.render_status_indicators::<TestUla, PixelBufA24, SpectrumPalRGB24>(
+            &mut buffer, pitch, BorderSize::Full);
+        let disk = SpectrumPalRGB24::get_pixel(10);
+        let offs = (height - mm - 1) * pitch;
+        let line: Vec<[u8;3]> = buffer[offs..offs + pitch].chunks(3).map(|p| p.try_into().unwrap()).collect();
+        assert_eq!(&line[width - 3 * (mw + mm)..width - 2 * (mw + mm)],
+                   &[&[disk; 8][..], &[border; 2][..]].concat()[..]);
+        assert_eq!(&line[width - 2 * (mw + mm)..width - (mw + mm)], &[border; 10][..]);
+        assert_eq!(&line[width - (mw + mm)..width - mm], &[tape; 8][..]);
+        // too small border
+        let mut buffer = vec![0u8; pitch * height];
+        overlay.render_status_indicators::<TestUla, PixelBufA24, SpectrumPalRGB24>(
+            &mut buffer, pitch, BorderSize::Nil);
+        assert!(buffer.iter().all(|&b| b == 0));
+    }
+}
@@ -0,0 +1,705 @@
+/*
+    test_rzx: tests for the SPECTRUSTY library.
+    Copyright (C) 2020-2022  Rafal Michalski
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+    Author contact information: see Cargo.toml file, section [package.authors].
+*/
+//! Replays **RZX** input recordings of the ZX Spectrum 48k and compares the emulator state
+//! with the **SZX** snapshots embedded in the recordings.
+//!
+//! The recording is being replayed frame by frame. The data of each `IN` instruction is taken
+//! from the recording and the number of opcode fetches in each emulated frame must match
+//! the recorded fetch counter, so any change of the CPU or the contention timing that moves
+//! the frame boundaries is detected. The first snapshot in the recording sets up the emulator.
+//! Each following snapshot block is compared with the emulator state: the CPU registers,
+//! the frame T-state counter, the border color and a checksum of each RAM bank.
+//!
+//! The recording "resources/rzx/boot48.rzx" covers 200 frames of the 48k ROM booting from
+//! the power-on state and typing `PRINT 1+1`, with snapshots taken every 60 frames and at the end.
+//! It was made by this emulator with [record_boot48_baseline], which can be run with
+//! `cargo test --test test_rzx -- --ignored` when the baseline has to be re-created on purpose.
+//! It is a regression baseline only: it detects changes of the emulated timing, but not whether
+//! the timing was correct in the first place.
+//!
+//! **NOTE**: The validation against Fuse is not done. No Fuse-produced recording is included
+//! and the 128k models are not covered, so this harness doesn't show compatibility with Fuse.
+//!
+//! Only the subset of both formats used by the harness is implemented: the **RZX** creator,
+//! snapshot and input recording blocks and the **SZX** `Z80R`, `SPCR` and `RAMP` blocks of the
+//! 48k machine. Other blocks are skipped.
+use core::num::NonZeroU16;
+use std::convert::{TryFrom, TryInto};
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+use compression::prelude::*;
+use spectrusty::z80emu::*;
+use spectrusty::chip::{ControlUnit, FrameState, MemoryAccess, ula::{UlaPAL, UlaVideoFrame}};
+use spectrusty::clock::{FTs, VideoTs};
+use spectrusty::memory::{Memory48k, MemoryKind, ZxMemory};
+use spectrusty::peripherals::{KeyboardInterface, ZXKeyboardMap};
+use spectrusty::video::{BorderColor, Video, VideoFrame};
+
+type Result<T> = core::result::Result<T, Box<dyn Error>>;
+type Ula48 = UlaPAL<Memory48k>;
+
+const ROM48: &[u8] = include_bytes!("../resources/roms/48.rom");
+const BASELINE_RZX: &str = "resources/rzx/boot48.rzx";
+
+const RZX_SIGNATURE: &[u8;4] = b"RZX!";
+const RZX_VERSION: [u8;2] = [0, 13];
+const RZX_CREATOR_BLOCK: u8 = 0x10;
+const RZX_SNAPSHOT_BLOCK: u8 = 0x30;
+const RZX_INPUT_BLOCK: u8 = 0x80;
+const RZX_COMPRESSED: u32 = 2;
+const RZX_REPEATED_FRAME: u16 = u16::MAX;
+
+const SZX_SIGNATURE: &[u8;4] = b"ZXST";
+const SZX_VERSION: [u8;2] = [1, 4];
+const SZX_MACHINE_48K: u8 = 1;
+const SZX_COMPRESSED: u16 = 1;
+const SZX_Z80R_LENGTH: usize = 37;
+const SZX_EILAST: u8 = 1;
+const SZX_HALTED: u8 = 2;
+/// The 16kb RAM pages of the 48k machine in the order of their addresses starting at 0x4000.
+const SZX_48K_PAGES: [u8;3] = [5, 2, 0];
+const PAGE_SIZE: usize = 0x4000;
+
+const NO_DEBUG: Option<CpuDebugFn> = None;
+const EI_OPCODE: u8 = 0xFB;
+
+/// The CPU state as it is stored in the **SZX** `Z80R` block.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct CpuState {
+    af: u16, bc: u16, de: u16, hl: u16,
+    af_alt: u16, bc_alt: u16, de_alt: u16, hl_alt: u16,
+    ix: u16, iy: u16, sp: u16, pc: u16,
+    i: u8, r: u8, iff1: bool, iff2: bool, im: u8,
+    memptr: u16, halted: bool, last_ei: bool
+}
+
+/// The machine state compared at each snapshot boundary.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct MachineState {
+    cpu: CpuState,
+    tstates: u32,
+    border: u8,
+    ram_checksums: Vec<u64>
+}
+
+#[derive(Clone, Debug)]
+struct Snapshot {
+    cpu: CpuState,
+    tstates: u32,
+    border: u8,
+    ram: Vec<u8>
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct RzxFrame {
+    fetches: u16,
+    inputs: Vec<u8>
+}
+
+#[derive(Clone, Debug)]
+enum RzxBlock {
+    Snapshot(Snapshot),
+    Input { tstates: u32, frames: Vec<RzxFrame> }
+}
+
+impl CpuState {
+    fn from_cpu(cpu: &Z80NMOS) -> Self {
+        let (iff1, iff2) = cpu.get_iffs();
+        CpuState {
+            af: cpu.get_reg16(StkReg16::AF),
+            bc: cpu.get_reg16(StkReg16::BC),
+            de: cpu.get_reg16(StkReg16::DE),
+            hl: cpu.get_reg16(StkReg16::HL),
+            af_alt: cpu.get_alt_reg16(StkReg16::AF),
+            bc_alt: cpu.get_alt_reg16(StkReg16::BC),
+            de_alt: cpu.get_alt_reg16(StkReg16::DE),
+            hl_alt: cpu.get_alt_reg16(StkReg16::HL),
+            ix: cpu.get_index16(Prefix::Xdd),
+            iy: cpu.get_index16(Prefix::Yfd),
+            sp: cpu.get_sp(),
+            pc: cpu.get_pc(),
+            i: cpu.get_i(),
+            r: cpu.get_r(),
+            iff1, iff2,
+            im: cpu.get_im() as u8,
+            memptr: cpu.get_memptr(),
+            halted: cpu.is_halt(),
+            last_ei: cpu.is_after_ei()
+        }
+    }
+
+    fn to_cpu(&self) -> Result<Z80NMOS> {
+        let mut cpu = Z80NMOS::default();
+        cpu.reset();
+        if self.last_ei {
+            // the only way to set the EI flag is to execute EI
+            let mut tsc = host::TsCounter::<i32>::default();
+            cpu.execute_instruction(&mut Dummy, &mut tsc, NO_DEBUG, EI_OPCODE)
+               .map_err(|_| "EI failed")?;
+        }
+        cpu.set_reg16(StkReg16::AF, self.af_alt);
+        cpu.set_reg16(StkReg16::BC, self.bc_alt);
+        cpu.set_reg16(StkReg16::DE, self.de_alt);
+        cpu.set_reg16(StkReg16::HL, self.hl_alt);
+        cpu.ex_af_af();
+        cpu.exx();
+        cpu.set_reg16(StkReg16::AF, self.af);
+        cpu.set_reg16(StkReg16::BC, self.bc);
+        cpu.set_reg16(StkReg16::DE, self.de);
+        cpu.set_reg16(StkReg16::HL, self.hl);
+        cpu.set_index16(Prefix::Xdd, self.ix);
+        cpu.set_index16(Prefix::Yfd, self.iy);
+        cpu.set_sp(self.sp);
+        cpu.set_pc(self.pc);
+        cpu.set_i(self.i);
+        cpu.set_r(self.r);
+        cpu.set_iffs(self.iff1, self.iff2);
+        cpu.set_im(self.im.try_into().map_err(|_| "invalid interrupt mode")?);
+        cpu.set_memptr(self.memptr);
+        if self.halted {
+            cpu.halt();
+        }
+        Ok(cpu)
+    }
+}
+
+impl MachineState {
+    fn from_ula(ula: &Ula48, cpu: &Z80NMOS) -> Self {
+        MachineState {
+            cpu: CpuState::from_cpu(cpu),
+            tstates: frame_tstate(ula),
+            border: ula.border_color().into(),
+            ram_checksums: ram_checksums(ula.memory_ref())
+        }
+    }
+}
+
+impl Snapshot {
+    fn from_ula(ula: &Ula48, cpu: &Z80NMOS) -> Self {
+        Snapshot {
+            cpu: CpuState::from_cpu(cpu),
+            tstates: frame_tstate(ula),
+            border: ula.border_color().into(),
+            ram: ula.memory_ref().ram_ref().to_vec()
+        }
+    }
+
+    fn machine_state(&self) -> MachineState {
+        let mut mem = Memory48k::default();
+        mem.ram_mut().copy_from_slice(&self.ram);
+        MachineState {
+            cpu: self.cpu.clone(),
+            tstates: self.tstates,
+            border: self.border,
+            ram_checksums: ram_checksums(&mem)
+        }
+    }
+
+    fn restore(&self, ula: &mut Ula48) -> Result<Z80NMOS> {
+        ula.memory_mut().ram_mut().copy_from_slice(&self.ram);
+        ula.set_frame_tstate(self.tstates as FTs);
+        ula.set_border_color(BorderColor::try_from(self.border)?);
+        self.cpu.to_cpu()
+    }
+}
+
+/// Returns the frame T-state counter as it is stored in the snapshot and the input recording.
+///
+/// The frame of the emulated ULA may end a few T-states before the next interrupt, so the counter
+/// may be negative right after the frame has been wrapped around.
+fn frame_tstate(ula: &Ula48) -> u32 {
+    ula.current_tstate().rem_euclid(UlaVideoFrame::FRAME_TSTATES_COUNT) as u32
+}
+
+fn ram_checksums<M: ZxMemory>(mem: &M) -> Vec<u64> {
+    (0..=M::RAM_BANKS_MAX).map(|bank| mem.bank_checksum(MemoryKind::Ram, bank).unwrap())
+                          .collect()
+}
+
+/// A stand-in memory and I/O for executing an `EI` instruction.
+struct Dummy;
+impl Memory for Dummy { type Timestamp = i32; }
+impl Io for Dummy {
+    type Timestamp = i32;
+    type WrIoBreak = ();
+    type RetiBreak = ();
+}
+
+/// Delegates the memory and I/O access to the ULA, substituting the data read by `IN`
+/// instructions with the recorded values or recording them.
+struct InputBus<'a, I> {
+    ula: &'a mut Ula48,
+    replay: Option<I>,
+    recorded: Vec<u8>,
+    overrun: bool
+}
+
+impl<'a, I: Iterator<Item=u8>> Memory for InputBus<'a, I> {
+    type Timestamp = VideoTs;
+
+    fn read_debug(&self, addr: u16) -> u8 {
+        self.ula.read_debug(addr)
+    }
+
+    fn read_mem(&self, addr: u16, ts: VideoTs) -> u8 {
+        self.ula.read_mem(addr, ts)
+    }
+
+    fn read_mem16(&self, addr: u16, ts: VideoTs) -> u16 {
+        self.ula.read_mem16(addr, ts)
+    }
+
+    fn read_opcode(&mut self, pc: u16, ir: u16, ts: VideoTs) -> u8 {
+        self.ula.read_opcode(pc, ir, ts)
+    }
+
+    fn write_mem(&mut self, addr: u16, val: u8, ts: VideoTs) {
+        self.ula.write_mem(addr, val, ts)
+    }
+}
+
+impl<'a, I: Iterator<Item=u8>> Io for InputBus<'a, I> {
+    type Timestamp = VideoTs;
+    type WrIoBreak = ();
+    type RetiBreak = ();
+
+    fn is_irq(&mut self, ts: VideoTs) -> bool {
+        self.ula.is_irq(ts)
+    }
+
+    fn read_io(&mut self, port: u16, ts: VideoTs) -> (u8, Option<NonZeroU16>) {
+        let (mut data, ws) = self.ula.read_io(port, ts);
+        if let Some(replay) = self.replay.as_mut() {
+            match replay.next() {
+                Some(recorded) => data = recorded,
+                None => self.overrun = true
+            }
+        }
+        self.recorded.push(data);
+        (data, ws)
+    }
+
+    fn write_io(&mut self, port: u16, data: u8, ts: VideoTs) -> (Option<()>, Option<NonZeroU16>) {
+        self.ula.write_io(port, data, ts)
+    }
+}
+
+/// Executes a single frame one instruction at a time, optionally replaying the recorded `IN`
+/// data. Returns the frame with the number of opcode fetches and the data read.
+fn run_frame(ula: &mut Ula48, cpu: &mut Z80NMOS, replay: Option<&RzxFrame>) -> Result<RzxFrame> {
+    ula.ensure_next_frame();
+    let mut vtsc = ula.current_video_clock();
+    let mut bus = InputBus {
+        ula, replay: replay.map(|frame| frame.inputs.iter().copied()), recorded: Vec::new(), overrun: false
+    };
+    let mut fetches: u32 = 0;
+    while !vtsc.is_eof() {
+        let r = cpu.get_r();
+        let _ = cpu.execute_next(&mut bus, &mut vtsc, NO_DEBUG);
+        fetches += u32::from(cpu.get_r().wrapping_sub(r) & 0x7F);
+    }
+    let InputBus { ula, recorded: inputs, overrun, .. } = bus;
+    ula.set_video_ts(vtsc.into());
+    if overrun {
+        return Err(format!("more IN instructions than {} recorded in a frame", inputs.len() - 1).into())
+    }
+    let fetches = fetches.try_into().map_err(|_| "too many fetches in a frame")?;
+    Ok(RzxFrame { fetches, inputs })
+}
+
+fn expect_eq<T: PartialEq + core::fmt::Debug>(emulated: T, recorded: T, what: &str, nframes: usize) -> Result<()> {
+    if emulated != recorded {
+        return Err(format!("{} mismatch after {} frames\nemulated: {:?}\nrecorded: {:?}",
+                           what, nframes, emulated, recorded).into())
+    }
+    Ok(())
+}
+
+/// Replays the RZX blocks and returns the number of frames and snapshots verified.
+fn replay_rzx(blocks: &[RzxBlock]) -> Result<(usize, usize)> {
+    let mut blocks = blocks.iter();
+    let mut ula = Ula48::default();
+    ula.memory_mut().load_into_rom(ROM48)?;
+    let mut cpu = match blocks.next() {
+        Some(RzxBlock::Snapshot(snap)) => snap.restore(&mut ula)?,
+        _ => return Err("the recording doesn't start with a snapshot".into())
+    };
+    let mut nframes = 0;
+    let mut nsnapshots = 0;
+    for block in blocks {
+        ula.ensure_next_frame();
+        match block {
+            RzxBlock::Input { tstates, frames } => {
+                expect_eq(frame_tstate(&ula), *tstates, "T-states", nframes)?;
+                for frame in frames.iter() {
+                    let played = run_frame(&mut ula, &mut cpu, Some(frame))?;
+                    expect_eq(played.fetches, frame.fetches, "fetches", nframes)?;
+                    expect_eq(played.inputs.len(), frame.inputs.len(), "IN count", nframes)?;
+                    nframes += 1;
+                }
+            }
+            RzxBlock::Snapshot(snap) => {
+                expect_eq(MachineState::from_ula(&ula, &cpu), snap.machine_state(), "snapshot", nframes)?;
+                nsnapshots += 1;
+            }
+        }
+    }
+    Ok((nframes, nsnapshots))
+}
+
+/*********************************** RZX and SZX files ***********************************/
+
+fn zlib_decompress(data: &[u8]) -> Result<Vec<u8>> {
+    Ok(data.iter().copied()
+           .decode(&mut ZlibDecoder::new())
+           .collect::<core::result::Result<Vec<_>, _>>()?)
+}
+
+fn zlib_compress(data: &[u8]) -> Result<Vec<u8>> {
+    Ok(data.iter().copied()
+           .encode(&mut ZlibEncoder::new(), Action::Finish)
+           .collect::<core::result::Result<Vec<_>, _>>()?)
+}
+
+fn take<'a>(data: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+    if data.len() < len {
+        return Err("unexpected end of data".into())
+    }
+    let (head, tail) = data.split_at(len);
+    *data = tail;
+    Ok(head)
+}
+
+fn take_u8(data: &mut &[u8]) -> Result<u8> {
+    Ok(take(data, 1)?[0])
+}
+
+fn take_u16(data: &mut &[u8]) -> Result<u16> {
+    Ok(u16::from_le_bytes(take(data, 2)?.try_into()?))
+}
+
+fn take_u32(data: &mut &[u8]) -> Result<u32> {
+    Ok(u32::from_le_bytes(take(data, 4)?.try_into()?))
+}
+
+fn parse_rzx(mut data: &[u8]) -> Result<Vec<RzxBlock>> {
+    if take(&mut data, 4)? != RZX_SIGNATURE {
+        return Err("not an RZX file".into())
+    }
+    let _version = take(&mut data, 2)?;
+    let _flags = take_u32(&mut data)?;
+    let mut blocks = Vec::new();
+    while !data.is_empty() {
+        let id = take_u8(&mut data)?;
+        let len = take_u32(&mut data)? as usize;
+        let mut body = take(&mut data, len.checked_sub(5).ok_or("invalid block length")?)?;
+        match id {
+            RZX_SNAPSHOT_BLOCK => {
+                let flags = take_u32(&mut body)?;
+                let ext = take(&mut body, 4)?;
+                let _length = take_u32(&mut body)?;
+                if !ext.eq_ignore_ascii_case(b"szx\0") {
+                    return Err("only SZX snapshots are supported".into())
+                }
+                let snap = if flags & RZX_COMPRESSED != 0 {
+                    parse_szx(&zlib_decompress(body)?)?
+                }
+                else {
+                    parse_szx(body)?
+                };
+                blocks.push(RzxBlock::Snapshot(snap));
+            }
+            RZX_INPUT_BLOCK => {
+                let nframes = take_u32(&mut body)?;
+                let _reserved = take_u8(&mut body)?;
+                let tstates = take_u32(&mut body)?;
+                let flags = take_u32(&mut body)?;
+                let decompressed;
+                if flags & RZX_COMPRESSED != 0 {
+                    decompressed = zlib_decompress(body)?;
+                    body = &decompressed;
+                }
+                let mut frames: Vec<RzxFrame> = Vec::with_capacity(nframes as usize);
+                for _ in 0..nframes {
+                    let fetches = take_u16(&mut body)?;
+                    let inputs = match take_u16(&mut body)? {
+                        RZX_REPEATED_FRAME => frames.last().map(|frame| frame.inputs.clone())
+                                                     .unwrap_or_default(),
+                        count => take(&mut body, count.into())?.to_vec()
+                    };
+                    frames.push(RzxFrame { fetches, inputs });
+                }
+                blocks.push(RzxBlock::Input { tstates, frames });
+            }
+            _ => {}
+        }
+    }
+    Ok(blocks)
+}
+
+fn parse_szx(mut data: &[u8]) -> Result<Snapshot> {
+    if take(&mut data, 4)? != SZX_SIGNATURE {
+        return Err("not an SZX file".into())
+    }
+    let _version = take(&mut data, 2)?;
+    if take_u8(&mut data)? != SZX_MACHINE_48K {
+        return Err("only the 48k machine snapshots are supported".into())
+    }
+    let _flags = take_u8(&mut data)?;
+    let mut cpu_tstates = None;
+    let mut border = 0;
+    let mut ram = vec![0u8; SZX_48K_PAGES.len() * PAGE_SIZE];
+    while !data.is_empty() {
+        let id = take(&mut data, 4)?;
+        let len = take_u32(&mut data)? as usize;
+        let mut body = take(&mut data, len)?;
+        match id {
+            b"Z80R" => {
+                if len < SZX_Z80R_LENGTH {
+                    return Err("Z80R block is too short".into())
+                }
+                let mut regs = [0u16;12];
+                for reg in regs.iter_mut() {
+                    *reg = take_u16(&mut body)?;
+                }
+                let [af, bc, de, hl, af_alt, bc_alt, de_alt, hl_alt, ix, iy, sp, pc] = regs;
+                let i = take_u8(&mut body)?;
+                let r = take_u8(&mut body)?;
+                let iff1 = take_u8(&mut body)? != 0;
+                let iff2 = take_u8(&mut body)? != 0;
+                let im = take_u8(&mut body)?;
+                let tstates = take_u32(&mut body)?;
+                let _hold_int_req_cycles = take_u8(&mut body)?;
+                let flags = take_u8(&mut body)?;
+                let memptr = take_u16(&mut body)?;
+                cpu_tstates = Some((CpuState {
+                    af, bc, de, hl, af_alt, bc_alt, de_alt, hl_alt, ix, iy, sp, pc,
+                    i, r, iff1, iff2, im, memptr,
+                    halted: flags & SZX_HALTED != 0,
+                    last_ei: flags & SZX_EILAST != 0
+                }, tstates));
+            }
+            b"SPCR" => {
+                border = take_u8(&mut body)?;
+            }
+            b"RAMP" => {
+                let flags = take_u16(&mut body)?;
+                let page = take_u8(&mut body)?;
+                let index = SZX_48K_PAGES.iter().position(|&p| p == page)
+                                         .ok_or("unexpected RAM page")?;
+                let decompressed;
+                if flags & SZX_COMPRESSED != 0 {
+                    decompressed = zlib_decompress(body)?;
+                    body = &decompressed;
+                }
+                if body.len() != PAGE_SIZE {
+                    return Err("invalid RAM page size".into())
+                }
+                ram[index * PAGE_SIZE..(index + 1) * PAGE_SIZE].copy_from_slice(body);
+            }
+            _ => {}
+        }
+    }
+    let (cpu, tstates) = cpu_tstates.ok_or("missing Z80R block")?;
+    Ok(Snapshot { cpu, tstates, border, ram })
+}
+
+fn write_block(out: &mut Vec<u8>, id: &[u8;4], body: &[u8]) {
+    out.extend_from_slice(id);
+    out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    out.extend_from_slice(body);
+}
+
+fn write_szx(snap: &Snapshot) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(SZX_SIGNATURE);
+    out.extend_from_slice(&SZX_VERSION);
+    out.extend_from_slice(&[SZX_MACHINE_48K, 0]);
+
+    let cpu = &snap.cpu;
+    let mut z80r = Vec::with_capacity(SZX_Z80R_LENGTH);
+    for reg in [cpu.af, cpu.bc, cpu.de, cpu.hl, cpu.af_alt, cpu.bc_alt, cpu.de_alt, cpu.hl_alt,
+                cpu.ix, cpu.iy, cpu.sp, cpu.pc]
+    {
+        z80r.extend_from_slice(&reg.to_le_bytes());
+    }
+    z80r.extend_from_slice(&[cpu.i, cpu.r, cpu.iff1.into(), cpu.iff2.into(), cpu.im]);
+    z80r.extend_from_slice(&snap.tstates.to_le_bytes());
+    let flags = if cpu.last_ei { SZX_EILAST } else { 0 } | if cpu.halted { SZX_HALTED } else { 0 };
+    z80r.extend_from_slice(&[32, flags]);
+    z80r.extend_from_slice(&cpu.memptr.to_le_bytes());
+    write_block(&mut out, b"Z80R", &z80r);
+
+    write_block(&mut out, b"SPCR", &[snap.border, 0, 0, snap.border, 0, 0, 0, 0]);
+
+    for (page, data) in SZX_48K_PAGES.iter().zip(snap.ram.chunks(PAGE_SIZE)) {
+        let mut ramp = vec![0, 0, *page];
+        ramp.extend_from_slice(data);
+        write_block(&mut out, b"RAMP", &ramp);
+    }
+    out
+}
+
+fn write_rzx_block(out: &mut Vec<u8>, id: u8, body: &[u8]) {
+    out.push(id);
+    out.extend_from_slice(&(body.len() as u32 + 5).to_le_bytes());
+    out.extend_from_slice(body);
+}
+
+fn write_rzx(blocks: &[RzxBlock]) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    out.extend_from_slice(RZX_SIGNATURE);
+    out.extend_from_slice(&RZX_VERSION);
+    out.extend_from_slice(&0u32.to_le_bytes());
+
+    let mut creator = [0u8;24];
+    creator[..10].copy_from_slice(b"SPECTRUSTY");
+    creator[20..].copy_from_slice(&[0, 0, 4, 0]);
+    write_rzx_block(&mut out, RZX_CREATOR_BLOCK, &creator);
+
+    for block in blocks {
+        match block {
+            RzxBlock::Snapshot(snap) => {
+                let szx = write_szx(snap);
+                let mut body = Vec::new();
+                body.extend_from_slice(&RZX_COMPRESSED.to_le_bytes());
+                body.extend_from_slice(b"szx\0");
+                body.extend_from_slice(&(szx.len() as u32).to_le_bytes());
+                body.extend_from_slice(&zlib_compress(&szx)?);
+                write_rzx_block(&mut out, RZX_SNAPSHOT_BLOCK, &body);
+            }
+            RzxBlock::Input { tstates, frames } => {
+                let mut data = Vec::new();
+                let mut prev: Option<&RzxFrame> = None;
+                for frame in frames.iter() {
+                    data.extend_from_slice(&frame.fetches.to_le_bytes());
+                    match prev {
+                        Some(prev) if prev.inputs == frame.inputs => {
+                            data.extend_from_slice(&RZX_REPEATED_FRAME.to_le_bytes());
+                        }
+                        _ => {
+                            let count: u16 = frame.inputs.len().try_into()?;
+                            data.extend_from_slice(&count.to_le_bytes());
+                            data.extend_from_slice(&frame.inputs);
+                        }
+                    }
+                    prev = Some(frame);
+                }
+                let mut body = Vec::new();
+                body.extend_from_slice(&(frames.len() as u32).to_le_bytes());
+                body.push(0);
+                body.extend_from_slice(&tstates.to_le_bytes());
+                body.extend_from_slice(&RZX_COMPRESSED.to_le_bytes());
+                body.extend_from_slice(&zlib_compress(&data)?);
+                write_rzx_block(&mut out, RZX_INPUT_BLOCK, &body);
+            }
+        }
+    }
+    Ok(out)
+}
+
+/*********************************** tests ***********************************/
+
+/// Keys pressed while recording the baseline: `(first frame, last frame, keys)`.
+const REFERENCE_KEYS: &[(u32, u32, ZXKeyboardMap)] = &[
+    (100, 103, ZXKeyboardMap::P),
+    (106, 109, ZXKeyboardMap::N1),
+    (112, 115, ZXKeyboardMap::SS.union(ZXKeyboardMap::K)),
+    (118, 121, ZXKeyboardMap::N1),
+    (124, 127, ZXKeyboardMap::EN),
+];
+const REFERENCE_FRAMES: u32 = 200;
+const REFERENCE_SNAPSHOT_EVERY: u32 = 60;
+
+fn baseline_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(BASELINE_RZX)
+}
+
+fn record_boot48() -> Result<Vec<RzxBlock>> {
+    let mut ula = Ula48::default();
+    let mut cpu = Z80NMOS::default();
+    cpu.reset();
+    ula.memory_mut().load_into_rom(ROM48)?;
+    let mut blocks = vec![RzxBlock::Snapshot(Snapshot::from_ula(&ula, &cpu))];
+    let mut frames = Vec::new();
+    let mut tstates = frame_tstate(&ula);
+    for fc in 0..REFERENCE_FRAMES {
+        let keys = REFERENCE_KEYS.iter()
+                   .filter(|(first, last, _)| (*first..=*last).contains(&fc))
+                   .fold(ZXKeyboardMap::empty(), |keys, (.., k)| keys | *k);
+        ula.set_key_state(keys);
+        frames.push(run_frame(&mut ula, &mut cpu, None)?);
+        if (fc + 1) % REFERENCE_SNAPSHOT_EVERY == 0 || fc + 1 == REFERENCE_FRAMES {
+            ula.ensure_next_frame();
+            blocks.push(RzxBlock::Input { tstates, frames: core::mem::take(&mut frames) });
+            blocks.push(RzxBlock::Snapshot(Snapshot::from_ula(&ula, &cpu)));
+            tstates = frame_tstate(&ula);
+        }
+    }
+    Ok(blocks)
+}
+
+#[test]
+#[ignore]
+fn record_boot48_baseline() -> Result<()> {
+    let rzx = write_rzx(&record_boot48()?)?;
+    let path = baseline_path();
+    fs::create_dir_all(path.parent().unwrap())?;
+    fs::write(path, rzx)?;
+    Ok(())
+}
+
+#[test]
+fn test_rzx_file_round_trip() -> Result<()> {
+    let data = fs::read(baseline_path())?;
+    let blocks = parse_rzx(&data)?;
+    assert_eq!(write_rzx(&blocks)?, data);
+    Ok(())
+}
+
+#[test]
+fn test_rzx_replay_boot48() -> Result<()> {
+    let blocks = parse_rzx(&fs::read(baseline_path())?)?;
+    let (nframes, nsnapshots) = replay_rzx(&blocks)?;
+    assert_eq!(nframes, REFERENCE_FRAMES as usize);
+    assert_eq!(nsnapshots, 4);
+    Ok(())
+}
+
+#[test]
+fn test_rzx_replay_detects_divergence() -> Result<()> {
+    let mut blocks = parse_rzx(&fs::read(baseline_path())?)?;
+    // without the key presses "PRINT 1+1" is never typed in
+    for block in blocks.iter_mut() {
+        if let RzxBlock::Input { frames, .. } = block {
+            for frame in frames.iter_mut() {
+                for data in frame.inputs.iter_mut() {
+                    if *data & 0x1F != 0x1F {
+                        *data = 0xFF;
+                    }
+                }
+            }
+        }
+    }
+    assert!(replay_rzx(&blocks).is_err());
+    Ok(())
+}